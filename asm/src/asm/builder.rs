@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+
+use super::error::{AsmError, Result};
+use crate::isa::decoder::Decoder;
+use crate::isa::encoder::Encoder;
+use crate::isa::error::VmError;
+use crate::isa::hardware_config::REGISTERS_COUNT;
+use crate::isa::instructions::Instruction;
+use crate::isa::pod::{Endianness, Pod};
+
+/// A general-purpose register operand of the `ProgramBuilder`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Reg(pub u8);
+
+pub const R0: Reg = Reg(0);
+pub const R1: Reg = Reg(1);
+pub const R2: Reg = Reg(2);
+pub const R3: Reg = Reg(3);
+pub const R4: Reg = Reg(4);
+pub const R5: Reg = Reg(5);
+pub const R6: Reg = Reg(6);
+pub const R7: Reg = Reg(7);
+
+/// A 32-bit immediate or address operand of the `ProgramBuilder`: a number or a label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A number, negative numbers are stored in two's complement.
+    Number(u32),
+    /// A label, resolved to its address when the program is built.
+    Label(String),
+}
+
+impl From<i32> for Value {
+    fn from(number: i32) -> Self {
+        Value::Number(number as u32)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(number: u32) -> Self {
+        Value::Number(number)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(label: &str) -> Self {
+        Value::Label(label.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(label: String) -> Self {
+        Value::Label(label)
+    }
+}
+
+/// An instruction or a label added to the `ProgramBuilder`.
+enum Item {
+    /// An instruction, with the label of its address or immediate operand.
+    Instruction(Instruction<i32, u32>, Option<String>),
+    Label(String),
+}
+
+/// Builder constructing a program from Rust code, the typed counterpart of the `Assembler`.
+///
+/// The instructions are added in order with one method per instruction, or with `instruction`
+/// for any `Instruction`. The labels are defined with `label` and can be used in place of any
+/// address or immediate operand, they are resolved when the program is built. The bytecode is
+/// validated by the `Decoder` of the VM, so a program that builds is decoded as written.
+///
+/// The errors report the position of the faulty instruction or label as its `line`, the 1-based
+/// index of the call that added it to the builder.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::builder::{ProgramBuilder, R0, R1};
+/// use forge_vm::VM;
+/// let mut builder = ProgramBuilder::new();
+/// builder
+///     .mov(R0, 3)
+///     .mov(R1, 0)
+///     .label("loop")
+///     .inc(R1)
+///     .dec(R0)
+///     .jmpnz("loop")
+///     .hlt();
+/// let program = builder.build().unwrap();
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// assert!(vm.run(&program).is_ok());
+/// assert_eq!(vm.snapshot().cpu.registers[1], 3);
+/// ```
+pub struct ProgramBuilder {
+    items: Vec<Item>,
+    /// The number of registers of the target CPU, register operands must be lower.
+    registers_count: u8,
+    /// The address where the program is loaded, added to the offset of the labels.
+    origin: u32,
+    /// The order of the bytes of the immediate values and addresses.
+    endianness: Endianness,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::with_registers(REGISTERS_COUNT)
+    }
+
+    /// Create a builder for a CPU with a specific number of registers.
+    ///
+    /// # Parameters
+    /// - `registers_count`: The number of registers of the target CPU.
+    pub fn with_registers(registers_count: u8) -> Self {
+        Self {
+            items: Vec::new(),
+            registers_count,
+            origin: 0,
+            endianness: Endianness::Little,
+        }
+    }
+
+    /// Set the address where the program is loaded in the memory, added to the value of the
+    /// labels. See `Assembler::origin`.
+    pub fn origin(&mut self, address: u32) -> &mut Self {
+        self.origin = address;
+        self
+    }
+
+    /// Set the order of the bytes of the immediate values and addresses.
+    /// See `Assembler::endianness`.
+    pub fn endianness(&mut self, endianness: Endianness) -> &mut Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Define a label at the address of the next instruction.
+    pub fn label(&mut self, name: &str) -> &mut Self {
+        self.items.push(Item::Label(name.to_string()));
+        self
+    }
+
+    /// Add any instruction, without label.
+    pub fn instruction(&mut self, instruction: Instruction<i32, u32>) -> &mut Self {
+        self.items.push(Item::Instruction(instruction, None));
+        self
+    }
+
+    /// Add an instruction with a 32-bit operand, built with `0` for a label.
+    fn with_value(
+        &mut self,
+        value: Value,
+        instruction: impl FnOnce(u32) -> Instruction<i32, u32>,
+    ) -> &mut Self {
+        let item = match value {
+            Value::Number(number) => Item::Instruction(instruction(number), None),
+            Value::Label(label) => Item::Instruction(instruction(0), Some(label)),
+        };
+        self.items.push(item);
+        self
+    }
+
+    pub fn nop(&mut self) -> &mut Self {
+        self.instruction(Instruction::NOP)
+    }
+
+    pub fn hlt(&mut self) -> &mut Self {
+        self.instruction(Instruction::HLT)
+    }
+
+    pub fn exit(&mut self, code: i32) -> &mut Self {
+        self.instruction(Instruction::EXIT { code })
+    }
+
+    pub fn mov(&mut self, dest: Reg, value: impl Into<Value>) -> &mut Self {
+        self.with_value(value.into(), |value| Instruction::MOV {
+            dest: dest.0,
+            value: value as i32,
+        })
+    }
+
+    pub fn movr(&mut self, dest: Reg, src: Reg) -> &mut Self {
+        self.instruction(Instruction::MOVR {
+            dest: dest.0,
+            src: src.0,
+        })
+    }
+
+    pub fn ld(&mut self, dest: Reg, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::LD {
+            dest: dest.0,
+            address,
+        })
+    }
+
+    pub fn st(&mut self, src: Reg, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::ST {
+            src: src.0,
+            address,
+        })
+    }
+
+    /// Load the word at the address held by `reg`, `LDR dest [reg]`.
+    pub fn ldr(&mut self, dest: Reg, reg: Reg) -> &mut Self {
+        self.instruction(Instruction::LDR {
+            dest: dest.0,
+            reg: reg.0,
+        })
+    }
+
+    /// Store a word at the address held by `reg`, `STR src [reg]`.
+    pub fn str(&mut self, src: Reg, reg: Reg) -> &mut Self {
+        self.instruction(Instruction::STR {
+            src: src.0,
+            reg: reg.0,
+        })
+    }
+
+    pub fn add(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::ADD {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn sub(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::SUB {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn adc(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::ADC {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn sbb(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::SBB {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn mult(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::MULT {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn div(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::DIV {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    /// The remainder of the division, `MOD dest reg1 reg2`.
+    pub fn modulo(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::MOD {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    /// The fixed-point product, `FXMUL dest reg1 reg2`.
+    pub fn fxmul(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::FXMUL {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    /// The fixed-point quotient, `FXDIV dest reg1 reg2`.
+    pub fn fxdiv(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::FXDIV {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn and(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::AND {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn or(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::OR {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn xor(&mut self, dest: Reg, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::XOR {
+            dest: dest.0,
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn not(&mut self, dest: Reg, reg: Reg) -> &mut Self {
+        self.instruction(Instruction::NOT {
+            dest: dest.0,
+            reg: reg.0,
+        })
+    }
+
+    pub fn cmp(&mut self, reg1: Reg, reg2: Reg) -> &mut Self {
+        self.instruction(Instruction::CMP {
+            reg1: reg1.0,
+            reg2: reg2.0,
+        })
+    }
+
+    pub fn inc(&mut self, reg: Reg) -> &mut Self {
+        self.instruction(Instruction::INC { reg: reg.0 })
+    }
+
+    pub fn dec(&mut self, reg: Reg) -> &mut Self {
+        self.instruction(Instruction::DEC { reg: reg.0 })
+    }
+
+    pub fn push(&mut self, value: impl Into<Value>) -> &mut Self {
+        self.with_value(value.into(), |value| Instruction::PUSH {
+            value: value as i32,
+        })
+    }
+
+    pub fn push_reg(&mut self, reg: Reg) -> &mut Self {
+        self.instruction(Instruction::PUSHREG { reg: reg.0 })
+    }
+
+    pub fn pop_reg(&mut self, reg: Reg) -> &mut Self {
+        self.instruction(Instruction::POPREG { reg: reg.0 })
+    }
+
+    pub fn jmp(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMP { address })
+    }
+
+    pub fn jmpz(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPZ { address })
+    }
+
+    pub fn jmpnz(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPNZ { address })
+    }
+
+    pub fn jmpn(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPN { address })
+    }
+
+    pub fn jmpp(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPP { address })
+    }
+
+    pub fn jmplt(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPLT { address })
+    }
+
+    pub fn jmple(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPLE { address })
+    }
+
+    pub fn jmpgt(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPGT { address })
+    }
+
+    pub fn jmpge(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::JMPGE { address })
+    }
+
+    pub fn call(&mut self, address: impl Into<Value>) -> &mut Self {
+        self.with_value(address.into(), |address| Instruction::CALL { address })
+    }
+
+    pub fn ret(&mut self) -> &mut Self {
+        self.instruction(Instruction::RET)
+    }
+
+    pub fn syscall(&mut self, number: u8) -> &mut Self {
+        self.instruction(Instruction::SYSCALL { number })
+    }
+
+    /// `IN`, named `input` as `in` is a keyword.
+    pub fn input(&mut self, dest: Reg, port: u16) -> &mut Self {
+        self.instruction(Instruction::IN { dest: dest.0, port })
+    }
+
+    /// `OUT`, named `output` as `input`.
+    pub fn output(&mut self, port: u16, src: Reg) -> &mut Self {
+        self.instruction(Instruction::OUT { port, src: src.0 })
+    }
+
+    /// Build the bytecode of the program.
+    ///
+    /// # Errors
+    /// Returns `AsmError::DuplicateLabel` or `AsmError::UndefinedLabel` for a wrong label, and
+    /// `AsmError::InvalidRegister` for a register operand beyond the registers of the CPU.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        // first pass: compute the address of every label
+        let mut labels = HashMap::new();
+        let mut offset = 0;
+        for (index, item) in self.items.iter().enumerate() {
+            match item {
+                Item::Instruction(instruction, _) => offset += instruction.size(),
+                Item::Label(label) => {
+                    let address = self.origin.wrapping_add(offset as u32);
+                    if labels.insert(label.as_str(), address).is_some() {
+                        return Err(AsmError::DuplicateLabel {
+                            line: index + 1,
+                            label: label.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        // second pass: resolve the labels, encode and validate the instructions
+        let encoder = Encoder::new().with_endianness(self.endianness);
+        let decoder =
+            Decoder::<i32>::with_registers(self.registers_count).with_endianness(self.endianness);
+        let mut bytecode = Vec::with_capacity(offset);
+        for (index, item) in self.items.iter().enumerate() {
+            let Item::Instruction(instruction, label) = item else {
+                continue;
+            };
+            let line = index + 1;
+            let start = bytecode.len();
+            encoder.encode_instruction(instruction, &mut bytecode);
+            if let Some(label) = label {
+                // the 32-bit address or immediate field is the last field of the instruction
+                let address =
+                    labels
+                        .get(label.as_str())
+                        .ok_or_else(|| AsmError::UndefinedLabel {
+                            line,
+                            label: label.clone(),
+                        })?;
+                let field = bytecode.len() - 4;
+                address.store(&mut bytecode[field..], self.endianness);
+            }
+            if let Err(VmError::InvalidRegister { register }) =
+                decoder.decode_instruction(&bytecode[start..])
+            {
+                return Err(AsmError::InvalidRegister {
+                    line,
+                    operand: format!("R{}", register),
+                });
+            }
+        }
+        Ok(bytecode)
+    }
+}
+
+impl Default for ProgramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+
+    #[test]
+    fn test_builder_matches_assembler() {
+        let source = "
+                MOV R0 data
+                LDR R1 [R0]
+            loop:
+                PUSHREG R1
+                DEC R1
+                JMPNZ loop
+                CALL function
+                HLT
+            function:
+                POPREG R2
+                RET
+            data:
+        ";
+        let mut builder = ProgramBuilder::new();
+        builder
+            .mov(R0, "data")
+            .ldr(R1, R0)
+            .label("loop")
+            .push_reg(R1)
+            .dec(R1)
+            .jmpnz("loop")
+            .call("function")
+            .hlt()
+            .label("function")
+            .pop_reg(R2)
+            .ret()
+            .label("data");
+        assert_eq!(builder.build(), Assembler::new().assemble(source));
+    }
+
+    #[test]
+    fn test_builder_origin() {
+        let mut builder = ProgramBuilder::new();
+        builder.origin(0x40).label("start").jmp("start");
+        assert_eq!(builder.build(), Ok(vec![0x12, 0x40, 0x00, 0x00, 0x00]));
+
+        builder.endianness(Endianness::Big);
+        assert_eq!(builder.build(), Ok(vec![0x12, 0x00, 0x00, 0x00, 0x40]));
+    }
+}
@@ -0,0 +1,213 @@
+//! This module contains the error types used by the assembler.
+
+/// The `Result` type is a type alias for a `Result` type that uses the `AsmError` type as the error variant.
+pub type Result<T> = std::result::Result<T, AsmError>;
+
+/// Represents the possible errors that can occur while assembling a source text or linking modules.
+/// The assembling errors carry the 1-based `line` of the source where the error was found, and
+/// the linking errors carry the index of the `module` in the modules given to the linker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    /// Unknown instruction mnemonic.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `mnemonic`: The mnemonic that is not recognized.
+    UnknownMnemonic { line: usize, mnemonic: String },
+
+    /// Wrong number of operands for an instruction.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `expected`: The number of operands required by the instruction.
+    /// - `found`: The number of operands given in the source.
+    OperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    /// Operand that is not a valid register, e.g. `R9` or `X0`.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `operand`: The invalid operand.
+    InvalidRegister { line: usize, operand: String },
+
+    /// Operand that is not a valid number or does not fit in the operand size.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `operand`: The invalid operand.
+    InvalidNumber { line: usize, operand: String },
+
+    /// Label definition with an invalid name.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `label`: The invalid label.
+    InvalidLabel { line: usize, label: String },
+
+    /// Label defined more than once.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the second definition.
+    /// - `label`: The duplicated label.
+    DuplicateLabel { line: usize, label: String },
+
+    /// Reference to a label that is never defined.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the reference.
+    /// - `label`: The undefined label.
+    UndefinedLabel { line: usize, label: String },
+
+    /// Directive that is not recognized, e.g. `.foo`.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `directive`: The unknown directive.
+    UnknownDirective { line: usize, directive: String },
+
+    /// Statement in a section that cannot hold it, e.g. an instruction in the `.data` section
+    /// or a `.word` in the `.bss` section.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `statement`: The mnemonic or the directive of the statement.
+    WrongSection { line: usize, statement: String },
+
+    /// String operand that is not double-quoted or has an invalid escape sequence.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `operand`: The invalid operand.
+    InvalidString { line: usize, operand: String },
+
+    /// `.org` address before the current address of the code or before the origin.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the error.
+    /// - `operand`: The address operand.
+    InvalidOrg { line: usize, operand: String },
+
+    /// Data section in a source assembled with `Assembler::assemble`, which only returns the code.
+    /// Use `Assembler::assemble_program` to assemble the `.data` and `.bss` sections.
+    ///
+    /// # Parameters
+    /// - `line`: The line of the first data in the `.data` or `.bss` section.
+    DataSection { line: usize },
+
+    /// Word size other than `1`, `2` or `4` bytes, or other than `4` bytes for an object module.
+    ///
+    /// # Parameters
+    /// - `size`: The word size set with `Assembler::word_size`.
+    UnsupportedWordSize { size: usize },
+
+    /// Symbol exported with `.global` by more than one module.
+    ///
+    /// # Parameters
+    /// - `module`: The second module exporting the symbol.
+    /// - `symbol`: The duplicated symbol.
+    DuplicateSymbol { module: usize, symbol: String },
+
+    /// Reference to a symbol that is neither defined by the module nor exported by another one.
+    ///
+    /// # Parameters
+    /// - `module`: The module referencing the symbol.
+    /// - `symbol`: The undefined symbol.
+    UndefinedSymbol { module: usize, symbol: String },
+
+    /// Relocation of a field outside the section of its module.
+    ///
+    /// # Parameters
+    /// - `module`: The module of the relocation.
+    /// - `offset`: The offset of the field in its section.
+    InvalidRelocation { module: usize, offset: usize },
+
+    /// Module assembled with another endianness than the first module.
+    ///
+    /// # Parameters
+    /// - `module`: The module with the other endianness.
+    MixedEndianness { module: usize },
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic: {}", line, mnemonic)
+            }
+            AsmError::OperandCount {
+                line,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "line {}: expected {} operands, found {}",
+                    line, expected, found
+                )
+            }
+            AsmError::InvalidRegister { line, operand } => {
+                write!(f, "line {}: invalid register: {}", line, operand)
+            }
+            AsmError::InvalidNumber { line, operand } => {
+                write!(f, "line {}: invalid number: {}", line, operand)
+            }
+            AsmError::InvalidLabel { line, label } => {
+                write!(f, "line {}: invalid label: {}", line, label)
+            }
+            AsmError::DuplicateLabel { line, label } => {
+                write!(f, "line {}: duplicate label: {}", line, label)
+            }
+            AsmError::UndefinedLabel { line, label } => {
+                write!(f, "line {}: undefined label: {}", line, label)
+            }
+            AsmError::UnknownDirective { line, directive } => {
+                write!(f, "line {}: unknown directive: {}", line, directive)
+            }
+            AsmError::WrongSection { line, statement } => {
+                write!(f, "line {}: {} in the wrong section", line, statement)
+            }
+            AsmError::InvalidString { line, operand } => {
+                write!(f, "line {}: invalid string: {}", line, operand)
+            }
+            AsmError::InvalidOrg { line, operand } => {
+                write!(f, "line {}: .org moves backwards: {}", line, operand)
+            }
+            AsmError::DataSection { line } => {
+                write!(
+                    f,
+                    "line {}: data sections need Assembler::assemble_program",
+                    line
+                )
+            }
+            AsmError::UnsupportedWordSize { size } => {
+                write!(f, "unsupported word size: {} bytes", size)
+            }
+            AsmError::DuplicateSymbol { module, symbol } => {
+                write!(f, "module {}: duplicate symbol: {}", module, symbol)
+            }
+            AsmError::UndefinedSymbol { module, symbol } => {
+                write!(f, "module {}: undefined symbol: {}", module, symbol)
+            }
+            AsmError::InvalidRelocation { module, offset } => {
+                write!(
+                    f,
+                    "module {}: invalid relocation at offset: {}",
+                    module, offset
+                )
+            }
+            AsmError::MixedEndianness { module } => {
+                write!(
+                    f,
+                    "module {}: endianness differs from the first module",
+                    module
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::error::{AsmError, Result};
+use super::Section;
+use crate::isa::pod::{Endianness, Pod};
+use crate::isa::program::Program;
+
+/// An object module assembled by `Assembler::assemble_object`: its sections with the
+/// unresolved references to labels, to be linked with other modules by the `Linker`.
+///
+/// With the `serde` feature, objects can be serialized to be stored as object files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Object {
+    /// The code of the `.text` section, with the relocated fields left to `0`.
+    pub code: Vec<u8>,
+    /// The initialized data of the `.data` section, with the relocated fields left to `0`.
+    pub data: Vec<u8>,
+    /// The size of the `.bss` section.
+    pub bss_size: usize,
+    /// The labels defined by the module, with their section and their offset in it.
+    pub symbols: BTreeMap<String, (Section, usize)>,
+    /// The labels exported to the other modules with `.global`.
+    pub globals: BTreeSet<String>,
+    /// The fields referencing a label, set to its address by the linker.
+    pub relocations: Vec<Relocation>,
+    /// The order of the bytes of the module, see `Assembler::endianness`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub endianness: Endianness,
+}
+
+/// A 32-bit field of an object module holding the address of a label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Relocation {
+    /// The section of the field, `Section::Text` or `Section::Data`.
+    pub section: Section,
+    /// The offset of the field in its section.
+    pub offset: usize,
+    /// The referenced label, defined by the module or exported by another module.
+    pub symbol: String,
+}
+
+/// Linker combining object modules into a program.
+///
+/// The sections of the modules are concatenated in the order of the modules: the code starts at
+/// the `origin` and the program starts at the first instruction of the first module, the data
+/// of each module is aligned to 4 bytes and the `.bss` sections follow the `.data` sections.
+/// A label is resolved to the definition of its module, or else to the module exporting it.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::linker::Linker;
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::VM;
+/// let main = Assembler::new().assemble_object("CALL increment\nHLT").unwrap();
+/// let library = Assembler::new()
+///     .assemble_object(".global increment\nincrement: INC R0\nRET")
+///     .unwrap();
+/// let program = Linker::new().link(&[main, library]).unwrap();
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// assert_eq!(vm.run_program(&program).map(|termination| termination.steps), Ok(4));
+/// assert_eq!(vm.snapshot().cpu.registers[0], 1);
+/// ```
+pub struct Linker {
+    /// The address where the program is loaded, see `Assembler::origin`.
+    origin: u32,
+    /// The address of the data, after the code when `None`, see `Assembler::data_origin`.
+    data_origin: Option<u32>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self {
+            origin: 0,
+            data_origin: None,
+        }
+    }
+
+    /// Set the address where the program is loaded in the memory.
+    pub fn origin(mut self, address: u32) -> Self {
+        self.origin = address;
+        self
+    }
+
+    /// Set the address of the data in the memory.
+    /// By default, the data follows the code loaded at the `origin`, aligned to 4 bytes.
+    pub fn data_origin(mut self, address: u32) -> Self {
+        self.data_origin = Some(address);
+        self
+    }
+
+    /// Link object modules into a program.
+    ///
+    /// # Parameters
+    /// - `objects`: The modules, the first one holds the entry point of the program.
+    ///
+    /// # Errors
+    /// Returns `AsmError::DuplicateSymbol` if a label is exported by two modules,
+    /// `AsmError::UndefinedSymbol` if a referenced label is not found,
+    /// `AsmError::InvalidRelocation` if a relocation is outside its section and
+    /// `AsmError::MixedEndianness` if the modules do not have the same endianness.
+    pub fn link(&self, objects: &[Object]) -> Result<Program<'static>> {
+        // place the sections of every module
+        let mut code = Vec::new();
+        let mut data = Vec::new();
+        let mut bss_size = 0;
+        let mut bases = Vec::with_capacity(objects.len());
+        for (module, object) in objects.iter().enumerate() {
+            if object.endianness != objects[0].endianness {
+                return Err(AsmError::MixedEndianness { module });
+            }
+            data.resize(data.len().next_multiple_of(4), 0);
+            bss_size = usize::next_multiple_of(bss_size, 4);
+            bases.push((code.len(), data.len(), bss_size));
+            code.extend_from_slice(&object.code);
+            data.extend_from_slice(&object.data);
+            bss_size += object.bss_size;
+        }
+        let data_origin = self.data_origin.unwrap_or_else(|| {
+            self.origin
+                .wrapping_add(code.len() as u32)
+                .next_multiple_of(4)
+        });
+        let bss_origin = data_origin.wrapping_add(data.len() as u32);
+        let address = |module: usize, (section, offset): (Section, usize)| {
+            let (text_base, data_base, bss_base) = bases[module];
+            let (origin, base) = match section {
+                Section::Text => (self.origin, text_base),
+                Section::Data => (data_origin, data_base),
+                Section::Bss => (bss_origin, bss_base),
+            };
+            origin.wrapping_add((base + offset) as u32)
+        };
+
+        // resolve the exported labels
+        let mut globals = HashMap::new();
+        for (module, object) in objects.iter().enumerate() {
+            for symbol in &object.globals {
+                let definition =
+                    object
+                        .symbols
+                        .get(symbol)
+                        .ok_or_else(|| AsmError::UndefinedSymbol {
+                            module,
+                            symbol: symbol.clone(),
+                        })?;
+                if globals
+                    .insert(symbol.as_str(), address(module, *definition))
+                    .is_some()
+                {
+                    return Err(AsmError::DuplicateSymbol {
+                        module,
+                        symbol: symbol.clone(),
+                    });
+                }
+            }
+        }
+
+        // patch the fields referencing a label
+        for (module, object) in objects.iter().enumerate() {
+            for relocation in &object.relocations {
+                let value = match object.symbols.get(&relocation.symbol) {
+                    Some(definition) => address(module, *definition),
+                    None => *globals.get(relocation.symbol.as_str()).ok_or_else(|| {
+                        AsmError::UndefinedSymbol {
+                            module,
+                            symbol: relocation.symbol.clone(),
+                        }
+                    })?,
+                };
+                let (text_base, data_base, _) = bases[module];
+                let (section, base, size) = match relocation.section {
+                    Section::Text => (&mut code, text_base, object.code.len()),
+                    Section::Data => (&mut data, data_base, object.data.len()),
+                    Section::Bss => (&mut data, data_base, 0),
+                };
+                if relocation.offset.saturating_add(4) > size {
+                    return Err(AsmError::InvalidRelocation {
+                        module,
+                        offset: relocation.offset,
+                    });
+                }
+                let field = base + relocation.offset;
+                value.store(&mut section[field..field + 4], object.endianness);
+            }
+        }
+        Ok(Program::new(&code)
+            .with_data(data_origin as usize, &data, bss_size)
+            .into_owned())
+    }
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+
+    #[test]
+    fn test_link_origin() {
+        let objects = [
+            Assembler::new().assemble_object("JMP end").unwrap(),
+            Assembler::new()
+                .assemble_object(".global end\nend: HLT\n.data\naddress: .word end")
+                .unwrap(),
+        ];
+        let program = Linker::new()
+            .origin(0x100)
+            .data_origin(0x200)
+            .link(&objects)
+            .unwrap();
+        assert_eq!(program.code(), [0x12, 0x05, 0x01, 0x00, 0x00, 0xff]);
+        assert_eq!(program.data_address(), 0x200);
+        assert_eq!(program.data(), [0x05, 0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_link_errors() {
+        let exporting = Assembler::new()
+            .assemble_object(".global f\nf: RET")
+            .unwrap();
+        assert_eq!(
+            Linker::new().link(&[exporting.clone(), exporting]),
+            Err(AsmError::DuplicateSymbol {
+                module: 1,
+                symbol: "f".to_string()
+            })
+        );
+
+        let object = Assembler::new().assemble_object("CALL g").unwrap();
+        assert_eq!(
+            Linker::new().link(std::slice::from_ref(&object)),
+            Err(AsmError::UndefinedSymbol {
+                module: 0,
+                symbol: "g".to_string()
+            })
+        );
+
+        let mut invalid = object;
+        invalid.relocations[0].offset = 2;
+        invalid.symbols.insert("g".to_string(), (Section::Text, 0));
+        assert_eq!(
+            Linker::new().link(&[invalid]),
+            Err(AsmError::InvalidRelocation {
+                module: 0,
+                offset: 2
+            })
+        );
+
+        let big = Assembler::new()
+            .endianness(Endianness::Big)
+            .assemble_object("RET")
+            .unwrap();
+        let little = Assembler::new().assemble_object("RET").unwrap();
+        assert_eq!(
+            Linker::new().link(&[little, big]),
+            Err(AsmError::MixedEndianness { module: 1 })
+        );
+
+        assert_eq!(
+            Assembler::new().assemble_object(".global h"),
+            Err(AsmError::UndefinedLabel {
+                line: 1,
+                label: "h".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_link_matches_assemble() {
+        // every instruction with an address or an immediate operand references a label
+        let source = "
+            start:
+                MOV R0 value
+                LD R1 value
+                ST R1 value
+                LDB R1 value
+                LDBU R1 value
+                LDH R1 value
+                LDHU R1 value
+                STB R1 value
+                STH R1 value
+                LDF R1 value
+                STF R1 value
+                PUSH value
+                ENTER value
+                SPAWN R2 start
+                CALL start
+                JMP start
+                JMPNZ start
+                JMPGE start
+                HLT
+            .data
+            value: .word start, value
+        ";
+        let assembler = Assembler::new().origin(0x40);
+        let object = assembler.assemble_object(source).unwrap();
+        assert_eq!(object.relocations.len(), 20);
+        assert_eq!(
+            Linker::new().origin(0x40).link(&[object]),
+            assembler.assemble_program(source)
+        );
+    }
+}
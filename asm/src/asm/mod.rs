@@ -0,0 +1,1782 @@
+pub mod builder;
+pub mod error;
+pub mod linker;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::isa::debug_info::DebugInfo;
+use crate::isa::encoder::Encoder;
+use crate::isa::hardware_config::REGISTERS_COUNT;
+use crate::isa::instructions::{Instruction, OpCode};
+use crate::isa::pod::{Endianness, Pod};
+use crate::isa::program::Program;
+use error::{AsmError, Result};
+use linker::{Object, Relocation};
+
+/// Assembler translating textual assembly into the bytecode accepted by `VM::run`.
+///
+/// The source contains one instruction per line, written as a mnemonic followed by its
+/// operands separated by spaces or commas, in the order of the `Display` implementation
+/// of `Instruction`:
+/// - Registers are written `R0` to `R3`, or up to the register count given to `with_registers`.
+///   Float registers are written `F0` to `F3` in the same way.
+/// - Numbers are decimal, hexadecimal (`0x`) or binary (`0b`), optionally negative.
+///   Float values, such as `1.5` or `-2e3`, are decimal.
+/// - Addresses are numbers or labels. A label is defined by `name:` at the start of a line
+///   and evaluates to the offset of the next instruction, plus the `origin` of the program.
+/// - Comments start with `;` and run to the end of the line.
+/// - Constants are defined with `.equ NAME value` and can be used in place of any number.
+/// - `.org address` moves the next instruction to an address, the gap is filled with `NOP`.
+///
+/// Mnemonics and register names are case-insensitive, labels are case-sensitive.
+///
+/// The instructions are in the `.text` section. The `.data` and `.bss` directives switch to
+/// the data sections of the program, assembled with `assemble_program`:
+/// - The `.data` section holds initialized data: `.byte` values, `.word` 32-bit values or
+///   labels, `.ascii` strings, `.asciz` null-terminated strings and `.space` zeroed bytes.
+///   The strings are double-quoted, with the escape sequences `\n`, `\t`, `\r`, `\0`, `\\`
+///   and `\"`.
+/// - The `.bss` section holds zeroed data reserved with `.space`, and follows the `.data`
+///   section in the memory.
+///
+/// The labels of the data sections evaluate to their address in the memory, starting at the
+/// `data_origin` of the program.
+///
+/// A source can also be assembled into an object module with `assemble_object`, to be linked
+/// with other modules by the `Linker`. The labels of a module are local to it, except the
+/// labels exported with the `.global` directive, which can be referenced by the other modules.
+pub struct Assembler {
+    encoder: Encoder,
+    /// The number of registers of the target CPU, register operands must be lower.
+    registers_count: u8,
+    /// The address where the program is loaded, added to the offset of the labels.
+    origin: u32,
+    /// The address of the `.data` section, after the code when `None`.
+    data_origin: Option<u32>,
+    /// The order of the bytes of the immediate values, the addresses and the data words.
+    endianness: Endianness,
+    /// The size in bytes of the immediate values, the addresses and the data words.
+    word_size: usize,
+}
+
+/// The kind of an instruction operand in the source text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operand {
+    /// A register, `R0` to `R3`.
+    Register,
+    /// A 32-bit immediate value, a number or a label.
+    Immediate,
+    /// A 32-bit memory address, a number or a label.
+    Address,
+    /// An 8-bit number, such as a performance counter id or a syscall number.
+    Byte,
+    /// A register holding a memory address, `[R0]` to `[R3]`.
+    Indirect,
+    /// A float register, `F0` to `F3`.
+    FloatRegister,
+    /// A 32-bit float value, stored as its bits.
+    Float,
+    /// A 16-bit I/O port of `IN` and `OUT`.
+    Port,
+}
+
+/// A section of the program, selected by the `.text`, `.data` and `.bss` directives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Section {
+    /// The instructions.
+    Text,
+    /// The initialized data.
+    Data,
+    /// The zeroed data.
+    Bss,
+}
+
+/// An instruction of the source, split into its parts.
+struct Statement<'a> {
+    line: usize,
+    /// The offset of the instruction in the code, past the padding of `.org`.
+    offset: usize,
+    opcode: OpCode,
+    operands: Vec<&'a str>,
+}
+
+/// A directive of the `.data` section, split into its parts.
+enum DataStatement<'a> {
+    /// The `.byte` or `.word` values, with the size of each value in bytes.
+    Values {
+        line: usize,
+        size: usize,
+        operands: Vec<&'a str>,
+    },
+    /// The bytes of `.ascii`, `.asciz` and `.space`.
+    Bytes(Vec<u8>),
+}
+
+/// The sections of an assembled program.
+struct Sections {
+    code: Vec<u8>,
+    data_origin: u32,
+    data: Vec<u8>,
+    bss_size: usize,
+    /// The line of the first data of the `.data` or `.bss` section.
+    data_line: Option<usize>,
+    debug_info: DebugInfo,
+    /// The labels with their section and their offset in it.
+    symbols: BTreeMap<String, (Section, usize)>,
+    /// The labels exported with `.global`.
+    globals: BTreeSet<String>,
+    /// The fields referencing a label, only when assembling an object module.
+    relocations: Vec<Relocation>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::with_registers(REGISTERS_COUNT)
+    }
+
+    /// Create an assembler for a CPU with a specific number of registers.
+    ///
+    /// # Parameters
+    /// - `registers_count`: The number of registers of the target CPU.
+    pub fn with_registers(registers_count: u8) -> Self {
+        Self {
+            encoder: Encoder::new(),
+            registers_count,
+            origin: 0,
+            data_origin: None,
+            endianness: Endianness::Little,
+            word_size: 4,
+        }
+    }
+
+    /// Set the address where the program is loaded in the memory, added to the value of the
+    /// labels. Programs run in the Von Neumann mode of `VmBuilder::von_neumann` use its base.
+    pub fn origin(mut self, address: u32) -> Self {
+        self.origin = address;
+        self
+    }
+
+    /// Set the address of the `.data` section in the memory.
+    /// By default, the data follows the code loaded at the `origin`, aligned to 4 bytes.
+    pub fn data_origin(mut self, address: u32) -> Self {
+        self.data_origin = Some(address);
+        self
+    }
+
+    /// Set the order of the bytes of the immediate values, the addresses and the `.word` data,
+    /// little-endian by default. Big-endian programs run on a VM built with the same
+    /// `VmBuilder::endianness`.
+    pub fn endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self.encoder = Encoder::new().with_endianness(endianness);
+        self
+    }
+
+    /// Set the size in bytes of the data word of the target VM: `4` by default for `VM<i32>`,
+    /// `2` for `VM<i16>` or `1` for `VM<i8>`. The immediate values, the addresses and the `.word`
+    /// data take the size of the word, and a value that does not fit in it, as a signed or an
+    /// unsigned integer, is an `AsmError::InvalidNumber`. The labels and the `origin` must
+    /// be addressable by the word, up to `0xffff` for `VM<i16>` and `0xff` for `VM<i8>`.
+    ///
+    /// The object modules of `assemble_object` are always 32-bit, and the other sizes fail with
+    /// `AsmError::UnsupportedWordSize`.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::VM;
+    /// let program = Assembler::new()
+    ///     .word_size(1)
+    ///     .assemble("MOV R0 100\nMOV R1 28\nADD R0 R0 R1\nHLT")
+    ///     .unwrap();
+    /// let mut vm = VM::<i8>::new(16, 256);
+    /// vm.run(&program).unwrap();
+    /// // the sum wraps to the minimum of the 8-bit word
+    /// assert_eq!(vm.snapshot().cpu.registers[0], -128);
+    /// ```
+    pub fn word_size(mut self, size: usize) -> Self {
+        self.word_size = size;
+        self
+    }
+
+    /// Assemble a source text into bytecode.
+    ///
+    /// # Parameters
+    /// - `source`: The assembly source text.
+    ///
+    /// # Returns
+    /// The bytecode of the program.
+    ///
+    /// # Errors
+    /// Returns an error describing the first invalid line of the source, and
+    /// `AsmError::DataSection` if the source has data in the `.data` or `.bss` sections.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::VM;
+    /// let program = Assembler::new()
+    ///     .assemble("MOV R0 5\nMOV R1 7\nADD R2 R0 R1\nHLT")
+    ///     .unwrap();
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+    /// ```
+    pub fn assemble(&self, source: &str) -> Result<Vec<u8>> {
+        let sections = self.assemble_sections(source, false)?;
+        if let Some(line) = sections.data_line {
+            return Err(AsmError::DataSection { line });
+        }
+        Ok(sections.code)
+    }
+
+    /// Assemble a source text into a program with its `.data` and `.bss` sections,
+    /// run with `VM::run_program`.
+    ///
+    /// # Parameters
+    /// - `source`: The assembly source text.
+    ///
+    /// # Errors
+    /// Returns an error describing the first invalid line of the source.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::VM;
+    /// let source = "
+    ///         LD R0 table
+    ///         HLT
+    ///     .data
+    ///     table: .word 42
+    /// ";
+    /// let program = Assembler::new().assemble_program(source).unwrap();
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// assert_eq!(vm.run_program(&program).map(|termination| termination.steps), Ok(2));
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 42);
+    /// ```
+    pub fn assemble_program(&self, source: &str) -> Result<Program<'static>> {
+        self.assemble_with_debug_info(source)
+            .map(|(program, _)| program)
+    }
+
+    /// Assemble a source text into a program with its debug information: the address of every
+    /// label and the source line of every instruction.
+    ///
+    /// # Parameters
+    /// - `source`: The assembly source text.
+    ///
+    /// # Errors
+    /// Returns an error describing the first invalid line of the source.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::VM;
+    /// let source = "
+    ///     main:
+    ///         MOV R0 0x10000
+    ///         LDR R1 [R0]
+    /// ";
+    /// let (program, debug_info) = Assembler::new().assemble_with_debug_info(source).unwrap();
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.set_debug_info(debug_info);
+    /// assert!(vm.run_program(&program).is_err());
+    /// assert_eq!(vm.location(), "in function `main`, line 4");
+    /// ```
+    pub fn assemble_with_debug_info(&self, source: &str) -> Result<(Program<'static>, DebugInfo)> {
+        let sections = self.assemble_sections(source, false)?;
+        let program = Program::new(&sections.code).with_data(
+            sections.data_origin as usize,
+            &sections.data,
+            sections.bss_size,
+        );
+        Ok((program.into_owned(), sections.debug_info))
+    }
+
+    /// Assemble a source text into an object module, linked with other modules by the `Linker`.
+    ///
+    /// The labels are not resolved: every field referencing a label is left to `0` and recorded
+    /// as a relocation, and the labels that are not defined by the module are expected to be
+    /// exported by another module.
+    ///
+    /// # Parameters
+    /// - `source`: The assembly source text.
+    ///
+    /// # Errors
+    /// Returns an error describing the first invalid line of the source.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// let object = Assembler::new().assemble_object("CALL print\nHLT").unwrap();
+    /// assert_eq!(object.relocations[0].symbol, "print");
+    /// ```
+    pub fn assemble_object(&self, source: &str) -> Result<Object> {
+        let sections = self.assemble_sections(source, true)?;
+        Ok(Object {
+            code: sections.code,
+            data: sections.data,
+            bss_size: sections.bss_size,
+            symbols: sections.symbols,
+            globals: sections.globals,
+            relocations: sections.relocations,
+            endianness: self.endianness,
+        })
+    }
+
+    /// Assemble the sections of a source text.
+    /// The labels are left to the linker as relocations when assembling an `object` module.
+    fn assemble_sections(&self, source: &str, object: bool) -> Result<Sections> {
+        if !matches!(self.word_size, 1 | 2 | 4) || object && self.word_size != 4 {
+            return Err(AsmError::UnsupportedWordSize {
+                size: self.word_size,
+            });
+        }
+        // first pass: split the lines and compute the offset of every label in its section
+        let mut statements = Vec::new();
+        let mut data_statements = Vec::new();
+        let mut offsets = HashMap::new();
+        let mut section = Section::Text;
+        let mut offset = 0;
+        let mut data_size = 0;
+        let mut bss_size = 0;
+        let mut data_line = None;
+        let mut globals = Vec::new();
+        let mut constants = HashMap::new();
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let text = strip_comment(text).trim();
+            // a colon in a string is not a label
+            let (label, text) = match text.split_once(':') {
+                Some((label, rest)) if !label.contains('"') => (Some(label.trim()), rest.trim()),
+                _ => (None, text),
+            };
+            if let Some(label) = label {
+                if !is_label(label) {
+                    return Err(AsmError::InvalidLabel {
+                        line,
+                        label: label.to_string(),
+                    });
+                }
+                let label_offset = match section {
+                    Section::Text => offset,
+                    Section::Data => data_size,
+                    Section::Bss => bss_size,
+                };
+                if constants.contains_key(label)
+                    || offsets.insert(label, (section, label_offset)).is_some()
+                {
+                    return Err(AsmError::DuplicateLabel {
+                        line,
+                        label: label.to_string(),
+                    });
+                }
+            }
+            let mut tokens = text
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|token| !token.is_empty());
+            let Some(mnemonic) = tokens.next() else {
+                continue;
+            };
+            if mnemonic.starts_with('.') {
+                let operands: Vec<_> = tokens.collect();
+                let count = |expected: usize| {
+                    if operands.len() == expected {
+                        Ok(())
+                    } else {
+                        Err(AsmError::OperandCount {
+                            line,
+                            expected,
+                            found: operands.len(),
+                        })
+                    }
+                };
+                match (mnemonic, section) {
+                    (".text" | ".data" | ".bss", _) => {
+                        count(0)?;
+                        section = match mnemonic {
+                            ".text" => Section::Text,
+                            ".data" => Section::Data,
+                            _ => Section::Bss,
+                        };
+                        continue;
+                    }
+                    (".global", _) => {
+                        globals.extend(operands.into_iter().map(|label| (label, line)));
+                        continue;
+                    }
+                    (".equ", _) => {
+                        count(2)?;
+                        let (name, value) = (operands[0], operands[1]);
+                        if !is_label(name) {
+                            return Err(AsmError::InvalidLabel {
+                                line,
+                                label: name.to_string(),
+                            });
+                        }
+                        let value = parse_value(value, &constants, line)?;
+                        if offsets.contains_key(name) || constants.insert(name, value).is_some() {
+                            return Err(AsmError::DuplicateLabel {
+                                line,
+                                label: name.to_string(),
+                            });
+                        }
+                        continue;
+                    }
+                    (".org", Section::Text) => {
+                        count(1)?;
+                        let address = parse_value(operands[0], &constants, line)?;
+                        offset = address
+                            .checked_sub(self.origin)
+                            .map(|target| target as usize)
+                            .filter(|target| *target >= offset)
+                            .ok_or_else(|| AsmError::InvalidOrg {
+                                line,
+                                operand: operands[0].to_string(),
+                            })?;
+                        continue;
+                    }
+                    (".byte" | ".word", Section::Data) => {
+                        let size = if mnemonic == ".byte" {
+                            1
+                        } else {
+                            self.word_size
+                        };
+                        data_size += size * operands.len();
+                        data_statements.push(DataStatement::Values {
+                            line,
+                            size,
+                            operands,
+                        });
+                    }
+                    (".ascii" | ".asciz", Section::Data) => {
+                        let string = text[mnemonic.len()..].trim();
+                        let mut bytes =
+                            parse_string(string).ok_or_else(|| AsmError::InvalidString {
+                                line,
+                                operand: string.to_string(),
+                            })?;
+                        if mnemonic == ".asciz" {
+                            bytes.push(0);
+                        }
+                        data_size += bytes.len();
+                        data_statements.push(DataStatement::Bytes(bytes));
+                    }
+                    (".space", Section::Data | Section::Bss) => {
+                        count(1)?;
+                        let size = parse_constant(operands[0], &constants)
+                            .and_then(|size| usize::try_from(size).ok())
+                            .ok_or_else(|| AsmError::InvalidNumber {
+                                line,
+                                operand: operands[0].to_string(),
+                            })?;
+                        if section == Section::Data {
+                            data_size += size;
+                            data_statements.push(DataStatement::Bytes(vec![0; size]));
+                        } else {
+                            bss_size += size;
+                        }
+                    }
+                    (".org" | ".byte" | ".word" | ".ascii" | ".asciz" | ".space", _) => {
+                        return Err(AsmError::WrongSection {
+                            line,
+                            statement: mnemonic.to_string(),
+                        })
+                    }
+                    _ => {
+                        return Err(AsmError::UnknownDirective {
+                            line,
+                            directive: mnemonic.to_string(),
+                        })
+                    }
+                }
+                data_line = data_line.or(Some(line));
+                continue;
+            }
+            if section != Section::Text {
+                return Err(AsmError::WrongSection {
+                    line,
+                    statement: mnemonic.to_string(),
+                });
+            }
+            let opcode = parse_mnemonic(mnemonic).ok_or_else(|| AsmError::UnknownMnemonic {
+                line,
+                mnemonic: mnemonic.to_string(),
+            })?;
+            statements.push(Statement {
+                line,
+                offset,
+                opcode,
+                operands: tokens.collect(),
+            });
+            offset += self.instruction_size(opcode);
+        }
+
+        // the labels evaluate to their address in the memory
+        let data_origin = self
+            .data_origin
+            .unwrap_or_else(|| self.origin.wrapping_add(offset as u32).next_multiple_of(4));
+        let mut labels: HashMap<_, _> = offsets
+            .iter()
+            .map(|(label, (section, label_offset))| {
+                let base = match section {
+                    Section::Text => self.origin,
+                    Section::Data => data_origin,
+                    Section::Bss => data_origin.wrapping_add(data_size as u32),
+                };
+                (*label, base.wrapping_add(*label_offset as u32))
+            })
+            .collect();
+        for (label, line) in &globals {
+            if !offsets.contains_key(label) {
+                return Err(AsmError::UndefinedLabel {
+                    line: *line,
+                    label: label.to_string(),
+                });
+            }
+        }
+
+        let mut debug_info = DebugInfo::new();
+        for (label, address) in &labels {
+            debug_info.add_symbol(label, *address as usize);
+        }
+        labels.extend(&constants);
+
+        // second pass: resolve the operands and encode the instructions and the data
+        let mut relocations = Vec::new();
+        let mut code = Vec::with_capacity(offset);
+        for statement in &statements {
+            code.resize(statement.offset, 0);
+            let instruction = if object {
+                // the 32-bit address or immediate, if any, is the last field of an instruction
+                let field = code.len() + statement.opcode.size::<i32, u32>().saturating_sub(4);
+                let values = operands(statement.opcode)
+                    .iter()
+                    .zip(&statement.operands)
+                    .filter(|(kind, _)| matches!(kind, Operand::Immediate | Operand::Address))
+                    .map(|(_, operand)| *operand);
+                let unresolved =
+                    relocate(values, &constants, Section::Text, field, &mut relocations);
+                build_instruction(statement, &unresolved, self.registers_count)?
+            } else {
+                build_instruction(statement, &labels, self.registers_count)?
+            };
+            let address = self.origin.wrapping_add(code.len() as u32);
+            debug_info.add_line(address as usize, statement.line);
+            self.encoder.encode_instruction(&instruction, &mut code);
+            self.narrow_field(statement, &mut code)?;
+        }
+        code.resize(offset, 0);
+        let mut data = Vec::with_capacity(data_size);
+        for statement in &data_statements {
+            match statement {
+                DataStatement::Values {
+                    line,
+                    size,
+                    operands,
+                } => {
+                    for operand in operands {
+                        if *size == 1 {
+                            let values = if object { &constants } else { &labels };
+                            let byte = parse_constant(operand, values)
+                                .filter(|number| (i8::MIN as i64..=u8::MAX as i64).contains(number))
+                                .ok_or_else(|| AsmError::InvalidNumber {
+                                    line: *line,
+                                    operand: operand.to_string(),
+                                })?;
+                            data.push(byte as u8);
+                        } else {
+                            let word = if object {
+                                let unresolved = relocate(
+                                    [*operand].into_iter(),
+                                    &constants,
+                                    Section::Data,
+                                    data.len(),
+                                    &mut relocations,
+                                );
+                                parse_value(operand, &unresolved, *line)?
+                            } else {
+                                parse_value(operand, &labels, *line)?
+                            };
+                            let start = data.len();
+                            data.resize(start + self.word_size, 0);
+                            if !store_narrow(word, &mut data[start..], self.endianness) {
+                                return Err(AsmError::InvalidNumber {
+                                    line: *line,
+                                    operand: operand.to_string(),
+                                });
+                            }
+                        }
+                    }
+                }
+                DataStatement::Bytes(bytes) => data.extend_from_slice(bytes),
+            }
+        }
+        Ok(Sections {
+            code,
+            data_origin,
+            data,
+            bss_size,
+            data_line,
+            debug_info,
+            symbols: offsets
+                .into_iter()
+                .map(|(label, symbol)| (label.to_string(), symbol))
+                .collect(),
+            globals: globals
+                .into_iter()
+                .map(|(label, _)| label.to_string())
+                .collect(),
+            relocations,
+        })
+    }
+
+    /// Get the size of an instruction for the word size of the target.
+    fn instruction_size(&self, opcode: OpCode) -> usize {
+        match self.word_size {
+            1 => opcode.size::<i8, u8>(),
+            2 => opcode.size::<i16, u16>(),
+            _ => opcode.size::<i32, u32>(),
+        }
+    }
+
+    /// Narrow the 32-bit immediate value or address of an instruction just encoded at the end
+    /// of the code, the last field of the instruction, to the word size of the target.
+    fn narrow_field(&self, statement: &Statement, code: &mut Vec<u8>) -> Result<()> {
+        let wide = statement.opcode.size::<i32, u32>();
+        if self.instruction_size(statement.opcode) == wide {
+            return Ok(());
+        }
+        let field = code.len() - 4;
+        let value = u32::load(&code[field..], self.endianness);
+        code.truncate(field + self.word_size);
+        if store_narrow(value, &mut code[field..], self.endianness) {
+            return Ok(());
+        }
+        let operand = operands(statement.opcode)
+            .iter()
+            .zip(&statement.operands)
+            .find(|(kind, _)| matches!(kind, Operand::Immediate | Operand::Address))
+            .map_or("", |(_, operand)| *operand);
+        Err(AsmError::InvalidNumber {
+            line: statement.line,
+            operand: operand.to_string(),
+        })
+    }
+}
+
+/// Store a 32-bit value in the 1, 2 or 4 bytes of `bytes`.
+/// Returns `false` if the value does not fit in them as a signed or an unsigned integer.
+fn store_narrow(value: u32, bytes: &mut [u8], endianness: Endianness) -> bool {
+    match bytes.len() {
+        1 => (value as u8).store(bytes, endianness),
+        2 => (value as u16).store(bytes, endianness),
+        _ => {
+            value.store(bytes, endianness);
+            return true;
+        }
+    }
+    let bits = bytes.len() as u32 * 8;
+    value >> bits == 0 || (value as i32) >> (bits - 1) == -1
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the opcode matching a mnemonic, ignoring case
+fn parse_mnemonic(mnemonic: &str) -> Option<OpCode> {
+    let opcode = match mnemonic.to_ascii_uppercase().as_str() {
+        "NOP" => OpCode::NOP,
+        "MOV" => OpCode::MOV,
+        "LD" => OpCode::LD,
+        "ST" => OpCode::ST,
+        "AND" => OpCode::AND,
+        "OR" => OpCode::OR,
+        "XOR" => OpCode::XOR,
+        "NOT" => OpCode::NOT,
+        "CMP" => OpCode::CMP,
+        "ADD" => OpCode::ADD,
+        "SUB" => OpCode::SUB,
+        "ADC" => OpCode::ADC,
+        "SBB" => OpCode::SBB,
+        "FXMUL" => OpCode::FXMUL,
+        "FXDIV" => OpCode::FXDIV,
+        "MULT" => OpCode::MULT,
+        "DIV" => OpCode::DIV,
+        "MOD" => OpCode::MOD,
+        "INC" => OpCode::INC,
+        "DEC" => OpCode::DEC,
+        "PUSHREG" => OpCode::PUSHREG,
+        "POPREG" => OpCode::POPREG,
+        "JMP" => OpCode::JMP,
+        "JMPN" => OpCode::JMPN,
+        "JMPP" => OpCode::JMPP,
+        "JMPZ" => OpCode::JMPZ,
+        "CALL" => OpCode::CALL,
+        "RET" => OpCode::RET,
+        "CLF" => OpCode::CLF,
+        "PUSHF" => OpCode::PUSHF,
+        "POPF" => OpCode::POPF,
+        "HLT" => OpCode::HLT,
+        "EXIT" => OpCode::EXIT,
+        "RDCNT" => OpCode::RDCNT,
+        "RDCYCLE" => OpCode::RDCYCLE,
+        "SYSCALL" => OpCode::SYSCALL,
+        "IN" => OpCode::IN,
+        "OUT" => OpCode::OUT,
+        "EI" => OpCode::EI,
+        "DI" => OpCode::DI,
+        "IRET" => OpCode::IRET,
+        "SHL" => OpCode::SHL,
+        "SHLI" => OpCode::SHLI,
+        "SHR" => OpCode::SHR,
+        "SHRI" => OpCode::SHRI,
+        "SAR" => OpCode::SAR,
+        "SARI" => OpCode::SARI,
+        "ROL" => OpCode::ROL,
+        "ROR" => OpCode::ROR,
+        "MOVR" => OpCode::MOVR,
+        "LDR" => OpCode::LDR,
+        "STR" => OpCode::STR,
+        "LDB" => OpCode::LDB,
+        "LDBU" => OpCode::LDBU,
+        "LDH" => OpCode::LDH,
+        "LDHU" => OpCode::LDHU,
+        "STB" => OpCode::STB,
+        "STH" => OpCode::STH,
+        "MULU" => OpCode::MULU,
+        "DIVU" => OpCode::DIVU,
+        "MODU" => OpCode::MODU,
+        "CMPU" => OpCode::CMPU,
+        "JMPB" => OpCode::JMPB,
+        "JMPBE" => OpCode::JMPBE,
+        "JMPA" => OpCode::JMPA,
+        "JMPAE" => OpCode::JMPAE,
+        "JMPLT" => OpCode::JMPLT,
+        "JMPLE" => OpCode::JMPLE,
+        "JMPGT" => OpCode::JMPGT,
+        "JMPGE" => OpCode::JMPGE,
+        "JMPC" => OpCode::JMPC,
+        "JMPNC" => OpCode::JMPNC,
+        "JMPNZ" => OpCode::JMPNZ,
+        "JMPO" => OpCode::JMPO,
+        "CMOVZ" => OpCode::CMOVZ,
+        "CMOVN" => OpCode::CMOVN,
+        "MIN" => OpCode::MIN,
+        "MAX" => OpCode::MAX,
+        "MINU" => OpCode::MINU,
+        "MAXU" => OpCode::MAXU,
+        "POPCNT" => OpCode::POPCNT,
+        "CLZ" => OpCode::CLZ,
+        "CTZ" => OpCode::CTZ,
+        "BT" => OpCode::BT,
+        "BTI" => OpCode::BTI,
+        "BS" => OpCode::BS,
+        "BSI" => OpCode::BSI,
+        "BC" => OpCode::BC,
+        "BCI" => OpCode::BCI,
+        "BTG" => OpCode::BTG,
+        "BTGI" => OpCode::BTGI,
+        "PUSH" => OpCode::PUSH,
+        "DUP" => OpCode::DUP,
+        "SWAP" => OpCode::SWAP,
+        "DROP" => OpCode::DROP,
+        "PUSHA" => OpCode::PUSHA,
+        "POPA" => OpCode::POPA,
+        "FMOV" => OpCode::FMOV,
+        "FADD" => OpCode::FADD,
+        "FSUB" => OpCode::FSUB,
+        "FMUL" => OpCode::FMUL,
+        "FDIV" => OpCode::FDIV,
+        "FCMP" => OpCode::FCMP,
+        "ITOF" => OpCode::ITOF,
+        "FTOI" => OpCode::FTOI,
+        "MULH" => OpCode::MULH,
+        "UMULH" => OpCode::UMULH,
+        "CAS" => OpCode::CAS,
+        "XADD" => OpCode::XADD,
+        "SPAWN" => OpCode::SPAWN,
+        "YIELD" => OpCode::YIELD,
+        "JOIN" => OpCode::JOIN,
+        "MMUON" => OpCode::MMUON,
+        "MMUOFF" => OpCode::MMUOFF,
+        "TLBFLUSH" => OpCode::TLBFLUSH,
+        "RDFAULT" => OpCode::RDFAULT,
+        "RDSP" => OpCode::RDSP,
+        "WRSP" => OpCode::WRSP,
+        "ENTER" => OpCode::ENTER,
+        "LEAVE" => OpCode::LEAVE,
+        "LDF" => OpCode::LDF,
+        "STF" => OpCode::STF,
+        "RDFP" => OpCode::RDFP,
+        _ => return None,
+    };
+    Some(opcode)
+}
+
+/// Get the operands expected by an opcode, in source order
+fn operands(opcode: OpCode) -> &'static [Operand] {
+    use Operand::*;
+    match opcode {
+        OpCode::NOP
+        | OpCode::RET
+        | OpCode::CLF
+        | OpCode::PUSHF
+        | OpCode::POPF
+        | OpCode::EI
+        | OpCode::DI
+        | OpCode::IRET
+        | OpCode::DUP
+        | OpCode::SWAP
+        | OpCode::DROP
+        | OpCode::PUSHA
+        | OpCode::POPA
+        | OpCode::YIELD
+        | OpCode::LEAVE
+        | OpCode::MMUOFF
+        | OpCode::TLBFLUSH
+        | OpCode::HLT => &[],
+        OpCode::MOV | OpCode::LDF | OpCode::STF => &[Register, Immediate],
+        OpCode::LD | OpCode::ST => &[Register, Address],
+        OpCode::AND
+        | OpCode::OR
+        | OpCode::XOR
+        | OpCode::ADD
+        | OpCode::SUB
+        | OpCode::ADC
+        | OpCode::SBB
+        | OpCode::MULT
+        | OpCode::DIV
+        | OpCode::MOD
+        | OpCode::FXMUL
+        | OpCode::FXDIV => &[Register, Register, Register],
+        OpCode::NOT | OpCode::CMP | OpCode::POPCNT | OpCode::CLZ | OpCode::CTZ => {
+            &[Register, Register]
+        }
+        OpCode::INC
+        | OpCode::DEC
+        | OpCode::PUSHREG
+        | OpCode::POPREG
+        | OpCode::JOIN
+        | OpCode::MMUON
+        | OpCode::RDFAULT
+        | OpCode::RDSP
+        | OpCode::WRSP
+        | OpCode::RDFP
+        | OpCode::RDCYCLE => &[Register],
+        OpCode::JMP
+        | OpCode::JMPN
+        | OpCode::JMPP
+        | OpCode::JMPZ
+        | OpCode::JMPB
+        | OpCode::JMPBE
+        | OpCode::JMPA
+        | OpCode::JMPAE
+        | OpCode::JMPLT
+        | OpCode::JMPLE
+        | OpCode::JMPGT
+        | OpCode::JMPGE
+        | OpCode::JMPC
+        | OpCode::JMPNC
+        | OpCode::JMPNZ
+        | OpCode::JMPO
+        | OpCode::CALL
+        | OpCode::ENTER => &[Address],
+        OpCode::RDCNT => &[Register, Byte],
+        OpCode::SYSCALL => &[Byte],
+        OpCode::IN => &[Register, Port],
+        OpCode::OUT => &[Port, Register],
+        OpCode::SHL => &[Register, Register, Register],
+        OpCode::SHLI => &[Register, Register, Byte],
+        OpCode::SHR => &[Register, Register, Register],
+        OpCode::SHRI => &[Register, Register, Byte],
+        OpCode::SAR => &[Register, Register, Register],
+        OpCode::SARI => &[Register, Register, Byte],
+        OpCode::ROL => &[Register, Register, Register],
+        OpCode::ROR => &[Register, Register, Register],
+        OpCode::MOVR | OpCode::CMOVZ | OpCode::CMOVN => &[Register, Register],
+        OpCode::LDR => &[Register, Indirect],
+        OpCode::STR => &[Register, Indirect],
+        OpCode::LDB => &[Register, Address],
+        OpCode::LDBU => &[Register, Address],
+        OpCode::LDH => &[Register, Address],
+        OpCode::LDHU => &[Register, Address],
+        OpCode::STB => &[Register, Address],
+        OpCode::STH => &[Register, Address],
+        OpCode::MULU => &[Register, Register, Register],
+        OpCode::DIVU => &[Register, Register, Register],
+        OpCode::MODU => &[Register, Register, Register],
+        OpCode::MIN => &[Register, Register, Register],
+        OpCode::MAX => &[Register, Register, Register],
+        OpCode::MINU => &[Register, Register, Register],
+        OpCode::MAXU => &[Register, Register, Register],
+        OpCode::CMPU => &[Register, Register],
+        OpCode::BT => &[Register, Register],
+        OpCode::BTI => &[Register, Byte],
+        OpCode::BS => &[Register, Register],
+        OpCode::BSI => &[Register, Byte],
+        OpCode::BC => &[Register, Register],
+        OpCode::BCI => &[Register, Byte],
+        OpCode::BTG => &[Register, Register],
+        OpCode::BTGI => &[Register, Byte],
+        OpCode::PUSH | OpCode::EXIT => &[Immediate],
+        OpCode::FMOV => &[FloatRegister, Float],
+        OpCode::FADD => &[FloatRegister, FloatRegister, FloatRegister],
+        OpCode::FSUB => &[FloatRegister, FloatRegister, FloatRegister],
+        OpCode::FMUL => &[FloatRegister, FloatRegister, FloatRegister],
+        OpCode::FDIV => &[FloatRegister, FloatRegister, FloatRegister],
+        OpCode::FCMP => &[FloatRegister, FloatRegister],
+        OpCode::ITOF => &[FloatRegister, Register],
+        OpCode::FTOI => &[Register, FloatRegister],
+        OpCode::MULH => &[Register, Register, Register],
+        OpCode::UMULH => &[Register, Register, Register],
+        OpCode::CAS => &[Register, Register, Indirect],
+        OpCode::XADD => &[Register, Indirect],
+        OpCode::SPAWN => &[Register, Address],
+    }
+}
+
+/// Build the instruction of a statement, resolving its operands
+fn build_instruction(
+    statement: &Statement,
+    labels: &HashMap<&str, u32>,
+    registers_count: u8,
+) -> Result<Instruction<i32, u32>> {
+    let line = statement.line;
+    let kinds = operands(statement.opcode);
+    if statement.operands.len() != kinds.len() {
+        return Err(AsmError::OperandCount {
+            line,
+            expected: kinds.len(),
+            found: statement.operands.len(),
+        });
+    }
+
+    // every operand is converted to a u32, registers and bytes fit in its low byte
+    let mut values = [0u32; 3];
+    for (value, (kind, operand)) in values.iter_mut().zip(kinds.iter().zip(&statement.operands)) {
+        *value = match kind {
+            Operand::Register => parse_register(operand, 'R', registers_count, line)? as u32,
+            Operand::FloatRegister => parse_register(operand, 'F', registers_count, line)? as u32,
+            Operand::Indirect => operand
+                .strip_prefix('[')
+                .and_then(|register| register.strip_suffix(']'))
+                .and_then(|register| parse_register(register, 'R', registers_count, line).ok())
+                .ok_or_else(|| AsmError::InvalidRegister {
+                    line,
+                    operand: operand.to_string(),
+                })? as u32,
+            Operand::Immediate | Operand::Address => parse_value(operand, labels, line)?,
+            Operand::Float => operand
+                .parse::<f32>()
+                .map_err(|_| AsmError::InvalidNumber {
+                    line,
+                    operand: operand.to_string(),
+                })?
+                .to_bits(),
+            Operand::Byte => parse_constant(operand, labels)
+                .and_then(|number| u8::try_from(number).ok())
+                .ok_or_else(|| AsmError::InvalidNumber {
+                    line,
+                    operand: operand.to_string(),
+                })? as u32,
+            Operand::Port => parse_constant(operand, labels)
+                .and_then(|number| u16::try_from(number).ok())
+                .ok_or_else(|| AsmError::InvalidNumber {
+                    line,
+                    operand: operand.to_string(),
+                })? as u32,
+        };
+    }
+    let [a, b, c] = values;
+    let (r1, r2, r3) = (a as u8, b as u8, c as u8);
+
+    let instruction = match statement.opcode {
+        OpCode::NOP => Instruction::NOP,
+        OpCode::MOV => Instruction::MOV {
+            dest: r1,
+            value: b as i32,
+        },
+        OpCode::LD => Instruction::LD {
+            dest: r1,
+            address: b,
+        },
+        OpCode::ST => Instruction::ST {
+            src: r1,
+            address: b,
+        },
+        OpCode::AND => Instruction::AND {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::OR => Instruction::OR {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::XOR => Instruction::XOR {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::NOT => Instruction::NOT { dest: r1, reg: r2 },
+        OpCode::CMP => Instruction::CMP { reg1: r1, reg2: r2 },
+        OpCode::ADD => Instruction::ADD {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::SUB => Instruction::SUB {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::ADC => Instruction::ADC {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::SBB => Instruction::SBB {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::MULT => Instruction::MULT {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::DIV => Instruction::DIV {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::MOD => Instruction::MOD {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::FXMUL => Instruction::FXMUL {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::FXDIV => Instruction::FXDIV {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::INC => Instruction::INC { reg: r1 },
+        OpCode::DEC => Instruction::DEC { reg: r1 },
+        OpCode::PUSHREG => Instruction::PUSHREG { reg: r1 },
+        OpCode::POPREG => Instruction::POPREG { reg: r1 },
+        OpCode::JMP => Instruction::JMP { address: a },
+        OpCode::JMPN => Instruction::JMPN { address: a },
+        OpCode::JMPP => Instruction::JMPP { address: a },
+        OpCode::JMPZ => Instruction::JMPZ { address: a },
+        OpCode::CALL => Instruction::CALL { address: a },
+        OpCode::RET => Instruction::RET,
+        OpCode::CLF => Instruction::CLF,
+        OpCode::PUSHF => Instruction::PUSHF,
+        OpCode::POPF => Instruction::POPF,
+        OpCode::HLT => Instruction::HLT,
+        OpCode::RDCNT => Instruction::RDCNT {
+            dest: r1,
+            counter: r2,
+        },
+        OpCode::SYSCALL => Instruction::SYSCALL { number: r1 },
+        OpCode::IN => Instruction::IN {
+            dest: r1,
+            port: b as u16,
+        },
+        OpCode::OUT => Instruction::OUT {
+            port: a as u16,
+            src: r2,
+        },
+        OpCode::EI => Instruction::EI,
+        OpCode::DI => Instruction::DI,
+        OpCode::IRET => Instruction::IRET,
+        OpCode::SHL => Instruction::SHL {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::SHLI => Instruction::SHLI {
+            dest: r1,
+            reg: r2,
+            amount: r3,
+        },
+        OpCode::SHR => Instruction::SHR {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::SHRI => Instruction::SHRI {
+            dest: r1,
+            reg: r2,
+            amount: r3,
+        },
+        OpCode::SAR => Instruction::SAR {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::SARI => Instruction::SARI {
+            dest: r1,
+            reg: r2,
+            amount: r3,
+        },
+        OpCode::ROL => Instruction::ROL {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::ROR => Instruction::ROR {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::MOVR => Instruction::MOVR { dest: r1, src: r2 },
+        OpCode::LDR => Instruction::LDR { dest: r1, reg: r2 },
+        OpCode::STR => Instruction::STR { src: r1, reg: r2 },
+        OpCode::LDB => Instruction::LDB {
+            dest: r1,
+            address: b,
+        },
+        OpCode::LDBU => Instruction::LDBU {
+            dest: r1,
+            address: b,
+        },
+        OpCode::LDH => Instruction::LDH {
+            dest: r1,
+            address: b,
+        },
+        OpCode::LDHU => Instruction::LDHU {
+            dest: r1,
+            address: b,
+        },
+        OpCode::STB => Instruction::STB {
+            src: r1,
+            address: b,
+        },
+        OpCode::STH => Instruction::STH {
+            src: r1,
+            address: b,
+        },
+        OpCode::MULU => Instruction::MULU {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::DIVU => Instruction::DIVU {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::MODU => Instruction::MODU {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::CMPU => Instruction::CMPU { reg1: r1, reg2: r2 },
+        OpCode::JMPB => Instruction::JMPB { address: a },
+        OpCode::JMPBE => Instruction::JMPBE { address: a },
+        OpCode::JMPA => Instruction::JMPA { address: a },
+        OpCode::JMPAE => Instruction::JMPAE { address: a },
+        OpCode::JMPLT => Instruction::JMPLT { address: a },
+        OpCode::JMPLE => Instruction::JMPLE { address: a },
+        OpCode::JMPGT => Instruction::JMPGT { address: a },
+        OpCode::JMPGE => Instruction::JMPGE { address: a },
+        OpCode::JMPC => Instruction::JMPC { address: a },
+        OpCode::JMPNC => Instruction::JMPNC { address: a },
+        OpCode::JMPNZ => Instruction::JMPNZ { address: a },
+        OpCode::JMPO => Instruction::JMPO { address: a },
+        OpCode::CMOVZ => Instruction::CMOVZ { dest: r1, src: r2 },
+        OpCode::CMOVN => Instruction::CMOVN { dest: r1, src: r2 },
+        OpCode::MIN => Instruction::MIN {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::MAX => Instruction::MAX {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::MINU => Instruction::MINU {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::MAXU => Instruction::MAXU {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::POPCNT => Instruction::POPCNT { dest: r1, reg: r2 },
+        OpCode::CLZ => Instruction::CLZ { dest: r1, reg: r2 },
+        OpCode::CTZ => Instruction::CTZ { dest: r1, reg: r2 },
+        OpCode::BT => Instruction::BT { reg1: r1, reg2: r2 },
+        OpCode::BTI => Instruction::BTI { reg: r1, bit: r2 },
+        OpCode::BS => Instruction::BS { reg1: r1, reg2: r2 },
+        OpCode::BSI => Instruction::BSI { reg: r1, bit: r2 },
+        OpCode::BC => Instruction::BC { reg1: r1, reg2: r2 },
+        OpCode::BCI => Instruction::BCI { reg: r1, bit: r2 },
+        OpCode::BTG => Instruction::BTG { reg1: r1, reg2: r2 },
+        OpCode::BTGI => Instruction::BTGI { reg: r1, bit: r2 },
+        OpCode::PUSH => Instruction::PUSH { value: a as i32 },
+        OpCode::EXIT => Instruction::EXIT { code: a as i32 },
+        OpCode::DUP => Instruction::DUP,
+        OpCode::SWAP => Instruction::SWAP,
+        OpCode::DROP => Instruction::DROP,
+        OpCode::PUSHA => Instruction::PUSHA,
+        OpCode::POPA => Instruction::POPA,
+        OpCode::FMOV => Instruction::FMOV { dest: r1, value: b },
+        OpCode::FADD => Instruction::FADD {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::FSUB => Instruction::FSUB {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::FMUL => Instruction::FMUL {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::FDIV => Instruction::FDIV {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::FCMP => Instruction::FCMP { reg1: r1, reg2: r2 },
+        OpCode::ITOF => Instruction::ITOF { dest: r1, src: r2 },
+        OpCode::FTOI => Instruction::FTOI { dest: r1, src: r2 },
+        OpCode::MULH => Instruction::MULH {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::UMULH => Instruction::UMULH {
+            dest: r1,
+            reg1: r2,
+            reg2: r3,
+        },
+        OpCode::CAS => Instruction::CAS {
+            expected: r1,
+            new: r2,
+            reg: r3,
+        },
+        OpCode::XADD => Instruction::XADD { src: r1, reg: r2 },
+        OpCode::SPAWN => Instruction::SPAWN {
+            dest: r1,
+            address: b,
+        },
+        OpCode::YIELD => Instruction::YIELD,
+        OpCode::JOIN => Instruction::JOIN { reg: r1 },
+        OpCode::MMUON => Instruction::MMUON { reg: r1 },
+        OpCode::MMUOFF => Instruction::MMUOFF,
+        OpCode::TLBFLUSH => Instruction::TLBFLUSH,
+        OpCode::RDFAULT => Instruction::RDFAULT { dest: r1 },
+        OpCode::RDSP => Instruction::RDSP { dest: r1 },
+        OpCode::WRSP => Instruction::WRSP { src: r1 },
+        OpCode::ENTER => Instruction::ENTER { size: a },
+        OpCode::LEAVE => Instruction::LEAVE,
+        OpCode::LDF => Instruction::LDF {
+            dest: r1,
+            offset: b as i32,
+        },
+        OpCode::STF => Instruction::STF {
+            src: r1,
+            offset: b as i32,
+        },
+        OpCode::RDFP => Instruction::RDFP { dest: r1 },
+        OpCode::RDCYCLE => Instruction::RDCYCLE { dest: r1 },
+    };
+    Ok(instruction)
+}
+
+/// Record a relocation of a field for the label operands of an object module.
+/// Returns the constants and the labels resolved to `0`, the value of the field before the linking.
+fn relocate<'a>(
+    operands: impl Iterator<Item = &'a str>,
+    constants: &HashMap<&'a str, u32>,
+    section: Section,
+    offset: usize,
+    relocations: &mut Vec<Relocation>,
+) -> HashMap<&'a str, u32> {
+    let mut resolved = constants.clone();
+    for label in operands.filter(|operand| is_label(operand) && !constants.contains_key(operand)) {
+        relocations.push(Relocation {
+            section,
+            offset,
+            symbol: label.to_string(),
+        });
+        resolved.insert(label, 0);
+    }
+    resolved
+}
+
+/// Parse a number or a constant operand
+fn parse_constant(operand: &str, constants: &HashMap<&str, u32>) -> Option<i64> {
+    match constants.get(operand) {
+        Some(value) => Some(*value as i64),
+        None => parse_number(operand),
+    }
+}
+
+/// Parse a register operand such as `R2`, or `F2` for the float registers with `prefix` `F`
+/// Returns the register index if it is within the bounds of the available registers
+fn parse_register(operand: &str, prefix: char, registers_count: u8, line: usize) -> Result<u8> {
+    operand
+        .strip_prefix([prefix, prefix.to_ascii_lowercase()])
+        .and_then(|index| index.parse::<u8>().ok())
+        .filter(|index| *index < registers_count)
+        .ok_or_else(|| AsmError::InvalidRegister {
+            line,
+            operand: operand.to_string(),
+        })
+}
+
+/// Parse a 32-bit immediate or address operand, either a number or a label
+/// Negative numbers are stored in two's complement
+fn parse_value(operand: &str, labels: &HashMap<&str, u32>, line: usize) -> Result<u32> {
+    if is_label(operand) {
+        return labels
+            .get(operand)
+            .copied()
+            .ok_or_else(|| AsmError::UndefinedLabel {
+                line,
+                label: operand.to_string(),
+            });
+    }
+    parse_number(operand)
+        .filter(|number| (i32::MIN as i64..=u32::MAX as i64).contains(number))
+        .map(|number| number as u32)
+        .ok_or_else(|| AsmError::InvalidNumber {
+            line,
+            operand: operand.to_string(),
+        })
+}
+
+/// Parse a decimal, hexadecimal (`0x`) or binary (`0b`) number with an optional sign
+fn parse_number(operand: &str) -> Option<i64> {
+    let (negative, digits) = match operand.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, operand),
+    };
+    let magnitude = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        digits.parse::<i64>()
+    }
+    .ok()?;
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Remove the comment of a line, from the first `;` outside a string
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Parse a double-quoted string operand into its bytes
+/// The escape sequences `\n`, `\t`, `\r`, `\0`, `\\` and `\"` are supported
+fn parse_string(operand: &str) -> Option<Vec<u8>> {
+    let content = operand.strip_prefix('"')?.strip_suffix('"')?;
+    let mut bytes = Vec::with_capacity(content.len());
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                '\\' => '\\',
+                '"' => '"',
+                _ => return None,
+            },
+            '"' => return None,
+            c => c,
+        };
+        let mut buffer = [0; 4];
+        bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+    }
+    Some(bytes)
+}
+
+/// Check if a token is a valid label name: a letter or `_` followed by letters, digits or `_`
+fn is_label(token: &str) -> bool {
+    let mut chars = token.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_instructions() {
+        let program = Assembler::new()
+            .assemble("MOV R0 5\nadd r2, r0, r1\nHLT")
+            .unwrap();
+        assert_eq!(
+            program,
+            vec![0x01, 0x00, 0x05, 0x00, 0x00, 0x00, 0x09, 0x02, 0x00, 0x01, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_assemble_numbers() {
+        let program = Assembler::new()
+            .assemble("MOV R0 -1\nMOV R1 0x10\nMOV R2 0b11\nLD R3 0xFFFFFFFF")
+            .unwrap();
+        assert_eq!(
+            program,
+            vec![
+                0x01, 0x00, 0xff, 0xff, 0xff, 0xff, 0x01, 0x01, 0x10, 0x00, 0x00, 0x00, 0x01, 0x02,
+                0x03, 0x00, 0x00, 0x00, 0x02, 0x03, 0xff, 0xff, 0xff, 0xff,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_origin() {
+        let source = "
+            loop: JMP loop
+        ";
+        let program = Assembler::new().origin(0x200).assemble(source).unwrap();
+        assert_eq!(program, [0x12, 0x00, 0x02, 0x00, 0x00]); // JMP 0x200
+    }
+
+    #[test]
+    fn test_assemble_syscall() {
+        let program = Assembler::new().assemble("SYSCALL 0x2a\nHLT").unwrap();
+        assert_eq!(program, vec![0x1a, 0x2a, 0xff]);
+    }
+
+    #[test]
+    fn test_assemble_indirect() {
+        let program = Assembler::new()
+            .assemble("LDR R0 [R1]\nSTR r2, [r3]")
+            .unwrap();
+        assert_eq!(program, vec![0x27, 0x00, 0x01, 0x28, 0x02, 0x03]);
+        assert_eq!(
+            Assembler::new().assemble("LDR R0 R1"),
+            Err(AsmError::InvalidRegister {
+                line: 1,
+                operand: "R1".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_unknown_mnemonic() {
+        assert_eq!(
+            Assembler::new().assemble("NOP\nFOO R0"),
+            Err(AsmError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "FOO".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_operand_count() {
+        assert_eq!(
+            Assembler::new().assemble("ADD R0 R1"),
+            Err(AsmError::OperandCount {
+                line: 1,
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_invalid_register() {
+        assert_eq!(
+            Assembler::new().assemble("INC R4"),
+            Err(AsmError::InvalidRegister {
+                line: 1,
+                operand: "R4".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_with_registers() {
+        assert_eq!(
+            Assembler::with_registers(8).assemble("INC R7"),
+            Ok(vec![0x0e, 0x07])
+        );
+    }
+
+    #[test]
+    fn test_assemble_invalid_number() {
+        assert_eq!(
+            Assembler::new().assemble("MOV R0 0x100000000"),
+            Err(AsmError::InvalidNumber {
+                line: 1,
+                operand: "0x100000000".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble("OUT 0x10000 R0"),
+            Err(AsmError::InvalidNumber {
+                line: 1,
+                operand: "0x10000".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_label_errors() {
+        assert_eq!(
+            Assembler::new().assemble("JMP nowhere"),
+            Err(AsmError::UndefinedLabel {
+                line: 1,
+                label: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble("a: NOP\na: NOP"),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble("1a: NOP"),
+            Err(AsmError::InvalidLabel {
+                line: 1,
+                label: "1a".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_program_sections() {
+        let source = "
+                LD R0 table
+                MOV R1 counter
+                HLT
+            .data
+            table: .word 7, end
+            bytes: .byte 1, -1
+                   .space 2
+            .bss
+            counter: .space 4
+            .text
+            end: HLT
+        ";
+        let program = Assembler::new().assemble_program(source).unwrap();
+        // the data follows the 14 bytes of code, aligned to 4 bytes
+        assert_eq!(program.data_address(), 0x10);
+        assert_eq!(program.data(), [7, 0, 0, 0, 0x0d, 0, 0, 0, 1, 0xff, 0, 0]);
+        assert_eq!(program.bss_size(), 4);
+        // MOV R1 0x1c, the BSS follows the 12 bytes of data
+        assert_eq!(
+            &program.code()[6..12],
+            &[0x01, 0x01, 0x1c, 0x00, 0x00, 0x00]
+        );
+
+        let program = Assembler::new()
+            .origin(0x100)
+            .data_origin(0x400)
+            .assemble_program(".data\nvalue: .word value")
+            .unwrap();
+        assert_eq!(program.data_address(), 0x400);
+        assert_eq!(program.data(), [0x00, 0x04, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_assemble_section_errors() {
+        assert_eq!(
+            Assembler::new().assemble(".data\n.word 1"),
+            Err(AsmError::DataSection { line: 2 })
+        );
+        assert_eq!(
+            Assembler::new().assemble_program(".data\nNOP"),
+            Err(AsmError::WrongSection {
+                line: 2,
+                statement: "NOP".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble_program(".bss\n.byte 1"),
+            Err(AsmError::WrongSection {
+                line: 2,
+                statement: ".byte".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble_program(".rodata"),
+            Err(AsmError::UnknownDirective {
+                line: 1,
+                directive: ".rodata".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble_program(".data\n.byte 256"),
+            Err(AsmError::InvalidNumber {
+                line: 2,
+                operand: "256".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble_program(".bss\n.space"),
+            Err(AsmError::OperandCount {
+                line: 2,
+                expected: 1,
+                found: 0
+            })
+        );
+        // an empty data section is accepted by `assemble`
+        assert_eq!(
+            Assembler::new().assemble(".data\n.text\nHLT"),
+            Ok(vec![0xff])
+        );
+    }
+
+    #[test]
+    fn test_assemble_with_debug_info() {
+        let source = "
+            main:
+                MOV R0 3
+            loop:
+                DEC R0 ; counter
+                JMPNZ loop
+                HLT
+            .data
+            value: .word 1
+        ";
+        let (program, debug_info) = Assembler::new()
+            .origin(0x100)
+            .assemble_with_debug_info(source)
+            .unwrap();
+        assert_eq!(debug_info.symbol("main"), Some(0x100));
+        assert_eq!(debug_info.symbol("loop"), Some(0x106));
+        assert_eq!(debug_info.symbol("value"), Some(program.data_address()));
+        assert_eq!(debug_info.line_at(0x106), Some(5));
+        assert_eq!(debug_info.line_at(0x108), Some(6));
+        assert_eq!(debug_info.describe(0x10d), "in function `loop`, line 7");
+    }
+
+    #[test]
+    fn test_assemble_constants() {
+        let source = "
+            .equ PRINT 0x2a
+            .equ SIZE 8
+            .equ ADDRESS 0x100
+                MOV R0 SIZE
+                ST R0 ADDRESS
+                SYSCALL PRINT
+            .data
+            .byte SIZE
+            .space SIZE
+        ";
+        let program = Assembler::new().assemble_program(source).unwrap();
+        assert_eq!(
+            program.code(),
+            [0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x01, 0x00, 0x00, 0x1a, 0x2a]
+        );
+        assert_eq!(program.data(), [8, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        // constants are resolved in object modules, the labels are relocated
+        let object = Assembler::new()
+            .assemble_object(".equ SIZE 8\nMOV R0 SIZE\nJMP end")
+            .unwrap();
+        assert_eq!(object.relocations.len(), 1);
+        assert_eq!(&object.code[2..6], &[0x08, 0x00, 0x00, 0x00]);
+
+        assert_eq!(
+            Assembler::new().assemble(".equ A 1\nA: NOP"),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "A".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_strings() {
+        let source = r#"
+            .data
+            hello: .ascii "Hi; there: \"you\""  ; a comment
+            .asciz "\n"
+        "#;
+        let program = Assembler::new().assemble_program(source).unwrap();
+        assert_eq!(program.data(), b"Hi; there: \"you\"\n\0");
+        assert_eq!(
+            Assembler::new().assemble_program(".data\n.ascii \"\\q\""),
+            Err(AsmError::InvalidString {
+                line: 2,
+                operand: "\"\\q\"".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble_program(".data\n.ascii hello"),
+            Err(AsmError::InvalidString {
+                line: 2,
+                operand: "hello".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_org() {
+        let source = "
+                JMP main
+            .org 0x108
+            main:
+                HLT
+        ";
+        let program = Assembler::new().origin(0x100).assemble(source).unwrap();
+        assert_eq!(program.len(), 9);
+        assert_eq!(&program[..5], &[0x12, 0x08, 0x01, 0x00, 0x00]); // JMP 0x108
+        assert_eq!(&program[5..], &[0x00, 0x00, 0x00, 0xff]);
+
+        // a trailing `.org` pads the end of the code
+        assert_eq!(
+            Assembler::new().assemble("HLT\n.org 4"),
+            Ok(vec![0xff, 0, 0, 0])
+        );
+
+        assert_eq!(
+            Assembler::new().assemble("JMP 0\n.org 2"),
+            Err(AsmError::InvalidOrg {
+                line: 2,
+                operand: "2".to_string()
+            })
+        );
+        assert_eq!(
+            Assembler::new().assemble(".data\n.org 2"),
+            Err(AsmError::WrongSection {
+                line: 2,
+                statement: ".org".to_string()
+            })
+        );
+    }
+}
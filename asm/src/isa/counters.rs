@@ -0,0 +1,134 @@
+use super::error::{Result, VmError};
+use super::word::Word;
+
+/// Identifiers of the performance counters the guest can query with `RDCNT`.
+/// The numeric value of each variant is the counter id encoded in the instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Counter {
+    /// Number of instructions executed since the program started, including `RDCNT` itself.
+    Steps = 0x0,
+    /// Highest number of values held by the stack since the program started.
+    StackHighWater = 0x1,
+    /// Number of steps the program may still execute before hitting its step limit.
+    FuelRemaining = 0x2,
+    /// Cycles consumed since the program started under the cost model of the VM, including the
+    /// reading instruction itself.
+    Cycles = 0x3,
+    /// Number of interrupts delivered to their handler since the program started, the traps
+    /// excluded.
+    InterruptsDelivered = 0x4,
+}
+
+impl TryFrom<u8> for Counter {
+    type Error = VmError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0x0 => Ok(Counter::Steps),
+            0x1 => Ok(Counter::StackHighWater),
+            0x2 => Ok(Counter::FuelRemaining),
+            0x3 => Ok(Counter::Cycles),
+            0x4 => Ok(Counter::InterruptsDelivered),
+            _ => Err(VmError::InvalidCounter { counter: value }),
+        }
+    }
+}
+
+impl From<Counter> for u8 {
+    fn from(counter: Counter) -> u8 {
+        counter as u8
+    }
+}
+
+/// Snapshot of the performance counters of a running program.
+/// The VM refreshes the snapshot before each instruction so that the guest
+/// always observes up-to-date values.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PerfCounters {
+    /// Number of instructions executed so far.
+    pub steps: u128,
+    /// Number of steps left before the step limit, `u128::MAX` minus the steps when unlimited.
+    pub fuel_remaining: u128,
+    /// Highest number of values held by the stack so far.
+    pub stack_high_water: usize,
+    /// Number of cycles consumed so far, see `VmBuilder::cost_model`.
+    pub cycles: u128,
+    /// Number of interrupts delivered so far.
+    pub interrupts_delivered: u128,
+}
+
+impl PerfCounters {
+    /// Read a counter as a guest register value.
+    /// Values that do not fit in the word saturate to its maximum value.
+    ///
+    /// # Parameters
+    /// - `counter`: The id of the counter to read.
+    ///
+    /// # Errors
+    /// Returns an error if the counter id is unknown.
+    pub fn read<T: Word>(&self, counter: u8) -> Result<T> {
+        Ok(T::saturating_from_u128(self.value(counter)?))
+    }
+
+    /// Get the full value of a counter.
+    fn value(&self, counter: u8) -> Result<u128> {
+        let value = match Counter::try_from(counter)? {
+            Counter::Steps => self.steps,
+            Counter::StackHighWater => self.stack_high_water as u128,
+            Counter::FuelRemaining => self.fuel_remaining,
+            Counter::Cycles => self.cycles,
+            Counter::InterruptsDelivered => self.interrupts_delivered,
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_from_u8() {
+        assert_eq!(Counter::try_from(0x0), Ok(Counter::Steps));
+        assert_eq!(Counter::try_from(0x1), Ok(Counter::StackHighWater));
+        assert_eq!(Counter::try_from(0x2), Ok(Counter::FuelRemaining));
+        assert_eq!(Counter::try_from(0x3), Ok(Counter::Cycles));
+        assert_eq!(Counter::try_from(0x4), Ok(Counter::InterruptsDelivered));
+        assert_eq!(
+            Counter::try_from(0x5),
+            Err(VmError::InvalidCounter { counter: 0x5 })
+        );
+    }
+
+    #[test]
+    fn test_counters_read() {
+        let counters = PerfCounters {
+            steps: 12,
+            fuel_remaining: 88,
+            stack_high_water: 3,
+            cycles: 40,
+            interrupts_delivered: 2,
+        };
+        assert_eq!(counters.read::<i32>(Counter::Steps.into()), Ok(12));
+        assert_eq!(counters.read::<i32>(Counter::StackHighWater.into()), Ok(3));
+        assert_eq!(counters.read::<i32>(Counter::FuelRemaining.into()), Ok(88));
+        assert_eq!(counters.read::<i32>(Counter::Cycles.into()), Ok(40));
+        assert_eq!(
+            counters.read::<i32>(Counter::InterruptsDelivered.into()),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_counters_read_saturates() {
+        let counters = PerfCounters {
+            steps: u128::MAX,
+            fuel_remaining: 0,
+            stack_high_water: 0,
+            cycles: 0,
+            interrupts_delivered: 0,
+        };
+        assert_eq!(counters.read::<i32>(Counter::Steps.into()), Ok(i32::MAX));
+        assert_eq!(counters.read::<i64>(Counter::Steps.into()), Ok(i64::MAX));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use super::error::{Result, VmError};
+
+/// Debug information of a program: the addresses of its symbols and the source line of its
+/// instructions, emitted by `Assembler::assemble_with_debug_info`.
+///
+/// The VM and the disassembler use it to describe an address in terms of the source, e.g.
+/// ``in function `loop`, line 12`` for the program counter of a fault. The function of an
+/// address is the nearest symbol at or before it.
+///
+/// The debug information is stored in a sidecar text file, next to the program, with one entry
+/// per line: `symbol <name> <address>` or `line <address> <line>`, with hexadecimal addresses.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::debug_info::DebugInfo;
+/// let mut debug_info = DebugInfo::new();
+/// debug_info.add_symbol("main", 0x0);
+/// debug_info.add_symbol("loop", 0x6);
+/// debug_info.add_line(0x6, 3);
+/// debug_info.add_line(0x8, 4);
+/// assert_eq!(debug_info.describe(0x8), "in function `loop`, line 4");
+/// assert_eq!(DebugInfo::parse(&debug_info.to_text()), Ok(debug_info));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// The address of each symbol, by name.
+    symbols: BTreeMap<String, usize>,
+    /// The source line of each instruction, by address.
+    lines: BTreeMap<usize, usize>,
+}
+
+impl DebugInfo {
+    /// Create empty debug information.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a symbol, replacing any symbol with the same name.
+    pub fn add_symbol(&mut self, name: &str, address: usize) {
+        self.symbols.insert(name.to_string(), address);
+    }
+
+    /// Add the source line of the instruction at an address.
+    pub fn add_line(&mut self, address: usize, line: usize) {
+        self.lines.insert(address, line);
+    }
+
+    /// Get the address of a symbol.
+    pub fn symbol(&self, name: &str) -> Option<usize> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Get the symbols defined at an address, in alphabetical order.
+    pub fn symbols_at(&self, address: usize) -> impl Iterator<Item = &str> {
+        self.symbols
+            .iter()
+            .filter(move |(_, symbol)| **symbol == address)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Get the function of an address, the nearest symbol at or before it, with its address.
+    pub fn function_at(&self, address: usize) -> Option<(&str, usize)> {
+        self.symbols
+            .iter()
+            .filter(|(_, symbol)| **symbol <= address)
+            .max_by_key(|(_, symbol)| **symbol)
+            .map(|(name, symbol)| (name.as_str(), *symbol))
+    }
+
+    /// Get the source line of the instruction at or containing an address.
+    pub fn line_at(&self, address: usize) -> Option<usize> {
+        self.lines
+            .range(..=address)
+            .next_back()
+            .map(|(_, line)| *line)
+    }
+
+    /// Get the instructions with their source line, by increasing address.
+    pub fn lines(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.lines.iter().map(|(address, line)| (*address, *line))
+    }
+
+    /// Get the address of the first instruction of a source line.
+    pub fn address_of_line(&self, line: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .find(|(_, instruction)| **instruction == line)
+            .map(|(address, _)| *address)
+    }
+
+    /// Describe an address with its function and its source line, e.g.
+    /// ``in function `loop`, line 12``, or with the address when neither is known.
+    pub fn describe(&self, address: usize) -> String {
+        match (self.function_at(address), self.line_at(address)) {
+            (Some((function, _)), Some(line)) => {
+                format!("in function `{}`, line {}", function, line)
+            }
+            (Some((function, _)), None) => format!("in function `{}`", function),
+            (None, Some(line)) => format!("line {}", line),
+            (None, None) => format!("at address 0x{:x}", address),
+        }
+    }
+
+    /// Write the debug information in the sidecar text format.
+    pub fn to_text(&self) -> String {
+        let symbols = self
+            .symbols
+            .iter()
+            .map(|(name, address)| format!("symbol {} 0x{:x}\n", name, address));
+        let lines = self
+            .lines
+            .iter()
+            .map(|(address, line)| format!("line 0x{:x} {}\n", address, line));
+        symbols.chain(lines).collect()
+    }
+
+    /// Parse debug information in the sidecar text format, the empty lines are ignored.
+    ///
+    /// # Errors
+    /// Returns `VmError::Other` describing the first invalid line.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut debug_info = Self::new();
+        for (index, entry) in text.lines().enumerate() {
+            let fields: Vec<_> = entry.split_whitespace().collect();
+            let address = |field: &str| {
+                field
+                    .strip_prefix("0x")
+                    .and_then(|digits| usize::from_str_radix(digits, 16).ok())
+            };
+            let parsed = match fields[..] {
+                [] => Some(()),
+                ["symbol", name, symbol] => {
+                    address(symbol).map(|symbol| debug_info.add_symbol(name, symbol))
+                }
+                ["line", instruction, line] => address(instruction)
+                    .zip(line.parse().ok())
+                    .map(|(instruction, line)| debug_info.add_line(instruction, line)),
+                _ => None,
+            };
+            if parsed.is_none() {
+                return Err(VmError::Other(format!(
+                    "Invalid debug info at line {}: {}",
+                    index + 1,
+                    entry
+                )));
+            }
+        }
+        Ok(debug_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_info_lookup() {
+        let mut debug_info = DebugInfo::new();
+        debug_info.add_symbol("start", 0x10);
+        debug_info.add_symbol("entry", 0x10);
+        debug_info.add_symbol("end", 0x20);
+        debug_info.add_line(0x10, 1);
+        debug_info.add_line(0x16, 2);
+
+        assert_eq!(debug_info.symbol("end"), Some(0x20));
+        assert_eq!(
+            debug_info.symbols_at(0x10).collect::<Vec<_>>(),
+            ["entry", "start"]
+        );
+        assert_eq!(debug_info.function_at(0x1f), Some(("start", 0x10)));
+        assert_eq!(debug_info.function_at(0xf), None);
+        assert_eq!(debug_info.line_at(0x18), Some(2));
+        assert_eq!(debug_info.address_of_line(2), Some(0x16));
+        assert_eq!(debug_info.address_of_line(3), None);
+        assert_eq!(debug_info.describe(0x20), "in function `end`, line 2");
+        assert_eq!(debug_info.describe(0x4), "at address 0x4");
+    }
+
+    #[test]
+    fn test_debug_info_text() {
+        let mut debug_info = DebugInfo::new();
+        debug_info.add_symbol("main", 0x100);
+        debug_info.add_line(0x100, 7);
+        assert_eq!(debug_info.to_text(), "symbol main 0x100\nline 0x100 7\n");
+        assert_eq!(DebugInfo::parse(&debug_info.to_text()), Ok(debug_info));
+        assert_eq!(
+            DebugInfo::parse("\nline 0x10"),
+            Err(VmError::Other(
+                "Invalid debug info at line 2: line 0x10".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,728 @@
+use std::marker::PhantomData;
+
+use super::counters::Counter;
+use super::error::{Result as VmResult, VmError};
+use super::hardware_config::REGISTERS_COUNT;
+use super::instructions::{Instruction, OpCode};
+use super::pod::Endianness;
+use super::program::Program;
+use super::word::Word;
+
+/// The decoder of the bytecode.
+/// The decoder is generic over the data type of the architecture, which sets the size
+/// of the immediate values and addresses in the bytecode.
+pub struct Decoder<T> {
+    architecture: PhantomData<T>,
+    /// The number of registers of the CPU, register operands must be lower.
+    registers_count: u8,
+    /// The order of the bytes of the immediate values and addresses.
+    endianness: Endianness,
+}
+
+/// implementation of the Decoder, generic over the data word of the architecture
+/// The immediate values have the size of the word and the addresses the size of its address type.
+/// **Note:** The validation of the registers was done in the `register_address` method
+/// against the number of registers of the decoder, all the register operands are validated there
+impl<T: Word> Decoder<T> {
+    pub fn new() -> Self {
+        Self::with_registers(REGISTERS_COUNT)
+    }
+
+    /// Create a decoder for a CPU with a specific number of registers.
+    ///
+    /// # Parameters
+    /// - `registers_count`: The number of registers of the CPU.
+    pub fn with_registers(registers_count: u8) -> Self {
+        Self {
+            architecture: PhantomData,
+            registers_count,
+            endianness: Endianness::Little,
+        }
+    }
+
+    /// Set the order of the bytes of the immediate values and addresses, little-endian by
+    /// default.
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    pub fn decode_next_instruction(
+        &self,
+        program: &Program,
+        pc: usize,
+    ) -> VmResult<Instruction<T, T::Address>> {
+        self.decode_instruction(program.slice_from(pc))
+    }
+
+    /// Decode the instruction at the start of a slice of bytecode.
+    /// Used to fetch the instructions from the memory in the Von Neumann mode of the VM.
+    ///
+    /// # Parameters
+    /// - `program_slice`: The bytecode from the address of the instruction.
+    ///
+    /// # Errors
+    /// Returns `VmError::InvalidInstruction` if the slice is too short for the instruction,
+    /// or the error of an invalid opcode or register.
+    pub fn decode_instruction(&self, program_slice: &[u8]) -> VmResult<Instruction<T, T::Address>> {
+        // check if the program slice is empty and contains at least the opcode
+        if program_slice.is_empty() {
+            return Err(VmError::InvalidInstruction);
+        }
+
+        // convert the first byte of the program slice to an OpCode
+        let opcode: OpCode = program_slice[0].try_into()?;
+
+        let instruction_len = opcode.size::<T, T::Address>();
+
+        // check if the program slice is long enough to contain the instruction
+        if program_slice.len() < instruction_len {
+            return Err(VmError::InvalidInstruction);
+        }
+
+        match opcode {
+            OpCode::NOP => Ok(Instruction::<T, T::Address>::NOP),
+            OpCode::MOV => {
+                let dest = self.register_address(program_slice[1])?;
+                let value = self.read_word::<T>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::MOV { dest, value })
+            }
+            OpCode::LD => {
+                let dest = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::LD { dest, address })
+            }
+            OpCode::ST => {
+                let src = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::ST { src, address })
+            }
+            OpCode::AND => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::AND { dest, reg1, reg2 })
+            }
+            OpCode::OR => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::OR { dest, reg1, reg2 })
+            }
+            OpCode::XOR => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::XOR { dest, reg1, reg2 })
+            }
+            OpCode::NOT => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::NOT { dest, reg })
+            }
+            OpCode::CMP => {
+                let reg1 = self.register_address(program_slice[1])?;
+                let reg2 = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::CMP { reg1, reg2 })
+            }
+            OpCode::ADD => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::ADD { dest, reg1, reg2 })
+            }
+            OpCode::SUB => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::SUB { dest, reg1, reg2 })
+            }
+            OpCode::ADC => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::ADC { dest, reg1, reg2 })
+            }
+            OpCode::SBB => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::SBB { dest, reg1, reg2 })
+            }
+            OpCode::MULT => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MULT { dest, reg1, reg2 })
+            }
+            OpCode::DIV => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::DIV { dest, reg1, reg2 })
+            }
+            OpCode::MOD => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MOD { dest, reg1, reg2 })
+            }
+            OpCode::FXMUL => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::FXMUL { dest, reg1, reg2 })
+            }
+            OpCode::FXDIV => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::FXDIV { dest, reg1, reg2 })
+            }
+            OpCode::INC => {
+                let reg = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::INC { reg })
+            }
+            OpCode::DEC => {
+                let reg = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::DEC { reg })
+            }
+            OpCode::PUSHREG => {
+                let reg = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::PUSHREG { reg })
+            }
+            OpCode::POPREG => {
+                let reg = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::POPREG { reg })
+            }
+            OpCode::JMP => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMP { address })
+            }
+            OpCode::JMPN => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPN { address })
+            }
+            OpCode::JMPP => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPP { address })
+            }
+            OpCode::JMPZ => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPZ { address })
+            }
+            OpCode::CALL => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::CALL { address })
+            }
+            OpCode::RET => Ok(Instruction::<T, T::Address>::RET),
+            OpCode::CLF => Ok(Instruction::<T, T::Address>::CLF),
+            OpCode::PUSHF => Ok(Instruction::<T, T::Address>::PUSHF),
+            OpCode::POPF => Ok(Instruction::<T, T::Address>::POPF),
+            OpCode::RDCNT => {
+                let dest = self.register_address(program_slice[1])?;
+                let counter = Counter::try_from(program_slice[2])?.into();
+                Ok(Instruction::<T, T::Address>::RDCNT { dest, counter })
+            }
+            OpCode::RDCYCLE => {
+                let dest = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::RDCYCLE { dest })
+            }
+            OpCode::SYSCALL => {
+                let number = program_slice[1];
+                Ok(Instruction::<T, T::Address>::SYSCALL { number })
+            }
+            OpCode::IN => {
+                let dest = self.register_address(program_slice[1])?;
+                let port = self.read_word::<u16>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::IN { dest, port })
+            }
+            OpCode::OUT => {
+                let port = self.read_word::<u16>(program_slice, 1)?;
+                let src = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::OUT { port, src })
+            }
+            OpCode::EI => Ok(Instruction::<T, T::Address>::EI),
+            OpCode::DI => Ok(Instruction::<T, T::Address>::DI),
+            OpCode::IRET => Ok(Instruction::<T, T::Address>::IRET),
+            OpCode::SHL => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::SHL { dest, reg1, reg2 })
+            }
+            OpCode::SHLI => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                let amount = program_slice[3];
+                Ok(Instruction::<T, T::Address>::SHLI { dest, reg, amount })
+            }
+            OpCode::SHR => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::SHR { dest, reg1, reg2 })
+            }
+            OpCode::SHRI => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                let amount = program_slice[3];
+                Ok(Instruction::<T, T::Address>::SHRI { dest, reg, amount })
+            }
+            OpCode::SAR => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::SAR { dest, reg1, reg2 })
+            }
+            OpCode::SARI => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                let amount = program_slice[3];
+                Ok(Instruction::<T, T::Address>::SARI { dest, reg, amount })
+            }
+            OpCode::ROL => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::ROL { dest, reg1, reg2 })
+            }
+            OpCode::ROR => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::ROR { dest, reg1, reg2 })
+            }
+            OpCode::MOVR => {
+                let dest = self.register_address(program_slice[1])?;
+                let src = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::MOVR { dest, src })
+            }
+            OpCode::LDR => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::LDR { dest, reg })
+            }
+            OpCode::STR => {
+                let src = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::STR { src, reg })
+            }
+            OpCode::LDB => {
+                let dest = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::LDB { dest, address })
+            }
+            OpCode::LDBU => {
+                let dest = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::LDBU { dest, address })
+            }
+            OpCode::LDH => {
+                let dest = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::LDH { dest, address })
+            }
+            OpCode::LDHU => {
+                let dest = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::LDHU { dest, address })
+            }
+            OpCode::STB => {
+                let src = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::STB { src, address })
+            }
+            OpCode::STH => {
+                let src = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::STH { src, address })
+            }
+            OpCode::MULU => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MULU { dest, reg1, reg2 })
+            }
+            OpCode::DIVU => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::DIVU { dest, reg1, reg2 })
+            }
+            OpCode::MODU => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MODU { dest, reg1, reg2 })
+            }
+            OpCode::CMPU => {
+                let reg1 = self.register_address(program_slice[1])?;
+                let reg2 = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::CMPU { reg1, reg2 })
+            }
+            OpCode::JMPB => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPB { address })
+            }
+            OpCode::JMPBE => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPBE { address })
+            }
+            OpCode::JMPA => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPA { address })
+            }
+            OpCode::JMPAE => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPAE { address })
+            }
+            OpCode::JMPLT => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPLT { address })
+            }
+            OpCode::JMPLE => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPLE { address })
+            }
+            OpCode::JMPGT => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPGT { address })
+            }
+            OpCode::JMPGE => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPGE { address })
+            }
+            OpCode::JMPC => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPC { address })
+            }
+            OpCode::JMPNC => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPNC { address })
+            }
+            OpCode::JMPNZ => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPNZ { address })
+            }
+            OpCode::JMPO => {
+                let address = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::JMPO { address })
+            }
+            OpCode::CMOVZ => {
+                let dest = self.register_address(program_slice[1])?;
+                let src = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::CMOVZ { dest, src })
+            }
+            OpCode::CMOVN => {
+                let dest = self.register_address(program_slice[1])?;
+                let src = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::CMOVN { dest, src })
+            }
+            OpCode::MIN => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MIN { dest, reg1, reg2 })
+            }
+            OpCode::MAX => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MAX { dest, reg1, reg2 })
+            }
+            OpCode::MINU => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MINU { dest, reg1, reg2 })
+            }
+            OpCode::MAXU => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MAXU { dest, reg1, reg2 })
+            }
+            OpCode::POPCNT => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::POPCNT { dest, reg })
+            }
+            OpCode::CLZ => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::CLZ { dest, reg })
+            }
+            OpCode::CTZ => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::CTZ { dest, reg })
+            }
+            OpCode::BT => {
+                let reg1 = self.register_address(program_slice[1])?;
+                let reg2 = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::BT { reg1, reg2 })
+            }
+            OpCode::BTI => {
+                let reg = self.register_address(program_slice[1])?;
+                let bit = program_slice[2];
+                Ok(Instruction::<T, T::Address>::BTI { reg, bit })
+            }
+            OpCode::BS => {
+                let reg1 = self.register_address(program_slice[1])?;
+                let reg2 = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::BS { reg1, reg2 })
+            }
+            OpCode::BSI => {
+                let reg = self.register_address(program_slice[1])?;
+                let bit = program_slice[2];
+                Ok(Instruction::<T, T::Address>::BSI { reg, bit })
+            }
+            OpCode::BC => {
+                let reg1 = self.register_address(program_slice[1])?;
+                let reg2 = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::BC { reg1, reg2 })
+            }
+            OpCode::BCI => {
+                let reg = self.register_address(program_slice[1])?;
+                let bit = program_slice[2];
+                Ok(Instruction::<T, T::Address>::BCI { reg, bit })
+            }
+            OpCode::BTG => {
+                let reg1 = self.register_address(program_slice[1])?;
+                let reg2 = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::BTG { reg1, reg2 })
+            }
+            OpCode::BTGI => {
+                let reg = self.register_address(program_slice[1])?;
+                let bit = program_slice[2];
+                Ok(Instruction::<T, T::Address>::BTGI { reg, bit })
+            }
+            OpCode::PUSH => {
+                let value = self.read_word::<T>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::PUSH { value })
+            }
+            OpCode::DUP => Ok(Instruction::<T, T::Address>::DUP),
+            OpCode::SWAP => Ok(Instruction::<T, T::Address>::SWAP),
+            OpCode::DROP => Ok(Instruction::<T, T::Address>::DROP),
+            OpCode::PUSHA => Ok(Instruction::<T, T::Address>::PUSHA),
+            OpCode::POPA => Ok(Instruction::<T, T::Address>::POPA),
+            OpCode::FMOV => {
+                let dest = self.register_address(program_slice[1])?;
+                let value = self.read_word::<u32>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::FMOV { dest, value })
+            }
+            OpCode::FADD => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::FADD { dest, reg1, reg2 })
+            }
+            OpCode::FSUB => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::FSUB { dest, reg1, reg2 })
+            }
+            OpCode::FMUL => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::FMUL { dest, reg1, reg2 })
+            }
+            OpCode::FDIV => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::FDIV { dest, reg1, reg2 })
+            }
+            OpCode::FCMP => {
+                let reg1 = self.register_address(program_slice[1])?;
+                let reg2 = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::FCMP { reg1, reg2 })
+            }
+            OpCode::ITOF => {
+                let dest = self.register_address(program_slice[1])?;
+                let src = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::ITOF { dest, src })
+            }
+            OpCode::FTOI => {
+                let dest = self.register_address(program_slice[1])?;
+                let src = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::FTOI { dest, src })
+            }
+            OpCode::MULH => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::MULH { dest, reg1, reg2 })
+            }
+            OpCode::UMULH => {
+                let dest = self.register_address(program_slice[1])?;
+                let reg1 = self.register_address(program_slice[2])?;
+                let reg2 = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::UMULH { dest, reg1, reg2 })
+            }
+            OpCode::CAS => {
+                let expected = self.register_address(program_slice[1])?;
+                let new = self.register_address(program_slice[2])?;
+                let reg = self.register_address(program_slice[3])?;
+                Ok(Instruction::<T, T::Address>::CAS { expected, new, reg })
+            }
+            OpCode::XADD => {
+                let src = self.register_address(program_slice[1])?;
+                let reg = self.register_address(program_slice[2])?;
+                Ok(Instruction::<T, T::Address>::XADD { src, reg })
+            }
+            OpCode::SPAWN => {
+                let dest = self.register_address(program_slice[1])?;
+                let address = self.read_word::<T::Address>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::SPAWN { dest, address })
+            }
+            OpCode::YIELD => Ok(Instruction::<T, T::Address>::YIELD),
+            OpCode::JOIN => {
+                let reg = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::JOIN { reg })
+            }
+            OpCode::MMUON => {
+                let reg = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::MMUON { reg })
+            }
+            OpCode::MMUOFF => Ok(Instruction::<T, T::Address>::MMUOFF),
+            OpCode::TLBFLUSH => Ok(Instruction::<T, T::Address>::TLBFLUSH),
+            OpCode::RDFAULT => {
+                let dest = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::RDFAULT { dest })
+            }
+            OpCode::RDSP => {
+                let dest = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::RDSP { dest })
+            }
+            OpCode::WRSP => {
+                let src = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::WRSP { src })
+            }
+            OpCode::ENTER => {
+                let size = self.read_word::<T::Address>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::ENTER { size })
+            }
+            OpCode::LEAVE => Ok(Instruction::<T, T::Address>::LEAVE),
+            OpCode::LDF => {
+                let dest = self.register_address(program_slice[1])?;
+                let offset = self.read_word::<T>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::LDF { dest, offset })
+            }
+            OpCode::STF => {
+                let src = self.register_address(program_slice[1])?;
+                let offset = self.read_word::<T>(program_slice, 2)?;
+                Ok(Instruction::<T, T::Address>::STF { src, offset })
+            }
+            OpCode::RDFP => {
+                let dest = self.register_address(program_slice[1])?;
+                Ok(Instruction::<T, T::Address>::RDFP { dest })
+            }
+            OpCode::EXIT => {
+                let code = self.read_word::<T>(program_slice, 1)?;
+                Ok(Instruction::<T, T::Address>::EXIT { code })
+            }
+            OpCode::HLT => Ok(Instruction::<T, T::Address>::HLT),
+        }
+    }
+
+    /// Check if the register is within the bounds of the available registers
+    /// Returns the register if it is valid, otherwise an error
+    /// # Parameters
+    /// - `register`: The register to check
+    fn register_address(&self, register: u8) -> VmResult<u8> {
+        if register >= self.registers_count {
+            return Err(VmError::InvalidRegister { register });
+        }
+        Ok(register)
+    }
+
+    /// Read a word in the endianness of the decoder from a slice of bytes
+    /// the start parameter is the index of the first byte of the word
+    /// the length of the slice must be at least start + W::SIZE
+    fn read_word<W: Word>(&self, data: &[u8], start: usize) -> VmResult<W> {
+        data.get(start..start + W::SIZE)
+            .map(|bytes| W::load(bytes, self.endianness))
+            .ok_or(VmError::InvalidInstruction)
+    }
+}
+
+impl<T: Word> Default for Decoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_word() {
+        let data = [0xf0, 0xde, 0xbc, 0x9a, 0x78, 0x56, 0x34, 0x12];
+        let decoder = Decoder::<i32>::new();
+        assert_eq!(decoder.read_word::<i32>(&data, 4).unwrap(), 0x12345678);
+        assert_eq!(decoder.read_word::<u32>(&data, 4).unwrap(), 0x12345678);
+        assert_eq!(
+            decoder.read_word::<i64>(&data, 0).unwrap(),
+            0x123456789abcdef0
+        );
+        assert_eq!(
+            decoder.read_word::<u64>(&data, 0).unwrap(),
+            0x123456789abcdef0
+        );
+        assert_eq!(decoder.read_word::<u16>(&data, 6).unwrap(), 0x1234);
+        assert!(decoder.read_word::<u32>(&data, 6).is_err());
+
+        let decoder = Decoder::<i32>::new().with_endianness(Endianness::Big);
+        assert_eq!(decoder.read_word::<u32>(&data, 4).unwrap(), 0x78563412);
+        assert_eq!(decoder.read_word::<u16>(&data, 6).unwrap(), 0x3412);
+    }
+
+    #[test]
+    fn test_decode_register_count() {
+        let program = Program::new(&[0x0e, 0x05]); // INC 5
+        assert_eq!(
+            Decoder::<i32>::new().decode_next_instruction(&program, 0),
+            Err(VmError::InvalidRegister { register: 5 })
+        );
+        assert_eq!(
+            Decoder::<i32>::with_registers(8).decode_next_instruction(&program, 0),
+            Ok(Instruction::INC { reg: 5 })
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid_destination_register() {
+        let program = Program::new(&[0x01, 0x04, 0x00, 0x00, 0x00, 0x00]); // MOV 4 0
+        assert_eq!(
+            Decoder::<i32>::new().decode_next_instruction(&program, 0),
+            Err(VmError::InvalidRegister { register: 4 })
+        );
+    }
+
+    #[test]
+    fn test_decode_64_bit_instructions() {
+        let mut bytes = vec![0x01, 0x02]; // MOV 2 -2
+        bytes.extend_from_slice(&(-2i64).to_le_bytes());
+        bytes.push(0x12); // JMP 0x100000000
+        bytes.extend_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        let program = Program::new(&bytes);
+        let decoder = Decoder::<i64>::new();
+
+        let mov = decoder.decode_next_instruction(&program, 0).unwrap();
+        assert_eq!(mov, Instruction::MOV { dest: 2, value: -2 });
+        assert_eq!(mov.size(), 10);
+        let jmp = decoder.decode_next_instruction(&program, 10).unwrap();
+        assert_eq!(
+            jmp,
+            Instruction::JMP {
+                address: 0x1_0000_0000
+            }
+        );
+        assert_eq!(jmp.size(), 9);
+    }
+}
@@ -0,0 +1,383 @@
+use super::instructions::Instruction;
+use super::pod::Endianness;
+use super::word::Word;
+
+pub struct Encoder {
+    /// The order of the bytes of the immediate values and addresses.
+    endianness: Endianness,
+}
+
+/// Implementation of the Encoder, generic over the data word of the architecture
+/// The encoder is the inverse of the `Decoder`: it produces the bytecode of an instruction
+/// in the variable-length format read by `Decoder::decode_next_instruction`, little-endian by
+/// default.
+/// **Note:** The encoder does not validate the registers, the decoder does it at runtime.
+impl Encoder {
+    pub fn new() -> Self {
+        Self {
+            endianness: Endianness::Little,
+        }
+    }
+
+    /// Set the order of the bytes of the immediate values and addresses, see
+    /// `Decoder::with_endianness`.
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Encode an instruction and append its bytes to `output`.
+    ///
+    /// # Parameters
+    /// - `instruction`: The instruction to encode.
+    /// - `output`: The buffer the encoded bytes are appended to.
+    pub fn encode_instruction<D: Word, A: Word>(
+        &self,
+        instruction: &Instruction<D, A>,
+        output: &mut Vec<u8>,
+    ) {
+        output.push(instruction.opcode().into());
+        match *instruction {
+            Instruction::NOP
+            | Instruction::RET
+            | Instruction::CLF
+            | Instruction::PUSHF
+            | Instruction::POPF
+            | Instruction::EI
+            | Instruction::DI
+            | Instruction::IRET
+            | Instruction::DUP
+            | Instruction::SWAP
+            | Instruction::DROP
+            | Instruction::PUSHA
+            | Instruction::POPA
+            | Instruction::YIELD
+            | Instruction::LEAVE
+            | Instruction::MMUOFF
+            | Instruction::TLBFLUSH
+            | Instruction::HLT => {}
+            Instruction::MOV { dest, value }
+            | Instruction::LDF {
+                dest,
+                offset: value,
+            }
+            | Instruction::STF {
+                src: dest,
+                offset: value,
+            } => {
+                output.push(dest);
+                self.write_word(value, output);
+            }
+            Instruction::LD { dest, address }
+            | Instruction::LDB { dest, address }
+            | Instruction::LDBU { dest, address }
+            | Instruction::LDH { dest, address }
+            | Instruction::LDHU { dest, address }
+            | Instruction::SPAWN { dest, address } => {
+                output.push(dest);
+                self.write_word(address, output);
+            }
+            Instruction::ST { src, address }
+            | Instruction::STB { src, address }
+            | Instruction::STH { src, address } => {
+                output.push(src);
+                self.write_word(address, output);
+            }
+            Instruction::AND { dest, reg1, reg2 }
+            | Instruction::OR { dest, reg1, reg2 }
+            | Instruction::XOR { dest, reg1, reg2 }
+            | Instruction::ADD { dest, reg1, reg2 }
+            | Instruction::SUB { dest, reg1, reg2 }
+            | Instruction::ADC { dest, reg1, reg2 }
+            | Instruction::SBB { dest, reg1, reg2 }
+            | Instruction::MULT { dest, reg1, reg2 }
+            | Instruction::DIV { dest, reg1, reg2 }
+            | Instruction::MOD { dest, reg1, reg2 }
+            | Instruction::FXMUL { dest, reg1, reg2 }
+            | Instruction::FXDIV { dest, reg1, reg2 }
+            | Instruction::SHL { dest, reg1, reg2 }
+            | Instruction::SHR { dest, reg1, reg2 }
+            | Instruction::SAR { dest, reg1, reg2 }
+            | Instruction::ROL { dest, reg1, reg2 }
+            | Instruction::ROR { dest, reg1, reg2 }
+            | Instruction::MULU { dest, reg1, reg2 }
+            | Instruction::DIVU { dest, reg1, reg2 }
+            | Instruction::MODU { dest, reg1, reg2 }
+            | Instruction::MIN { dest, reg1, reg2 }
+            | Instruction::MAX { dest, reg1, reg2 }
+            | Instruction::MINU { dest, reg1, reg2 }
+            | Instruction::MAXU { dest, reg1, reg2 }
+            | Instruction::MULH { dest, reg1, reg2 }
+            | Instruction::UMULH { dest, reg1, reg2 }
+            | Instruction::FADD { dest, reg1, reg2 }
+            | Instruction::FSUB { dest, reg1, reg2 }
+            | Instruction::FMUL { dest, reg1, reg2 }
+            | Instruction::FDIV { dest, reg1, reg2 } => {
+                output.extend_from_slice(&[dest, reg1, reg2]);
+            }
+            Instruction::SHLI { dest, reg, amount }
+            | Instruction::SHRI { dest, reg, amount }
+            | Instruction::SARI { dest, reg, amount } => {
+                output.extend_from_slice(&[dest, reg, amount]);
+            }
+            Instruction::NOT { dest, reg }
+            | Instruction::POPCNT { dest, reg }
+            | Instruction::CLZ { dest, reg }
+            | Instruction::CTZ { dest, reg } => {
+                output.extend_from_slice(&[dest, reg]);
+            }
+            Instruction::CMP { reg1, reg2 }
+            | Instruction::CMPU { reg1, reg2 }
+            | Instruction::BT { reg1, reg2 }
+            | Instruction::BS { reg1, reg2 }
+            | Instruction::BC { reg1, reg2 }
+            | Instruction::BTG { reg1, reg2 }
+            | Instruction::FCMP { reg1, reg2 } => {
+                output.extend_from_slice(&[reg1, reg2]);
+            }
+            Instruction::BTI { reg, bit }
+            | Instruction::BSI { reg, bit }
+            | Instruction::BCI { reg, bit }
+            | Instruction::BTGI { reg, bit } => {
+                output.extend_from_slice(&[reg, bit]);
+            }
+            Instruction::INC { reg }
+            | Instruction::DEC { reg }
+            | Instruction::PUSHREG { reg }
+            | Instruction::POPREG { reg }
+            | Instruction::JOIN { reg }
+            | Instruction::MMUON { reg }
+            | Instruction::RDFAULT { dest: reg }
+            | Instruction::RDSP { dest: reg }
+            | Instruction::WRSP { src: reg }
+            | Instruction::RDFP { dest: reg }
+            | Instruction::RDCYCLE { dest: reg } => {
+                output.push(reg);
+            }
+            Instruction::JMP { address }
+            | Instruction::JMPN { address }
+            | Instruction::JMPP { address }
+            | Instruction::JMPZ { address }
+            | Instruction::JMPB { address }
+            | Instruction::JMPBE { address }
+            | Instruction::JMPA { address }
+            | Instruction::JMPAE { address }
+            | Instruction::JMPLT { address }
+            | Instruction::JMPLE { address }
+            | Instruction::JMPGT { address }
+            | Instruction::JMPGE { address }
+            | Instruction::JMPC { address }
+            | Instruction::JMPNC { address }
+            | Instruction::JMPNZ { address }
+            | Instruction::JMPO { address }
+            | Instruction::CALL { address }
+            | Instruction::ENTER { size: address } => {
+                self.write_word(address, output);
+            }
+            Instruction::RDCNT { dest, counter } => {
+                output.extend_from_slice(&[dest, counter]);
+            }
+            Instruction::SYSCALL { number } => {
+                output.push(number);
+            }
+            Instruction::IN { dest, port } => {
+                output.push(dest);
+                self.write_word(port, output);
+            }
+            Instruction::OUT { port, src } => {
+                self.write_word(port, output);
+                output.push(src);
+            }
+            Instruction::MOVR { dest, src }
+            | Instruction::CMOVZ { dest, src }
+            | Instruction::CMOVN { dest, src }
+            | Instruction::ITOF { dest, src }
+            | Instruction::FTOI { dest, src } => {
+                output.extend_from_slice(&[dest, src]);
+            }
+            Instruction::LDR { dest, reg } => {
+                output.extend_from_slice(&[dest, reg]);
+            }
+            Instruction::STR { src, reg } | Instruction::XADD { src, reg } => {
+                output.extend_from_slice(&[src, reg]);
+            }
+            Instruction::CAS { expected, new, reg } => {
+                output.extend_from_slice(&[expected, new, reg]);
+            }
+            Instruction::PUSH { value } | Instruction::EXIT { code: value } => {
+                self.write_word(value, output);
+            }
+            Instruction::FMOV { dest, value } => {
+                output.push(dest);
+                self.write_word(value, output);
+            }
+        }
+    }
+
+    /// Encode the instructions of a program into its bytecode, one after the other.
+    ///
+    /// # Parameters
+    /// - `instructions`: The instructions of the program, in order.
+    pub fn encode_program<D: Word, A: Word>(&self, instructions: &[Instruction<D, A>]) -> Vec<u8> {
+        let mut bytecode = Vec::new();
+        for instruction in instructions {
+            self.encode_instruction(instruction, &mut bytecode);
+        }
+        bytecode
+    }
+
+    /// Append the bytes of a word in the endianness of the encoder to `output`.
+    fn write_word<W: Word>(&self, word: W, output: &mut Vec<u8>) {
+        let start = output.len();
+        output.resize(start + W::SIZE, 0);
+        word.store(&mut output[start..], self.endianness);
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isa::decoder::Decoder;
+    use crate::isa::program::Program;
+
+    #[test]
+    fn test_encode_mov() {
+        let mut output = Vec::new();
+        Encoder::new().encode_instruction(
+            &Instruction::<i32, u32>::MOV {
+                dest: 1,
+                value: 0x12345678,
+            },
+            &mut output,
+        );
+        assert_eq!(output, vec![0x01, 0x01, 0x78, 0x56, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_encode_program() {
+        let instructions = [
+            Instruction::<i32, u32>::MOV { dest: 0, value: 7 },
+            Instruction::JMP { address: 0 },
+            Instruction::HLT,
+        ];
+        assert_eq!(
+            Encoder::new().encode_program(&instructions),
+            vec![0x01, 0x00, 0x07, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, 0x00, 0xff]
+        );
+        assert_eq!(
+            Encoder::new().encode_program::<i32, u32>(&[]),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn test_encode_16_bit_jmp() {
+        let mut output = Vec::new();
+        Encoder::new().encode_instruction(
+            &Instruction::<i16, u16>::JMP { address: 0x1234 },
+            &mut output,
+        );
+        assert_eq!(output, vec![0x12, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let instructions = [
+            Instruction::NOP,
+            Instruction::LD {
+                dest: 2,
+                address: 0x400,
+            },
+            Instruction::ADD {
+                dest: 0,
+                reg1: 1,
+                reg2: 2,
+            },
+            Instruction::NOT { dest: 3, reg: 0 },
+            Instruction::FMOV {
+                dest: 1,
+                value: 1.5f32.to_bits(),
+            },
+            Instruction::JMPZ { address: 42 },
+            Instruction::LDHU {
+                dest: 1,
+                address: 0x10,
+            },
+            Instruction::STB {
+                src: 3,
+                address: 0x11,
+            },
+            Instruction::SAR {
+                dest: 1,
+                reg1: 2,
+                reg2: 3,
+            },
+            Instruction::SHLI {
+                dest: 0,
+                reg: 1,
+                amount: 31,
+            },
+            Instruction::RDCNT {
+                dest: 0,
+                counter: 1,
+            },
+            Instruction::RDCYCLE { dest: 2 },
+            Instruction::ADC {
+                dest: 0,
+                reg1: 1,
+                reg2: 2,
+            },
+            Instruction::SBB {
+                dest: 3,
+                reg1: 2,
+                reg2: 1,
+            },
+            Instruction::PUSHF,
+            Instruction::POPF,
+            Instruction::FXMUL {
+                dest: 1,
+                reg1: 1,
+                reg2: 0,
+            },
+            Instruction::FXDIV {
+                dest: 2,
+                reg1: 3,
+                reg2: 1,
+            },
+            Instruction::ENTER { size: 16 },
+            Instruction::STF { src: 2, offset: -4 },
+            Instruction::LEAVE,
+            Instruction::EXIT { code: -2 },
+            Instruction::IN {
+                dest: 1,
+                port: 0x3f8,
+            },
+            Instruction::OUT {
+                port: 0xffff,
+                src: 3,
+            },
+            Instruction::HLT,
+        ];
+        let encoder = Encoder::new();
+        let mut bytes = Vec::new();
+        for instruction in &instructions {
+            encoder.encode_instruction(instruction, &mut bytes);
+        }
+
+        let program = Program::new(&bytes);
+        let decoder = Decoder::<i32>::new();
+        let mut pc = 0;
+        for instruction in &instructions {
+            let decoded = decoder.decode_next_instruction(&program, pc).unwrap();
+            assert_eq!(&decoded, instruction);
+            pc += decoded.size();
+        }
+        assert_eq!(pc, bytes.len());
+    }
+}
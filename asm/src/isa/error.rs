@@ -0,0 +1,425 @@
+//! This module contains the error types used by the VM.
+
+use super::instructions::Instruction;
+use super::protection::Permission;
+use super::watchpoint::Access;
+use super::word::Word;
+
+/// The `Result` type is a type alias for a `Result` type that uses the `VmError` type as the error variant.
+pub type Result<T> = std::result::Result<T, VmError>;
+
+/// Represents the possible errors that can occur during the execution of the VM.
+/// The errors are related to memory access, invalid instructions, invalid opcodes,
+/// invalid registers, division by zero, stack underflow, stack overflow, and other errors.
+/// The `Other` variant is used to represent any other error that does not fit the other categories.
+/// The `String` field contains a description of the error.
+/// With the `serde` feature, the errors can be serialized into error reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VmError {
+    // ==========================================
+    // Memory errors
+    // ==========================================
+    //
+    /// Memory access out of bounds.
+    /// Contains the address and the size of the memory access.
+    ///
+    /// # Parameters
+    /// - `address`: The address of the memory access.
+    /// - `size`: The size of the memory access.
+    MemoryOutOfBounds { address: usize, size: usize },
+
+    /// Memory access not aligned.
+    /// Contains the address and the size of the memory access.
+    ///
+    /// # Parameters
+    /// - `address`: The address of the memory access.
+    /// - `size`: The size of the memory access.
+    MemoryNotAligned { address: usize, size: usize },
+
+    /// Page fault raised by the MMU.
+    /// The virtual address is not mapped, or is read-only for a write.
+    ///
+    /// # Parameters
+    /// - `address`: The virtual address of the memory access.
+    /// - `write`: `true` if the memory access is a write.
+    PageFault { address: usize, write: bool },
+
+    /// Memory access not allowed by the region descriptors of the memory, see `Region`.
+    ///
+    /// # Parameters
+    /// - `address`: The address of the memory access.
+    /// - `size`: The size of the memory access.
+    /// - `permission`: The operation denied.
+    ProtectionFault {
+        address: usize,
+        size: usize,
+        permission: Permission,
+    },
+
+    /// Memory access that triggered a watchpoint.
+    /// The access is performed before the execution stops.
+    ///
+    /// # Parameters
+    /// - `pc`: The program counter of the instruction that performed the access.
+    /// - `address`: The address of the memory access.
+    /// - `size`: The size of the memory access.
+    /// - `access`: The kind of the memory access.
+    WatchpointHit {
+        pc: usize,
+        address: usize,
+        size: usize,
+        access: Access,
+    },
+
+    // ==========================================
+    // Stack errors
+    // ==========================================
+    //
+    /// Stack underflow error.
+    /// This error is used when the stack is empty and an operation that requires a value is attempted.
+    StackUnderflow,
+
+    /// Stack overflow error.
+    /// This error is used when the stack is full and an operation that requires space is attempted.
+    StackOverflow,
+
+    // ==========================================
+    // Instruction errors
+    // ==========================================
+    //
+    /// Invalid opcode encountered.
+    /// Contains the opcode that caused the error.
+    ///
+    /// # Parameters
+    /// - `opcode`: The opcode that caused the error.
+    InvalidOpcode { opcode: u8 },
+
+    /// Invalid instruction encountered.
+    /// This error is used when an instruction is invalid.
+    /// For example, when the instruction is not long enough to contain the opcode.
+    InvalidInstruction,
+
+    // ==========================================
+    // Register errors
+    // ==========================================
+    //
+    /// Invalid register encountered, out of bounds.
+    /// Contains the register that caused the error.
+    ///
+    /// # Parameters
+    /// - `register`: The register that caused the error.
+    InvalidRegister { register: u8 },
+
+    // ==========================================
+    // Syscall errors
+    // ==========================================
+    //
+    /// Syscall number without a registered host function.
+    ///
+    /// # Parameters
+    /// - `number`: The syscall number that caused the error.
+    UnknownSyscall { number: u8 },
+
+    /// I/O port without a registered handler.
+    ///
+    /// # Parameters
+    /// - `port`: The port accessed by `IN` or `OUT`.
+    UnmappedPort { port: u16 },
+
+    /// Address freed or reallocated without a live allocation of the guest heap.
+    ///
+    /// # Parameters
+    /// - `address`: The address given to the heap syscall.
+    InvalidHeapAddress { address: usize },
+
+    // ==========================================
+    // Counter errors
+    // ==========================================
+    //
+    /// Invalid performance counter id encountered.
+    /// Contains the counter id that caused the error.
+    ///
+    /// # Parameters
+    /// - `counter`: The counter id that caused the error.
+    InvalidCounter { counter: u8 },
+
+    // ==========================================
+    // Arithmetic errors
+    // ==========================================
+    //
+    /// Division by zero error.
+    DivisionByZero,
+    /// Signed overflow of an arithmetic instruction, with `VmBuilder::checked_arithmetic`.
+    ArithmeticOverflow,
+
+    // ==========================================
+    // Execution limit errors
+    // ==========================================
+    //
+    /// Step limit exceeded.
+    /// This error is used when the program did not halt within the number of steps it was given.
+    StepLimitExceeded,
+
+    /// Cycle budget exceeded.
+    /// This error is used when the program did not halt within the cycles it was given,
+    /// see `VM::run_with_cycle_budget`.
+    CycleBudgetExceeded,
+
+    /// Out of gas.
+    /// This error is used when the program consumed all the gas it was given,
+    /// see `VM::run_with_gas`.
+    OutOfGas,
+
+    /// Run cancelled.
+    /// This error is used when the `CancelToken` of an asynchronous run was cancelled,
+    /// see `VM::run_async`.
+    Cancelled,
+
+    /// Watchdog expired.
+    /// This error is used when the guest did not pet a `WatchdogDevice` in time.
+    ///
+    /// # Parameters
+    /// - `period`: The number of steps allowed between two pets.
+    WatchdogExpired { period: u64 },
+
+    // ==========================================
+    // Hook errors
+    // ==========================================
+    //
+    /// Execution paused by the hook of the VM, it can be resumed with `VM::step`.
+    ///
+    /// # Parameters
+    /// - `pc`: The program counter of the next instruction.
+    Paused { pc: usize },
+
+    /// Execution aborted by the hook of the VM.
+    ///
+    /// # Parameters
+    /// - `pc`: The program counter of the instruction when the hook aborted the execution.
+    Aborted { pc: usize },
+
+    // ==========================================
+    // Replay errors
+    // ==========================================
+    //
+    /// Replay diverging from its recording.
+    /// This error is used when the replayed program makes a syscall or a device read that was
+    /// not recorded, see `VM::replay`.
+    ReplayDivergence,
+
+    // ==========================================
+    // History errors
+    // ==========================================
+    //
+    /// No executed instruction to step back.
+    /// This error is used when the history is disabled or exhausted, see `VM::step_back`.
+    NoHistory,
+
+    // ==========================================
+    // Thread errors
+    // ==========================================
+    //
+    /// Deadlock of the green threads.
+    /// This error is used when all the remaining threads are waiting in `JOIN`.
+    Deadlock,
+
+    // ==========================================
+    // Program errors
+    // ==========================================
+    //
+    /// Invalid executable image.
+    /// This error is used when an image in the `.fvm` format cannot be parsed.
+    ///
+    /// # Parameters
+    /// - `description`: A description of the error.
+    InvalidImage(String),
+
+    // ==========================================
+    // Other errors
+    // ==========================================
+    //
+    /// Other error.
+    /// Contains a description of the error.
+    ///
+    /// # Parameters
+    /// - `description`: A description of the error.
+    Other(String),
+}
+
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VmError::MemoryOutOfBounds { address, size } => {
+                write!(
+                    f,
+                    "Memory access out of bounds at address: 0x{:x} with size: {}",
+                    address, size
+                )
+            }
+            VmError::MemoryNotAligned { address, size } => {
+                write!(
+                    f,
+                    "Memory access not aligned at address: 0x{:x} with size: {}",
+                    address, size
+                )
+            }
+            VmError::PageFault { address, write } => {
+                write!(
+                    f,
+                    "Page fault on {} at address: 0x{:x}",
+                    if *write { "write" } else { "read" },
+                    address
+                )
+            }
+            VmError::ProtectionFault {
+                address,
+                size,
+                permission,
+            } => {
+                write!(
+                    f,
+                    "Protection fault on {} at address: 0x{:x} with size: {}",
+                    permission, address, size
+                )
+            }
+            VmError::WatchpointHit {
+                pc,
+                address,
+                size,
+                access,
+            } => {
+                write!(
+                    f,
+                    "Watchpoint hit by {} at address: 0x{:x} with size: {} (pc: 0x{:x})",
+                    access, address, size, pc
+                )
+            }
+            VmError::InvalidOpcode { opcode } => {
+                write!(f, "Invalid opcode encountered: 0x{:02x}", opcode)
+            }
+            VmError::InvalidInstruction => {
+                write!(f, "Invalid instruction encountered")
+            }
+            VmError::InvalidRegister { register } => {
+                write!(f, "Register out of bounds: {}", register)
+            }
+            VmError::UnknownSyscall { number } => {
+                write!(f, "No host function registered for syscall: {}", number)
+            }
+            VmError::UnmappedPort { port } => {
+                write!(f, "No handler registered for port: 0x{:x}", port)
+            }
+            VmError::InvalidHeapAddress { address } => {
+                write!(f, "No heap allocation at address: 0x{:x}", address)
+            }
+            VmError::InvalidCounter { counter } => {
+                write!(f, "Invalid performance counter: 0x{:02x}", counter)
+            }
+            VmError::DivisionByZero => {
+                write!(f, "Attempted to divide by zero")
+            }
+            VmError::ArithmeticOverflow => {
+                write!(f, "Arithmetic overflow")
+            }
+            VmError::StepLimitExceeded => {
+                write!(f, "Step limit exceeded")
+            }
+            VmError::CycleBudgetExceeded => {
+                write!(f, "Cycle budget exceeded")
+            }
+            VmError::OutOfGas => {
+                write!(f, "Out of gas")
+            }
+            VmError::Cancelled => {
+                write!(f, "Run cancelled")
+            }
+            VmError::WatchdogExpired { period } => {
+                write!(f, "Watchdog not petted within {} steps", period)
+            }
+            VmError::Paused { pc } => {
+                write!(f, "Execution paused by the hook at pc: 0x{:x}", pc)
+            }
+            VmError::Aborted { pc } => {
+                write!(f, "Execution aborted by the hook at pc: 0x{:x}", pc)
+            }
+            VmError::ReplayDivergence => {
+                write!(f, "Execution diverged from the recording")
+            }
+            VmError::NoHistory => {
+                write!(f, "No executed instruction to step back")
+            }
+            VmError::Deadlock => {
+                write!(f, "All the threads are waiting for each other")
+            }
+            VmError::InvalidImage(description) => {
+                write!(f, "Invalid executable image: {}", description)
+            }
+            VmError::StackUnderflow => {
+                write!(f, "Stack underflow error")
+            }
+            VmError::StackOverflow => {
+                write!(f, "Stack overflow error")
+            }
+            VmError::Other(description) => {
+                write!(f, "Error: {}", description)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// An error stopping a run, with the location of the faulting instruction.
+/// Returned by the `run` functions of the VM, the kind of the error is its `source`.
+///
+/// # Parameters
+/// - `pc`: The address of the faulting instruction, the entry point for a loading error.
+/// - `step`: The number of the faulting step, counted from `1`, `0` for a loading error.
+/// - `instruction`: The faulting instruction, `None` if it cannot be decoded.
+/// - `source`: The error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionError<T: Word> {
+    pub pc: usize,
+    pub step: u128,
+    pub instruction: Option<Instruction<T, T::Address>>,
+    pub source: VmError,
+}
+
+impl<T: Word> std::fmt::Display for ExecutionError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at address 0x{:x}", self.source, self.pc)?;
+        if let Some(instruction) = &self.instruction {
+            write!(f, " ({})", instruction)?;
+        }
+        write!(f, ", step {}", self.step)
+    }
+}
+
+impl<T: Word> std::error::Error for ExecutionError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl<T: Word> From<ExecutionError<T>> for VmError {
+    fn from(error: ExecutionError<T>) -> Self {
+        error.source
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::isa::instructions::{Instruction, OpCode};
+
+    fn assert_serde<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+
+    #[test]
+    fn test_serde_implemented() {
+        assert_serde::<VmError>();
+        assert_serde::<Instruction<i32, u32>>();
+        assert_serde::<Instruction<i64, u64>>();
+        assert_serde::<OpCode>();
+    }
+}
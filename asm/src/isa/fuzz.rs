@@ -0,0 +1,252 @@
+//! Structured inputs for fuzzing: instructions and programs built from arbitrary bytes.
+//!
+//! A fuzzer mutating raw bytecode mostly produces invalid opcodes and registers, which stop the
+//! VM at the first instruction. The functions of this module consume the bytes of a fuzzing
+//! input like `arbitrary::Unstructured` does, and build valid instructions from them, so that
+//! every input exercises the decoder and the CPU. A `cargo fuzz` target runs them as:
+//!
+//! ```
+//! use forge_vm::vm::fuzz::arbitrary_program;
+//! use forge_vm::VM;
+//! # let data: &[u8] = &[0x09, 0x01, 0x02, 0x03, 0x13, 0x00, 0x00, 0x00, 0x00];
+//! let program = arbitrary_program(data, 4);
+//! let mut vm = VM::<i32>::new(64, 1024);
+//! let _ = vm.run_with_limit(&program, 10_000);
+//! ```
+
+use super::counters::Counter;
+use super::decoder::Decoder;
+use super::encoder::Encoder;
+use super::error::VmError;
+#[cfg(feature = "arbitrary")]
+use super::hardware_config::REGISTERS_COUNT;
+use super::instructions::{Instruction, OpCode};
+
+/// Build an instruction from the next bytes of a fuzzing input.
+///
+/// The first byte selects the opcode among the valid ones and the next bytes are the operands,
+/// the register operands are reduced to the registers of the CPU and the counter operands to
+/// the valid counters. The jump targets are left as given, see `arbitrary_program` for targets
+/// inside the program.
+///
+/// # Parameters
+/// - `input`: The remaining bytes of the input, advanced past the consumed bytes.
+/// - `registers_count`: The number of registers of the CPU, at least `1`.
+///
+/// # Returns
+/// The instruction, `None` only if the input is too short for it.
+pub fn arbitrary_instruction(
+    input: &mut &[u8],
+    registers_count: u8,
+) -> Option<Instruction<i32, u32>> {
+    let opcodes: Vec<OpCode> = (0..=u8::MAX)
+        .filter_map(|byte| OpCode::try_from(byte).ok())
+        .collect();
+    let (&selector, rest) = input.split_first()?;
+    let opcode = opcodes[selector as usize % opcodes.len()];
+    let operands = rest.get(..opcode.size::<i32, u32>() - 1)?;
+    *input = &rest[operands.len()..];
+
+    let registers_count = registers_count.max(1);
+    let decoder = Decoder::<i32>::with_registers(registers_count);
+    let mut bytes = vec![u8::from(opcode)];
+    bytes.extend_from_slice(operands);
+    loop {
+        match decoder.decode_instruction(&bytes) {
+            // the register is replaced by a valid one, and so are the other operand bytes with
+            // its value, which keeps the input valid when it is not a register
+            Err(VmError::InvalidRegister { register }) => {
+                for byte in bytes[1..].iter_mut().filter(|byte| **byte == register) {
+                    *byte = register % registers_count;
+                }
+            }
+            // the operands only decrease, so the loop ends
+            Err(VmError::InvalidCounter { counter }) => {
+                let counters = (0..=u8::MAX)
+                    .filter(|byte| Counter::try_from(*byte).is_ok())
+                    .count() as u8;
+                for byte in bytes[1..].iter_mut().filter(|byte| **byte == counter) {
+                    *byte = counter % counters;
+                }
+            }
+            Ok(instruction) => return Some(instruction),
+            Err(error) => unreachable!("operands of {:?} not sanitized: {}", opcode, error),
+        }
+    }
+}
+
+/// Build a structurally valid program from a fuzzing input.
+///
+/// The instructions of `arbitrary_instruction` are taken until the input is exhausted, their
+/// jump targets are moved to the start of one of the instructions, and a final `HLT` is added.
+/// The program can loop forever: run it with a step limit.
+///
+/// # Parameters
+/// - `input`: The bytes of the fuzzing input.
+/// - `registers_count`: The number of registers of the CPU, at least `1`.
+///
+/// # Returns
+/// The bytecode of the program.
+pub fn arbitrary_program(mut input: &[u8], registers_count: u8) -> Vec<u8> {
+    let mut instructions = Vec::new();
+    while let Some(instruction) = arbitrary_instruction(&mut input, registers_count) {
+        instructions.push(instruction);
+    }
+    instructions.push(Instruction::HLT);
+    bound_jump_targets(&mut instructions);
+    Encoder::new().encode_program(&instructions)
+}
+
+/// A structurally valid program built by `arbitrary_program` from the whole input, for the CPU
+/// with the default number of registers, to take as the argument of a `cargo fuzz` target.
+#[cfg(feature = "arbitrary")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArbitraryProgram(pub Vec<u8>);
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Instruction<i32, u32> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut input = u.peek_bytes(u.len()).unwrap_or_default();
+        let len = input.len();
+        let instruction = arbitrary_instruction(&mut input, REGISTERS_COUNT)
+            .ok_or(arbitrary::Error::NotEnoughData)?;
+        u.bytes(len - input.len())?;
+        Ok(instruction)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ArbitraryProgram {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let input = u.peek_bytes(u.len()).unwrap_or_default();
+        u.bytes(input.len())?;
+        Ok(ArbitraryProgram(arbitrary_program(input, REGISTERS_COUNT)))
+    }
+}
+
+/// Move the jump targets of a program to the start of one of its instructions.
+pub fn bound_jump_targets(instructions: &mut [Instruction<i32, u32>]) {
+    let mut boundaries = Vec::with_capacity(instructions.len());
+    let mut address = 0;
+    for instruction in instructions.iter() {
+        boundaries.push(address as u32);
+        address += instruction.size();
+    }
+    for instruction in instructions.iter_mut() {
+        if let Some(target) = instruction.jump_target_mut() {
+            *target = boundaries[*target as usize % boundaries.len()];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isa::program::Program;
+
+    /// A deterministic pseudo-random input.
+    fn input(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_arbitrary_instruction() {
+        let mut bytes: &[u8] = &[0x09, 0x07, 0x01, 0x02, 0x00];
+        assert_eq!(
+            arbitrary_instruction(&mut bytes, 4),
+            Some(Instruction::ADD {
+                dest: 3,
+                reg1: 1,
+                reg2: 2
+            })
+        );
+        assert_eq!(bytes, [0x00]);
+        assert_eq!(arbitrary_instruction(&mut bytes, 4), Some(Instruction::NOP));
+        assert_eq!(arbitrary_instruction(&mut bytes, 4), None);
+
+        // too short for the operands
+        let mut bytes: &[u8] = &[0x01, 0x00, 0x01];
+        assert_eq!(arbitrary_instruction(&mut bytes, 4), None);
+
+        // the counter is reduced to a valid one
+        let selector = (0..=u8::MAX)
+            .filter_map(|byte| OpCode::try_from(byte).ok())
+            .position(|opcode| opcode == OpCode::RDCNT)
+            .unwrap() as u8;
+        let mut bytes: &[u8] = &[selector, 0x01, 0x07];
+        assert_eq!(
+            arbitrary_instruction(&mut bytes, 4),
+            Some(Instruction::RDCNT {
+                dest: 1,
+                counter: 0x07 % 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_arbitrary_instruction_consumes_input() {
+        // only the end of the input stops the instructions
+        for seed in 1..50 {
+            let data = input(seed, 256);
+            let mut bytes: &[u8] = &data;
+            while let Some(instruction) = arbitrary_instruction(&mut bytes, 1) {
+                assert!(instruction.size() > 0);
+            }
+            if let Some(&selector) = bytes.first() {
+                let opcodes: Vec<OpCode> = (0..=u8::MAX)
+                    .filter_map(|byte| OpCode::try_from(byte).ok())
+                    .collect();
+                let opcode = opcodes[selector as usize % opcodes.len()];
+                assert!(bytes.len() < opcode.size::<i32, u32>());
+            }
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data = input(7, 256);
+        let mut u = Unstructured::new(&data);
+        let mut instructions = 0;
+        while let Ok(instruction) = Instruction::<i32, u32>::arbitrary(&mut u) {
+            assert!(instruction.size() > 0);
+            instructions += 1;
+        }
+        assert!(instructions > 1);
+        assert!(u.len() < 16);
+
+        let ArbitraryProgram(bytecode) =
+            ArbitraryProgram::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        assert_eq!(bytecode, arbitrary_program(&data, REGISTERS_COUNT));
+    }
+
+    #[test]
+    fn test_arbitrary_program() {
+        for seed in 1..50 {
+            let bytecode = arbitrary_program(&input(seed, 256), 4);
+            let program = Program::new(&bytecode);
+            let decoder = Decoder::<i32>::new();
+            let mut boundaries = Vec::new();
+            let mut targets = Vec::new();
+            let mut pc = 0;
+            while pc < bytecode.len() {
+                let instruction = decoder.decode_next_instruction(&program, pc).unwrap();
+                boundaries.push(pc as u32);
+                targets.extend(instruction.jump_target().copied());
+                pc += instruction.size();
+            }
+            assert_eq!(bytecode.last(), Some(&0xff));
+            assert!(targets.iter().all(|target| boundaries.contains(target)));
+        }
+    }
+}
@@ -0,0 +1,11 @@
+/// The default number of registers in the VM.
+pub const REGISTERS_COUNT: u8 = 4;
+
+/// The default maximum number of values the stack can hold.
+pub const STACK_CAPACITY: usize = 1024;
+
+/// The default size of the memory in bytes.
+pub const MEMORY_SIZE: usize = 65536;
+
+/// The default address of the interrupt vector table in memory.
+pub const VECTOR_TABLE: usize = 0;
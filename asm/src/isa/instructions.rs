@@ -0,0 +1,2121 @@
+use super::error::{Result, VmError};
+
+/// Represents the set of all possible instructions for the `ForgeVM` virtual machine.
+/// Each instruction can manipulate registers, perform arithmetic or logical operations,
+/// control program flow, or interact with memory.
+///
+/// This enum is used to decode and execute instructions from the bytecode loaded into the VM.
+/// With the `serde` feature, the decoded instructions can be serialized, e.g. to exchange
+/// disassembled programs with other tools.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instruction<D, A> {
+    // ==========================================
+    // Control Flow Instructions
+    // ==========================================
+    //
+    /// No operation.
+    /// Performs no action and is typically used to consume a cycle without any effect.
+    NOP,
+
+    /// Jump to a specified address in the program
+    JMP {
+        /// The address to jump to
+        address: A,
+    },
+
+    /// Jump if negative flag is set
+    JMPN {
+        /// The address to jump to if the negative flag is set
+        address: A,
+    },
+
+    /// Jump if negative flag is not set
+    JMPP {
+        /// The address to jump to if the negative flag is not set
+        address: A,
+    },
+
+    /// Jump if zero flag is set
+    JMPZ {
+        /// The address to jump to if the zero flag is set
+        address: A,
+    },
+
+    /// Jump if below, the carry flag is set
+    JMPB {
+        /// The address to jump to if the carry flag is set
+        address: A,
+    },
+
+    /// Jump if below or equal, the carry flag or the zero flag is set
+    JMPBE {
+        /// The address to jump to if the carry flag or the zero flag is set
+        address: A,
+    },
+
+    /// Jump if above, neither the carry flag nor the zero flag is set
+    JMPA {
+        /// The address to jump to if neither the carry flag nor the zero flag is set
+        address: A,
+    },
+
+    /// Jump if above or equal, the carry flag is not set
+    JMPAE {
+        /// The address to jump to if the carry flag is not set
+        address: A,
+    },
+
+    /// Jump if less, the negative flag differs from the overflow flag
+    JMPLT {
+        /// The address to jump to if the negative flag differs from the overflow flag
+        address: A,
+    },
+
+    /// Jump if less or equal, the zero flag is set or the negative flag differs from the overflow flag
+    JMPLE {
+        /// The address to jump to if the zero flag is set or the negative flag differs from the overflow flag
+        address: A,
+    },
+
+    /// Jump if greater, the zero flag is not set and the negative flag equals the overflow flag
+    JMPGT {
+        /// The address to jump to if the zero flag is not set and the negative flag equals the overflow flag
+        address: A,
+    },
+
+    /// Jump if greater or equal, the negative flag equals the overflow flag
+    JMPGE {
+        /// The address to jump to if the negative flag equals the overflow flag
+        address: A,
+    },
+
+    /// Jump if carry flag is set
+    JMPC {
+        /// The address to jump to if the carry flag is set
+        address: A,
+    },
+
+    /// Jump if carry flag is not set
+    JMPNC {
+        /// The address to jump to if the carry flag is not set
+        address: A,
+    },
+
+    /// Jump if zero flag is not set
+    JMPNZ {
+        /// The address to jump to if the zero flag is not set
+        address: A,
+    },
+
+    /// Jump if overflow flag is set
+    JMPO {
+        /// The address to jump to if the overflow flag is set
+        address: A,
+    },
+
+    /// Call a function at a specified address in the program
+    ///
+    /// This operation pushes the current program counter onto the stack and jumps to the specified address.
+    CALL {
+        /// The address of the function to call
+        address: A,
+    },
+
+    /// Return from a function call
+    ///
+    /// This operation pops the top of the stack and sets the program counter to the popped value.
+    RET,
+
+    /// Halt the program execution
+    ///
+    /// This operation ends the running thread, the program terminates when no thread is left,
+    /// with the value of the `R0` register as exit code.
+    HLT,
+
+    /// Terminate the program with an exit code
+    ///
+    /// This operation terminates the program at once, whatever the other threads, with the
+    /// immediate `code` as exit code.
+    EXIT {
+        /// The exit code of the program.
+        code: D,
+    },
+
+    /// Moves a specified `value` into the designated `dest` register.
+    MOV {
+        /// The destination register where the value will be stored.
+        dest: u8,
+        /// The value to store in the register.
+        value: D,
+    },
+
+    /// Copies the value of the `src` register into the `dest` register.
+    MOVR {
+        /// The destination register where the value will be stored.
+        dest: u8,
+        /// The source register holding the value to copy.
+        src: u8,
+    },
+
+    /// Copies the value of the `src` register into the `dest` register if the zero flag is set.
+    CMOVZ {
+        /// The destination register where the value will be stored.
+        dest: u8,
+        /// The source register holding the value to copy.
+        src: u8,
+    },
+
+    /// Copies the value of the `src` register into the `dest` register if the negative flag is set.
+    CMOVN {
+        /// The destination register where the value will be stored.
+        dest: u8,
+        /// The source register holding the value to copy.
+        src: u8,
+    },
+
+    /// Loads a value from the specified `address` in memory into the `dest` register.
+    ///
+    /// This operation reads the memory at the given address and updates the register with the value found.
+    LD {
+        /// The destination register where the memory content will be loaded.
+        dest: u8,
+        /// Memory address from which data is to be read.
+        address: A,
+    },
+
+    /// Stores the value from `src` register into the memory at `address`.
+    ///
+    /// This operation writes the value in the specified register to the specified address in memory.
+    ST {
+        /// The source register whose value will be stored in memory.
+        src: u8,
+        /// Memory address where the data is to be stored.
+        address: A,
+    },
+
+    /// Loads a value from memory at the address held in the `reg` register into the `dest` register.
+    ///
+    /// This operation reads the memory at the address found in a register, to follow pointers.
+    LDR {
+        /// The destination register where the memory content will be loaded.
+        dest: u8,
+        /// The register holding the memory address from which data is to be read.
+        reg: u8,
+    },
+
+    /// Stores the value from `src` register into the memory at the address held in the `reg` register.
+    ///
+    /// This operation writes the value of a register to the address found in another register.
+    STR {
+        /// The source register containing the value to store.
+        src: u8,
+        /// The register holding the memory address where the value will be stored.
+        reg: u8,
+    },
+    /// Loads a byte from the specified `address` in memory into the `dest` register, sign-extending it.
+    LDB {
+        /// The destination register where the memory content will be loaded.
+        dest: u8,
+        /// Memory address from which data is to be read.
+        address: A,
+    },
+
+    /// Loads a byte from the specified `address` in memory into the `dest` register, zero-extending it.
+    LDBU {
+        /// The destination register where the memory content will be loaded.
+        dest: u8,
+        /// Memory address from which data is to be read.
+        address: A,
+    },
+
+    /// Loads a halfword (2 bytes) from the specified `address` in memory into the `dest` register, sign-extending it.
+    LDH {
+        /// The destination register where the memory content will be loaded.
+        dest: u8,
+        /// Memory address from which data is to be read.
+        address: A,
+    },
+
+    /// Loads a halfword (2 bytes) from the specified `address` in memory into the `dest` register, zero-extending it.
+    LDHU {
+        /// The destination register where the memory content will be loaded.
+        dest: u8,
+        /// Memory address from which data is to be read.
+        address: A,
+    },
+
+    /// Stores the lowest byte of the `src` register into the memory at `address`.
+    STB {
+        /// The source register containing the value to store.
+        src: u8,
+        /// Memory address where the value will be stored.
+        address: A,
+    },
+
+    /// Stores the lowest halfword (2 bytes) of the `src` register into the memory at `address`.
+    STH {
+        /// The source register containing the value to store.
+        src: u8,
+        /// Memory address where the value will be stored.
+        address: A,
+    },
+
+    /// Atomically compares and swaps the value in memory at the address held in the `reg` register.
+    ///
+    /// If the value in memory equals the `expected` register, the `new` register is stored in
+    /// its place and the zero flag is set, otherwise the memory is left unchanged and the zero
+    /// flag is cleared. In both cases the `expected` register receives the value read from memory.
+    /// The read and the write happen in the same instruction, no interrupt can occur between them.
+    CAS {
+        /// The register holding the value expected in memory, receives the value read.
+        expected: u8,
+        /// The register holding the value to store if the comparison succeeds.
+        new: u8,
+        /// The register holding the memory address.
+        reg: u8,
+    },
+    /// Atomically adds the `src` register to the value in memory at the address held in the `reg` register.
+    ///
+    /// The `src` register receives the value read from memory before the addition, and the flags are
+    /// set from the sum like `ADD`. The read and the write happen in the same instruction,
+    /// no interrupt can occur between them.
+    XADD {
+        /// The register holding the value to add, receives the value read.
+        src: u8,
+        /// The register holding the memory address.
+        reg: u8,
+    },
+
+    /// Push the value from `reg` register onto the stack.
+    ///
+    /// This operation pushes the value from the specified register onto the stack.
+    PUSHREG {
+        /// The register whose value is to be pushed onto the stack.
+        reg: u8,
+    },
+
+    /// Pop the value from the top of the stack into the `reg` register.
+    ///
+    /// This operation pops the value from the top of the stack and stores it in the specified register.
+    POPREG {
+        /// The register where the popped value will be stored.
+        reg: u8,
+    },
+
+    /// Push an immediate `value` onto the stack.
+    PUSH {
+        /// The value to push onto the stack.
+        value: D,
+    },
+
+    /// Duplicate the value on the top of the stack.
+    DUP,
+
+    /// Swap the two values on the top of the stack.
+    SWAP,
+
+    /// Discard the value on the top of the stack.
+    DROP,
+
+    /// Push all the registers onto the stack, from `R0` to the last register.
+    ///
+    /// Nothing is pushed if the stack cannot hold all the registers.
+    PUSHA,
+
+    /// Pop all the registers from the stack, from the last register to `R0`.
+    ///
+    /// Nothing is popped if the stack does not hold a value for each register.
+    POPA,
+
+    /// Read the stack pointer into the `dest` register.
+    ///
+    /// Only valid when the stack lives in the memory, see `VmBuilder::memory_stack`: the stack
+    /// pointer is the address of the value on the top of the stack.
+    RDSP {
+        /// The destination register where the stack pointer will be stored.
+        dest: u8,
+    },
+
+    /// Set the stack pointer to the value of the `src` register.
+    ///
+    /// Only valid when the stack lives in the memory. The values between the old and the new
+    /// stack pointer are pushed or dropped at once, without checking the bounds of the stack.
+    WRSP {
+        /// The register holding the new stack pointer.
+        src: u8,
+    },
+
+    /// Enter a stack frame of `size` bytes.
+    ///
+    /// Only valid when the stack lives in the memory. This operation pushes the frame pointer,
+    /// sets the frame pointer to the stack pointer and moves the stack pointer down by `size`
+    /// bytes, reserving the local variables of a function below the frame pointer.
+    /// Nothing is pushed if the stack cannot hold the frame.
+    ENTER {
+        /// The size of the local variables, in bytes.
+        size: A,
+    },
+
+    /// Leave the stack frame entered by `ENTER`.
+    ///
+    /// Only valid when the stack lives in the memory. This operation sets the stack pointer to the
+    /// frame pointer, dropping the local variables, and pops the frame pointer of the caller.
+    LEAVE,
+
+    /// Load the value at `offset` bytes from the frame pointer into the `dest` register.
+    ///
+    /// Only valid when the stack lives in the memory. The offset is signed: the local variables
+    /// are below the frame pointer and the arguments pushed by the caller are above it.
+    LDF {
+        /// The destination register where the value will be stored.
+        dest: u8,
+        /// The signed offset from the frame pointer, in bytes.
+        offset: D,
+    },
+
+    /// Store the value of the `src` register at `offset` bytes from the frame pointer.
+    ///
+    /// Only valid when the stack lives in the memory, see `LDF`.
+    STF {
+        /// The register holding the value to store.
+        src: u8,
+        /// The signed offset from the frame pointer, in bytes.
+        offset: D,
+    },
+
+    /// Read the frame pointer into the `dest` register.
+    ///
+    /// Only valid when the stack lives in the memory, the address of a local variable is the
+    /// frame pointer plus its offset.
+    RDFP {
+        /// The destination register where the frame pointer will be stored.
+        dest: u8,
+    },
+
+    // ==========================================
+    // Arithmetic Instructions
+    // ==========================================
+    //
+    /// Add two registers and store the result in a destination register
+    ///
+    /// This operation adds the values in two registers and stores the result in the destination register.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the unsigned result is too large to fit in the register, the carry flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    ADD {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be added.
+        reg1: u8,
+        /// The second register containing the value to be added.
+        reg2: u8,
+    },
+
+    /// Subtract two registers and store the result in a destination register
+    ///
+    /// This operation subtracts the value in the second register from the value in the first register
+    /// and stores the result in the destination register.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the unsigned subtraction borrows, the carry flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    SUB {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be subtracted from.
+        reg1: u8,
+        /// The second register containing the value to be subtracted.
+        reg2: u8,
+    },
+
+    /// Add two registers and the carry flag, and store the result in a destination register
+    ///
+    /// This operation adds the carry of a previous `ADD` or `ADC` to the sum, so that
+    /// the words of a wider integer can be added from the least significant one.
+    /// The flags are set like `ADD`, the carry flag holds the carry out of the sum.
+    ADC {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be added.
+        reg1: u8,
+        /// The second register containing the value to be added.
+        reg2: u8,
+    },
+
+    /// Subtract a register and the carry flag from a register, and store the result in a
+    /// destination register
+    ///
+    /// This operation subtracts the borrow of a previous `SUB` or `SBB` from the difference, so that
+    /// the words of a wider integer can be subtracted from the least significant one.
+    /// The flags are set like `SUB`, the carry flag holds the borrow of the subtraction.
+    SBB {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be subtracted from.
+        reg1: u8,
+        /// The second register containing the value to be subtracted.
+        reg2: u8,
+    },
+
+    /// Multiply two registers and store the result in a destination register
+    ///
+    /// This operation multiplies the values in two registers and stores the result in the destination register.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    MULT {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be multiplied.
+        reg1: u8,
+        /// The second register containing the value to be multiplied.
+        reg2: u8,
+    },
+
+    /// Divide two registers and store the result in a destination register
+    ///
+    /// This operation divides the value in the first register by the value in the second register
+    /// and stores the result in the destination register.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    DIV {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the dividend.
+        reg1: u8,
+        /// The second register containing the divisor.
+        reg2: u8,
+    },
+
+    /// Calculate the remainder of dividing two registers and store the result in a destination register
+    ///
+    /// This operation calculates the remainder of dividing the value in the first register by the value in the second register
+    /// and stores the result in the destination register.
+    /// If the result is zero, the zero flag is set.
+    MOD {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the dividend.
+        reg1: u8,
+        /// The second register containing the divisor.
+        reg2: u8,
+    },
+
+    /// Multiply two fixed-point registers and store the result in a destination register
+    ///
+    /// This operation multiplies the values in two registers as fixed-point numbers whose lower half
+    /// is the fraction, Q16.16 for 32-bit words, rounded by `CPU::set_fixed_rounding`.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    FXMUL {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be multiplied.
+        reg1: u8,
+        /// The second register containing the value to be multiplied.
+        reg2: u8,
+    },
+
+    /// Divide two fixed-point registers and store the result in a destination register
+    ///
+    /// This operation divides the value in the first register by the value in the second register
+    /// as fixed-point numbers like `FXMUL`, rounded by `CPU::set_fixed_rounding`.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    FXDIV {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the dividend.
+        reg1: u8,
+        /// The second register containing the divisor.
+        reg2: u8,
+    },
+
+    /// Increment a register
+    ///
+    /// This operation increments the value in the specified register by one.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    INC {
+        /// The register to increment.
+        reg: u8,
+    },
+
+    /// Decrement a register
+    ///
+    /// This operation decrements the value in the specified register by one.
+    /// If the result is too large to fit in the register, the overflow flag is set.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    DEC {
+        /// The register to decrement.
+        reg: u8,
+    },
+
+    // ==========================================
+    // Logical Instructions
+    // ==========================================
+    //
+    /// Logical AND two registers and store the result in a destination register
+    ///
+    /// This operation performs a bitwise AND operation on the values in two registers
+    /// and stores the result in the destination register.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    AND {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be ANDed.
+        reg1: u8,
+        /// The second register containing the value to be ANDed.
+        reg2: u8,
+    },
+
+    /// Logical OR two registers and store the result in a destination register
+    ///
+    /// This operation performs a bitwise OR operation on the values in two registers
+    /// and stores the result in the destination register.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    OR {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be ORed.
+        reg1: u8,
+        /// The second register containing the value to be ORed.
+        reg2: u8,
+    },
+
+    /// Logical XOR two registers and store the result in a destination register
+    ///
+    /// This operation performs a bitwise XOR operation on the values in two registers
+    /// and stores the result in the destination register.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    XOR {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register containing the value to be XORed.
+        reg1: u8,
+        /// The second register containing the value to be XORed.
+        reg2: u8,
+    },
+
+    /// Logical NOT a register and store the result in a destination register
+    ///
+    /// This operation performs a bitwise NOT operation on the value in the specified register
+    /// and stores the result in the destination register.
+    /// If the result is zero, the zero flag is set.
+    /// If the result is negative, the negative flag is set.
+    NOT {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register containing the value to be NOTed.
+        reg: u8,
+    },
+
+    /// Population count
+    ///
+    /// This operation stores the number of bits set in the `reg` register in the destination register.
+    POPCNT {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value whose bits are counted.
+        reg: u8,
+    },
+
+    /// Count leading zeros
+    ///
+    /// This operation stores the number of zero bits above the most significant bit set in the `reg` register, the size of the word in bits if it is zero in the destination register.
+    CLZ {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value whose bits are counted.
+        reg: u8,
+    },
+
+    /// Count trailing zeros
+    ///
+    /// This operation stores the number of zero bits below the least significant bit set in the `reg` register, the size of the word in bits if it is zero in the destination register.
+    CTZ {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value whose bits are counted.
+        reg: u8,
+    },
+
+    /// Compare two registers
+    ///
+    /// This operation subtracts the second register from the first without storing the result.
+    /// The zero and negative flags are set from the difference, the overflow flag if the signed
+    /// subtraction overflows and the carry flag if the unsigned subtraction borrows.
+    CMP {
+        /// The first register to compare.
+        reg1: u8,
+        /// The second register to compare.
+        reg2: u8,
+    },
+
+    // ==========================================
+    // Flag Operations
+    // ==========================================
+    //
+    /// Clear the flags
+    ///
+    /// This operation clears all the flags in the status register.
+    CLF,
+
+    /// Push the flags onto the stack
+    ///
+    /// This operation pushes the status register packed into a word by `StatusFlags::bits`,
+    /// as the interrupts save it.
+    PUSHF,
+
+    /// Pop the flags from the stack
+    ///
+    /// This operation pops a word packed by `PUSHF` into the status register, including the
+    /// interrupt enable flag. The bits unknown to `StatusFlags::bits` are ignored.
+    POPF,
+
+    // ==========================================
+    // Counter Instructions
+    // ==========================================
+    //
+    /// Read a performance counter into a register
+    ///
+    /// This operation copies the current value of the performance counter identified by `counter`
+    /// into the destination register, allowing the guest to adapt to its own resource usage.
+    RDCNT {
+        /// The destination register where the counter value will be stored.
+        dest: u8,
+        /// The id of the counter to read.
+        counter: u8,
+    },
+
+    /// Read the cycle counter into a register
+    ///
+    /// This operation copies the cycles consumed since the program started, as `RDCNT` with the
+    /// `Counter::Cycles` counter, so that the guest can measure its own code and implement delays.
+    RDCYCLE {
+        /// The destination register where the cycle count will be stored.
+        dest: u8,
+    },
+
+    // ==========================================
+    // System Instructions
+    // ==========================================
+    //
+    /// Call a host function
+    ///
+    /// This operation calls the host function registered under `number` with `VM::register_host_fn`,
+    /// giving it access to the registers and the memory. Arguments and results are exchanged
+    /// through the registers by convention between the guest and the host.
+    SYSCALL {
+        /// The number of the host function to call.
+        number: u8,
+    },
+
+    /// Read a value from an I/O port
+    ///
+    /// This operation reads the value of the handler registered on the `port` in the port bus
+    /// of the VM into the `dest` register.
+    IN {
+        /// The destination register where the value will be stored.
+        dest: u8,
+        /// The port to read from.
+        port: u16,
+    },
+
+    /// Write a value to an I/O port
+    ///
+    /// This operation writes the value of the `src` register to the handler registered on the
+    /// `port` in the port bus of the VM.
+    OUT {
+        /// The port to write to.
+        port: u16,
+        /// The register holding the value to write.
+        src: u8,
+    },
+
+    // ==========================================
+    // Interrupt Instructions
+    // ==========================================
+    //
+    /// Enable the interrupts
+    ///
+    /// This operation sets the interrupt-enable flag, pending interrupts are delivered
+    /// before the next instructions.
+    EI,
+    /// Disable the interrupts
+    ///
+    /// This operation clears the interrupt-enable flag, interrupts stay pending until enabled.
+    DI,
+    /// Return from an interrupt handler
+    ///
+    /// This operation pops the status flags and the program counter saved by the delivery
+    /// of the interrupt, which enables the interrupts again.
+    IRET,
+
+    // ==========================================
+    // Memory Management Instructions
+    // ==========================================
+    //
+    // The paging is translated by the MMU of the CPU, see `mmu::Mmu` for the page table format.
+    //
+    /// Enable the paging
+    ///
+    /// This operation enables the MMU with the page directory at the physical address in the `reg`
+    /// register and flushes the TLB. The following memory accesses use virtual addresses.
+    MMUON {
+        /// The register holding the physical address of the page directory.
+        reg: u8,
+    },
+    /// Disable the paging
+    ///
+    /// This operation disables the MMU and flushes the TLB, the following memory accesses use
+    /// physical addresses.
+    MMUOFF,
+    /// Flush the TLB
+    ///
+    /// This operation drops the translations cached by the MMU, it must follow any change
+    /// of the page tables.
+    TLBFLUSH,
+    /// Read the page fault address
+    ///
+    /// This operation stores the virtual address of the last page fault in the destination register,
+    /// so that a page fault handler can map the missing page.
+    RDFAULT {
+        /// The destination register where the address will be stored.
+        dest: u8,
+    },
+
+    // ==========================================
+    // Shift Instructions
+    // ==========================================
+    //
+    /// Logical shift left
+    ///
+    /// This operation shifts the value of `reg1` to the left by the number of bits in `reg2`, filling with zeros.
+    /// The carry flag holds the last bit shifted out, shifting by the size of the word or more
+    /// gives zero.
+    SHL {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to shift.
+        reg1: u8,
+        /// The register holding the number of bits to shift.
+        reg2: u8,
+    },
+    /// Logical shift left by an immediate
+    ///
+    /// This operation shifts the value of `reg` to the left by `amount` bits, filling with zeros.
+    SHLI {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to shift.
+        reg: u8,
+        /// The number of bits to shift.
+        amount: u8,
+    },
+    /// Logical shift right
+    ///
+    /// This operation shifts the value of `reg1` to the right by the number of bits in `reg2`, filling with zeros.
+    /// The carry flag holds the last bit shifted out, shifting by the size of the word or more
+    /// gives zero.
+    SHR {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to shift.
+        reg1: u8,
+        /// The register holding the number of bits to shift.
+        reg2: u8,
+    },
+    /// Logical shift right by an immediate
+    ///
+    /// This operation shifts the value of `reg` to the right by `amount` bits, filling with zeros.
+    SHRI {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to shift.
+        reg: u8,
+        /// The number of bits to shift.
+        amount: u8,
+    },
+    /// Arithmetic shift right
+    ///
+    /// This operation shifts the value of `reg1` to the right by the number of bits in `reg2`, filling with the sign bit.
+    /// The carry flag holds the last bit shifted out, shifting by the size of the word or more
+    /// gives the sign bit in every bit.
+    SAR {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to shift.
+        reg1: u8,
+        /// The register holding the number of bits to shift.
+        reg2: u8,
+    },
+    /// Arithmetic shift right by an immediate
+    ///
+    /// This operation shifts the value of `reg` to the right by `amount` bits, filling with the sign bit.
+    SARI {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to shift.
+        reg: u8,
+        /// The number of bits to shift.
+        amount: u8,
+    },
+
+    // ==========================================
+    // Rotate Instructions
+    // ==========================================
+    //
+    /// Rotate left
+    ///
+    /// This operation rotates the value of `reg1` to the left by the number of bits in `reg2`,
+    /// the bits shifted out re-enter on the other side. The number of bits is taken modulo the size
+    /// of the word and the carry flag holds the last bit rotated out.
+    ROL {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to rotate.
+        reg1: u8,
+        /// The register holding the number of bits to rotate.
+        reg2: u8,
+    },
+    /// Rotate right
+    ///
+    /// This operation rotates the value of `reg1` to the right by the number of bits in `reg2`,
+    /// the bits shifted out re-enter on the other side. The number of bits is taken modulo the size
+    /// of the word and the carry flag holds the last bit rotated out.
+    ROR {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the value to rotate.
+        reg1: u8,
+        /// The register holding the number of bits to rotate.
+        reg2: u8,
+    },
+
+    // ==========================================
+    // Bit Instructions
+    // ==========================================
+    //
+    /// Bit test
+    ///
+    /// This operation tests the bit of `reg1` whose index is in `reg2`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BT {
+        /// The register holding the bits.
+        reg1: u8,
+        /// The register holding the index of the bit.
+        reg2: u8,
+    },
+    /// Bit test by an immediate
+    ///
+    /// This operation tests the bit `bit` of `reg`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BTI {
+        /// The register holding the bits.
+        reg: u8,
+        /// The index of the bit.
+        bit: u8,
+    },
+    /// Bit set
+    ///
+    /// This operation sets the bit of `reg1` whose index is in `reg2`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BS {
+        /// The register holding the bits.
+        reg1: u8,
+        /// The register holding the index of the bit.
+        reg2: u8,
+    },
+    /// Bit set by an immediate
+    ///
+    /// This operation sets the bit `bit` of `reg`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BSI {
+        /// The register holding the bits.
+        reg: u8,
+        /// The index of the bit.
+        bit: u8,
+    },
+    /// Bit clear
+    ///
+    /// This operation clears the bit of `reg1` whose index is in `reg2`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BC {
+        /// The register holding the bits.
+        reg1: u8,
+        /// The register holding the index of the bit.
+        reg2: u8,
+    },
+    /// Bit clear by an immediate
+    ///
+    /// This operation clears the bit `bit` of `reg`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BCI {
+        /// The register holding the bits.
+        reg: u8,
+        /// The index of the bit.
+        bit: u8,
+    },
+    /// Bit toggle
+    ///
+    /// This operation toggles the bit of `reg1` whose index is in `reg2`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BTG {
+        /// The register holding the bits.
+        reg1: u8,
+        /// The register holding the index of the bit.
+        reg2: u8,
+    },
+    /// Bit toggle by an immediate
+    ///
+    /// This operation toggles the bit `bit` of `reg`, modulo the size of the word.
+    /// The carry flag holds the bit before the operation.
+    BTGI {
+        /// The register holding the bits.
+        reg: u8,
+        /// The index of the bit.
+        bit: u8,
+    },
+
+    /// Signed high multiplication
+    ///
+    /// This operation multiplies the values of two registers as signed integers and stores the upper half
+    /// of the double-width product in the destination register, `MULT` giving the lower half.
+    MULH {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register to multiply.
+        reg1: u8,
+        /// The second register to multiply.
+        reg2: u8,
+    },
+
+    /// Unsigned high multiplication
+    ///
+    /// This operation multiplies the values of two registers as unsigned integers and stores the upper half
+    /// of the double-width product in the destination register, `MULT` giving the lower half.
+    UMULH {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register to multiply.
+        reg1: u8,
+        /// The second register to multiply.
+        reg2: u8,
+    },
+
+    // ==========================================
+    // Thread Instructions
+    // ==========================================
+    //
+    // The green threads are scheduled cooperatively by the VM, see `thread::Scheduler`.
+    //
+    /// Spawn a thread starting at `address`
+    ///
+    /// The new thread gets a copy of the registers, with zero in `dest`, and an empty stack.
+    /// With the stack in memory, the new thread starts with the stack pointer of the running
+    /// thread and must move its stack with `WRSP`.
+    /// The `dest` register of the running thread receives the id of the new thread.
+    SPAWN {
+        /// The register receiving the id of the new thread.
+        dest: u8,
+        /// The address where the new thread starts.
+        address: A,
+    },
+    /// Suspend the running thread and resume the next ready thread, if any.
+    YIELD,
+    /// Wait for the end of the thread whose id is in the `reg` register.
+    ///
+    /// The running thread continues at once if the thread has already ended.
+    JOIN {
+        /// The register holding the id of the thread to wait for.
+        reg: u8,
+    },
+
+    // ==========================================
+    // Floating-Point Instructions
+    // ==========================================
+    //
+    // The float registers `F0`, `F1`, ... form a separate bank of `f32` registers,
+    // with as many registers as the integer bank.
+    //
+    /// Moves a `f32` value, given by its bits, into the `dest` float register.
+    FMOV {
+        /// The destination float register where the value will be stored.
+        dest: u8,
+        /// The bits of the `f32` value, as returned by `f32::to_bits`.
+        value: u32,
+    },
+    /// Floating-point addition
+    ///
+    /// This operation adds the float registers `reg1` and `reg2` and stores the result in the `dest` float register.
+    /// The zero and negative flags are set from the result.
+    FADD {
+        /// The destination float register where the result will be stored.
+        dest: u8,
+        /// The first float register of the operation.
+        reg1: u8,
+        /// The second float register of the operation.
+        reg2: u8,
+    },
+    /// Floating-point subtraction
+    ///
+    /// This operation subtracts the float register `reg2` from `reg1` and stores the result in the `dest` float register.
+    /// The zero and negative flags are set from the result.
+    FSUB {
+        /// The destination float register where the result will be stored.
+        dest: u8,
+        /// The first float register of the operation.
+        reg1: u8,
+        /// The second float register of the operation.
+        reg2: u8,
+    },
+    /// Floating-point multiplication
+    ///
+    /// This operation multiplies the float registers `reg1` and `reg2` and stores the result in the `dest` float register.
+    /// The zero and negative flags are set from the result.
+    FMUL {
+        /// The destination float register where the result will be stored.
+        dest: u8,
+        /// The first float register of the operation.
+        reg1: u8,
+        /// The second float register of the operation.
+        reg2: u8,
+    },
+    /// Floating-point division
+    ///
+    /// This operation divides the float register `reg1` by `reg2` and stores the result in the `dest` float register.
+    /// The zero and negative flags are set from the result.
+    FDIV {
+        /// The destination float register where the result will be stored.
+        dest: u8,
+        /// The first float register of the operation.
+        reg1: u8,
+        /// The second float register of the operation.
+        reg2: u8,
+    },
+    /// Floating-point comparison
+    ///
+    /// This operation compares the float registers `reg1` and `reg2`: the zero flag is set if they
+    /// are equal, the carry and negative flags if `reg1` is less than `reg2` and the overflow flag
+    /// if they are unordered, when one of them is NaN. The unsigned jumps `JMPB`, `JMPBE`, `JMPA`
+    /// and `JMPAE` branch on the result.
+    FCMP {
+        /// The first float register to compare.
+        reg1: u8,
+        /// The second float register to compare.
+        reg2: u8,
+    },
+    /// Converts the integer register `src` to the nearest `f32` in the `dest` float register.
+    ITOF {
+        /// The destination float register where the value will be stored.
+        dest: u8,
+        /// The integer register holding the value to convert.
+        src: u8,
+    },
+    /// Converts the float register `src` to an integer in the `dest` register.
+    ///
+    /// The value is rounded toward zero and saturated to the bounds of the word, NaN gives zero.
+    FTOI {
+        /// The destination integer register where the value will be stored.
+        dest: u8,
+        /// The float register holding the value to convert.
+        src: u8,
+    },
+
+    // ==========================================
+    // Unsigned Instructions
+    // ==========================================
+    //
+    /// Unsigned multiplication
+    ///
+    /// This operation multiplies the values of two registers as unsigned integers and stores the result
+    /// in the destination register. The overflow flag is set if the product does not fit in the word.
+    MULU {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register to multiply.
+        reg1: u8,
+        /// The second register to multiply.
+        reg2: u8,
+    },
+    /// Unsigned division
+    ///
+    /// This operation divides the value of `reg1` by the value of `reg2` as unsigned integers
+    /// and stores the quotient in the destination register.
+    DIVU {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the dividend.
+        reg1: u8,
+        /// The register holding the divisor.
+        reg2: u8,
+    },
+    /// Unsigned modulo
+    ///
+    /// This operation computes the remainder of the division of `reg1` by `reg2` as unsigned integers
+    /// and stores it in the destination register.
+    MODU {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The register holding the dividend.
+        reg1: u8,
+        /// The register holding the divisor.
+        reg2: u8,
+    },
+    /// Unsigned comparison
+    ///
+    /// This operation compares the values in two registers as unsigned integers by subtracting `reg2`
+    /// from `reg1`: the zero flag is set if they are equal and the carry flag if `reg1` is below `reg2`.
+    CMPU {
+        /// The first register to compare.
+        reg1: u8,
+        /// The second register to compare.
+        reg2: u8,
+    },
+    /// Signed minimum
+    ///
+    /// This operation stores the smaller of the values in `reg1` and `reg2` as signed integers in the destination register.
+    MIN {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register to compare.
+        reg1: u8,
+        /// The second register to compare.
+        reg2: u8,
+    },
+    /// Signed maximum
+    ///
+    /// This operation stores the larger of the values in `reg1` and `reg2` as signed integers in the destination register.
+    MAX {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register to compare.
+        reg1: u8,
+        /// The second register to compare.
+        reg2: u8,
+    },
+    /// Unsigned minimum
+    ///
+    /// This operation stores the smaller of the values in `reg1` and `reg2` as unsigned integers in the destination register.
+    MINU {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register to compare.
+        reg1: u8,
+        /// The second register to compare.
+        reg2: u8,
+    },
+    /// Unsigned maximum
+    ///
+    /// This operation stores the larger of the values in `reg1` and `reg2` as unsigned integers in the destination register.
+    MAXU {
+        /// The destination register where the result will be stored.
+        dest: u8,
+        /// The first register to compare.
+        reg1: u8,
+        /// The second register to compare.
+        reg2: u8,
+    },
+}
+
+impl<D, T> std::fmt::Display for Instruction<D, T>
+where
+    D: std::fmt::Display,
+    T: std::fmt::Display + std::fmt::LowerHex,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Instruction::NOP => write!(f, "NOP"),
+            Instruction::MOV { dest, value } => write!(f, "MOV R{} {}", dest, value),
+            Instruction::LD { dest, address } => write!(f, "LD R{} 0x{:x}", dest, address),
+            Instruction::ST { src, address } => write!(f, "ST R{} 0x{:x}", src, address),
+            Instruction::AND { dest, reg1, reg2 } => write!(f, "AND R{} R{} R{}", dest, reg1, reg2),
+            Instruction::OR { dest, reg1, reg2 } => write!(f, "OR R{} R{} R{}", dest, reg1, reg2),
+            Instruction::XOR { dest, reg1, reg2 } => write!(f, "XOR R{} R{} R{}", dest, reg1, reg2),
+            Instruction::NOT { dest, reg } => write!(f, "NOT R{} R{}", dest, reg),
+            Instruction::CMP { reg1, reg2 } => write!(f, "CMP R{} R{}", reg1, reg2),
+            Instruction::ADD { dest, reg1, reg2 } => write!(f, "ADD R{} R{} R{}", dest, reg1, reg2),
+            Instruction::SUB { dest, reg1, reg2 } => write!(f, "SUB R{} R{} R{}", dest, reg1, reg2),
+            Instruction::ADC { dest, reg1, reg2 } => write!(f, "ADC R{} R{} R{}", dest, reg1, reg2),
+            Instruction::SBB { dest, reg1, reg2 } => write!(f, "SBB R{} R{} R{}", dest, reg1, reg2),
+            Instruction::MULT { dest, reg1, reg2 } => {
+                write!(f, "MULT R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::DIV { dest, reg1, reg2 } => write!(f, "DIV R{} R{} R{}", dest, reg1, reg2),
+            Instruction::MOD { dest, reg1, reg2 } => write!(f, "MOD R{} R{} R{}", dest, reg1, reg2),
+            Instruction::FXMUL { dest, reg1, reg2 } => {
+                write!(f, "FXMUL R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::FXDIV { dest, reg1, reg2 } => {
+                write!(f, "FXDIV R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::INC { reg } => write!(f, "INC R{}", reg),
+            Instruction::DEC { reg } => write!(f, "DEC R{}", reg),
+            Instruction::PUSHREG { reg } => write!(f, "PUSHREG R{}", reg),
+            Instruction::POPREG { reg } => write!(f, "POPREG R{}", reg),
+            Instruction::JMP { address } => write!(f, "JMP 0x{:x}", address),
+            Instruction::JMPN { address } => write!(f, "JMPN 0x{:x}", address),
+            Instruction::JMPP { address } => write!(f, "JMPP 0x{:x}", address),
+            Instruction::JMPZ { address } => write!(f, "JMPZ 0x{:x}", address),
+            Instruction::CALL { address } => write!(f, "CALL 0x{:x}", address),
+            Instruction::RET => write!(f, "RET"),
+            Instruction::CLF => write!(f, "CLF"),
+            Instruction::PUSHF => write!(f, "PUSHF"),
+            Instruction::POPF => write!(f, "POPF"),
+            Instruction::RDCNT { dest, counter } => write!(f, "RDCNT R{} {}", dest, counter),
+            Instruction::RDCYCLE { dest } => write!(f, "RDCYCLE R{}", dest),
+            Instruction::HLT => write!(f, "HLT"),
+            Instruction::EXIT { code } => write!(f, "EXIT {}", code),
+            Instruction::SYSCALL { number } => write!(f, "SYSCALL {}", number),
+            Instruction::IN { dest, port } => write!(f, "IN R{} 0x{:x}", dest, port),
+            Instruction::OUT { port, src } => write!(f, "OUT 0x{:x} R{}", port, src),
+            Instruction::EI => write!(f, "EI"),
+            Instruction::DI => write!(f, "DI"),
+            Instruction::IRET => write!(f, "IRET"),
+            Instruction::SHL { dest, reg1, reg2 } => {
+                write!(f, "SHL R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::SHLI { dest, reg, amount } => {
+                write!(f, "SHLI R{} R{} {}", dest, reg, amount)
+            }
+            Instruction::SHR { dest, reg1, reg2 } => {
+                write!(f, "SHR R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::SHRI { dest, reg, amount } => {
+                write!(f, "SHRI R{} R{} {}", dest, reg, amount)
+            }
+            Instruction::SAR { dest, reg1, reg2 } => {
+                write!(f, "SAR R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::SARI { dest, reg, amount } => {
+                write!(f, "SARI R{} R{} {}", dest, reg, amount)
+            }
+            Instruction::ROL { dest, reg1, reg2 } => {
+                write!(f, "ROL R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::ROR { dest, reg1, reg2 } => {
+                write!(f, "ROR R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::MOVR { dest, src } => write!(f, "MOVR R{} R{}", dest, src),
+            Instruction::LDR { dest, reg } => write!(f, "LDR R{} [R{}]", dest, reg),
+            Instruction::STR { src, reg } => write!(f, "STR R{} [R{}]", src, reg),
+            Instruction::LDB { dest, address } => write!(f, "LDB R{} 0x{:x}", dest, address),
+            Instruction::LDBU { dest, address } => write!(f, "LDBU R{} 0x{:x}", dest, address),
+            Instruction::LDH { dest, address } => write!(f, "LDH R{} 0x{:x}", dest, address),
+            Instruction::LDHU { dest, address } => write!(f, "LDHU R{} 0x{:x}", dest, address),
+            Instruction::STB { src, address } => write!(f, "STB R{} 0x{:x}", src, address),
+            Instruction::STH { src, address } => write!(f, "STH R{} 0x{:x}", src, address),
+            Instruction::MULU { dest, reg1, reg2 } => {
+                write!(f, "MULU R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::DIVU { dest, reg1, reg2 } => {
+                write!(f, "DIVU R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::MODU { dest, reg1, reg2 } => {
+                write!(f, "MODU R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::CMPU { reg1, reg2 } => write!(f, "CMPU R{} R{}", reg1, reg2),
+            Instruction::JMPB { address } => write!(f, "JMPB 0x{:x}", address),
+            Instruction::JMPBE { address } => write!(f, "JMPBE 0x{:x}", address),
+            Instruction::JMPA { address } => write!(f, "JMPA 0x{:x}", address),
+            Instruction::JMPAE { address } => write!(f, "JMPAE 0x{:x}", address),
+            Instruction::JMPLT { address } => write!(f, "JMPLT 0x{:x}", address),
+            Instruction::JMPLE { address } => write!(f, "JMPLE 0x{:x}", address),
+            Instruction::JMPGT { address } => write!(f, "JMPGT 0x{:x}", address),
+            Instruction::JMPGE { address } => write!(f, "JMPGE 0x{:x}", address),
+            Instruction::JMPC { address } => write!(f, "JMPC 0x{:x}", address),
+            Instruction::JMPNC { address } => write!(f, "JMPNC 0x{:x}", address),
+            Instruction::JMPNZ { address } => write!(f, "JMPNZ 0x{:x}", address),
+            Instruction::JMPO { address } => write!(f, "JMPO 0x{:x}", address),
+            Instruction::CMOVZ { dest, src } => write!(f, "CMOVZ R{} R{}", dest, src),
+            Instruction::CMOVN { dest, src } => write!(f, "CMOVN R{} R{}", dest, src),
+            Instruction::MIN { dest, reg1, reg2 } => {
+                write!(f, "MIN R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::MAX { dest, reg1, reg2 } => {
+                write!(f, "MAX R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::MINU { dest, reg1, reg2 } => {
+                write!(f, "MINU R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::MAXU { dest, reg1, reg2 } => {
+                write!(f, "MAXU R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::POPCNT { dest, reg } => write!(f, "POPCNT R{} R{}", dest, reg),
+            Instruction::CLZ { dest, reg } => write!(f, "CLZ R{} R{}", dest, reg),
+            Instruction::CTZ { dest, reg } => write!(f, "CTZ R{} R{}", dest, reg),
+            Instruction::BT { reg1, reg2 } => write!(f, "BT R{} R{}", reg1, reg2),
+            Instruction::BTI { reg, bit } => write!(f, "BTI R{} {}", reg, bit),
+            Instruction::BS { reg1, reg2 } => write!(f, "BS R{} R{}", reg1, reg2),
+            Instruction::BSI { reg, bit } => write!(f, "BSI R{} {}", reg, bit),
+            Instruction::BC { reg1, reg2 } => write!(f, "BC R{} R{}", reg1, reg2),
+            Instruction::BCI { reg, bit } => write!(f, "BCI R{} {}", reg, bit),
+            Instruction::BTG { reg1, reg2 } => write!(f, "BTG R{} R{}", reg1, reg2),
+            Instruction::BTGI { reg, bit } => write!(f, "BTGI R{} {}", reg, bit),
+            Instruction::PUSH { value } => write!(f, "PUSH {}", value),
+            Instruction::DUP => write!(f, "DUP"),
+            Instruction::SWAP => write!(f, "SWAP"),
+            Instruction::DROP => write!(f, "DROP"),
+            Instruction::PUSHA => write!(f, "PUSHA"),
+            Instruction::POPA => write!(f, "POPA"),
+            Instruction::FMOV { dest, value } => {
+                write!(f, "FMOV F{} {}", dest, f32::from_bits(*value))
+            }
+            Instruction::FADD { dest, reg1, reg2 } => {
+                write!(f, "FADD F{} F{} F{}", dest, reg1, reg2)
+            }
+            Instruction::FSUB { dest, reg1, reg2 } => {
+                write!(f, "FSUB F{} F{} F{}", dest, reg1, reg2)
+            }
+            Instruction::FMUL { dest, reg1, reg2 } => {
+                write!(f, "FMUL F{} F{} F{}", dest, reg1, reg2)
+            }
+            Instruction::FDIV { dest, reg1, reg2 } => {
+                write!(f, "FDIV F{} F{} F{}", dest, reg1, reg2)
+            }
+            Instruction::FCMP { reg1, reg2 } => write!(f, "FCMP F{} F{}", reg1, reg2),
+            Instruction::ITOF { dest, src } => write!(f, "ITOF F{} R{}", dest, src),
+            Instruction::FTOI { dest, src } => write!(f, "FTOI R{} F{}", dest, src),
+            Instruction::MULH { dest, reg1, reg2 } => {
+                write!(f, "MULH R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::UMULH { dest, reg1, reg2 } => {
+                write!(f, "UMULH R{} R{} R{}", dest, reg1, reg2)
+            }
+            Instruction::CAS { expected, new, reg } => {
+                write!(f, "CAS R{} R{} [R{}]", expected, new, reg)
+            }
+            Instruction::XADD { src, reg } => write!(f, "XADD R{} [R{}]", src, reg),
+            Instruction::SPAWN { dest, address } => write!(f, "SPAWN R{} 0x{:x}", dest, address),
+            Instruction::YIELD => write!(f, "YIELD"),
+            Instruction::JOIN { reg } => write!(f, "JOIN R{}", reg),
+            Instruction::MMUON { reg } => write!(f, "MMUON R{}", reg),
+            Instruction::MMUOFF => write!(f, "MMUOFF"),
+            Instruction::TLBFLUSH => write!(f, "TLBFLUSH"),
+            Instruction::RDFAULT { dest } => write!(f, "RDFAULT R{}", dest),
+            Instruction::RDSP { dest } => write!(f, "RDSP R{}", dest),
+            Instruction::WRSP { src } => write!(f, "WRSP R{}", src),
+            Instruction::ENTER { size } => write!(f, "ENTER {}", size),
+            Instruction::LEAVE => write!(f, "LEAVE"),
+            Instruction::LDF { dest, offset } => write!(f, "LDF R{} {}", dest, offset),
+            Instruction::STF { src, offset } => write!(f, "STF R{} {}", src, offset),
+            Instruction::RDFP { dest } => write!(f, "RDFP R{}", dest),
+        }
+    }
+}
+
+impl<D, A> Instruction<D, A> {
+    /// Get the target of a conditional jump, `None` for the other instructions.
+    pub fn conditional_target(&self) -> Option<&A> {
+        match self {
+            Instruction::JMPN { address }
+            | Instruction::JMPP { address }
+            | Instruction::JMPZ { address }
+            | Instruction::JMPNZ { address }
+            | Instruction::JMPB { address }
+            | Instruction::JMPBE { address }
+            | Instruction::JMPA { address }
+            | Instruction::JMPAE { address }
+            | Instruction::JMPLT { address }
+            | Instruction::JMPLE { address }
+            | Instruction::JMPGT { address }
+            | Instruction::JMPGE { address }
+            | Instruction::JMPC { address }
+            | Instruction::JMPNC { address }
+            | Instruction::JMPO { address } => Some(address),
+            _ => None,
+        }
+    }
+
+    /// Get the target of a jump, a call or a spawn, `None` for the other instructions.
+    pub fn jump_target(&self) -> Option<&A> {
+        match self {
+            Instruction::JMP { address }
+            | Instruction::CALL { address }
+            | Instruction::SPAWN { address, .. } => Some(address),
+            _ => self.conditional_target(),
+        }
+    }
+
+    /// Get the target of a jump, a call or a spawn mutably, `None` for the other instructions.
+    pub fn jump_target_mut(&mut self) -> Option<&mut A> {
+        match self {
+            Instruction::JMP { address }
+            | Instruction::CALL { address }
+            | Instruction::SPAWN { address, .. }
+            | Instruction::JMPN { address }
+            | Instruction::JMPP { address }
+            | Instruction::JMPZ { address }
+            | Instruction::JMPNZ { address }
+            | Instruction::JMPB { address }
+            | Instruction::JMPBE { address }
+            | Instruction::JMPA { address }
+            | Instruction::JMPAE { address }
+            | Instruction::JMPLT { address }
+            | Instruction::JMPLE { address }
+            | Instruction::JMPGT { address }
+            | Instruction::JMPGE { address }
+            | Instruction::JMPC { address }
+            | Instruction::JMPNC { address }
+            | Instruction::JMPO { address } => Some(address),
+            _ => None,
+        }
+    }
+
+    /// Get the opcode identifying the instruction in the bytecode.
+    pub fn opcode(&self) -> OpCode {
+        match self {
+            Instruction::NOP => OpCode::NOP,
+            Instruction::MOV { .. } => OpCode::MOV,
+            Instruction::LD { .. } => OpCode::LD,
+            Instruction::ST { .. } => OpCode::ST,
+            Instruction::AND { .. } => OpCode::AND,
+            Instruction::OR { .. } => OpCode::OR,
+            Instruction::XOR { .. } => OpCode::XOR,
+            Instruction::NOT { .. } => OpCode::NOT,
+            Instruction::CMP { .. } => OpCode::CMP,
+            Instruction::ADD { .. } => OpCode::ADD,
+            Instruction::SUB { .. } => OpCode::SUB,
+            Instruction::ADC { .. } => OpCode::ADC,
+            Instruction::SBB { .. } => OpCode::SBB,
+            Instruction::MULT { .. } => OpCode::MULT,
+            Instruction::DIV { .. } => OpCode::DIV,
+            Instruction::MOD { .. } => OpCode::MOD,
+            Instruction::FXMUL { .. } => OpCode::FXMUL,
+            Instruction::FXDIV { .. } => OpCode::FXDIV,
+            Instruction::INC { .. } => OpCode::INC,
+            Instruction::DEC { .. } => OpCode::DEC,
+            Instruction::PUSHREG { .. } => OpCode::PUSHREG,
+            Instruction::POPREG { .. } => OpCode::POPREG,
+            Instruction::JMP { .. } => OpCode::JMP,
+            Instruction::JMPN { .. } => OpCode::JMPN,
+            Instruction::JMPP { .. } => OpCode::JMPP,
+            Instruction::JMPZ { .. } => OpCode::JMPZ,
+            Instruction::CALL { .. } => OpCode::CALL,
+            Instruction::RET => OpCode::RET,
+            Instruction::CLF => OpCode::CLF,
+            Instruction::PUSHF => OpCode::PUSHF,
+            Instruction::POPF => OpCode::POPF,
+            Instruction::HLT => OpCode::HLT,
+            Instruction::EXIT { .. } => OpCode::EXIT,
+            Instruction::RDCNT { .. } => OpCode::RDCNT,
+            Instruction::RDCYCLE { .. } => OpCode::RDCYCLE,
+            Instruction::SYSCALL { .. } => OpCode::SYSCALL,
+            Instruction::IN { .. } => OpCode::IN,
+            Instruction::OUT { .. } => OpCode::OUT,
+            Instruction::EI => OpCode::EI,
+            Instruction::DI => OpCode::DI,
+            Instruction::IRET => OpCode::IRET,
+            Instruction::SHL { .. } => OpCode::SHL,
+            Instruction::SHLI { .. } => OpCode::SHLI,
+            Instruction::SHR { .. } => OpCode::SHR,
+            Instruction::SHRI { .. } => OpCode::SHRI,
+            Instruction::SAR { .. } => OpCode::SAR,
+            Instruction::SARI { .. } => OpCode::SARI,
+            Instruction::ROL { .. } => OpCode::ROL,
+            Instruction::ROR { .. } => OpCode::ROR,
+            Instruction::MOVR { .. } => OpCode::MOVR,
+            Instruction::LDR { .. } => OpCode::LDR,
+            Instruction::STR { .. } => OpCode::STR,
+            Instruction::LDB { .. } => OpCode::LDB,
+            Instruction::LDBU { .. } => OpCode::LDBU,
+            Instruction::LDH { .. } => OpCode::LDH,
+            Instruction::LDHU { .. } => OpCode::LDHU,
+            Instruction::STB { .. } => OpCode::STB,
+            Instruction::STH { .. } => OpCode::STH,
+            Instruction::MULU { .. } => OpCode::MULU,
+            Instruction::DIVU { .. } => OpCode::DIVU,
+            Instruction::MODU { .. } => OpCode::MODU,
+            Instruction::CMPU { .. } => OpCode::CMPU,
+            Instruction::JMPB { .. } => OpCode::JMPB,
+            Instruction::JMPBE { .. } => OpCode::JMPBE,
+            Instruction::JMPA { .. } => OpCode::JMPA,
+            Instruction::JMPAE { .. } => OpCode::JMPAE,
+            Instruction::JMPLT { .. } => OpCode::JMPLT,
+            Instruction::JMPLE { .. } => OpCode::JMPLE,
+            Instruction::JMPGT { .. } => OpCode::JMPGT,
+            Instruction::JMPGE { .. } => OpCode::JMPGE,
+            Instruction::JMPC { .. } => OpCode::JMPC,
+            Instruction::JMPNC { .. } => OpCode::JMPNC,
+            Instruction::JMPNZ { .. } => OpCode::JMPNZ,
+            Instruction::JMPO { .. } => OpCode::JMPO,
+            Instruction::CMOVZ { .. } => OpCode::CMOVZ,
+            Instruction::CMOVN { .. } => OpCode::CMOVN,
+            Instruction::MIN { .. } => OpCode::MIN,
+            Instruction::MAX { .. } => OpCode::MAX,
+            Instruction::MINU { .. } => OpCode::MINU,
+            Instruction::MAXU { .. } => OpCode::MAXU,
+            Instruction::POPCNT { .. } => OpCode::POPCNT,
+            Instruction::CLZ { .. } => OpCode::CLZ,
+            Instruction::CTZ { .. } => OpCode::CTZ,
+            Instruction::BT { .. } => OpCode::BT,
+            Instruction::BTI { .. } => OpCode::BTI,
+            Instruction::BS { .. } => OpCode::BS,
+            Instruction::BSI { .. } => OpCode::BSI,
+            Instruction::BC { .. } => OpCode::BC,
+            Instruction::BCI { .. } => OpCode::BCI,
+            Instruction::BTG { .. } => OpCode::BTG,
+            Instruction::BTGI { .. } => OpCode::BTGI,
+            Instruction::PUSH { .. } => OpCode::PUSH,
+            Instruction::DUP => OpCode::DUP,
+            Instruction::SWAP => OpCode::SWAP,
+            Instruction::DROP => OpCode::DROP,
+            Instruction::PUSHA => OpCode::PUSHA,
+            Instruction::POPA => OpCode::POPA,
+            Instruction::FMOV { .. } => OpCode::FMOV,
+            Instruction::FADD { .. } => OpCode::FADD,
+            Instruction::FSUB { .. } => OpCode::FSUB,
+            Instruction::FMUL { .. } => OpCode::FMUL,
+            Instruction::FDIV { .. } => OpCode::FDIV,
+            Instruction::FCMP { .. } => OpCode::FCMP,
+            Instruction::ITOF { .. } => OpCode::ITOF,
+            Instruction::FTOI { .. } => OpCode::FTOI,
+            Instruction::MULH { .. } => OpCode::MULH,
+            Instruction::UMULH { .. } => OpCode::UMULH,
+            Instruction::CAS { .. } => OpCode::CAS,
+            Instruction::XADD { .. } => OpCode::XADD,
+            Instruction::SPAWN { .. } => OpCode::SPAWN,
+            Instruction::YIELD => OpCode::YIELD,
+            Instruction::JOIN { .. } => OpCode::JOIN,
+            Instruction::MMUON { .. } => OpCode::MMUON,
+            Instruction::MMUOFF => OpCode::MMUOFF,
+            Instruction::TLBFLUSH => OpCode::TLBFLUSH,
+            Instruction::RDFAULT { .. } => OpCode::RDFAULT,
+            Instruction::RDSP { .. } => OpCode::RDSP,
+            Instruction::WRSP { .. } => OpCode::WRSP,
+            Instruction::ENTER { .. } => OpCode::ENTER,
+            Instruction::LEAVE => OpCode::LEAVE,
+            Instruction::LDF { .. } => OpCode::LDF,
+            Instruction::STF { .. } => OpCode::STF,
+            Instruction::RDFP { .. } => OpCode::RDFP,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::NOP => 1,
+            Instruction::MOV { .. } => 2 + std::mem::size_of::<D>(),
+            Instruction::LD { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::ST { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::AND { .. } => 4,
+            Instruction::OR { .. } => 4,
+            Instruction::XOR { .. } => 4,
+            Instruction::NOT { .. } => 3,
+            Instruction::CMP { .. } => 3,
+            Instruction::ADD { .. } => 4,
+            Instruction::SUB { .. } => 4,
+            Instruction::ADC { .. } | Instruction::SBB { .. } => 4,
+            Instruction::MULT { .. } => 4,
+            Instruction::DIV { .. } => 4,
+            Instruction::MOD { .. } => 4,
+            Instruction::FXMUL { .. } | Instruction::FXDIV { .. } => 4,
+            Instruction::INC { .. } => 2,
+            Instruction::DEC { .. } => 2,
+            Instruction::PUSHREG { .. } => 2,
+            Instruction::POPREG { .. } => 2,
+            Instruction::JMP { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPN { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPP { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPZ { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::CALL { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::RET => 1,
+            Instruction::CLF | Instruction::PUSHF | Instruction::POPF => 1,
+            Instruction::RDCNT { .. } => 3,
+            Instruction::RDCYCLE { .. } => 2,
+            Instruction::HLT => 1,
+            Instruction::EXIT { .. } => 1 + std::mem::size_of::<D>(),
+            Instruction::SYSCALL { .. } => 2,
+            Instruction::IN { .. } | Instruction::OUT { .. } => 4,
+            Instruction::EI => 1,
+            Instruction::DI => 1,
+            Instruction::IRET => 1,
+            Instruction::SHL { .. } => 4,
+            Instruction::SHLI { .. } => 4,
+            Instruction::SHR { .. } => 4,
+            Instruction::SHRI { .. } => 4,
+            Instruction::SAR { .. } => 4,
+            Instruction::SARI { .. } => 4,
+            Instruction::ROL { .. } => 4,
+            Instruction::ROR { .. } => 4,
+            Instruction::MOVR { .. } => 3,
+            Instruction::LDR { .. } => 3,
+            Instruction::STR { .. } => 3,
+            Instruction::LDB { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::LDBU { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::LDH { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::LDHU { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::STB { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::STH { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::MULU { .. } => 4,
+            Instruction::DIVU { .. } => 4,
+            Instruction::MODU { .. } => 4,
+            Instruction::CMPU { .. } => 3,
+            Instruction::JMPB { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPBE { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPA { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPAE { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPLT { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPLE { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPGT { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPGE { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPC { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPNC { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPNZ { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::JMPO { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::CMOVZ { .. } => 3,
+            Instruction::CMOVN { .. } => 3,
+            Instruction::MIN { .. } => 4,
+            Instruction::MAX { .. } => 4,
+            Instruction::MINU { .. } => 4,
+            Instruction::MAXU { .. } => 4,
+            Instruction::POPCNT { .. } => 3,
+            Instruction::CLZ { .. } => 3,
+            Instruction::CTZ { .. } => 3,
+            Instruction::BT { .. } => 3,
+            Instruction::BTI { .. } => 3,
+            Instruction::BS { .. } => 3,
+            Instruction::BSI { .. } => 3,
+            Instruction::BC { .. } => 3,
+            Instruction::BCI { .. } => 3,
+            Instruction::BTG { .. } => 3,
+            Instruction::BTGI { .. } => 3,
+            Instruction::PUSH { .. } => 1 + std::mem::size_of::<D>(),
+            Instruction::DUP => 1,
+            Instruction::SWAP => 1,
+            Instruction::DROP => 1,
+            Instruction::PUSHA => 1,
+            Instruction::POPA => 1,
+            Instruction::FMOV { .. } => 6,
+            Instruction::FADD { .. } => 4,
+            Instruction::FSUB { .. } => 4,
+            Instruction::FMUL { .. } => 4,
+            Instruction::FDIV { .. } => 4,
+            Instruction::FCMP { .. } => 3,
+            Instruction::ITOF { .. } => 3,
+            Instruction::FTOI { .. } => 3,
+            Instruction::MULH { .. } => 4,
+            Instruction::UMULH { .. } => 4,
+            Instruction::CAS { .. } => 4,
+            Instruction::XADD { .. } => 3,
+            Instruction::SPAWN { .. } => 2 + std::mem::size_of::<A>(),
+            Instruction::YIELD => 1,
+            Instruction::JOIN { .. } => 2,
+            Instruction::MMUON { .. } => 2,
+            Instruction::MMUOFF => 1,
+            Instruction::TLBFLUSH => 1,
+            Instruction::RDFAULT { .. } => 2,
+            Instruction::RDSP { .. } => 2,
+            Instruction::WRSP { .. } => 2,
+            Instruction::ENTER { .. } => 1 + std::mem::size_of::<A>(),
+            Instruction::LEAVE => 1,
+            Instruction::LDF { .. } => 2 + std::mem::size_of::<D>(),
+            Instruction::STF { .. } => 2 + std::mem::size_of::<D>(),
+            Instruction::RDFP { .. } => 2,
+        }
+    }
+}
+
+/// Enumeration of all possible opcodes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpCode {
+    NOP = 0x0,
+    MOV = 0x1,
+    LD = 0x2,
+    ST = 0x3,
+    AND = 0x4,
+    OR = 0x5,
+    XOR = 0x6,
+    NOT = 0x7,
+    CMP = 0x8,
+    ADD = 0x9,
+    SUB = 0xA,
+    MULT = 0xB,
+    DIV = 0xC,
+    MOD = 0xD,
+    INC = 0xE,
+    DEC = 0xF,
+    PUSHREG = 0x10,
+    POPREG = 0x11,
+    JMP = 0x12,
+    JMPN = 0x13,
+    JMPP = 0x14,
+    JMPZ = 0x15,
+    CALL = 0x16,
+    RET = 0x17,
+    CLF = 0x18,
+    RDCNT = 0x19,
+    SYSCALL = 0x1A,
+    EI = 0x1B,
+    DI = 0x1C,
+    IRET = 0x1D,
+    SHL = 0x1E,
+    SHLI = 0x1F,
+    SHR = 0x20,
+    SHRI = 0x21,
+    SAR = 0x22,
+    SARI = 0x23,
+    ROL = 0x24,
+    ROR = 0x25,
+    MOVR = 0x26,
+    LDR = 0x27,
+    STR = 0x28,
+    LDB = 0x29,
+    LDBU = 0x2A,
+    LDH = 0x2B,
+    LDHU = 0x2C,
+    STB = 0x2D,
+    STH = 0x2E,
+    MULU = 0x2F,
+    DIVU = 0x30,
+    MODU = 0x31,
+    CMPU = 0x32,
+    JMPB = 0x33,
+    JMPBE = 0x34,
+    JMPA = 0x35,
+    JMPAE = 0x36,
+    JMPLT = 0x37,
+    JMPLE = 0x38,
+    JMPGT = 0x39,
+    JMPGE = 0x3A,
+    JMPC = 0x3B,
+    JMPNC = 0x3C,
+    JMPNZ = 0x3D,
+    JMPO = 0x3E,
+    CMOVZ = 0x3F,
+    CMOVN = 0x40,
+    MIN = 0x41,
+    MAX = 0x42,
+    MINU = 0x43,
+    MAXU = 0x44,
+    POPCNT = 0x45,
+    CLZ = 0x46,
+    CTZ = 0x47,
+    BT = 0x48,
+    BTI = 0x49,
+    BS = 0x4A,
+    BSI = 0x4B,
+    BC = 0x4C,
+    BCI = 0x4D,
+    BTG = 0x4E,
+    BTGI = 0x4F,
+    PUSH = 0x50,
+    DUP = 0x51,
+    SWAP = 0x52,
+    DROP = 0x53,
+    PUSHA = 0x54,
+    POPA = 0x55,
+    FMOV = 0x56,
+    FADD = 0x57,
+    FSUB = 0x58,
+    FMUL = 0x59,
+    FDIV = 0x5A,
+    FCMP = 0x5B,
+    ITOF = 0x5C,
+    FTOI = 0x5D,
+    MULH = 0x5E,
+    UMULH = 0x5F,
+    CAS = 0x60,
+    XADD = 0x61,
+    SPAWN = 0x62,
+    YIELD = 0x63,
+    JOIN = 0x64,
+    MMUON = 0x65,
+    MMUOFF = 0x66,
+    TLBFLUSH = 0x67,
+    RDFAULT = 0x68,
+    RDSP = 0x69,
+    WRSP = 0x6A,
+    ENTER = 0x6B,
+    LEAVE = 0x6C,
+    LDF = 0x6D,
+    STF = 0x6E,
+    RDFP = 0x6F,
+    EXIT = 0x70,
+    IN = 0x71,
+    OUT = 0x72,
+    RDCYCLE = 0x73,
+    PUSHF = 0x74,
+    POPF = 0x75,
+    ADC = 0x76,
+    SBB = 0x77,
+    FXMUL = 0x78,
+    FXDIV = 0x79,
+    HLT = 0xFF,
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = VmError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0x0 => Ok(OpCode::NOP),
+            0x1 => Ok(OpCode::MOV),
+            0x2 => Ok(OpCode::LD),
+            0x3 => Ok(OpCode::ST),
+            0x4 => Ok(OpCode::AND),
+            0x5 => Ok(OpCode::OR),
+            0x6 => Ok(OpCode::XOR),
+            0x7 => Ok(OpCode::NOT),
+            0x8 => Ok(OpCode::CMP),
+            0x9 => Ok(OpCode::ADD),
+            0xA => Ok(OpCode::SUB),
+            0xB => Ok(OpCode::MULT),
+            0xC => Ok(OpCode::DIV),
+            0xD => Ok(OpCode::MOD),
+            0xE => Ok(OpCode::INC),
+            0xF => Ok(OpCode::DEC),
+            0x10 => Ok(OpCode::PUSHREG),
+            0x11 => Ok(OpCode::POPREG),
+            0x12 => Ok(OpCode::JMP),
+            0x13 => Ok(OpCode::JMPN),
+            0x14 => Ok(OpCode::JMPP),
+            0x15 => Ok(OpCode::JMPZ),
+            0x16 => Ok(OpCode::CALL),
+            0x17 => Ok(OpCode::RET),
+            0x18 => Ok(OpCode::CLF),
+            0x19 => Ok(OpCode::RDCNT),
+            0x1A => Ok(OpCode::SYSCALL),
+            0x1B => Ok(OpCode::EI),
+            0x1C => Ok(OpCode::DI),
+            0x1D => Ok(OpCode::IRET),
+            0x1E => Ok(OpCode::SHL),
+            0x1F => Ok(OpCode::SHLI),
+            0x20 => Ok(OpCode::SHR),
+            0x21 => Ok(OpCode::SHRI),
+            0x22 => Ok(OpCode::SAR),
+            0x23 => Ok(OpCode::SARI),
+            0x24 => Ok(OpCode::ROL),
+            0x25 => Ok(OpCode::ROR),
+            0x26 => Ok(OpCode::MOVR),
+            0x27 => Ok(OpCode::LDR),
+            0x28 => Ok(OpCode::STR),
+            0x29 => Ok(OpCode::LDB),
+            0x2A => Ok(OpCode::LDBU),
+            0x2B => Ok(OpCode::LDH),
+            0x2C => Ok(OpCode::LDHU),
+            0x2D => Ok(OpCode::STB),
+            0x2E => Ok(OpCode::STH),
+            0x2F => Ok(OpCode::MULU),
+            0x30 => Ok(OpCode::DIVU),
+            0x31 => Ok(OpCode::MODU),
+            0x32 => Ok(OpCode::CMPU),
+            0x33 => Ok(OpCode::JMPB),
+            0x34 => Ok(OpCode::JMPBE),
+            0x35 => Ok(OpCode::JMPA),
+            0x36 => Ok(OpCode::JMPAE),
+            0x37 => Ok(OpCode::JMPLT),
+            0x38 => Ok(OpCode::JMPLE),
+            0x39 => Ok(OpCode::JMPGT),
+            0x3A => Ok(OpCode::JMPGE),
+            0x3B => Ok(OpCode::JMPC),
+            0x3C => Ok(OpCode::JMPNC),
+            0x3D => Ok(OpCode::JMPNZ),
+            0x3E => Ok(OpCode::JMPO),
+            0x3F => Ok(OpCode::CMOVZ),
+            0x40 => Ok(OpCode::CMOVN),
+            0x41 => Ok(OpCode::MIN),
+            0x42 => Ok(OpCode::MAX),
+            0x43 => Ok(OpCode::MINU),
+            0x44 => Ok(OpCode::MAXU),
+            0x45 => Ok(OpCode::POPCNT),
+            0x46 => Ok(OpCode::CLZ),
+            0x47 => Ok(OpCode::CTZ),
+            0x48 => Ok(OpCode::BT),
+            0x49 => Ok(OpCode::BTI),
+            0x4A => Ok(OpCode::BS),
+            0x4B => Ok(OpCode::BSI),
+            0x4C => Ok(OpCode::BC),
+            0x4D => Ok(OpCode::BCI),
+            0x4E => Ok(OpCode::BTG),
+            0x4F => Ok(OpCode::BTGI),
+            0x50 => Ok(OpCode::PUSH),
+            0x51 => Ok(OpCode::DUP),
+            0x52 => Ok(OpCode::SWAP),
+            0x53 => Ok(OpCode::DROP),
+            0x54 => Ok(OpCode::PUSHA),
+            0x55 => Ok(OpCode::POPA),
+            0x56 => Ok(OpCode::FMOV),
+            0x57 => Ok(OpCode::FADD),
+            0x58 => Ok(OpCode::FSUB),
+            0x59 => Ok(OpCode::FMUL),
+            0x5A => Ok(OpCode::FDIV),
+            0x5B => Ok(OpCode::FCMP),
+            0x5C => Ok(OpCode::ITOF),
+            0x5D => Ok(OpCode::FTOI),
+            0x5E => Ok(OpCode::MULH),
+            0x5F => Ok(OpCode::UMULH),
+            0x60 => Ok(OpCode::CAS),
+            0x61 => Ok(OpCode::XADD),
+            0x62 => Ok(OpCode::SPAWN),
+            0x63 => Ok(OpCode::YIELD),
+            0x64 => Ok(OpCode::JOIN),
+            0x65 => Ok(OpCode::MMUON),
+            0x66 => Ok(OpCode::MMUOFF),
+            0x67 => Ok(OpCode::TLBFLUSH),
+            0x68 => Ok(OpCode::RDFAULT),
+            0x69 => Ok(OpCode::RDSP),
+            0x6A => Ok(OpCode::WRSP),
+            0x6B => Ok(OpCode::ENTER),
+            0x6C => Ok(OpCode::LEAVE),
+            0x6D => Ok(OpCode::LDF),
+            0x6E => Ok(OpCode::STF),
+            0x6F => Ok(OpCode::RDFP),
+            0x70 => Ok(OpCode::EXIT),
+            0x71 => Ok(OpCode::IN),
+            0x72 => Ok(OpCode::OUT),
+            0x73 => Ok(OpCode::RDCYCLE),
+            0x74 => Ok(OpCode::PUSHF),
+            0x75 => Ok(OpCode::POPF),
+            0x76 => Ok(OpCode::ADC),
+            0x77 => Ok(OpCode::SBB),
+            0x78 => Ok(OpCode::FXMUL),
+            0x79 => Ok(OpCode::FXDIV),
+            0xFF => Ok(OpCode::HLT),
+            _ => Err(VmError::InvalidOpcode { opcode: value }),
+        }
+    }
+}
+
+impl From<OpCode> for u8 {
+    fn from(opcode: OpCode) -> u8 {
+        opcode as u8
+    }
+}
+
+impl OpCode {
+    pub fn size<D, T>(&self) -> usize {
+        match self {
+            OpCode::NOP => 1,
+            OpCode::MOV => 2 + std::mem::size_of::<D>(),
+            OpCode::LD => 2 + std::mem::size_of::<T>(),
+            OpCode::ST => 2 + std::mem::size_of::<T>(),
+            OpCode::AND => 4,
+            OpCode::OR => 4,
+            OpCode::XOR => 4,
+            OpCode::NOT => 3,
+            OpCode::CMP => 3,
+            OpCode::ADD => 4,
+            OpCode::SUB => 4,
+            OpCode::ADC | OpCode::SBB => 4,
+            OpCode::MULT => 4,
+            OpCode::DIV => 4,
+            OpCode::MOD => 4,
+            OpCode::FXMUL | OpCode::FXDIV => 4,
+            OpCode::INC => 2,
+            OpCode::DEC => 2,
+            OpCode::PUSHREG => 2,
+            OpCode::POPREG => 2,
+            OpCode::JMP => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPN => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPP => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPZ => 1 + std::mem::size_of::<T>(),
+            OpCode::CALL => 1 + std::mem::size_of::<T>(),
+            OpCode::RET => 1,
+            OpCode::CLF | OpCode::PUSHF | OpCode::POPF => 1,
+            OpCode::RDCNT => 3,
+            OpCode::RDCYCLE => 2,
+            OpCode::HLT => 1,
+            OpCode::EXIT => 1 + std::mem::size_of::<D>(),
+            OpCode::SYSCALL => 2,
+            OpCode::IN => 4,
+            OpCode::OUT => 4,
+            OpCode::EI => 1,
+            OpCode::DI => 1,
+            OpCode::IRET => 1,
+            OpCode::SHL => 4,
+            OpCode::SHLI => 4,
+            OpCode::SHR => 4,
+            OpCode::SHRI => 4,
+            OpCode::SAR => 4,
+            OpCode::SARI => 4,
+            OpCode::ROL => 4,
+            OpCode::ROR => 4,
+            OpCode::MOVR => 3,
+            OpCode::LDR => 3,
+            OpCode::STR => 3,
+            OpCode::LDB => 2 + std::mem::size_of::<T>(),
+            OpCode::LDBU => 2 + std::mem::size_of::<T>(),
+            OpCode::LDH => 2 + std::mem::size_of::<T>(),
+            OpCode::LDHU => 2 + std::mem::size_of::<T>(),
+            OpCode::STB => 2 + std::mem::size_of::<T>(),
+            OpCode::STH => 2 + std::mem::size_of::<T>(),
+            OpCode::MULU => 4,
+            OpCode::DIVU => 4,
+            OpCode::MODU => 4,
+            OpCode::CMPU => 3,
+            OpCode::JMPB => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPBE => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPA => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPAE => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPLT => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPLE => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPGT => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPGE => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPC => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPNC => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPNZ => 1 + std::mem::size_of::<T>(),
+            OpCode::JMPO => 1 + std::mem::size_of::<T>(),
+            OpCode::CMOVZ => 3,
+            OpCode::CMOVN => 3,
+            OpCode::MIN => 4,
+            OpCode::MAX => 4,
+            OpCode::MINU => 4,
+            OpCode::MAXU => 4,
+            OpCode::POPCNT => 3,
+            OpCode::CLZ => 3,
+            OpCode::CTZ => 3,
+            OpCode::BT => 3,
+            OpCode::BTI => 3,
+            OpCode::BS => 3,
+            OpCode::BSI => 3,
+            OpCode::BC => 3,
+            OpCode::BCI => 3,
+            OpCode::BTG => 3,
+            OpCode::BTGI => 3,
+            OpCode::PUSH => 1 + std::mem::size_of::<T>(),
+            OpCode::DUP => 1,
+            OpCode::SWAP => 1,
+            OpCode::DROP => 1,
+            OpCode::PUSHA => 1,
+            OpCode::POPA => 1,
+            OpCode::FMOV => 6,
+            OpCode::FADD => 4,
+            OpCode::FSUB => 4,
+            OpCode::FMUL => 4,
+            OpCode::FDIV => 4,
+            OpCode::FCMP => 3,
+            OpCode::ITOF => 3,
+            OpCode::FTOI => 3,
+            OpCode::MULH => 4,
+            OpCode::UMULH => 4,
+            OpCode::CAS => 4,
+            OpCode::XADD => 3,
+            OpCode::SPAWN => 2 + std::mem::size_of::<T>(),
+            OpCode::YIELD => 1,
+            OpCode::JOIN => 2,
+            OpCode::MMUON => 2,
+            OpCode::MMUOFF => 1,
+            OpCode::TLBFLUSH => 1,
+            OpCode::RDFAULT => 2,
+            OpCode::RDSP => 2,
+            OpCode::WRSP => 2,
+            OpCode::ENTER => 1 + std::mem::size_of::<T>(),
+            OpCode::LEAVE => 1,
+            OpCode::LDF => 2 + std::mem::size_of::<D>(),
+            OpCode::STF => 2 + std::mem::size_of::<D>(),
+            OpCode::RDFP => 2,
+        }
+    }
+}
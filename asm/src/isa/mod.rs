@@ -0,0 +1,16 @@
+//! The instruction set of ForgeVM, re-exported by `forge_vm::vm`.
+
+pub mod counters;
+pub mod debug_info;
+pub mod decoder;
+pub mod encoder;
+pub mod error;
+pub mod fuzz;
+pub mod hardware_config;
+pub mod instructions;
+pub mod pod;
+pub mod program;
+pub mod protection;
+pub mod verifier;
+pub mod watchpoint;
+pub mod word;
@@ -0,0 +1,116 @@
+//! The values read from and written to the memory, see `Pod`.
+
+/// The order of the bytes of the values in the memory and in the bytecode of the guest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Endianness {
+    /// The least significant byte first, the default.
+    #[default]
+    Little,
+    /// The most significant byte first.
+    Big,
+}
+
+/// A plain value stored in the memory as its bytes, as many as its size, in the endianness of
+/// the guest.
+///
+/// The memory converts the values from and to their bytes instead of reinterpreting its content,
+/// so the accesses are defined on every host, whatever its endianness and its alignment rules.
+/// It is implemented for the primitive integer types and the float types.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::pod::Pod;
+/// let mut bytes = [0; 4];
+/// 0x12345678u32.store_le(&mut bytes);
+/// assert_eq!(bytes, [0x78, 0x56, 0x34, 0x12]);
+/// assert_eq!(u32::load_le(&bytes), 0x12345678);
+/// ```
+pub trait Pod: Copy {
+    /// Read a value from its little-endian bytes.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is not exactly the size of the value.
+    fn load_le(bytes: &[u8]) -> Self;
+
+    /// Write the little-endian bytes of the value into `bytes`.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is not exactly the size of the value.
+    fn store_le(self, bytes: &mut [u8]);
+
+    /// Read a value from its big-endian bytes, see `load_le`.
+    fn load_be(bytes: &[u8]) -> Self;
+
+    /// Write the big-endian bytes of the value into `bytes`, see `store_le`.
+    fn store_be(self, bytes: &mut [u8]);
+
+    /// Read a value from its bytes in an endianness, see `load_le`.
+    fn load(bytes: &[u8], endianness: Endianness) -> Self {
+        match endianness {
+            Endianness::Little => Self::load_le(bytes),
+            Endianness::Big => Self::load_be(bytes),
+        }
+    }
+
+    /// Write the bytes of the value in an endianness into `bytes`, see `store_le`.
+    fn store(self, bytes: &mut [u8], endianness: Endianness) {
+        match endianness {
+            Endianness::Little => self.store_le(bytes),
+            Endianness::Big => self.store_be(bytes),
+        }
+    }
+}
+
+macro_rules! impl_pod {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl Pod for $type {
+                fn load_le(bytes: &[u8]) -> Self {
+                    let bytes = bytes.try_into().expect("size of the value");
+                    <$type>::from_le_bytes(bytes)
+                }
+
+                fn store_le(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn load_be(bytes: &[u8]) -> Self {
+                    let bytes = bytes.try_into().expect("size of the value");
+                    <$type>::from_be_bytes(bytes)
+                }
+
+                fn store_be(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_pod!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pod_roundtrip() {
+        let mut bytes = [0; 8];
+        (-2i64).store_le(&mut bytes);
+        assert_eq!(bytes, [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(i64::load_le(&bytes), -2);
+
+        let mut bytes = [0; 4];
+        1.5f32.store_le(&mut bytes);
+        assert_eq!(bytes, 1.5f32.to_le_bytes());
+        assert_eq!(f32::load_le(&bytes), 1.5);
+        assert_eq!(i16::load_le(&[0x34, 0x12]), 0x1234);
+
+        let mut bytes = [0; 4];
+        0x12345678u32.store(&mut bytes, Endianness::Big);
+        assert_eq!(bytes, [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(u32::load(&bytes, Endianness::Big), 0x12345678);
+        assert_eq!(u32::load(&bytes, Endianness::Little), 0x78563412);
+    }
+}
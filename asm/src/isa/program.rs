@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+
+use super::decoder::Decoder;
+use super::error::{Result, VmError};
+use super::verifier::{self, Diagnostic};
+use super::word::Word;
+
+/// A program run by the VM: its code, its entry point and its data segments.
+///
+/// A program is created from raw bytecode with `Program::new`, or from an executable image in
+/// the `.fvm` format with `Program::from_image`. A program borrows the bytes it is created from
+/// instead of copying them, `into_owned` detaches it from these bytes. The `.fvm` format is a header of little-endian
+/// fields followed by the code and the initialized data:
+///
+/// | Offset | Size | Field |
+/// |--------|------|-------|
+/// | `0`  | `4` | Magic number `FVM\0` |
+/// | `4`  | `2` | Version, `1` |
+/// | `6`  | `2` | Reserved, `0` |
+/// | `8`  | `4` | Entry point, offset of the first instruction in the code |
+/// | `12` | `4` | Size of the code segment |
+/// | `16` | `4` | Address of the data segment in the memory |
+/// | `20` | `4` | Size of the initialized data segment |
+/// | `24` | `4` | Size of the BSS segment, zeroed after the data segment |
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::program::Program;
+/// let program = Program::new(&[0x0e, 0x00, 0xff]) // INC R0, HLT
+///     .with_entry(2)
+///     .with_data(0x100, &[1, 2, 3, 4], 12);
+/// let image = program.to_image();
+/// assert_eq!(Program::from_image(&image), Ok(program));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program<'a> {
+    code: Cow<'a, [u8]>,
+    /// The offset of the first instruction in the code.
+    entry: usize,
+    /// The address of the data segment in the memory.
+    data_address: usize,
+    /// The initialized data, copied into the memory at `data_address` before the execution.
+    data: Cow<'a, [u8]>,
+    /// The size of the zeroed segment following the initialized data.
+    bss_size: usize,
+}
+
+impl<'a> Program<'a> {
+    /// The magic number at the start of a `.fvm` image.
+    pub const MAGIC: [u8; 4] = *b"FVM\0";
+    /// The version of the `.fvm` format written by `to_image`.
+    pub const VERSION: u16 = 1;
+    /// The size of the header of a `.fvm` image.
+    pub const HEADER_SIZE: usize = 28;
+
+    /// Create a program from raw bytecode, starting at its first byte, without data.
+    /// The program borrows the bytecode.
+    pub fn new(code: &'a [u8]) -> Self {
+        Program {
+            code: Cow::Borrowed(code),
+            entry: 0,
+            data_address: 0,
+            data: Cow::Borrowed(&[]),
+            bss_size: 0,
+        }
+    }
+
+    /// Copy the code and the data borrowed by the program, to keep it beyond the bytes it was
+    /// created from.
+    pub fn into_owned(self) -> Program<'static> {
+        Program {
+            code: Cow::Owned(self.code.into_owned()),
+            entry: self.entry,
+            data_address: self.data_address,
+            data: Cow::Owned(self.data.into_owned()),
+            bss_size: self.bss_size,
+        }
+    }
+
+    /// Set the entry point of the program, the offset of its first instruction in the code.
+    pub fn with_entry(mut self, entry: usize) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    /// Set the data segments of the program.
+    ///
+    /// # Parameters
+    /// - `address`: The address of the data segment in the memory.
+    /// - `data`: The initialized data, borrowed by the program.
+    /// - `bss_size`: The size of the zeroed segment following the initialized data.
+    pub fn with_data(mut self, address: usize, data: &'a [u8], bss_size: usize) -> Self {
+        self.data_address = address;
+        self.data = Cow::Borrowed(data);
+        self.bss_size = bss_size;
+        self
+    }
+
+    /// Parse an executable image in the `.fvm` format.
+    /// The program borrows its code and its data from the image.
+    ///
+    /// # Errors
+    /// Returns `VmError::InvalidImage` if the image is truncated or has trailing bytes, if its
+    /// magic number or its version is not supported, or if its entry point is outside the code.
+    pub fn from_image(image: &'a [u8]) -> Result<Self> {
+        let header = image
+            .get(..Self::HEADER_SIZE)
+            .ok_or_else(|| VmError::InvalidImage("Truncated header".to_string()))?;
+        if header[0..4] != Self::MAGIC {
+            return Err(VmError::InvalidImage("Invalid magic number".to_string()));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != Self::VERSION {
+            return Err(VmError::InvalidImage(format!(
+                "Unsupported version {}",
+                version
+            )));
+        }
+        let field = |offset: usize| {
+            u32::from_le_bytes([
+                header[offset],
+                header[offset + 1],
+                header[offset + 2],
+                header[offset + 3],
+            ]) as usize
+        };
+        let entry = field(8);
+        let code_size = field(12);
+        let data_address = field(16);
+        let data_size = field(20);
+        let bss_size = field(24);
+
+        let segments = &image[Self::HEADER_SIZE..];
+        if segments.len() != code_size + data_size {
+            return Err(VmError::InvalidImage(format!(
+                "Segments of {} bytes for a code of {} bytes and data of {} bytes",
+                segments.len(),
+                code_size,
+                data_size
+            )));
+        }
+        if code_size > 0 && entry >= code_size {
+            return Err(VmError::InvalidImage(format!(
+                "Entry point 0x{:x} outside the code of {} bytes",
+                entry, code_size
+            )));
+        }
+        let (code, data) = segments.split_at(code_size);
+        Ok(Self::new(code)
+            .with_entry(entry)
+            .with_data(data_address, data, bss_size))
+    }
+
+    /// Write the program as an executable image in the `.fvm` format.
+    /// The sizes and addresses are written as `u32`, larger values are truncated.
+    pub fn to_image(&self) -> Vec<u8> {
+        let mut image = Vec::with_capacity(Self::HEADER_SIZE + self.code.len() + self.data.len());
+        image.extend_from_slice(&Self::MAGIC);
+        image.extend_from_slice(&Self::VERSION.to_le_bytes());
+        image.extend_from_slice(&0u16.to_le_bytes());
+        for field in [
+            self.entry,
+            self.code.len(),
+            self.data_address,
+            self.data.len(),
+            self.bss_size,
+        ] {
+            image.extend_from_slice(&(field as u32).to_le_bytes());
+        }
+        image.extend_from_slice(&self.code);
+        image.extend_from_slice(&self.data);
+        image
+    }
+
+    /// Get the code from `start` to the end of the program.
+    /// The slice is empty when `start` is past the end of the program.
+    pub fn slice_from(&self, start: usize) -> &[u8] {
+        self.code.get(start..).unwrap_or_default()
+    }
+
+    /// Get the code of the program.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Get the entry point, the offset of the first instruction in the code.
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    /// Get the address of the data segment in the memory.
+    pub fn data_address(&self) -> usize {
+        self.data_address
+    }
+
+    /// Get the initialized data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get the size of the zeroed segment following the initialized data.
+    pub fn bss_size(&self) -> usize {
+        self.bss_size
+    }
+
+    pub fn size(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Decode the whole code ahead of the execution and check its instructions, for a CPU with
+    /// the default number of registers: the validity of the opcodes and of the registers, the
+    /// targets of the jumps, calls and spawns landing on the start of an instruction, the entry
+    /// point, and the truncation of the last instruction. The targets are offsets in the code.
+    ///
+    /// # Returns
+    /// The diagnostics with their offset in the code, sorted by offset, empty for a valid program.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::program::Program;
+    /// use forge_vm::vm::verifier::DiagnosticKind;
+    /// let program = Program::new(&[0x0e, 0x00, 0x12, 0x01, 0x00, 0x00, 0x00]); // INC R0, JMP 0x1
+    /// let diagnostics = program.verify::<i32>();
+    /// assert_eq!(diagnostics[0].offset, 2);
+    /// assert_eq!(diagnostics[0].kind, DiagnosticKind::InvalidJumpTarget { target: 1 });
+    /// ```
+    pub fn verify<T: Word>(&self) -> Vec<Diagnostic> {
+        verifier::verify(self, &Decoder::<T>::new())
+    }
+
+    /// Verify the program for a CPU with a specific number of registers, see `verify`.
+    pub fn verify_with_registers<T: Word>(&self, registers_count: u8) -> Vec<Diagnostic> {
+        verifier::verify(self, &Decoder::<T>::with_registers(registers_count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_image_roundtrip() {
+        let program = Program::new(&[0x00, 0x0e, 0x01, 0xff])
+            .with_entry(1)
+            .with_data(0x200, &[0xaa, 0xbb], 8);
+        let image = program.to_image();
+        assert_eq!(&image[..4], b"FVM\0");
+        assert_eq!(image.len(), Program::HEADER_SIZE + 6);
+        assert_eq!(Program::from_image(&image), Ok(program));
+    }
+
+    #[test]
+    fn test_program_borrows_its_bytes() {
+        let code = [0x0e, 0x00, 0x12, 0x00, 0x00, 0x00, 0x00]; // INC R0, JMP 0x0
+        let program = Program::new(&code);
+        assert_eq!(program.code().as_ptr(), code.as_ptr());
+        let image = program.clone().with_data(0x20, &[1, 2], 0).to_image();
+        let parsed = Program::from_image(&image).unwrap();
+        assert_eq!(
+            parsed.code().as_ptr(),
+            image[Program::HEADER_SIZE..].as_ptr()
+        );
+        let owned: Program<'static> = parsed.clone().into_owned();
+        assert_eq!(owned, parsed);
+    }
+
+    #[test]
+    fn test_program_invalid_image() {
+        let image = Program::new(&[0x00, 0xff]).with_entry(1).to_image();
+        assert!(Program::from_image(&image[..Program::HEADER_SIZE - 1]).is_err());
+        assert!(Program::from_image(&image[..image.len() - 1]).is_err());
+
+        let mut invalid = image.clone();
+        invalid[0] = b'X';
+        assert_eq!(
+            Program::from_image(&invalid),
+            Err(VmError::InvalidImage("Invalid magic number".to_string()))
+        );
+
+        let mut invalid = image.clone();
+        invalid[4] = 2;
+        assert_eq!(
+            Program::from_image(&invalid),
+            Err(VmError::InvalidImage("Unsupported version 2".to_string()))
+        );
+
+        let mut invalid = image;
+        invalid[8] = 2;
+        assert!(Program::from_image(&invalid).is_err());
+    }
+}
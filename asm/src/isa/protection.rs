@@ -0,0 +1,175 @@
+use std::ops::Range;
+
+/// An operation of the guest on the memory, checked against the permissions of the regions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Permission {
+    /// A load of the program.
+    Read,
+    /// A store of the program.
+    Write,
+    /// The fetch of an instruction, in the Von Neumann mode.
+    Execute,
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Permission::Read => write!(f, "read"),
+            Permission::Write => write!(f, "write"),
+            Permission::Execute => write!(f, "execute"),
+        }
+    }
+}
+
+/// The set of the operations allowed in a region of the memory.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Permissions {
+    /// No access, e.g. for a guard region.
+    pub const NONE: Self = Self::new(false, false, false);
+    /// Read-only data.
+    pub const READ: Self = Self::new(true, false, false);
+    /// Data, read and written.
+    pub const READ_WRITE: Self = Self::new(true, true, false);
+    /// Code, read and executed but not modified.
+    pub const READ_EXECUTE: Self = Self::new(true, false, true);
+    /// Any access, e.g. for a self-modifying program.
+    pub const ALL: Self = Self::new(true, true, true);
+
+    /// Create a set of permissions.
+    pub const fn new(read: bool, write: bool, execute: bool) -> Self {
+        Self {
+            read,
+            write,
+            execute,
+        }
+    }
+
+    /// Check if an operation is allowed.
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Read => self.read,
+            Permission::Write => self.write,
+            Permission::Execute => self.execute,
+        }
+    }
+}
+
+impl std::fmt::Display for Permissions {
+    /// Format the permissions like `ls`, e.g. `r-x`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let flag = |allowed: bool, letter: char| if allowed { letter } else { '-' };
+        write!(
+            f,
+            "{}{}{}",
+            flag(self.read, 'r'),
+            flag(self.write, 'w'),
+            flag(self.execute, 'x')
+        )
+    }
+}
+
+/// A region descriptor: a range of addresses of the memory with its permissions.
+///
+/// Once a region is added to the memory, the memory is protected: each byte accessed by the
+/// guest must be in a region allowing the access, otherwise the access raises
+/// `VmError::ProtectionFault`. When regions overlap, the last added one applies.
+/// The accesses of the host, like `Memory::write_bytes` and the loading of the programs, and the
+/// reads of the interrupt vector table by the CPU are not checked.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::protection::{Permission, Permissions, Region};
+/// let code = Region::new(0x0..0x100, Permissions::READ_EXECUTE);
+/// assert!(code.allows(0xfc, 4, Permission::Execute));
+/// assert!(!code.allows(0xfc, 4, Permission::Write));
+/// assert_eq!(code.to_string(), "0x00000000..0x00000100 r-x");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// The addresses of the region, in bytes.
+    pub range: Range<usize>,
+    pub permissions: Permissions,
+}
+
+impl Region {
+    /// Create a region descriptor.
+    pub fn new(range: Range<usize>, permissions: Permissions) -> Self {
+        Self { range, permissions }
+    }
+
+    /// Check if the region contains an address.
+    pub fn contains(&self, address: usize) -> bool {
+        self.range.contains(&address)
+    }
+
+    /// Check if an access of `size` bytes at `address` is in the region and allowed by it.
+    pub fn allows(&self, address: usize, size: usize, permission: Permission) -> bool {
+        self.permissions.allows(permission)
+            && address >= self.range.start
+            && address.saturating_add(size) <= self.range.end
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "0x{:08x}..0x{:08x} {}",
+            self.range.start, self.range.end, self.permissions
+        )
+    }
+}
+
+/// Check an access against the region descriptors of a memory.
+///
+/// # Returns
+/// `true` if there is no region, or if each byte of the access is in a region, the last one
+/// containing it, allowing the access.
+pub fn allows(regions: &[Region], address: usize, size: usize, permission: Permission) -> bool {
+    regions.is_empty()
+        || (address..address.saturating_add(size)).all(|byte| {
+            regions
+                .iter()
+                .rev()
+                .find(|region| region.contains(byte))
+                .is_some_and(|region| region.permissions.allows(permission))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protection_permissions() {
+        assert!(Permissions::READ_WRITE.allows(Permission::Write));
+        assert!(!Permissions::READ_WRITE.allows(Permission::Execute));
+        assert!(!Permissions::NONE.allows(Permission::Read));
+        assert_eq!(Permissions::ALL.to_string(), "rwx");
+        assert_eq!(Permissions::READ.to_string(), "r--");
+    }
+
+    #[test]
+    fn test_protection_regions() {
+        let regions = [
+            Region::new(0x0..0x100, Permissions::READ_WRITE),
+            // a read-only word hides the data region
+            Region::new(0x40..0x44, Permissions::READ),
+        ];
+        assert!(allows(&[], 0x1000, 4, Permission::Execute));
+        assert!(allows(&regions, 0x3c, 4, Permission::Write));
+        assert!(allows(&regions, 0x40, 4, Permission::Read));
+        assert!(!allows(&regions, 0x40, 4, Permission::Write));
+        // an access overlapping the read-only word, or the end of the regions
+        assert!(!allows(&regions, 0x3e, 4, Permission::Write));
+        assert!(!allows(&regions, 0xfe, 4, Permission::Read));
+        assert!(allows(&regions, 0xfc, 4, Permission::Read));
+    }
+}
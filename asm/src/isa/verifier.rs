@@ -0,0 +1,192 @@
+//! The static verification of the bytecode of a program, see `Program::verify`.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::decoder::Decoder;
+use super::error::VmError;
+use super::instructions::OpCode;
+use super::program::Program;
+use super::word::Word;
+
+/// A problem found in the bytecode of a program by the verifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The offset in the code of the instruction with the problem.
+    pub offset: usize,
+    pub kind: DiagnosticKind,
+}
+
+/// The kind of problem found by the verifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The byte is not an opcode, the following invalid bytes are not reported.
+    InvalidOpcode { opcode: u8 },
+    /// A register operand is not lower than the number of registers.
+    InvalidRegister { register: u8 },
+    /// Another operand of the instruction is invalid.
+    InvalidOperand(VmError),
+    /// The instruction extends past the end of the code.
+    TruncatedInstruction { opcode: OpCode },
+    /// The target of a jump, a call or a spawn is not the start of an instruction.
+    InvalidJumpTarget { target: usize },
+    /// The entry point is not the start of an instruction.
+    InvalidEntryPoint { entry: usize },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:08x}: ", self.offset)?;
+        match &self.kind {
+            DiagnosticKind::InvalidOpcode { opcode } => {
+                write!(f, "invalid opcode 0x{:02x}", opcode)
+            }
+            DiagnosticKind::InvalidRegister { register } => {
+                write!(f, "invalid register R{}", register)
+            }
+            DiagnosticKind::InvalidOperand(error) => write!(f, "invalid operand: {}", error),
+            DiagnosticKind::TruncatedInstruction { opcode } => {
+                write!(f, "truncated instruction {:?}", opcode)
+            }
+            DiagnosticKind::InvalidJumpTarget { target } => {
+                write!(f, "jump to 0x{:x}, not the start of an instruction", target)
+            }
+            DiagnosticKind::InvalidEntryPoint { entry } => {
+                write!(
+                    f,
+                    "entry point 0x{:x}, not the start of an instruction",
+                    entry
+                )
+            }
+        }
+    }
+}
+
+/// Decode the whole code of a program and check its instructions, see `Program::verify`.
+pub(crate) fn verify<T: Word>(program: &Program, decoder: &Decoder<T>) -> Vec<Diagnostic> {
+    let code = program.code();
+    let mut diagnostics = Vec::new();
+    let mut boundaries = BTreeSet::new();
+    let mut jumps = Vec::new();
+    let mut offset = 0;
+    // set after an invalid opcode, until the next valid opcode
+    let mut resyncing = false;
+    while offset < code.len() {
+        let opcode = match OpCode::try_from(code[offset]) {
+            Ok(opcode) => opcode,
+            Err(_) => {
+                if !resyncing {
+                    diagnostics.push(Diagnostic {
+                        offset,
+                        kind: DiagnosticKind::InvalidOpcode {
+                            opcode: code[offset],
+                        },
+                    });
+                }
+                resyncing = true;
+                offset += 1;
+                continue;
+            }
+        };
+        resyncing = false;
+        let size = opcode.size::<T, T::Address>();
+        if code.len() - offset < size {
+            diagnostics.push(Diagnostic {
+                offset,
+                kind: DiagnosticKind::TruncatedInstruction { opcode },
+            });
+            break;
+        }
+        boundaries.insert(offset);
+        match decoder.decode_instruction(&code[offset..]) {
+            Ok(instruction) => {
+                if let Some(target) = instruction.jump_target() {
+                    jumps.push((offset, target.to_usize()));
+                }
+            }
+            Err(VmError::InvalidRegister { register }) => diagnostics.push(Diagnostic {
+                offset,
+                kind: DiagnosticKind::InvalidRegister { register },
+            }),
+            Err(error) => diagnostics.push(Diagnostic {
+                offset,
+                kind: DiagnosticKind::InvalidOperand(error),
+            }),
+        }
+        offset += size;
+    }
+    if !code.is_empty() && !boundaries.contains(&program.entry()) {
+        diagnostics.push(Diagnostic {
+            offset: program.entry(),
+            kind: DiagnosticKind::InvalidEntryPoint {
+                entry: program.entry(),
+            },
+        });
+    }
+    diagnostics.extend(
+        jumps
+            .into_iter()
+            .filter(|(_, target)| !boundaries.contains(target))
+            .map(|(offset, target)| Diagnostic {
+                offset,
+                kind: DiagnosticKind::InvalidJumpTarget { target },
+            }),
+    );
+    diagnostics.sort_by_key(|diagnostic| diagnostic.offset);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+
+    #[test]
+    fn test_verify_valid_program() {
+        let program = Assembler::new()
+            .assemble_program("MOV R0 3\nloop: DEC R0\nJMPNZ loop\nCALL f\nHLT\nf: RET")
+            .unwrap();
+        assert!(program.verify::<i32>().is_empty());
+    }
+
+    #[test]
+    fn test_verify_diagnostics() {
+        let code = [
+            0x0e, 0x07, // INC R7, with 4 registers
+            0x12, 0x03, 0x00, 0x00, 0x00, // JMP 0x3, inside the JMP
+            0xfe, 0xfd, // invalid bytes
+            0x0e, 0x00, // INC R0
+            0x01, 0x00, 0x01, // truncated MOV
+        ];
+        let program = Program::new(&code).with_entry(1);
+        let diagnostics = program.verify::<i32>();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| &diagnostic.kind)
+                .collect::<Vec<_>>(),
+            [
+                &DiagnosticKind::InvalidRegister { register: 7 },
+                &DiagnosticKind::InvalidEntryPoint { entry: 1 },
+                &DiagnosticKind::InvalidJumpTarget { target: 3 },
+                &DiagnosticKind::InvalidOpcode { opcode: 0xfe },
+                &DiagnosticKind::TruncatedInstruction {
+                    opcode: OpCode::MOV
+                },
+            ]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.offset)
+                .collect::<Vec<_>>(),
+            [0x0, 0x1, 0x2, 0x7, 0xb]
+        );
+        assert_eq!(
+            diagnostics[2].to_string(),
+            "0x00000002: jump to 0x3, not the start of an instruction"
+        );
+        // 8 registers make R7 valid
+        assert_eq!(program.verify_with_registers::<i32>(8).len(), 4);
+    }
+}
@@ -0,0 +1,101 @@
+use std::ops::Range;
+
+/// The kind of a memory access.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl std::fmt::Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// A watchpoint on a range of memory addresses.
+/// The watchpoint triggers when a memory access overlaps the range with a watched access kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// The watched addresses, in bytes.
+    pub range: Range<usize>,
+    /// Trigger on reads.
+    pub read: bool,
+    /// Trigger on writes.
+    pub write: bool,
+}
+
+impl Watchpoint {
+    /// Create a watchpoint triggering on reads of the range.
+    pub fn read(range: Range<usize>) -> Self {
+        Self {
+            range,
+            read: true,
+            write: false,
+        }
+    }
+
+    /// Create a watchpoint triggering on writes to the range.
+    pub fn write(range: Range<usize>) -> Self {
+        Self {
+            range,
+            read: false,
+            write: true,
+        }
+    }
+
+    /// Create a watchpoint triggering on reads and writes of the range.
+    pub fn access(range: Range<usize>) -> Self {
+        Self {
+            range,
+            read: true,
+            write: true,
+        }
+    }
+
+    /// Check if an access of `size` bytes at `address` triggers the watchpoint.
+    pub fn triggers(&self, address: usize, size: usize, access: Access) -> bool {
+        let watched = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+        watched && address < self.range.end && address.saturating_add(size) > self.range.start
+    }
+}
+
+/// A memory access that triggered a watchpoint.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// The address of the access.
+    pub address: usize,
+    /// The size of the access in bytes.
+    pub size: usize,
+    /// The kind of the access.
+    pub access: Access,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchpoint_overlap() {
+        let watchpoint = Watchpoint::access(8..12);
+        assert!(!watchpoint.triggers(4, 4, Access::Read));
+        assert!(watchpoint.triggers(5, 4, Access::Read));
+        assert!(watchpoint.triggers(11, 1, Access::Write));
+        assert!(!watchpoint.triggers(12, 4, Access::Write));
+    }
+
+    #[test]
+    fn test_watchpoint_access_kind() {
+        assert!(Watchpoint::read(0..4).triggers(0, 4, Access::Read));
+        assert!(!Watchpoint::read(0..4).triggers(0, 4, Access::Write));
+        assert!(Watchpoint::write(0..4).triggers(0, 4, Access::Write));
+        assert!(!Watchpoint::write(0..4).triggers(0, 4, Access::Read));
+    }
+}
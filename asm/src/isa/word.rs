@@ -0,0 +1,395 @@
+use super::pod::Pod;
+
+/// The data word of an architecture of the VM.
+///
+/// The CPU, the decoder and the VM are generic over this trait, which provides the
+/// arithmetic, the sign tests and the little-endian encoding they need. It is implemented
+/// for all the primitive integer types from 8 to 64 bits, so `VM<i16>` or `VM<u32>` work
+/// out of the box.
+///
+/// Each word type defines the type of the addresses of its architecture,
+/// which is the unsigned integer of the same size.
+pub trait Word:
+    Copy
+    + Default
+    + Eq
+    + Ord
+    + std::fmt::Debug
+    + std::fmt::Display
+    + std::fmt::LowerHex
+    + std::ops::BitAnd<Output = Self>
+    + std::ops::BitOr<Output = Self>
+    + std::ops::BitXor<Output = Self>
+    + std::ops::Not<Output = Self>
+    + Pod
+    + 'static
+{
+    /// The type of the memory addresses of the architecture.
+    type Address: Word;
+
+    /// The size of the word in bytes.
+    const SIZE: usize = std::mem::size_of::<Self>();
+
+    /// The value zero.
+    fn zero() -> Self;
+
+    /// The value one.
+    fn one() -> Self;
+
+    /// Check if the value is negative, always false for unsigned types.
+    fn is_negative(self) -> bool;
+
+    /// Add two values, returning the wrapped result and whether an overflow occurred.
+    fn overflowing_add(self, rhs: Self) -> (Self, bool);
+
+    /// Subtract two values, returning the wrapped result and whether an overflow occurred.
+    fn overflowing_sub(self, rhs: Self) -> (Self, bool);
+
+    /// Multiply two values, returning the wrapped result and whether an overflow occurred.
+    fn overflowing_mul(self, rhs: Self) -> (Self, bool);
+
+    /// Multiply two values, returning the upper half of the double-width product.
+    /// The product is signed for the signed types and unsigned for the unsigned types.
+    fn mul_high(self, rhs: Self) -> Self;
+
+    /// Divide two values, returning the wrapped result and whether an overflow occurred.
+    /// Returns `None` if `rhs` is zero.
+    fn overflowing_div(self, rhs: Self) -> Option<(Self, bool)>;
+
+    /// Compute the remainder of a division, returning the wrapped result and whether an overflow occurred.
+    /// Returns `None` if `rhs` is zero.
+    fn overflowing_rem(self, rhs: Self) -> Option<(Self, bool)>;
+
+    /// Shift left by `amount` bits, returning the result and the last bit shifted out.
+    /// Shifting by the size of the word or more gives zero.
+    fn shift_left(self, amount: u32) -> (Self, bool);
+
+    /// Shift right by `amount` bits filling with zeros, returning the result and the last bit shifted out.
+    /// Shifting by the size of the word or more gives zero.
+    fn shift_right(self, amount: u32) -> (Self, bool);
+
+    /// Shift right by `amount` bits filling with the most significant bit, returning the result
+    /// and the last bit shifted out. The most significant bit is the sign bit even for unsigned types.
+    fn shift_right_arithmetic(self, amount: u32) -> (Self, bool);
+
+    /// Rotate left by `amount` bits modulo the size of the word, returning the result
+    /// and the last bit rotated out, which is the least significant bit of the result.
+    fn rotate_left(self, amount: u32) -> (Self, bool);
+
+    /// Rotate right by `amount` bits modulo the size of the word, returning the result
+    /// and the last bit rotated out, which is the most significant bit of the result.
+    fn rotate_right(self, amount: u32) -> (Self, bool);
+
+    /// Count the number of bits set in the value.
+    fn count_ones(self) -> u32;
+
+    /// Count the number of zero bits before the most significant bit set.
+    /// Returns the size of the word in bits for zero.
+    fn leading_zeros(self) -> u32;
+
+    /// Count the number of zero bits after the least significant bit set.
+    /// Returns the size of the word in bits for zero.
+    fn trailing_zeros(self) -> u32;
+
+    /// Reinterpret the bits of the value as its unsigned address type.
+    fn to_unsigned(self) -> Self::Address;
+
+    /// Reinterpret the bits of an unsigned value as the word.
+    fn from_unsigned(value: Self::Address) -> Self;
+
+    /// Read a little-endian value from a slice of exactly `SIZE` bytes.
+    /// Returns `None` if the slice does not have the size of the word.
+    fn read_le(bytes: &[u8]) -> Option<Self>;
+
+    /// Append the little-endian bytes of the value to `output`.
+    fn write_le(self, output: &mut Vec<u8>);
+
+    /// Convert the value to a host index, sign-extending negative values.
+    fn to_usize(self) -> usize;
+
+    /// Convert a host index to a value, truncating the upper bits.
+    fn from_usize(value: usize) -> Self;
+
+    /// Convert a counter to a value, saturating to the maximum value of the type.
+    fn saturating_from_u128(value: u128) -> Self;
+
+    /// Convert the value to a 128-bit integer, sign-extending the signed types.
+    fn to_i128(self) -> i128;
+
+    /// Convert a 128-bit integer to a value, returning the truncated result and whether
+    /// the integer is out of the bounds of the type.
+    fn overflowing_from_i128(value: i128) -> (Self, bool);
+
+    /// Convert the value to the nearest `f32`.
+    fn to_f32(self) -> f32;
+
+    /// Convert a `f32` to a value, rounding toward zero and saturating to the bounds of the type.
+    /// NaN gives zero.
+    fn from_f32(value: f32) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($word:ty => $address:ty),* $(,)?) => {
+        $(
+            impl Word for $word {
+                type Address = $address;
+
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                #[allow(unused_comparisons)]
+                fn is_negative(self) -> bool {
+                    self < 0
+                }
+
+                fn overflowing_add(self, rhs: Self) -> (Self, bool) {
+                    <$word>::overflowing_add(self, rhs)
+                }
+
+                fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
+                    <$word>::overflowing_sub(self, rhs)
+                }
+
+                fn overflowing_mul(self, rhs: Self) -> (Self, bool) {
+                    <$word>::overflowing_mul(self, rhs)
+                }
+
+                #[allow(unused_comparisons)]
+                fn mul_high(self, rhs: Self) -> Self {
+                    // the product of two 64-bit values fits in 128 bits
+                    if <$word>::MIN < 0 {
+                        ((self as i128 * rhs as i128) >> <$word>::BITS) as $word
+                    } else {
+                        ((self as u128 * rhs as u128) >> <$word>::BITS) as $word
+                    }
+                }
+
+                fn overflowing_div(self, rhs: Self) -> Option<(Self, bool)> {
+                    (rhs != 0).then(|| <$word>::overflowing_div(self, rhs))
+                }
+
+                fn overflowing_rem(self, rhs: Self) -> Option<(Self, bool)> {
+                    (rhs != 0).then(|| <$word>::overflowing_rem(self, rhs))
+                }
+
+                fn shift_left(self, amount: u32) -> (Self, bool) {
+                    let bits = self as $address;
+                    if amount == 0 {
+                        return (self, false);
+                    }
+                    let carry = amount <= <$address>::BITS
+                        && (bits >> (<$address>::BITS - amount)) & 1 == 1;
+                    (bits.checked_shl(amount).unwrap_or(0) as $word, carry)
+                }
+
+                fn shift_right(self, amount: u32) -> (Self, bool) {
+                    let bits = self as $address;
+                    if amount == 0 {
+                        return (self, false);
+                    }
+                    let carry = amount <= <$address>::BITS && (bits >> (amount - 1)) & 1 == 1;
+                    (bits.checked_shr(amount).unwrap_or(0) as $word, carry)
+                }
+
+                fn shift_right_arithmetic(self, amount: u32) -> (Self, bool) {
+                    let bits = self as $address;
+                    let sign = bits >> (<$address>::BITS - 1) == 1;
+                    if amount == 0 {
+                        return (self, false);
+                    } else if amount >= <$address>::BITS {
+                        return (if sign { !0 as $word } else { 0 }, sign);
+                    }
+                    let carry = (bits >> (amount - 1)) & 1 == 1;
+                    // the complement turns the sign fill into a zero fill
+                    let result = if sign { !(!bits >> amount) } else { bits >> amount };
+                    (result as $word, carry)
+                }
+
+                fn rotate_left(self, amount: u32) -> (Self, bool) {
+                    let bits = (self as $address).rotate_left(amount % <$address>::BITS);
+                    (bits as $word, amount != 0 && bits & 1 == 1)
+                }
+
+                fn rotate_right(self, amount: u32) -> (Self, bool) {
+                    let bits = (self as $address).rotate_right(amount % <$address>::BITS);
+                    (bits as $word, amount != 0 && bits >> (<$address>::BITS - 1) == 1)
+                }
+
+                fn count_ones(self) -> u32 {
+                    <$word>::count_ones(self)
+                }
+
+                fn leading_zeros(self) -> u32 {
+                    <$word>::leading_zeros(self)
+                }
+
+                fn trailing_zeros(self) -> u32 {
+                    <$word>::trailing_zeros(self)
+                }
+
+                fn to_unsigned(self) -> Self::Address {
+                    self as $address
+                }
+
+                fn from_unsigned(value: Self::Address) -> Self {
+                    value as $word
+                }
+
+                fn read_le(bytes: &[u8]) -> Option<Self> {
+                    bytes.try_into().ok().map(<$word>::from_le_bytes)
+                }
+
+                fn write_le(self, output: &mut Vec<u8>) {
+                    output.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+
+                fn from_usize(value: usize) -> Self {
+                    value as $word
+                }
+
+                fn saturating_from_u128(value: u128) -> Self {
+                    <$word>::try_from(value).unwrap_or(<$word>::MAX)
+                }
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+
+                fn overflowing_from_i128(value: i128) -> (Self, bool) {
+                    (value as $word, <$word>::try_from(value).is_err())
+                }
+
+                fn to_f32(self) -> f32 {
+                    self as f32
+                }
+
+                fn from_f32(value: f32) -> Self {
+                    value as $word
+                }
+            }
+        )*
+    };
+}
+
+impl_word! {
+    i8 => u8,
+    u8 => u8,
+    i16 => u16,
+    u16 => u16,
+    i32 => u32,
+    u32 => u32,
+    i64 => u64,
+    u64 => u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_size() {
+        assert_eq!(<i8 as Word>::SIZE, 1);
+        assert_eq!(<u16 as Word>::SIZE, 2);
+        assert_eq!(<i32 as Word>::SIZE, 4);
+        assert_eq!(<u64 as Word>::SIZE, 8);
+    }
+
+    #[test]
+    fn test_word_sign() {
+        assert!(Word::is_negative(-1i16));
+        assert!(!Word::is_negative(0i32));
+        assert!(!Word::is_negative(u32::MAX));
+    }
+
+    #[test]
+    fn test_word_division() {
+        assert_eq!(Word::overflowing_div(7i32, 2), Some((3, false)));
+        assert_eq!(Word::overflowing_div(i32::MIN, -1), Some((i32::MIN, true)));
+        assert_eq!(Word::overflowing_div(7u8, 0), None);
+        assert_eq!(Word::overflowing_rem(7i64, 0), None);
+        assert_eq!(Word::overflowing_rem(i64::MIN, -1), Some((0, true)));
+    }
+
+    #[test]
+    fn test_word_mul_high() {
+        assert_eq!(Word::mul_high(0x4000i16, 4), 1);
+        assert_eq!(Word::mul_high(-1i32, 1), -1);
+        assert_eq!(Word::mul_high(u64::MAX, u64::MAX), u64::MAX - 1);
+        assert_eq!(Word::mul_high(i64::MIN, i64::MIN), 1 << 62);
+        assert_eq!(Word::mul_high(0xffu8, 2), 1);
+    }
+
+    #[test]
+    fn test_word_shifts() {
+        assert_eq!(Word::shift_left(0x4000_0001i32, 1), (-0x7fff_fffe, false));
+        assert_eq!(Word::shift_left(0x8000_0001u32, 1), (2, true));
+        assert_eq!(Word::shift_left(1u8, 8), (0, true));
+        assert_eq!(Word::shift_left(1u8, 9), (0, false));
+        assert_eq!(Word::shift_right(-2i16, 1), (0x7fff, false));
+        assert_eq!(Word::shift_right(3u16, 1), (1, true));
+        assert_eq!(Word::shift_right(-1i64, 64), (0, true));
+        assert_eq!(Word::shift_right_arithmetic(-4i32, 1), (-2, false));
+        assert_eq!(Word::shift_right_arithmetic(0x81u8, 1), (0xc0, true));
+        assert_eq!(Word::shift_right_arithmetic(5i32, 2), (1, false));
+        assert_eq!(Word::shift_right_arithmetic(-1i8, 100), (-1, true));
+        assert_eq!(Word::shift_right_arithmetic(7u32, 0), (7, false));
+    }
+
+    #[test]
+    fn test_word_rotations() {
+        assert_eq!(Word::rotate_left(0x81u8, 1), (0x03, true));
+        assert_eq!(Word::rotate_left(0x4000_0000i32, 1), (i32::MIN, false));
+        assert_eq!(Word::rotate_left(0x1234u16, 20), (0x2341, true));
+        assert_eq!(Word::rotate_right(0x81u8, 1), (0xc0, true));
+        assert_eq!(Word::rotate_right(2i64, 1), (1, false));
+        assert_eq!(Word::rotate_right(1u32, 32), (1, false));
+        assert_eq!(Word::rotate_left(5u32, 0), (5, false));
+    }
+
+    #[test]
+    fn test_word_bit_counts() {
+        assert_eq!(Word::count_ones(-1i16), 16);
+        assert_eq!(Word::count_ones(0b1011u8), 3);
+        assert_eq!(Word::leading_zeros(1u32), 31);
+        assert_eq!(Word::leading_zeros(-1i64), 0);
+        assert_eq!(Word::trailing_zeros(8i32), 3);
+        assert_eq!(Word::trailing_zeros(0u8), 8);
+    }
+
+    #[test]
+    fn test_word_le_bytes() {
+        assert_eq!(i32::read_le(&[0x78, 0x56, 0x34, 0x12]), Some(0x12345678));
+        assert_eq!(i32::read_le(&[0x78, 0x56, 0x34]), None);
+
+        let mut output = Vec::new();
+        0x1234u16.write_le(&mut output);
+        assert_eq!(output, vec![0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_word_conversions() {
+        assert_eq!(i8::saturating_from_u128(1000), i8::MAX);
+        assert_eq!(u64::saturating_from_u128(1000), 1000);
+        assert_eq!(u8::from_usize(0x1ff), 0xff);
+        assert_eq!(Word::to_usize(0x10u16), 0x10);
+        assert_eq!(Word::to_f32(-3i8), -3.0);
+        assert_eq!(i16::from_f32(-2.9), -2);
+        assert_eq!(u8::from_f32(-1.0), 0);
+        assert_eq!(i32::from_f32(f32::NAN), 0);
+        assert_eq!(i64::from_f32(f32::INFINITY), i64::MAX);
+        assert_eq!(Word::to_i128(-1i8), -1);
+        assert_eq!(Word::to_i128(u64::MAX), u64::MAX as i128);
+        assert_eq!(i16::overflowing_from_i128(-0x8000), (-0x8000, false));
+        assert_eq!(u8::overflowing_from_i128(0x1ff), (0xff, true));
+        assert_eq!(u8::overflowing_from_i128(-1), (0xff, true));
+    }
+}
@@ -0,0 +1,11 @@
+//! The instruction set and the assembler of ForgeVM, re-exported by the `forge_vm` crate.
+//!
+//! The `isa` module defines the instructions, their encoding and decoding, and the programs
+//! they make, and the `asm` module assembles the source text into them. They are shared by the
+//! VM of `forge_vm` and by the `forge_asm!` macro of `forge_vm_macros`, which runs the assembler
+//! at compile time. Use them through `forge_vm::vm` and `forge_vm::asm`.
+
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod asm;
+pub mod isa;
@@ -0,0 +1,44 @@
+//! The throughput of the interpreter loop with and without the superinstructions, measured by
+//! criterion in instructions per second on the workloads of `forge_vm::bench`, next to the
+//! `match`-based `Reference` interpreter as a baseline for the handler table of the CPU.
+//!
+//! Run with `cargo bench --bench dispatch`, optionally with the name of a workload to run it
+//! alone, e.g. `cargo bench --bench dispatch -- memory`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use forge_vm::bench::{self, WORKLOADS};
+use forge_vm::vm::reference::Reference;
+use forge_vm::VmBuilder;
+
+/// The number of iterations of each loop, per measured run.
+const ITERATIONS: i32 = 10_000;
+
+fn dispatch(c: &mut Criterion) {
+    for workload in &WORKLOADS {
+        let program = workload.program(ITERATIONS);
+        let mut group = c.benchmark_group(workload.name);
+        for (name, superinstructions) in [("fused", true), ("unfused", false)] {
+            let mut vm = VmBuilder::new()
+                .superinstructions(superinstructions)
+                .build::<i32>();
+            let steps = bench::run_benchmark_program(&mut vm, &program)
+                .unwrap()
+                .steps;
+            group.throughput(Throughput::Elements(steps as u64));
+            group.bench_function(name, |b| {
+                b.iter(|| bench::run_benchmark_program(&mut vm, black_box(&program)).unwrap())
+            });
+        }
+        // the baseline dispatches with a `match` and decodes each instruction at every step
+        let mut reference = Reference::new(1024, 1024);
+        let steps = reference.run(&program, u128::MAX).unwrap().steps;
+        group.throughput(Throughput::Elements(steps as u64));
+        group.bench_function("match", |b| {
+            b.iter(|| reference.run(black_box(&program), u128::MAX).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
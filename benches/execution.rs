@@ -0,0 +1,31 @@
+//! The benchmark suite of the execution core: the workloads of `forge_vm::bench`, measured by
+//! criterion with their throughput in instructions per second.
+//!
+//! Run with `cargo bench --bench execution`, optionally with the name of a workload to run it
+//! alone, e.g. `cargo bench --bench execution -- calls`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use forge_vm::bench::{self, WORKLOADS};
+use forge_vm::VM;
+
+/// The number of iterations of each loop, per measured run.
+const ITERATIONS: i32 = 10_000;
+
+fn execution(c: &mut Criterion) {
+    let mut group = c.benchmark_group("execution");
+    for workload in &WORKLOADS {
+        let program = workload.program(ITERATIONS);
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let steps = bench::run_benchmark_program(&mut vm, &program)
+            .unwrap()
+            .steps;
+        group.throughput(Throughput::Elements(steps as u64));
+        group.bench_function(workload.name, |b| {
+            b.iter(|| bench::run_benchmark_program(&mut vm, black_box(&program)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, execution);
+criterion_main!(benches);
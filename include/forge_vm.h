@@ -0,0 +1,136 @@
+/*
+ * C interface of ForgeVM, see the `ffi` module of the `forge_vm` crate.
+ *
+ * Link with the `cdylib` built by `cargo build --release` (`libforge_vm.so`,
+ * `libforge_vm.dylib` or `forge_vm.dll`).
+ */
+
+#ifndef FORGE_VM_H
+#define FORGE_VM_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/** A VM of the `i32` architecture, opaque on the C side. */
+typedef struct ForgeVm ForgeVm;
+
+/**
+ * The function succeeded.
+ */
+#define FORGEVM_OK 0
+
+/**
+ * A pointer argument is null.
+ */
+#define FORGEVM_ERR_NULL_POINTER -1
+
+/**
+ * A memory access is out of bounds, not aligned or faults.
+ */
+#define FORGEVM_ERR_MEMORY -2
+
+/**
+ * The stack underflows or overflows.
+ */
+#define FORGEVM_ERR_STACK -3
+
+/**
+ * The program holds an invalid opcode or instruction.
+ */
+#define FORGEVM_ERR_INVALID_INSTRUCTION -4
+
+/**
+ * A register index is out of range.
+ */
+#define FORGEVM_ERR_INVALID_REGISTER -5
+
+/**
+ * The program divides by zero.
+ */
+#define FORGEVM_ERR_DIVISION_BY_ZERO -6
+
+/**
+ * The run exceeds its step limit, cycle budget or gas.
+ */
+#define FORGEVM_ERR_LIMIT -7
+
+/**
+ * Any other error of the VM.
+ */
+#define FORGEVM_ERR_OTHER -8
+
+/**
+ * The program has not terminated, it was not run or its run stopped with an error.
+ */
+#define FORGEVM_ERR_NOT_TERMINATED -9
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a VM, see `VM::new`.
+ *
+ * # Returns
+ * The VM, to release with `forgevm_free`, or null if its creation panics, e.g. on a memory
+ * size overflowing the address space.
+ */
+ForgeVm *forgevm_new(size_t stack_capacity, size_t memory_size);
+
+/**
+ * Release a VM created by `forgevm_new`, nothing is done if `vm` is null.
+ *
+ * # Safety
+ * `vm` must be null or returned by `forgevm_new` and not released yet.
+ */
+void forgevm_free(ForgeVm *vm);
+
+/**
+ * Run a program, see `VM::run`.
+ *
+ * # Parameters
+ * - `code`, `len`: The bytecode of the program, `code` can be null for an empty program.
+ * - `steps`: Set to the number of executed steps on success, ignored if null.
+ *
+ * # Safety
+ * `vm` must be a live VM of `forgevm_new` and `code` must point to `len` readable bytes, or be
+ * null if `len` is `0`.
+ */
+int32_t forgevm_run(ForgeVm *vm, const uint8_t *code, size_t len, uint64_t *steps);
+
+/**
+ * Get the exit code of the program terminated by the last run, see `VM::exit_code`.
+ *
+ * # Safety
+ * `vm` must be a live VM of `forgevm_new` and `code` must point to a writable `int32_t`.
+ */
+int32_t forgevm_exit_code(const ForgeVm *vm, int32_t *code);
+
+/**
+ * Read a register of the VM.
+ *
+ * # Safety
+ * `vm` must be a live VM of `forgevm_new` and `value` must point to a writable `int32_t`.
+ */
+int32_t forgevm_get_register(const ForgeVm *vm, uint8_t index, int32_t *value);
+
+/**
+ * Copy `len` bytes of the memory of the VM from `address` into `buffer`.
+ *
+ * # Safety
+ * `vm` must be a live VM of `forgevm_new` and `buffer` must point to `len` writable bytes.
+ */
+int32_t forgevm_read_memory(const ForgeVm *vm, size_t address, uint8_t *buffer, size_t len);
+
+/**
+ * Get the description of an error code, as a static NUL-terminated string.
+ */
+const char *forgevm_error_message(int32_t code);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FORGE_VM_H */
@@ -0,0 +1,71 @@
+//! Procedural macros of ForgeVM, re-exported by the `forge_vm` crate.
+//!
+//! The macros run the assembler of ForgeVM at compile time. A procedural macro crate cannot
+//! depend on `forge_vm` while being re-exported by it, so the assembler is taken from the
+//! `forge_vm_asm` crate both depend on.
+
+use forge_vm_asm::asm::Assembler;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Ident, LitInt, LitStr, Token};
+
+/// The input of `forge_asm!`: an optional number of registers and the source text.
+struct AsmInput {
+    registers_count: Option<u8>,
+    source: LitStr,
+}
+
+impl Parse for AsmInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut registers_count = None;
+        if input.peek(Ident) {
+            let option: Ident = input.parse()?;
+            if option != "registers" {
+                return Err(syn::Error::new(
+                    option.span(),
+                    format!("unknown option `{}`, expected `registers`", option),
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            registers_count = Some(input.parse::<LitInt>()?.base10_parse()?);
+            input.parse::<Token![,]>()?;
+        }
+        let source = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self {
+            registers_count,
+            source,
+        })
+    }
+}
+
+/// Assemble ForgeVM assembly at compile time into a `&'static [u8]` of bytecode.
+///
+/// The source is a string literal in the syntax of `Assembler::assemble`, optionally preceded
+/// by the number of registers of the target CPU, `registers = 8,`, which defaults to the
+/// registers of the default CPU. An assembler error, such as an unknown mnemonic or a register
+/// out of range, is reported as a compile error.
+#[proc_macro]
+pub fn forge_asm(input: TokenStream) -> TokenStream {
+    let AsmInput {
+        registers_count,
+        source,
+    } = parse_macro_input!(input as AsmInput);
+    let assembler = match registers_count {
+        Some(registers_count) => Assembler::with_registers(registers_count),
+        None => Assembler::new(),
+    };
+    match assembler.assemble(&source.value()) {
+        Ok(bytecode) => quote! {
+            {
+                const BYTECODE: &[u8] = &[#(#bytecode),*];
+                BYTECODE
+            }
+        }
+        .into(),
+        Err(error) => syn::Error::new(source.span(), error)
+            .to_compile_error()
+            .into(),
+    }
+}
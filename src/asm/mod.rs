@@ -0,0 +1,259 @@
+//! The assembler of ForgeVM, see `forge_vm_asm::asm`.
+//!
+//! The assembler lives in the `forge_vm_asm` crate with the instruction set it encodes, so that
+//! the `forge_asm!` macro can run it at compile time. This module re-exports it.
+
+pub use forge_vm_asm::asm::*;
+
+#[cfg(test)]
+mod tests {
+    use super::builder::*;
+    use super::error::AsmError;
+    use super::linker::Linker;
+    use super::*;
+    use crate::vm::pod::Endianness;
+    use crate::{VmBuilder, VM};
+
+    #[test]
+    fn test_assemble_labels() {
+        let source = "
+            ; count down from 3 to 0
+                MOV R0 3
+            loop:
+                DEC R0
+                JMPZ end
+                JMP loop
+            end: HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        assert_eq!(&program[6..8], &[0x0f, 0x00]); // DEC R0 at offset 6
+        assert_eq!(&program[8..13], &[0x15, 0x12, 0x00, 0x00, 0x00]); // JMPZ 18
+        assert_eq!(&program[13..18], &[0x12, 0x06, 0x00, 0x00, 0x00]); // JMP 6
+        assert_eq!(program[18], 0xff);
+
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(
+            vm.run(&program).map(|termination| termination.steps),
+            Ok(10)
+        );
+    }
+
+    #[test]
+    fn test_assemble_program_run() {
+        // sum the words of the table into the zeroed total
+        let source = "
+                MOV R0 table
+                MOV R1 4
+            loop:
+                LDR R2 [R0]
+                LD R3 total
+                ADD R3 R3 R2
+                ST R3 total
+                MOV R2 4
+                ADD R0 R0 R2
+                DEC R1
+                JMPNZ loop
+                HLT
+            .data
+            table: .word 1, 2, 3, 4
+            .bss
+            total: .space 4
+        ";
+        let program = Assembler::new().assemble_program(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run_program(&program).is_ok());
+        assert_eq!(
+            vm.snapshot().memory[program.data_address() + 16..][..4],
+            10i32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_assemble_big_endian() {
+        let source = "
+                MOV R0 0x1234
+                LD R1 value
+                ADD R0 R0 R1
+                ST R0 value
+                HLT
+            .data
+            value: .word 0x10
+        ";
+        let assembler = Assembler::new().endianness(Endianness::Big);
+        let program = assembler.assemble_program(source).unwrap();
+        assert_eq!(&program.code()[..6], &[0x01, 0x00, 0x00, 0x00, 0x12, 0x34]); // MOV R0 0x1234
+        assert_eq!(program.data(), [0x00, 0x00, 0x00, 0x10]);
+
+        let mut vm = VmBuilder::new().endianness(Endianness::Big).build::<i32>();
+        assert!(vm.run_program(&program).is_ok());
+        assert_eq!(vm.snapshot().cpu.registers[0], 0x1244);
+        assert_eq!(
+            vm.snapshot().memory[program.data_address()..][..4],
+            0x1244i32.to_be_bytes()
+        );
+
+        // the linker patches the fields in the endianness of the modules
+        let object = assembler.assemble_object(source).unwrap();
+        assert_eq!(Linker::new().link(&[object]), Ok(program));
+    }
+
+    #[test]
+    fn test_assemble_word_size() {
+        // sum the 16-bit words of a table
+        let source = "
+                MOV R0 0
+                MOV R1 table
+                MOV R2 end
+            loop:
+                LDR R3 [R1]
+                ADD R0 R0 R3
+                INC R1
+                INC R1
+                CMP R1 R2
+                JMPNZ loop
+                HLT
+            .data
+            table: .word 1000 -1 0x1000
+            end:
+        ";
+        let program = Assembler::new()
+            .word_size(2)
+            .assemble_program(source)
+            .unwrap();
+        assert_eq!(&program.code()[..4], &[0x01, 0x00, 0x00, 0x00]); // MOV R0 0
+        assert_eq!(program.data(), [0xe8, 0x03, 0xff, 0xff, 0x00, 0x10]);
+        let mut vm = VM::<i16>::new(1024, 1024);
+        assert!(vm.run_program(&program).is_ok());
+        assert_eq!(vm.snapshot().cpu.registers[0], 1000 - 1 + 0x1000);
+
+        let assembler = Assembler::new().word_size(1);
+        assert_eq!(
+            assembler.assemble("MOV R0 0xff\nMOV R1 -128\nJMP 0x100"),
+            Err(AsmError::InvalidNumber {
+                line: 3,
+                operand: "0x100".to_string()
+            })
+        );
+        assert_eq!(
+            assembler.assemble_program(".data\n.word 200 300"),
+            Err(AsmError::InvalidNumber {
+                line: 2,
+                operand: "300".to_string()
+            })
+        );
+        assert_eq!(
+            assembler.assemble_object("HLT"),
+            Err(AsmError::UnsupportedWordSize { size: 1 })
+        );
+        assert_eq!(
+            Assembler::new().word_size(8).assemble("HLT"),
+            Err(AsmError::UnsupportedWordSize { size: 8 })
+        );
+    }
+
+    #[test]
+    fn test_builder_run() {
+        // compute 5! in R1
+        let mut builder = ProgramBuilder::with_registers(8);
+        builder
+            .mov(R0, 5)
+            .mov(R1, 1)
+            .mov(R7, 0)
+            .label("loop")
+            .mult(R1, R1, R0)
+            .dec(R0)
+            .cmp(R0, R7)
+            .jmpgt("loop")
+            .st(R1, 0x100u32)
+            .hlt();
+        let program = builder.build().unwrap();
+        let mut vm = VmBuilder::new()
+            .registers(8)
+            .memory_size(0x200)
+            .build::<i32>();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.snapshot().cpu.registers[1], 120);
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let mut builder = ProgramBuilder::new();
+        builder.label("a").nop().label("a");
+        assert_eq!(
+            builder.build(),
+            Err(AsmError::DuplicateLabel {
+                line: 3,
+                label: "a".to_string()
+            })
+        );
+
+        let mut builder = ProgramBuilder::new();
+        builder.nop().jmp("nowhere");
+        assert_eq!(
+            builder.build(),
+            Err(AsmError::UndefinedLabel {
+                line: 2,
+                label: "nowhere".to_string()
+            })
+        );
+
+        let mut builder = ProgramBuilder::new();
+        builder.inc(R4);
+        assert_eq!(
+            builder.build(),
+            Err(AsmError::InvalidRegister {
+                line: 1,
+                operand: "R4".to_string()
+            })
+        );
+
+        let mut vm = VM::<i32>::new(16, 16);
+        assert_eq!(
+            vm.run(&ProgramBuilder::new().hlt().build().unwrap())
+                .map(|termination| termination.steps),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn test_link_modules() {
+        // both modules define a local `loop`, the counter is exported by the second module
+        let main = "
+                MOV R1 3
+            loop:
+                CALL add
+                DEC R1
+                JMPNZ loop
+                LD R0 counter
+                HLT
+            .data
+            step: .word 2
+        ";
+        let library = "
+            .global add, counter
+            add:
+                LD R2 counter
+                LD R3 step
+                ADD R2 R2 R3
+                ST R2 counter
+                RET
+            loop:
+                JMP loop
+            .data
+            step: .word 5
+            .bss
+            counter: .space 4
+        ";
+        let objects = [
+            Assembler::new().assemble_object(main).unwrap(),
+            Assembler::new().assemble_object(library).unwrap(),
+        ];
+        let program = Linker::new().link(&objects).unwrap();
+        assert_eq!(program.data(), [2, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(program.bss_size(), 4);
+
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run_program(&program).is_ok());
+        assert_eq!(vm.snapshot().cpu.registers[0], 15);
+    }
+}
@@ -0,0 +1,149 @@
+//! The workloads of the benchmarks of the execution core, see `run_benchmark_program`.
+//!
+//! The benchmarks of `benches/` time these workloads, so that the regressions of the decoder and
+//! of the CPU hot path are measured on the same programs from one change to the next:
+//!
+//! ```
+//! use forge_vm::bench::{self, WORKLOADS};
+//! use forge_vm::VM;
+//! for workload in &WORKLOADS {
+//!     let mut vm = VM::<i32>::new(1024, 1024);
+//!     let measurement = bench::run_benchmark_program(&mut vm, &workload.program(1000)).unwrap();
+//!     assert!(measurement.steps > 3000);
+//! }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::asm::Assembler;
+use crate::vm::word::Word;
+use crate::{ExecutionError, VM};
+
+/// A loop of the benchmarks, counting down its iterations in `R0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Workload {
+    /// The name of the workload in the reports.
+    pub name: &'static str,
+    /// The source of the loop, `R0` holding the number of iterations.
+    source: &'static str,
+}
+
+/// The workloads of the benchmarks: a tight arithmetic loop, a loop of memory and stack
+/// accesses, and a storm of calls and returns.
+pub const WORKLOADS: [Workload; 3] = [
+    Workload {
+        name: "arithmetic",
+        source: "
+            MOV R1 3
+        loop:
+            ADD R2 R2 R1
+            XOR R3 R3 R2
+            MULT R3 R3 R1
+            DEC R0
+            JMPNZ loop
+            HLT
+        ",
+    },
+    Workload {
+        name: "memory",
+        source: "
+        loop:
+            LD R1 0x100
+            INC R1
+            ST R1 0x100
+            PUSHREG R1
+            POPREG R2
+            DEC R0
+            JMPNZ loop
+            HLT
+        ",
+    },
+    Workload {
+        name: "calls",
+        source: "
+        loop:
+            CALL f
+            DEC R0
+            JMPNZ loop
+            HLT
+        f:
+            INC R1
+            RET
+        ",
+    },
+];
+
+impl Workload {
+    /// Assemble the workload for a number of iterations of its loop, at least `1`.
+    /// The program runs on a default VM with a memory of at least `0x104` bytes.
+    pub fn program(&self, iterations: i32) -> Vec<u8> {
+        let source = format!("MOV R0 {}\n{}", iterations, self.source);
+        Assembler::new()
+            .assemble(&source)
+            .expect("the workloads assemble")
+    }
+}
+
+/// The measurement of a run of a benchmark program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    /// The number of executed instructions, including the final `HLT` or `EXIT`.
+    pub steps: u128,
+    /// The wall-clock time of the run.
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    /// Get the throughput of the run, in millions of instructions per second.
+    pub fn mips(&self) -> f64 {
+        self.steps as f64 / self.elapsed.as_secs_f64() / 1e6
+    }
+}
+
+/// Run a program to its termination and measure the wall-clock time of the run.
+///
+/// The loading of the program is timed with its execution, like in `VM::run`, so the programs
+/// of a benchmark should run for long enough to make it negligible.
+///
+/// # Errors
+/// The error of the run if the program does not terminate successfully.
+pub fn run_benchmark_program<T: Word>(
+    vm: &mut VM<T>,
+    program: &[u8],
+) -> Result<Measurement, ExecutionError<T>> {
+    let start = Instant::now();
+    let termination = vm.run(program)?;
+    Ok(Measurement {
+        steps: termination.steps,
+        elapsed: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_workloads() {
+        // the steps of the loops grow with their iterations
+        for (workload, steps_per_iteration) in WORKLOADS.iter().zip([5, 7, 5]) {
+            let mut vm = VM::<i32>::new(1024, 1024);
+            let short = run_benchmark_program(&mut vm, &workload.program(10)).unwrap();
+            let long = run_benchmark_program(&mut vm, &workload.program(110)).unwrap();
+            assert_eq!(
+                long.steps - short.steps,
+                100 * steps_per_iteration,
+                "{}",
+                workload.name
+            );
+            assert!(long.mips() > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_bench_error() {
+        let mut vm = VM::<i32>::new(1024, 16);
+        let program = WORKLOADS[1].program(10);
+        assert!(run_benchmark_program(&mut vm, &program).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! The interactive debugger of `forge debug`, built on `VM::load_program` and `VM::step`.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use forge_vm::vm::decoder::Decoder;
+use forge_vm::vm::program::Program;
+use forge_vm::{VmError, VM};
+
+use crate::format_state;
+
+/// The number of instructions that can be undone with `back`.
+const HISTORY_DEPTH: usize = 10_000;
+
+const HELP: &str = "\
+Commands:
+  step [count], s        Execute the next instruction, or `count` instructions
+  back [count]           Undo the last instruction, or the last `count` instructions
+  continue, c            Execute until a breakpoint or the end of the program
+  break [address], b     Set a breakpoint, or list the breakpoints without address
+  delete <address>       Remove a breakpoint
+  registers, r           Show the registers, the flags and the program counter
+  memory <address> [len] Show `len` bytes of the memory, 64 by default
+  stack                  Show the stack, from the bottom to the top
+  disassemble [address] [count], d
+                         Disassemble `count` instructions, 5 from the program counter by default
+  reset                  Restart the program
+  quit, q                Exit the debugger
+";
+
+/// A debugging session of a program, driven by textual commands.
+pub struct Debugger {
+    vm: VM<i32>,
+    program: Program<'static>,
+    decoder: Decoder<i32>,
+    breakpoints: BTreeSet<usize>,
+    /// Set when the program halted or stopped with an error, until it is restarted.
+    finished: bool,
+}
+
+impl Debugger {
+    /// Start a debugging session, with the program loaded and stopped at its entry point.
+    ///
+    /// # Parameters
+    /// - `vm`: The VM executing the program.
+    /// - `program`: The program to debug.
+    /// - `registers_count`: The number of registers of the VM, to disassemble the program.
+    pub fn new(
+        mut vm: VM<i32>,
+        program: Program<'static>,
+        registers_count: u8,
+    ) -> Result<Self, VmError> {
+        vm.set_history_depth(HISTORY_DEPTH);
+        vm.load_program(&program)?;
+        Ok(Self {
+            vm,
+            program,
+            decoder: Decoder::with_registers(registers_count),
+            breakpoints: BTreeSet::new(),
+            finished: false,
+        })
+    }
+
+    /// Read the commands from `input` until `quit` or the end of the input, and write their
+    /// output to `output`.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        write!(output, "{}(forge) ", self.disassemble(None, 1))?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            if matches!(line.trim(), "quit" | "q") {
+                break;
+            }
+            write!(output, "{}(forge) ", self.execute(&line))?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    /// Execute a command and return its output.
+    pub fn execute(&mut self, line: &str) -> String {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return String::new();
+        };
+        let mut numbers = args.iter().map(|arg| parse_number(arg));
+        if numbers.clone().any(|number| number.is_none()) {
+            return format!("Invalid number in `{}`\n", line.trim());
+        }
+        let mut number = || numbers.next().flatten();
+        match (command, args.len()) {
+            ("step" | "s", 0 | 1) => self.resume(Some(number().unwrap_or(1))),
+            ("back", 0 | 1) => self.step_back(number().unwrap_or(1)),
+            ("continue" | "c", 0) => self.resume(None),
+            ("break" | "b", 0) => self
+                .breakpoints
+                .iter()
+                .map(|address| format!("Breakpoint at 0x{:08x}\n", address))
+                .collect(),
+            ("break" | "b", 1) => {
+                let address = number().unwrap_or_default();
+                self.breakpoints.insert(address);
+                format!("Breakpoint set at 0x{:08x}\n", address)
+            }
+            ("delete", 1) => {
+                let address = number().unwrap_or_default();
+                match self.breakpoints.remove(&address) {
+                    true => format!("Breakpoint removed at 0x{:08x}\n", address),
+                    false => format!("No breakpoint at 0x{:08x}\n", address),
+                }
+            }
+            ("registers" | "r", 0) => format_state(&self.vm),
+            ("memory", 1 | 2) => {
+                let address = number().unwrap_or_default();
+                self.memory(address, number().unwrap_or(64))
+            }
+            ("stack", 0) => self
+                .vm
+                .snapshot()
+                .stack
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("{}: 0x{:08x} ({})\n", index, value, value))
+                .collect(),
+            ("disassemble" | "d", 0..=2) => self.disassemble(number(), number().unwrap_or(5)),
+            ("reset", 0) => match self.vm.load_program(&self.program) {
+                Ok(()) => {
+                    self.finished = false;
+                    self.disassemble(None, 1)
+                }
+                Err(error) => format!("Error: {}\n", error),
+            },
+            ("help" | "h", 0) => HELP.to_string(),
+            _ => format!("Invalid command `{}`, type `help` for help\n", line.trim()),
+        }
+    }
+
+    /// Execute `count` instructions, or until a breakpoint when `count` is `None`, and
+    /// show the next instruction.
+    fn resume(&mut self, count: Option<usize>) -> String {
+        if self.finished {
+            return "The program is not running, `reset` to restart it\n".to_string();
+        }
+        let mut executed = 0;
+        loop {
+            match self.vm.step() {
+                Ok(false) => {}
+                Ok(true) => {
+                    self.finished = true;
+                    return format!(
+                        "Program halted after {} steps with exit code {}\n",
+                        self.vm.counters().steps,
+                        self.vm.exit_code().unwrap_or_default()
+                    );
+                }
+                Err(error) => {
+                    self.finished = true;
+                    return format!("Error {}: {}\n", self.vm.location(), error);
+                }
+            }
+            executed += 1;
+            if count == Some(executed) {
+                break;
+            }
+            if count.is_none() && self.breakpoints.contains(&self.vm.pc()) {
+                break;
+            }
+        }
+        self.disassemble(None, 1)
+    }
+
+    /// Undo up to `count` instructions, including the one that halted or stopped the program,
+    /// and show the next instruction.
+    fn step_back(&mut self, count: usize) -> String {
+        for undone in 0..count {
+            if self.vm.step_back().is_err() {
+                if undone == 0 {
+                    return "No instruction to step back\n".to_string();
+                }
+                break;
+            }
+        }
+        self.finished = false;
+        self.disassemble(None, 1)
+    }
+
+    /// Disassemble `count` instructions from an address, or from the program counter.
+    /// The program counter is marked with `=>` and the breakpoints with `*`.
+    fn disassemble(&self, address: Option<usize>, count: usize) -> String {
+        let pc = self.vm.pc();
+        let mut address = address.unwrap_or(pc);
+        let mut listing = String::new();
+        for _ in 0..count {
+            let marker = match (address == pc, self.breakpoints.contains(&address)) {
+                (true, _) => "=>",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+            match self
+                .decoder
+                .decode_instruction(self.program.slice_from(address))
+            {
+                Ok(instruction) => {
+                    listing += &format!("{} 0x{:08x}: {}\n", marker, address, instruction);
+                    address += instruction.size();
+                }
+                Err(error) => {
+                    listing += &format!("{} 0x{:08x}: {}\n", marker, address, error);
+                    break;
+                }
+            }
+        }
+        listing
+    }
+
+    /// Show the bytes of the memory from an address, 16 per line.
+    fn memory(&self, address: usize, len: usize) -> String {
+        let memory = self.vm.memory();
+        if address >= memory.capacity() {
+            return format!("Address 0x{:08x} outside the memory\n", address);
+        }
+        memory.dump(address..address.saturating_add(len))
+    }
+}
+
+/// Parse a decimal number or a hexadecimal number prefixed with `0x`.
+fn parse_number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(digits) => usize::from_str_radix(digits, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use forge_vm::asm::Assembler;
+
+    fn debugger(source: &str) -> Debugger {
+        let program = Assembler::new().assemble_program(source).unwrap();
+        Debugger::new(VM::new(16, 256), program, 4).unwrap()
+    }
+
+    #[test]
+    fn test_debugger_step_and_break() {
+        // MOV (6 bytes), then the loop at 0x6: DEC (2 bytes), JMPNZ (5 bytes), HLT at 0xd
+        let mut debugger = debugger("MOV R0 2\nloop: DEC R0\nJMPNZ loop\nHLT");
+        assert_eq!(debugger.execute("step"), "=> 0x00000006: DEC R0\n");
+        assert_eq!(
+            debugger.execute("disassemble 0 2"),
+            "   0x00000000: MOV R0 2\n=> 0x00000006: DEC R0\n"
+        );
+        assert_eq!(
+            debugger.execute("break 0xd"),
+            "Breakpoint set at 0x0000000d\n"
+        );
+        assert_eq!(debugger.execute("continue"), "=> 0x0000000d: HLT\n");
+        assert!(debugger.execute("registers").contains("R0  0x00000000 0 "));
+        assert_eq!(
+            debugger.execute("c"),
+            "Program halted after 6 steps with exit code 0\n"
+        );
+        assert_eq!(
+            debugger.execute("s"),
+            "The program is not running, `reset` to restart it\n"
+        );
+        assert_eq!(debugger.execute("reset"), "=> 0x00000000: MOV R0 2\n");
+        assert_eq!(debugger.execute("step 2"), "=> 0x00000008: JMPNZ 0x6\n");
+    }
+
+    #[test]
+    fn test_debugger_inspect() {
+        let mut debugger = debugger("MOV R0 0x1234\nST R0 0x10\nPUSHREG R0\nPUSH 7\nDIV R0 R1 R1");
+        debugger.execute("step 4");
+        assert_eq!(
+            debugger.execute("memory 0x10 4"),
+            "00000010  34 12 00 00                                       |4...|\n"
+        );
+        assert_eq!(
+            debugger.execute("stack"),
+            "0: 0x00001234 (4660)\n1: 0x00000007 (7)\n"
+        );
+        assert!(debugger.execute("c").starts_with("Error at address 0x13:"));
+        assert_eq!(
+            debugger.execute("memory 0x1000"),
+            "Address 0x00001000 outside the memory\n"
+        );
+        assert_eq!(
+            debugger.execute("delete 4"),
+            "No breakpoint at 0x00000004\n"
+        );
+        assert_eq!(
+            debugger.execute("jump 4"),
+            "Invalid command `jump 4`, type `help` for help\n"
+        );
+        assert_eq!(debugger.execute("b x"), "Invalid number in `b x`\n");
+    }
+
+    #[test]
+    fn test_debugger_back() {
+        let mut debugger = debugger("MOV R0 2\nloop: DEC R0\nJMPNZ loop\nHLT");
+        assert_eq!(debugger.execute("back"), "No instruction to step back\n");
+        assert_eq!(
+            debugger.execute("c"),
+            "Program halted after 6 steps with exit code 0\n"
+        );
+        assert_eq!(debugger.execute("back"), "=> 0x0000000d: HLT\n");
+        assert_eq!(debugger.execute("back 2"), "=> 0x00000006: DEC R0\n");
+        assert!(debugger.execute("registers").contains("R0  0x00000001 1 "));
+        assert_eq!(debugger.execute("back 10"), "=> 0x00000000: MOV R0 2\n");
+        assert_eq!(
+            debugger.execute("c"),
+            "Program halted after 6 steps with exit code 0\n"
+        );
+    }
+
+    #[test]
+    fn test_debugger_run() {
+        let mut debugger = debugger("INC R0\nHLT");
+        let mut output = Vec::new();
+        debugger
+            .run("s\nquit\ns\n".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "=> 0x00000000: INC R0\n(forge) => 0x00000002: HLT\n(forge) \n"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! The `forge` command line tool, built with the `cli` feature.
+//!
+//! ```text
+//! forge run program.fvm --memory 64k --stack 1024
+//! forge debug program.fvm
+//! forge dap
+//! ```
+
+mod debugger;
+
+use std::process::ExitCode;
+
+use forge_vm::dap::DapServer;
+use forge_vm::vm::hardware_config::{MEMORY_SIZE, REGISTERS_COUNT, STACK_CAPACITY};
+use forge_vm::vm::loader::{load_binary, HexImage};
+use forge_vm::vm::program::Program;
+use forge_vm::{ExecutionError, Termination, VmBuilder, VM};
+
+const USAGE: &str = "\
+Usage: forge run <program> [options]
+       forge debug <program> [options]
+       forge dap
+
+Run a program and print the state of the VM when it stops, or debug it interactively.
+`forge dap` serves the Debug Adapter Protocol over the standard input and output.
+The program is a `.fvm` image, an Intel HEX file (`.hex`) or raw bytecode.
+
+Options:
+  --memory <size>     Size of the memory in bytes, with an optional `k` or `m` suffix
+  --stack <count>     Maximum number of values on the stack
+  --registers <count> Number of registers of the CPU
+  --max-steps <count> Stop with an error after this number of steps, with `run`";
+
+/// The exit status of a program stopped by an error of the VM.
+const EXIT_VM_ERROR: u8 = 1;
+/// The exit status of invalid arguments or an unreadable program.
+const EXIT_USAGE: u8 = 2;
+
+/// The subcommands of the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Run,
+    Debug,
+    Dap,
+}
+
+/// The options of the command line.
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    command: Command,
+    path: String,
+    memory_size: usize,
+    stack_capacity: usize,
+    registers: u8,
+    max_steps: u128,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if options.command == Command::Dap {
+        let mut server = DapServer::new(std::io::stdin().lock(), std::io::stdout());
+        return match server.serve() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                ExitCode::from(EXIT_VM_ERROR)
+            }
+        };
+    }
+    let program = match load_program(&options.path) {
+        Ok(program) => program,
+        Err(message) => {
+            eprintln!("error: {}: {}", options.path, message);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let mut vm = VmBuilder::new()
+        .memory_size(options.memory_size)
+        .stack_capacity(options.stack_capacity)
+        .registers(options.registers)
+        .build::<i32>();
+    if options.command == Command::Debug {
+        let mut debugger = match debugger::Debugger::new(vm, program, options.registers) {
+            Ok(debugger) => debugger,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                return ExitCode::from(EXIT_VM_ERROR);
+            }
+        };
+        if let Err(error) = debugger.run(std::io::stdin().lock(), std::io::stdout()) {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_VM_ERROR);
+        }
+        return ExitCode::SUCCESS;
+    }
+    let result = vm.run_program_with_limit(&program, options.max_steps);
+    print!("{}", format_state(&vm));
+    if let Err(error) = &result {
+        eprintln!("error: {}", error);
+    }
+    ExitCode::from(exit_status(&result))
+}
+
+/// The exit status of `forge run`: the low byte of the exit code of a terminated program, or
+/// `EXIT_VM_ERROR` if the VM stopped with an error.
+fn exit_status(result: &Result<Termination<i32>, ExecutionError<i32>>) -> u8 {
+    match result {
+        Ok(termination) => termination.exit_code as u8,
+        Err(_) => EXIT_VM_ERROR,
+    }
+}
+
+/// Parse the arguments of the command line, without the name of the executable.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut args = args.iter();
+    let command = match args.next().map(String::as_str) {
+        Some("run") => Command::Run,
+        Some("debug") => Command::Debug,
+        Some("dap") => Command::Dap,
+        Some(command) => return Err(format!("unknown command `{}`", command)),
+        None => return Err("missing command".to_string()),
+    };
+    let mut path = None;
+    let mut options = Options {
+        command,
+        path: String::new(),
+        memory_size: MEMORY_SIZE,
+        stack_capacity: STACK_CAPACITY,
+        registers: REGISTERS_COUNT,
+        max_steps: u128::MAX,
+    };
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if path.replace(arg.clone()).is_some() {
+                return Err(format!("unexpected argument `{}`", arg));
+            }
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for `{}`", arg))?;
+        let invalid = || format!("invalid value for `{}`: {}", arg, value);
+        match arg.as_str() {
+            "--memory" => options.memory_size = parse_size(value).ok_or_else(invalid)?,
+            "--stack" => options.stack_capacity = value.parse().map_err(|_| invalid())?,
+            "--registers" => options.registers = value.parse().map_err(|_| invalid())?,
+            "--max-steps" => options.max_steps = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("unknown option `{}`", arg)),
+        }
+    }
+    options.path = match (command, path) {
+        (Command::Dap, None) => String::new(),
+        (Command::Dap, Some(path)) => return Err(format!("unexpected argument `{}`", path)),
+        (_, path) => path.ok_or("missing program")?,
+    };
+    Ok(options)
+}
+
+/// Parse a size in bytes, with an optional `k` (KiB) or `m` (MiB) suffix.
+fn parse_size(text: &str) -> Option<usize> {
+    let (digits, unit) = match text.char_indices().last()? {
+        (index, 'k' | 'K') => (&text[..index], 1 << 10),
+        (index, 'm' | 'M') => (&text[..index], 1 << 20),
+        _ => (text, 1),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// Load a program from a file: a `.fvm` image, recognized by its magic number, an Intel HEX
+/// file with the `.hex` extension, or raw bytecode.
+fn load_program(path: &str) -> Result<Program<'static>, String> {
+    let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+    if bytes.starts_with(&Program::MAGIC) {
+        Program::from_image(&bytes)
+            .map(Program::into_owned)
+            .map_err(|error| error.to_string())
+    } else if path.ends_with(".hex") {
+        let text = String::from_utf8(bytes).map_err(|error| error.to_string())?;
+        HexImage::parse(&text)
+            .and_then(|image| image.to_program(0))
+            .map_err(|error| error.to_string())
+    } else {
+        Ok(load_binary(&bytes).into_owned())
+    }
+}
+
+/// Format the state of the VM when it stops: the steps, the exit code of a terminated program
+/// and the state rendered by `VM::render_state`.
+fn format_state(vm: &VM<i32>) -> String {
+    let exit_code = match vm.exit_code() {
+        Some(code) => format!("exit code: {}\n", code),
+        None => String::new(),
+    };
+    format!(
+        "steps: {}\n{}{}",
+        vm.counters().steps,
+        exit_code,
+        vm.render_state()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_args(&args(
+                "run program.fvm --memory 64k --stack 16 --registers 8"
+            )),
+            Ok(Options {
+                command: Command::Run,
+                path: "program.fvm".to_string(),
+                memory_size: 65536,
+                stack_capacity: 16,
+                registers: 8,
+                max_steps: u128::MAX,
+            })
+        );
+        assert_eq!(
+            parse_args(&args("run --max-steps 10 program.bin")).map(|options| options.max_steps),
+            Ok(10)
+        );
+        assert_eq!(
+            parse_args(&args("debug program.hex")).map(|options| options.command),
+            Ok(Command::Debug)
+        );
+        assert_eq!(
+            parse_args(&args("dap")).map(|options| options.command),
+            Ok(Command::Dap)
+        );
+        assert!(parse_args(&args("dap program.s")).is_err());
+        assert!(parse_args(&args("run")).is_err());
+        assert!(parse_args(&args("walk program.fvm")).is_err());
+        assert!(parse_args(&args("run a.fvm b.fvm")).is_err());
+        assert!(parse_args(&args("run a.fvm --memory")).is_err());
+        assert!(parse_args(&args("run a.fvm --memory lots")).is_err());
+        assert!(parse_args(&args("run a.fvm --speed 2")).is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("64k"), Some(65536));
+        assert_eq!(parse_size("2M"), Some(2 << 20));
+        assert_eq!(parse_size("k"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let mut vm = VM::<i32>::new(16, 16);
+        let program = forge_vm::asm::Assembler::new()
+            .assemble("MOV R0 3\nHLT")
+            .unwrap();
+        assert_eq!(exit_status(&vm.run(&program)), 3);
+        let program = forge_vm::asm::Assembler::new()
+            .assemble("MOV R0 0\nHLT")
+            .unwrap();
+        assert_eq!(exit_status(&vm.run(&program)), 0);
+        // only the low byte of the exit code is kept
+        let program = forge_vm::asm::Assembler::new()
+            .assemble("MOV R0 0x102\nHLT")
+            .unwrap();
+        assert_eq!(exit_status(&vm.run(&program)), 2);
+        let program = forge_vm::asm::Assembler::new()
+            .assemble("LD R0 0xffff\nHLT")
+            .unwrap();
+        assert_eq!(exit_status(&vm.run(&program)), EXIT_VM_ERROR);
+    }
+
+    #[test]
+    fn test_format_state() {
+        let mut vm = VM::<i32>::new(16, 16);
+        assert!(vm.run(&[0x0f, 0x01, 0xff]).is_ok()); // DEC R1, HLT
+        assert_eq!(
+            format_state(&vm),
+            "steps: 2\n\
+             exit code: 0\n\
+             R0  0x00000000 0   R1  0xffffffff -1  R2  0x00000000 0   R3  0x00000000 0\n\
+             flags Z=0 C=0 O=0 N=1 I=0\n\
+             pc    0x00000002 HLT\n\
+             stack [] (depth 0)\n"
+        );
+    }
+}
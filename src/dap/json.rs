@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A JSON value, as exchanged by the Debug Adapter Protocol.
+///
+/// The numbers are stored as `f64` and the integers are written without a fraction.
+///
+/// # Example:
+/// ```
+/// use forge_vm::dap::json::Json;
+/// let value = Json::parse(r#"{"command": "next", "arguments": {"threadId": 1}}"#).unwrap();
+/// assert_eq!(value.get("command").and_then(Json::as_str), Some("next"));
+/// assert_eq!(value.to_string(), r#"{"arguments":{"threadId":1},"command":"next"}"#);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    /// Parse a JSON text.
+    ///
+    /// # Errors
+    /// Returns a description of the first syntax error with its byte offset.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser { text, offset: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        match parser.offset == text.len() {
+            true => Ok(value),
+            false => Err(parser.error("trailing characters")),
+        }
+    }
+
+    /// Create an object from its members.
+    pub fn object<const N: usize>(members: [(&str, Json); N]) -> Self {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Get a member of an object.
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.get(name),
+            _ => None,
+        }
+    }
+
+    /// Insert a member into an object, a value of another type is left unchanged.
+    pub fn insert(&mut self, name: &str, value: Json) {
+        if let Json::Object(members) = self {
+            members.insert(name.to_string(), value);
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Get a non-negative integer, `None` for a fraction or a negative number.
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|value| value.fract() == 0.0 && *value >= 0.0)
+            .map(|value| value as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Self {
+        Json::Array(values)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+                write!(f, "{}", *value as i64)
+            }
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Write a string with its special characters escaped.
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for character in value.chars() {
+        match character {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            character if character.is_control() => write!(f, "\\u{:04x}", character as u32)?,
+            character => write!(f, "{}", character)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Recursive descent parser of a JSON text.
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, description: &str) -> String {
+        format!("Invalid JSON at offset {}: {}", self.offset, description)
+    }
+
+    fn whitespace(&mut self) {
+        let rest = &self.text[self.offset..];
+        self.offset += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    /// Consume the expected text.
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.text[self.offset..].starts_with(expected) {
+            true => {
+                self.offset += expected.len();
+                Ok(())
+            }
+            false => Err(self.error(&format!("expected `{}`", expected))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.peek() {
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => {
+                self.offset += 1;
+                let mut values = Vec::new();
+                self.whitespace();
+                if self.peek() == Some(']') {
+                    self.offset += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.whitespace();
+                    match self.peek() {
+                        Some(',') => self.offset += 1,
+                        _ => break,
+                    }
+                }
+                self.expect("]").map(|_| Json::Array(values))
+            }
+            Some('{') => {
+                self.offset += 1;
+                let mut members = BTreeMap::new();
+                self.whitespace();
+                if self.peek() == Some('}') {
+                    self.offset += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.whitespace();
+                    let name = self.string()?;
+                    self.whitespace();
+                    self.expect(":")?;
+                    members.insert(name, self.value()?);
+                    self.whitespace();
+                    match self.peek() {
+                        Some(',') => self.offset += 1,
+                        _ => break,
+                    }
+                }
+                self.expect("}").map(|_| Json::Object(members))
+            }
+            Some('-' | '0'..='9') => {
+                let rest = &self.text[self.offset..];
+                let end = rest
+                    .find(|character: char| !"+-.eE0123456789".contains(character))
+                    .unwrap_or(rest.len());
+                let number = rest[..end]
+                    .parse()
+                    .map_err(|_| self.error("invalid number"))?;
+                self.offset += end;
+                Ok(Json::Number(number))
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut value = String::new();
+        loop {
+            let character = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.offset += character.len_utf8();
+            match character {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escape = self.peek().ok_or_else(|| self.error("invalid escape"))?;
+                    self.offset += 1;
+                    value.push(match escape {
+                        '"' | '\\' | '/' => escape,
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    });
+                }
+                character => value.push(character),
+            }
+        }
+    }
+
+    /// Parse the code of a `\u` escape, with the low surrogate of a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let mut code = self.hex_code()?;
+        if (0xd800..0xdc00).contains(&code) {
+            self.expect("\\u")?;
+            let low = self.hex_code()?;
+            code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+        }
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex_code(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.offset..self.offset + 4)
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.offset += 4;
+        Ok(digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_roundtrip() {
+        let text = r#"{"a":[1,-2.5,true,null],"b":{},"c":"x\"\\\n\u0001é"}"#;
+        let value = Json::parse(text).unwrap();
+        assert_eq!(
+            value.get("a").and_then(|a| a.as_array()).map(<[_]>::len),
+            Some(4)
+        );
+        assert_eq!(value.to_string(), text);
+        assert_eq!(Json::parse(&value.to_string()), Ok(value));
+    }
+
+    #[test]
+    fn test_json_parse() {
+        let value = Json::parse(" [ \"\\ud83d\\ude00\" , 1e3 , [ ] ] ").unwrap();
+        assert_eq!(
+            value,
+            Json::Array(vec![
+                Json::from("😀"),
+                Json::Number(1000.0),
+                Json::Array(vec![])
+            ])
+        );
+        assert_eq!(Json::Number(3.0).as_usize(), Some(3));
+        assert_eq!(Json::Number(-3.0).as_usize(), None);
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("\"abc").is_err());
+        assert_eq!(
+            Json::parse("1 2"),
+            Err("Invalid JSON at offset 2: trailing characters".to_string())
+        );
+    }
+}
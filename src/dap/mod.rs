@@ -0,0 +1,877 @@
+pub mod json;
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::asm::Assembler;
+use crate::vm::debug_info::DebugInfo;
+use crate::vm::decoder::Decoder;
+use crate::vm::hardware_config::REGISTERS_COUNT;
+use crate::vm::instructions::Instruction;
+use crate::vm::program::Program;
+use crate::{VmBuilder, VM};
+use json::Json;
+
+/// The variables reference of the registers scope.
+const REGISTERS_REFERENCE: usize = 1;
+/// The variables reference of the stack scope.
+const STACK_REFERENCE: usize = 2;
+/// The variables reference of the memory scope, one variable per row of `MEMORY_ROW` bytes.
+const MEMORY_REFERENCE: usize = 3;
+/// The number of bytes of a row of the memory scope.
+const MEMORY_ROW: usize = 16;
+/// The id of the only thread reported to the client.
+const THREAD_ID: usize = 1;
+
+/// Debug Adapter Protocol (DAP) server, to debug ForgeVM programs from an editor like VS Code.
+///
+/// The server reads the requests of the client from its input and writes the responses and
+/// the events to its output, with the `Content-Length` framing of the protocol, usually over
+/// the standard input and output of `forge dap`.
+///
+/// A session starts with a request:
+/// - `launch`: assemble the source file `program` with its debug information, or load a
+///   `.fvm` image with the debug information of its sidecar file `<program>.dbg` if any.
+///   The VM is configured by the optional `memorySize`, `stackCapacity` and `registers`
+///   arguments, and `stopOnEntry` stops the program before its first instruction.
+/// - `attach`: debug the program given to `with_program` by the host embedding the server,
+///   with its source file given by the optional `source` argument.
+///
+/// The breakpoints are set on the source lines, the stack frames are tracked from the `CALL`
+/// and `RET` instructions, and the registers, the stack and the memory are shown as variables.
+/// The memory can also be read by the memory view of the client with `readMemory`.
+///
+/// The program runs on the thread of the server between two requests, so `pause` only
+/// reports the position of a program that is already stopped.
+pub struct DapServer<R, W> {
+    input: R,
+    output: W,
+    /// The sequence number of the last message sent.
+    seq: usize,
+    /// The program debugged by `attach`, with its debug information.
+    attached: Option<(Program<'static>, DebugInfo)>,
+    session: Option<Session>,
+}
+
+/// A debugged program with its VM.
+struct Session {
+    vm: VM<i32>,
+    program: Program<'static>,
+    debug_info: DebugInfo,
+    /// The path of the source file of the program.
+    source: Option<String>,
+    decoder: Decoder<i32>,
+    breakpoints: BTreeSet<usize>,
+    /// The functions called and not returned yet, from the outermost.
+    frames: Vec<Frame>,
+    stop_on_entry: bool,
+    /// Set when the program halted or stopped with an error.
+    finished: bool,
+}
+
+/// A function called by `CALL`.
+struct Frame {
+    /// The address of the `CALL` instruction.
+    call_site: usize,
+    /// The address of the function.
+    function: usize,
+}
+
+/// How far `Session::resume` executes the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepMode {
+    /// Until a breakpoint or the end of the program.
+    Continue,
+    /// Until the source line changes, entering the called functions.
+    In,
+    /// Until the source line changes in the current function or a caller.
+    Over,
+    /// Until the current function returns.
+    Out,
+}
+
+/// Why the execution stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Stop {
+    Entry,
+    Breakpoint,
+    Step,
+    Pause,
+    Exception(String),
+    /// The program ended, with its exit code.
+    Terminated(usize),
+}
+
+impl<R: BufRead, W: Write> DapServer<R, W> {
+    /// Create a server reading the requests from `input` and writing to `output`.
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            seq: 0,
+            attached: None,
+            session: None,
+        }
+    }
+
+    /// Set the program debugged when the client sends an `attach` request.
+    pub fn with_program(mut self, program: Program<'static>, debug_info: DebugInfo) -> Self {
+        self.attached = Some((program, debug_info));
+        self
+    }
+
+    /// Serve the requests until the `disconnect` request or the end of the input.
+    ///
+    /// # Errors
+    /// Returns the I/O errors of the input and the output.
+    pub fn serve(&mut self) -> io::Result<()> {
+        while let Some(message) = self.read_message()? {
+            match Json::parse(&message) {
+                Ok(request) => {
+                    if !self.handle(&request)? {
+                        break;
+                    }
+                }
+                Err(error) => self.output_event("stderr", &format!("{}\n", error))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the content of the next message, `None` at the end of the input.
+    fn read_message(&mut self) -> io::Result<Option<String>> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            match line.trim_end().strip_prefix("Content-Length:") {
+                Some(value) => length = value.trim().parse().ok(),
+                None if line.trim_end().is_empty() && length.is_some() => break,
+                None => {}
+            }
+        }
+        let mut content = vec![0; length.unwrap_or_default()];
+        self.input.read_exact(&mut content)?;
+        Ok(Some(String::from_utf8_lossy(&content).into_owned()))
+    }
+
+    /// Write a message with its header.
+    fn write_message(&mut self, mut message: Json) -> io::Result<()> {
+        self.seq += 1;
+        message.insert("seq", self.seq.into());
+        let content = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )?;
+        self.output.flush()
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.write_message(Json::object([
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ]))
+    }
+
+    fn output_event(&mut self, category: &str, output: &str) -> io::Result<()> {
+        self.event(
+            "output",
+            Json::object([("category", category.into()), ("output", output.into())]),
+        )
+    }
+
+    /// Handle a request, returns `false` after the `disconnect` request.
+    fn handle(&mut self, request: &Json) -> io::Result<bool> {
+        let command = request
+            .get("command")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        let arguments = request.get("arguments").unwrap_or(&Json::Null);
+        let result = match command {
+            "initialize" => Ok(capabilities()),
+            "launch" => self.launch(arguments),
+            "attach" => self.attach(arguments),
+            "disconnect" => Ok(Json::Null),
+            _ => match &mut self.session {
+                Some(session) => session.handle(command, arguments),
+                None => Err(format!(
+                    "Unsupported request `{}` without a program",
+                    command
+                )),
+            },
+        };
+
+        let mut response = Json::object([
+            ("type", "response".into()),
+            (
+                "request_seq",
+                request.get("seq").cloned().unwrap_or(Json::Null),
+            ),
+            ("command", command.into()),
+            ("success", result.is_ok().into()),
+        ]);
+        match &result {
+            Ok(Json::Null) => {}
+            Ok(body) => response.insert("body", body.clone()),
+            Err(message) => response.insert("message", message.as_str().into()),
+        }
+        self.write_message(response)?;
+        if result.is_err() {
+            return Ok(true);
+        }
+
+        let Some(session) = &mut self.session else {
+            return Ok(command != "disconnect");
+        };
+        let stop = match command {
+            "launch" | "attach" => {
+                return self.event("initialized", Json::object([])).map(|_| true)
+            }
+            "configurationDone" if session.stop_on_entry => Stop::Entry,
+            "configurationDone" | "continue" => session.resume(StepMode::Continue),
+            "next" => session.resume(StepMode::Over),
+            "stepIn" => session.resume(StepMode::In),
+            "stepOut" => session.resume(StepMode::Out),
+            "pause" => Stop::Pause,
+            "disconnect" => return Ok(false),
+            _ => return Ok(true),
+        };
+        self.report(stop)?;
+        Ok(true)
+    }
+
+    /// Send the events of a stop of the execution.
+    fn report(&mut self, stop: Stop) -> io::Result<()> {
+        let reason = match &stop {
+            Stop::Entry => "entry",
+            Stop::Breakpoint => "breakpoint",
+            Stop::Step => "step",
+            Stop::Pause => "pause",
+            Stop::Exception(description) => {
+                self.output_event("stderr", &format!("{}\n", description))?;
+                "exception"
+            }
+            Stop::Terminated(exit_code) => {
+                self.event("exited", Json::object([("exitCode", (*exit_code).into())]))?;
+                return self.event("terminated", Json::object([]));
+            }
+        };
+        let mut body = Json::object([
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ]);
+        if let Stop::Exception(description) = stop {
+            body.insert("text", description.into());
+        }
+        self.event("stopped", body)
+    }
+
+    /// Start a session with a program from a file.
+    fn launch(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("Missing `program` argument")?;
+        let registers = registers_argument(arguments)?;
+        let (program, debug_info, source) = if path.ends_with(".fvm") {
+            let image = std::fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+            let program = Program::from_image(&image)
+                .map_err(|error| error.to_string())?
+                .into_owned();
+            let debug_info = match std::fs::read_to_string(format!("{}.dbg", path)) {
+                Ok(text) => DebugInfo::parse(&text).map_err(|error| error.to_string())?,
+                Err(_) => DebugInfo::new(),
+            };
+            (program, debug_info, None)
+        } else {
+            let text =
+                std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+            let (program, debug_info) = Assembler::with_registers(registers)
+                .assemble_with_debug_info(&text)
+                .map_err(|error| format!("{}: {}", path, error))?;
+            (program, debug_info, Some(path.to_string()))
+        };
+        self.start(program, debug_info, source, arguments)
+    }
+
+    /// Start a session with the program of the host.
+    fn attach(&mut self, arguments: &Json) -> Result<Json, String> {
+        let (program, debug_info) = self.attached.clone().ok_or("No program to attach to")?;
+        let source = arguments
+            .get("source")
+            .and_then(Json::as_str)
+            .map(String::from);
+        self.start(program, debug_info, source, arguments)
+    }
+
+    fn start(
+        &mut self,
+        program: Program<'static>,
+        debug_info: DebugInfo,
+        source: Option<String>,
+        arguments: &Json,
+    ) -> Result<Json, String> {
+        let registers = registers_argument(arguments)?;
+        let mut builder = VmBuilder::new().registers(registers);
+        if let Some(size) = arguments.get("memorySize").and_then(Json::as_usize) {
+            builder = builder.memory_size(size);
+        }
+        if let Some(capacity) = arguments.get("stackCapacity").and_then(Json::as_usize) {
+            builder = builder.stack_capacity(capacity);
+        }
+        let mut vm = builder.build::<i32>();
+        vm.set_debug_info(debug_info.clone());
+        vm.load_program(&program)
+            .map_err(|error| error.to_string())?;
+        self.session = Some(Session {
+            vm,
+            program,
+            debug_info,
+            source,
+            decoder: Decoder::with_registers(registers),
+            breakpoints: BTreeSet::new(),
+            frames: Vec::new(),
+            stop_on_entry: arguments
+                .get("stopOnEntry")
+                .and_then(Json::as_bool)
+                .unwrap_or(false),
+            finished: false,
+        });
+        Ok(Json::Null)
+    }
+}
+
+impl Session {
+    /// Handle a request of a started session.
+    fn handle(&mut self, command: &str, arguments: &Json) -> Result<Json, String> {
+        match command {
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "configurationDone" | "next" | "stepIn" | "stepOut" | "pause" => Ok(Json::Null),
+            "continue" => Ok(Json::object([("allThreadsContinued", true.into())])),
+            "threads" => Ok(Json::object([(
+                "threads",
+                vec![Json::object([
+                    ("id", THREAD_ID.into()),
+                    ("name", "main".into()),
+                ])]
+                .into(),
+            )])),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(self.scopes()),
+            "variables" => self.variables(arguments),
+            "readMemory" => self.read_memory(arguments),
+            _ => Err(format!("Unsupported request `{}`", command)),
+        }
+    }
+
+    /// Get the source line of the program counter.
+    fn line(&self) -> Option<usize> {
+        self.debug_info.line_at(self.vm.pc())
+    }
+
+    /// Execute an instruction, tracking the calls and the returns.
+    fn step(&mut self) -> Result<bool, String> {
+        let pc = self.vm.pc();
+        let instruction = self
+            .decoder
+            .decode_instruction(self.program.slice_from(pc))
+            .ok();
+        let halted = self
+            .vm
+            .step()
+            .map_err(|error| format!("{} {}", error, self.vm.location()))?;
+        match instruction {
+            Some(Instruction::CALL { address }) if self.vm.pc() == address as usize => {
+                self.frames.push(Frame {
+                    call_site: pc,
+                    function: address as usize,
+                });
+            }
+            Some(Instruction::RET) => {
+                self.frames.pop();
+            }
+            _ => {}
+        }
+        Ok(halted)
+    }
+
+    /// Execute the program until a breakpoint, the end of the step or the end of the program.
+    fn resume(&mut self, mode: StepMode) -> Stop {
+        if self.finished {
+            return Stop::Terminated(1);
+        }
+        let depth = self.frames.len();
+        let line = self.line();
+        loop {
+            match self.step() {
+                Ok(false) => {}
+                Ok(true) => {
+                    self.finished = true;
+                    return Stop::Terminated(0);
+                }
+                Err(description) => {
+                    self.finished = true;
+                    return Stop::Exception(description);
+                }
+            }
+            if self.breakpoints.contains(&self.vm.pc()) {
+                return Stop::Breakpoint;
+            }
+            let line_changed = line.is_none() || self.line() != line;
+            let stop = match mode {
+                StepMode::Continue => false,
+                StepMode::In => line_changed,
+                StepMode::Over => self.frames.len() <= depth && line_changed,
+                StepMode::Out => self.frames.len() < depth,
+            };
+            if stop {
+                return Stop::Step;
+            }
+        }
+    }
+
+    /// Replace the breakpoints with the lines of the source of the program.
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let path = arguments
+            .get("source")
+            .and_then(|source| source.get("path"))
+            .and_then(Json::as_str);
+        let is_source = match (path, &self.source) {
+            (Some(path), Some(source)) => same_file(path, source),
+            _ => false,
+        };
+        self.breakpoints.clear();
+        let requested = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or_default();
+        let breakpoints = requested
+            .iter()
+            .filter_map(|breakpoint| breakpoint.get("line").and_then(Json::as_usize))
+            .map(|line| {
+                let address = self.debug_info.address_of_line(line).filter(|_| is_source);
+                if let Some(address) = address {
+                    self.breakpoints.insert(address);
+                }
+                Json::object([
+                    ("verified", address.is_some().into()),
+                    ("line", line.into()),
+                ])
+            })
+            .collect::<Vec<_>>();
+        Json::object([("breakpoints", breakpoints.into())])
+    }
+
+    /// The frames from the program counter to the outermost caller.
+    fn stack_trace(&self) -> Json {
+        // the program counter in the innermost function, then the call sites in the callers
+        let mut pcs = vec![(self.vm.pc(), self.frames.last().map(|frame| frame.function))];
+        for (index, frame) in self.frames.iter().enumerate().rev() {
+            let caller = index
+                .checked_sub(1)
+                .map(|caller| self.frames[caller].function);
+            pcs.push((frame.call_site, caller));
+        }
+        let frames: Vec<_> = pcs
+            .into_iter()
+            .enumerate()
+            .map(|(id, (pc, function))| {
+                let name = match (self.debug_info.function_at(pc), function) {
+                    (Some((name, _)), _) => name.to_string(),
+                    (None, Some(function)) => format!("0x{:08x}", function),
+                    (None, None) => "main".to_string(),
+                };
+                let line = self.line_at(pc);
+                let mut frame = Json::object([
+                    ("id", id.into()),
+                    ("name", name.into()),
+                    ("line", line.unwrap_or(0).into()),
+                    ("column", usize::from(line.is_some()).into()),
+                    (
+                        "instructionPointerReference",
+                        format!("0x{:08x}", pc).into(),
+                    ),
+                ]);
+                if let (Some(_), Some(source)) = (line, &self.source) {
+                    let name = Path::new(source)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    frame.insert(
+                        "source",
+                        Json::object([("name", name.into()), ("path", source.as_str().into())]),
+                    );
+                }
+                frame
+            })
+            .collect();
+        Json::object([
+            ("totalFrames", frames.len().into()),
+            ("stackFrames", frames.into()),
+        ])
+    }
+
+    /// Get the source line of an address, when the source is known.
+    fn line_at(&self, address: usize) -> Option<usize> {
+        self.source
+            .as_ref()
+            .and_then(|_| self.debug_info.line_at(address))
+    }
+
+    fn scopes(&self) -> Json {
+        let rows = self.vm.snapshot().memory.len().div_ceil(MEMORY_ROW);
+        let scope = |name: &str, reference: usize, expensive: bool| {
+            Json::object([
+                ("name", name.into()),
+                ("variablesReference", reference.into()),
+                ("expensive", expensive.into()),
+            ])
+        };
+        let mut memory = scope("Memory", MEMORY_REFERENCE, true);
+        memory.insert("indexedVariables", rows.into());
+        Json::object([(
+            "scopes",
+            vec![
+                scope("Registers", REGISTERS_REFERENCE, false),
+                scope("Stack", STACK_REFERENCE, false),
+                memory,
+            ]
+            .into(),
+        )])
+    }
+
+    fn variables(&self, arguments: &Json) -> Result<Json, String> {
+        let snapshot = self.vm.snapshot();
+        let variable = |name: String, value: String| {
+            Json::object([
+                ("name", name.into()),
+                ("value", value.into()),
+                ("variablesReference", 0usize.into()),
+            ])
+        };
+        let word = |value: i32| format!("{} (0x{:08x})", value, value);
+        let variables: Vec<_> = match arguments.get("variablesReference").and_then(Json::as_usize) {
+            Some(REGISTERS_REFERENCE) => {
+                let cpu = &snapshot.cpu;
+                let flags = cpu.status_flags;
+                let registers = cpu
+                    .registers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| variable(format!("R{}", index), word(*value)));
+                registers
+                    .chain([
+                        variable("PC".to_string(), format!("0x{:08x}", cpu.pc)),
+                        variable(
+                            "Flags".to_string(),
+                            format!(
+                                "zero={} carry={} overflow={} negative={}",
+                                flags.zero, flags.carry, flags.overflow, flags.negative
+                            ),
+                        ),
+                    ])
+                    .collect()
+            }
+            Some(STACK_REFERENCE) => snapshot
+                .stack
+                .iter()
+                .enumerate()
+                .map(|(index, value)| variable(format!("[{}]", index), word(*value)))
+                .collect(),
+            Some(MEMORY_REFERENCE) => {
+                let start = arguments.get("start").and_then(Json::as_usize).unwrap_or(0);
+                let count = arguments
+                    .get("count")
+                    .and_then(Json::as_usize)
+                    .unwrap_or(usize::MAX);
+                snapshot
+                    .memory
+                    .chunks(MEMORY_ROW)
+                    .enumerate()
+                    .skip(start)
+                    .take(count)
+                    .map(|(row, bytes)| {
+                        let bytes: Vec<_> =
+                            bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                        variable(format!("0x{:08x}", row * MEMORY_ROW), bytes.join(" "))
+                    })
+                    .collect()
+            }
+            _ => return Err("Invalid variables reference".to_string()),
+        };
+        Ok(Json::object([("variables", variables.into())]))
+    }
+
+    /// Read the memory for the memory view of the client, the bytes are encoded in base64.
+    fn read_memory(&self, arguments: &Json) -> Result<Json, String> {
+        let reference = arguments
+            .get("memoryReference")
+            .and_then(Json::as_str)
+            .ok_or("Missing `memoryReference` argument")?;
+        let base = match reference.strip_prefix("0x") {
+            Some(digits) => i64::from_str_radix(digits, 16),
+            None => reference.parse(),
+        }
+        .map_err(|_| format!("Invalid memory reference `{}`", reference))?;
+        let offset = arguments
+            .get("offset")
+            .and_then(Json::as_f64)
+            .unwrap_or(0.0) as i64;
+        let count = arguments.get("count").and_then(Json::as_usize).unwrap_or(0);
+        let memory = self.vm.snapshot().memory;
+        let address = usize::try_from(base + offset).unwrap_or(usize::MAX);
+        let bytes = memory
+            .get(address.min(memory.len())..address.saturating_add(count).min(memory.len()))
+            .unwrap_or_default();
+        Ok(Json::object([
+            ("address", format!("0x{:08x}", address).into()),
+            ("data", encode_base64(bytes).into()),
+            ("unreadableBytes", (count - bytes.len()).into()),
+        ]))
+    }
+}
+
+/// The capabilities of the server, the body of the `initialize` response.
+fn capabilities() -> Json {
+    Json::object([
+        ("supportsConfigurationDoneRequest", true.into()),
+        ("supportsReadMemoryRequest", true.into()),
+    ])
+}
+
+/// Get the `registers` argument of `launch` and `attach`.
+fn registers_argument(arguments: &Json) -> Result<u8, String> {
+    match arguments.get("registers") {
+        Some(registers) => registers
+            .as_usize()
+            .and_then(|registers| u8::try_from(registers).ok())
+            .ok_or_else(|| format!("Invalid `registers` argument: {}", registers)),
+        None => Ok(REGISTERS_COUNT),
+    }
+}
+
+/// Check whether two paths are the same file, comparing their canonical paths if they exist.
+fn same_file(path: &str, other: &str) -> bool {
+    path == other
+        || matches!(
+            (std::fs::canonicalize(path), std::fs::canonicalize(other)),
+            (Ok(path), Ok(other)) if path == other
+        )
+}
+
+/// Encode bytes in base64 with padding.
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame the requests as the client does.
+    fn requests(requests: &[&str]) -> Vec<u8> {
+        requests
+            .iter()
+            .enumerate()
+            .map(|(seq, request)| {
+                let request = request.replacen('{', &format!("{{\"seq\":{},", seq + 1), 1);
+                format!("Content-Length: {}\r\n\r\n{}", request.len(), request)
+            })
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    /// Parse the messages written by the server.
+    fn messages(output: &[u8]) -> Vec<Json> {
+        let output = String::from_utf8(output.to_vec()).unwrap();
+        output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|message| Json::parse(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .collect()
+    }
+
+    /// Find the message of a response or an event.
+    fn find<'a>(messages: &'a [Json], name: &str) -> Vec<&'a Json> {
+        messages
+            .iter()
+            .filter(|message| {
+                message.get("command").and_then(Json::as_str) == Some(name)
+                    || message.get("event").and_then(Json::as_str) == Some(name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_dap_session() {
+        let source = "
+            main:
+                MOV R0 2
+                CALL double
+                HLT
+            double:
+                ADD R0 R0 R0
+                RET
+        ";
+        let (program, debug_info) = Assembler::new().assemble_with_debug_info(source).unwrap();
+        let input = requests(&[
+            r#"{"type":"request","command":"initialize","arguments":{}}"#,
+            r#"{"type":"request","command":"attach","arguments":{"source":"main.s"}}"#,
+            r#"{"type":"request","command":"setBreakpoints","arguments":{"source":{"path":"main.s"},"breakpoints":[{"line":7},{"line":1}]}}"#,
+            r#"{"type":"request","command":"configurationDone"}"#,
+            r#"{"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#,
+            r#"{"type":"request","command":"variables","arguments":{"variablesReference":1}}"#,
+            r#"{"type":"request","command":"stepOut","arguments":{"threadId":1}}"#,
+            r#"{"type":"request","command":"continue","arguments":{"threadId":1}}"#,
+            r#"{"type":"request","command":"disconnect"}"#,
+            r#"{"type":"request","command":"threads"}"#,
+        ]);
+        let mut output = Vec::new();
+        DapServer::new(input.as_slice(), &mut output)
+            .with_program(program, debug_info)
+            .serve()
+            .unwrap();
+        let messages = messages(&output);
+
+        let capabilities = find(&messages, "initialize")[0].get("body").unwrap();
+        assert_eq!(capabilities, &super::capabilities());
+        assert_eq!(find(&messages, "initialized").len(), 1);
+        let breakpoints = find(&messages, "setBreakpoints")[0].get("body").unwrap();
+        assert_eq!(
+            breakpoints.to_string(),
+            r#"{"breakpoints":[{"line":7,"verified":true},{"line":1,"verified":false}]}"#
+        );
+
+        // stopped at the breakpoint in `double`, called from `main`
+        let stopped = find(&messages, "stopped");
+        assert_eq!(
+            stopped[0].get("body").unwrap().get("reason"),
+            Some(&"breakpoint".into())
+        );
+        let frames = find(&messages, "stackTrace")[0].get("body").unwrap();
+        let frames = frames.get("stackFrames").and_then(Json::as_array).unwrap();
+        let names: Vec<_> = frames
+            .iter()
+            .map(|frame| frame.get("name").unwrap().to_string())
+            .collect();
+        assert_eq!(names, [r#""double""#, r#""main""#]);
+        let lines: Vec<_> = frames
+            .iter()
+            .map(|frame| frame.get("line").unwrap().to_string())
+            .collect();
+        assert_eq!(lines, ["7", "4"]);
+        assert_eq!(
+            frames[0].get("source").unwrap().to_string(),
+            r#"{"name":"main.s","path":"main.s"}"#
+        );
+
+        let variables = find(&messages, "variables")[0].get("body").unwrap();
+        let r0 = &variables.get("variables").and_then(Json::as_array).unwrap()[0];
+        assert_eq!(r0.get("value"), Some(&"2 (0x00000002)".into()));
+
+        // back in `main` after the call, then to the end of the program
+        assert_eq!(
+            stopped[1].get("body").unwrap().get("reason"),
+            Some(&"step".into())
+        );
+        assert_eq!(
+            find(&messages, "exited")[0]
+                .get("body")
+                .unwrap()
+                .to_string(),
+            r#"{"exitCode":0}"#
+        );
+        assert_eq!(find(&messages, "terminated").len(), 1);
+        assert_eq!(find(&messages, "disconnect").len(), 1);
+        assert!(find(&messages, "threads").is_empty());
+    }
+
+    #[test]
+    fn test_dap_launch() {
+        let path = std::env::temp_dir().join("forge_vm_test_dap_launch.s");
+        std::fs::write(&path, "ST R0 0x20\nINC R0\nST R0 0x24\nHLT").unwrap();
+        let launch = format!(
+            r#"{{"type":"request","command":"launch","arguments":{{"program":{},"stopOnEntry":true,"memorySize":64}}}}"#,
+            Json::from(path.to_string_lossy().into_owned())
+        );
+        let input = requests(&[
+            &launch,
+            r#"{"type":"request","command":"configurationDone"}"#,
+            r#"{"type":"request","command":"next","arguments":{"threadId":1}}"#,
+            r#"{"type":"request","command":"next","arguments":{"threadId":1}}"#,
+            r#"{"type":"request","command":"next","arguments":{"threadId":1}}"#,
+            r#"{"type":"request","command":"readMemory","arguments":{"memoryReference":"0x22","count":4}}"#,
+            r#"{"type":"request","command":"variables","arguments":{"variablesReference":3,"start":2,"count":1}}"#,
+            r#"{"type":"request","command":"scopes","arguments":{"frameId":0}}"#,
+            r#"{"type":"request","command":"evaluate","arguments":{"expression":"R0"}}"#,
+        ]);
+        let mut output = Vec::new();
+        DapServer::new(input.as_slice(), &mut output)
+            .serve()
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+        let messages = messages(&output);
+
+        let reasons: Vec<_> = find(&messages, "stopped")
+            .iter()
+            .map(|stopped| {
+                stopped
+                    .get("body")
+                    .unwrap()
+                    .get("reason")
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            [r#""entry""#, r#""step""#, r#""step""#, r#""step""#]
+        );
+        let memory = find(&messages, "readMemory")[0].get("body").unwrap();
+        assert_eq!(
+            memory.to_string(),
+            r#"{"address":"0x00000022","data":"AAABAA==","unreadableBytes":0}"#
+        );
+        let row = find(&messages, "variables")[0].get("body").unwrap();
+        assert_eq!(
+            row.to_string(),
+            r#"{"variables":[{"name":"0x00000020","value":"00 00 00 00 01 00 00 00 00 00 00 00 00 00 00 00","variablesReference":0}]}"#
+        );
+        let scopes = find(&messages, "scopes")[0]
+            .get("body")
+            .unwrap()
+            .to_string();
+        assert!(scopes.contains(r#""indexedVariables":4"#));
+        let evaluate = find(&messages, "evaluate")[0];
+        assert_eq!(evaluate.get("success"), Some(&false.into()));
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    }
+}
@@ -0,0 +1,361 @@
+//! C interface of the VM, to embed it in C, C++ or any language with a C FFI.
+//!
+//! The crate is also built as a `cdylib`, and `include/forge_vm.h` declares these functions
+//! and error codes. The header is generated from this module by cbindgen, see `cbindgen.toml`. The VM is the `i32` architecture with the default hardware, handled through
+//! an opaque `ForgeVm` pointer created by `forgevm_new` and released by `forgevm_free`.
+//!
+//! The functions return `FORGEVM_OK` on success or a negative error code, and write their
+//! results through out pointers. A panic of the VM does not unwind into the caller: the
+//! function returns `FORGEVM_ERR_OTHER`, or null for `forgevm_new`, and the VM should be
+//! released.
+
+use std::ffi::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::vm::error::VmError;
+use crate::VM;
+
+/// The VM handled by the C functions, opaque on the C side.
+pub type ForgeVm = VM<i32>;
+
+/// The function succeeded.
+pub const FORGEVM_OK: i32 = 0;
+/// A pointer argument is null.
+pub const FORGEVM_ERR_NULL_POINTER: i32 = -1;
+/// A memory access is out of bounds, not aligned or faults.
+pub const FORGEVM_ERR_MEMORY: i32 = -2;
+/// The stack underflows or overflows.
+pub const FORGEVM_ERR_STACK: i32 = -3;
+/// The program holds an invalid opcode or instruction.
+pub const FORGEVM_ERR_INVALID_INSTRUCTION: i32 = -4;
+/// A register index is out of range.
+pub const FORGEVM_ERR_INVALID_REGISTER: i32 = -5;
+/// The program divides by zero.
+pub const FORGEVM_ERR_DIVISION_BY_ZERO: i32 = -6;
+/// The run exceeds its step limit, cycle budget or gas.
+pub const FORGEVM_ERR_LIMIT: i32 = -7;
+/// Any other error of the VM.
+pub const FORGEVM_ERR_OTHER: i32 = -8;
+/// The program has not terminated, it was not run or its run stopped with an error.
+pub const FORGEVM_ERR_NOT_TERMINATED: i32 = -9;
+
+/// Get the C error code of an error of the VM.
+pub fn error_code(error: &VmError) -> i32 {
+    match error {
+        VmError::MemoryOutOfBounds { .. }
+        | VmError::MemoryNotAligned { .. }
+        | VmError::PageFault { .. }
+        | VmError::ProtectionFault { .. } => FORGEVM_ERR_MEMORY,
+        VmError::StackUnderflow | VmError::StackOverflow => FORGEVM_ERR_STACK,
+        VmError::InvalidOpcode { .. } | VmError::InvalidInstruction => {
+            FORGEVM_ERR_INVALID_INSTRUCTION
+        }
+        VmError::InvalidRegister { .. } => FORGEVM_ERR_INVALID_REGISTER,
+        VmError::DivisionByZero => FORGEVM_ERR_DIVISION_BY_ZERO,
+        VmError::StepLimitExceeded | VmError::CycleBudgetExceeded | VmError::OutOfGas => {
+            FORGEVM_ERR_LIMIT
+        }
+        _ => FORGEVM_ERR_OTHER,
+    }
+}
+
+/// Run the body of a C function, returning `fallback` if it panics instead of unwinding across
+/// the FFI boundary.
+fn catch_panic<R>(fallback: R, body: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(fallback)
+}
+
+/// Create a VM, see `VM::new`.
+///
+/// # Returns
+/// The VM, to release with `forgevm_free`, or null if its creation panics, e.g. on a memory
+/// size overflowing the address space.
+#[no_mangle]
+pub extern "C" fn forgevm_new(stack_capacity: usize, memory_size: usize) -> *mut ForgeVm {
+    catch_panic(std::ptr::null_mut(), || {
+        Box::into_raw(Box::new(VM::new(stack_capacity, memory_size)))
+    })
+}
+
+/// Release a VM created by `forgevm_new`, nothing is done if `vm` is null.
+///
+/// # Safety
+/// `vm` must be null or returned by `forgevm_new` and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn forgevm_free(vm: *mut ForgeVm) {
+    catch_panic((), || {
+        if !vm.is_null() {
+            drop(Box::from_raw(vm));
+        }
+    })
+}
+
+/// Run a program, see `VM::run`.
+///
+/// # Parameters
+/// - `code`, `len`: The bytecode of the program, `code` can be null for an empty program.
+/// - `steps`: Set to the number of executed steps on success, ignored if null.
+///
+/// # Safety
+/// `vm` must be a live VM of `forgevm_new` and `code` must point to `len` readable bytes, or be
+/// null if `len` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn forgevm_run(
+    vm: *mut ForgeVm,
+    code: *const u8,
+    len: usize,
+    steps: *mut u64,
+) -> i32 {
+    catch_panic(FORGEVM_ERR_OTHER, || {
+        let Some(vm) = vm.as_mut() else {
+            return FORGEVM_ERR_NULL_POINTER;
+        };
+        let program = match (code.is_null(), len) {
+            (true, 0) => &[][..],
+            (true, _) => return FORGEVM_ERR_NULL_POINTER,
+            (false, _) => std::slice::from_raw_parts(code, len),
+        };
+        match vm.run(program) {
+            Ok(termination) => {
+                if let Some(steps) = steps.as_mut() {
+                    *steps = u64::try_from(termination.steps).unwrap_or(u64::MAX);
+                }
+                FORGEVM_OK
+            }
+            Err(error) => error_code(&error.source),
+        }
+    })
+}
+
+/// Get the exit code of the program terminated by the last run, see `VM::exit_code`.
+///
+/// # Safety
+/// `vm` must be a live VM of `forgevm_new` and `code` must point to a writable `int32_t`.
+#[no_mangle]
+pub unsafe extern "C" fn forgevm_exit_code(vm: *const ForgeVm, code: *mut i32) -> i32 {
+    catch_panic(FORGEVM_ERR_OTHER, || {
+        let (Some(vm), Some(code)) = (vm.as_ref(), code.as_mut()) else {
+            return FORGEVM_ERR_NULL_POINTER;
+        };
+        match vm.exit_code() {
+            Some(exit_code) => {
+                *code = exit_code;
+                FORGEVM_OK
+            }
+            None => FORGEVM_ERR_NOT_TERMINATED,
+        }
+    })
+}
+
+/// Read a register of the VM.
+///
+/// # Safety
+/// `vm` must be a live VM of `forgevm_new` and `value` must point to a writable `int32_t`.
+#[no_mangle]
+pub unsafe extern "C" fn forgevm_get_register(
+    vm: *const ForgeVm,
+    index: u8,
+    value: *mut i32,
+) -> i32 {
+    catch_panic(FORGEVM_ERR_OTHER, || {
+        let (Some(vm), Some(value)) = (vm.as_ref(), value.as_mut()) else {
+            return FORGEVM_ERR_NULL_POINTER;
+        };
+        match vm.registers().get(index as usize) {
+            Some(register) => {
+                *value = *register;
+                FORGEVM_OK
+            }
+            None => FORGEVM_ERR_INVALID_REGISTER,
+        }
+    })
+}
+
+/// Copy `len` bytes of the memory of the VM from `address` into `buffer`.
+///
+/// # Safety
+/// `vm` must be a live VM of `forgevm_new` and `buffer` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn forgevm_read_memory(
+    vm: *const ForgeVm,
+    address: usize,
+    buffer: *mut u8,
+    len: usize,
+) -> i32 {
+    catch_panic(FORGEVM_ERR_OTHER, || {
+        let (Some(vm), false) = (vm.as_ref(), buffer.is_null()) else {
+            return FORGEVM_ERR_NULL_POINTER;
+        };
+        let bytes = address
+            .checked_add(len)
+            .and_then(|end| vm.memory().bytes().get(address..end));
+        match bytes {
+            Some(bytes) => {
+                std::slice::from_raw_parts_mut(buffer, len).copy_from_slice(bytes);
+                FORGEVM_OK
+            }
+            None => FORGEVM_ERR_MEMORY,
+        }
+    })
+}
+
+/// Get the description of an error code, as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn forgevm_error_message(code: i32) -> *const c_char {
+    let message: &'static std::ffi::CStr = catch_panic(c"error of the VM", || match code {
+        FORGEVM_OK => c"success",
+        FORGEVM_ERR_NULL_POINTER => c"null pointer",
+        FORGEVM_ERR_MEMORY => c"memory access error",
+        FORGEVM_ERR_STACK => c"stack underflow or overflow",
+        FORGEVM_ERR_INVALID_INSTRUCTION => c"invalid instruction",
+        FORGEVM_ERR_INVALID_REGISTER => c"invalid register",
+        FORGEVM_ERR_DIVISION_BY_ZERO => c"division by zero",
+        FORGEVM_ERR_LIMIT => c"limit exceeded",
+        FORGEVM_ERR_OTHER => c"error of the VM",
+        FORGEVM_ERR_NOT_TERMINATED => c"program not terminated",
+        _ => c"unknown error code",
+    });
+    message.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_ffi_run() {
+        let code = crate::asm::Assembler::new()
+            .assemble("MOV R0 5\nINC R0\nST R0 0x10\nHLT")
+            .unwrap();
+        unsafe {
+            let vm = forgevm_new(1024, 1024);
+            let mut exit_code = 0;
+            assert_eq!(
+                forgevm_exit_code(vm, &mut exit_code),
+                FORGEVM_ERR_NOT_TERMINATED
+            );
+            let mut steps = 0;
+            assert_eq!(
+                forgevm_run(vm, code.as_ptr(), code.len(), &mut steps),
+                FORGEVM_OK
+            );
+            assert_eq!(steps, 4);
+            assert_eq!(forgevm_exit_code(vm, &mut exit_code), FORGEVM_OK);
+            assert_eq!(exit_code, 6);
+
+            let mut value = 0;
+            assert_eq!(forgevm_get_register(vm, 0, &mut value), FORGEVM_OK);
+            assert_eq!(value, 6);
+            assert_eq!(
+                forgevm_get_register(vm, 4, &mut value),
+                FORGEVM_ERR_INVALID_REGISTER
+            );
+
+            let mut buffer = [0; 4];
+            assert_eq!(
+                forgevm_read_memory(vm, 0x10, buffer.as_mut_ptr(), 4),
+                FORGEVM_OK
+            );
+            assert_eq!(buffer, 6i32.to_le_bytes());
+            assert_eq!(
+                forgevm_read_memory(vm, 1022, buffer.as_mut_ptr(), 4),
+                FORGEVM_ERR_MEMORY
+            );
+
+            let code = crate::asm::Assembler::new()
+                .assemble("DIV R0 R0 R1\nHLT")
+                .unwrap();
+            assert_eq!(
+                forgevm_run(vm, code.as_ptr(), code.len(), std::ptr::null_mut()),
+                FORGEVM_ERR_DIVISION_BY_ZERO
+            );
+            assert_eq!(
+                forgevm_exit_code(vm, &mut exit_code),
+                FORGEVM_ERR_NOT_TERMINATED
+            );
+            assert_eq!(
+                forgevm_run(std::ptr::null_mut(), code.as_ptr(), code.len(), &mut steps),
+                FORGEVM_ERR_NULL_POINTER
+            );
+            forgevm_free(vm);
+            forgevm_free(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_ffi_empty_program() {
+        // a null pointer is an empty program, without an instruction to fetch
+        unsafe {
+            let vm = forgevm_new(1024, 1024);
+            let mut steps = 0;
+            assert_eq!(
+                forgevm_run(vm, std::ptr::null(), 0, &mut steps),
+                forgevm_run(vm, [].as_ptr(), 0, &mut steps)
+            );
+            assert_eq!(
+                forgevm_run(vm, std::ptr::null(), 0, &mut steps),
+                FORGEVM_ERR_INVALID_INSTRUCTION
+            );
+            assert_eq!(
+                forgevm_run(vm, std::ptr::null(), 1, &mut steps),
+                FORGEVM_ERR_NULL_POINTER
+            );
+            forgevm_free(vm);
+        }
+    }
+
+    #[test]
+    fn test_ffi_panic() {
+        // the allocation of the memory panics on the overflow of its size
+        assert!(forgevm_new(1024, usize::MAX).is_null());
+        assert_eq!(
+            catch_panic(FORGEVM_ERR_OTHER, || panic!("a bug of the VM")),
+            FORGEVM_ERR_OTHER
+        );
+        assert_eq!(catch_panic(FORGEVM_ERR_OTHER, || FORGEVM_OK), FORGEVM_OK);
+    }
+
+    #[test]
+    fn test_ffi_error_message() {
+        let message = unsafe { CStr::from_ptr(forgevm_error_message(FORGEVM_ERR_STACK)) };
+        assert_eq!(message.to_str(), Ok("stack underflow or overflow"));
+        assert_eq!(error_code(&VmError::OutOfGas), FORGEVM_ERR_LIMIT);
+        assert_eq!(error_code(&VmError::Deadlock), FORGEVM_ERR_OTHER);
+    }
+
+    #[test]
+    fn test_header_declarations() {
+        // the header declares every function and error code with the same value
+        let header = include_str!("../include/forge_vm.h");
+        for function in [
+            "forgevm_new(",
+            "forgevm_free(",
+            "forgevm_run(",
+            "forgevm_exit_code(",
+            "forgevm_get_register(",
+            "forgevm_read_memory(",
+            "forgevm_error_message(",
+        ] {
+            assert!(header.contains(function), "{} is not declared", function);
+        }
+        for (name, code) in [
+            ("FORGEVM_OK", FORGEVM_OK),
+            ("FORGEVM_ERR_NULL_POINTER", FORGEVM_ERR_NULL_POINTER),
+            ("FORGEVM_ERR_MEMORY", FORGEVM_ERR_MEMORY),
+            ("FORGEVM_ERR_STACK", FORGEVM_ERR_STACK),
+            (
+                "FORGEVM_ERR_INVALID_INSTRUCTION",
+                FORGEVM_ERR_INVALID_INSTRUCTION,
+            ),
+            ("FORGEVM_ERR_INVALID_REGISTER", FORGEVM_ERR_INVALID_REGISTER),
+            ("FORGEVM_ERR_DIVISION_BY_ZERO", FORGEVM_ERR_DIVISION_BY_ZERO),
+            ("FORGEVM_ERR_LIMIT", FORGEVM_ERR_LIMIT),
+            ("FORGEVM_ERR_OTHER", FORGEVM_ERR_OTHER),
+            ("FORGEVM_ERR_NOT_TERMINATED", FORGEVM_ERR_NOT_TERMINATED),
+        ] {
+            let define = format!("#define {} {}\n", name, code);
+            assert!(header.contains(&define), "{} is not declared", define);
+        }
+    }
+}
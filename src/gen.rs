@@ -0,0 +1,405 @@
+//! Random valid programs for stress testing, see `Generator`.
+//!
+//! Unlike the programs of `vm::fuzz` and `testing`, which can fault or loop forever, the programs
+//! of a `Generator` pass `Program::verify` and always halt within `Generator::max_steps`: the
+//! loops are counted and the other jumps go forward. They suit the differential tests of an
+//! interpreter change, which compare the final states of two VMs, and the soak tests:
+//!
+//! ```
+//! use forge_vm::gen::Generator;
+//! use forge_vm::VM;
+//! let generator = Generator::new().instructions(100);
+//! for seed in 0..20 {
+//!     let program = generator.generate(seed);
+//!     let mut vm = VM::<i32>::new(1024, 1024);
+//!     let termination = vm.run_with_limit(&program, generator.max_steps()).unwrap();
+//!     assert!(termination.steps <= generator.max_steps());
+//! }
+//! ```
+
+use std::ops::Range;
+
+#[cfg(feature = "proptest")]
+use proptest::prelude::*;
+
+use crate::asm::builder::{ProgramBuilder, Reg};
+use crate::vm::decoder::Decoder;
+use crate::vm::hardware_config::REGISTERS_COUNT;
+use crate::vm::program::Program;
+use crate::vm::random::{RandomSource, Rng};
+use crate::Instruction;
+
+/// The generator of random programs which pass the verifier and halt.
+///
+/// A program is a sequence of random register, memory and comparison instructions, nested in
+/// counted loops and forward conditional jumps, ending with `HLT`. A loop keeps its counter in a
+/// register the instructions of its body do not write, so a CPU needs more registers than the
+/// nesting depth for the programs to have loops.
+///
+/// The programs run on a default `VM<i32>` whose memory covers the memory window, they do not
+/// divide, use the stack or the interrupts. With `VmBuilder::checked_arithmetic`, their
+/// arithmetic can overflow.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    /// The number of registers of the CPU, the bound of the register operands.
+    registers_count: u8,
+    /// The number of instructions before the final `HLT`.
+    instructions: usize,
+    /// The maximum nesting of the loops and the conditional blocks.
+    max_depth: u32,
+    /// The maximum number of iterations of a loop.
+    max_iterations: u32,
+    /// The addresses read and written by the memory instructions.
+    memory: Range<u32>,
+}
+
+impl Generator {
+    /// Create a generator for the default CPU, with `64` instructions, loops of up to `4`
+    /// iterations nested up to `2` levels, and the memory window `0x100..0x200`.
+    pub fn new() -> Self {
+        Self {
+            registers_count: REGISTERS_COUNT,
+            instructions: 64,
+            max_depth: 2,
+            max_iterations: 4,
+            memory: 0x100..0x200,
+        }
+    }
+
+    /// Set the number of registers of the CPU running the programs.
+    pub fn registers(mut self, registers_count: u8) -> Self {
+        self.registers_count = registers_count;
+        self
+    }
+
+    /// Set the number of instructions before the final `HLT`.
+    pub fn instructions(mut self, instructions: usize) -> Self {
+        self.instructions = instructions;
+        self
+    }
+
+    /// Set the maximum nesting of the loops and the conditional blocks, `0` for straight-line
+    /// programs.
+    pub fn max_depth(mut self, depth: u32) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Set the maximum number of iterations of a loop, at least `1`.
+    pub fn max_iterations(mut self, iterations: u32) -> Self {
+        self.max_iterations = iterations.max(1);
+        self
+    }
+
+    /// Set the addresses read and written by the memory instructions, which access aligned words.
+    /// A window smaller than a word disables the memory instructions.
+    pub fn memory(mut self, window: Range<u32>) -> Self {
+        self.memory = window;
+        self
+    }
+
+    /// Get the maximum number of steps of the programs, including the final `HLT`.
+    pub fn max_steps(&self) -> u128 {
+        // an instruction runs at most once per iteration of each enclosing loop
+        let iterations = u128::from(self.max_iterations).saturating_pow(self.max_depth);
+        (self.instructions as u128 + 1).saturating_mul(iterations)
+    }
+
+    /// Generate the bytecode of a program, the same for the same seed.
+    pub fn generate(&self, seed: u64) -> Vec<u8> {
+        let mut generation = Generation {
+            generator: self,
+            rng: Rng::new(RandomSource::Seeded(seed)),
+            builder: ProgramBuilder::with_registers(self.registers_count.max(1)),
+            budget: self.instructions,
+            labels: 0,
+        };
+        let writable: Vec<u8> = (0..self.registers_count.max(1)).collect();
+        generation.block(0, &writable);
+        generation.builder.hlt();
+        generation
+            .builder
+            .build()
+            .expect("the generated instructions are valid")
+    }
+
+    /// Generate the instructions of a program, those of the bytecode of `Generator::generate`.
+    pub fn generate_instructions(&self, seed: u64) -> Vec<Instruction<i32, u32>> {
+        decode(&self.generate(seed), self.registers_count.max(1))
+    }
+
+    /// Get a proptest strategy of the instructions of the programs, see `generate_instructions`.
+    ///
+    /// A failing program shrinks to the programs of the same seed with fewer instructions, which
+    /// still pass the verifier and halt within `max_steps`.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::gen::Generator;
+    /// use forge_vm::testing::encode;
+    /// use forge_vm::VM;
+    /// use proptest::prelude::*;
+    ///
+    /// let generator = Generator::new().instructions(32);
+    /// proptest!(|(instructions in generator.strategy())| {
+    ///     let mut vm = VM::<i32>::new(1024, 1024);
+    ///     prop_assert!(vm.run_with_limit(&encode(&instructions), generator.max_steps()).is_ok());
+    /// });
+    /// ```
+    #[cfg(feature = "proptest")]
+    pub fn strategy(&self) -> impl Strategy<Value = Vec<Instruction<i32, u32>>> {
+        let generator = self.clone();
+        (any::<u64>().no_shrink(), 0..=self.instructions).prop_map(move |(seed, instructions)| {
+            generator
+                .clone()
+                .instructions(instructions)
+                .generate_instructions(seed)
+        })
+    }
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decode the instructions of a generated program, in order.
+fn decode(bytecode: &[u8], registers_count: u8) -> Vec<Instruction<i32, u32>> {
+    let program = Program::new(bytecode);
+    let decoder = Decoder::<i32>::with_registers(registers_count);
+    let mut instructions = Vec::new();
+    let mut pc = 0;
+    while pc < bytecode.len() {
+        let instruction = decoder
+            .decode_next_instruction(&program, pc)
+            .expect("the generated programs decode");
+        pc += instruction.size();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// The state of the generation of a program.
+struct Generation<'a> {
+    generator: &'a Generator,
+    rng: Rng,
+    builder: ProgramBuilder,
+    /// The number of instructions left to generate.
+    budget: usize,
+    /// The number of labels defined, to name the next one.
+    labels: usize,
+}
+
+impl Generation<'_> {
+    /// Get a random number below `bound`, which is not zero.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.rng.next_u64() % bound as u64) as usize
+    }
+
+    /// Get a random register among `registers`.
+    fn pick(&mut self, registers: &[u8]) -> u8 {
+        registers[self.below(registers.len())]
+    }
+
+    /// Get a random register, read by an instruction.
+    fn register(&mut self) -> u8 {
+        self.below(self.generator.registers_count.max(1) as usize) as u8
+    }
+
+    /// Define a new label at the next instruction.
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("L{}", self.labels)
+    }
+
+    /// Generate instructions until the budget is spent, or randomly for a nested block.
+    /// The instructions write only the `writable` registers.
+    fn block(&mut self, depth: u32, writable: &[u8]) {
+        while self.budget > 0 {
+            if depth > 0 && self.below(8) == 0 {
+                return;
+            }
+            let nested = depth < self.generator.max_depth && self.budget >= 4;
+            match self.below(10) {
+                0 if nested && writable.len() > 1 => self.counted_loop(depth, writable),
+                1 if nested => self.conditional(depth, writable),
+                _ => self.instruction(writable),
+            }
+        }
+    }
+
+    /// Generate a loop running its body a random number of times, counted in a register.
+    fn counted_loop(&mut self, depth: u32, writable: &[u8]) {
+        let counter = self.pick(writable);
+        let iterations = 1 + self.below(self.generator.max_iterations as usize) as i32;
+        let start = self.label();
+        self.builder.mov(Reg(counter), iterations).label(&start);
+        self.budget -= 3;
+        let body: Vec<u8> = writable
+            .iter()
+            .copied()
+            .filter(|register| *register != counter)
+            .collect();
+        self.nested(depth, &body);
+        self.builder
+            .instruction(Instruction::DEC { reg: counter })
+            .jmpnz(start.as_str());
+    }
+
+    /// Generate a block skipped by a random comparison.
+    fn conditional(&mut self, depth: u32, writable: &[u8]) {
+        let (reg1, reg2) = (self.register(), self.register());
+        let end = self.label();
+        self.builder.instruction(Instruction::CMP { reg1, reg2 });
+        match self.below(8) {
+            0 => self.builder.jmpz(end.as_str()),
+            1 => self.builder.jmpnz(end.as_str()),
+            2 => self.builder.jmpn(end.as_str()),
+            3 => self.builder.jmpp(end.as_str()),
+            4 => self.builder.jmplt(end.as_str()),
+            5 => self.builder.jmple(end.as_str()),
+            6 => self.builder.jmpgt(end.as_str()),
+            _ => self.builder.jmpge(end.as_str()),
+        };
+        self.budget -= 2;
+        self.nested(depth, writable);
+        self.builder.label(&end);
+    }
+
+    /// Generate the body of a loop or a conditional block, with a part of the budget.
+    fn nested(&mut self, depth: u32, writable: &[u8]) {
+        let body = 1 + self.below(self.budget / 2 + 1);
+        let rest = self.budget - body;
+        self.budget = body;
+        self.block(depth + 1, writable);
+        self.budget += rest;
+    }
+
+    /// Generate a single instruction, neither a jump nor a division.
+    fn instruction(&mut self, writable: &[u8]) {
+        let dest = self.pick(writable);
+        let (reg1, reg2) = (self.register(), self.register());
+        let base = self.generator.memory.start.next_multiple_of(4);
+        let words = (self.generator.memory.end.saturating_sub(base) / 4) as usize;
+        let instruction = match self.below(22) {
+            0 => Instruction::MOVR { dest, src: reg1 },
+            1 => Instruction::ADD { dest, reg1, reg2 },
+            2 => Instruction::SUB { dest, reg1, reg2 },
+            3 => Instruction::MULT { dest, reg1, reg2 },
+            4 => Instruction::AND { dest, reg1, reg2 },
+            5 => Instruction::OR { dest, reg1, reg2 },
+            6 => Instruction::XOR { dest, reg1, reg2 },
+            7 => Instruction::NOT { dest, reg: reg1 },
+            8 => Instruction::INC { reg: dest },
+            9 => Instruction::DEC { reg: dest },
+            10 => Instruction::SHL { dest, reg1, reg2 },
+            11 => Instruction::SHR { dest, reg1, reg2 },
+            12 => Instruction::SAR { dest, reg1, reg2 },
+            13 => Instruction::ROL { dest, reg1, reg2 },
+            14 => Instruction::ROR { dest, reg1, reg2 },
+            15 => Instruction::MIN { dest, reg1, reg2 },
+            16 => Instruction::MAX { dest, reg1, reg2 },
+            17 => Instruction::MULH { dest, reg1, reg2 },
+            18 => Instruction::CMP { reg1, reg2 },
+            19 if words > 0 => Instruction::LD {
+                dest,
+                address: base + 4 * self.below(words) as u32,
+            },
+            20 if words > 0 => Instruction::ST {
+                src: reg1,
+                address: base + 4 * self.below(words) as u32,
+            },
+            // the small values make the comparisons and the shifts interesting
+            _ => Instruction::MOV {
+                dest,
+                value: match self.below(2) {
+                    0 => self.below(33) as i32 - 16,
+                    _ => self.rng.next_u64() as i32,
+                },
+            },
+        };
+        self.builder.instruction(instruction);
+        self.budget -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VM;
+
+    #[test]
+    fn test_generate() {
+        let generator = Generator::new().instructions(40);
+        assert_eq!(generator.generate(7), generator.generate(7));
+        assert_ne!(generator.generate(7), generator.generate(8));
+        for seed in 0..200 {
+            let bytecode = generator.generate(seed);
+            assert_eq!(Program::new(&bytecode).verify::<i32>(), vec![]);
+            let instructions = generator.generate_instructions(seed);
+            assert_eq!(instructions, decode(&bytecode, REGISTERS_COUNT));
+            assert_eq!(instructions.len(), 41);
+            assert_eq!(instructions.last(), Some(&Instruction::HLT));
+
+            let mut vm = VM::<i32>::new(1024, 1024);
+            let termination = vm.run_with_limit(&bytecode, generator.max_steps());
+            assert!(termination.is_ok(), "seed {}: {:?}", seed, termination);
+        }
+    }
+
+    #[test]
+    fn test_generate_loops() {
+        // the loops of a single level run their body up to 3 times
+        let generator = Generator::new()
+            .registers(2)
+            .max_depth(1)
+            .max_iterations(3)
+            .memory(0..0);
+        assert_eq!(generator.max_steps(), 65 * 3);
+        let mut repeated = 0;
+        for seed in 0..100 {
+            let bytecode = generator.generate(seed);
+            let instructions = decode(&bytecode, 2);
+            assert!(!instructions
+                .iter()
+                .any(|instruction| matches!(instruction, Instruction::LD { .. })));
+            let mut vm = VM::<i32>::new(16, 1024);
+            let steps = vm
+                .run_with_limit(&bytecode, generator.max_steps())
+                .unwrap()
+                .steps;
+            // the forward jumps skip instructions, the loops repeat them
+            if steps > instructions.len() as u128 {
+                repeated += 1;
+            }
+        }
+        assert!(repeated > 0);
+
+        // a single register leaves no register for the counter of a loop
+        let generator = Generator::new().registers(1);
+        for seed in 0..20 {
+            let bytecode = generator.generate(seed);
+            assert_eq!(
+                Program::new(&bytecode).verify_with_registers::<i32>(1),
+                vec![]
+            );
+            let mut vm = VM::<i32>::new(1024, 1024);
+            assert!(vm.run_with_limit(&bytecode, generator.max_steps()).is_ok());
+        }
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest! {
+        #[test]
+        fn test_strategy(instructions in Generator::new().registers(4).instructions(24).strategy()) {
+            // the counts shrink down to a program made of its `HLT`
+            prop_assert!((1..=25).contains(&instructions.len()));
+            prop_assert_eq!(instructions.last(), Some(&Instruction::HLT));
+            let bytecode = crate::testing::encode(&instructions);
+            prop_assert_eq!(Program::new(&bytecode).verify_with_registers::<i32>(4), vec![]);
+            let mut vm = crate::VmBuilder::new().registers(4).build::<i32>();
+            prop_assert!(vm.run_with_limit(&bytecode, Generator::new().instructions(24).max_steps()).is_ok());
+        }
+    }
+}
@@ -1,5 +1,64 @@
-mod vm;
+#![allow(clippy::upper_case_acronyms)]
 
-pub use vm::error::VmError;
+pub mod asm;
+pub mod bench;
+pub mod dap;
+pub mod ffi;
+pub mod gen;
+pub mod testing;
+pub mod vm;
+
+/// # Example:
+/// ```
+/// use forge_vm::{forge_asm, VM};
+/// const PROGRAM: &[u8] = forge_asm!("
+///     MOV R0 5
+///     INC R0
+///     HLT
+/// ");
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// assert_eq!(vm.run(PROGRAM).map(|termination| termination.steps), Ok(3));
+/// assert_eq!(vm.snapshot().cpu.registers[0], 6);
+/// ```
+///
+/// Unknown mnemonics and registers out of range do not compile:
+/// ```compile_fail
+/// const PROGRAM: &[u8] = forge_vm::forge_asm!("INC R4");
+/// ```
+/// ```compile_fail
+/// const PROGRAM: &[u8] = forge_vm::forge_asm!("JUMP 0");
+/// ```
+pub use forge_vm_macros::forge_asm;
+pub use vm::builder::VmBuilder;
+pub use vm::error::{ExecutionError, VmError};
 pub use vm::instructions::Instruction;
+pub use vm::termination::Termination;
+pub use vm::word::Word;
 pub use vm::VM;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asm::Assembler;
+
+    #[test]
+    fn test_forge_asm_macro() {
+        const PROGRAM: &[u8] = forge_asm!(
+            "
+                MOV R0 3
+            loop:
+                DEC R0
+                JMPNZ loop
+                HLT
+            "
+        );
+        let source = "MOV R0 3\nloop: DEC R0\nJMPNZ loop\nHLT";
+        assert_eq!(Ok(PROGRAM.to_vec()), Assembler::new().assemble(source));
+
+        let program = forge_asm!(registers = 8, "MOVR R7 R6\nHLT");
+        assert_eq!(
+            Ok(program.to_vec()),
+            Assembler::with_registers(8).assemble("MOVR R7 R6\nHLT")
+        );
+    }
+}
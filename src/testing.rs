@@ -0,0 +1,167 @@
+//! Property-based testing of the interpreter with random programs.
+//!
+//! A `ProgramStrategy` generates random sequences of valid instructions ending with `HLT`, whose
+//! jump targets are the starts of their instructions, from a seed. `ProgramStrategy::check`
+//! runs a property against many of them and reports the seed of the first failing program, to
+//! test extensions of the VM, e.g. an optimization pass, against the interpreter:
+//!
+//! ```
+//! use forge_vm::testing::{encode, ProgramStrategy};
+//! use forge_vm::VM;
+//! let result = ProgramStrategy::new().max_instructions(16).check(100, |instructions| {
+//!     let program = encode(instructions);
+//!     let mut first = VM::<i32>::new(64, 1024);
+//!     let mut second = VM::<i32>::new(64, 1024);
+//!     let results = (first.run_with_limit(&program, 500), second.run_with_limit(&program, 500));
+//!     if results.0 == results.1 && first.snapshot() == second.snapshot() {
+//!         Ok(())
+//!     } else {
+//!         Err("the runs differ".to_string())
+//!     }
+//! });
+//! assert_eq!(result, Ok(()));
+//! ```
+
+use crate::vm::encoder::Encoder;
+use crate::vm::fuzz::{arbitrary_instruction, bound_jump_targets};
+use crate::vm::hardware_config::REGISTERS_COUNT;
+use crate::vm::random::{RandomSource, Rng};
+use crate::Instruction;
+
+/// The generator of the random programs of the property tests.
+#[derive(Debug, Clone)]
+pub struct ProgramStrategy {
+    /// The number of registers of the CPU, the bound of the register operands.
+    registers_count: u8,
+    /// The maximum number of instructions before the final `HLT`.
+    max_instructions: usize,
+}
+
+/// A program falsifying a property, see `ProgramStrategy::check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// The seed generating the program.
+    pub seed: u64,
+    /// The instructions of the program.
+    pub instructions: Vec<Instruction<i32, u32>>,
+    /// The message returned by the property.
+    pub message: String,
+}
+
+impl ProgramStrategy {
+    /// Create a strategy for the default CPU, with up to `32` instructions.
+    pub fn new() -> Self {
+        Self {
+            registers_count: REGISTERS_COUNT,
+            max_instructions: 32,
+        }
+    }
+
+    /// Set the number of registers of the CPU running the programs.
+    pub fn registers(mut self, registers_count: u8) -> Self {
+        self.registers_count = registers_count;
+        self
+    }
+
+    /// Set the maximum number of instructions before the final `HLT`.
+    pub fn max_instructions(mut self, max_instructions: usize) -> Self {
+        self.max_instructions = max_instructions;
+        self
+    }
+
+    /// Generate the instructions of a program, the same for the same seed.
+    pub fn generate(&self, seed: u64) -> Vec<Instruction<i32, u32>> {
+        let mut random = Rng::new(RandomSource::Seeded(seed));
+        let count = random.next_u64() as usize % (self.max_instructions + 1);
+        let mut instructions = Vec::with_capacity(count + 1);
+        while instructions.len() < count {
+            // more bytes than the longest instruction
+            let bytes: Vec<u8> = (0..16).map(|_| random.next_u64() as u8).collect();
+            instructions.extend(arbitrary_instruction(&mut &bytes[..], self.registers_count));
+        }
+        instructions.push(Instruction::HLT);
+        bound_jump_targets(&mut instructions);
+        instructions
+    }
+
+    /// Check a property on the programs of the seeds `0..cases`.
+    ///
+    /// # Returns
+    /// The first program for which the property returns an error, if any.
+    pub fn check<F>(&self, cases: u64, mut property: F) -> Result<(), Failure>
+    where
+        F: FnMut(&[Instruction<i32, u32>]) -> Result<(), String>,
+    {
+        for seed in 0..cases {
+            let instructions = self.generate(seed);
+            if let Err(message) = property(&instructions) {
+                return Err(Failure {
+                    seed,
+                    instructions,
+                    message,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ProgramStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Encode the instructions of a program into its bytecode.
+pub fn encode(instructions: &[Instruction<i32, u32>]) -> Vec<u8> {
+    Encoder::new().encode_program(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::decoder::Decoder;
+    use crate::vm::program::Program;
+
+    #[test]
+    fn test_generate() {
+        let strategy = ProgramStrategy::new().registers(2).max_instructions(8);
+        assert_eq!(strategy.generate(7), strategy.generate(7));
+        for seed in 0..100 {
+            let instructions = strategy.generate(seed);
+            assert!(instructions.len() <= 9);
+            assert_eq!(instructions.last(), Some(&Instruction::HLT));
+
+            // the programs decode back on a CPU with 2 registers
+            let bytecode = encode(&instructions);
+            let program = Program::new(&bytecode);
+            let decoder = Decoder::<i32>::with_registers(2);
+            let mut pc = 0;
+            for instruction in &instructions {
+                assert_eq!(
+                    decoder.decode_next_instruction(&program, pc),
+                    Ok(*instruction)
+                );
+                pc += instruction.size();
+            }
+            for target in instructions.iter().filter_map(Instruction::jump_target) {
+                assert!((*target as usize) < bytecode.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let strategy = ProgramStrategy::new();
+        assert_eq!(strategy.check(50, |_| Ok(())), Ok(()));
+
+        let failure = strategy
+            .check(50, |instructions| match instructions.len() {
+                1 => Ok(()),
+                _ => Err("not empty".to_string()),
+            })
+            .unwrap_err();
+        assert_eq!(failure.instructions, strategy.generate(failure.seed));
+        assert_eq!(failure.message, "not empty");
+    }
+}
@@ -0,0 +1,447 @@
+use super::hardware_config::{MEMORY_SIZE, REGISTERS_COUNT, STACK_CAPACITY, VECTOR_TABLE};
+use super::pod::Endianness;
+use super::word::Word;
+use super::{
+    cost, cpu, decode_cache, fixed, gas, interrupt, layout, machine, memory, port, program,
+    protection, random, replay, stack, syscall, thread, VM,
+};
+
+/// Builder configuring the hardware parameters of a VM at runtime.
+/// Every parameter not set explicitly takes its default value from `hardware_config`.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::builder::VmBuilder;
+/// let mut vm = VmBuilder::new()
+///     .registers(8)
+///     .stack_capacity(256)
+///     .memory_size(4096)
+///     .strict_alignment(false)
+///     .build::<i32>();
+/// let program = vec![0x0e, 0x07, 0xff]; // INC 7, HLT
+/// assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(2));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmBuilder {
+    registers: u8,
+    stack_capacity: usize,
+    memory_size: usize,
+    strict_alignment: bool,
+    endianness: Endianness,
+    vector_table: usize,
+    memory_stack: bool,
+    program_base: Option<usize>,
+    layout: Option<layout::MemoryLayout>,
+    regions: Vec<protection::Region>,
+    cost_model: cost::CostModel,
+    gas_schedule: gas::GasSchedule,
+    random: Option<random::RandomSource>,
+    checked_arithmetic: bool,
+    fixed_rounding: fixed::Rounding,
+    superinstructions: bool,
+    #[cfg(feature = "jit")]
+    jit: Option<u32>,
+}
+
+impl VmBuilder {
+    /// Create a builder with the default hardware parameters.
+    pub fn new() -> Self {
+        Self {
+            registers: REGISTERS_COUNT,
+            stack_capacity: STACK_CAPACITY,
+            memory_size: MEMORY_SIZE,
+            strict_alignment: true,
+            endianness: Endianness::Little,
+            vector_table: VECTOR_TABLE,
+            memory_stack: false,
+            program_base: None,
+            layout: None,
+            regions: Vec::new(),
+            cost_model: cost::CostModel::new(),
+            gas_schedule: gas::GasSchedule::default(),
+            random: None,
+            checked_arithmetic: false,
+            fixed_rounding: fixed::Rounding::TowardZero,
+            superinstructions: true,
+            #[cfg(feature = "jit")]
+            jit: Some(super::jit::JIT_THRESHOLD),
+        }
+    }
+
+    /// Set the number of registers of the CPU.
+    /// The decoder rejects the register operands greater than or equal to this count.
+    pub fn registers(mut self, count: u8) -> Self {
+        self.registers = count;
+        self
+    }
+
+    /// Set the maximum number of values the stack can hold.
+    pub fn stack_capacity(mut self, capacity: usize) -> Self {
+        self.stack_capacity = capacity;
+        self
+    }
+
+    /// Set the size of the memory in bytes.
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory_size = size;
+        self
+    }
+
+    /// Enable or disable the alignment check of the memory accesses.
+    /// When enabled, the default, unaligned accesses fail with `VmError::MemoryNotAligned`.
+    pub fn strict_alignment(mut self, strict: bool) -> Self {
+        self.strict_alignment = strict;
+        self
+    }
+
+    /// Set the order of the bytes of the guest, little-endian by default: the immediate values
+    /// and addresses of the bytecode, assembled with `Assembler::endianness`, and the values
+    /// read from and written to the memory, including the vector table and the page tables.
+    /// The devices receive the bytes of the accesses in this order.
+    pub fn endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Set the address of the interrupt vector table in memory.
+    pub fn vector_table(mut self, address: usize) -> Self {
+        self.vector_table = address;
+        self
+    }
+
+    /// Place the stack in the memory, addressed by the stack pointer (SP) of the CPU,
+    /// instead of a separate stack invisible to the guest.
+    /// The stack takes the `stack_capacity` words at the top of the memory and grows down.
+    pub fn memory_stack(mut self, enabled: bool) -> Self {
+        self.memory_stack = enabled;
+        self
+    }
+
+    /// Enable the Von Neumann mode: the program is copied into the memory at `base` before each
+    /// run and the instructions are fetched from the memory, starting at `base`.
+    /// The program can then modify its own code and embed data alongside it. The addresses of
+    /// the program must account for the base, see `Assembler::origin`.
+    /// The instructions are fetched at physical addresses, the MMU only translates the data accesses.
+    pub fn von_neumann(mut self, base: usize) -> Self {
+        self.program_base = Some(base);
+        self
+    }
+
+    /// Set the layout of the memory, see `MemoryLayout`.
+    /// The memory grows to the size of the layout if needed. The VM runs in the Von Neumann mode
+    /// with the program at the start of the code region, and the stack lives in the memory, in
+    /// the stack region, instead of the `stack_capacity` words at the top of the memory.
+    pub fn layout(mut self, layout: layout::MemoryLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Add a region descriptor to the memory, protecting it, see `VM::add_region`.
+    /// The regions of a layout are given by `MemoryLayout::protection`.
+    pub fn region(mut self, region: protection::Region) -> Self {
+        self.regions.push(region);
+        self
+    }
+
+    /// Set the cost in cycles of the instructions, one cycle per instruction by default.
+    /// The consumed cycles are returned by `VM::cycles` and bounded by `VM::run_with_cycle_budget`.
+    pub fn cost_model(mut self, model: cost::CostModel) -> Self {
+        self.cost_model = model;
+        self
+    }
+
+    /// Set the gas charged for the instructions, the memory accesses and the stack operations,
+    /// see `VM::run_with_gas`.
+    pub fn gas_schedule(mut self, schedule: gas::GasSchedule) -> Self {
+        self.gas_schedule = schedule;
+        self
+    }
+
+    /// Set the source of the random numbers read by the guest with `SYSCALL Rng::SYSCALL`,
+    /// a seed for reproducible runs or the entropy of the OS.
+    /// Without a source, the syscall is not registered.
+    pub fn random(mut self, source: random::RandomSource) -> Self {
+        self.random = Some(source);
+        self
+    }
+
+    /// Enable or disable the checked arithmetic, see `CPU::set_checked_arithmetic`.
+    /// When enabled, a signed overflow of `ADD`, `SUB`, `MULT`, `DIV`, `FXMUL`, `FXDIV`, `INC` or
+    /// `DEC` raises the `DivisionByZero` arithmetic trap, or stops the execution with
+    /// `VmError::ArithmeticOverflow` without handler, instead of silently wrapping.
+    pub fn checked_arithmetic(mut self, checked: bool) -> Self {
+        self.checked_arithmetic = checked;
+        self
+    }
+
+    /// Set the rounding of the fixed-point instructions `FXMUL` and `FXDIV`, see
+    /// `CPU::set_fixed_rounding`.
+    pub fn fixed_rounding(mut self, rounding: fixed::Rounding) -> Self {
+        self.fixed_rounding = rounding;
+        self
+    }
+
+    /// Build the syscall table, with the syscall of the random number generator if configured.
+    fn build_syscalls<T: Word>(&self) -> syscall::SyscallTable<T> {
+        let mut syscalls = syscall::SyscallTable::new();
+        if let Some(source) = self.random {
+            random::Rng::new(source).register(&mut syscalls);
+        }
+        syscalls
+    }
+
+    /// Get the size of the memory, large enough for the layout.
+    fn total_memory_size(&self) -> usize {
+        self.layout.as_ref().map_or(self.memory_size, |layout| {
+            self.memory_size.max(layout.size())
+        })
+    }
+
+    /// Get the address of the program in the memory in the Von Neumann mode.
+    fn program_base(&self) -> Option<usize> {
+        self.layout
+            .as_ref()
+            .map(|layout| layout.code.start)
+            .or(self.program_base)
+    }
+
+    /// Get the region of the stack of the core `index` of `cores`, if the stack lives in the memory.
+    /// The stack region of the layout, or the top of the memory, is split evenly between the cores.
+    fn stack_region<T: Word>(&self, index: usize, cores: usize) -> Option<stack::MemoryStack> {
+        if let Some(layout) = &self.layout {
+            let size = layout.stack.len() / cores.max(1);
+            let top = layout.stack.end - index * size;
+            return Some(stack::MemoryStack {
+                bottom: top - size,
+                top,
+            });
+        }
+        if !self.memory_stack {
+            return None;
+        }
+        let size = self.stack_capacity.saturating_mul(T::SIZE);
+        Some(stack::MemoryStack::new(
+            self.memory_size.saturating_sub(index.saturating_mul(size)),
+            self.stack_capacity,
+            T::SIZE,
+        ))
+    }
+
+    /// Enable or disable the superinstructions, enabled by default: when disabled, the `run`
+    /// functions execute the fused pairs of instructions one at a time, e.g. to measure the
+    /// benefit of the fusion.
+    pub fn superinstructions(mut self, enabled: bool) -> Self {
+        self.superinstructions = enabled;
+        self
+    }
+
+    /// Enable or disable the just-in-time compiler, enabled by default with the `jit` feature:
+    /// the `run` functions compile the hot blocks of register instructions into native code,
+    /// see the `jit` module. It is only available for the signed words.
+    #[cfg(feature = "jit")]
+    pub fn jit(mut self, enabled: bool) -> Self {
+        self.jit = enabled.then_some(self.jit.unwrap_or(super::jit::JIT_THRESHOLD));
+        self
+    }
+
+    /// Set the number of visits of an address before the JIT compiles the block starting there,
+    /// `16` by default, and enable the JIT.
+    #[cfg(feature = "jit")]
+    pub fn jit_threshold(mut self, visits: u32) -> Self {
+        self.jit = Some(visits.max(1));
+        self
+    }
+
+    /// Build the CPU of the core `index` of `cores` with the configured registers and its stack.
+    fn build_cpu<T: Word>(&self, index: usize, cores: usize) -> cpu::CPU<T> {
+        let mut cpu = cpu::CPU::<T>::with_registers(self.registers);
+        cpu.set_memory_stack(self.stack_region::<T>(index, cores));
+        cpu.set_checked_arithmetic(self.checked_arithmetic);
+        cpu.set_fixed_rounding(self.fixed_rounding);
+        cpu
+    }
+
+    /// Build a VM with the configured hardware parameters.
+    ///
+    /// # Generics:
+    /// - `T`: The data word of the architecture of the VM.
+    pub fn build<T: Word>(self) -> VM<T> {
+        log::debug!("Creating new VM with {:?}...", self);
+        let mut memory = memory::Memory::new(self.total_memory_size());
+        memory.set_strict_alignment(self.strict_alignment);
+        memory.set_endianness(self.endianness);
+        self.regions
+            .iter()
+            .for_each(|region| memory.add_region(region.clone()));
+        VM {
+            stack: stack::Stack::<T>::new(self.stack_capacity),
+            memory,
+            cpu: self.build_cpu(0, 1),
+            steps: 0,
+            step_limit: u128::MAX,
+            cycles: 0,
+            cycle_limit: u128::MAX,
+            interrupts_delivered: 0,
+            cost_model: self.cost_model.clone(),
+            gas_used: 0,
+            gas_limit: u64::MAX,
+            gas_schedule: self.gas_schedule.clone(),
+            syscalls: self.build_syscalls(),
+            ports: port::PortBus::new(),
+            interrupts: interrupt::InterruptController::new(self.vector_table),
+            threads: thread::Scheduler::new(),
+            program_base: self.program_base(),
+            layout: self.layout,
+            images: Vec::new(),
+            debug_info: None,
+            program: program::Program::new(&[]),
+            tracer: None,
+            hook: None,
+            paused_at: None,
+            counted: true,
+            exit_code: None,
+            profile: None,
+            coverage: None,
+            controller: None,
+            replay: replay::Replay::Off,
+            history: None,
+            decoded: decode_cache::DecodeCache::new(),
+            superinstructions: self.superinstructions,
+            #[cfg(feature = "jit")]
+            jit: self.jit.and_then(super::jit::Jit::new),
+        }
+    }
+
+    /// Build a multi-core machine with the configured hardware parameters.
+    /// Each core has the configured registers and its own stack, the memory is shared.
+    /// With the stack in memory, the stack of the core `n` is the `n`-th region of
+    /// `stack_capacity` words from the top of the memory, or the `n`-th part of the stack
+    /// region of the layout.
+    ///
+    /// # Generics:
+    /// - `T`: The data word of the architecture of the cores.
+    ///
+    /// # Parameters:
+    /// - `cores`: The number of cores.
+    pub fn build_machine<T: Word>(self, cores: usize) -> machine::Machine<T> {
+        log::debug!(
+            "Creating new machine with {} cores and {:?}...",
+            cores,
+            self
+        );
+        let mut memory = memory::Memory::new(self.total_memory_size());
+        memory.set_strict_alignment(self.strict_alignment);
+        memory.set_endianness(self.endianness);
+        self.regions
+            .iter()
+            .for_each(|region| memory.add_region(region.clone()));
+        let cores = (0..cores)
+            .map(|index| {
+                (
+                    self.build_cpu(index, cores),
+                    stack::Stack::<T>::new(self.stack_capacity),
+                )
+            })
+            .collect();
+        let mut machine = machine::Machine::new(cores, memory, self.program_base());
+        if let Some(source) = self.random {
+            machine.register_random(source);
+        }
+        machine
+    }
+}
+
+impl Default for VmBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::error::VmError;
+
+    #[test]
+    fn test_builder_defaults() {
+        let vm = VmBuilder::new().build::<i32>();
+        assert_eq!(vm.cpu.registers_count(), REGISTERS_COUNT);
+        assert_eq!(vm.stack.capacity(), STACK_CAPACITY);
+        assert_eq!(vm.memory.capacity(), MEMORY_SIZE);
+    }
+
+    #[test]
+    fn test_builder_registers() {
+        let mut vm = VmBuilder::new().registers(2).build::<i32>();
+        let program = vec![0x0e, 0x01, 0x0e, 0x02, 0xff]; // INC 1, INC 2, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::InvalidRegister { register: 2 })
+        );
+
+        let mut vm = VmBuilder::new().registers(3).build::<i32>();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.cpu.get_register(2), Ok(1));
+    }
+
+    #[test]
+    fn test_builder_strict_alignment() {
+        let program = vec![0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0xff]; // LD 0 0x1, HLT
+        let mut vm = VmBuilder::new().memory_size(16).build::<i32>();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::MemoryNotAligned {
+                address: 1,
+                size: 4
+            })
+        );
+
+        let mut vm = VmBuilder::new()
+            .memory_size(16)
+            .strict_alignment(false)
+            .build::<i32>();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(2));
+    }
+
+    #[test]
+    fn test_builder_memory_stack() {
+        let program = vec![0x0e, 0x00, 0x10, 0x00, 0xff]; // INC 0, PUSHREG 0, HLT
+        let mut vm = VmBuilder::new()
+            .stack_capacity(4)
+            .memory_size(64)
+            .memory_stack(true)
+            .build::<i32>();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.cpu.sp(), Some(60));
+        assert_eq!(vm.memory.read::<i32>(60), Ok(1));
+        assert!(vm.stack.is_empty());
+        assert_eq!(vm.counters().stack_high_water, 1);
+
+        let machine = VmBuilder::new()
+            .stack_capacity(4)
+            .memory_size(64)
+            .memory_stack(true)
+            .build_machine::<i32>(2);
+        assert_eq!(machine.cpu(1).unwrap().sp(), Some(48));
+        assert_eq!(machine.cpu(1).unwrap().memory_stack().unwrap().bottom, 32);
+    }
+
+    #[test]
+    fn test_builder_layout() {
+        let layout =
+            layout::MemoryLayout::new(0x0..0x100, 0x100..0x200, 0x200..0x200, 0x200..0x240)
+                .unwrap();
+        let machine = VmBuilder::new()
+            .memory_size(0x100)
+            .layout(layout)
+            .build_machine::<i32>(2);
+        assert_eq!(machine.memory().capacity(), 0x240);
+        assert_eq!(
+            machine.cpu(1).unwrap().memory_stack(),
+            Some(stack::MemoryStack {
+                bottom: 0x200,
+                top: 0x220
+            })
+        );
+    }
+}
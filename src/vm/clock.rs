@@ -0,0 +1,173 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::device::Device;
+use super::error::{Result, VmError};
+
+/// Offset of the real-time register: the nanoseconds elapsed since the Unix epoch.
+pub const CLOCK_REALTIME: usize = 0x00;
+/// Offset of the monotonic register: the nanoseconds elapsed since the reset of the clock.
+pub const CLOCK_MONOTONIC: usize = 0x08;
+
+/// The time base of a `ClockDevice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockMode {
+    /// The time of the host.
+    Real,
+    /// A virtual time advancing by a fixed duration per step of the VM, for deterministic runs.
+    Virtual {
+        /// The real time at the reset of the clock, in nanoseconds since the Unix epoch.
+        epoch: u64,
+        /// The nanoseconds elapsed per step.
+        step: u64,
+    },
+}
+
+/// A clock device giving the current time and a monotonic tick counter.
+/// The clock has 2 read-only registers of 8 bytes, a register can be read with any size up to
+/// 8 bytes, the writes are ignored:
+/// - `CLOCK_REALTIME` (`0x00`): Nanoseconds since the Unix epoch.
+/// - `CLOCK_MONOTONIC` (`0x08`): Nanoseconds since the reset of the clock, never decreasing.
+///
+/// The clock is reset before each run, in virtual time the registers only depend on the number
+/// of steps executed since.
+/// Use `ClockDevice::SIZE` as the length of the mapped range.
+#[derive(Debug, Clone)]
+pub struct ClockDevice {
+    mode: ClockMode,
+    start: Instant,
+    steps: u64,
+}
+
+impl ClockDevice {
+    /// The number of bytes of the registers of the clock.
+    pub const SIZE: usize = 0x10;
+
+    /// Create a clock with a time base.
+    pub fn new(mode: ClockMode) -> Self {
+        Self {
+            mode,
+            start: Instant::now(),
+            steps: 0,
+        }
+    }
+
+    /// Get the nanoseconds elapsed since the reset of the clock.
+    pub fn monotonic(&self) -> u64 {
+        match self.mode {
+            ClockMode::Real => u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX),
+            ClockMode::Virtual { step, .. } => self.steps.saturating_mul(step),
+        }
+    }
+
+    /// Get the nanoseconds elapsed since the Unix epoch.
+    pub fn realtime(&self) -> u64 {
+        match self.mode {
+            ClockMode::Real => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| {
+                    u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+                }),
+            ClockMode::Virtual { epoch, .. } => epoch.saturating_add(self.monotonic()),
+        }
+    }
+
+    /// Check the access targets a single register.
+    fn check_register(offset: usize, size: usize) -> Result<()> {
+        if !offset.is_multiple_of(8) || size > 8 || offset >= Self::SIZE {
+            return Err(VmError::MemoryOutOfBounds {
+                address: offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Device for ClockDevice {
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        Self::check_register(offset, size)?;
+        let value = match offset {
+            CLOCK_REALTIME => self.realtime(),
+            _ => self.monotonic(),
+        };
+        Ok(value.to_le_bytes()[..size].to_vec())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        Self::check_register(offset, data.len())
+    }
+
+    fn tick(&mut self) {
+        self.steps += 1;
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_clock_virtual() {
+        let mut clock = ClockDevice::new(ClockMode::Virtual {
+            epoch: 1_000,
+            step: 10,
+        });
+        assert_eq!(
+            clock.read(CLOCK_MONOTONIC, 8),
+            Ok(0u64.to_le_bytes().to_vec())
+        );
+        clock.tick();
+        clock.tick();
+        assert_eq!(clock.monotonic(), 20);
+        assert_eq!(clock.read(CLOCK_REALTIME, 2), Ok(vec![0xfc, 0x03]));
+        clock.write(CLOCK_REALTIME, &[0]).unwrap();
+        assert_eq!(clock.realtime(), 1_020);
+        clock.reset();
+        assert_eq!(clock.realtime(), 1_000);
+        assert_eq!(
+            clock.read(0x10, 8),
+            Err(VmError::MemoryOutOfBounds {
+                address: 0x10,
+                size: 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_clock_real() {
+        let mut clock = ClockDevice::new(ClockMode::Real);
+        let first = clock.monotonic();
+        assert!(clock.monotonic() >= first);
+        // after 2020-01-01
+        assert!(clock.realtime() > 1_577_836_800_000_000_000);
+        clock.tick();
+        assert!(clock.monotonic() >= first);
+    }
+
+    #[test]
+    fn test_clock_deterministic_runs() {
+        let source = "
+            LD R0 0x1008
+            NOP
+            NOP
+            LD R1 0x1008
+            HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(8192, 1024);
+        let mode = ClockMode::Virtual { epoch: 0, step: 5 };
+        vm.map_device(0x1000..0x1000 + ClockDevice::SIZE, ClockDevice::new(mode))
+            .unwrap();
+        for _ in 0..2 {
+            vm.run(&program).unwrap();
+            let registers = vm.snapshot().cpu.registers;
+            assert_eq!((registers[0], registers[1]), (0, 15));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{Read, Stdin, Stdout, Write};
+
+use super::device::Device;
+use super::error::{Result, VmError};
+
+/// A console device, similar to a UART, to write and read characters.
+/// The device has a single data register at offset `0` of its range, of any size up to 8 bytes:
+/// - A write sends the lowest byte of the value to the output.
+/// - A read returns the next byte of the input, or `-1` at the end of the input.
+///
+/// Use `ConsoleDevice::SIZE` as the length of the mapped range.
+pub struct ConsoleDevice<R, W> {
+    input: R,
+    output: W,
+}
+
+impl ConsoleDevice<Stdin, Stdout> {
+    /// Create a console reading from the standard input and writing to the standard output.
+    pub fn stdio() -> Self {
+        Self::new(std::io::stdin(), std::io::stdout())
+    }
+}
+
+impl<R: Read, W: Write> ConsoleDevice<R, W> {
+    /// The number of bytes of the data register.
+    pub const SIZE: usize = 8;
+
+    /// Create a console with a specific input and output.
+    ///
+    /// # Parameters
+    /// - `input`: The source of the bytes read by the guest.
+    /// - `output`: The destination of the bytes written by the guest.
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    /// Get the output of the console.
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// Get the output of the console, mutably.
+    pub fn output_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Consume the console and get its input and output.
+    pub fn into_inner(self) -> (R, W) {
+        (self.input, self.output)
+    }
+
+    /// Check the access targets the data register.
+    fn check_register(offset: usize, size: usize) -> Result<()> {
+        if offset != 0 || size > Self::SIZE {
+            return Err(VmError::MemoryOutOfBounds {
+                address: offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + 'static, W: Write + 'static> Device for ConsoleDevice<R, W> {
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        Self::check_register(offset, size)?;
+        let mut byte = [0u8];
+        let read = self
+            .input
+            .read(&mut byte)
+            .map_err(|error| VmError::Other(format!("Console input error: {}", error)))?;
+        if read == 0 {
+            return Ok(vec![0xff; size]);
+        }
+        let mut bytes = vec![0; size];
+        bytes[0] = byte[0];
+        Ok(bytes)
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        Self::check_register(offset, data.len())?;
+        self.output
+            .write_all(&data[..1])
+            .and_then(|_| self.output.flush())
+            .map_err(|error| VmError::Other(format!("Console output error: {}", error)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    type TestConsole = ConsoleDevice<Cursor<Vec<u8>>, Vec<u8>>;
+
+    #[test]
+    fn test_console_read_write() {
+        let mut console = ConsoleDevice::new(Cursor::new(b"a".to_vec()), Vec::new());
+        assert_eq!(console.read(0, 4), Ok(vec![b'a', 0, 0, 0]));
+        assert_eq!(console.read(0, 4), Ok(vec![0xff; 4]));
+        console.write(0, &[b'z', 0, 0, 0]).unwrap();
+        assert_eq!(console.output(), b"z");
+        assert_eq!(
+            console.write(4, &[0]),
+            Err(VmError::MemoryOutOfBounds {
+                address: 4,
+                size: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_console_hello_world() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.map_device(
+            0x1000..0x1000 + TestConsole::SIZE,
+            ConsoleDevice::new(Cursor::new(Vec::new()), Vec::new()),
+        )
+        .unwrap();
+        let mut source = String::new();
+        for byte in b"Hello, world!\n" {
+            source.push_str(&format!("MOV R0 {}\nST R0 0x1000\n", byte));
+        }
+        source.push_str("HLT");
+        let program = Assembler::new().assemble(&source).unwrap();
+        vm.run(&program).unwrap();
+
+        let console = vm.device::<TestConsole>(0x1000).unwrap();
+        assert_eq!(console.output(), b"Hello, world!\n");
+    }
+
+    #[test]
+    fn test_console_echo() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.map_device(
+            0x1000..0x1000 + TestConsole::SIZE,
+            ConsoleDevice::new(Cursor::new(b"echo".to_vec()), Vec::new()),
+        )
+        .unwrap();
+        let source = "
+            loop:
+                LD R0 0x1000
+                INC R0
+                JMPZ end
+                DEC R0
+                ST R0 0x1000
+                JMP loop
+            end:
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        vm.run_with_limit(&program, 1000).unwrap();
+
+        let console = vm.device::<TestConsole>(0x1000).unwrap();
+        assert_eq!(console.output(), b"echo");
+    }
+}
@@ -0,0 +1,155 @@
+//! The control of a running VM from another thread, see `VM::set_controller`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// A handle pausing and resuming the VMs it is attached to, shared by its clones.
+///
+/// The VM checks the handle at each instruction boundary: once a pause is requested, the thread
+/// running the VM blocks before the next instruction until the execution is resumed. The VM is
+/// created and run on a worker thread with a clone of the handle, the other clones drive it
+/// from any thread.
+///
+/// # Example:
+/// ```
+/// use std::time::Duration;
+/// use forge_vm::vm::control::VmController;
+/// use forge_vm::VM;
+///
+/// let controller = VmController::new();
+/// let handle = controller.clone();
+/// controller.pause();
+/// let worker = std::thread::spawn(move || {
+///     let mut vm = VM::<i32>::new(1024, 1024);
+///     vm.set_controller(handle);
+///     vm.run(&[0x0e, 0x00, 0xff]) // INC R0, HLT
+/// });
+/// // the VM pauses before its first instruction
+/// assert!(controller.wait_paused(Duration::from_secs(10)));
+/// controller.resume();
+/// assert_eq!(worker.join().unwrap().map(|termination| termination.steps), Ok(2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VmController {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// Set while a pause is requested, checked by the VM at each instruction without locking.
+    pause_requested: AtomicBool,
+    /// Whether a VM is blocked at an instruction boundary.
+    paused: Mutex<bool>,
+    /// Notified when a VM blocks and when the execution is resumed.
+    changed: Condvar,
+}
+
+impl VmController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the VM to pause at the next instruction boundary.
+    pub fn pause(&self) {
+        let _paused = self.lock();
+        self.shared.pause_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Resume the execution of the VM paused or about to pause.
+    pub fn resume(&self) {
+        let _paused = self.lock();
+        self.shared.pause_requested.store(false, Ordering::SeqCst);
+        self.shared.changed.notify_all();
+    }
+
+    /// Check if a pause is requested, the VM may still be executing its current instruction.
+    pub fn is_pause_requested(&self) -> bool {
+        self.shared.pause_requested.load(Ordering::SeqCst)
+    }
+
+    /// Check if the VM is blocked at an instruction boundary.
+    pub fn is_paused(&self) -> bool {
+        *self.lock()
+    }
+
+    /// Wait until the VM is blocked at an instruction boundary after a pause request.
+    ///
+    /// # Returns
+    /// `false` if the VM did not pause within `timeout`, e.g. because it halted before the
+    /// next instruction boundary.
+    pub fn wait_paused(&self, timeout: Duration) -> bool {
+        let (paused, _) = self
+            .shared
+            .changed
+            .wait_timeout_while(self.lock(), timeout, |paused| !*paused)
+            .unwrap_or_else(|error| error.into_inner());
+        *paused
+    }
+
+    /// Block the calling VM while a pause is requested.
+    pub(crate) fn checkpoint(&self) {
+        if !self.is_pause_requested() {
+            return;
+        }
+        let mut paused = self.lock();
+        *paused = true;
+        self.shared.changed.notify_all();
+        log::debug!("Execution paused by the controller.");
+        let mut paused = self
+            .shared
+            .changed
+            .wait_while(paused, |_| self.is_pause_requested())
+            .unwrap_or_else(|error| error.into_inner());
+        *paused = false;
+        log::debug!("Execution resumed by the controller.");
+    }
+
+    fn lock(&self) -> MutexGuard<'_, bool> {
+        self.shared
+            .paused
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_controller_pause_resume() {
+        let program = Assembler::new()
+            .assemble("MOV R0 1000\nloop: DEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        let controller = VmController::new();
+        let handle = controller.clone();
+        controller.pause();
+        assert!(controller.is_pause_requested());
+        let worker = std::thread::spawn(move || {
+            let mut vm = VM::<i32>::new(16, 64);
+            vm.set_controller(handle);
+            vm.run(&program).map(|termination| termination.steps)
+        });
+
+        assert!(controller.wait_paused(Duration::from_secs(10)));
+        assert!(controller.is_paused());
+        controller.resume();
+        assert!(!controller.is_pause_requested());
+        assert_eq!(worker.join().unwrap(), Ok(2002));
+        assert!(!controller.is_paused());
+    }
+
+    #[test]
+    fn test_controller_wait_timeout() {
+        let controller = VmController::new();
+        controller.pause();
+        assert!(!controller.wait_paused(Duration::from_millis(1)));
+        // a VM without pause request is not blocked
+        controller.resume();
+        controller.checkpoint();
+        assert!(!controller.is_paused());
+    }
+}
@@ -0,0 +1,106 @@
+//! The cycle cost of the instructions, see `VmBuilder::cost_model`.
+
+use super::instructions::OpCode;
+
+/// The cost in cycles of each opcode, charged before the instruction is executed.
+/// Every opcode costs one cycle by default, so that the cycles count the steps.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::cost::CostModel;
+/// use forge_vm::vm::instructions::OpCode;
+/// use forge_vm::VmBuilder;
+///
+/// let model = CostModel::new()
+///     .with_cost(OpCode::MULT, 3)
+///     .with_cost(OpCode::DIV, 20);
+/// assert_eq!(model.cost(OpCode::DIV), 20);
+/// assert_eq!(model.cost(OpCode::INC), 1);
+///
+/// let mut vm = VmBuilder::new().cost_model(model).build::<i32>();
+/// vm.run(&[0x0b, 0x00, 0x01, 0x02, 0xff]).unwrap(); // MULT R0 R1 R2, HLT
+/// assert_eq!(vm.cycles(), 4);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostModel {
+    /// The cost of each opcode, by value of the opcode.
+    costs: [u64; 256],
+}
+
+impl CostModel {
+    /// Create a model where every opcode costs one cycle.
+    pub fn new() -> Self {
+        Self::uniform(1)
+    }
+
+    /// Create a model where every opcode costs `cost` cycles.
+    pub fn uniform(cost: u64) -> Self {
+        Self { costs: [cost; 256] }
+    }
+
+    /// Set the cost of an opcode.
+    pub fn with_cost(mut self, opcode: OpCode, cost: u64) -> Self {
+        self.costs[u8::from(opcode) as usize] = cost;
+        self
+    }
+
+    /// Get the cost of an opcode.
+    pub fn cost(&self, opcode: OpCode) -> u64 {
+        self.costs[u8::from(opcode) as usize]
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The resources consumed by a run.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// The number of executed instructions, including `HLT`.
+    pub steps: u128,
+    /// The number of consumed cycles, see `CostModel`.
+    pub cycles: u128,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::vm::builder::VmBuilder;
+    use crate::vm::error::VmError;
+
+    #[test]
+    fn test_cost_model_run() {
+        // 3 iterations of DEC and JMPNZ
+        let program = Assembler::new()
+            .assemble("MOV R0 3\nloop: DEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        let model = CostModel::uniform(2)
+            .with_cost(OpCode::JMPNZ, 5)
+            .with_cost(OpCode::HLT, 0);
+        let mut vm = VmBuilder::new().cost_model(model).build::<i32>();
+        assert_eq!(
+            vm.run_with_cycle_budget(&program, 29),
+            Ok(RunStats {
+                steps: 8,
+                cycles: 2 + 3 * (2 + 5)
+            })
+        );
+        assert_eq!(
+            vm.run_with_cycle_budget(&program, 22)
+                .map_err(|error| error.source),
+            Err(VmError::CycleBudgetExceeded)
+        );
+        // the last JMPNZ exceeded the budget and was not executed
+        assert_eq!(vm.cycles(), 23);
+        assert_eq!(vm.counters().steps, 7);
+
+        // one cycle per instruction by default
+        let mut vm = VmBuilder::new().build::<i32>();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(8));
+        assert_eq!(vm.cycles(), 8);
+    }
+}
@@ -0,0 +1,169 @@
+//! The coverage of the executed instructions, see `VM::set_coverage`.
+
+use std::collections::BTreeMap;
+
+use super::debug_info::DebugInfo;
+
+/// The directions taken by a conditional jump.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BranchCoverage {
+    /// The number of executions jumping to the target.
+    pub taken: u64,
+    /// The number of executions falling through to the next instruction.
+    pub not_taken: u64,
+}
+
+/// The coverage of a program: the number of executions of each instruction, by address, and
+/// the directions taken by the conditional jumps.
+///
+/// The coverage accumulates the runs, so that it can measure a whole test suite of a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    hits: BTreeMap<usize, u64>,
+    branches: BTreeMap<usize, BranchCoverage>,
+}
+
+impl Coverage {
+    /// Record an execution of the instruction at an address.
+    pub(crate) fn record(&mut self, address: usize) {
+        *self.hits.entry(address).or_default() += 1;
+    }
+
+    /// Record the direction taken by the conditional jump at an address.
+    pub(crate) fn record_branch(&mut self, address: usize, taken: bool) {
+        let branch = self.branches.entry(address).or_default();
+        match taken {
+            true => branch.taken += 1,
+            false => branch.not_taken += 1,
+        }
+    }
+
+    /// Remove all the recorded executions.
+    pub fn clear(&mut self) {
+        self.hits.clear();
+        self.branches.clear();
+    }
+
+    /// Get the number of executions of the instruction at an address.
+    pub fn hits(&self, address: usize) -> u64 {
+        self.hits.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Get the addresses of the executed instructions, in increasing order.
+    pub fn executed(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hits.keys().copied()
+    }
+
+    /// Get the directions taken by the conditional jump at an address, `None` if the jump was
+    /// not executed.
+    pub fn branch(&self, address: usize) -> Option<BranchCoverage> {
+        self.branches.get(&address).copied()
+    }
+
+    /// Get the executed conditional jumps with their directions, by increasing address.
+    pub fn branches(&self) -> impl Iterator<Item = (usize, BranchCoverage)> + '_ {
+        self.branches
+            .iter()
+            .map(|(address, branch)| (*address, *branch))
+    }
+
+    /// Export the executed addresses as a bitmap of `size` bits: the bit `address % 8` of the
+    /// byte `address / 8` is set when an instruction starts at the address and was executed.
+    /// The addresses beyond `size` are ignored.
+    pub fn to_bitmap(&self, size: usize) -> Vec<u8> {
+        let mut bitmap = vec![0; size.div_ceil(8)];
+        for address in self.executed().take_while(|address| *address < size) {
+            bitmap[address / 8] |= 1 << (address % 8);
+        }
+        bitmap
+    }
+
+    /// Export the coverage in the lcov tracefile format, with the source lines of the
+    /// instructions from the debug information of the program.
+    ///
+    /// Every instruction of the debug information is reported with its number of executions
+    /// (`DA`), and each executed conditional jump with its two directions (`BRDA`), the jump
+    /// to the target as branch `0` and the fall through as branch `1`.
+    ///
+    /// # Parameters
+    /// - `source`: The path of the source file of the program.
+    /// - `debug_info`: The debug information of the program.
+    pub fn to_lcov(&self, source: &str, debug_info: &DebugInfo) -> String {
+        let mut lines = BTreeMap::<usize, u64>::new();
+        for (address, line) in debug_info.lines() {
+            *lines.entry(line).or_default() += self.hits(address);
+        }
+        let mut report = format!("TN:\nSF:{}\n", source);
+        let mut branches_hit = 0;
+        for (address, branch) in self.branches() {
+            let Some(line) = debug_info.line_at(address) else {
+                continue;
+            };
+            report += &format!("BRDA:{},0,0,{}\n", line, branch.taken);
+            report += &format!("BRDA:{},0,1,{}\n", line, branch.not_taken);
+            branches_hit += (branch.taken > 0) as usize + (branch.not_taken > 0) as usize;
+        }
+        report += &format!("BRF:{}\nBRH:{}\n", self.branches.len() * 2, branches_hit);
+        for (line, hits) in &lines {
+            report += &format!("DA:{},{}\n", line, hits);
+        }
+        let lines_hit = lines.values().filter(|hits| **hits > 0).count();
+        report += &format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), lines_hit);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_coverage_run() {
+        // MOV at 0x0, DEC at 0x6, JMPNZ at 0x8, HLT at 0xd, INC at 0xe
+        let source = "MOV R0 2\nloop: DEC R0\nJMPNZ loop\nHLT\nINC R0";
+        let (program, debug_info) = Assembler::new().assemble_with_debug_info(source).unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        assert!(vm.coverage().is_none());
+        vm.set_coverage(true);
+        assert_eq!(
+            vm.run_program(&program)
+                .map(|termination| termination.steps),
+            Ok(6)
+        );
+
+        let coverage = vm.coverage().unwrap();
+        assert_eq!(
+            coverage.executed().collect::<Vec<_>>(),
+            [0x0, 0x6, 0x8, 0xd]
+        );
+        assert_eq!(coverage.hits(0x6), 2);
+        assert_eq!(coverage.hits(0xe), 0);
+        assert_eq!(
+            coverage.branch(0x8),
+            Some(BranchCoverage {
+                taken: 1,
+                not_taken: 1
+            })
+        );
+        assert_eq!(coverage.branch(0x6), None);
+        assert_eq!(coverage.to_bitmap(16), [0b0100_0001, 0b0010_0001]);
+        assert_eq!(coverage.to_bitmap(4), [0b0000_0001]);
+        assert_eq!(
+            coverage.to_lcov("loop.s", &debug_info),
+            "TN:\nSF:loop.s\nBRDA:3,0,0,1\nBRDA:3,0,1,1\nBRF:2\nBRH:2\n\
+             DA:1,1\nDA:2,2\nDA:3,2\nDA:4,1\nDA:5,0\nLF:5\nLH:4\nend_of_record\n"
+        );
+
+        // the coverage accumulates the runs
+        assert_eq!(
+            vm.run_program(&program)
+                .map(|termination| termination.steps),
+            Ok(6)
+        );
+        assert_eq!(vm.coverage().unwrap().hits(0x6), 4);
+        vm.set_coverage(false);
+        assert!(vm.coverage().is_none());
+    }
+}
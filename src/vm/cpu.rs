@@ -1,41 +1,245 @@
+use super::counters::{Counter, PerfCounters};
 use super::error::{Result as VmResult, VmError};
+use super::fixed::{self, Rounding};
 use super::hardware_config::REGISTERS_COUNT;
-use super::instructions::Instruction;
+use super::instructions::{Instruction, OpCode};
 use super::memory::Memory;
-use super::stack::Stack;
+use super::mmu::Mmu;
+use super::pod::Pod;
+use super::port::PortBus;
+use super::stack::{MemoryStack, Stack};
+use super::syscall::SyscallTable;
+use super::word::Word;
 
 /// The CPU structure used by the VM.
-/// The CPU has a fixed number of registers and status flags.
+/// The CPU has a number of registers fixed at creation, as many `f32` float registers and status flags.
 /// The CPU has a program counter (PC) that points to the current instruction.
 /// The CPU can execute instructions and interact with memory and the stack.
+/// The memory accesses of the instructions are translated by the MMU of the CPU when the paging is enabled.
+/// The stack is either a separate `Stack` or a region of the memory addressed by the stack pointer (SP),
+/// with a frame pointer (FP) for the frames of `ENTER` and `LEAVE`.
 /// The CPU is generic over the data type used for the registers.
 pub struct CPU<T> {
     /// The registers of the CPU.
-    /// ***Note:*** REGISTERS_COUNT is defined in hardware_config.rs
-    registers: [T; REGISTERS_COUNT as usize],
+    /// ***Note:*** the default count REGISTERS_COUNT is defined in hardware_config.rs
+    registers: Vec<T>,
+    /// The float registers of the CPU, as many as the registers.
+    float_registers: Vec<f32>,
     /// The status flags of the CPU.
     status_flags: StatusFlags,
     /// The program counter (PC) of the CPU.
     pc: usize,
+    /// The memory management unit of the CPU, with its TLB.
+    mmu: Mmu,
+    /// The region of the memory holding the stack, `None` when the stack is a separate `Stack`.
+    memory_stack: Option<MemoryStack>,
+    /// The stack pointer (SP) of the CPU, used when the stack lives in the memory.
+    sp: usize,
+    /// The frame pointer (FP) of the CPU, used when the stack lives in the memory.
+    fp: usize,
+    /// The lowest stack pointer since the initialization, for the stack high-water mark.
+    sp_low: usize,
+    /// The number of pushes and pops since the creation of the CPU, for the gas accounting.
+    pushes: u64,
+    pops: u64,
+    /// Raise `VmError::ArithmeticOverflow` on the signed overflows instead of wrapping.
+    checked_arithmetic: bool,
+    /// The rounding of the fixed-point instructions.
+    fixed_rounding: Rounding,
 }
 
-/// Implementation of the CPU for the 32-bit architecture
+/// Destructure the instruction given to a handler, which is dispatched by its opcode.
+macro_rules! operands {
+    ($instruction:ident, $pattern:pat) => {
+        let $pattern = $instruction else {
+            unreachable!("{} dispatched to the wrong handler", $instruction)
+        };
+    };
+}
+
+/// The handler executing the instructions of an opcode, see `CPU::execute_instruction`.
+type Handler<T> =
+    fn(&mut CPU<T>, Instruction<T, <T as Word>::Address>, &mut Execution<'_, T>) -> VmResult<()>;
+
+/// The handler executing a pair of fused instructions, see `CPU::fuse`.
+type FusedHandler<T> = fn(
+    &mut CPU<T>,
+    Instruction<T, <T as Word>::Address>,
+    Instruction<T, <T as Word>::Address>,
+    &mut Execution<'_, T>,
+) -> VmResult<()>;
+
+/// A superinstruction: two consecutive instructions executed by a single handler.
+#[derive(Clone, Copy)]
+pub(crate) struct Fused<T: Word> {
+    first: Instruction<T, T::Address>,
+    second: Instruction<T, T::Address>,
+    handler: FusedHandler<T>,
+}
+
+impl<T: Word> Fused<T> {
+    /// Get the two fused instructions, in the order of the code.
+    pub(crate) fn instructions(&self) -> (Instruction<T, T::Address>, Instruction<T, T::Address>) {
+        (self.first, self.second)
+    }
+}
+
+/// The end of the execution requested by an instruction, see `CPU::execute_instruction`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stop<T> {
+    /// `HLT` ends the running thread, with the value of `R0` as exit code of the program if it
+    /// is the last one.
+    Halt { code: T },
+    /// `EXIT` terminates the program with its immediate exit code.
+    Exit { code: T },
+}
+
+/// The resources of the VM used by the execution of an instruction besides the CPU.
+struct Execution<'a, T> {
+    memory: &'a mut Memory,
+    stack: &'a mut Stack<T>,
+    counters: &'a PerfCounters,
+    syscalls: &'a mut SyscallTable<T>,
+    ports: &'a mut PortBus<T>,
+    /// The address of the instruction following the current one, overwritten by taken jumps.
+    next_pc: usize,
+    /// The end of the execution requested by the instruction, if any.
+    stop: Option<Stop<T>>,
+}
+
+/// Implementation of the CPU, generic over the data word of the architecture
 /// The index of the registers was verified in decoder.rs
-impl CPU<i32> {
+impl<T: Word> CPU<T> {
     pub fn new() -> Self {
+        Self::with_registers(REGISTERS_COUNT)
+    }
+
+    /// Create a CPU with a specific number of registers.
+    ///
+    /// # Parameters
+    /// - `count`: The number of registers.
+    pub fn with_registers(count: u8) -> Self {
         Self {
-            registers: [0; REGISTERS_COUNT as usize],
+            registers: vec![T::zero(); count as usize],
+            float_registers: vec![0.0; count as usize],
             status_flags: StatusFlags::default(),
             pc: 0,
+            mmu: Mmu::new(),
+            memory_stack: None,
+            sp: 0,
+            fp: 0,
+            sp_low: 0,
+            pushes: 0,
+            pops: 0,
+            checked_arithmetic: false,
+            fixed_rounding: Rounding::default(),
         }
     }
 
     /// Initialize the CPU by clearing the registers and status flags.
-    /// The program counter is set to zero.
+    /// The program counter is set to zero, the interrupts and the paging are disabled.
+    /// The stack and frame pointers are set to the top of the stack in memory, if any.
     pub fn init(&mut self) {
-        self.registers = [0; REGISTERS_COUNT as usize];
-        self.status_flags.clear();
+        self.registers.fill(T::zero());
+        self.float_registers.fill(0.0);
+        self.status_flags = StatusFlags::default();
         self.pc = 0;
+        self.mmu = Mmu::new();
+        self.reset_sp();
+    }
+
+    /// Place the stack in a region of the memory, or in the separate `Stack` with `None`.
+    /// The stack pointer is set to the top of the region.
+    ///
+    /// # Parameters
+    /// - `memory_stack`: The region of the memory holding the stack.
+    pub fn set_memory_stack(&mut self, memory_stack: Option<MemoryStack>) {
+        self.memory_stack = memory_stack;
+        self.reset_sp();
+    }
+
+    /// Enable or disable the checked arithmetic: `ADD`, `SUB`, `MULT`, `DIV`, `FXMUL`, `FXDIV`, `INC`
+    /// and `DEC` fail with `VmError::ArithmeticOverflow` on a signed overflow, without changing the
+    /// registers and the flags, instead of wrapping and setting the overflow flag.
+    /// `ADC` and `SBB` are never checked, the lower words of the multi-word integers are unsigned.
+    pub fn set_checked_arithmetic(&mut self, checked: bool) {
+        self.checked_arithmetic = checked;
+    }
+
+    /// Check if the checked arithmetic is enabled, see `CPU::set_checked_arithmetic`.
+    pub fn checked_arithmetic(&self) -> bool {
+        self.checked_arithmetic
+    }
+
+    /// Set the rounding of the fixed-point results of `FXMUL` and `FXDIV`, toward zero by default.
+    pub fn set_fixed_rounding(&mut self, rounding: Rounding) {
+        self.fixed_rounding = rounding;
+    }
+
+    /// Get the rounding of the fixed-point instructions, see `CPU::set_fixed_rounding`.
+    pub fn fixed_rounding(&self) -> Rounding {
+        self.fixed_rounding
+    }
+
+    /// Fail on a signed overflow when the checked arithmetic is enabled.
+    fn check_overflow(&self, overflow: bool) -> VmResult<()> {
+        if overflow && self.checked_arithmetic {
+            return Err(VmError::ArithmeticOverflow);
+        }
+        Ok(())
+    }
+
+    /// Get the region of the memory holding the stack, `None` when the stack is a separate `Stack`.
+    pub fn memory_stack(&self) -> Option<MemoryStack> {
+        self.memory_stack
+    }
+
+    /// Get the stack pointer, `None` when the stack does not live in the memory.
+    pub fn sp(&self) -> Option<usize> {
+        self.memory_stack.map(|_| self.sp)
+    }
+
+    /// Get the frame pointer, `None` when the stack does not live in the memory.
+    pub fn fp(&self) -> Option<usize> {
+        self.memory_stack.map(|_| self.fp)
+    }
+
+    /// Set the stack and frame pointers to the top of the stack in memory, the stack is empty.
+    fn reset_sp(&mut self) {
+        self.sp = self.memory_stack.map_or(0, |region| region.top);
+        self.fp = self.sp;
+        self.sp_low = self.sp;
+    }
+
+    /// Get the address at a signed offset from the frame pointer.
+    ///
+    /// # Errors
+    /// Returns `VmError::InvalidInstruction` if the stack does not live in the memory.
+    fn frame_address(&self, offset: T) -> VmResult<usize> {
+        let fp = self.fp().ok_or(VmError::InvalidInstruction)?;
+        // the offset is sign-extended, adding a negative offset wraps below the frame pointer
+        Ok(fp.wrapping_add(offset.to_usize()))
+    }
+
+    /// Get the number of registers of the CPU.
+    pub fn registers_count(&self) -> u8 {
+        self.registers.len() as u8
+    }
+
+    /// Get the values of the registers.
+    pub fn registers(&self) -> &[T] {
+        &self.registers
+    }
+
+    /// Get the status flags of the CPU.
+    pub fn flags(&self) -> StatusFlags {
+        self.status_flags
+    }
+
+    /// Get the registers and the status flags to update them at once, as the compiled code.
+    #[cfg(feature = "jit")]
+    pub(crate) fn registers_and_flags_mut(&mut self) -> (&mut [T], &mut StatusFlags) {
+        (&mut self.registers, &mut self.status_flags)
     }
 
     /// Get the program counter (PC) of the CPU.
@@ -43,6 +247,252 @@ impl CPU<i32> {
         self.pc
     }
 
+    /// Set the program counter (PC) of the CPU.
+    pub(crate) fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    /// Check if the interrupts are enabled.
+    pub fn interrupts_enabled(&self) -> bool {
+        self.status_flags.interrupt_enable
+    }
+
+    /// Deliver an interrupt at the instruction boundary.
+    /// The program counter and the status flags are pushed on the stack, the interrupts are
+    /// disabled and the execution continues at the handler. `IRET` restores the saved state.
+    ///
+    /// # Parameters
+    /// - `handler`: The address of the interrupt handler in the program.
+    /// - `memory`: The memory, holding the stack when it lives in the memory.
+    /// - `stack`: The stack where the state is saved.
+    ///
+    /// # Errors
+    /// Returns an error if the stack overflows.
+    pub fn interrupt(
+        &mut self,
+        handler: usize,
+        memory: &mut Memory,
+        stack: &mut Stack<T>,
+    ) -> VmResult<()> {
+        self.push(memory, stack, T::from_usize(self.pc))?;
+        self.push(
+            memory,
+            stack,
+            T::from_usize(self.status_flags.bits() as usize),
+        )?;
+        self.status_flags.interrupt_enable = false;
+        self.pc = handler;
+        Ok(())
+    }
+
+    /// Add two values and an incoming carry, and update the flags from the sum.
+    /// The carry flag holds the unsigned carry out, the overflow flag the signed overflow.
+    fn add_with_carry(&mut self, value1: T, value2: T, carry: bool) -> T {
+        let carry_in = if carry { T::one() } else { T::zero() };
+        let (sum, overflow1) = value1.overflowing_add(value2);
+        let (result, overflow2) = sum.overflowing_add(carry_in);
+        let (sum, carry1) = value1.to_unsigned().overflowing_add(value2.to_unsigned());
+        let (_, carry2) = sum.overflowing_add(carry_in.to_unsigned());
+
+        // the two signed additions overflow at once only when the overflows cancel out
+        self.status_flags.overflow = overflow1 != overflow2;
+        self.status_flags.carry = carry1 || carry2;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        result
+    }
+
+    /// Subtract a value and an incoming borrow from a value, and update the flags from the
+    /// difference. The carry flag holds the unsigned borrow, the overflow flag the signed overflow.
+    fn sub_with_borrow(&mut self, value1: T, value2: T, borrow: bool) -> T {
+        let borrow_in = if borrow { T::one() } else { T::zero() };
+        let (difference, overflow1) = value1.overflowing_sub(value2);
+        let (result, overflow2) = difference.overflowing_sub(borrow_in);
+        let (difference, borrow1) = value1.to_unsigned().overflowing_sub(value2.to_unsigned());
+        let (_, borrow2) = difference.overflowing_sub(borrow_in.to_unsigned());
+
+        self.status_flags.overflow = overflow1 != overflow2;
+        self.status_flags.carry = borrow1 || borrow2;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        result
+    }
+
+    /// Store the result of a shift or a rotation and update the flags.
+    /// The carry flag holds the last bit shifted out.
+    fn set_shift_result(&mut self, dest: u8, (result, carry): (T, bool)) {
+        self.registers[dest as usize] = result;
+
+        self.status_flags.carry = carry;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+    }
+
+    /// Apply a bit operation to the bit `bit` of a register, modulo the size of the word.
+    /// The operation receives the value of the register and the mask of the bit,
+    /// the carry flag holds the bit before the operation.
+    fn set_bit_result(&mut self, reg: u8, bit: usize, operation: fn(T, T) -> T) {
+        let (mask, _) = T::one().shift_left((bit % (T::SIZE * 8)) as u32);
+        let value = self.registers[reg as usize];
+
+        self.registers[reg as usize] = operation(value, mask);
+
+        self.status_flags.carry = value & mask != T::zero();
+    }
+
+    /// Store the result of a floating-point operation and update the flags.
+    fn set_float_result(&mut self, dest: u8, result: f32) {
+        self.float_registers[dest as usize] = result;
+
+        self.status_flags.zero = result == 0.0;
+        self.status_flags.negative = result < 0.0;
+    }
+
+    /// Get the memory management unit of the CPU.
+    pub fn mmu(&self) -> &Mmu {
+        &self.mmu
+    }
+
+    /// Read a value from the memory at a virtual address, translated by the MMU.
+    fn load<W: Pod>(&mut self, memory: &mut Memory, address: usize) -> VmResult<W> {
+        let address = self
+            .mmu
+            .translate(memory, address, std::mem::size_of::<W>(), false)?;
+        memory.read::<W>(address)
+    }
+
+    /// Write a value to the memory at a virtual address, translated by the MMU.
+    fn store<W: Pod>(&mut self, memory: &mut Memory, address: usize, value: W) -> VmResult<()> {
+        let address = self
+            .mmu
+            .translate(memory, address, std::mem::size_of::<W>(), true)?;
+        memory.write::<W>(address, value)
+    }
+
+    /// Push a value on the stack, in the memory at the stack pointer or on the separate `Stack`.
+    fn push(&mut self, memory: &mut Memory, stack: &mut Stack<T>, value: T) -> VmResult<()> {
+        self.pushes += 1;
+        let Some(region) = self.memory_stack else {
+            return stack.push(value);
+        };
+        let sp = self
+            .sp
+            .checked_sub(T::SIZE)
+            .filter(|sp| *sp >= region.bottom)
+            .ok_or(VmError::StackOverflow)?;
+        self.store::<T>(memory, sp, value)?;
+        self.sp = sp;
+        self.sp_low = self.sp_low.min(sp);
+        Ok(())
+    }
+
+    /// Pop a value from the stack, in the memory at the stack pointer or from the separate `Stack`.
+    fn pop(&mut self, memory: &mut Memory, stack: &mut Stack<T>) -> VmResult<T> {
+        self.pops += 1;
+        let value = self.peek(memory, stack)?;
+        match self.memory_stack {
+            Some(_) => self.sp += T::SIZE,
+            None => {
+                stack.pop()?;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Read the value on the top of the stack without popping it.
+    fn peek(&mut self, memory: &mut Memory, stack: &Stack<T>) -> VmResult<T> {
+        let Some(region) = self.memory_stack else {
+            return stack.peek().copied();
+        };
+        if self.sp.saturating_add(T::SIZE) > region.top {
+            return Err(VmError::StackUnderflow);
+        }
+        self.load::<T>(memory, self.sp)
+    }
+
+    /// Get the number of values on the stack.
+    fn stack_len(&self, stack: &Stack<T>) -> usize {
+        match self.memory_stack {
+            Some(region) => region.top.saturating_sub(self.sp) / T::SIZE,
+            None => stack.len(),
+        }
+    }
+
+    /// Get the number of values that can still be pushed on the stack.
+    fn stack_free(&self, stack: &Stack<T>) -> usize {
+        match self.memory_stack {
+            Some(region) => self.sp.saturating_sub(region.bottom) / T::SIZE,
+            None => stack.capacity() - stack.len(),
+        }
+    }
+
+    /// Get the number of pushes and pops since the creation of the CPU.
+    pub fn stack_operations(&self) -> (u64, u64) {
+        (self.pushes, self.pops)
+    }
+
+    /// Get the highest number of values held by the stack since the initialization,
+    /// in the memory or in the separate `Stack`.
+    pub fn stack_high_water_mark(&self, stack: &Stack<T>) -> usize {
+        match self.memory_stack {
+            Some(region) => region.top.saturating_sub(self.sp_low) / T::SIZE,
+            None => stack.high_water_mark(),
+        }
+    }
+
+    /// Get the state of the CPU: registers, status flags, program counter, stack and frame pointers
+    /// and page directory.
+    pub fn state(&self) -> CpuState<T> {
+        CpuState {
+            registers: self.registers.clone(),
+            float_registers: self
+                .float_registers
+                .iter()
+                .map(|value| value.to_bits())
+                .collect(),
+            status_flags: self.status_flags,
+            pc: self.pc,
+            sp: self.sp,
+            fp: self.fp,
+            page_directory: self.mmu.directory(),
+        }
+    }
+
+    /// Restore a state previously returned by `state`.
+    ///
+    /// # Errors
+    /// Returns an error if the state does not have the number of registers of the CPU.
+    pub fn restore(&mut self, state: &CpuState<T>) -> VmResult<()> {
+        if state.registers.len() != self.registers.len() {
+            return Err(VmError::Other(format!(
+                "State with {} registers restored on a CPU with {} registers",
+                state.registers.len(),
+                self.registers.len()
+            )));
+        }
+        self.registers.copy_from_slice(&state.registers);
+        // states saved before the float registers existed have none
+        if state.float_registers.len() == self.float_registers.len() {
+            for (register, bits) in self.float_registers.iter_mut().zip(&state.float_registers) {
+                *register = f32::from_bits(*bits);
+            }
+        } else {
+            self.float_registers.fill(0.0);
+        }
+        self.status_flags = state.status_flags;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.fp = state.fp;
+        self.sp_low = self.sp_low.min(state.sp);
+        if state.page_directory != self.mmu.directory() {
+            match state.page_directory {
+                Some(directory) => self.mmu.enable(directory),
+                None => self.mmu.disable(),
+            }
+        }
+        Ok(())
+    }
+
     /// Get the value of a register by index.
     ///
     /// # Parameters
@@ -53,13 +503,37 @@ impl CPU<i32> {
     ///
     /// # Errors
     /// Returns an error if the register index is out of bounds.
-    pub fn get_register(&self, index: u8) -> VmResult<i32> {
-        if index as usize >= REGISTERS_COUNT as usize {
+    pub fn get_register(&self, index: u8) -> VmResult<T> {
+        if index as usize >= self.registers.len() {
             return Err(VmError::InvalidRegister { register: index });
         }
         Ok(self.registers[index as usize])
     }
 
+    /// Set the value of a register by index.
+    ///
+    /// # Errors
+    /// Returns an error if the register index is out of bounds.
+    pub fn set_register(&mut self, index: u8, value: T) -> VmResult<()> {
+        let register = self
+            .registers
+            .get_mut(index as usize)
+            .ok_or(VmError::InvalidRegister { register: index })?;
+        *register = value;
+        Ok(())
+    }
+
+    /// Get the value of a float register by index.
+    ///
+    /// # Errors
+    /// Returns an error if the register index is out of bounds.
+    pub fn get_float_register(&self, index: u8) -> VmResult<f32> {
+        self.float_registers
+            .get(index as usize)
+            .copied()
+            .ok_or(VmError::InvalidRegister { register: index })
+    }
+
     /// Execute an instruction on the CPU.
     /// The instruction modifies the registers, status flags, program counter, memory, and stack.
     ///
@@ -67,188 +541,1901 @@ impl CPU<i32> {
     /// - `instruction`: The instruction to execute.
     /// - `memory`: The memory to read from and write to.
     /// - `stack`: The stack to push to and pop from.
+    /// - `counters`: The performance counters readable by the instruction.
+    /// - `syscalls`: The host functions callable by the instruction.
+    /// - `ports`: The I/O ports accessed by the instruction.
+    ///
+    /// # Returns
+    /// The end of the execution requested by `HLT` and `EXIT`, which leave the program counter
+    /// on themselves, `None` for the other instructions.
     ///
     /// # Errors
-    /// Returns an error if the instruction is invalid or if the HLT instruction is executed.
+    /// Returns an error if the instruction is invalid or if it divides by zero.
     ///
     /// **Note:** Instructions that use registers did already validate by the decoder.
     /// The registers are accessed directly without additional validation.
     pub fn execute_instruction(
         &mut self,
-        instruction: Instruction<i32, u32>,
+        instruction: Instruction<T, T::Address>,
         memory: &mut Memory,
-        stack: &mut Stack<i32>,
-    ) -> VmResult<()> {
-        match instruction {
-            Instruction::NOP => {}
-            Instruction::MOV { dest, value } => {
-                self.registers[dest as usize] = value;
-            }
-            Instruction::LD { dest, address } => {
-                self.registers[dest as usize] = memory.read::<i32>(address as usize)?;
-            }
-            Instruction::ST { src, address } => {
-                memory.write::<i32>(address as usize, self.registers[src as usize])?;
+        stack: &mut Stack<T>,
+        counters: &PerfCounters,
+        syscalls: &mut SyscallTable<T>,
+        ports: &mut PortBus<T>,
+    ) -> VmResult<Option<Stop<T>>> {
+        let mut execution = Execution {
+            memory,
+            stack,
+            counters,
+            syscalls,
+            ports,
+            next_pc: self.pc + instruction.size(),
+            stop: None,
+        };
+        Self::HANDLERS[instruction.opcode() as usize](self, instruction, &mut execution)?;
+        self.pc = execution.next_pc;
+        Ok(execution.stop)
+    }
+
+    /// Fuse two consecutive instructions into a superinstruction, if the pair is frequent in
+    /// loops: `CMP` followed by a conditional jump, `DEC` followed by `JMPNZ`, and `LD` followed
+    /// by `ADD`. The first instruction of these pairs neither jumps nor writes into the memory.
+    pub(crate) fn fuse(
+        first: Instruction<T, T::Address>,
+        second: Instruction<T, T::Address>,
+    ) -> Option<Fused<T>> {
+        let handler: FusedHandler<T> = match (first, second) {
+            (Instruction::CMP { .. }, _) if second.conditional_target().is_some() => {
+                Self::exec_cmp_jump
             }
-            Instruction::ADD { dest, reg1, reg2 } => {
-                let (result, overflow) =
-                    self.registers[reg1 as usize].overflowing_add(self.registers[reg2 as usize]);
+            (Instruction::DEC { .. }, Instruction::JMPNZ { .. }) => Self::exec_dec_jmpnz,
+            (Instruction::LD { .. }, Instruction::ADD { .. }) => Self::exec_ld_add,
+            _ => return None,
+        };
+        Some(Fused {
+            first,
+            second,
+            handler,
+        })
+    }
 
-                self.registers[dest as usize] = result;
+    /// Execute a superinstruction, as `execute_instruction` would execute its two instructions.
+    /// When the first instruction fails, the second one is not executed and the program counter
+    /// is left on the first one.
+    ///
+    /// # Errors
+    /// Returns the error of the first instruction, the second one does not fail.
+    pub(crate) fn execute_fused(
+        &mut self,
+        fused: Fused<T>,
+        memory: &mut Memory,
+        stack: &mut Stack<T>,
+        counters: &PerfCounters,
+        syscalls: &mut SyscallTable<T>,
+        ports: &mut PortBus<T>,
+    ) -> VmResult<()> {
+        let mut execution = Execution {
+            memory,
+            stack,
+            counters,
+            syscalls,
+            ports,
+            next_pc: self.pc + fused.first.size() + fused.second.size(),
+            stop: None,
+        };
+        (fused.handler)(self, fused.first, fused.second, &mut execution)?;
+        self.pc = execution.next_pc;
+        Ok(())
+    }
 
-                self.status_flags.overflow = overflow;
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::SUB { dest, reg1, reg2 } => {
-                let (result, overflow) =
-                    self.registers[reg1 as usize].overflowing_sub(self.registers[reg2 as usize]);
+    fn exec_cmp_jump(
+        &mut self,
+        compare: Instruction<T, T::Address>,
+        jump: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.exec_cmp(compare, execution)?;
+        Self::HANDLERS[jump.opcode() as usize](self, jump, execution)
+    }
 
-                self.registers[dest as usize] = result;
+    fn exec_dec_jmpnz(
+        &mut self,
+        decrement: Instruction<T, T::Address>,
+        jump: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.exec_dec(decrement, execution)?;
+        self.exec_jmpnz(jump, execution)
+    }
 
-                self.status_flags.overflow = overflow;
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::MULT { dest, reg1, reg2 } => {
-                let (result, overflow) =
-                    self.registers[reg1 as usize].overflowing_mul(self.registers[reg2 as usize]);
+    fn exec_ld_add(
+        &mut self,
+        load: Instruction<T, T::Address>,
+        add: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.exec_ld(load, execution)?;
+        self.exec_add(add, execution)
+    }
 
-                self.registers[dest as usize] = result;
+    /// The handlers of the instructions, indexed by opcode.
+    const HANDLERS: [Handler<T>; 256] = {
+        let mut handlers = [Self::exec_invalid as Handler<T>; 256];
+        handlers[OpCode::NOP as usize] = Self::exec_nop;
+        handlers[OpCode::MOV as usize] = Self::exec_mov;
+        handlers[OpCode::LD as usize] = Self::exec_ld;
+        handlers[OpCode::ST as usize] = Self::exec_st;
+        handlers[OpCode::ADD as usize] = Self::exec_add;
+        handlers[OpCode::SUB as usize] = Self::exec_sub;
+        handlers[OpCode::ADC as usize] = Self::exec_adc;
+        handlers[OpCode::SBB as usize] = Self::exec_sbb;
+        handlers[OpCode::MULT as usize] = Self::exec_mult;
+        handlers[OpCode::DIV as usize] = Self::exec_div;
+        handlers[OpCode::MOD as usize] = Self::exec_mod;
+        handlers[OpCode::FXMUL as usize] = Self::exec_fxmul;
+        handlers[OpCode::FXDIV as usize] = Self::exec_fxdiv;
+        handlers[OpCode::AND as usize] = Self::exec_and;
+        handlers[OpCode::OR as usize] = Self::exec_or;
+        handlers[OpCode::XOR as usize] = Self::exec_xor;
+        handlers[OpCode::NOT as usize] = Self::exec_not;
+        handlers[OpCode::CMP as usize] = Self::exec_cmp;
+        handlers[OpCode::INC as usize] = Self::exec_inc;
+        handlers[OpCode::DEC as usize] = Self::exec_dec;
+        handlers[OpCode::PUSHREG as usize] = Self::exec_pushreg;
+        handlers[OpCode::POPREG as usize] = Self::exec_popreg;
+        handlers[OpCode::JMP as usize] = Self::exec_jmp;
+        handlers[OpCode::JMPN as usize] = Self::exec_jmpn;
+        handlers[OpCode::JMPP as usize] = Self::exec_jmpp;
+        handlers[OpCode::JMPZ as usize] = Self::exec_jmpz;
+        handlers[OpCode::CALL as usize] = Self::exec_call;
+        handlers[OpCode::RET as usize] = Self::exec_ret;
+        handlers[OpCode::CLF as usize] = Self::exec_clf;
+        handlers[OpCode::PUSHF as usize] = Self::exec_pushf;
+        handlers[OpCode::POPF as usize] = Self::exec_popf;
+        handlers[OpCode::RDCNT as usize] = Self::exec_rdcnt;
+        handlers[OpCode::RDCYCLE as usize] = Self::exec_rdcycle;
+        handlers[OpCode::SYSCALL as usize] = Self::exec_syscall;
+        handlers[OpCode::EI as usize] = Self::exec_ei;
+        handlers[OpCode::DI as usize] = Self::exec_di;
+        handlers[OpCode::IRET as usize] = Self::exec_iret;
+        handlers[OpCode::SHL as usize] = Self::exec_shl;
+        handlers[OpCode::SHLI as usize] = Self::exec_shli;
+        handlers[OpCode::SHR as usize] = Self::exec_shr;
+        handlers[OpCode::SHRI as usize] = Self::exec_shri;
+        handlers[OpCode::SAR as usize] = Self::exec_sar;
+        handlers[OpCode::SARI as usize] = Self::exec_sari;
+        handlers[OpCode::ROL as usize] = Self::exec_rol;
+        handlers[OpCode::ROR as usize] = Self::exec_ror;
+        handlers[OpCode::MOVR as usize] = Self::exec_movr;
+        handlers[OpCode::LDR as usize] = Self::exec_ldr;
+        handlers[OpCode::STR as usize] = Self::exec_str;
+        handlers[OpCode::LDB as usize] = Self::exec_ldb;
+        handlers[OpCode::LDBU as usize] = Self::exec_ldbu;
+        handlers[OpCode::LDH as usize] = Self::exec_ldh;
+        handlers[OpCode::LDHU as usize] = Self::exec_ldhu;
+        handlers[OpCode::STB as usize] = Self::exec_stb;
+        handlers[OpCode::STH as usize] = Self::exec_sth;
+        handlers[OpCode::MULU as usize] = Self::exec_mulu;
+        handlers[OpCode::DIVU as usize] = Self::exec_divu;
+        handlers[OpCode::MODU as usize] = Self::exec_modu;
+        handlers[OpCode::CMPU as usize] = Self::exec_cmpu;
+        handlers[OpCode::JMPB as usize] = Self::exec_jmpb;
+        handlers[OpCode::JMPBE as usize] = Self::exec_jmpbe;
+        handlers[OpCode::JMPA as usize] = Self::exec_jmpa;
+        handlers[OpCode::JMPAE as usize] = Self::exec_jmpae;
+        handlers[OpCode::JMPLT as usize] = Self::exec_jmplt;
+        handlers[OpCode::JMPLE as usize] = Self::exec_jmple;
+        handlers[OpCode::JMPGT as usize] = Self::exec_jmpgt;
+        handlers[OpCode::JMPGE as usize] = Self::exec_jmpge;
+        handlers[OpCode::JMPC as usize] = Self::exec_jmpc;
+        handlers[OpCode::JMPNC as usize] = Self::exec_jmpnc;
+        handlers[OpCode::JMPNZ as usize] = Self::exec_jmpnz;
+        handlers[OpCode::JMPO as usize] = Self::exec_jmpo;
+        handlers[OpCode::CMOVZ as usize] = Self::exec_cmovz;
+        handlers[OpCode::CMOVN as usize] = Self::exec_cmovn;
+        handlers[OpCode::MIN as usize] = Self::exec_min;
+        handlers[OpCode::MAX as usize] = Self::exec_max;
+        handlers[OpCode::MINU as usize] = Self::exec_minu;
+        handlers[OpCode::MAXU as usize] = Self::exec_maxu;
+        handlers[OpCode::POPCNT as usize] = Self::exec_popcnt;
+        handlers[OpCode::CLZ as usize] = Self::exec_clz;
+        handlers[OpCode::CTZ as usize] = Self::exec_ctz;
+        handlers[OpCode::BT as usize] = Self::exec_bt;
+        handlers[OpCode::BTI as usize] = Self::exec_bti;
+        handlers[OpCode::BS as usize] = Self::exec_bs;
+        handlers[OpCode::BSI as usize] = Self::exec_bsi;
+        handlers[OpCode::BC as usize] = Self::exec_bc;
+        handlers[OpCode::BCI as usize] = Self::exec_bci;
+        handlers[OpCode::BTG as usize] = Self::exec_btg;
+        handlers[OpCode::BTGI as usize] = Self::exec_btgi;
+        handlers[OpCode::PUSH as usize] = Self::exec_push;
+        handlers[OpCode::DUP as usize] = Self::exec_dup;
+        handlers[OpCode::SWAP as usize] = Self::exec_swap;
+        handlers[OpCode::DROP as usize] = Self::exec_drop;
+        handlers[OpCode::PUSHA as usize] = Self::exec_pusha;
+        handlers[OpCode::POPA as usize] = Self::exec_popa;
+        handlers[OpCode::FMOV as usize] = Self::exec_fmov;
+        handlers[OpCode::FADD as usize] = Self::exec_fadd;
+        handlers[OpCode::FSUB as usize] = Self::exec_fsub;
+        handlers[OpCode::FMUL as usize] = Self::exec_fmul;
+        handlers[OpCode::FDIV as usize] = Self::exec_fdiv;
+        handlers[OpCode::FCMP as usize] = Self::exec_fcmp;
+        handlers[OpCode::ITOF as usize] = Self::exec_itof;
+        handlers[OpCode::FTOI as usize] = Self::exec_ftoi;
+        handlers[OpCode::MULH as usize] = Self::exec_mulh;
+        handlers[OpCode::UMULH as usize] = Self::exec_umulh;
+        handlers[OpCode::CAS as usize] = Self::exec_cas;
+        handlers[OpCode::XADD as usize] = Self::exec_xadd;
+        handlers[OpCode::SPAWN as usize] = Self::exec_spawn;
+        handlers[OpCode::YIELD as usize] = Self::exec_spawn;
+        handlers[OpCode::JOIN as usize] = Self::exec_spawn;
+        handlers[OpCode::MMUON as usize] = Self::exec_mmuon;
+        handlers[OpCode::MMUOFF as usize] = Self::exec_mmuoff;
+        handlers[OpCode::TLBFLUSH as usize] = Self::exec_tlbflush;
+        handlers[OpCode::RDFAULT as usize] = Self::exec_rdfault;
+        handlers[OpCode::RDSP as usize] = Self::exec_rdsp;
+        handlers[OpCode::WRSP as usize] = Self::exec_wrsp;
+        handlers[OpCode::ENTER as usize] = Self::exec_enter;
+        handlers[OpCode::LEAVE as usize] = Self::exec_leave;
+        handlers[OpCode::LDF as usize] = Self::exec_ldf;
+        handlers[OpCode::STF as usize] = Self::exec_stf;
+        handlers[OpCode::RDFP as usize] = Self::exec_rdfp;
+        handlers[OpCode::EXIT as usize] = Self::exec_exit;
+        handlers[OpCode::IN as usize] = Self::exec_in;
+        handlers[OpCode::OUT as usize] = Self::exec_out;
+        handlers[OpCode::HLT as usize] = Self::exec_hlt;
+        handlers
+    };
 
-                self.status_flags.overflow = overflow;
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::DIV { dest, reg1, reg2 } => {
-                let (result, overflow) =
-                    self.registers[reg1 as usize].overflowing_div(self.registers[reg2 as usize]);
+    fn exec_nop(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        Ok(())
+    }
 
-                self.registers[dest as usize] = result;
+    fn exec_mov(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MOV { dest, value });
+        self.registers[dest as usize] = value;
+        Ok(())
+    }
 
-                self.status_flags.overflow = overflow;
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::MOD { dest, reg1, reg2 } => {
-                let result = self.registers[reg1 as usize] % self.registers[reg2 as usize];
+    fn exec_ld(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::LD { dest, address });
+        self.registers[dest as usize] = self.load::<T>(execution.memory, address.to_usize())?;
+        Ok(())
+    }
 
-                self.registers[dest as usize] = result;
+    fn exec_st(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::ST { src, address });
+        self.store::<T>(
+            execution.memory,
+            address.to_usize(),
+            self.registers[src as usize],
+        )?;
+        Ok(())
+    }
 
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::AND { dest, reg1, reg2 } => {
-                let result = self.registers[reg1 as usize] & self.registers[reg2 as usize];
+    fn exec_add(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::ADD { dest, reg1, reg2 });
+        let (value1, value2) = (self.registers[reg1 as usize], self.registers[reg2 as usize]);
+        self.check_overflow(value1.overflowing_add(value2).1)?;
+        self.registers[dest as usize] = self.add_with_carry(value1, value2, false);
+        Ok(())
+    }
 
-                self.registers[dest as usize] = result;
+    fn exec_sub(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SUB { dest, reg1, reg2 });
+        let (value1, value2) = (self.registers[reg1 as usize], self.registers[reg2 as usize]);
+        self.check_overflow(value1.overflowing_sub(value2).1)?;
+        self.registers[dest as usize] = self.sub_with_borrow(value1, value2, false);
+        Ok(())
+    }
 
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::OR { dest, reg1, reg2 } => {
-                let result = self.registers[reg1 as usize] | self.registers[reg2 as usize];
+    fn exec_adc(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::ADC { dest, reg1, reg2 });
+        let result = self.add_with_carry(
+            self.registers[reg1 as usize],
+            self.registers[reg2 as usize],
+            self.status_flags.carry,
+        );
+        self.registers[dest as usize] = result;
+        Ok(())
+    }
 
-                self.registers[dest as usize] = result;
+    fn exec_sbb(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SBB { dest, reg1, reg2 });
+        let result = self.sub_with_borrow(
+            self.registers[reg1 as usize],
+            self.registers[reg2 as usize],
+            self.status_flags.carry,
+        );
+        self.registers[dest as usize] = result;
+        Ok(())
+    }
 
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::XOR { dest, reg1, reg2 } => {
-                let result = self.registers[reg1 as usize] ^ self.registers[reg2 as usize];
+    fn exec_mult(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MULT { dest, reg1, reg2 });
+        let (result, overflow) =
+            self.registers[reg1 as usize].overflowing_mul(self.registers[reg2 as usize]);
+        self.check_overflow(overflow)?;
 
-                self.registers[dest as usize] = result;
+        self.registers[dest as usize] = result;
 
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::NOT { dest, reg } => {
-                let result = !self.registers[reg as usize];
+        self.status_flags.overflow = overflow;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
 
-                self.registers[dest as usize] = result;
+    fn exec_div(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::DIV { dest, reg1, reg2 });
+        let (result, overflow) = self.registers[reg1 as usize]
+            .overflowing_div(self.registers[reg2 as usize])
+            .ok_or(VmError::DivisionByZero)?;
+        self.check_overflow(overflow)?;
 
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::CMP { reg1, reg2 } => {
-                let result = self.registers[reg1 as usize].cmp(&self.registers[reg2 as usize]);
+        self.registers[dest as usize] = result;
 
-                self.status_flags.zero = result == std::cmp::Ordering::Equal;
-            }
-            Instruction::INC { reg } => {
-                let (result, overflow) = self.registers[reg as usize].overflowing_add(1);
+        self.status_flags.overflow = overflow;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
 
-                self.registers[reg as usize] = result;
+    fn exec_fxmul(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FXMUL { dest, reg1, reg2 });
+        let (result, overflow) = fixed::multiply(
+            self.registers[reg1 as usize],
+            self.registers[reg2 as usize],
+            self.fixed_rounding,
+        );
+        self.check_overflow(overflow)?;
 
-                self.status_flags.overflow = overflow;
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::DEC { reg } => {
-                let (result, overflow) = self.registers[reg as usize].overflowing_sub(1);
+        self.registers[dest as usize] = result;
 
-                self.registers[reg as usize] = result;
+        self.status_flags.overflow = overflow;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
 
-                self.status_flags.overflow = overflow;
-                self.status_flags.zero = result == 0;
-                self.status_flags.negative = result < 0;
-            }
-            Instruction::PUSHREG { reg } => {
-                stack.push(self.registers[reg as usize])?;
-            }
-            Instruction::POPREG { reg } => {
-                self.registers[reg as usize] = stack.pop()?;
-            }
-            Instruction::JMP { address } => {
-                self.pc = address as usize;
-            }
-            Instruction::JMPN { address } => {
-                if self.status_flags.negative {
-                    self.pc = address as usize;
-                }
-            }
-            Instruction::JMPP { address } => {
-                if !self.status_flags.negative {
-                    self.pc = address as usize;
-                }
-            }
-            Instruction::JMPZ { address } => {
-                if self.status_flags.zero {
-                    self.pc = address as usize;
-                }
-            }
-            Instruction::CALL { address } => {
-                stack.push(self.pc as i32)?;
-                self.pc = address as usize;
-            }
-            Instruction::RET => {
-                self.pc = stack.pop()? as usize;
-            }
-            Instruction::CLF => {
-                self.status_flags.clear();
-            }
-            Instruction::HLT => {
-                return Err(VmError::Other("HLT instruction executed".to_string()));
-            }
-        }
-        self.pc += instruction.size();
+    fn exec_fxdiv(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FXDIV { dest, reg1, reg2 });
+        let (result, overflow) = fixed::divide(
+            self.registers[reg1 as usize],
+            self.registers[reg2 as usize],
+            self.fixed_rounding,
+        )
+        .ok_or(VmError::DivisionByZero)?;
+        self.check_overflow(overflow)?;
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.overflow = overflow;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
         Ok(())
     }
-}
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct StatusFlags {
-    pub zero: bool,
-    pub carry: bool,
-    pub overflow: bool,
-    pub negative: bool,
-}
+    fn exec_mod(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MOD { dest, reg1, reg2 });
+        let (result, _) = self.registers[reg1 as usize]
+            .overflowing_rem(self.registers[reg2 as usize])
+            .ok_or(VmError::DivisionByZero)?;
 
-impl StatusFlags {
-    pub fn clear(&mut self) {
-        self.zero = false;
-        //self.carry = false;
-        self.overflow = false;
-        self.negative = false;
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_and(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::AND { dest, reg1, reg2 });
+        let result = self.registers[reg1 as usize] & self.registers[reg2 as usize];
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_or(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::OR { dest, reg1, reg2 });
+        let result = self.registers[reg1 as usize] | self.registers[reg2 as usize];
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_xor(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::XOR { dest, reg1, reg2 });
+        let result = self.registers[reg1 as usize] ^ self.registers[reg2 as usize];
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_not(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::NOT { dest, reg });
+        let result = !self.registers[reg as usize];
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_cmp(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CMP { reg1, reg2 });
+        let value1 = self.registers[reg1 as usize];
+        let value2 = self.registers[reg2 as usize];
+        let (result, overflow) = value1.overflowing_sub(value2);
+        let (_, borrow) = value1.to_unsigned().overflowing_sub(value2.to_unsigned());
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        self.status_flags.overflow = overflow;
+        self.status_flags.carry = borrow;
+        Ok(())
+    }
+
+    fn exec_inc(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::INC { reg });
+        let (result, overflow) = self.registers[reg as usize].overflowing_add(T::one());
+        self.check_overflow(overflow)?;
+
+        self.registers[reg as usize] = result;
+
+        self.status_flags.overflow = overflow;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_dec(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::DEC { reg });
+        let (result, overflow) = self.registers[reg as usize].overflowing_sub(T::one());
+        self.check_overflow(overflow)?;
+
+        self.registers[reg as usize] = result;
+
+        self.status_flags.overflow = overflow;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_pushreg(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::PUSHREG { reg });
+        self.push(
+            execution.memory,
+            execution.stack,
+            self.registers[reg as usize],
+        )?;
+        Ok(())
+    }
+
+    fn exec_popreg(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::POPREG { reg });
+        self.registers[reg as usize] = self.pop(execution.memory, execution.stack)?;
+        Ok(())
+    }
+
+    fn exec_jmp(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMP { address });
+        execution.next_pc = address.to_usize();
+        Ok(())
+    }
+
+    fn exec_jmpn(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPN { address });
+        if self.status_flags.negative {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpp(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPP { address });
+        if !self.status_flags.negative {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpz(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPZ { address });
+        if self.status_flags.zero {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_call(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CALL { address });
+        self.push(
+            execution.memory,
+            execution.stack,
+            T::from_usize(execution.next_pc),
+        )?;
+        execution.next_pc = address.to_usize();
+        Ok(())
+    }
+
+    fn exec_ret(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        execution.next_pc = self.pop(execution.memory, execution.stack)?.to_usize();
+        Ok(())
+    }
+
+    fn exec_clf(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.status_flags.clear();
+        Ok(())
+    }
+
+    fn exec_pushf(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        let flags = T::from_usize(self.status_flags.bits() as usize);
+        self.push(execution.memory, execution.stack, flags)?;
+        Ok(())
+    }
+
+    fn exec_popf(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        let flags = self.pop(execution.memory, execution.stack)?;
+        self.status_flags = StatusFlags::from_bits(flags.to_usize() as u8);
+        Ok(())
+    }
+
+    fn exec_rdcnt(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::RDCNT { dest, counter });
+        self.registers[dest as usize] = execution.counters.read::<T>(counter)?;
+        Ok(())
+    }
+
+    fn exec_rdcycle(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::RDCYCLE { dest });
+        self.registers[dest as usize] = execution.counters.read::<T>(Counter::Cycles.into())?;
+        Ok(())
+    }
+
+    fn exec_syscall(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SYSCALL { number });
+        let mut view = CpuView {
+            registers: &mut self.registers,
+            status_flags: &mut self.status_flags,
+            pc: self.pc,
+        };
+        execution
+            .syscalls
+            .call(number, &mut view, execution.memory)?;
+        Ok(())
+    }
+
+    fn exec_in(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::IN { dest, port });
+        self.registers[dest as usize] = execution.ports.read(port)?;
+        Ok(())
+    }
+
+    fn exec_out(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::OUT { port, src });
+        execution.ports.write(port, self.registers[src as usize])
+    }
+
+    fn exec_ei(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.status_flags.interrupt_enable = true;
+        Ok(())
+    }
+
+    fn exec_di(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.status_flags.interrupt_enable = false;
+        Ok(())
+    }
+
+    fn exec_iret(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        let flags = self.pop(execution.memory, execution.stack)?;
+        self.status_flags = StatusFlags::from_bits(flags.to_usize() as u8);
+        execution.next_pc = self.pop(execution.memory, execution.stack)?.to_usize();
+        Ok(())
+    }
+
+    fn exec_shl(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SHL { dest, reg1, reg2 });
+        let amount = shift_amount(self.registers[reg2 as usize]);
+        let result = self.registers[reg1 as usize].shift_left(amount);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_shli(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SHLI { dest, reg, amount });
+        let result = self.registers[reg as usize].shift_left(amount as u32);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_shr(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SHR { dest, reg1, reg2 });
+        let amount = shift_amount(self.registers[reg2 as usize]);
+        let result = self.registers[reg1 as usize].shift_right(amount);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_shri(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SHRI { dest, reg, amount });
+        let result = self.registers[reg as usize].shift_right(amount as u32);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_sar(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SAR { dest, reg1, reg2 });
+        let amount = shift_amount(self.registers[reg2 as usize]);
+        let result = self.registers[reg1 as usize].shift_right_arithmetic(amount);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_sari(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::SARI { dest, reg, amount });
+        let result = self.registers[reg as usize].shift_right_arithmetic(amount as u32);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_rol(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::ROL { dest, reg1, reg2 });
+        let amount = shift_amount(self.registers[reg2 as usize]);
+        let result = self.registers[reg1 as usize].rotate_left(amount);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_ror(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::ROR { dest, reg1, reg2 });
+        let amount = shift_amount(self.registers[reg2 as usize]);
+        let result = self.registers[reg1 as usize].rotate_right(amount);
+        self.set_shift_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_movr(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MOVR { dest, src });
+        self.registers[dest as usize] = self.registers[src as usize];
+        Ok(())
+    }
+
+    fn exec_ldr(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::LDR { dest, reg });
+        let address = self.registers[reg as usize].to_usize();
+        self.registers[dest as usize] = self.load::<T>(execution.memory, address)?;
+        Ok(())
+    }
+
+    fn exec_str(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::STR { src, reg });
+        let address = self.registers[reg as usize].to_usize();
+        self.store::<T>(execution.memory, address, self.registers[src as usize])?;
+        Ok(())
+    }
+
+    fn exec_ldb(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::LDB { dest, address });
+        let value = self.load::<i8>(execution.memory, address.to_usize())?;
+        self.registers[dest as usize] = T::from_usize(value as isize as usize);
+        Ok(())
+    }
+
+    fn exec_ldbu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::LDBU { dest, address });
+        let value = self.load::<u8>(execution.memory, address.to_usize())?;
+        self.registers[dest as usize] = T::from_usize(value as usize);
+        Ok(())
+    }
+
+    fn exec_ldh(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::LDH { dest, address });
+        let value = self.load::<i16>(execution.memory, address.to_usize())?;
+        self.registers[dest as usize] = T::from_usize(value as isize as usize);
+        Ok(())
+    }
+
+    fn exec_ldhu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::LDHU { dest, address });
+        let value = self.load::<u16>(execution.memory, address.to_usize())?;
+        self.registers[dest as usize] = T::from_usize(value as usize);
+        Ok(())
+    }
+
+    fn exec_stb(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::STB { src, address });
+        let value = self.registers[src as usize].to_usize() as u8;
+        self.store::<u8>(execution.memory, address.to_usize(), value)?;
+        Ok(())
+    }
+
+    fn exec_sth(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::STH { src, address });
+        let value = self.registers[src as usize].to_usize() as u16;
+        self.store::<u16>(execution.memory, address.to_usize(), value)?;
+        Ok(())
+    }
+
+    fn exec_mulu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MULU { dest, reg1, reg2 });
+        let (result, overflow) = self.registers[reg1 as usize]
+            .to_unsigned()
+            .overflowing_mul(self.registers[reg2 as usize].to_unsigned());
+        let result = T::from_unsigned(result);
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.overflow = overflow;
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_divu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::DIVU { dest, reg1, reg2 });
+        let (result, _) = self.registers[reg1 as usize]
+            .to_unsigned()
+            .overflowing_div(self.registers[reg2 as usize].to_unsigned())
+            .ok_or(VmError::DivisionByZero)?;
+        let result = T::from_unsigned(result);
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_modu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MODU { dest, reg1, reg2 });
+        let (result, _) = self.registers[reg1 as usize]
+            .to_unsigned()
+            .overflowing_rem(self.registers[reg2 as usize].to_unsigned())
+            .ok_or(VmError::DivisionByZero)?;
+        let result = T::from_unsigned(result);
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_cmpu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CMPU { reg1, reg2 });
+        let (result, borrow) = self.registers[reg1 as usize]
+            .to_unsigned()
+            .overflowing_sub(self.registers[reg2 as usize].to_unsigned());
+
+        self.status_flags.carry = borrow;
+        self.status_flags.zero = result == T::Address::zero();
+        self.status_flags.negative = T::from_unsigned(result).is_negative();
+        Ok(())
+    }
+
+    fn exec_jmpb(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPB { address });
+        if self.status_flags.carry {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpbe(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPBE { address });
+        if self.status_flags.carry || self.status_flags.zero {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpa(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPA { address });
+        if !self.status_flags.carry && !self.status_flags.zero {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpae(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPAE { address });
+        if !self.status_flags.carry {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmplt(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPLT { address });
+        if self.status_flags.negative != self.status_flags.overflow {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmple(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPLE { address });
+        if self.status_flags.zero || self.status_flags.negative != self.status_flags.overflow {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpgt(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPGT { address });
+        if !self.status_flags.zero && self.status_flags.negative == self.status_flags.overflow {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpge(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPGE { address });
+        if self.status_flags.negative == self.status_flags.overflow {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpc(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPC { address });
+        if self.status_flags.carry {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpnc(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPNC { address });
+        if !self.status_flags.carry {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpnz(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPNZ { address });
+        if !self.status_flags.zero {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_jmpo(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::JMPO { address });
+        if self.status_flags.overflow {
+            execution.next_pc = address.to_usize();
+        }
+        Ok(())
+    }
+
+    fn exec_cmovz(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CMOVZ { dest, src });
+        if self.status_flags.zero {
+            self.registers[dest as usize] = self.registers[src as usize];
+        }
+        Ok(())
+    }
+
+    fn exec_cmovn(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CMOVN { dest, src });
+        if self.status_flags.negative {
+            self.registers[dest as usize] = self.registers[src as usize];
+        }
+        Ok(())
+    }
+
+    fn exec_min(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MIN { dest, reg1, reg2 });
+        self.registers[dest as usize] =
+            std::cmp::min(self.registers[reg1 as usize], self.registers[reg2 as usize]);
+        Ok(())
+    }
+
+    fn exec_max(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MAX { dest, reg1, reg2 });
+        self.registers[dest as usize] =
+            std::cmp::max(self.registers[reg1 as usize], self.registers[reg2 as usize]);
+        Ok(())
+    }
+
+    fn exec_minu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MINU { dest, reg1, reg2 });
+        self.registers[dest as usize] = T::from_unsigned(std::cmp::min(
+            self.registers[reg1 as usize].to_unsigned(),
+            self.registers[reg2 as usize].to_unsigned(),
+        ));
+        Ok(())
+    }
+
+    fn exec_maxu(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MAXU { dest, reg1, reg2 });
+        self.registers[dest as usize] = T::from_unsigned(std::cmp::max(
+            self.registers[reg1 as usize].to_unsigned(),
+            self.registers[reg2 as usize].to_unsigned(),
+        ));
+        Ok(())
+    }
+
+    fn exec_popcnt(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::POPCNT { dest, reg });
+        let result = T::from_usize(self.registers[reg as usize].count_ones() as usize);
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        Ok(())
+    }
+
+    fn exec_clz(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CLZ { dest, reg });
+        let result = T::from_usize(self.registers[reg as usize].leading_zeros() as usize);
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        Ok(())
+    }
+
+    fn exec_ctz(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CTZ { dest, reg });
+        let result = T::from_usize(self.registers[reg as usize].trailing_zeros() as usize);
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        Ok(())
+    }
+
+    fn exec_bt(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BT { reg1, reg2 });
+        let bit = self.registers[reg2 as usize].to_unsigned().to_usize();
+        self.set_bit_result(reg1, bit, |value, _| value);
+        Ok(())
+    }
+
+    fn exec_bti(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BTI { reg, bit });
+        self.set_bit_result(reg, bit as usize, |value, _| value);
+        Ok(())
+    }
+
+    fn exec_bs(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BS { reg1, reg2 });
+        let bit = self.registers[reg2 as usize].to_unsigned().to_usize();
+        self.set_bit_result(reg1, bit, |value, mask| value | mask);
+        Ok(())
+    }
+
+    fn exec_bsi(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BSI { reg, bit });
+        self.set_bit_result(reg, bit as usize, |value, mask| value | mask);
+        Ok(())
+    }
+
+    fn exec_bc(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BC { reg1, reg2 });
+        let bit = self.registers[reg2 as usize].to_unsigned().to_usize();
+        self.set_bit_result(reg1, bit, |value, mask| value & !mask);
+        Ok(())
+    }
+
+    fn exec_bci(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BCI { reg, bit });
+        self.set_bit_result(reg, bit as usize, |value, mask| value & !mask);
+        Ok(())
+    }
+
+    fn exec_btg(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BTG { reg1, reg2 });
+        let bit = self.registers[reg2 as usize].to_unsigned().to_usize();
+        self.set_bit_result(reg1, bit, |value, mask| value ^ mask);
+        Ok(())
+    }
+
+    fn exec_btgi(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::BTGI { reg, bit });
+        self.set_bit_result(reg, bit as usize, |value, mask| value ^ mask);
+        Ok(())
+    }
+
+    fn exec_push(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::PUSH { value });
+        self.push(execution.memory, execution.stack, value)?;
+        Ok(())
+    }
+
+    fn exec_dup(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        let value = self.peek(execution.memory, execution.stack)?;
+        self.push(execution.memory, execution.stack, value)?;
+        Ok(())
+    }
+
+    fn exec_swap(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        if self.stack_len(execution.stack) < 2 {
+            return Err(VmError::StackUnderflow);
+        }
+        let top = self.pop(execution.memory, execution.stack)?;
+        let below = self.pop(execution.memory, execution.stack)?;
+        self.push(execution.memory, execution.stack, top)?;
+        self.push(execution.memory, execution.stack, below)?;
+        Ok(())
+    }
+
+    fn exec_drop(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.pop(execution.memory, execution.stack)?;
+        Ok(())
+    }
+
+    fn exec_pusha(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        if self.stack_free(execution.stack) < self.registers.len() {
+            return Err(VmError::StackOverflow);
+        }
+        for index in 0..self.registers.len() {
+            self.push(execution.memory, execution.stack, self.registers[index])?;
+        }
+        Ok(())
+    }
+
+    fn exec_popa(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        if self.stack_len(execution.stack) < self.registers.len() {
+            return Err(VmError::StackUnderflow);
+        }
+        for index in (0..self.registers.len()).rev() {
+            self.registers[index] = self.pop(execution.memory, execution.stack)?;
+        }
+        Ok(())
+    }
+
+    fn exec_fmov(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FMOV { dest, value });
+        self.float_registers[dest as usize] = f32::from_bits(value);
+        Ok(())
+    }
+
+    fn exec_fadd(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FADD { dest, reg1, reg2 });
+        let result = self.float_registers[reg1 as usize] + self.float_registers[reg2 as usize];
+        self.set_float_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_fsub(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FSUB { dest, reg1, reg2 });
+        let result = self.float_registers[reg1 as usize] - self.float_registers[reg2 as usize];
+        self.set_float_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_fmul(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FMUL { dest, reg1, reg2 });
+        let result = self.float_registers[reg1 as usize] * self.float_registers[reg2 as usize];
+        self.set_float_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_fdiv(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FDIV { dest, reg1, reg2 });
+        let result = self.float_registers[reg1 as usize] / self.float_registers[reg2 as usize];
+        self.set_float_result(dest, result);
+        Ok(())
+    }
+
+    fn exec_fcmp(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FCMP { reg1, reg2 });
+        let ordering =
+            self.float_registers[reg1 as usize].partial_cmp(&self.float_registers[reg2 as usize]);
+        let less = ordering == Some(std::cmp::Ordering::Less);
+
+        self.status_flags.zero = ordering == Some(std::cmp::Ordering::Equal);
+        self.status_flags.carry = less;
+        self.status_flags.negative = less;
+        self.status_flags.overflow = ordering.is_none();
+        Ok(())
+    }
+
+    fn exec_itof(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::ITOF { dest, src });
+        self.float_registers[dest as usize] = self.registers[src as usize].to_f32();
+        Ok(())
+    }
+
+    fn exec_ftoi(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::FTOI { dest, src });
+        self.registers[dest as usize] = T::from_f32(self.float_registers[src as usize]);
+        Ok(())
+    }
+
+    fn exec_mulh(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MULH { dest, reg1, reg2 });
+        let result = self.registers[reg1 as usize].mul_high(self.registers[reg2 as usize]);
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_umulh(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::UMULH { dest, reg1, reg2 });
+        let result = T::from_unsigned(
+            self.registers[reg1 as usize]
+                .to_unsigned()
+                .mul_high(self.registers[reg2 as usize].to_unsigned()),
+        );
+
+        self.registers[dest as usize] = result;
+
+        self.status_flags.zero = result == T::zero();
+        self.status_flags.negative = result.is_negative();
+        Ok(())
+    }
+
+    fn exec_cas(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::CAS { expected, new, reg });
+        let address = self.registers[reg as usize].to_usize();
+        let value = self.load::<T>(execution.memory, address)?;
+        let swapped = value == self.registers[expected as usize];
+        if swapped {
+            self.store::<T>(execution.memory, address, self.registers[new as usize])?;
+        }
+
+        self.registers[expected as usize] = value;
+
+        self.status_flags.zero = swapped;
+        Ok(())
+    }
+
+    fn exec_xadd(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::XADD { src, reg });
+        let address = self.registers[reg as usize].to_usize();
+        let value = self.load::<T>(execution.memory, address)?;
+        let (result, _) = value.overflowing_add(self.registers[src as usize]);
+        self.store::<T>(execution.memory, address, result)?;
+
+        self.add_with_carry(value, self.registers[src as usize], false);
+        self.registers[src as usize] = value;
+        Ok(())
+    }
+
+    fn exec_spawn(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        Err(VmError::Other(
+            "Thread instructions are executed by the VM".to_string(),
+        ))
+    }
+
+    fn exec_mmuon(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::MMUON { reg });
+        self.mmu.enable(self.registers[reg as usize].to_usize());
+        Ok(())
+    }
+
+    fn exec_mmuoff(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.mmu.disable();
+        Ok(())
+    }
+
+    fn exec_tlbflush(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        self.mmu.flush();
+        Ok(())
+    }
+
+    fn exec_rdfault(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::RDFAULT { dest });
+        self.registers[dest as usize] = T::from_usize(self.mmu.fault_address());
+        Ok(())
+    }
+
+    fn exec_rdsp(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::RDSP { dest });
+        let sp = self.sp().ok_or(VmError::InvalidInstruction)?;
+        self.registers[dest as usize] = T::from_usize(sp);
+        Ok(())
+    }
+
+    fn exec_wrsp(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::WRSP { src });
+        if self.memory_stack.is_none() {
+            return Err(VmError::InvalidInstruction);
+        }
+        self.sp = self.registers[src as usize].to_usize();
+        self.sp_low = self.sp_low.min(self.sp);
+        Ok(())
+    }
+
+    fn exec_enter(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::ENTER { size });
+        let region = self.memory_stack.ok_or(VmError::InvalidInstruction)?;
+        let frame = self
+            .sp
+            .checked_sub(T::SIZE)
+            .and_then(|fp| fp.checked_sub(size.to_usize()))
+            .filter(|sp| *sp >= region.bottom)
+            .ok_or(VmError::StackOverflow)?;
+        self.push(execution.memory, execution.stack, T::from_usize(self.fp))?;
+        self.fp = self.sp;
+        self.sp = frame;
+        self.sp_low = self.sp_low.min(frame);
+        Ok(())
+    }
+
+    fn exec_leave(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        if self.memory_stack.is_none() {
+            return Err(VmError::InvalidInstruction);
+        }
+        let sp = std::mem::replace(&mut self.sp, self.fp);
+        match self.pop(execution.memory, execution.stack) {
+            Ok(fp) => self.fp = fp.to_usize(),
+            Err(error) => {
+                self.sp = sp;
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    fn exec_ldf(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::LDF { dest, offset });
+        let address = self.frame_address(offset)?;
+        self.registers[dest as usize] = self.load::<T>(execution.memory, address)?;
+        Ok(())
+    }
+
+    fn exec_stf(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::STF { src, offset });
+        let address = self.frame_address(offset)?;
+        self.store::<T>(execution.memory, address, self.registers[src as usize])?;
+        Ok(())
+    }
+
+    fn exec_rdfp(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::RDFP { dest });
+        let fp = self.fp().ok_or(VmError::InvalidInstruction)?;
+        self.registers[dest as usize] = T::from_usize(fp);
+        Ok(())
+    }
+
+    fn exec_exit(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        operands!(instruction, Instruction::EXIT { code });
+        execution.next_pc = self.pc;
+        execution.stop = Some(Stop::Exit { code });
+        Ok(())
+    }
+
+    fn exec_hlt(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        execution.next_pc = self.pc;
+        // a CPU without registers halts with the exit code 0
+        let code = self.registers.first().copied().unwrap_or(T::zero());
+        execution.stop = Some(Stop::Halt { code });
+        Ok(())
+    }
+
+    /// The handler of the opcodes without instruction, never dispatched.
+    fn exec_invalid(
+        &mut self,
+        _instruction: Instruction<T, T::Address>,
+        _execution: &mut Execution<'_, T>,
+    ) -> VmResult<()> {
+        Err(VmError::InvalidInstruction)
+    }
+}
+
+impl<T: Word> Default for CPU<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert the value of a register to a number of bits to shift or rotate.
+/// Negative values and values too large for a `u32` shift all the bits out.
+fn shift_amount<T: Word>(value: T) -> u32 {
+    u32::try_from(value.to_usize()).unwrap_or(u32::MAX)
+}
+
+/// A view of the CPU given to the host functions called with `SYSCALL`.
+/// The view gives access to the registers and the status flags, the program counter is read-only.
+pub struct CpuView<'a, T> {
+    registers: &'a mut [T],
+    status_flags: &'a mut StatusFlags,
+    pc: usize,
+}
+
+impl<T: Word> CpuView<'_, T> {
+    /// Get the value of a register by index.
+    ///
+    /// # Errors
+    /// Returns an error if the register index is out of bounds.
+    pub fn register(&self, index: u8) -> VmResult<T> {
+        self.registers
+            .get(index as usize)
+            .copied()
+            .ok_or(VmError::InvalidRegister { register: index })
+    }
+
+    /// Set the value of a register by index.
+    ///
+    /// # Errors
+    /// Returns an error if the register index is out of bounds.
+    pub fn set_register(&mut self, index: u8, value: T) -> VmResult<()> {
+        let register = self
+            .registers
+            .get_mut(index as usize)
+            .ok_or(VmError::InvalidRegister { register: index })?;
+        *register = value;
+        Ok(())
+    }
+
+    /// Get the status flags of the CPU.
+    pub fn status_flags(&mut self) -> &mut StatusFlags {
+        self.status_flags
+    }
+
+    /// Get the program counter of the `SYSCALL` instruction.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+}
+
+/// The architectural state of a CPU, used to save and restore it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuState<T> {
+    /// The registers of the CPU.
+    pub registers: Vec<T>,
+    /// The bits of the float registers of the CPU, as returned by `f32::to_bits`,
+    /// so that the state can be compared and NaN values are restored exactly.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub float_registers: Vec<u32>,
+    /// The status flags of the CPU.
+    pub status_flags: StatusFlags,
+    /// The program counter (PC) of the CPU.
+    pub pc: usize,
+    /// The stack pointer (SP) of the CPU, `0` when the stack does not live in the memory.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sp: usize,
+    /// The frame pointer (FP) of the CPU, `0` when the stack does not live in the memory.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fp: usize,
+    /// The physical address of the page directory, `None` when the paging is disabled.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub page_directory: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusFlags {
+    pub zero: bool,
+    pub carry: bool,
+    pub overflow: bool,
+    pub negative: bool,
+    /// Deliver the pending interrupts, set by `EI` and cleared by `DI`.
+    /// ***Note:*** `CLF` does not change this flag.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub interrupt_enable: bool,
+}
+
+impl StatusFlags {
+    pub fn clear(&mut self) {
+        self.zero = false;
+        //self.carry = false;
+        self.overflow = false;
+        self.negative = false;
+    }
+
+    /// Pack the flags into a byte, as saved on the stack by the interrupts.
+    /// Bit 0 is the zero flag, then carry, overflow, negative and interrupt enable.
+    pub fn bits(&self) -> u8 {
+        self.zero as u8
+            | (self.carry as u8) << 1
+            | (self.overflow as u8) << 2
+            | (self.negative as u8) << 3
+            | (self.interrupt_enable as u8) << 4
+    }
+
+    /// Unpack flags packed by `bits`.
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            zero: bits & 1 != 0,
+            carry: bits & 1 << 1 != 0,
+            overflow: bits & 1 << 2 != 0,
+            negative: bits & 1 << 3 != 0,
+            interrupt_enable: bits & 1 << 4 != 0,
+        }
     }
 }
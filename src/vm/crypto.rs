@@ -0,0 +1,322 @@
+//! The checksum and hash syscalls of the VM, see `Crypto`.
+
+use super::cpu::CpuView;
+use super::error::{Result, VmError};
+use super::memory::Memory;
+use super::syscall::SyscallTable;
+use super::word::Word;
+
+/// The number of bytes of a SHA-256 digest.
+pub const SHA256_SIZE: usize = 32;
+
+/// The checksum and hash functions of the host, exposed as syscalls over a region of the memory of
+/// the guest, much faster than their implementation in the guest.
+///
+/// Every syscall takes the address of the region in `R0` and its length in `R1`:
+/// - `SYSCALL Crypto::CRC32`: Compute the CRC-32 (IEEE) of the region into `R0`, truncated to the
+///   word.
+/// - `SYSCALL Crypto::SHA256`: Compute the SHA-256 digest of the region and store its
+///   `SHA256_SIZE` bytes at the address in `R2`.
+///
+/// The region is read and the digest written byte by byte as by `LDB` and `STB`: through the
+/// devices, the watchpoints and the regions of the memory. A region out of the memory stops the
+/// execution with `VmError::MemoryOutOfBounds`, and a region the guest may not access with
+/// `VmError::ProtectionFault`.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::crypto::{self, Crypto};
+/// use forge_vm::VM;
+///
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// vm.register_crypto();
+/// let source = "MOV R0 text\nMOV R1 9\nSYSCALL 0xfb\nHLT\n.data\ntext: .ascii \"123456789\"";
+/// vm.run_program(&Assembler::new().assemble_program(source).unwrap())
+///     .unwrap();
+/// assert_eq!(vm.snapshot().cpu.registers[0] as u32, crypto::crc32(b"123456789"));
+/// assert_eq!(crypto::crc32(b"123456789"), 0xcbf4_3926);
+/// ```
+pub struct Crypto;
+
+impl Crypto {
+    /// The syscall number computing the CRC-32 of a region.
+    pub const CRC32: u8 = 0xfb;
+    /// The syscall number computing the SHA-256 digest of a region.
+    pub const SHA256: u8 = 0xfc;
+
+    /// Register the syscalls in a syscall table, replacing the functions registered under
+    /// the same numbers.
+    pub fn register<T: Word>(syscalls: &mut SyscallTable<T>) {
+        syscalls.register(
+            Self::CRC32,
+            Box::new(|cpu, memory| {
+                let checksum = crc32(&region(cpu, memory)?);
+                cpu.set_register(0, T::from_usize(checksum as usize))
+            }),
+        );
+        syscalls.register(
+            Self::SHA256,
+            Box::new(|cpu, memory| {
+                let digest = sha256(&region(cpu, memory)?);
+                let address = cpu.register(2)?.to_usize();
+                if address.saturating_add(SHA256_SIZE) > memory.capacity() {
+                    return Err(VmError::MemoryOutOfBounds {
+                        address,
+                        size: SHA256_SIZE,
+                    });
+                }
+                for (offset, byte) in digest.iter().enumerate() {
+                    memory.write::<u8>(address + offset, *byte)?;
+                }
+                Ok(())
+            }),
+        );
+    }
+}
+
+/// Read the region of a syscall, from the address in `R0` and the length in `R1`.
+fn region<T: Word>(cpu: &CpuView<T>, memory: &mut Memory) -> Result<Vec<u8>> {
+    let address = cpu.register(0)?.to_usize();
+    let size = cpu.register(1)?.to_usize();
+    if address.saturating_add(size) > memory.capacity() {
+        return Err(VmError::MemoryOutOfBounds { address, size });
+    }
+    (address..address + size)
+        .map(|address| memory.read::<u8>(address))
+        .collect()
+}
+
+/// The lookup table of the CRC-32, one entry per byte.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            // the reflected polynomial of the IEEE 802.3 CRC-32
+            value = if value & 1 == 1 {
+                (value >> 1) ^ 0xedb8_8320
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+};
+
+/// Compute the CRC-32 of bytes, the IEEE 802.3 checksum of zlib and PNG.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// The round constants of SHA-256.
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Compute the SHA-256 digest of bytes.
+pub fn sha256(bytes: &[u8]) -> [u8; SHA256_SIZE] {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    // the message is padded with a bit `1`, zeros and its length in bits to a multiple of 64 bytes
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    message.resize((message.len() + 8).next_multiple_of(64) - 8, 0);
+    message.extend_from_slice(&((bytes.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut schedule = [0u32; 64];
+        for (word, chunk) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for index in 16..64 {
+            let s0 = schedule[index - 15].rotate_right(7)
+                ^ schedule[index - 15].rotate_right(18)
+                ^ (schedule[index - 15] >> 3);
+            let s1 = schedule[index - 2].rotate_right(17)
+                ^ schedule[index - 2].rotate_right(19)
+                ^ (schedule[index - 2] >> 10);
+            schedule[index] = schedule[index - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[index - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for (k, w) in SHA256_K.iter().zip(schedule) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(*k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0u8; SHA256_SIZE];
+    for (chunk, value) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::vm::protection::{Permission, Permissions, Region};
+    use crate::VM;
+
+    /// Format a digest in hexadecimal.
+    fn hex(digest: &[u8]) -> String {
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_crypto_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+    }
+
+    #[test]
+    fn test_crypto_sha256() {
+        assert_eq!(
+            hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // the padding of 56 bytes does not fit in the block of the message
+        assert_eq!(
+            hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex(&sha256(&[b'a'; 64])),
+            "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"
+        );
+    }
+
+    #[test]
+    fn test_crypto_syscalls() {
+        // hash the text into the digest, then checksum the digest
+        let source = "
+            MOV R0 text
+            MOV R1 3
+            MOV R2 digest
+            SYSCALL 0xfc
+            MOV R0 digest
+            MOV R1 32
+            SYSCALL 0xfb
+            HLT
+        .data
+        text: .ascii \"abc\"
+        digest: .byte 0
+        ";
+        let program = Assembler::new().assemble_program(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_crypto();
+        vm.run_program(&program).unwrap();
+
+        let digest = program.data_address() + 3;
+        let memory = vm.snapshot().memory;
+        assert_eq!(memory[digest..digest + SHA256_SIZE], sha256(b"abc"));
+        assert_eq!(
+            vm.snapshot().cpu.registers[0] as u32,
+            crc32(&sha256(b"abc"))
+        );
+
+        // a region out of the memory stops the execution
+        let program = Assembler::new()
+            .assemble("MOV R0 1000\nMOV R1 100\nSYSCALL 0xfb\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::MemoryOutOfBounds {
+                address: 1000,
+                size: 100
+            })
+        );
+    }
+
+    #[test]
+    fn test_crypto_syscalls_protection() {
+        // the digest cannot be written into the read-only code, nor the text read without access
+        let mut vm = crate::VmBuilder::new()
+            .memory_size(0x200)
+            .region(Region::new(0x000..0x100, Permissions::READ))
+            .region(Region::new(0x100..0x180, Permissions::READ_WRITE))
+            .region(Region::new(0x180..0x200, Permissions::NONE))
+            .build::<i32>();
+        vm.register_crypto();
+        let program = Assembler::new()
+            .assemble("MOV R0 0x100\nMOV R1 3\nMOV R2 0x20\nSYSCALL 0xfc\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::ProtectionFault {
+                address: 0x20,
+                size: 1,
+                permission: Permission::Write
+            })
+        );
+        assert_eq!(vm.memory().peek::<u8>(0x20), Ok(0));
+
+        let program = Assembler::new()
+            .assemble("MOV R0 0x17f\nMOV R1 2\nSYSCALL 0xfb\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::ProtectionFault {
+                address: 0x180,
+                size: 1,
+                permission: Permission::Read
+            })
+        );
+
+        let program = Assembler::new()
+            .assemble("MOV R0 0x100\nMOV R1 3\nMOV R2 0x140\nSYSCALL 0xfc\nHLT")
+            .unwrap();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.memory().peek::<u8>(0x140), Ok(sha256(&[0; 3])[0]));
+    }
+}
@@ -0,0 +1,154 @@
+//! The cache of the decoded instructions of the program run by the VM.
+
+use super::cpu::Fused;
+use super::instructions::Instruction;
+use super::word::Word;
+
+/// The instructions decoded by the VM, indexed by their address, so that the instructions of a
+/// loop are decoded once. The cache is cleared when a program is loaded, and in the Von Neumann
+/// mode when the program writes into its code.
+///
+/// An instruction is cached with the superinstruction it forms with the next instruction, if
+/// any, see `CPU::fuse`.
+pub(crate) struct DecodeCache<T: Word> {
+    entries: Vec<Option<Entry<T>>>,
+}
+
+/// An instruction decoded by the VM, with the superinstruction it starts.
+#[derive(Clone, Copy)]
+pub(crate) struct Entry<T: Word> {
+    pub(crate) instruction: Instruction<T, T::Address>,
+    pub(crate) fused: Option<Fused<T>>,
+}
+
+impl<T: Word> DecodeCache<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Get the instruction decoded at an address, if any.
+    pub(crate) fn get(&self, address: usize) -> Option<Entry<T>> {
+        self.entries.get(address).copied().flatten()
+    }
+
+    /// Add the instruction decoded at an address.
+    pub(crate) fn insert(&mut self, address: usize, entry: Entry<T>) {
+        if address >= self.entries.len() {
+            self.entries.resize(address + 1, None);
+        }
+        self.entries[address] = Some(entry);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::Assembler;
+    use crate::vm::builder::VmBuilder;
+    use crate::vm::error::VmError;
+    use crate::vm::program::Program;
+
+    #[test]
+    fn test_decode_cache_self_modifying_code() {
+        // the loop runs twice, the first iteration patches the immediate of the MOV to R1
+        let source = "
+                MOV R3 2
+            loop:
+            patch:
+                MOV R1 5
+                ADD R2 R2 R1
+                MOV R0 patch
+                MOV R1 2
+                ADD R0 R0 R1
+                MOV R1 7
+                STR R1 [R0]
+                DEC R3
+                JMPNZ loop
+                HLT
+        ";
+        let program = Assembler::new().origin(0x100).assemble(source).unwrap();
+        let mut vm = VmBuilder::new()
+            .memory_size(1024)
+            .strict_alignment(false)
+            .von_neumann(0x100)
+            .build::<i32>();
+        assert_eq!(
+            vm.run(&program).map(|termination| termination.steps),
+            Ok(20)
+        );
+        assert_eq!(vm.snapshot().cpu.registers[2], 5 + 7);
+        // the patched code is loaded again and decoded at the next run
+        assert_eq!(
+            vm.run(&program).map(|termination| termination.steps),
+            Ok(20)
+        );
+        assert_eq!(vm.snapshot().cpu.registers[2], 5 + 7);
+
+        // the cached instructions of a program are replaced by the next program
+        let mut vm = VmBuilder::new().build::<i32>();
+        let first = Assembler::new().assemble("MOV R0 1\nHLT").unwrap();
+        let second = Assembler::new().assemble("MOV R0 2\nHLT").unwrap();
+        assert_eq!(vm.run(&first).map(|termination| termination.steps), Ok(2));
+        assert_eq!(vm.run(&second).map(|termination| termination.steps), Ok(2));
+        assert_eq!(vm.snapshot().cpu.registers[0], 2);
+    }
+
+    #[test]
+    fn test_decode_cache_fused_instructions() {
+        // LD + ADD, CMP + JMPLT and DEC + JMPNZ are fused
+        let program = Assembler::new()
+            .assemble(
+                "
+                    MOV R0 50
+                    MOV R2 3
+                    ST R2 0x20
+                loop:
+                    LD R1 0x20
+                    ADD R3 R3 R1
+                    CMP R3 R2
+                    JMPLT skip
+                    INC R2
+                skip:
+                    DEC R0
+                    JMPNZ loop
+                    HLT
+                ",
+            )
+            .unwrap();
+        let mut stepped = VmBuilder::new().memory_size(64).build::<i32>();
+        stepped.load_program(&Program::new(&program)).unwrap();
+        while !stepped.step().unwrap() {}
+
+        let mut vm = VmBuilder::new().memory_size(64).build::<i32>();
+        let steps = vm.run(&program).unwrap().steps;
+        assert_eq!(steps, stepped.counters().steps);
+        assert_eq!(vm.cycles(), stepped.cycles());
+        assert_eq!(vm.snapshot(), stepped.snapshot());
+        // the fused instructions never overshoot a limit, even between them
+        for limit in 1..steps {
+            assert_eq!(
+                vm.run_with_limit(&program, limit)
+                    .map_err(|error| error.source),
+                Err(VmError::StepLimitExceeded)
+            );
+            assert_eq!(vm.counters().steps, limit + 1);
+        }
+
+        // the fault of the first instruction leaves the second one unexecuted
+        let program = Assembler::new()
+            .assemble("MOV R1 1\nLD R0 0x100\nADD R1 R1 R1\nHLT")
+            .unwrap();
+        assert!(matches!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::MemoryOutOfBounds { .. })
+        ));
+        assert_eq!(vm.pc(), 6);
+        assert_eq!(vm.counters().steps, 2);
+        assert_eq!(vm.snapshot().cpu.registers[1], 1);
+    }
+}
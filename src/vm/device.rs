@@ -0,0 +1,114 @@
+use std::any::Any;
+use std::ops::Range;
+
+use super::error::{Result, VmError};
+
+/// A memory-mapped I/O device.
+/// A device is mapped into a range of addresses of the memory, the loads and stores
+/// to this range are routed to the device instead of the memory.
+/// The addresses given to the device are offsets from the start of its range.
+/// Mapped devices can be retrieved by their concrete type with `Memory::device`.
+pub trait Device: Any {
+    /// Read `size` bytes at `offset` in the device, little-endian.
+    /// Missing bytes are read as zero and extra bytes are ignored.
+    ///
+    /// # Parameters
+    /// - `offset`: The offset of the first byte from the start of the device range.
+    /// - `size`: The number of bytes to read.
+    ///
+    /// # Errors
+    /// Returns an error to abort the access, the error stops the execution.
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>>;
+
+    /// Write bytes at `offset` in the device, little-endian.
+    ///
+    /// # Parameters
+    /// - `offset`: The offset of the first byte from the start of the device range.
+    /// - `data`: The bytes to write.
+    ///
+    /// # Errors
+    /// Returns an error to abort the access, the error stops the execution.
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()>;
+
+    /// Advance the device by one step of the VM, does nothing by default.
+    /// The VM ticks the devices after each executed instruction.
+    fn tick(&mut self) {}
+
+    /// Check if the device requests an interrupt, `false` by default.
+    /// The request is level-triggered: it is delivered again after the handler returns
+    /// until the device is serviced. The device must be mapped with an interrupt line.
+    fn interrupt(&self) -> bool {
+        false
+    }
+
+    /// Check if the device raises a fault after a step, `None` by default.
+    /// The fault stops the execution, as a fault of the instruction without guest handler.
+    fn fault(&self) -> Option<VmError> {
+        None
+    }
+
+    /// Reset the device before a program runs, does nothing by default.
+    fn reset(&mut self) {}
+}
+
+/// A device mapped into a range of addresses.
+pub(crate) struct MappedDevice {
+    pub(crate) range: Range<usize>,
+    pub(crate) device: Box<dyn Device>,
+    /// The interrupt line of the device, if it can request interrupts.
+    pub(crate) interrupt: Option<u8>,
+}
+
+impl MappedDevice {
+    /// Check if an access of `size` bytes at `address` overlaps the device range.
+    pub(crate) fn overlaps(&self, address: usize, size: usize) -> bool {
+        address < self.range.end && self.range.start < address.saturating_add(size)
+    }
+
+    /// Check if an access of `size` bytes at `address` is entirely within the device range.
+    pub(crate) fn contains(&self, address: usize, size: usize) -> bool {
+        self.range.start <= address && address.saturating_add(size) <= self.range.end
+    }
+}
+
+impl std::fmt::Debug for MappedDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MappedDevice")
+            .field("range", &self.range)
+            .field("interrupt", &self.interrupt)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NullDevice;
+
+    impl Device for NullDevice {
+        fn read(&mut self, _offset: usize, size: usize) -> Result<Vec<u8>> {
+            Ok(vec![0; size])
+        }
+
+        fn write(&mut self, _offset: usize, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mapped_device_bounds() {
+        let mapped = MappedDevice {
+            range: 16..24,
+            device: Box::new(NullDevice),
+            interrupt: None,
+        };
+        assert!(mapped.contains(16, 8));
+        assert!(mapped.contains(20, 4));
+        assert!(!mapped.contains(20, 8));
+        assert!(mapped.overlaps(12, 8));
+        assert!(mapped.overlaps(23, 1));
+        assert!(!mapped.overlaps(12, 4));
+        assert!(!mapped.overlaps(24, 4));
+    }
+}
@@ -0,0 +1,180 @@
+use super::debug_info::DebugInfo;
+use super::decoder::Decoder;
+use super::error::Result as VmResult;
+use super::instructions::Instruction;
+use super::program::Program;
+
+/// Disassembler converting bytecode back to instructions.
+/// It decodes the program from its first byte to its last one with the `Decoder` used by the VM,
+/// so any bytecode accepted by the disassembler is decoded the same way at runtime.
+pub struct Disassembler {
+    decoder: Decoder<i32>,
+}
+
+/// Implementation of the Disassembler for the 32-bit architecture
+impl Disassembler {
+    pub fn new() -> Self {
+        Self {
+            decoder: Decoder::new(),
+        }
+    }
+
+    /// Decode every instruction of a program.
+    ///
+    /// # Parameters
+    /// - `program`: The program to disassemble.
+    ///
+    /// # Returns
+    /// The instructions of the program with their offset in bytes.
+    ///
+    /// # Errors
+    /// Returns an error if the program contains an invalid or truncated instruction.
+    pub fn disassemble(&self, program: &Program) -> VmResult<Vec<(usize, Instruction<i32, u32>)>> {
+        let mut instructions = Vec::new();
+        let mut offset = 0;
+        while offset < program.size() {
+            let instruction = self.decoder.decode_next_instruction(program, offset)?;
+            instructions.push((offset, instruction));
+            offset += instruction.size();
+        }
+        Ok(instructions)
+    }
+
+    /// Produce a textual listing of a program, one instruction per line.
+    /// Each line holds the offset in hexadecimal followed by the instruction.
+    ///
+    /// # Parameters
+    /// - `program`: The program to disassemble.
+    ///
+    /// # Errors
+    /// Returns an error if the program contains an invalid or truncated instruction.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::disassembler::Disassembler;
+    /// use forge_vm::vm::program::Program;
+    /// let program = Program::new(&[0x0e, 0x01, 0xff]); // INC R1, HLT
+    /// let listing = Disassembler::new().listing(&program).unwrap();
+    /// assert_eq!(listing, "0x00000000: INC R1\n0x00000002: HLT\n");
+    /// ```
+    pub fn listing(&self, program: &Program) -> VmResult<String> {
+        let listing = self
+            .disassemble(program)?
+            .iter()
+            .map(|(offset, instruction)| format!("0x{:08x}: {}\n", offset, instruction))
+            .collect();
+        Ok(listing)
+    }
+
+    /// Produce a textual listing of a program annotated with its debug information.
+    /// The symbols are written as labels before their instruction, and each instruction is
+    /// followed by its source line as a comment.
+    ///
+    /// # Parameters
+    /// - `program`: The program to disassemble.
+    /// - `debug_info`: The debug information of the program.
+    /// - `origin`: The address where the program is loaded, added to the offsets to match the
+    ///   addresses of the debug information.
+    ///
+    /// # Errors
+    /// Returns an error if the program contains an invalid or truncated instruction.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::vm::disassembler::Disassembler;
+    /// let (program, debug_info) = Assembler::new()
+    ///     .assemble_with_debug_info("main:\n  INC R1\n  HLT")
+    ///     .unwrap();
+    /// let listing = Disassembler::new().annotated_listing(&program, &debug_info, 0);
+    /// assert_eq!(
+    ///     listing.unwrap(),
+    ///     "main:\n0x00000000: INC R1 ; line 2\n0x00000002: HLT ; line 3\n"
+    /// );
+    /// ```
+    pub fn annotated_listing(
+        &self,
+        program: &Program,
+        debug_info: &DebugInfo,
+        origin: usize,
+    ) -> VmResult<String> {
+        let mut listing = String::new();
+        for (offset, instruction) in self.disassemble(program)? {
+            let address = origin + offset;
+            for symbol in debug_info.symbols_at(address) {
+                listing.push_str(&format!("{}:\n", symbol));
+            }
+            listing.push_str(&format!("0x{:08x}: {}", address, instruction));
+            if let Some(line) = debug_info.line_at(address) {
+                listing.push_str(&format!(" ; line {}", line));
+            }
+            listing.push('\n');
+        }
+        Ok(listing)
+    }
+}
+
+impl Default for Disassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::error::VmError;
+
+    #[test]
+    fn test_disassemble() {
+        let program = Program::new(&[
+            0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x01, 0xff,
+        ]); // MOV 0 2, ADD 0 0 1, HLT
+        assert_eq!(
+            Disassembler::new().disassemble(&program),
+            Ok(vec![
+                (0, Instruction::MOV { dest: 0, value: 2 }),
+                (
+                    6,
+                    Instruction::ADD {
+                        dest: 0,
+                        reg1: 0,
+                        reg2: 1
+                    }
+                ),
+                (10, Instruction::HLT),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_listing() {
+        let program = Program::new(&[0x12, 0x10, 0x00, 0x00, 0x00, 0x00, 0xff]); // JMP 0x10, NOP, HLT
+        assert_eq!(
+            Disassembler::new().listing(&program),
+            Ok("0x00000000: JMP 0x10\n0x00000005: NOP\n0x00000006: HLT\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_disassemble_truncated() {
+        let program = Program::new(&[0x00, 0x12, 0x10]); // NOP, truncated JMP
+        assert_eq!(
+            Disassembler::new().disassemble(&program),
+            Err(VmError::InvalidInstruction)
+        );
+    }
+
+    #[test]
+    fn test_annotated_listing() {
+        let mut debug_info = DebugInfo::new();
+        debug_info.add_symbol("start", 0x100);
+        debug_info.add_symbol("end", 0x102);
+        debug_info.add_line(0x100, 4);
+        let program = Program::new(&[0x0e, 0x01, 0xff]); // INC R1, HLT
+        assert_eq!(
+            Disassembler::new().annotated_listing(&program, &debug_info, 0x100),
+            Ok("start:\n0x00000100: INC R1 ; line 4\nend:\n0x00000102: HLT ; line 4\n".to_string())
+        );
+    }
+}
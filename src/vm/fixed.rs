@@ -0,0 +1,138 @@
+//! The fixed-point arithmetic of the `FXMUL` and `FXDIV` instructions.
+//!
+//! A fixed-point value is a word whose lower half holds the fraction: Q16.16 for `VM<i32>`,
+//! Q8.8 for `VM<i16>`, Q32.32 for `VM<i64>`. The integer `n` is `n << (T::SIZE * 4)`.
+//! The results are exact integer computations rounded by a `Rounding` mode, so they are
+//! deterministic on all the hosts.
+
+use super::word::Word;
+
+/// The rounding of the fixed-point results which are between two representable values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rounding {
+    /// Round toward zero, like `DIV`.
+    #[default]
+    TowardZero,
+    /// Round toward negative infinity, like an arithmetic shift.
+    Floor,
+    /// Round to the nearest value, the ties away from zero.
+    Nearest,
+}
+
+impl Rounding {
+    /// Divide two integers with the rounding mode, `divisor` is not zero.
+    fn divide(self, dividend: i128, divisor: i128) -> i128 {
+        let quotient = dividend / divisor;
+        let remainder = dividend % divisor;
+        if remainder == 0 {
+            return quotient;
+        }
+        // the exact quotient is negative when the operands have opposite signs
+        let negative = (dividend < 0) != (divisor < 0);
+        match self {
+            Rounding::TowardZero => quotient,
+            Rounding::Floor if negative => quotient - 1,
+            Rounding::Floor => quotient,
+            Rounding::Nearest if 2 * remainder.abs() < divisor.abs() => quotient,
+            Rounding::Nearest if negative => quotient - 1,
+            Rounding::Nearest => quotient + 1,
+        }
+    }
+}
+
+/// Get the number of fraction bits of a word, the lower half.
+pub fn fraction_bits<T: Word>() -> u32 {
+    T::SIZE as u32 * 4
+}
+
+/// Multiply two fixed-point values.
+///
+/// # Returns
+/// The truncated product and whether it overflows the word.
+pub fn multiply<T: Word>(value1: T, value2: T, rounding: Rounding) -> (T, bool) {
+    let (product, wrapped) = value1.to_i128().overflowing_mul(value2.to_i128());
+    if wrapped {
+        // only the products of two large `u64` wrap, the bits of the result are kept
+        return (
+            T::overflowing_from_i128(product >> fraction_bits::<T>()).0,
+            true,
+        );
+    }
+    T::overflowing_from_i128(rounding.divide(product, 1 << fraction_bits::<T>()))
+}
+
+/// Divide two fixed-point values.
+///
+/// # Returns
+/// The truncated quotient and whether it overflows the word, or `None` if `value2` is zero.
+pub fn divide<T: Word>(value1: T, value2: T, rounding: Rounding) -> Option<(T, bool)> {
+    let divisor = value2.to_i128();
+    (divisor != 0).then(|| {
+        let dividend = value1.to_i128() << fraction_bits::<T>();
+        T::overflowing_from_i128(rounding.divide(dividend, divisor))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Convert a float to a Q16.16 value.
+    fn q16(value: f64) -> i32 {
+        (value * 65536.0) as i32
+    }
+
+    #[test]
+    fn test_fixed_multiply() {
+        assert_eq!(
+            multiply(q16(1.5), q16(-2.25), Rounding::TowardZero),
+            (q16(-3.375), false)
+        );
+        // 3 units of the last place by 0.5 is a tie between 1 and 2 units
+        for (rounding, positive, negative) in [
+            (Rounding::TowardZero, 1, -1),
+            (Rounding::Floor, 1, -2),
+            (Rounding::Nearest, 2, -2),
+        ] {
+            assert_eq!(multiply(3, q16(0.5), rounding), (positive, false));
+            assert_eq!(multiply(-3, q16(0.5), rounding), (negative, false));
+        }
+        assert!(multiply(q16(200.0), q16(200.0), Rounding::Nearest).1);
+        assert_eq!(
+            multiply(0x0180u16, 0x0200, Rounding::Floor),
+            (0x0300, false)
+        );
+        assert!(multiply(u64::MAX, u64::MAX, Rounding::Floor).1);
+    }
+
+    #[test]
+    fn test_fixed_divide() {
+        assert_eq!(
+            divide(q16(1.0), q16(4.0), Rounding::TowardZero),
+            Some((q16(0.25), false))
+        );
+        assert_eq!(divide(q16(1.0), 0, Rounding::Nearest), None);
+        // 1 / 3 is 0x5555.55... units of the last place
+        assert_eq!(
+            divide(q16(1.0), q16(3.0), Rounding::Nearest),
+            Some((0x5555, false))
+        );
+        assert_eq!(
+            divide(q16(2.0), q16(3.0), Rounding::TowardZero),
+            Some((0xaaaa, false))
+        );
+        assert_eq!(
+            divide(q16(2.0), q16(3.0), Rounding::Nearest),
+            Some((0xaaab, false))
+        );
+        assert_eq!(
+            divide(q16(-2.0), q16(3.0), Rounding::Floor),
+            Some((-0xaaab, false))
+        );
+        assert_eq!(
+            divide(i32::MAX, 1, Rounding::Floor).map(|(_, overflow)| overflow),
+            Some(true)
+        );
+    }
+}
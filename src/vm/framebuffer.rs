@@ -0,0 +1,240 @@
+use super::device::Device;
+use super::error::{Result, VmError};
+
+/// A frame presented by the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// The number of the frame, from `0`.
+    pub number: u64,
+    /// The number of pixels per row.
+    pub width: usize,
+    /// The number of rows.
+    pub height: usize,
+    /// The pixels, row by row, `0xAARRGGBB` by convention.
+    pub pixels: &'a [u32],
+}
+
+/// A host callback receiving the frames presented by the guest.
+pub type PresentFn = Box<dyn FnMut(&Frame<'_>)>;
+
+/// A linear framebuffer device of 32-bit pixels.
+/// The device range starts with the pixels, row by row, each pixel a little-endian `u32`
+/// accessed with any size and alignment, followed by the present register of 8 bytes at offset
+/// `FramebufferDevice::present_offset`:
+/// - A write presents the frame to the host callback.
+/// - A read returns the number of frames presented.
+///
+/// The pixels are cleared before each run.
+/// Use `FramebufferDevice::size` as the length of the mapped range.
+///
+/// # Example:
+/// ```
+/// use std::sync::mpsc;
+///
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::framebuffer::FramebufferDevice;
+/// use forge_vm::VM;
+///
+/// let (sender, receiver) = mpsc::channel();
+/// let framebuffer = FramebufferDevice::new(2, 2)
+///     .with_present(move |frame| sender.send(frame.pixels.to_vec()).unwrap());
+/// let size = framebuffer.size();
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// vm.map_device(0x1000..0x1000 + size, framebuffer).unwrap();
+/// // a red pixel at (1, 0), then present the frame
+/// let source = "MOV R0 0xff0000\nST R0 0x1004\nST R0 0x1010\nHLT";
+/// vm.run(&Assembler::new().assemble(source).unwrap()).unwrap();
+/// assert_eq!(receiver.recv(), Ok(vec![0, 0xff0000, 0, 0]));
+/// ```
+pub struct FramebufferDevice {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+    frames: u64,
+    present: Option<PresentFn>,
+}
+
+impl FramebufferDevice {
+    /// The number of bytes of the present register.
+    pub const PRESENT_SIZE: usize = 8;
+
+    /// Create a black framebuffer without presentation callback.
+    ///
+    /// # Parameters
+    /// - `width`: The number of pixels per row.
+    /// - `height`: The number of rows.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            frames: 0,
+            present: None,
+        }
+    }
+
+    /// Set the host callback called with the frame each time the guest presents it.
+    pub fn with_present<F: FnMut(&Frame<'_>) + 'static>(mut self, present: F) -> Self {
+        self.present = Some(Box::new(present));
+        self
+    }
+
+    /// Get the number of bytes of the device range: the pixels and the present register.
+    pub fn size(&self) -> usize {
+        self.present_offset() + Self::PRESENT_SIZE
+    }
+
+    /// Get the offset of the present register, after the pixels.
+    pub fn present_offset(&self) -> usize {
+        self.pixels.len() * 4
+    }
+
+    /// Get a snapshot of the current content of the framebuffer.
+    pub fn frame(&self) -> Frame<'_> {
+        Frame {
+            number: self.frames,
+            width: self.width,
+            height: self.height,
+            pixels: &self.pixels,
+        }
+    }
+
+    /// Get the pixel at a position, or `None` outside of the framebuffer.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.pixels[y * self.width + x])
+    }
+
+    /// Check the access targets the pixels or the present register.
+    fn check_access(&self, offset: usize, size: usize) -> Result<()> {
+        let pixels = offset.saturating_add(size) <= self.present_offset();
+        let present = offset == self.present_offset() && size <= Self::PRESENT_SIZE;
+        if !pixels && !present {
+            return Err(VmError::MemoryOutOfBounds {
+                address: offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Device for FramebufferDevice {
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        self.check_access(offset, size)?;
+        if offset == self.present_offset() {
+            return Ok(self.frames.to_le_bytes()[..size].to_vec());
+        }
+        Ok((offset..offset + size)
+            .map(|byte| self.pixels[byte / 4].to_le_bytes()[byte % 4])
+            .collect())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        self.check_access(offset, data.len())?;
+        if offset == self.present_offset() {
+            if let Some(present) = &mut self.present {
+                present(&Frame {
+                    number: self.frames,
+                    width: self.width,
+                    height: self.height,
+                    pixels: &self.pixels,
+                });
+            }
+            self.frames += 1;
+            return Ok(());
+        }
+        for (byte, value) in (offset..).zip(data) {
+            let mut bytes = self.pixels[byte / 4].to_le_bytes();
+            bytes[byte % 4] = *value;
+            self.pixels[byte / 4] = u32::from_le_bytes(bytes);
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.pixels.fill(0);
+        self.frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_framebuffer_pixels() {
+        let mut framebuffer = FramebufferDevice::new(3, 2);
+        assert_eq!(framebuffer.size(), 32);
+        framebuffer.write(4, &[0x33, 0x22, 0x11, 0x00]).unwrap();
+        framebuffer.write(22, &[0xff]).unwrap();
+        assert_eq!(framebuffer.pixel(1, 0), Some(0x0011_2233));
+        assert_eq!(framebuffer.pixel(2, 1), Some(0x00ff_0000));
+        assert_eq!(framebuffer.pixel(3, 0), None);
+        assert_eq!(framebuffer.read(5, 2), Ok(vec![0x22, 0x11]));
+        assert_eq!(
+            framebuffer.read(22, 4),
+            Err(VmError::MemoryOutOfBounds {
+                address: 22,
+                size: 4
+            })
+        );
+
+        framebuffer.write(24, &[1]).unwrap();
+        assert_eq!(framebuffer.frame().number, 1);
+        assert_eq!(framebuffer.read(24, 8), Ok(1u64.to_le_bytes().to_vec()));
+        framebuffer.reset();
+        assert_eq!(framebuffer.frame().number, 0);
+        assert!(framebuffer.frame().pixels.iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    fn test_framebuffer_present() {
+        // draw the diagonal of a 4x4 framebuffer, presenting a frame per pixel
+        let source = "
+                MOV R0 0x1000
+                MOV R1 0xffffff
+                MOV R2 20
+                MOV R3 0x1050
+            loop:
+                STR R1 [R0]
+                ST R1 0x1040
+                ADD R0 R0 R2
+                CMP R0 R3
+                JMPNZ loop
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let presented = Rc::clone(&frames);
+        let framebuffer = FramebufferDevice::new(4, 4).with_present(move |frame| {
+            presented
+                .borrow_mut()
+                .push((frame.number, frame.pixels.to_vec()))
+        });
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.map_device(0x1000..0x1000 + framebuffer.size(), framebuffer)
+            .unwrap();
+        vm.run(&program).unwrap();
+
+        let frames = frames.borrow();
+        assert_eq!(frames.len(), 4);
+        for (number, (presented, pixels)) in frames.iter().enumerate() {
+            assert_eq!(*presented, number as u64);
+            let lit = (0..16)
+                .filter(|index| pixels[*index] != 0)
+                .collect::<Vec<_>>();
+            assert_eq!(lit, (0..=number).map(|pixel| pixel * 5).collect::<Vec<_>>());
+        }
+        let framebuffer = vm.device::<FramebufferDevice>(0x1000).unwrap();
+        assert_eq!(framebuffer.frame().number, 4);
+        assert_eq!(framebuffer.pixel(3, 3), Some(0xffffff));
+    }
+}
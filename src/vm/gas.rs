@@ -0,0 +1,99 @@
+//! The gas accounting of untrusted programs, see `VM::run_with_gas`.
+
+use super::cost::CostModel;
+
+/// The gas charged for the resources used by a program.
+///
+/// The gas of an instruction is charged before it is executed, and the gas of its memory
+/// accesses and stack operations after it is executed. With the stack in the memory, a push or
+/// a pop is also a memory access. Every charge is one gas by default.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::cost::CostModel;
+/// use forge_vm::vm::gas::GasSchedule;
+/// use forge_vm::vm::instructions::OpCode;
+///
+/// let schedule = GasSchedule {
+///     instructions: CostModel::new().with_cost(OpCode::DIV, 5),
+///     memory_write: 10,
+///     ..GasSchedule::default()
+/// };
+/// assert_eq!(schedule.memory_read, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasSchedule {
+    /// The gas of each instruction, by opcode.
+    pub instructions: CostModel,
+    /// The gas of a memory read, of any size.
+    pub memory_read: u64,
+    /// The gas of a memory write, of any size.
+    pub memory_write: u64,
+    /// The gas of a push on the stack.
+    pub stack_push: u64,
+    /// The gas of a pop from the stack.
+    pub stack_pop: u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            instructions: CostModel::new(),
+            memory_read: 1,
+            memory_write: 1,
+            stack_push: 1,
+            stack_pop: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::vm::builder::VmBuilder;
+    use crate::vm::error::VmError;
+    use crate::vm::instructions::OpCode;
+
+    #[test]
+    fn test_gas_charges() {
+        let program = Assembler::new()
+            .assemble("ST R0 0x10\nLD R1 0x10\nPUSHREG R1\nPOPREG R2\nHLT")
+            .unwrap();
+        let schedule = GasSchedule {
+            instructions: CostModel::uniform(2).with_cost(OpCode::HLT, 0),
+            memory_read: 10,
+            memory_write: 20,
+            stack_push: 100,
+            stack_pop: 200,
+        };
+        let mut vm = VmBuilder::new().gas_schedule(schedule).build::<i32>();
+        assert_eq!(
+            vm.run_with_gas(&program, 1000),
+            Ok(4 * 2 + 10 + 20 + 100 + 200)
+        );
+        assert_eq!(vm.gas_used(), 338);
+        assert_eq!(
+            vm.run_with_gas(&program, 337).map_err(|error| error.source),
+            Err(VmError::OutOfGas)
+        );
+        assert_eq!(
+            vm.run_with_gas(&program, 100).map_err(|error| error.source),
+            Err(VmError::OutOfGas)
+        );
+        // the push exceeded the gas after it was executed
+        assert_eq!(vm.snapshot().stack, [0]);
+        assert_eq!(vm.gas_used(), 136);
+    }
+
+    #[test]
+    fn test_gas_memory_stack() {
+        // CALL and RET push and pop the return address with a memory access each
+        let program = Assembler::new().assemble("CALL f\nHLT\nf: RET").unwrap();
+        let mut vm = VmBuilder::new().memory_stack(true).build::<i32>();
+        assert_eq!(vm.run_with_gas(&program, 100), Ok(3 + 3 + 1));
+        // the gas is counted without limit by the other runs
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.gas_used(), 7);
+    }
+}
@@ -1,2 +0,0 @@
-/// The number of registers in the VM.
-pub const REGISTERS_COUNT: u8 = 4;
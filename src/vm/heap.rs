@@ -0,0 +1,363 @@
+//! The heap allocator of the guests, see `Heap`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::error::{Result, VmError};
+use super::memory::Memory;
+use super::syscall::SyscallTable;
+use super::word::Word;
+
+/// The allocations of a heap, managed by the host.
+#[derive(Debug)]
+struct Allocator {
+    region: Range<usize>,
+    limit: usize,
+    max_allocations: usize,
+    /// The size of the live allocations, indexed by address.
+    allocations: BTreeMap<usize, usize>,
+    used: usize,
+}
+
+impl Allocator {
+    /// Find the first free block of `size` bytes, aligned to `Heap::ALIGN`.
+    fn find(&self, size: usize) -> Option<usize> {
+        let mut start = self.region.start;
+        for (&address, &length) in &self.allocations {
+            if address - start >= size {
+                break;
+            }
+            start = address + length;
+        }
+        (start.checked_add(size)? <= self.region.end).then_some(start)
+    }
+
+    fn allocate(&mut self, size: usize) -> Option<usize> {
+        let size = Heap::block_size(size)?;
+        if self.used.checked_add(size)? > self.limit
+            || self.allocations.len() >= self.max_allocations
+        {
+            return None;
+        }
+        let address = self.find(size)?;
+        self.allocations.insert(address, size);
+        self.used += size;
+        Some(address)
+    }
+
+    fn free(&mut self, address: usize) -> Result<()> {
+        let size = self
+            .allocations
+            .remove(&address)
+            .ok_or(VmError::InvalidHeapAddress { address })?;
+        self.used -= size;
+        Ok(())
+    }
+
+    fn reallocate(
+        &mut self,
+        memory: &mut Memory,
+        address: usize,
+        size: usize,
+    ) -> Result<Option<usize>> {
+        let old = *self
+            .allocations
+            .get(&address)
+            .ok_or(VmError::InvalidHeapAddress { address })?;
+        let Some(size) = Heap::block_size(size) else {
+            return Ok(None);
+        };
+        if self.used - old + size > self.limit {
+            return Ok(None);
+        }
+        // resize in place when the block is followed by enough free space
+        let next = self
+            .allocations
+            .range(address + 1..)
+            .next()
+            .map_or(self.region.end, |(next, _)| *next);
+        if address + size <= next {
+            self.allocations.insert(address, size);
+            self.used = self.used - old + size;
+            return Ok(Some(address));
+        }
+        let Some(moved) = self.allocate(size) else {
+            return Ok(None);
+        };
+        let bytes = memory
+            .bytes()
+            .get(address..address + old)
+            .ok_or(VmError::MemoryOutOfBounds { address, size: old })?
+            .to_vec();
+        memory.write_bytes(moved, &bytes)?;
+        self.free(address)?;
+        Ok(Some(moved))
+    }
+}
+
+/// A heap in a region of the memory of the guest, managed by the host and exposed as syscalls.
+///
+/// The syscalls return the address `0` in `R0` when the allocation fails, the heap never
+/// allocates at address `0`:
+/// - `SYSCALL Heap::ALLOC`: Allocate `R0` bytes and return their address.
+/// - `SYSCALL Heap::FREE`: Free the allocation at `R0`, nothing for the address `0`.
+/// - `SYSCALL Heap::REALLOC`: Resize the allocation at `R0` to `R1` bytes and return its new
+///   address, the content is kept up to the smallest size. The allocation is unchanged when the
+///   reallocation fails, and the address `0` allocates a new block.
+///
+/// Freeing or reallocating an address without a live allocation stops the execution with
+/// `VmError::InvalidHeapAddress`.
+/// The allocations are aligned to `Heap::ALIGN` bytes and kept across runs, a heap is shared by
+/// its clones for the host to inspect it.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::heap::Heap;
+/// use forge_vm::VM;
+///
+/// let heap = Heap::new(0x200..0x400).with_limit(256);
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// vm.register_heap(heap.clone());
+/// let source = "MOV R0 100\nSYSCALL 0xf8\nMOVR R1 R0\nMOV R0 200\nSYSCALL 0xf8\nHLT";
+/// vm.run(&Assembler::new().assemble(source).unwrap()).unwrap();
+/// // the second allocation exceeds the limit of the heap
+/// assert_eq!(vm.snapshot().cpu.registers[..2], [0, 0x200]);
+/// assert_eq!(heap.used(), 104);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Heap {
+    allocator: Rc<RefCell<Allocator>>,
+}
+
+impl Heap {
+    /// The syscall number allocating a block.
+    pub const ALLOC: u8 = 0xf8;
+    /// The syscall number freeing a block.
+    pub const FREE: u8 = 0xf9;
+    /// The syscall number resizing a block.
+    pub const REALLOC: u8 = 0xfa;
+    /// The alignment of the allocations, and the granularity of their sizes.
+    pub const ALIGN: usize = 8;
+
+    /// Create a heap allocating in a region of the memory, `MemoryLayout::heap` for a VM built
+    /// with a layout. The region is shrunk to the aligned addresses, excluding `0`.
+    pub fn new(region: Range<usize>) -> Self {
+        let start = region.start.max(1).next_multiple_of(Self::ALIGN);
+        let end = region.end - region.end % Self::ALIGN;
+        Self {
+            allocator: Rc::new(RefCell::new(Allocator {
+                region: start..end.max(start),
+                limit: usize::MAX,
+                max_allocations: usize::MAX,
+                allocations: BTreeMap::new(),
+                used: 0,
+            })),
+        }
+    }
+
+    /// Set the maximum number of bytes allocated at once, the whole region by default.
+    pub fn with_limit(self, bytes: usize) -> Self {
+        self.allocator.borrow_mut().limit = bytes;
+        self
+    }
+
+    /// Set the maximum number of live allocations, unlimited by default.
+    pub fn with_max_allocations(self, count: usize) -> Self {
+        self.allocator.borrow_mut().max_allocations = count;
+        self
+    }
+
+    /// Get the number of bytes allocated, rounded up to `Heap::ALIGN` per allocation.
+    pub fn used(&self) -> usize {
+        self.allocator.borrow().used
+    }
+
+    /// Get the number of live allocations.
+    pub fn allocations(&self) -> usize {
+        self.allocator.borrow().allocations.len()
+    }
+
+    /// Free all the allocations, before running an independent program.
+    pub fn clear(&self) {
+        let mut allocator = self.allocator.borrow_mut();
+        allocator.allocations.clear();
+        allocator.used = 0;
+    }
+
+    /// Allocate a block, as the syscall `Heap::ALLOC`.
+    ///
+    /// # Returns
+    /// The address of the block, or `None` if the limits or the free space do not allow it.
+    pub fn allocate(&self, size: usize) -> Option<usize> {
+        self.allocator.borrow_mut().allocate(size)
+    }
+
+    /// Free a block, as the syscall `Heap::FREE`.
+    ///
+    /// # Errors
+    /// Returns `VmError::InvalidHeapAddress` if no block is allocated at the address.
+    pub fn free(&self, address: usize) -> Result<()> {
+        self.allocator.borrow_mut().free(address)
+    }
+
+    /// Resize a block, as the syscall `Heap::REALLOC`, moving its content if needed.
+    ///
+    /// # Returns
+    /// The address of the block, or `None` if the limits or the free space do not allow it.
+    ///
+    /// # Errors
+    /// Returns `VmError::InvalidHeapAddress` if no block is allocated at the address.
+    pub fn reallocate(
+        &self,
+        memory: &mut Memory,
+        address: usize,
+        size: usize,
+    ) -> Result<Option<usize>> {
+        self.allocator
+            .borrow_mut()
+            .reallocate(memory, address, size)
+    }
+
+    /// Register the syscalls in a syscall table, replacing the functions registered under
+    /// the same numbers.
+    pub fn register<T: Word>(&self, syscalls: &mut SyscallTable<T>) {
+        let heap = self.clone();
+        syscalls.register(
+            Self::ALLOC,
+            Box::new(move |cpu, _memory| {
+                let address = heap.allocate(cpu.register(0)?.to_usize());
+                cpu.set_register(0, T::from_usize(address.unwrap_or(0)))
+            }),
+        );
+        let heap = self.clone();
+        syscalls.register(
+            Self::FREE,
+            Box::new(move |cpu, _memory| match cpu.register(0)?.to_usize() {
+                0 => Ok(()),
+                address => heap.free(address),
+            }),
+        );
+        let heap = self.clone();
+        syscalls.register(
+            Self::REALLOC,
+            Box::new(move |cpu, memory| {
+                let size = cpu.register(1)?.to_usize();
+                let address = match cpu.register(0)?.to_usize() {
+                    0 => heap.allocate(size),
+                    address => heap.reallocate(memory, address, size)?,
+                };
+                cpu.set_register(0, T::from_usize(address.unwrap_or(0)))
+            }),
+        );
+    }
+
+    /// Get the size of the block of an allocation, at least one aligned unit.
+    fn block_size(size: usize) -> Option<usize> {
+        size.max(1).checked_next_multiple_of(Self::ALIGN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_heap_first_fit() {
+        let heap = Heap::new(0..64).with_max_allocations(3);
+        assert_eq!(heap.allocate(10), Some(8));
+        assert_eq!(heap.allocate(0), Some(24));
+        assert_eq!(heap.allocate(8), Some(32));
+        assert_eq!(heap.allocate(8), None);
+        assert_eq!(heap.used(), 32);
+
+        heap.free(8).unwrap();
+        assert_eq!(
+            heap.free(8),
+            Err(VmError::InvalidHeapAddress { address: 8 })
+        );
+        // the freed block is reused, the rest of the region is too small
+        assert_eq!(heap.allocate(16), Some(8));
+        heap.free(32).unwrap();
+        assert_eq!(heap.allocate(40), None);
+        assert_eq!(heap.allocate(32), Some(32));
+
+        heap.clear();
+        assert_eq!((heap.used(), heap.allocations()), (0, 0));
+    }
+
+    #[test]
+    fn test_heap_reallocate() {
+        let mut memory = Memory::new(128);
+        let heap = Heap::new(0..128).with_limit(48);
+        let first = heap.allocate(8).unwrap();
+        let second = heap.allocate(8).unwrap();
+        memory.write_bytes(first, b"forge vm").unwrap();
+
+        // the first block is followed by the second one, it moves
+        let moved = heap.reallocate(&mut memory, first, 16).unwrap().unwrap();
+        assert_eq!(moved, 24);
+        assert_eq!(&memory.bytes()[moved..moved + 8], b"forge vm");
+        assert_eq!(
+            heap.free(first),
+            Err(VmError::InvalidHeapAddress { address: first })
+        );
+
+        // the last block shrinks and grows in place, up to the limit
+        assert_eq!(heap.reallocate(&mut memory, moved, 4), Ok(Some(moved)));
+        assert_eq!(heap.reallocate(&mut memory, moved, 40), Ok(Some(moved)));
+        assert_eq!(heap.reallocate(&mut memory, moved, 48), Ok(None));
+        assert_eq!(heap.used(), 48);
+        assert_eq!(heap.allocations(), 2);
+        heap.free(second).unwrap();
+        assert_eq!(
+            heap.reallocate(&mut memory, 64, 8),
+            Err(VmError::InvalidHeapAddress { address: 64 })
+        );
+    }
+
+    #[test]
+    fn test_heap_syscalls() {
+        // allocate 2 blocks, free the first one and grow the second one
+        let source = "
+            MOV R0 4
+            SYSCALL 0xf8
+            MOVR R2 R0
+            MOV R1 0x2a
+            STR R1 [R0]
+            MOV R0 4
+            SYSCALL 0xf8
+            MOVR R3 R0
+            MOVR R0 R2
+            SYSCALL 0xf9
+            MOVR R0 R3
+            MOV R1 64
+            SYSCALL 0xfa
+            HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let heap = Heap::new(0x100..0x200);
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_heap(heap.clone());
+        vm.run(&program).unwrap();
+        let registers = vm.snapshot().cpu.registers;
+        assert_eq!(
+            (registers[2], registers[3], registers[0]),
+            (0x100, 0x108, 0x108)
+        );
+        assert_eq!((heap.used(), heap.allocations()), (64, 1));
+
+        // a double free stops the execution
+        let program = Assembler::new()
+            .assemble("MOV R0 0x108\nSYSCALL 0xf9\nSYSCALL 0xf9\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::InvalidHeapAddress { address: 0x108 })
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Hexadecimal dumps of the memory, see `HexDump`.
+
+use std::fmt;
+
+/// A range of bytes formatted as a classic hexadecimal dump, like `hexdump -C`: 16 bytes per
+/// line with the address of the first one, in hexadecimal and as ASCII characters.
+///
+/// `Display` and `Debug` format the same dump, so that a dump in an assertion or an error report
+/// shows the bytes.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::hexdump::HexDump;
+/// let dump = HexDump::new(0x10, b"Hello, ForgeVM!\0\x01\x02");
+/// assert_eq!(
+///     dump.to_string(),
+///     "00000010  48 65 6c 6c 6f 2c 20 46  6f 72 67 65 56 4d 21 00  |Hello, ForgeVM!.|\n\
+///      00000020  01 02                                             |..|\n"
+/// );
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct HexDump<'a> {
+    /// The address of the first byte.
+    address: usize,
+    /// The dumped bytes.
+    bytes: &'a [u8],
+}
+
+impl<'a> HexDump<'a> {
+    /// Number of bytes per line.
+    pub const LINE: usize = 16;
+
+    /// Create the dump of bytes located at an address.
+    pub fn new(address: usize, bytes: &'a [u8]) -> Self {
+        Self { address, bytes }
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, line) in self.bytes.chunks(Self::LINE).enumerate() {
+            write!(f, "{:08x} ", self.address + index * Self::LINE)?;
+            for column in 0..Self::LINE {
+                if column == Self::LINE / 2 {
+                    write!(f, " ")?;
+                }
+                match line.get(column) {
+                    Some(byte) => write!(f, " {:02x}", byte)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            let text: String = line
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7e => byte as char,
+                    _ => '.',
+                })
+                .collect();
+            writeln!(f, "  |{}|", text)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(HexDump::new(0, &[]).to_string(), "");
+        assert_eq!(
+            HexDump::new(0x1234, &[0x41, 0x7f, 0x20]).to_string(),
+            "00001234  41 7f 20                                          |A. |\n"
+        );
+        let bytes: Vec<u8> = (0..32).collect();
+        let dump = format!("{:?}", HexDump::new(0, &bytes));
+        assert_eq!(dump.lines().count(), 2);
+        assert!(dump.starts_with("00000000  00 01 02 03 04 05 06 07  08 09 0a 0b"));
+        assert!(dump.ends_with("1c 1d 1e 1f  |................|\n"));
+    }
+}
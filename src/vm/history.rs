@@ -0,0 +1,125 @@
+//! The history of the executed instructions, undone by `VM::step_back`.
+
+use std::collections::VecDeque;
+
+use super::cpu::CpuState;
+
+/// The state of the VM before an instruction, with the bytes of the memory it overwrote.
+pub(crate) struct HistoryEntry<T> {
+    pub(crate) cpu: CpuState<T>,
+    pub(crate) stack: Vec<T>,
+    pub(crate) steps: u128,
+    pub(crate) cycles: u128,
+    pub(crate) interrupts_delivered: u128,
+    pub(crate) gas_used: u64,
+    /// The overwritten bytes with their address, in the order of the writes.
+    pub(crate) memory: Vec<(usize, Vec<u8>)>,
+}
+
+/// The states of the VM before the last instructions, bounded to a depth.
+pub(crate) struct History<T> {
+    entries: VecDeque<HistoryEntry<T>>,
+    depth: usize,
+}
+
+impl<T> History<T> {
+    pub(crate) fn new(depth: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(depth),
+            depth,
+        }
+    }
+
+    /// Add the state before an instruction, dropping the oldest state beyond the depth.
+    pub(crate) fn push(&mut self, entry: HistoryEntry<T>) {
+        if self.entries.len() == self.depth {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Add the bytes overwritten by the last instruction to its state.
+    pub(crate) fn journal(&mut self, memory: Vec<(usize, Vec<u8>)>) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.memory.extend(memory);
+        }
+    }
+
+    /// Take the state before the last instruction.
+    pub(crate) fn pop(&mut self) -> Option<HistoryEntry<T>> {
+        self.entries.pop_back()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Get the number of instructions that can be undone.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::Assembler;
+    use crate::vm::error::VmError;
+    use crate::vm::program::Program;
+    use crate::VM;
+
+    #[test]
+    fn test_step_back() {
+        let program = Assembler::new()
+            .assemble("MOV R0 7\nST R0 0x10\nPUSHREG R0\nINC R0\nST R0 0x10\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        vm.set_history_depth(3);
+        vm.load_program(&Program::new(&program)).unwrap();
+        let mut snapshots = Vec::new();
+        for _ in 0..5 {
+            snapshots.push(vm.snapshot());
+            assert_eq!(vm.step(), Ok(false));
+        }
+        assert_eq!(vm.history_len(), 3);
+        for snapshot in snapshots.iter().rev().take(3) {
+            vm.step_back().unwrap();
+            assert_eq!(&vm.snapshot(), snapshot);
+        }
+        // the history is bounded to the last 3 instructions
+        assert_eq!(vm.step_back(), Err(VmError::NoHistory));
+        assert_eq!(vm.snapshot(), snapshots[2]);
+
+        // the instructions are executed again after stepping back
+        while !vm.step().unwrap() {}
+        assert_eq!(vm.snapshot().memory[0x10], 8);
+        assert_eq!(vm.counters().steps, 6);
+    }
+
+    #[test]
+    fn test_step_back_fault() {
+        let program = Assembler::new()
+            .assemble("MOV R0 0x1234\nST R0 0x10\nDIV R0 R1 R1\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        vm.set_history_depth(16);
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::DivisionByZero)
+        );
+        // undo the faulting instruction, fix the divisor and resume
+        vm.step_back().unwrap();
+        assert_eq!(vm.pc(), 12);
+        vm.step_back().unwrap();
+        assert_eq!(vm.snapshot().memory[0x10], 0);
+        assert_eq!(vm.step(), Ok(false));
+        assert_eq!(vm.snapshot().memory[0x10], 0x34);
+
+        // the history is cleared at each run and disabled with a depth of 0
+        vm.set_history_depth(0);
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::DivisionByZero)
+        );
+        assert_eq!(vm.step_back(), Err(VmError::NoHistory));
+    }
+}
@@ -0,0 +1,129 @@
+//! The hook called around each instruction, see `VM::set_hook`.
+
+use super::instructions::Instruction;
+use super::word::Word;
+
+/// The moment a hook is called.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HookPhase {
+    /// Before the instruction is executed.
+    Pre,
+    /// After the instruction is executed without fault.
+    Post,
+}
+
+/// The action requested by a hook.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum HookAction {
+    /// Continue the execution.
+    #[default]
+    Continue,
+    /// Stop the execution with `VmError::Paused`, it can be resumed with `VM::step`.
+    /// In the `Pre` phase the instruction is not executed, and the hook is not called again
+    /// in the `Pre` phase of this instruction when the execution resumes.
+    Pause,
+    /// Move the program counter to the next instruction without executing the instruction.
+    /// Only meaningful in the `Pre` phase, the action is ignored in the `Post` phase.
+    SkipInstruction,
+    /// Stop the execution with `VmError::Aborted`.
+    Abort,
+}
+
+/// The state of the VM passed to the hook.
+#[derive(Debug, Clone, Copy)]
+pub struct HookCtx<'a, T: Word> {
+    pub phase: HookPhase,
+    /// The address of the instruction.
+    pub pc: usize,
+    pub instruction: Instruction<T, T::Address>,
+    /// The number of executed instructions, including the instruction in the `Post` phase.
+    pub steps: u128,
+    pub registers: &'a [T],
+    pub memory: &'a [u8],
+    /// The values of the stack, from the bottom to the top.
+    pub stack: &'a [T],
+}
+
+/// The hook of a VM, boxed to be stored in the VM.
+pub(crate) type Hook<T> = Box<dyn FnMut(&HookCtx<T>) -> HookAction>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::vm::error::VmError;
+    use crate::VM;
+
+    #[test]
+    fn test_hook_actions() {
+        // MOV at 0x0, INC at 0x6, INC at 0x8, HLT at 0xa
+        let program = Assembler::new()
+            .assemble("MOV R0 5\nINC R0\nINC R1\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+
+        // skip the first INC
+        vm.set_hook(|ctx| match (ctx.phase, ctx.pc) {
+            (HookPhase::Pre, 0x6) => HookAction::SkipInstruction,
+            _ => HookAction::Continue,
+        });
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.snapshot().cpu.registers[..2], [5, 1]);
+
+        // pause after the first INC and before the second one
+        vm.set_hook(|ctx| match (ctx.phase, ctx.instruction) {
+            (HookPhase::Post, Instruction::INC { reg: 0 }) => HookAction::Pause,
+            (HookPhase::Pre, Instruction::INC { reg: 1 }) => HookAction::Pause,
+            _ => HookAction::Continue,
+        });
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::Paused { pc: 0x8 })
+        );
+        assert_eq!(vm.snapshot().cpu.registers[0], 6);
+        assert_eq!(vm.step(), Err(VmError::Paused { pc: 0x8 }));
+        assert_eq!(vm.snapshot().cpu.registers[1], 0);
+        // the paused instruction is executed when the execution resumes
+        assert_eq!(vm.step(), Ok(false));
+        assert_eq!(vm.snapshot().cpu.registers[1], 1);
+        assert_eq!(vm.step(), Ok(true));
+
+        // abort when R0 reaches 6
+        vm.set_hook(|ctx| match ctx.registers[0] {
+            6 => HookAction::Abort,
+            _ => HookAction::Continue,
+        });
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::Aborted { pc: 0x6 })
+        );
+        assert_eq!(vm.counters().steps, 2);
+
+        vm.clear_hook();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+    }
+
+    #[test]
+    fn test_hook_ctx() {
+        let program = Assembler::new().assemble("PUSH 3\nST R0 0x4\nHLT").unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        let calls = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let hook_calls = calls.clone();
+        vm.set_hook(move |ctx| {
+            hook_calls.borrow_mut().push((
+                ctx.phase,
+                ctx.pc,
+                ctx.steps,
+                ctx.stack.to_vec(),
+                ctx.memory.len(),
+            ));
+            HookAction::Continue
+        });
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        let calls = calls.borrow();
+        assert_eq!(calls.len(), 6);
+        assert_eq!(calls[0], (HookPhase::Pre, 0x0, 0, vec![], 64));
+        assert_eq!(calls[1], (HookPhase::Post, 0x0, 1, vec![3], 64));
+        assert_eq!(calls[4], (HookPhase::Pre, 0xb, 2, vec![3], 64));
+    }
+}
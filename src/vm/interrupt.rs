@@ -0,0 +1,182 @@
+use std::collections::BTreeSet;
+
+use super::error::VmError;
+use super::word::Word;
+
+/// The faults that can be handled by the guest instead of stopping the execution.
+/// The numeric value of each variant is the line of its handler in the interrupt vector table,
+/// the lines `0` to `3` are reserved for the traps.
+/// A trap is delivered like an interrupt, even when the interrupts are disabled, with the address
+/// of the faulting instruction saved on the stack. A vector table entry of `0` means no handler
+/// is installed and the fault stops the execution with its `VmError`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trap {
+    /// `DIV`, `MOD` or `FXDIV` by zero, or a signed overflow with `VmBuilder::checked_arithmetic`.
+    DivisionByZero = 0x0,
+    /// Memory access out of bounds, not aligned or denied by the region descriptors.
+    InvalidMemory = 0x1,
+    /// Invalid opcode or operand, or a program counter outside the program.
+    InvalidInstruction = 0x2,
+    /// Access to a virtual address not mapped by the MMU, or write to a read-only page.
+    PageFault = 0x3,
+}
+
+impl Trap {
+    /// Get the trap raised by a fault, if the fault can be handled by the guest.
+    pub fn from_error(error: &VmError) -> Option<Self> {
+        match error {
+            VmError::DivisionByZero | VmError::ArithmeticOverflow => Some(Trap::DivisionByZero),
+            VmError::MemoryOutOfBounds { .. }
+            | VmError::MemoryNotAligned { .. }
+            | VmError::ProtectionFault { .. } => Some(Trap::InvalidMemory),
+            VmError::InvalidOpcode { .. }
+            | VmError::InvalidInstruction
+            | VmError::InvalidRegister { .. }
+            | VmError::InvalidCounter { .. } => Some(Trap::InvalidInstruction),
+            VmError::PageFault { .. } => Some(Trap::PageFault),
+            _ => None,
+        }
+    }
+}
+
+impl From<Trap> for u8 {
+    fn from(trap: Trap) -> u8 {
+        trap as u8
+    }
+}
+
+/// The interrupt controller of the VM.
+/// The controller holds the interrupts raised by the host until the CPU accepts them.
+/// The handlers are found in a vector table in memory: the entry of the line `n` is the
+/// address of its handler, stored at `vector_table + n * size_of::<T::Address>()`.
+/// When several interrupts are pending, the lowest line is delivered first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterruptController {
+    /// The address of the vector table in memory.
+    vector_table: usize,
+    /// The lines raised by the host and not delivered yet.
+    pending: BTreeSet<u8>,
+}
+
+impl InterruptController {
+    /// Create an interrupt controller without pending interrupts.
+    ///
+    /// # Parameters
+    /// - `vector_table`: The address of the vector table in memory.
+    pub fn new(vector_table: usize) -> Self {
+        Self {
+            vector_table,
+            pending: BTreeSet::new(),
+        }
+    }
+
+    /// Raise an interrupt, it stays pending until it is delivered.
+    ///
+    /// # Parameters
+    /// - `line`: The interrupt line, the index of its handler in the vector table.
+    pub fn raise(&mut self, line: u8) {
+        self.pending.insert(line);
+    }
+
+    /// Check if an interrupt line is pending.
+    pub fn is_pending(&self, line: u8) -> bool {
+        self.pending.contains(&line)
+    }
+
+    /// Discard all the pending interrupts.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Take the next interrupt to deliver.
+    /// The lines asserted by the devices are level-triggered: they are not recorded
+    /// and stay asserted until the device is serviced.
+    ///
+    /// # Parameters
+    /// - `asserted`: The lines asserted by the devices.
+    ///
+    /// # Returns
+    /// The lowest pending or asserted line, if any.
+    pub fn take_next(&mut self, asserted: impl Iterator<Item = u8>) -> Option<u8> {
+        let line = self
+            .pending
+            .first()
+            .copied()
+            .into_iter()
+            .chain(asserted)
+            .min()?;
+        self.pending.remove(&line);
+        Some(line)
+    }
+
+    /// Get the address of the vector table entry of a line.
+    ///
+    /// # Generics
+    /// - `T`: The data word of the architecture, the entries have the size of its addresses.
+    pub fn vector_address<T: Word>(&self, line: u8) -> usize {
+        self.vector_table + line as usize * T::Address::SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::protection::Permission;
+
+    #[test]
+    fn test_trap_from_error() {
+        assert_eq!(
+            Trap::from_error(&VmError::DivisionByZero),
+            Some(Trap::DivisionByZero)
+        );
+        assert_eq!(
+            Trap::from_error(&VmError::MemoryNotAligned {
+                address: 1,
+                size: 4
+            }),
+            Some(Trap::InvalidMemory)
+        );
+        assert_eq!(
+            Trap::from_error(&VmError::InvalidInstruction),
+            Some(Trap::InvalidInstruction)
+        );
+        assert_eq!(
+            Trap::from_error(&VmError::PageFault {
+                address: 0x1000,
+                write: false
+            }),
+            Some(Trap::PageFault)
+        );
+        assert_eq!(
+            Trap::from_error(&VmError::ProtectionFault {
+                address: 0x1000,
+                size: 4,
+                permission: Permission::Write
+            }),
+            Some(Trap::InvalidMemory)
+        );
+        assert_eq!(Trap::from_error(&VmError::StackOverflow), None);
+        assert_eq!(Trap::from_error(&VmError::StepLimitExceeded), None);
+    }
+
+    #[test]
+    fn test_interrupt_priority() {
+        let mut controller = InterruptController::new(0);
+        controller.raise(5);
+        controller.raise(2);
+        assert_eq!(controller.take_next(std::iter::once(3)), Some(2));
+        assert_eq!(controller.take_next(std::iter::once(3)), Some(3));
+        assert_eq!(controller.take_next(std::iter::once(3)), Some(3));
+        assert!(controller.is_pending(5));
+        assert_eq!(controller.take_next(std::iter::empty()), Some(5));
+        assert_eq!(controller.take_next(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn test_vector_address() {
+        let controller = InterruptController::new(0x100);
+        assert_eq!(controller.vector_address::<i32>(2), 0x108);
+        assert_eq!(controller.vector_address::<i64>(2), 0x110);
+        assert_eq!(controller.vector_address::<i16>(2), 0x104);
+    }
+}
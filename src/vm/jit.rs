@@ -0,0 +1,1090 @@
+//! The just-in-time compiler of the VM, built with the `jit` feature.
+//!
+//! The `run` functions count the visits of the addresses of the program, and the block starting
+//! at an address visited `VmBuilder::jit_threshold` times is translated into native code with
+//! Cranelift. A block is a sequence of register instructions, ended by a jump or by the first
+//! instruction the compiler does not support. A block jumping back to its own start runs its
+//! loop natively, for as many iterations as the limits of the run allow.
+//!
+//! The compiled code only reads and writes the registers and the status flags. It leaves to the
+//! interpreter the faults, with a guard before each division returning at the divisor zero, and
+//! the instructions it does not support, like the memory and stack accesses.
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, Block as IrBlock, BlockArg, InstBuilder, MemFlagsData, Type, Value,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+
+use super::cost::CostModel;
+use super::cpu::StatusFlags;
+use super::instructions::Instruction;
+use super::word::Word;
+
+/// The number of visits of an address before the block starting there is compiled, by default.
+pub(crate) const JIT_THRESHOLD: u32 = 16;
+
+/// The maximum number of instructions of a block.
+pub(crate) const MAX_BLOCK_INSTRUCTIONS: usize = 64;
+
+/// The compiled code of a block: it runs the block at most `budget` times with the registers
+/// and the flags `[zero, carry, overflow, negative]`, writes the address of the next
+/// instruction to `next_pc` and returns the number of instructions executed.
+type BlockFn<T> =
+    unsafe extern "C" fn(registers: *mut T, flags: *mut u8, budget: u64, next_pc: *mut u64) -> u64;
+
+/// The state of an address of the program for the JIT.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    /// The number of visits of the address so far.
+    Cold(u32),
+    /// The index of the block compiled at the address.
+    Compiled(usize),
+    /// No block can be compiled at the address.
+    Unsupported,
+}
+
+/// The action of the VM at an address, see `Jit::visit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Visit {
+    /// Interpret the instruction at the address.
+    Interpret,
+    /// The address is hot: compile the block starting there with `Jit::compile`.
+    Compile,
+    /// Run the compiled block of this index.
+    Run(usize),
+}
+
+/// A block compiled by the JIT.
+pub(crate) struct CompiledBlock<T> {
+    function: BlockFn<T>,
+    /// The cycles and the gas of the first `n` instructions of the block, at index `n`.
+    cycles: Vec<u128>,
+    gas: Vec<u64>,
+}
+
+impl<T: Word> CompiledBlock<T> {
+    /// Get the number of instructions of the block.
+    pub(crate) fn len(&self) -> u64 {
+        self.cycles.len() as u64 - 1
+    }
+
+    /// Get the cycles and the gas of `executed` instructions, run from the start of the block.
+    pub(crate) fn cost(&self, executed: u64) -> (u128, u64) {
+        let (iterations, rest) = (executed / self.len(), (executed % self.len()) as usize);
+        let len = self.cycles.len() - 1;
+        (
+            (iterations as u128)
+                .saturating_mul(self.cycles[len])
+                .saturating_add(self.cycles[rest]),
+            iterations
+                .saturating_mul(self.gas[len])
+                .saturating_add(self.gas[rest]),
+        )
+    }
+
+    /// Run the block, at most `budget` times in a row when it loops.
+    ///
+    /// # Returns
+    /// The number of instructions executed and the address of the next instruction.
+    pub(crate) fn run(
+        &self,
+        registers: &mut [T],
+        flags: &mut StatusFlags,
+        budget: u64,
+    ) -> (u64, usize) {
+        let mut bytes = [flags.zero, flags.carry, flags.overflow, flags.negative].map(u8::from);
+        let mut next_pc = 0;
+        // SAFETY: the block only accesses the registers it was compiled for, which the CPU
+        // has, and the 4 flags, and its code lives as long as the module of the JIT
+        let executed = unsafe {
+            (self.function)(
+                registers.as_mut_ptr(),
+                bytes.as_mut_ptr(),
+                budget,
+                &mut next_pc,
+            )
+        };
+        flags.zero = bytes[0] != 0;
+        flags.carry = bytes[1] != 0;
+        flags.overflow = bytes[2] != 0;
+        flags.negative = bytes[3] != 0;
+        (executed, next_pc as usize)
+    }
+}
+
+/// The just-in-time compiler of a VM, with the blocks it compiled.
+pub(crate) struct Jit<T: Word> {
+    /// The number of visits of an address before its block is compiled.
+    threshold: u32,
+    /// The state of the addresses of the program, indexed by address.
+    slots: Vec<Slot>,
+    blocks: Vec<CompiledBlock<T>>,
+    /// The module holding the code of the blocks, created with the first block.
+    module: Option<JITModule>,
+    context: FunctionBuilderContext,
+}
+
+impl<T: Word> Jit<T> {
+    /// Create a JIT for the words of the VM, `None` for the unsigned words, whose flags the
+    /// compiler does not model.
+    pub(crate) fn new(threshold: u32) -> Option<Self> {
+        if !(!T::zero()).is_negative() || T::SIZE > 8 {
+            return None;
+        }
+        Some(Self {
+            threshold,
+            slots: Vec::new(),
+            blocks: Vec::new(),
+            module: None,
+            context: FunctionBuilderContext::new(),
+        })
+    }
+
+    /// Count a visit of an address and tell the VM what to do there.
+    pub(crate) fn visit(&mut self, pc: usize) -> Visit {
+        if pc >= self.slots.len() {
+            self.slots.resize(pc + 1, Slot::Cold(0));
+        }
+        match &mut self.slots[pc] {
+            Slot::Compiled(index) => Visit::Run(*index),
+            Slot::Unsupported => Visit::Interpret,
+            Slot::Cold(visits) => {
+                *visits += 1;
+                if *visits >= self.threshold {
+                    Visit::Compile
+                } else {
+                    Visit::Interpret
+                }
+            }
+        }
+    }
+
+    /// Get a compiled block.
+    pub(crate) fn block(&self, index: usize) -> &CompiledBlock<T> {
+        &self.blocks[index]
+    }
+
+    /// Get the number of compiled blocks.
+    #[cfg(test)]
+    pub(crate) fn blocks_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Remove the compiled blocks and the visits, e.g. when the code changes.
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.blocks.clear();
+        if let Some(module) = self.module.take() {
+            // SAFETY: the functions of the module were removed with the blocks
+            unsafe { module.free_memory() };
+        }
+    }
+
+    /// Compile the block starting at an address.
+    ///
+    /// # Parameters
+    /// - `start`: The address of the block.
+    /// - `instructions`: The instructions from the start, each with its address, until the first
+    ///   jump included, see `block_length` for the supported prefix.
+    /// - `cost_model` and `gas`: The costs of the instructions in cycles and in gas.
+    ///
+    /// # Returns
+    /// The index of the block, `None` if no block can be compiled at the address.
+    pub(crate) fn compile(
+        &mut self,
+        start: usize,
+        instructions: &[(usize, Instruction<T, T::Address>)],
+        cost_model: &CostModel,
+        gas: &CostModel,
+    ) -> Option<usize> {
+        let instructions = &instructions[..block_length(instructions)];
+        if instructions.is_empty() {
+            self.slots[start] = Slot::Unsupported;
+            return None;
+        }
+        let index = match self.translate(start, instructions) {
+            Ok(function) => {
+                log::debug!(
+                    "JIT compiled {} instructions at 0x{:x}",
+                    instructions.len(),
+                    start
+                );
+                self.blocks.push(CompiledBlock {
+                    function,
+                    cycles: prefix_sums(instructions, cost_model)
+                        .into_iter()
+                        .map(u128::from)
+                        .collect(),
+                    gas: prefix_sums(instructions, gas),
+                });
+                Some(self.blocks.len() - 1)
+            }
+            Err(error) => {
+                log::warn!("JIT compilation at 0x{:x} failed: {}", start, error);
+                None
+            }
+        };
+        self.slots[start] = index.map_or(Slot::Unsupported, Slot::Compiled);
+        index
+    }
+
+    /// Get the module of the JIT, created for the host on the first call.
+    fn module(&mut self) -> Result<&mut JITModule, String> {
+        if self.module.is_none() {
+            let mut flags = settings::builder();
+            flags
+                .set("use_colocated_libcalls", "false")
+                .map_err(|error| error.to_string())?;
+            flags
+                .set("is_pic", "false")
+                .map_err(|error| error.to_string())?;
+            flags
+                .set("opt_level", "speed")
+                .map_err(|error| error.to_string())?;
+            let isa = cranelift_native::builder()?
+                .finish(settings::Flags::new(flags))
+                .map_err(|error| error.to_string())?;
+            self.module = Some(JITModule::new(JITBuilder::with_isa(
+                isa,
+                default_libcall_names(),
+            )));
+        }
+        Ok(self.module.as_mut().unwrap())
+    }
+
+    /// Translate the instructions of a block into a native function.
+    fn translate(
+        &mut self,
+        start: usize,
+        instructions: &[(usize, Instruction<T, T::Address>)],
+    ) -> Result<BlockFn<T>, String> {
+        let module = self.module()?;
+        let pointer = module.target_config().pointer_type();
+        let mut context = module.make_context();
+        let signature = &mut context.func.signature;
+        signature
+            .params
+            .extend([pointer, pointer].map(AbiParam::new));
+        signature.params.push(AbiParam::new(types::I64));
+        signature.params.push(AbiParam::new(pointer));
+        signature.returns.push(AbiParam::new(types::I64));
+        let id = module
+            .declare_anonymous_function(&context.func.signature)
+            .map_err(|error| error.to_string())?;
+        let config = module.target_config();
+
+        let builder = FunctionBuilder::new(&mut context.func, &mut self.context);
+        Translator::new(builder, start, instructions)
+            .translate()
+            .finalize(config);
+
+        let module = self.module.as_mut().unwrap();
+        module
+            .define_function(id, &mut context)
+            .map_err(|error| format!("{:?}", error))?;
+        module.clear_context(&mut context);
+        module
+            .finalize_definitions()
+            .map_err(|error| error.to_string())?;
+        let code = module.get_finalized_function(id);
+        // SAFETY: the function was compiled with the signature of `BlockFn`
+        Ok(unsafe { std::mem::transmute::<*const u8, BlockFn<T>>(code) })
+    }
+}
+
+impl<T: Word> Drop for Jit<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Check that the compiler supports an instruction.
+fn is_supported<T: Word>(instruction: &Instruction<T, T::Address>) -> bool {
+    matches!(
+        instruction,
+        Instruction::NOP
+            | Instruction::MOV { .. }
+            | Instruction::MOVR { .. }
+            | Instruction::ADD { .. }
+            | Instruction::SUB { .. }
+            | Instruction::ADC { .. }
+            | Instruction::SBB { .. }
+            | Instruction::MULT { .. }
+            | Instruction::DIV { .. }
+            | Instruction::MOD { .. }
+            | Instruction::AND { .. }
+            | Instruction::OR { .. }
+            | Instruction::XOR { .. }
+            | Instruction::NOT { .. }
+            | Instruction::CMP { .. }
+            | Instruction::INC { .. }
+            | Instruction::DEC { .. }
+            | Instruction::JMP { .. }
+    ) || instruction.conditional_target().is_some()
+}
+
+/// Check that an instruction ends a block.
+pub(crate) fn ends_block<T: Word>(instruction: &Instruction<T, T::Address>) -> bool {
+    !is_supported(instruction)
+        || matches!(instruction, Instruction::JMP { .. })
+        || instruction.conditional_target().is_some()
+}
+
+/// Get the number of instructions of the block made of a sequence: the supported instructions
+/// up to the first jump included, or the longest block allowed.
+fn block_length<T: Word>(instructions: &[(usize, Instruction<T, T::Address>)]) -> usize {
+    let mut length = 0;
+    for (_, instruction) in instructions.iter().take(MAX_BLOCK_INSTRUCTIONS) {
+        if !is_supported(instruction) {
+            break;
+        }
+        length += 1;
+        if ends_block(instruction) {
+            break;
+        }
+    }
+    length
+}
+
+/// The translation of the instructions of a block into the IR of Cranelift.
+struct Translator<'a, 'b, T: Word> {
+    builder: FunctionBuilder<'b>,
+    start: usize,
+    instructions: &'a [(usize, Instruction<T, T::Address>)],
+    /// The type of the words.
+    word: Type,
+    registers: Vec<Variable>,
+    /// The flags zero, carry, overflow and negative, as `0` or `1`.
+    flags: [Variable; 4],
+    /// The number of complete runs of the block.
+    iterations: Variable,
+    /// The block returning to the VM, with the next address and the instructions executed in
+    /// the current run as parameters.
+    exit: IrBlock,
+}
+
+/// The indices of the flags in `Translator::flags` and in the flags of `BlockFn`.
+const ZERO: usize = 0;
+const CARRY: usize = 1;
+const OVERFLOW: usize = 2;
+const NEGATIVE: usize = 3;
+
+impl<'a, 'b, T: Word> Translator<'a, 'b, T> {
+    fn new(
+        mut builder: FunctionBuilder<'b>,
+        start: usize,
+        instructions: &'a [(usize, Instruction<T, T::Address>)],
+    ) -> Self {
+        let word = Type::int(T::SIZE as u16 * 8).unwrap();
+        let count = instructions
+            .iter()
+            .flat_map(|(_, instruction)| registers(instruction))
+            .max()
+            .map_or(0, |register| register as usize + 1);
+        let registers = (0..count).map(|_| builder.declare_var(word)).collect();
+        let flags = [(); 4].map(|()| builder.declare_var(types::I8));
+        let iterations = builder.declare_var(types::I64);
+        let exit = builder.create_block();
+        builder.append_block_param(exit, types::I64);
+        builder.append_block_param(exit, types::I64);
+        Self {
+            builder,
+            start,
+            instructions,
+            word,
+            registers,
+            flags,
+            iterations,
+            exit,
+        }
+    }
+
+    fn translate(mut self) -> FunctionBuilder<'b> {
+        let entry = self.builder.create_block();
+        let body = self.builder.create_block();
+        self.builder.append_block_params_for_function_params(entry);
+        self.builder.switch_to_block(entry);
+        let params = self.builder.block_params(entry).to_vec();
+        let (registers, flags, budget, next_pc) = (params[0], params[1], params[2], params[3]);
+        let memory = MemFlagsData::trusted();
+        for (index, &variable) in self.registers.clone().iter().enumerate() {
+            let offset = (index * T::SIZE) as i32;
+            let value = self
+                .builder
+                .ins()
+                .load(self.word, memory, registers, offset);
+            self.builder.def_var(variable, value);
+        }
+        for (index, variable) in self.flags.into_iter().enumerate() {
+            let value = self
+                .builder
+                .ins()
+                .load(types::I8, memory, flags, index as i32);
+            self.builder.def_var(variable, value);
+        }
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.builder.def_var(self.iterations, zero);
+        self.builder.ins().jump(body, &[]);
+
+        self.builder.switch_to_block(body);
+        for (index, &(pc, instruction)) in self.instructions.iter().enumerate() {
+            self.instruction(index, pc, instruction, body, budget);
+        }
+        if !ends_block(&self.instructions.last().unwrap().1) {
+            let (pc, instruction) = self.instructions.last().unwrap();
+            self.exit_to(pc + instruction.size(), self.instructions.len());
+        }
+
+        // store the state and return the instructions executed
+        self.builder.switch_to_block(self.exit);
+        let (pc, executed) = (
+            self.builder.block_params(self.exit)[0],
+            self.builder.block_params(self.exit)[1],
+        );
+        for (index, &variable) in self.registers.clone().iter().enumerate() {
+            let value = self.builder.use_var(variable);
+            let offset = (index * T::SIZE) as i32;
+            self.builder.ins().store(memory, value, registers, offset);
+        }
+        for (index, variable) in self.flags.into_iter().enumerate() {
+            let value = self.builder.use_var(variable);
+            self.builder.ins().store(memory, value, flags, index as i32);
+        }
+        self.builder.ins().store(memory, pc, next_pc, 0);
+        let iterations = self.builder.use_var(self.iterations);
+        let runs = self
+            .builder
+            .ins()
+            .imul_imm_u(iterations, self.instructions.len() as i64);
+        let executed = self.builder.ins().iadd(runs, executed);
+        self.builder.ins().return_(&[executed]);
+        self.builder.seal_all_blocks();
+        self.builder
+    }
+
+    /// Translate the instruction of index `index` in the block, at the address `pc`.
+    fn instruction(
+        &mut self,
+        index: usize,
+        pc: usize,
+        instruction: Instruction<T, T::Address>,
+        body: IrBlock,
+        budget: Value,
+    ) {
+        let next = pc + instruction.size();
+        match instruction {
+            Instruction::NOP => {}
+            Instruction::MOV { dest, value } => {
+                let value = self.constant(value.to_unsigned().to_usize() as u64);
+                self.set(dest, value);
+            }
+            Instruction::MOVR { dest, src } => {
+                let value = self.get(src);
+                self.set(dest, value);
+            }
+            Instruction::ADD { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let result = self.builder.ins().iadd(a, b);
+                let carry = self.builder.ins().icmp(IntCC::UnsignedLessThan, result, a);
+                let overflow = self.add_overflow(a, b, result);
+                self.set_flag(CARRY, carry);
+                self.set_flag(OVERFLOW, overflow);
+                self.set_result(dest, result);
+            }
+            Instruction::ADC { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let carry_in = self.flag_word(CARRY);
+                let sum = self.builder.ins().iadd(a, b);
+                let result = self.builder.ins().iadd(sum, carry_in);
+                let carry1 = self.builder.ins().icmp(IntCC::UnsignedLessThan, sum, a);
+                let carry2 = self
+                    .builder
+                    .ins()
+                    .icmp(IntCC::UnsignedLessThan, result, sum);
+                let carry = self.builder.ins().bor(carry1, carry2);
+                // the two signed additions overflow at once only when the overflows cancel out
+                let overflow1 = self.add_overflow(a, b, sum);
+                let overflow2 = self.add_overflow(sum, carry_in, result);
+                let overflow = self.builder.ins().bxor(overflow1, overflow2);
+                self.set_flag(CARRY, carry);
+                self.set_flag(OVERFLOW, overflow);
+                self.set_result(dest, result);
+            }
+            Instruction::SUB { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let result = self.subtract(a, b);
+                self.set(dest, result);
+            }
+            Instruction::SBB { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let borrow_in = self.flag_word(CARRY);
+                let difference = self.builder.ins().isub(a, b);
+                let result = self.builder.ins().isub(difference, borrow_in);
+                let borrow1 = self.builder.ins().icmp(IntCC::UnsignedLessThan, a, b);
+                let borrow2 =
+                    self.builder
+                        .ins()
+                        .icmp(IntCC::UnsignedLessThan, difference, borrow_in);
+                let borrow = self.builder.ins().bor(borrow1, borrow2);
+                let overflow1 = self.sub_overflow(a, b, difference);
+                let overflow2 = self.sub_overflow(difference, borrow_in, result);
+                let overflow = self.builder.ins().bxor(overflow1, overflow2);
+                self.set_flag(CARRY, borrow);
+                self.set_flag(OVERFLOW, overflow);
+                self.set_result(dest, result);
+            }
+            Instruction::MULT { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let result = self.builder.ins().imul(a, b);
+                let overflow = if self.word == types::I64 {
+                    // the high half of the product is the sign of the low half without overflow
+                    let high = self.builder.ins().smulhi(a, b);
+                    let sign = self.builder.ins().sshr_imm_u(result, 63);
+                    self.builder.ins().icmp(IntCC::NotEqual, high, sign)
+                } else {
+                    let wide_a = self.builder.ins().sextend(types::I64, a);
+                    let wide_b = self.builder.ins().sextend(types::I64, b);
+                    let product = self.builder.ins().imul(wide_a, wide_b);
+                    let wide = self.builder.ins().sextend(types::I64, result);
+                    self.builder.ins().icmp(IntCC::NotEqual, wide, product)
+                };
+                self.set_flag(OVERFLOW, overflow);
+                self.set_result(dest, result);
+            }
+            Instruction::DIV { dest, reg1, reg2 } | Instruction::MOD { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                // the interpreter raises the division by zero
+                let divide = self.builder.create_block();
+                let is_zero = self.builder.ins().icmp_imm_s(IntCC::Equal, b, 0);
+                let pc = self.builder.ins().iconst(types::I64, pc as i64);
+                let executed = self.builder.ins().iconst(types::I64, index as i64);
+                self.builder.ins().brif(
+                    is_zero,
+                    self.exit,
+                    &[BlockArg::Value(pc), BlockArg::Value(executed)],
+                    divide,
+                    &[],
+                );
+                self.builder.switch_to_block(divide);
+                // the division by -1 wraps like `overflowing_div` instead of trapping
+                let minus_one = self.constant(u64::MAX);
+                let one = self.constant(1);
+                let is_minus_one = self.builder.ins().icmp(IntCC::Equal, b, minus_one);
+                let divisor = self.builder.ins().select(is_minus_one, one, b);
+                if matches!(instruction, Instruction::DIV { .. }) {
+                    let quotient = self.builder.ins().sdiv(a, divisor);
+                    let negated = self.builder.ins().ineg(a);
+                    let result = self.builder.ins().select(is_minus_one, negated, quotient);
+                    let min = self.constant(1 << (T::SIZE * 8 - 1));
+                    let is_min = self.builder.ins().icmp(IntCC::Equal, a, min);
+                    let overflow = self.builder.ins().band(is_minus_one, is_min);
+                    self.set_flag(OVERFLOW, overflow);
+                    self.set_result(dest, result);
+                } else {
+                    let remainder = self.builder.ins().srem(a, divisor);
+                    let zero = self.constant(0);
+                    let result = self.builder.ins().select(is_minus_one, zero, remainder);
+                    self.set_result(dest, result);
+                }
+            }
+            Instruction::AND { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let result = self.builder.ins().band(a, b);
+                self.set_result(dest, result);
+            }
+            Instruction::OR { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let result = self.builder.ins().bor(a, b);
+                self.set_result(dest, result);
+            }
+            Instruction::XOR { dest, reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                let result = self.builder.ins().bxor(a, b);
+                self.set_result(dest, result);
+            }
+            Instruction::NOT { dest, reg } => {
+                let value = self.get(reg);
+                let result = self.builder.ins().bnot(value);
+                self.set_result(dest, result);
+            }
+            Instruction::CMP { reg1, reg2 } => {
+                let (a, b) = (self.get(reg1), self.get(reg2));
+                self.subtract(a, b);
+            }
+            Instruction::INC { reg } | Instruction::DEC { reg } => {
+                let value = self.get(reg);
+                let one = self.constant(1);
+                let (result, limit) = match instruction {
+                    Instruction::INC { .. } => (
+                        self.builder.ins().iadd(value, one),
+                        (1 << (T::SIZE * 8 - 1)) - 1,
+                    ),
+                    _ => (self.builder.ins().isub(value, one), 1 << (T::SIZE * 8 - 1)),
+                };
+                let limit = self.constant(limit);
+                let overflow = self.builder.ins().icmp(IntCC::Equal, value, limit);
+                self.set_flag(OVERFLOW, overflow);
+                self.set_result(reg, result);
+            }
+            Instruction::JMP { address } => self.jump_to(address.to_usize(), body, budget),
+            _ => {
+                let target = instruction.conditional_target().unwrap().to_usize();
+                let condition = self.condition(instruction);
+                let (taken, not_taken) = (self.builder.create_block(), self.builder.create_block());
+                self.builder
+                    .ins()
+                    .brif(condition, taken, &[], not_taken, &[]);
+                self.builder.switch_to_block(taken);
+                self.jump_to(target, body, budget);
+                self.builder.switch_to_block(not_taken);
+                self.jump_to(next, body, budget);
+            }
+        }
+    }
+
+    /// End the current run of the block with a jump to an address: the next run when it is the
+    /// start of the block and the budget allows it, otherwise the return to the VM.
+    fn jump_to(&mut self, target: usize, body: IrBlock, budget: Value) {
+        if target != self.start {
+            self.exit_to(target, self.instructions.len());
+            return;
+        }
+        let iterations = self.builder.use_var(self.iterations);
+        let iterations = self.builder.ins().iadd_imm_u(iterations, 1);
+        self.builder.def_var(self.iterations, iterations);
+        let more = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedLessThan, iterations, budget);
+        let pc = self.builder.ins().iconst(types::I64, target as i64);
+        let executed = self.builder.ins().iconst(types::I64, 0);
+        self.builder.ins().brif(
+            more,
+            body,
+            &[],
+            self.exit,
+            &[BlockArg::Value(pc), BlockArg::Value(executed)],
+        );
+    }
+
+    /// Return to the VM at an address, after `executed` instructions of the current run.
+    fn exit_to(&mut self, pc: usize, executed: usize) {
+        let pc = self.builder.ins().iconst(types::I64, pc as i64);
+        let executed = self.builder.ins().iconst(types::I64, executed as i64);
+        self.builder
+            .ins()
+            .jump(self.exit, &[BlockArg::Value(pc), BlockArg::Value(executed)]);
+    }
+
+    /// Compute the condition of a conditional jump from the flags.
+    fn condition(&mut self, instruction: Instruction<T, T::Address>) -> Value {
+        let [zero, carry, overflow, negative] = self.flags.map(|flag| self.builder.use_var(flag));
+        let less = self.builder.ins().bxor(negative, overflow);
+        let value = match instruction {
+            Instruction::JMPN { .. } => negative,
+            Instruction::JMPP { .. } => self.builder.ins().bxor_imm_u(negative, 1),
+            Instruction::JMPZ { .. } => zero,
+            Instruction::JMPNZ { .. } => self.builder.ins().bxor_imm_u(zero, 1),
+            Instruction::JMPB { .. } | Instruction::JMPC { .. } => carry,
+            Instruction::JMPAE { .. } | Instruction::JMPNC { .. } => {
+                self.builder.ins().bxor_imm_u(carry, 1)
+            }
+            Instruction::JMPBE { .. } => self.builder.ins().bor(carry, zero),
+            Instruction::JMPA { .. } => {
+                let below_or_equal = self.builder.ins().bor(carry, zero);
+                self.builder.ins().bxor_imm_u(below_or_equal, 1)
+            }
+            Instruction::JMPLT { .. } => less,
+            Instruction::JMPGE { .. } => self.builder.ins().bxor_imm_u(less, 1),
+            Instruction::JMPLE { .. } => self.builder.ins().bor(zero, less),
+            Instruction::JMPGT { .. } => {
+                let less_or_equal = self.builder.ins().bor(zero, less);
+                self.builder.ins().bxor_imm_u(less_or_equal, 1)
+            }
+            Instruction::JMPO { .. } => overflow,
+            _ => unreachable!("{} is not a conditional jump", instruction),
+        };
+        value
+    }
+
+    /// Subtract two words and set the flags of the difference, as `CMP` and `SUB`.
+    fn subtract(&mut self, a: Value, b: Value) -> Value {
+        let result = self.builder.ins().isub(a, b);
+        let borrow = self.builder.ins().icmp(IntCC::UnsignedLessThan, a, b);
+        let overflow = self.sub_overflow(a, b, result);
+        self.set_flag(CARRY, borrow);
+        self.set_flag(OVERFLOW, overflow);
+        self.set_zero_negative(result);
+        result
+    }
+
+    /// Check the signed overflow of the sum `result` of `a` and `b`: the operands have the same
+    /// sign, and the sum the other one.
+    fn add_overflow(&mut self, a: Value, b: Value, result: Value) -> Value {
+        let a_result = self.builder.ins().bxor(a, result);
+        let b_result = self.builder.ins().bxor(b, result);
+        let both = self.builder.ins().band(a_result, b_result);
+        self.builder
+            .ins()
+            .icmp_imm_s(IntCC::SignedLessThan, both, 0)
+    }
+
+    /// Check the signed overflow of the difference `result` of `a` and `b`: the operands have
+    /// different signs, and the difference the sign of `b`.
+    fn sub_overflow(&mut self, a: Value, b: Value, result: Value) -> Value {
+        let a_b = self.builder.ins().bxor(a, b);
+        let a_result = self.builder.ins().bxor(a, result);
+        let both = self.builder.ins().band(a_b, a_result);
+        self.builder
+            .ins()
+            .icmp_imm_s(IntCC::SignedLessThan, both, 0)
+    }
+
+    /// Get a word constant from its bits, truncated to the size of the word.
+    fn constant(&mut self, bits: u64) -> Value {
+        let mask = u64::MAX >> (64 - T::SIZE * 8);
+        self.builder.ins().iconst(self.word, (bits & mask) as i64)
+    }
+
+    /// Get a flag as a word, `0` or `1`.
+    fn flag_word(&mut self, flag: usize) -> Value {
+        let value = self.builder.use_var(self.flags[flag]);
+        match self.word == types::I8 {
+            true => value,
+            false => self.builder.ins().uextend(self.word, value),
+        }
+    }
+
+    fn get(&mut self, register: u8) -> Value {
+        self.builder.use_var(self.registers[register as usize])
+    }
+
+    fn set(&mut self, register: u8, value: Value) {
+        self.builder
+            .def_var(self.registers[register as usize], value);
+    }
+
+    fn set_flag(&mut self, flag: usize, value: Value) {
+        self.builder.def_var(self.flags[flag], value);
+    }
+
+    /// Store the result of an instruction and set the zero and negative flags from it.
+    fn set_result(&mut self, register: u8, result: Value) {
+        self.set(register, result);
+        self.set_zero_negative(result);
+    }
+
+    fn set_zero_negative(&mut self, result: Value) {
+        let zero = self.builder.ins().icmp_imm_s(IntCC::Equal, result, 0);
+        let negative = self
+            .builder
+            .ins()
+            .icmp_imm_s(IntCC::SignedLessThan, result, 0);
+        self.set_flag(ZERO, zero);
+        self.set_flag(NEGATIVE, negative);
+    }
+}
+
+/// Get the costs of the first `n` instructions of a block in a cost model, at index `n`.
+fn prefix_sums<T: Word>(
+    instructions: &[(usize, Instruction<T, T::Address>)],
+    cost_model: &CostModel,
+) -> Vec<u64> {
+    std::iter::once(0)
+        .chain(instructions.iter().scan(0, |sum, (_, instruction)| {
+            *sum += cost_model.cost(instruction.opcode());
+            Some(*sum)
+        }))
+        .collect()
+}
+
+/// Get the registers read or written by a supported instruction.
+fn registers<T: Word>(instruction: &Instruction<T, T::Address>) -> Vec<u8> {
+    match *instruction {
+        Instruction::MOV { dest, .. } => vec![dest],
+        Instruction::MOVR { dest, src } => vec![dest, src],
+        Instruction::ADD { dest, reg1, reg2 }
+        | Instruction::SUB { dest, reg1, reg2 }
+        | Instruction::ADC { dest, reg1, reg2 }
+        | Instruction::SBB { dest, reg1, reg2 }
+        | Instruction::MULT { dest, reg1, reg2 }
+        | Instruction::DIV { dest, reg1, reg2 }
+        | Instruction::MOD { dest, reg1, reg2 }
+        | Instruction::AND { dest, reg1, reg2 }
+        | Instruction::OR { dest, reg1, reg2 }
+        | Instruction::XOR { dest, reg1, reg2 } => vec![dest, reg1, reg2],
+        Instruction::NOT { dest, reg } => vec![dest, reg],
+        Instruction::CMP { reg1, reg2 } => vec![reg1, reg2],
+        Instruction::INC { reg } | Instruction::DEC { reg } => vec![reg],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::Generator;
+    use crate::testing::{encode, ProgramStrategy};
+    use crate::vm::builder::VmBuilder;
+    use crate::vm::encoder::Encoder;
+    use crate::vm::reference;
+    use crate::vm::VM;
+
+    /// Build a VM compiling every block on its first visit, or a VM without JIT.
+    fn build<T: Word>(jit: bool) -> VM<T> {
+        let builder = VmBuilder::new().registers(8).memory_size(1024);
+        match jit {
+            true => builder.jit_threshold(1),
+            false => builder.jit(false),
+        }
+        .build::<T>()
+    }
+
+    /// Run a program with and without JIT and compare the results, the states and the costs.
+    fn compare<T: Word>(program: &[u8], max_steps: u128) -> Result<(), String> {
+        let (mut compiled, mut interpreted) = (build::<T>(true), build::<T>(false));
+        let results = (
+            compiled.run_with_limit(program, max_steps),
+            interpreted.run_with_limit(program, max_steps),
+        );
+        if results.0 != results.1 {
+            return Err(format!("{:?} != {:?}", results.0, results.1));
+        }
+        if compiled.snapshot() != interpreted.snapshot() {
+            return Err(format!(
+                "{:?} != {:?}",
+                compiled.snapshot(),
+                interpreted.snapshot()
+            ));
+        }
+        let costs = |vm: &VM<T>| (vm.counters(), vm.cycles(), vm.gas_used());
+        if costs(&compiled) != costs(&interpreted) {
+            return Err(format!(
+                "{:?} != {:?}",
+                costs(&compiled),
+                costs(&interpreted)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get the program of a loop of `count` iterations over the arithmetic instructions, with
+    /// a forward conditional jump and a division by a counter reaching zero when `count` exceeds
+    /// `limit`.
+    fn arithmetic_loop<T: Word>(count: i128, limit: i128) -> Vec<u8> {
+        let word = |value: i128| T::overflowing_from_i128(value).0;
+        let address = |value: usize| T::Address::overflowing_from_i128(value as i128).0;
+        let mut instructions: Vec<Instruction<T, T::Address>> = vec![
+            Instruction::MOV {
+                dest: 0,
+                value: word(count),
+            },
+            Instruction::MOV {
+                dest: 1,
+                value: word(3),
+            },
+            Instruction::MOV {
+                dest: 2,
+                value: word(1),
+            },
+            Instruction::MOV {
+                dest: 7,
+                value: word(limit),
+            },
+            Instruction::MULT {
+                dest: 2,
+                reg1: 2,
+                reg2: 1,
+            },
+            Instruction::ADD {
+                dest: 3,
+                reg1: 3,
+                reg2: 2,
+            },
+            Instruction::ADC {
+                dest: 4,
+                reg1: 4,
+                reg2: 3,
+            },
+            Instruction::SBB {
+                dest: 5,
+                reg1: 5,
+                reg2: 2,
+            },
+            Instruction::XOR {
+                dest: 6,
+                reg1: 6,
+                reg2: 3,
+            },
+            Instruction::DIV {
+                dest: 6,
+                reg1: 6,
+                reg2: 7,
+            },
+            Instruction::MOD {
+                dest: 5,
+                reg1: 5,
+                reg2: 1,
+            },
+            Instruction::CMP { reg1: 3, reg2: 5 },
+            Instruction::JMPLT {
+                address: address(0),
+            },
+            Instruction::NOT { dest: 4, reg: 4 },
+            Instruction::DEC { reg: 7 },
+            Instruction::DEC { reg: 0 },
+            Instruction::JMPNZ {
+                address: address(0),
+            },
+            Instruction::HLT,
+        ];
+        let offset = |instructions: &[Instruction<T, T::Address>], index: usize| {
+            instructions[..index]
+                .iter()
+                .map(Instruction::size)
+                .sum::<usize>()
+        };
+        instructions[12] = Instruction::JMPLT {
+            address: address(offset(&instructions, 14)),
+        };
+        instructions[16] = Instruction::JMPNZ {
+            address: address(offset(&instructions, 4)),
+        };
+        Encoder::new().encode_program(&instructions)
+    }
+
+    #[test]
+    fn test_jit_generated_programs() {
+        let generator = Generator::new()
+            .registers(8)
+            .instructions(100)
+            .max_iterations(40);
+        for seed in 0..100 {
+            let program = generator.generate(seed);
+            assert_eq!(
+                compare::<i32>(&program, generator.max_steps()),
+                Ok(()),
+                "seed {}",
+                seed
+            );
+        }
+
+        let mut vm = build::<i32>(true);
+        let program = generator.generate(0);
+        assert_eq!(
+            reference::compare(&mut vm, &program, generator.max_steps()),
+            Ok(())
+        );
+        assert!(vm.jit.as_ref().unwrap().blocks_count() > 0);
+    }
+
+    #[test]
+    fn test_jit_random_programs() {
+        // the programs fault, trap, loop forever and stop at the step limit
+        let strategy = ProgramStrategy::new().registers(8).max_instructions(32);
+        let result = strategy.check(1000, |instructions| {
+            compare::<i32>(&encode(instructions), 500)
+        });
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_jit_words() {
+        for (count, limit) in [(50, 100), (50, 20)] {
+            assert_eq!(
+                compare::<i8>(&arithmetic_loop::<i8>(count, limit), 10_000),
+                Ok(())
+            );
+            assert_eq!(
+                compare::<i16>(&arithmetic_loop::<i16>(count, limit), 10_000),
+                Ok(())
+            );
+            assert_eq!(
+                compare::<i32>(&arithmetic_loop::<i32>(count, limit), 10_000),
+                Ok(())
+            );
+            assert_eq!(
+                compare::<i64>(&arithmetic_loop::<i64>(count, limit), 10_000),
+                Ok(())
+            );
+        }
+
+        // the division by zero is raised by the interpreter after the compiled instructions
+        let mut vm = build::<i64>(true);
+        let error = vm.run(&arithmetic_loop::<i64>(50, 20)).unwrap_err();
+        assert_eq!(error.source, crate::VmError::DivisionByZero);
+        assert_eq!(vm.registers()[7], 0);
+        assert!(vm.jit.as_ref().unwrap().blocks_count() > 0);
+
+        assert!(build::<u32>(true).jit.is_none());
+    }
+
+    #[test]
+    fn test_jit_limits() {
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 1000\nloop: ADD R1 R1 R0\nDEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        for max_steps in [0, 1, 2, 3, 4, 100, 1234, 3001, 3002, 3003] {
+            assert_eq!(compare::<i32>(&program, max_steps), Ok(()), "{}", max_steps);
+        }
+        let mut vm = build::<i32>(true);
+        assert_eq!(
+            vm.run(&program).map(|termination| termination.steps),
+            Ok(3002)
+        );
+        assert_eq!(vm.registers()[1], 500_500);
+
+        for budget in [0, 10, 1000, 3001, 3002, 5000] {
+            let (mut compiled, mut interpreted) = (build::<i32>(true), build::<i32>(false));
+            assert_eq!(
+                compiled.run_with_cycle_budget(&program, budget),
+                interpreted.run_with_cycle_budget(&program, budget)
+            );
+            assert_eq!(compiled.snapshot(), interpreted.snapshot());
+        }
+    }
+
+    #[test]
+    fn test_jit_self_modifying_code() {
+        // the program patches the immediate of the first instruction of its compiled inner loop
+        let source = "
+                MOV R2 2
+            outer:
+                MOV R0 20
+            inner:
+                MOV R1 1
+                ADD R3 R3 R1
+                DEC R0
+                JMPNZ inner
+                MOV R0 inner
+                MOV R1 2
+                ADD R0 R0 R1
+                MOV R1 5
+                STR R1 [R0]
+                DEC R2
+                JMPNZ outer
+                HLT
+        ";
+        let program = crate::asm::Assembler::new()
+            .origin(0x100)
+            .assemble(source)
+            .unwrap();
+        let build = |jit: bool| {
+            VmBuilder::new()
+                .memory_size(1024)
+                .strict_alignment(false)
+                .von_neumann(0x100)
+                .jit_threshold(1)
+                .jit(jit)
+                .build::<i32>()
+        };
+        let (mut compiled, mut interpreted) = (build(true), build(false));
+        assert_eq!(compiled.run(&program), interpreted.run(&program));
+        assert_eq!(compiled.snapshot(), interpreted.snapshot());
+        assert_eq!(compiled.registers()[3], 120);
+    }
+}
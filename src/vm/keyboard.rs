@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::device::Device;
+use super::error::{Result, VmError};
+
+/// Offset of the status register: the number of pending events, any write discards them.
+pub const KEYBOARD_STATUS: usize = 0x00;
+/// Offset of the data register: a read takes the next event, see `KeyEvent::encode`.
+pub const KEYBOARD_DATA: usize = 0x08;
+
+/// A key or input event injected by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    /// The code of the key, defined by the host and the guest.
+    pub code: u16,
+    /// `true` if the key is pressed, `false` if it is released.
+    pub pressed: bool,
+}
+
+impl KeyEvent {
+    /// Create the event of a pressed key.
+    pub fn press(code: u16) -> Self {
+        Self {
+            code,
+            pressed: true,
+        }
+    }
+
+    /// Create the event of a released key.
+    pub fn release(code: u16) -> Self {
+        Self {
+            code,
+            pressed: false,
+        }
+    }
+
+    /// Encode the event as read by the guest: the key code in bits `0` to `15`,
+    /// and bit `16` set if the key is pressed.
+    pub fn encode(self) -> u64 {
+        u64::from(self.code) | (u64::from(self.pressed) << 16)
+    }
+}
+
+/// The queue of the events shared by a keyboard and its senders.
+type EventQueue = Arc<Mutex<VecDeque<KeyEvent>>>;
+
+/// A handle injecting events in a `KeyboardDevice`, from any thread, even while the VM runs.
+#[derive(Debug, Clone)]
+pub struct KeyboardSender {
+    queue: EventQueue,
+}
+
+impl KeyboardSender {
+    /// Queue an event at the end of the pending events of the keyboard.
+    pub fn send(&self, event: KeyEvent) {
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back(event);
+    }
+}
+
+/// A keyboard device queuing the key events injected by the host.
+/// The keyboard has 2 registers of 8 bytes, a register can be accessed with any size up to
+/// 8 bytes:
+/// - `KEYBOARD_STATUS` (`0x00`): Number of pending events, any write discards them.
+/// - `KEYBOARD_DATA` (`0x08`): A read takes the next event, encoded by `KeyEvent::encode`,
+///   or returns `-1` without pending event. The writes are ignored.
+///
+/// The guest polls the status register, or receives an interrupt while events are pending.
+/// The pending events are kept across runs.
+/// Use `KeyboardDevice::SIZE` as the length of the mapped range.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::keyboard::{KeyEvent, KeyboardDevice};
+/// use forge_vm::VM;
+///
+/// let keyboard = KeyboardDevice::new();
+/// let sender = keyboard.sender();
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// vm.map_device(0x1000..0x1000 + KeyboardDevice::SIZE, keyboard).unwrap();
+/// sender.send(KeyEvent::press(0x41));
+/// let program = Assembler::new().assemble("LD R0 0x1008\nHLT").unwrap();
+/// vm.run(&program).unwrap();
+/// assert_eq!(vm.snapshot().cpu.registers[0], 0x1_0041);
+/// ```
+#[derive(Debug, Default)]
+pub struct KeyboardDevice {
+    queue: EventQueue,
+}
+
+impl KeyboardDevice {
+    /// The number of bytes of the registers of the keyboard.
+    pub const SIZE: usize = 0x10;
+
+    /// Create a keyboard without pending event.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a handle to inject events in the keyboard once it is mapped.
+    pub fn sender(&self) -> KeyboardSender {
+        KeyboardSender {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+
+    /// Queue an event at the end of the pending events.
+    pub fn push(&mut self, event: KeyEvent) {
+        self.events().push_back(event);
+    }
+
+    /// Get the number of pending events.
+    pub fn pending(&self) -> usize {
+        self.events().len()
+    }
+
+    /// Lock the pending events, shared with the senders.
+    fn events(&self) -> std::sync::MutexGuard<'_, VecDeque<KeyEvent>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Check the access targets a single register.
+    fn check_register(offset: usize, size: usize) -> Result<()> {
+        if !offset.is_multiple_of(8) || size > 8 || offset >= Self::SIZE {
+            return Err(VmError::MemoryOutOfBounds {
+                address: offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Device for KeyboardDevice {
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        Self::check_register(offset, size)?;
+        let value = match offset {
+            KEYBOARD_STATUS => self.pending() as u64,
+            _ => self.events().pop_front().map_or(u64::MAX, KeyEvent::encode),
+        };
+        Ok(value.to_le_bytes()[..size].to_vec())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        Self::check_register(offset, data.len())?;
+        if offset == KEYBOARD_STATUS {
+            self.events().clear();
+        }
+        Ok(())
+    }
+
+    fn interrupt(&self) -> bool {
+        self.pending() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_keyboard_queue() {
+        let mut keyboard = KeyboardDevice::new();
+        assert!(!keyboard.interrupt());
+        keyboard.push(KeyEvent::press(1));
+        keyboard.sender().send(KeyEvent::release(1));
+        assert!(keyboard.interrupt());
+        assert_eq!(keyboard.read(KEYBOARD_STATUS, 4), Ok(vec![2, 0, 0, 0]));
+        assert_eq!(keyboard.read(KEYBOARD_DATA, 4), Ok(vec![1, 0, 1, 0]));
+        assert_eq!(keyboard.read(KEYBOARD_DATA, 4), Ok(vec![1, 0, 0, 0]));
+        assert_eq!(keyboard.read(KEYBOARD_DATA, 2), Ok(vec![0xff, 0xff]));
+
+        keyboard.push(KeyEvent::press(2));
+        keyboard.write(KEYBOARD_STATUS, &[0]).unwrap();
+        assert_eq!(keyboard.pending(), 0);
+        assert_eq!(
+            keyboard.read(0x04, 4),
+            Err(VmError::MemoryOutOfBounds {
+                address: 4,
+                size: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_keyboard_interrupt() {
+        // the handler of the line 4 counts the events in R1 and sums them in R3
+        let source = "
+                MOV R0 handler
+                ST R0 16
+                MOV R2 3
+                EI
+            wait:
+                CMP R1 R2
+                JMPNZ wait
+                HLT
+            handler:
+                LD R0 0x2008
+                INC R1
+                ADD R3 R3 R0
+                IRET
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let keyboard = KeyboardDevice::new();
+        let sender = keyboard.sender();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.map_device_with_interrupt(0x2000..0x2000 + KeyboardDevice::SIZE, keyboard, 4)
+            .unwrap();
+        sender.send(KeyEvent::press(1));
+        sender.send(KeyEvent::release(1));
+        sender.send(KeyEvent::press(2));
+        assert!(vm.run_with_limit(&program, 1000).is_ok());
+        let registers = vm.snapshot().cpu.registers;
+        assert_eq!((registers[1], registers[3]), (3, 0x2_0004));
+        assert_eq!(vm.device::<KeyboardDevice>(0x2000).unwrap().pending(), 0);
+    }
+}
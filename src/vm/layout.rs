@@ -0,0 +1,117 @@
+use std::ops::Range;
+
+use super::error::{Result, VmError};
+use super::protection::{Permissions, Region};
+
+/// The layout of the memory of a VM: the regions of the code, the data, the heap and the stack.
+///
+/// A VM built with a layout runs in the Von Neumann mode with the program loaded at the start of
+/// the code region, and its stack lives in the stack region, growing down from its end. The data
+/// and heap regions are reserved for the program and the host, which can load a data blob or
+/// another program at a known address with `VM::load_at`.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::layout::MemoryLayout;
+/// let layout = MemoryLayout::new(0x0..0x1000, 0x1000..0x2000, 0x2000..0x3000, 0x3000..0x4000);
+/// assert_eq!(layout.unwrap().size(), 0x4000);
+/// assert!(MemoryLayout::new(0x0..0x1000, 0x800..0x2000, 0x2000..0x2000, 0x3000..0x4000).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// The region of the program.
+    pub code: Range<usize>,
+    /// The region of the static data.
+    pub data: Range<usize>,
+    /// The region of the dynamic allocations.
+    pub heap: Range<usize>,
+    /// The region of the stack.
+    pub stack: Range<usize>,
+}
+
+impl MemoryLayout {
+    /// Create a layout from its regions, an empty region is allowed.
+    ///
+    /// # Errors
+    /// Returns an error if a region ends before its start or if two regions overlap.
+    pub fn new(
+        code: Range<usize>,
+        data: Range<usize>,
+        heap: Range<usize>,
+        stack: Range<usize>,
+    ) -> Result<Self> {
+        let layout = Self {
+            code,
+            data,
+            heap,
+            stack,
+        };
+        let regions = layout.regions();
+        for (index, (name, region)) in regions.iter().enumerate() {
+            if region.start > region.end {
+                return Err(VmError::Other(format!(
+                    "Memory region {} {:?} ends before its start",
+                    name, region
+                )));
+            }
+            for (other_name, other) in &regions[index + 1..] {
+                let overlap = region.start < other.end && other.start < region.end;
+                if overlap && !region.is_empty() && !other.is_empty() {
+                    return Err(VmError::Other(format!(
+                        "Memory regions {} {:?} and {} {:?} overlap",
+                        name, region, other_name, other
+                    )));
+                }
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Get the size of the memory needed by the layout, the end of its last region.
+    pub fn size(&self) -> usize {
+        self.regions()
+            .iter()
+            .map(|(_, region)| region.end)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Get the region descriptors protecting the layout, see `VmBuilder::region`: the code can
+    /// be read and executed but not modified, the data, the heap and the stack can be read and
+    /// written but not executed. A self-modifying program needs another code region.
+    pub fn protection(&self) -> [Region; 4] {
+        [
+            Region::new(self.code.clone(), Permissions::READ_EXECUTE),
+            Region::new(self.data.clone(), Permissions::READ_WRITE),
+            Region::new(self.heap.clone(), Permissions::READ_WRITE),
+            Region::new(self.stack.clone(), Permissions::READ_WRITE),
+        ]
+    }
+
+    /// Get the regions of the layout with their names.
+    fn regions(&self) -> [(&'static str, &Range<usize>); 4] {
+        [
+            ("code", &self.code),
+            ("data", &self.data),
+            ("heap", &self.heap),
+            ("stack", &self.stack),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_new() {
+        let layout = MemoryLayout::new(0x0..0x100, 0x100..0x100, 0x200..0x300, 0x300..0x400);
+        assert_eq!(layout.map(|layout| layout.size()), Ok(0x400));
+        assert!(MemoryLayout::new(0x0..0x100, 0x100..0x200, 0x150..0x160, 0x300..0x400).is_err());
+        let reversed = Range {
+            start: 0x100,
+            end: 0x0,
+        };
+        assert!(MemoryLayout::new(reversed, 0x100..0x200, 0x200..0x300, 0x300..0x400).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use super::error::{Result, VmError};
+use super::program::Program;
+
+/// Intel HEX record type: data bytes at a 16-bit offset.
+const RECORD_DATA: u8 = 0x00;
+/// Intel HEX record type: end of the file.
+const RECORD_END_OF_FILE: u8 = 0x01;
+/// Intel HEX record type: segment base address, multiplied by 16.
+const RECORD_EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+/// Intel HEX record type: start address as a segment and an offset, `CS:IP`.
+const RECORD_START_SEGMENT_ADDRESS: u8 = 0x03;
+/// Intel HEX record type: upper 16 bits of the base address.
+const RECORD_EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+/// Intel HEX record type: 32-bit start address.
+const RECORD_START_LINEAR_ADDRESS: u8 = 0x05;
+
+/// Load a plain binary file, the raw bytecode of a program starting at its first byte.
+/// Use `VM::load_at` to load a binary file as a memory image at an address instead.
+pub fn load_binary(bytes: &[u8]) -> Program<'_> {
+    Program::new(bytes)
+}
+
+/// A memory image parsed from an Intel HEX file: blocks of bytes at their address in the
+/// memory, and the start address of the program.
+///
+/// The records of type `00` to `05` are supported. The data records at consecutive addresses
+/// are merged into one block, the blocks are sorted by address.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::loader::HexImage;
+/// let image = HexImage::parse(":03010000000EFFEF\n:00000001FF").unwrap();
+/// assert_eq!(image.blocks, vec![(0x100, vec![0x00, 0x0e, 0xff])]);
+/// let program = image.to_program(0x100).unwrap();
+/// assert_eq!(program.code(), [0x00, 0x0e, 0xff]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HexImage {
+    /// The blocks of bytes with their address, each can be loaded with `VM::load_at`.
+    pub blocks: Vec<(usize, Vec<u8>)>,
+    /// The start address given by a record of type `03` or `05`.
+    pub start: Option<usize>,
+}
+
+impl HexImage {
+    /// Parse the text of an Intel HEX file.
+    /// The lines that do not start with `:` are ignored, and parsing stops at the end of file record.
+    ///
+    /// # Errors
+    /// Returns `VmError::InvalidImage` if a record is malformed, has a wrong checksum or has an
+    /// unsupported type, or if two records overlap.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut image = Self::default();
+        let mut base = 0usize;
+        for (index, line) in text.lines().enumerate() {
+            let Some(record) = line.trim().strip_prefix(':') else {
+                continue;
+            };
+            let invalid = |description: &str| {
+                VmError::InvalidImage(format!("line {}: {}", index + 1, description))
+            };
+            let bytes = decode_hex(record).ok_or_else(|| invalid("invalid hexadecimal digits"))?;
+            let [count, high, low, kind, .., _checksum] = bytes[..] else {
+                return Err(invalid("truncated record"));
+            };
+            let data = &bytes[4..bytes.len() - 1];
+            if data.len() != count as usize {
+                return Err(invalid("record length does not match its byte count"));
+            }
+            if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+                return Err(invalid("invalid checksum"));
+            }
+            let field = || {
+                data.iter()
+                    .fold(0usize, |value, byte| value << 8 | *byte as usize)
+            };
+            match (kind, data.len()) {
+                (RECORD_DATA, _) => {
+                    let address = base + u16::from_be_bytes([high, low]) as usize;
+                    if !image.insert(address, data) {
+                        return Err(invalid("overlapping data"));
+                    }
+                }
+                (RECORD_END_OF_FILE, 0) => break,
+                (RECORD_EXTENDED_SEGMENT_ADDRESS, 2) => base = field() << 4,
+                (RECORD_START_SEGMENT_ADDRESS, 4) => {
+                    image.start = Some((field() >> 16 << 4) + (field() & 0xffff))
+                }
+                (RECORD_EXTENDED_LINEAR_ADDRESS, 2) => base = field() << 16,
+                (RECORD_START_LINEAR_ADDRESS, 4) => image.start = Some(field()),
+                _ => return Err(invalid(&format!("unsupported record type 0x{:02x}", kind))),
+            }
+        }
+        Ok(image)
+    }
+
+    /// Build the program of an image loaded at a base address. The code is the memory from the
+    /// base address to the end of the last block, with the gaps between the blocks zeroed,
+    /// and the entry point is the start address of the image, or the base address.
+    ///
+    /// # Parameters
+    /// - `base`: The address of the program, `0` in the Harvard mode or the base address of
+    ///   `VmBuilder::von_neumann`.
+    ///
+    /// # Errors
+    /// Returns `VmError::InvalidImage` if a block or the start address is below the base address.
+    pub fn to_program(&self, base: usize) -> Result<Program<'static>> {
+        let mut code = Vec::new();
+        for (address, bytes) in &self.blocks {
+            let offset = address.checked_sub(base).ok_or_else(|| {
+                VmError::InvalidImage(format!("Block at 0x{:x} below the base address", address))
+            })?;
+            code.resize(offset, 0);
+            code.extend_from_slice(bytes);
+        }
+        let entry = match self.start {
+            Some(start) => start.checked_sub(base).ok_or_else(|| {
+                VmError::InvalidImage(format!(
+                    "Start address 0x{:x} below the base address",
+                    start
+                ))
+            })?,
+            None => 0,
+        };
+        Ok(Program::new(&code).with_entry(entry).into_owned())
+    }
+
+    /// Insert data at an address, merging it with the adjacent blocks.
+    /// Returns `false` if the data overlaps a block.
+    fn insert(&mut self, address: usize, data: &[u8]) -> bool {
+        if data.is_empty() {
+            return true;
+        }
+        let index = self.blocks.partition_point(|(start, _)| *start <= address);
+        let end = address + data.len();
+        let previous_end = index
+            .checked_sub(1)
+            .map(|previous| self.blocks[previous].0 + self.blocks[previous].1.len());
+        let next_start = self.blocks.get(index).map(|(start, _)| *start);
+        if previous_end.is_some_and(|previous_end| previous_end > address)
+            || next_start.is_some_and(|next_start| next_start < end)
+        {
+            return false;
+        }
+        self.blocks.insert(index, (address, data.to_vec()));
+        if next_start == Some(end) {
+            let (_, next) = self.blocks.remove(index + 1);
+            self.blocks[index].1.extend(next);
+        }
+        if previous_end == Some(address) {
+            let (_, bytes) = self.blocks.remove(index);
+            self.blocks[index - 1].1.extend(bytes);
+        }
+        true
+    }
+}
+
+/// Decode a string of hexadecimal digit pairs into bytes.
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VM;
+
+    #[test]
+    fn test_load_binary() {
+        let program = load_binary(&[0x0e, 0x00, 0xff]); // INC R0, HLT
+        let mut vm = VM::<i32>::new(16, 16);
+        assert_eq!(
+            vm.run_program(&program)
+                .map(|termination| termination.steps),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_hex_parse() {
+        let text = "
+            :020000040001F9
+            :020010000102EB
+            :02000E00FEFFF3
+            :020020000304D7
+            :0400000500010010E6
+            :00000001FF
+            :02000000AAAAAA
+        ";
+        let image = HexImage::parse(text).unwrap();
+        assert_eq!(
+            image.blocks,
+            vec![
+                (0x1000e, vec![0xfe, 0xff, 0x01, 0x02]),
+                (0x10020, vec![0x03, 0x04])
+            ]
+        );
+        assert_eq!(image.start, Some(0x10010));
+
+        let program = image.to_program(0x10000).unwrap();
+        assert_eq!(program.size(), 0x22);
+        assert_eq!(program.code()[0x0e..0x12], [0xfe, 0xff, 0x01, 0x02]);
+        assert_eq!(program.entry(), 0x10);
+        assert!(image.to_program(0x10010).is_err());
+    }
+
+    #[test]
+    fn test_hex_segment_address() {
+        // segment 0x1000 and start address 0x1000:0x0004
+        let text = ":020000021000EC\n:01000000FF00\n:0400000310000004E5";
+        let image = HexImage::parse(text).unwrap();
+        assert_eq!(image.blocks, vec![(0x10000, vec![0xff])]);
+        assert_eq!(image.start, Some(0x10004));
+    }
+
+    #[test]
+    fn test_hex_invalid() {
+        assert_eq!(
+            HexImage::parse(":0100000000FE"),
+            Err(VmError::InvalidImage(
+                "line 1: invalid checksum".to_string()
+            ))
+        );
+        assert!(HexImage::parse(":01000000").is_err());
+        assert!(HexImage::parse(":0G000000").is_err());
+        assert!(HexImage::parse(":00000006FA").is_err());
+        assert!(HexImage::parse(":020000000102FB\n:01000100FFFF").is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use super::word::Word;
+use super::{counters, cpu, decoder, error, memory, port, program, random, stack, stdio, syscall};
+
+/// A core of a `Machine`: a CPU with its own stack.
+struct Core<T> {
+    cpu: cpu::CPU<T>,
+    stack: stack::Stack<T>,
+    halted: bool,
+}
+
+/// Multi-core machine: several CPUs, each with its own stack, sharing one memory.
+///
+/// All the cores run the same program from its first instruction, each core starting with its index
+/// in `R0` so that the guest can select the work of each core. The cores are stepped in
+/// round-robin, each executing up to `quantum` instructions before the next core runs.
+/// A core stops at `HLT` while the others keep running, and the machine stops when all the
+/// cores have halted or when a core executes `EXIT`. The interrupts and the traps are not delivered and the thread
+/// instructions are not supported, a fault on any core stops the machine with its error.
+///
+/// # Generics:
+/// - `T`: The data word of the architecture of the cores.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::VmBuilder;
+/// let program = Assembler::new().assemble("INC R0\nHLT").unwrap();
+/// let mut machine = VmBuilder::new().build_machine::<i32>(2);
+/// assert_eq!(machine.run(&program), Ok(4));
+/// assert_eq!(machine.cpu(1).unwrap().get_register(0), Ok(2));
+/// ```
+pub struct Machine<T> {
+    cores: Vec<Core<T>>,
+    memory: memory::Memory,
+    quantum: u32,
+    steps: u128,
+    step_limit: u128,
+    syscalls: syscall::SyscallTable<T>,
+    ports: port::PortBus<T>,
+    /// The address of the program in the memory in the Von Neumann mode, see `VmBuilder::von_neumann`.
+    program_base: Option<usize>,
+}
+
+impl<T: Word> Machine<T> {
+    /// Create a machine from its cores and the shared memory.
+    /// Use `VmBuilder::build_machine` to configure the hardware parameters.
+    pub(crate) fn new(
+        cores: Vec<(cpu::CPU<T>, stack::Stack<T>)>,
+        memory: memory::Memory,
+        program_base: Option<usize>,
+    ) -> Self {
+        Self {
+            cores: cores
+                .into_iter()
+                .map(|(cpu, stack)| Core {
+                    cpu,
+                    stack,
+                    halted: false,
+                })
+                .collect(),
+            memory,
+            quantum: 1,
+            steps: 0,
+            step_limit: u128::MAX,
+            syscalls: syscall::SyscallTable::new(),
+            ports: port::PortBus::new(),
+            program_base,
+        }
+    }
+
+    /// Set the number of instructions a core executes before the next core runs.
+    /// The default of `1` interleaves the cores instruction by instruction, a quantum of `0` is
+    /// treated as `1`.
+    pub fn set_quantum(&mut self, quantum: u32) {
+        self.quantum = quantum.max(1);
+    }
+
+    /// Get the number of cores of the machine.
+    pub fn cores_count(&self) -> usize {
+        self.cores.len()
+    }
+
+    /// Runs the program on all the cores until they have all halted.
+    ///
+    /// # Returns:
+    /// - `Ok(u128)`: Total number of steps executed by all the cores.
+    /// - `Err(VmError)`: The first error raised by a core.
+    pub fn run(&mut self, program: &[u8]) -> Result<u128, error::VmError> {
+        self.run_with_limit(program, u128::MAX)
+    }
+
+    /// Runs the program on all the cores with a maximum number of steps, summed over all the cores.
+    ///
+    /// # Returns:
+    /// - `Ok(u128)`: Total number of steps executed by all the cores.
+    /// - `Err(VmError::StepLimitExceeded)`: If the cores did not all halt within `max_steps` steps.
+    /// - `Err(VmError)`: The first error raised by a core.
+    pub fn run_with_limit(
+        &mut self,
+        program: &[u8],
+        max_steps: u128,
+    ) -> Result<u128, error::VmError> {
+        log::info!("Running program on {} cores...", self.cores.len());
+        self.steps = 0;
+        self.step_limit = max_steps;
+        self.memory.clear();
+        if let Some(base) = self.program_base {
+            self.memory.write_bytes(base, program)?;
+        }
+        for (index, core) in self.cores.iter_mut().enumerate() {
+            core.cpu.init();
+            if let Some(base) = self.program_base {
+                core.cpu.set_pc(base);
+            }
+            core.cpu.set_register(0, T::from_usize(index))?;
+            core.stack.clear();
+            core.halted = false;
+        }
+        let program = program::Program::new(program);
+
+        while self.cores.iter().any(|core| !core.halted) {
+            for index in 0..self.cores.len() {
+                for _ in 0..self.quantum {
+                    if self.cores[index].halted {
+                        break;
+                    }
+                    self.step(index, &program)?;
+                }
+            }
+        }
+        log::info!("Program executed successfully in {} steps.", self.steps);
+        Ok(self.steps)
+    }
+
+    /// Execute the next instruction of a core.
+    fn step(&mut self, index: usize, program: &program::Program) -> Result<(), error::VmError> {
+        let core = &mut self.cores[index];
+        let decoder = decoder::Decoder::<T>::with_registers(core.cpu.registers_count())
+            .with_endianness(self.memory.endianness());
+        let code = match self.program_base {
+            Some(_) => self.memory.bytes().get(core.cpu.pc()..).unwrap_or_default(),
+            None => program.slice_from(core.cpu.pc()),
+        };
+        // the opcode is checked before the decoding, the whole instruction after
+        let protected = self.program_base.is_some() && self.memory.is_protected();
+        if protected {
+            self.memory.check_execute(core.cpu.pc(), 1)?;
+        }
+        let instruction = decoder.decode_instruction(code)?;
+        if protected {
+            self.memory
+                .check_execute(core.cpu.pc(), instruction.size())?;
+        }
+        self.steps += 1;
+        if self.steps > self.step_limit {
+            log::info!("Step limit of {} exceeded.", self.step_limit);
+            return Err(error::VmError::StepLimitExceeded);
+        }
+        log::debug!("Core {} executing instruction: {:?}", index, instruction);
+        let counters = counters::PerfCounters {
+            steps: self.steps,
+            fuel_remaining: self.step_limit.saturating_sub(self.steps),
+            stack_high_water: core.cpu.stack_high_water_mark(&core.stack),
+            // the machine has no cost model, an instruction costs a cycle
+            cycles: self.steps,
+            // the interrupts are not delivered to the cores
+            interrupts_delivered: 0,
+        };
+        let stop = core.cpu.execute_instruction(
+            instruction,
+            &mut self.memory,
+            &mut core.stack,
+            &counters,
+            &mut self.syscalls,
+            &mut self.ports,
+        )?;
+        match stop {
+            Some(cpu::Stop::Halt { .. }) => core.halted = true,
+            Some(cpu::Stop::Exit { .. }) => {
+                for core in &mut self.cores {
+                    core.halted = true;
+                }
+            }
+            None => self.memory.tick_devices()?,
+        }
+        Ok(())
+    }
+
+    /// Check if a core has halted.
+    /// Returns `None` if the core does not exist.
+    pub fn halted(&self, core: usize) -> Option<bool> {
+        self.cores.get(core).map(|core| core.halted)
+    }
+
+    /// Get the CPU of a core.
+    /// Returns `None` if the core does not exist.
+    pub fn cpu(&self, core: usize) -> Option<&cpu::CPU<T>> {
+        self.cores.get(core).map(|core| &core.cpu)
+    }
+
+    /// Get the stack of a core.
+    /// Returns `None` if the core does not exist.
+    pub fn stack(&self, core: usize) -> Option<&stack::Stack<T>> {
+        self.cores.get(core).map(|core| &core.stack)
+    }
+
+    /// Get the memory shared by the cores.
+    pub fn memory(&self) -> &memory::Memory {
+        &self.memory
+    }
+
+    /// Register a host function called by the `SYSCALL` instruction of any core.
+    /// See `VM::register_host_fn`.
+    pub fn register_host_fn<F>(&mut self, number: u8, function: F)
+    where
+        F: FnMut(&mut cpu::CpuView<T>, &mut memory::Memory) -> error::Result<()> + 'static,
+    {
+        self.syscalls.register(number, Box::new(function));
+    }
+
+    /// Register the built-in standard I/O syscalls for every core. See `VM::register_stdio`.
+    pub fn register_stdio(&mut self, stdio: stdio::Stdio) {
+        stdio.register(&mut self.syscalls);
+    }
+
+    /// Register the syscall of a random number generator shared by the cores.
+    /// See `VmBuilder::random`.
+    pub fn register_random(&mut self, source: random::RandomSource) {
+        random::Rng::new(source).register(&mut self.syscalls);
+    }
+
+    /// Register the handler of an I/O port accessed by the `IN` and `OUT` instructions of any
+    /// core. See `VM::register_port`.
+    pub fn register_port<H: port::PortHandler<T> + 'static>(&mut self, port: u16, handler: H) {
+        self.ports.register(port, Box::new(handler));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::Assembler;
+    use crate::vm::builder::VmBuilder;
+    use crate::vm::error::VmError;
+
+    #[test]
+    fn test_machine_shared_counter() {
+        // every core adds 10 to the shared counter one atomic increment at a time
+        let source = "
+                MOV R1 10
+                MOV R2 0x100
+            loop:
+                MOV R3 1
+                XADD R3 [R2]
+                DEC R1
+                JMPNZ loop
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut machine = VmBuilder::new().memory_size(1024).build_machine::<i32>(4);
+        machine.set_quantum(3);
+        assert!(machine.run(&program).is_ok());
+        assert_eq!(machine.memory().bytes()[0x100], 40);
+        assert_eq!(machine.halted(3), Some(true));
+        assert_eq!(machine.halted(4), None);
+    }
+
+    #[test]
+    fn test_machine_core_index() {
+        // each core stores its index in the word at the address of four times its index
+        let source = "
+                SHLI R1 R0 2
+                STR R0 [R1]
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut machine = VmBuilder::new().memory_size(16).build_machine::<i32>(3);
+        assert_eq!(machine.run(&program), Ok(9));
+        assert_eq!(
+            machine.memory().bytes()[..12],
+            [0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]
+        );
+        assert_eq!(machine.cpu(2).unwrap().get_register(1), Ok(8));
+    }
+
+    #[test]
+    fn test_machine_exit() {
+        // the core 0 loops forever, the core 1 exits and stops the machine
+        let source = "
+                DEC R0
+                JMPZ exit
+            loop:
+                JMP loop
+            exit:
+                EXIT 1
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut machine = VmBuilder::new().build_machine::<i32>(2);
+        assert!(machine.run_with_limit(&program, 100).is_ok());
+        assert_eq!(machine.halted(0), Some(true));
+    }
+
+    #[test]
+    fn test_machine_step_limit() {
+        let program = Assembler::new().assemble("loop: JMP loop").unwrap();
+        let mut machine = VmBuilder::new().build_machine::<i32>(2);
+        assert_eq!(
+            machine.run_with_limit(&program, 100),
+            Err(VmError::StepLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_machine_von_neumann() {
+        // the first core patches the immediate of the first MOV before the second core runs it
+        let source = "
+                MOV R2 1
+                MOV R1 5
+                STB R1 0x2
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut machine = VmBuilder::new()
+            .memory_size(64)
+            .von_neumann(0x0)
+            .build_machine::<i32>(2);
+        machine.set_quantum(10);
+        assert_eq!(machine.run(&program), Ok(8));
+        assert_eq!(machine.cpu(0).unwrap().get_register(2), Ok(1));
+        assert_eq!(machine.cpu(1).unwrap().get_register(2), Ok(5));
+    }
+}
@@ -1,14 +1,70 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::ops::Range;
+
+use super::device::{Device, MappedDevice};
 use super::error::{Result, VmError};
+use super::hexdump::HexDump;
+use super::pod::{Endianness, Pod};
+use super::protection::{self, Permission, Region};
+use super::replay::DeviceReads;
+use super::trace::MemoryAccess;
+use super::watchpoint::{Access, Watchpoint, WatchpointHit};
 
 /// The memory structure used by the VM.
 /// The memory has a fixed size and can store any type.
 /// The memory is byte-addressable.
 /// The memory is cleared to zero when created.
 /// The memory can be read from and written to.
-/// The memory access must be aligned to the size of the type.
+/// The memory access must be aligned to the size of the type, unless strict alignment is disabled.
 /// The memory access must be within the bounds of the memory.
+/// Accesses overlapping a watchpoint are recorded and can be retrieved with `take_watchpoint_hit`.
+/// Region descriptors restrict the accesses of the guest to the permissions of their regions,
+/// see `Region`.
+/// Devices can be mapped into ranges of addresses, inside or beyond the size of the memory,
+/// the accesses to these ranges are routed to the devices.
+/// Only the content of the memory is serialized, the watchpoints, the regions and the devices are
+/// settings of the host.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Memory {
     data: Vec<u8>,
+    /// Reject the accesses that are not aligned to the size of the type.
+    strict_alignment: bool,
+    /// The order of the bytes of the values read and written.
+    #[cfg_attr(feature = "serde", serde(default))]
+    endianness: Endianness,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    watchpoints: Vec<Watchpoint>,
+    /// The first access that triggered a watchpoint since the last call to `take_watchpoint_hit`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    watchpoint_hit: Cell<Option<WatchpointHit>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    devices: Vec<MappedDevice>,
+    /// The region descriptors, the memory is not protected when there is none.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    regions: Vec<Region>,
+    /// The accesses since the last call to `take_accesses`, `None` when they are not recorded.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    accesses: Option<Vec<MemoryAccess>>,
+    /// The number of reads and writes since the creation of the memory, for the gas accounting.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    reads: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    writes: u64,
+    /// The device reads recorded or replayed, see `VM::start_recording`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    device_reads: DeviceReads,
+    /// The bytes overwritten since the last call to `take_journal`, with their address,
+    /// `None` when they are not journaled.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    journal: Option<Vec<(usize, Vec<u8>)>>,
+    /// The range of the code of the program in the memory, in the Von Neumann mode.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    code_range: Option<Range<usize>>,
+    /// Set when a write overlaps the code since the last call to `take_code_written`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    code_written: bool,
 }
 
 impl Memory {
@@ -19,70 +75,508 @@ impl Memory {
     pub fn new(size: usize) -> Self {
         Memory {
             data: vec![0; size],
+            strict_alignment: true,
+            endianness: Endianness::Little,
+            watchpoints: Vec::new(),
+            watchpoint_hit: Cell::new(None),
+            devices: Vec::new(),
+            regions: Vec::new(),
+            accesses: None,
+            reads: 0,
+            writes: 0,
+            device_reads: DeviceReads::Off,
+            journal: None,
+            code_range: None,
+            code_written: false,
         }
     }
 
     /// Clear the memory by setting all values to zero.
+    /// The watchpoints and the regions are kept but any pending watchpoint hit is discarded.
+    /// The devices are kept and reset.
     pub fn clear(&mut self) {
         self.data.iter_mut().for_each(|x| *x = 0);
+        self.watchpoint_hit.set(None);
+        self.devices
+            .iter_mut()
+            .for_each(|mapped| mapped.device.reset());
     }
 
-    /// Read a value from memory at the specified address.
-    /// The address must be aligned to the size of the type `T`.
+    /// Enable or disable the alignment check of the accesses, enabled by default.
     ///
     /// # Parameters
-    /// - `address`: The address to read from.
+    /// - `strict`: Reject the accesses that are not aligned to the size of the type.
+    pub fn set_strict_alignment(&mut self, strict: bool) {
+        self.strict_alignment = strict;
+    }
+
+    /// Set the order of the bytes of the values read and written, little-endian by default.
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+
+    /// Get the order of the bytes of the values read and written.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Add a watchpoint on a range of the memory.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Remove all the watchpoints.
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Check if a watchpoint is set.
+    pub(crate) fn has_watchpoints(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    /// Add a region descriptor, protecting the memory: the accesses of the guest outside the
+    /// regions, or not allowed by their permissions, raise `VmError::ProtectionFault`.
+    /// When regions overlap, the last added one applies.
+    pub fn add_region(&mut self, region: Region) {
+        self.regions.push(region);
+    }
+
+    /// Remove all the region descriptors, the memory is no longer protected.
+    pub fn clear_regions(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Get the region descriptors, in the order they were added.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Check if the memory is protected by region descriptors.
+    pub(crate) fn is_protected(&self) -> bool {
+        !self.regions.is_empty()
+    }
+
+    /// Check that the regions allow the fetch of an instruction of `size` bytes at `address`.
     ///
-    /// # Returns
-    /// The value read from memory.
+    /// # Errors
+    /// Returns `VmError::ProtectionFault` if the execution is not allowed.
+    pub(crate) fn check_execute(&self, address: usize, size: usize) -> Result<()> {
+        self.check_permission(address, size, Permission::Execute)
+    }
+
+    /// Check that the regions allow an operation on `size` bytes at `address`.
+    fn check_permission(&self, address: usize, size: usize, permission: Permission) -> Result<()> {
+        if protection::allows(&self.regions, address, size, permission) {
+            Ok(())
+        } else {
+            Err(VmError::ProtectionFault {
+                address,
+                size,
+                permission,
+            })
+        }
+    }
+
+    /// Map a device into a range of addresses.
+    /// The range may overlap the memory, the device then hides the memory in this range.
+    ///
+    /// # Parameters
+    /// - `range`: The addresses handled by the device.
+    /// - `device`: The device receiving the accesses to the range.
     ///
     /// # Errors
-    /// Returns an error if the address is out of bounds or not aligned.
-    pub fn read<T>(&self, address: usize) -> Result<T>
-    where
-        T: Copy,
-    {
-        if address + std::mem::size_of::<T>() > self.data.len() {
-            return Err(VmError::MemoryOutOfBounds {
+    /// Returns an error if the range is empty or overlaps another device.
+    pub fn map_device(&mut self, range: Range<usize>, device: Box<dyn Device>) -> Result<()> {
+        self.map_device_with_interrupt(range, device, None)
+    }
+
+    /// Map a device into a range of addresses, with an interrupt line.
+    ///
+    /// # Parameters
+    /// - `range`: The addresses handled by the device.
+    /// - `device`: The device receiving the accesses to the range.
+    /// - `interrupt`: The interrupt line asserted when the device requests an interrupt.
+    ///
+    /// # Errors
+    /// Returns an error if the range is empty or overlaps another device.
+    pub fn map_device_with_interrupt(
+        &mut self,
+        range: Range<usize>,
+        device: Box<dyn Device>,
+        interrupt: Option<u8>,
+    ) -> Result<()> {
+        if range.is_empty() {
+            return Err(VmError::Other(format!("Empty device range {:?}", range)));
+        }
+        if self
+            .devices
+            .iter()
+            .any(|mapped| mapped.overlaps(range.start, range.len()))
+        {
+            return Err(VmError::Other(format!(
+                "Device range {:?} overlaps a mapped device",
+                range
+            )));
+        }
+        self.devices.push(MappedDevice {
+            range,
+            device,
+            interrupt,
+        });
+        Ok(())
+    }
+
+    /// Advance all the devices by one step.
+    ///
+    /// # Errors
+    /// Returns the first fault raised by a device after the step, see `Device::fault`.
+    pub fn tick_devices(&mut self) -> Result<()> {
+        self.devices
+            .iter_mut()
+            .for_each(|mapped| mapped.device.tick());
+        self.devices
+            .iter()
+            .find_map(|mapped| mapped.device.fault())
+            .map_or(Ok(()), Err)
+    }
+
+    /// Get the interrupt lines asserted by the devices.
+    pub fn asserted_interrupts(&self) -> impl Iterator<Item = u8> + '_ {
+        self.devices
+            .iter()
+            .filter(|mapped| mapped.device.interrupt())
+            .filter_map(|mapped| mapped.interrupt)
+    }
+
+    /// Get the device of type `D` mapped at an address.
+    ///
+    /// # Parameters
+    /// - `address`: Any address in the range of the device.
+    ///
+    /// # Returns
+    /// The device, or `None` if no device of type `D` is mapped at the address.
+    pub fn device<D: Device>(&self, address: usize) -> Option<&D> {
+        let mapped = self
+            .devices
+            .iter()
+            .find(|mapped| mapped.range.contains(&address))?;
+        (mapped.device.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    /// Get the device of type `D` mapped at an address, mutably.
+    ///
+    /// # Parameters
+    /// - `address`: Any address in the range of the device.
+    ///
+    /// # Returns
+    /// The device, or `None` if no device of type `D` is mapped at the address.
+    pub fn device_mut<D: Device>(&mut self, address: usize) -> Option<&mut D> {
+        let mapped = self
+            .devices
+            .iter_mut()
+            .find(|mapped| mapped.range.contains(&address))?;
+        (mapped.device.as_mut() as &mut dyn Any).downcast_mut()
+    }
+
+    /// Check if a device is mapped, the devices being advanced after each instruction.
+    #[cfg(feature = "jit")]
+    pub(crate) fn has_devices(&self) -> bool {
+        !self.devices.is_empty()
+    }
+
+    /// Remove all the devices.
+    pub fn clear_devices(&mut self) {
+        self.devices.clear();
+    }
+
+    /// Take the first access that triggered a watchpoint since the last call, if any.
+    pub fn take_watchpoint_hit(&mut self) -> Option<WatchpointHit> {
+        self.watchpoint_hit.take()
+    }
+
+    /// Enable or disable the recording of the accesses, retrieved with `take_accesses`.
+    pub fn set_access_recording(&mut self, record: bool) {
+        self.accesses = record.then(Vec::new);
+    }
+
+    /// Take the accesses recorded since the last call, empty when the recording is disabled.
+    pub fn take_accesses(&mut self) -> Vec<MemoryAccess> {
+        self.accesses
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Get the number of reads and writes since the creation of the memory.
+    pub fn access_counts(&self) -> (u64, u64) {
+        (self.reads, self.writes)
+    }
+
+    /// Get the device reads recorded or replayed by the memory.
+    pub(crate) fn device_reads_mut(&mut self) -> &mut DeviceReads {
+        &mut self.device_reads
+    }
+
+    /// Enable or disable the journal of the overwritten bytes, retrieved with `take_journal`.
+    pub(crate) fn set_journal(&mut self, journal: bool) {
+        self.journal = journal.then(Vec::new);
+    }
+
+    /// Take the bytes overwritten since the last call, in the order of the writes.
+    pub(crate) fn take_journal(&mut self) -> Vec<(usize, Vec<u8>)> {
+        self.journal
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Set the range of the code of the program in the memory, whose writes are reported by
+    /// `take_code_written`.
+    pub(crate) fn set_code_range(&mut self, range: Option<Range<usize>>) {
+        self.code_range = range;
+    }
+
+    /// Check if a write overlapped the code since the last call.
+    pub(crate) fn take_code_written(&mut self) -> bool {
+        std::mem::take(&mut self.code_written)
+    }
+
+    /// Save the bytes about to be overwritten when the journal is enabled, and note the writes
+    /// into the code.
+    fn journal(&mut self, address: usize, size: usize) {
+        if let Some(journal) = &mut self.journal {
+            journal.push((address, self.data[address..address + size].to_vec()));
+        }
+        if let Some(code) = &self.code_range {
+            self.code_written |= address < code.end && code.start < address + size;
+        }
+    }
+
+    /// Record the access if it triggers a watchpoint and no other hit is pending.
+    fn watch(&self, address: usize, size: usize, access: Access) {
+        if self.watchpoint_hit.get().is_none()
+            && self
+                .watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.triggers(address, size, access))
+        {
+            self.watchpoint_hit.set(Some(WatchpointHit {
                 address,
-                size: std::mem::size_of::<T>(),
-            });
-        } else if address % std::mem::align_of::<T>() != 0 {
-            return Err(VmError::MemoryNotAligned {
+                size,
+                access,
+            }));
+        }
+    }
+
+    /// Record the access when the recording is enabled.
+    fn record(&mut self, address: usize, size: usize, access: Access) {
+        if let Some(accesses) = &mut self.accesses {
+            accesses.push(MemoryAccess {
                 address,
-                size: std::mem::size_of::<T>(),
+                size,
+                access,
             });
         }
+    }
 
-        Ok(unsafe { *(self.data.as_ptr().add(address) as *const T) })
+    /// Check the bounds, the alignment and the permissions of an access and find the device
+    /// handling it. An access overlapping a device must be entirely within the range of the
+    /// device. The regions are not checked without a `permission`.
+    ///
+    /// # Returns
+    /// The index of the device handling the access, or `None` if the access targets the memory.
+    fn check_access(
+        &self,
+        address: usize,
+        size: usize,
+        align: usize,
+        permission: Option<Permission>,
+    ) -> Result<Option<usize>> {
+        let device = self
+            .devices
+            .iter()
+            .position(|mapped| mapped.overlaps(address, size));
+        let in_bounds = match device {
+            Some(index) => self.devices[index].contains(address, size),
+            None => address.saturating_add(size) <= self.data.len(),
+        };
+        if !in_bounds {
+            return Err(VmError::MemoryOutOfBounds { address, size });
+        } else if self.strict_alignment && !address.is_multiple_of(align) {
+            return Err(VmError::MemoryNotAligned { address, size });
+        }
+        if let Some(permission) = permission {
+            self.check_permission(address, size, permission)?;
+        }
+        Ok(device)
+    }
+
+    /// Read a value from memory at the specified address, from its bytes in the endianness of
+    /// the memory.
+    /// The address must be aligned to the size of the type `T` when strict alignment is enabled.
+    /// The read is routed to the device mapped at the address, if any.
+    ///
+    /// # Parameters
+    /// - `address`: The address to read from.
+    ///
+    /// # Returns
+    /// The value read from memory.
+    ///
+    /// # Errors
+    /// Returns an error if the address is out of bounds, not aligned or not readable in the
+    /// regions, or the error of the device.
+    pub fn read<T: Pod>(&mut self, address: usize) -> Result<T> {
+        self.read_with(address, Some(Permission::Read))
+    }
+
+    /// Read a value from memory like `read`, without checking the regions: the reads of the CPU
+    /// itself, like the handlers in the interrupt vector table, are not accesses of the guest.
+    ///
+    /// # Errors
+    /// Returns an error if the address is out of bounds or not aligned, or the error of the device.
+    pub(crate) fn read_privileged<T: Pod>(&mut self, address: usize) -> Result<T> {
+        self.read_with(address, None)
+    }
+
+    /// Read a value from memory, checking the regions for a `permission`.
+    fn read_with<T: Pod>(&mut self, address: usize, permission: Option<Permission>) -> Result<T> {
+        let size = std::mem::size_of::<T>();
+        let device = self.check_access(address, size, size, permission)?;
+
+        self.watch(address, size, Access::Read);
+        self.record(address, size, Access::Read);
+        self.reads += 1;
+        match device {
+            Some(index) => {
+                let mut bytes = match &mut self.device_reads {
+                    DeviceReads::Replay(reads) => match reads.pop_front() {
+                        Some((recorded, bytes)) if recorded == address => bytes,
+                        _ => return Err(VmError::ReplayDivergence),
+                    },
+                    _ => {
+                        let mapped = &mut self.devices[index];
+                        mapped.device.read(address - mapped.range.start, size)?
+                    }
+                };
+                bytes.resize(size, 0);
+                if let DeviceReads::Record(reads) = &mut self.device_reads {
+                    reads.push((address, bytes.clone()));
+                }
+                Ok(T::load(&bytes, self.endianness))
+            }
+            None => Ok(T::load(
+                &self.data[address..address + size],
+                self.endianness,
+            )),
+        }
     }
 
-    /// Write a value to memory at the specified address.
-    /// The address must be aligned to the size of the type `T`.
+    /// Write a value to memory at the specified address, as its bytes in the endianness of the
+    /// memory.
+    /// The address must be aligned to the size of the type `T` when strict alignment is enabled.
+    /// The write is routed to the device mapped at the address, if any.
     ///
     /// # Parameters
     /// - `address`: The address to write to.
     /// - `value`: The value to write.
     ///
     /// # Errors
-    /// Returns an error if the address is out of bounds or not aligned.
-    pub fn write<T>(&mut self, address: usize, value: T) -> Result<()> {
-        if address + std::mem::size_of::<T>() > self.data.len() {
+    /// Returns an error if the address is out of bounds, not aligned or not writable in the
+    /// regions, or the error of the device.
+    pub fn write<T: Pod>(&mut self, address: usize, value: T) -> Result<()> {
+        let size = std::mem::size_of::<T>();
+        let device = self.check_access(address, size, size, Some(Permission::Write))?;
+
+        self.watch(address, size, Access::Write);
+        self.record(address, size, Access::Write);
+        self.writes += 1;
+        match device {
+            Some(index) => {
+                let mapped = &mut self.devices[index];
+                let mut bytes = vec![0; size];
+                value.store(&mut bytes, self.endianness);
+                mapped.device.write(address - mapped.range.start, &bytes)
+            }
+            None => {
+                self.journal(address, size);
+                value.store(&mut self.data[address..address + size], self.endianness);
+                Ok(())
+            }
+        }
+    }
+
+    /// Get the content of the memory.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Read a value from the memory without side effect, to inspect its content.
+    /// Unlike `read`, the value is not read from the devices, the watchpoints are not triggered
+    /// and the access is neither counted nor checked for alignment.
+    ///
+    /// # Errors
+    /// Returns `VmError::MemoryOutOfBounds` if the value is not in the memory.
+    pub fn peek<T: Pod>(&self, address: usize) -> Result<T> {
+        let size = std::mem::size_of::<T>();
+        address
+            .checked_add(size)
+            .and_then(|end| self.data.get(address..end))
+            .map(|bytes| T::load(bytes, self.endianness))
+            .ok_or(VmError::MemoryOutOfBounds { address, size })
+    }
+
+    /// Get a range of the memory as a hexadecimal dump, see `HexDump`.
+    /// The range is truncated to the memory.
+    pub fn hexdump(&self, range: Range<usize>) -> HexDump<'_> {
+        let end = range.end.min(self.data.len());
+        let start = range.start.min(end);
+        HexDump::new(start, &self.data[start..end])
+    }
+
+    /// Dump a range of the memory in hexadecimal and ASCII, 16 bytes per line, see `HexDump`.
+    /// The range is truncated to the memory.
+    pub fn dump(&self, range: Range<usize>) -> String {
+        self.hexdump(range).to_string()
+    }
+
+    /// Copy a slice of bytes into the memory starting at the specified address.
+    /// The copy does not trigger watchpoints and is not routed to the devices.
+    ///
+    /// # Parameters
+    /// - `address`: The address of the first byte to write.
+    /// - `bytes`: The bytes to write.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes do not fit in the memory.
+    pub fn write_bytes(&mut self, address: usize, bytes: &[u8]) -> Result<()> {
+        if address.saturating_add(bytes.len()) > self.data.len() {
             return Err(VmError::MemoryOutOfBounds {
                 address,
-                size: std::mem::size_of::<T>(),
-            });
-        } else if address % std::mem::align_of::<T>() != 0 {
-            return Err(VmError::MemoryNotAligned {
-                address,
-                size: std::mem::size_of::<T>(),
+                size: bytes.len(),
             });
         }
+        self.journal(address, bytes.len());
+        self.data[address..address + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
 
-        unsafe {
-            *(self.data.as_mut_ptr().add(address) as *mut T) = value;
+    /// Set a range of the memory to a byte, like `write_bytes` with as many copies of the byte,
+    /// without allocating them.
+    ///
+    /// # Parameters
+    /// - `address`: The address of the first byte to write.
+    /// - `size`: The number of bytes to write.
+    /// - `value`: The byte written.
+    ///
+    /// # Errors
+    /// Returns an error if the range does not fit in the memory.
+    pub fn fill_bytes(&mut self, address: usize, size: usize, value: u8) -> Result<()> {
+        if address.saturating_add(size) > self.data.len() {
+            return Err(VmError::MemoryOutOfBounds { address, size });
         }
-
+        self.journal(address, size);
+        self.data[address..address + size].fill(value);
         Ok(())
     }
 
@@ -95,6 +589,34 @@ impl Memory {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vm::protection::Permissions;
+
+    /// A device with 4 registers of one byte, counting the reads of each register.
+    struct CounterDevice {
+        reads: [u8; 4],
+    }
+
+    impl Device for CounterDevice {
+        fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+            let bytes = self.reads[offset..offset + size].to_vec();
+            self.reads[offset..offset + size]
+                .iter_mut()
+                .for_each(|reads| *reads += 1);
+            Ok(bytes)
+        }
+
+        fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+            if offset != 0 {
+                return Err(VmError::Other("read-only register".to_string()));
+            }
+            self.reads[..data.len()].copy_from_slice(data);
+            Ok(())
+        }
+
+        fn reset(&mut self) {
+            self.reads = [0; 4];
+        }
+    }
 
     #[test]
     fn test_memory_create() {
@@ -108,6 +630,14 @@ mod tests {
 
         memory.write::<u32>(0, 0x12345678).unwrap();
         assert_eq!(memory.read::<u32>(0).unwrap(), 0x12345678);
+        // the values are stored as their little-endian bytes, whatever the host
+        assert_eq!(memory.bytes()[..4], [0x78, 0x56, 0x34, 0x12]);
+        memory.write::<f32>(8, -1.0).unwrap();
+        assert_eq!(memory.read::<u32>(8).unwrap(), 0xbf800000);
+        memory.set_strict_alignment(false);
+        memory.write::<i64>(13, -2).unwrap();
+        assert_eq!(memory.read::<i64>(13).unwrap(), -2);
+        assert_eq!(memory.read::<u8>(20).unwrap(), 0xff);
     }
 
     #[test]
@@ -139,4 +669,209 @@ mod tests {
         assert!(memory.write::<u16>(0, 0x1234).is_ok());
         assert!(memory.write::<u16>(1, 0x1234).is_err());
     }
+
+    #[test]
+    fn test_memory_not_strict_alignment() {
+        let mut memory = Memory::new(1024);
+        memory.set_strict_alignment(false);
+
+        assert!(memory.write::<u32>(1, 0x12345678).is_ok());
+        assert_eq!(memory.read::<u32>(1).unwrap(), 0x12345678);
+        assert_eq!(memory.read::<u8>(1).unwrap(), 0x78);
+    }
+
+    #[test]
+    fn test_memory_write_bytes() {
+        let mut memory = Memory::new(8);
+
+        memory.write_bytes(4, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(memory.bytes(), &[0, 0, 0, 0, 1, 2, 3, 4]);
+        assert_eq!(
+            memory.write_bytes(6, &[1, 2, 3]),
+            Err(VmError::MemoryOutOfBounds {
+                address: 6,
+                size: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_memory_fill_bytes() {
+        let mut memory = Memory::new(8);
+
+        memory.write_bytes(0, &[1; 8]).unwrap();
+        memory.fill_bytes(2, 4, 0).unwrap();
+        assert_eq!(memory.bytes(), &[1, 1, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(
+            memory.fill_bytes(4, usize::MAX, 0),
+            Err(VmError::MemoryOutOfBounds {
+                address: 4,
+                size: usize::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn test_memory_watchpoint() {
+        let mut memory = Memory::new(1024);
+        memory.add_watchpoint(Watchpoint::write(8..12));
+
+        memory.write::<u32>(4, 1).unwrap();
+        memory.read::<u32>(8).unwrap();
+        assert_eq!(memory.take_watchpoint_hit(), None);
+
+        memory.write::<u32>(8, 1).unwrap();
+        memory.write::<u16>(10, 1).unwrap();
+        assert_eq!(
+            memory.take_watchpoint_hit(),
+            Some(WatchpointHit {
+                address: 8,
+                size: 4,
+                access: Access::Write
+            })
+        );
+        assert_eq!(memory.take_watchpoint_hit(), None);
+
+        memory.clear_watchpoints();
+        memory.write::<u32>(8, 1).unwrap();
+        assert_eq!(memory.take_watchpoint_hit(), None);
+    }
+
+    #[test]
+    fn test_memory_peek() {
+        let mut memory = Memory::new(16);
+        memory.add_watchpoint(Watchpoint::access(0..16));
+        memory.write::<u16>(2, 0x1234).unwrap();
+        memory.take_watchpoint_hit();
+
+        assert_eq!(memory.peek::<u16>(2), Ok(0x1234));
+        assert_eq!(memory.peek::<u8>(3), Ok(0x12));
+        assert_eq!(memory.take_watchpoint_hit(), None);
+        assert_eq!(memory.access_counts(), (0, 1));
+        assert_eq!(
+            memory.peek::<u32>(14),
+            Err(VmError::MemoryOutOfBounds {
+                address: 14,
+                size: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_memory_dump() {
+        let mut memory = Memory::new(20);
+        memory.write_bytes(16, b"Forg").unwrap();
+        assert_eq!(
+            memory.dump(12..24),
+            "0000000c  00 00 00 00 46 6f 72 67                           |....Forg|\n"
+        );
+        assert_eq!(memory.hexdump(24..32).to_string(), "");
+    }
+
+    #[test]
+    fn test_memory_device() {
+        let mut memory = Memory::new(16);
+        memory
+            .map_device(0x100..0x104, Box::new(CounterDevice { reads: [0; 4] }))
+            .unwrap();
+
+        assert_eq!(memory.read::<u8>(0x101), Ok(0));
+        assert_eq!(memory.read::<u8>(0x101), Ok(1));
+        assert_eq!(memory.read::<u32>(0x100), Ok(0x00000200));
+        assert_eq!(memory.read::<u16>(0x102), Ok(0x0101));
+        memory.write::<u16>(0x100, 0x0a0b).unwrap();
+        assert_eq!(memory.read::<u16>(0x100), Ok(0x0a0b));
+        assert_eq!(
+            memory.write::<u8>(0x102, 1),
+            Err(VmError::Other("read-only register".to_string()))
+        );
+        assert_eq!(memory.bytes(), &[0; 16]);
+
+        memory.clear();
+        assert_eq!(memory.read::<u32>(0x100), Ok(0));
+        assert_eq!(
+            memory
+                .device::<CounterDevice>(0x103)
+                .map(|device| device.reads),
+            Some([1, 1, 1, 1])
+        );
+        memory.device_mut::<CounterDevice>(0x100).unwrap().reads = [0; 4];
+        assert!(memory.device::<CounterDevice>(0x104).is_none());
+    }
+
+    #[test]
+    fn test_memory_device_bounds() {
+        let mut memory = Memory::new(16);
+        memory
+            .map_device(8..12, Box::new(CounterDevice { reads: [0; 4] }))
+            .unwrap();
+
+        assert_eq!(memory.read::<u32>(8), Ok(0));
+        assert_eq!(memory.read::<u32>(4), Ok(0));
+        assert_eq!(
+            memory.read::<u32>(10),
+            Err(VmError::MemoryOutOfBounds {
+                address: 10,
+                size: 4
+            })
+        );
+        assert_eq!(
+            memory.read::<u16>(9),
+            Err(VmError::MemoryNotAligned {
+                address: 9,
+                size: 2
+            })
+        );
+        assert!(memory
+            .map_device(10..14, Box::new(CounterDevice { reads: [0; 4] }))
+            .is_err());
+        assert!(memory
+            .map_device(12..12, Box::new(CounterDevice { reads: [0; 4] }))
+            .is_err());
+
+        memory.clear_devices();
+        assert_eq!(memory.read::<u32>(12), Ok(0));
+    }
+
+    #[test]
+    fn test_memory_regions() {
+        let mut memory = Memory::new(0x100);
+        memory.add_region(Region::new(0x0..0x40, Permissions::READ_EXECUTE));
+        memory.add_region(Region::new(0x40..0x80, Permissions::READ_WRITE));
+        assert!(memory.write::<u32>(0x40, 7).is_ok());
+        assert_eq!(memory.read::<u32>(0x40), Ok(7));
+        assert_eq!(
+            memory.write::<u32>(0x3c, 7),
+            Err(VmError::ProtectionFault {
+                address: 0x3c,
+                size: 4,
+                permission: Permission::Write
+            })
+        );
+        assert_eq!(
+            memory.read::<u8>(0x80),
+            Err(VmError::ProtectionFault {
+                address: 0x80,
+                size: 1,
+                permission: Permission::Read
+            })
+        );
+        // the bounds and the alignment are checked first
+        assert_eq!(
+            memory.read::<u16>(0x81),
+            Err(VmError::MemoryNotAligned {
+                address: 0x81,
+                size: 2
+            })
+        );
+        assert!(memory.check_execute(0x3c, 4).is_ok());
+        assert!(memory.check_execute(0x3e, 4).is_err());
+        // the host is not restricted
+        assert!(memory.write_bytes(0x0, &[1, 2, 3, 4]).is_ok());
+
+        memory.clear();
+        assert_eq!(memory.regions().len(), 2);
+        memory.clear_regions();
+        assert!(memory.write::<u32>(0x80, 7).is_ok());
+    }
 }
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use super::error::{Result, VmError};
+use super::memory::Memory;
+use super::pod::Pod;
+
+/// The size of a page and of a physical frame, in bytes.
+pub const PAGE_SIZE: usize = 0x1000;
+/// The number of translations cached by the TLB.
+pub const TLB_ENTRIES: usize = 64;
+/// The number of entries of a page directory or of a page table.
+pub const TABLE_ENTRIES: usize = 1024;
+/// Page table entry flag: the entry maps a table or a frame.
+pub const PTE_PRESENT: u32 = 0x1;
+/// Page table entry flag: the pages mapped by the entry can be written.
+pub const PTE_WRITABLE: u32 = 0x2;
+
+/// The bits of a page table entry holding the physical address of the table or of the frame.
+const PTE_FRAME: u32 = !(PAGE_SIZE as u32 - 1);
+
+/// A translation cached by the TLB: the physical frame of a virtual page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Translation {
+    frame: usize,
+    writable: bool,
+}
+
+/// Paging memory management unit of a CPU, translating the virtual addresses of the
+/// memory instructions into physical addresses.
+///
+/// The page tables are stored in the guest memory, in a two-level format for 32-bit virtual
+/// addresses: the bits `22..32` index the page directory, the bits `12..22` index a page table
+/// and the bits `0..12` are the offset in the page. The directory and the tables are pages of
+/// `1024` little-endian `u32` entries. The bits `12..32` of an entry hold the physical address
+/// of the page table or of the frame, the bit `0` is `PTE_PRESENT` and the bit `1` is
+/// `PTE_WRITABLE`. A page can be written only if its directory entry and its table entry are
+/// both writable.
+///
+/// The page tables are read at physical addresses, without watchpoints or devices. The
+/// translations are cached in a TLB that must be flushed with `TLBFLUSH` after changing the
+/// page tables. An access to a page that is not present, a write to a read-only page and an
+/// access beyond the 32-bit virtual address space fail with `VmError::PageFault`.
+///
+/// The MMU is disabled when the CPU is initialized: the addresses are physical until the
+/// guest enables the paging with `MMUON`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mmu {
+    /// The physical address of the page directory, `None` when the paging is disabled.
+    directory: Option<usize>,
+    /// The cached translations, indexed by virtual page number.
+    tlb: HashMap<usize, Translation>,
+    /// The virtual address of the last page fault.
+    fault_address: usize,
+}
+
+impl Mmu {
+    /// Create a disabled MMU with an empty TLB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable the paging with a page directory and flush the TLB.
+    ///
+    /// # Parameters
+    /// - `directory`: The physical address of the page directory.
+    pub fn enable(&mut self, directory: usize) {
+        self.directory = Some(directory);
+        self.flush();
+    }
+
+    /// Disable the paging and flush the TLB, the addresses become physical.
+    pub fn disable(&mut self) {
+        self.directory = None;
+        self.flush();
+    }
+
+    /// Get the physical address of the page directory, `None` when the paging is disabled.
+    pub fn directory(&self) -> Option<usize> {
+        self.directory
+    }
+
+    /// Drop all the translations cached by the TLB.
+    pub fn flush(&mut self) {
+        self.tlb.clear();
+    }
+
+    /// Get the number of translations cached by the TLB.
+    pub fn tlb_len(&self) -> usize {
+        self.tlb.len()
+    }
+
+    /// Get the virtual address of the last page fault, `0` if no page fault happened.
+    pub fn fault_address(&self) -> usize {
+        self.fault_address
+    }
+
+    /// Translate the virtual address of a memory access into a physical address.
+    /// The address is returned unchanged when the paging is disabled.
+    ///
+    /// An access crossing a page boundary must be mapped to contiguous frames.
+    ///
+    /// # Parameters
+    /// - `memory`: The memory holding the page tables.
+    /// - `address`: The virtual address of the access.
+    /// - `size`: The size of the access, in bytes.
+    /// - `write`: `true` for a write access.
+    ///
+    /// # Errors
+    /// Returns `VmError::PageFault` if a page of the access is not mapped or is read-only for a
+    /// write, and `VmError::MemoryOutOfBounds` if a page table is outside the memory.
+    pub fn translate(
+        &mut self,
+        memory: &Memory,
+        address: usize,
+        size: usize,
+        write: bool,
+    ) -> Result<usize> {
+        let Some(directory) = self.directory else {
+            return Ok(address);
+        };
+        let physical = self.translate_page(memory, directory, address, write)?;
+        let last = address.saturating_add(size.saturating_sub(1));
+        if last / PAGE_SIZE != address / PAGE_SIZE {
+            let last_physical = self.translate_page(memory, directory, last, write)?;
+            if last_physical != physical + (last - address) {
+                return Err(self.fault(last, write));
+            }
+        }
+        Ok(physical)
+    }
+
+    /// Translate a virtual address through the TLB, walking the page tables on a miss.
+    fn translate_page(
+        &mut self,
+        memory: &Memory,
+        directory: usize,
+        address: usize,
+        write: bool,
+    ) -> Result<usize> {
+        let page = address / PAGE_SIZE;
+        let translation = match self.tlb.get(&page) {
+            Some(translation) => *translation,
+            None => {
+                let Some(translation) = walk(memory, directory, page)? else {
+                    return Err(self.fault(address, write));
+                };
+                if self.tlb.len() >= TLB_ENTRIES {
+                    // evict any translation, the TLB has no notion of age
+                    if let Some(evicted) = self.tlb.keys().next().copied() {
+                        self.tlb.remove(&evicted);
+                    }
+                }
+                self.tlb.insert(page, translation);
+                translation
+            }
+        };
+        if write && !translation.writable {
+            return Err(self.fault(address, write));
+        }
+        Ok(translation.frame + address % PAGE_SIZE)
+    }
+
+    /// Record a page fault and build its error.
+    fn fault(&mut self, address: usize, write: bool) -> VmError {
+        self.fault_address = address;
+        VmError::PageFault { address, write }
+    }
+}
+
+/// Walk the page tables to find the frame of a virtual page.
+///
+/// # Returns
+/// The translation, or `None` if the page is not mapped.
+fn walk(memory: &Memory, directory: usize, page: usize) -> Result<Option<Translation>> {
+    if page >= TABLE_ENTRIES * TABLE_ENTRIES {
+        return Ok(None);
+    }
+    let directory_entry = read_entry(memory, directory, page / TABLE_ENTRIES)?;
+    if directory_entry & PTE_PRESENT == 0 {
+        return Ok(None);
+    }
+    let table = (directory_entry & PTE_FRAME) as usize;
+    let table_entry = read_entry(memory, table, page % TABLE_ENTRIES)?;
+    if table_entry & PTE_PRESENT == 0 {
+        return Ok(None);
+    }
+    Ok(Some(Translation {
+        frame: (table_entry & PTE_FRAME) as usize,
+        writable: directory_entry & table_entry & PTE_WRITABLE != 0,
+    }))
+}
+
+/// Read an entry of a page directory or of a page table at its physical address, in the
+/// endianness of the memory.
+fn read_entry(memory: &Memory, table: usize, index: usize) -> Result<u32> {
+    let address = table.saturating_add(index * 4);
+    memory
+        .bytes()
+        .get(address..address.saturating_add(4))
+        .map(|bytes| u32::load(bytes, memory.endianness()))
+        .ok_or(VmError::MemoryOutOfBounds { address, size: 4 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Map a virtual page to a frame with a directory at `0x0` and a single page table at `0x1000`.
+    fn map(memory: &mut Memory, page: usize, frame: usize, flags: u32) {
+        let directory_entry = 0x1000 | PTE_PRESENT | PTE_WRITABLE;
+        memory
+            .write_bytes((page / TABLE_ENTRIES) * 4, &directory_entry.to_le_bytes())
+            .unwrap();
+        let table_entry = frame as u32 | flags;
+        memory
+            .write_bytes(
+                0x1000 + (page % TABLE_ENTRIES) * 4,
+                &table_entry.to_le_bytes(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_mmu_disabled() {
+        let memory = Memory::new(16);
+        let mut mmu = Mmu::new();
+        assert_eq!(
+            mmu.translate(&memory, 0x1234_5678, 4, true),
+            Ok(0x1234_5678)
+        );
+        assert_eq!(mmu.tlb_len(), 0);
+    }
+
+    #[test]
+    fn test_mmu_translate() {
+        let mut memory = Memory::new(0x4000);
+        map(&mut memory, 0x5, 0x3000, PTE_PRESENT | PTE_WRITABLE);
+        map(&mut memory, 0x6, 0x2000, PTE_PRESENT);
+        let mut mmu = Mmu::new();
+        mmu.enable(0x0);
+
+        assert_eq!(mmu.translate(&memory, 0x5123, 4, true), Ok(0x3123));
+        assert_eq!(mmu.translate(&memory, 0x6ffc, 4, false), Ok(0x2ffc));
+        assert_eq!(mmu.tlb_len(), 2);
+        assert_eq!(
+            mmu.translate(&memory, 0x6000, 1, true),
+            Err(VmError::PageFault {
+                address: 0x6000,
+                write: true
+            })
+        );
+        assert_eq!(
+            mmu.translate(&memory, 0x7000, 1, false),
+            Err(VmError::PageFault {
+                address: 0x7000,
+                write: false
+            })
+        );
+        assert_eq!(mmu.fault_address(), 0x7000);
+        assert_eq!(
+            mmu.translate(&memory, 0x1_0000_0000, 1, false),
+            Err(VmError::PageFault {
+                address: 0x1_0000_0000,
+                write: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_mmu_page_crossing() {
+        let mut memory = Memory::new(0x4000);
+        map(&mut memory, 0x5, 0x2000, PTE_PRESENT);
+        map(&mut memory, 0x6, 0x3000, PTE_PRESENT);
+        map(&mut memory, 0x7, 0x1000, PTE_PRESENT);
+        let mut mmu = Mmu::new();
+        mmu.enable(0x0);
+
+        // pages 5 and 6 are in contiguous frames, pages 6 and 7 are not
+        assert_eq!(mmu.translate(&memory, 0x5ffe, 4, false), Ok(0x2ffe));
+        assert_eq!(
+            mmu.translate(&memory, 0x6ffe, 4, false),
+            Err(VmError::PageFault {
+                address: 0x7001,
+                write: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_mmu_tlb_flush() {
+        let mut memory = Memory::new(0x4000);
+        map(&mut memory, 0x0, 0x2000, PTE_PRESENT);
+        let mut mmu = Mmu::new();
+        mmu.enable(0x0);
+        assert_eq!(mmu.translate(&memory, 0x10, 4, false), Ok(0x2010));
+
+        // the stale translation is used until the TLB is flushed
+        map(&mut memory, 0x0, 0x3000, PTE_PRESENT);
+        assert_eq!(mmu.translate(&memory, 0x10, 4, false), Ok(0x2010));
+        mmu.flush();
+        assert_eq!(mmu.translate(&memory, 0x10, 4, false), Ok(0x3010));
+    }
+
+    #[test]
+    fn test_mmu_tlb_capacity() {
+        let mut memory = Memory::new(0x4000);
+        for page in 0..2 * TLB_ENTRIES {
+            map(&mut memory, page, 0x2000, PTE_PRESENT);
+        }
+        let mut mmu = Mmu::new();
+        mmu.enable(0x0);
+        for page in 0..2 * TLB_ENTRIES {
+            assert_eq!(
+                mmu.translate(&memory, page * PAGE_SIZE, 1, false),
+                Ok(0x2000)
+            );
+        }
+        assert_eq!(mmu.tlb_len(), TLB_ENTRIES);
+    }
+
+    #[test]
+    fn test_mmu_table_out_of_bounds() {
+        let memory = Memory::new(0x100);
+        let mut mmu = Mmu::new();
+        mmu.enable(0x1000);
+        assert_eq!(
+            mmu.translate(&memory, 0x0, 4, false),
+            Err(VmError::MemoryOutOfBounds {
+                address: 0x1000,
+                size: 4
+            })
+        );
+    }
+}
@@ -1,33 +1,138 @@
+pub mod builder;
+pub mod clock;
+pub mod console;
+pub mod control;
+pub mod cost;
+pub mod coverage;
 pub mod cpu;
-pub mod decoder;
-pub mod error;
-pub mod hardware_config;
-pub mod instructions;
+pub mod crypto;
+mod decode_cache;
+pub mod device;
+pub mod disassembler;
+pub mod fixed;
+pub mod framebuffer;
+pub mod gas;
+pub mod heap;
+pub mod hexdump;
+pub mod history;
+pub mod hook;
+pub mod interrupt;
+#[cfg(feature = "jit")]
+mod jit;
+pub mod keyboard;
+pub mod layout;
+pub mod loader;
+pub mod machine;
 pub mod memory;
-pub mod program;
+pub mod mmu;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod optimizer;
+pub mod port;
+pub mod profiler;
+pub mod random;
+pub mod reference;
+pub mod replay;
+pub mod run_async;
+pub mod snapshot;
 pub mod stack;
+pub mod stdio;
+pub mod syscall;
+pub mod termination;
+pub mod thread;
+pub mod timer;
+pub mod trace;
+pub mod watchdog;
 
-/// Virtual Machine (VM) designed for 32-bit architecture operations.
+// the instruction set, shared with the assembler of the `forge_asm!` macro
+pub use forge_vm_asm::isa::{
+    counters, debug_info, decoder, encoder, error, fuzz, hardware_config, instructions, pod,
+    program, protection, verifier, watchpoint, word,
+};
+
+use word::Word;
+
+/// Virtual Machine (VM) generic over the data word of its architecture.
 ///
 /// # Generics:
-/// - `T`: Represents the data type for the stack and CPU operations, e.g., `i32`.
-pub struct VM<T> {
+/// - `T`: Represents the data type for the stack and CPU operations, e.g., `i32` or `i64`.
+///   Any type implementing `Word` can be used, the addresses are the unsigned integers
+///   of the same size, e.g., `u32` for `i32`.
+pub struct VM<T: Word> {
     stack: stack::Stack<T>,
     memory: memory::Memory,
     cpu: cpu::CPU<T>,
     steps: u128,
+    step_limit: u128,
+    /// The cycles consumed by the run, see `cost_model`.
+    cycles: u128,
+    cycle_limit: u128,
+    /// The interrupts delivered by the run, see `Counter::InterruptsDelivered`.
+    interrupts_delivered: u128,
+    /// The cost of the instructions, see `VmBuilder::cost_model`.
+    cost_model: cost::CostModel,
+    /// The gas consumed by the run, see `run_with_gas`.
+    gas_used: u64,
+    gas_limit: u64,
+    gas_schedule: gas::GasSchedule,
+    syscalls: syscall::SyscallTable<T>,
+    ports: port::PortBus<T>,
+    interrupts: interrupt::InterruptController,
+    threads: thread::Scheduler<T>,
+    /// The address of the program in the memory in the Von Neumann mode, see `VmBuilder::von_neumann`.
+    program_base: Option<usize>,
+    /// The layout of the memory, see `VmBuilder::layout`.
+    layout: Option<layout::MemoryLayout>,
+    /// The images loaded with `load_at`, with their address, copied into the memory at each run.
+    images: Vec<(usize, Vec<u8>)>,
+    /// The debug information of the program, see `set_debug_info`.
+    debug_info: Option<debug_info::DebugInfo>,
+    /// The program loaded with `load_program`, executed by `step`.
+    program: program::Program<'static>,
+    /// The tracer of the executed instructions, see `set_tracer`.
+    tracer: Option<Box<dyn trace::Tracer<T>>>,
+    /// The hook called around each instruction, see `set_hook`.
+    hook: Option<hook::Hook<T>>,
+    /// The address of the instruction paused by the hook in the `Pre` phase, which is
+    /// executed without calling the hook again when the execution resumes.
+    paused_at: Option<usize>,
+    /// Whether the instruction at the program counter is counted in `steps`, which is not the
+    /// case of an instruction faulting before its execution, see `fault`.
+    counted: bool,
+    /// The exit code of the terminated program, `None` while it is running.
+    exit_code: Option<T>,
+    /// The profile of the current run, `None` when the profiling is disabled.
+    profile: Option<profiler::Profile>,
+    /// The coverage of the runs, `None` when the coverage is disabled.
+    coverage: Option<coverage::Coverage>,
+    /// The controller pausing the VM from another thread, see `set_controller`.
+    controller: Option<control::VmController>,
+    /// The recording or the replay of the nondeterministic inputs, see `start_recording`.
+    replay: replay::Replay<T>,
+    /// The states before the last instructions, `None` when the history is disabled.
+    history: Option<history::History<T>>,
+    /// The instructions of the program decoded by `step`.
+    decoded: decode_cache::DecodeCache<T>,
+    /// Whether the `run` functions execute the superinstructions, see
+    /// `VmBuilder::superinstructions`.
+    superinstructions: bool,
+    /// The just-in-time compiler of the `run` functions, `None` when it is disabled or the word
+    /// is unsigned, see `VmBuilder::jit`.
+    #[cfg(feature = "jit")]
+    jit: Option<jit::Jit<T>>,
 }
 
-/// Implementation specific for 32-bit integers.
-impl VM<i32> {
+/// Implementation generic over the data word of the architecture.
+impl<T: Word> VM<T> {
     /// Constructs a new instance of the VM.
+    /// The other hardware parameters take their default value, use `VmBuilder` to set them.
     ///
     /// # Parameters:
     /// - `stack_capacity`: Maximum number of elements the stack can hold.
     /// - `memory_size`: Size of the memory in bytes.
     ///
     /// # Returns:
-    /// A new instance of `VM<i32>`
+    /// A new instance of `VM<T>`
     ///
     /// # Example:
     /// ```
@@ -36,13 +141,10 @@ impl VM<i32> {
     /// let mut vm = VM::<i32>::new(1024, 1024);
     /// ```
     pub fn new(stack_capacity: usize, memory_size: usize) -> Self {
-        log::debug!("Creating new VM...");
-        Self {
-            stack: stack::Stack::<i32>::new(stack_capacity),
-            memory: memory::Memory::new(memory_size),
-            cpu: cpu::CPU::<i32>::new(),
-            steps: 0,
-        }
+        builder::VmBuilder::new()
+            .stack_capacity(stack_capacity)
+            .memory_size(memory_size)
+            .build()
     }
 
     /// Runs the VM with a given program.
@@ -51,103 +153,3838 @@ impl VM<i32> {
     /// - `program`: Byte array representing the machine code to execute.
     ///
     /// # Returns:
-    /// - `Ok(u128)`: Total number of steps executed upon successful completion.
-    /// - `Err(VmError)`: Error if an issue occurred during execution.
+    /// - `Ok(Termination)`: Total number of steps executed and exit code of the program upon
+    ///   successful completion, see `Termination`.
+    /// - `Err(ExecutionError)`: The error, with the faulting instruction, if an issue occurred during execution.
     ///
     /// # Example:
     /// ```
+    /// use forge_vm::vm::termination::Termination;
     /// use forge_vm::VM;
     /// let mut vm = VM::<i32>::new(1024, 1024); // Create a new VM
     /// let program = vec![0x00, 0x00, 0xff]; // NOP, NOP, HLT
-    /// assert_eq!(vm.run(&program), Ok(3));
+    /// assert_eq!(vm.run(&program), Ok(Termination { steps: 3, exit_code: 0 }));
+    /// ```
+    pub fn run(
+        &mut self,
+        program: &[u8],
+    ) -> Result<termination::Termination<T>, error::ExecutionError<T>> {
+        self.run_with_limit(program, u128::MAX)
+    }
+
+    /// Runs the VM with a given program and a maximum number of steps.
+    /// This guarantees the termination of untrusted programs.
+    ///
+    /// # Parameters:
+    /// - `program`: Byte array representing the machine code to execute.
+    /// - `max_steps`: Maximum number of steps the program may execute, including `HLT`.
+    ///
+    /// # Returns:
+    /// - `Ok(Termination)`: Total number of steps executed and exit code upon successful completion.
+    /// - `VmError::StepLimitExceeded`: If the program did not halt within `max_steps` steps.
+    /// - `Err(ExecutionError)`: The error, with the faulting instruction, if another issue occurred.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::{Instruction, VmError, VM};
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// let program = vec![0x12, 0x00, 0x00, 0x00, 0x00]; // JMP 0x0
+    /// let error = vm.run_with_limit(&program, 100).unwrap_err();
+    /// assert_eq!(error.source, VmError::StepLimitExceeded);
+    /// assert_eq!(error.step, 101);
+    /// assert_eq!(error.instruction, Some(Instruction::JMP { address: 0 }));
+    /// ```
+    pub fn run_with_limit(
+        &mut self,
+        program: &[u8],
+        max_steps: u128,
+    ) -> Result<termination::Termination<T>, error::ExecutionError<T>> {
+        self.run_program_with_limit(&program::Program::new(program), max_steps)
+    }
+
+    /// Runs the VM with a given program and a budget of cycles, the cost of the instructions
+    /// being given by the cost model of the VM, see `VmBuilder::cost_model`.
+    ///
+    /// # Parameters:
+    /// - `program`: Byte array representing the machine code to execute.
+    /// - `budget`: Maximum number of cycles the program may consume, including `HLT`.
+    ///
+    /// # Returns:
+    /// - `Ok(RunStats)`: The steps executed and the cycles consumed upon successful completion.
+    /// - `VmError::CycleBudgetExceeded`: If the program did not halt within `budget` cycles.
+    /// - `Err(ExecutionError)`: The error, with the faulting instruction, if another issue occurred.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::cost::RunStats;
+    /// use forge_vm::{VmError, VM};
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// let program = vec![0x00, 0x00, 0xff]; // NOP, NOP, HLT
+    /// assert_eq!(vm.run_with_cycle_budget(&program, 3), Ok(RunStats { steps: 3, cycles: 3 }));
+    /// assert_eq!(
+    ///     vm.run_with_cycle_budget(&program, 2).map_err(|error| error.source),
+    ///     Err(VmError::CycleBudgetExceeded)
+    /// );
+    /// ```
+    pub fn run_with_cycle_budget(
+        &mut self,
+        program: &[u8],
+        budget: u128,
+    ) -> Result<cost::RunStats, error::ExecutionError<T>> {
+        self.run_program_with_cycle_budget(&program::Program::new(program), budget)
+    }
+
+    /// Runs the VM with a program from its entry point and a budget of cycles.
+    /// See `run_program` and `run_with_cycle_budget`.
+    pub fn run_program_with_cycle_budget(
+        &mut self,
+        program: &program::Program,
+        budget: u128,
+    ) -> Result<cost::RunStats, error::ExecutionError<T>> {
+        self.load(program).map_err(|error| self.fault(error))?;
+        self.cycle_limit = budget;
+        self.execute(program)?;
+        Ok(cost::RunStats {
+            steps: self.steps,
+            cycles: self.cycles,
+        })
+    }
+
+    /// Runs the VM with a given program and a limit of gas, the gas of the instructions, the
+    /// memory accesses and the stack operations being given by the gas schedule of the VM,
+    /// see `VmBuilder::gas_schedule`. This guarantees a fair accounting of untrusted programs.
+    ///
+    /// # Parameters:
+    /// - `program`: Byte array representing the machine code to execute.
+    /// - `gas`: Maximum gas the program may consume.
+    ///
+    /// # Returns:
+    /// - `Ok(u64)`: The gas used upon successful completion.
+    /// - `VmError::OutOfGas`: If the program consumed more than `gas`.
+    /// - `Err(ExecutionError)`: The error, with the faulting instruction, if another issue occurred.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::{VmError, VM};
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// let program = vec![0x03, 0x00, 0x10, 0x00, 0x00, 0x00, 0xff]; // ST R0 0x10, HLT
+    /// assert_eq!(vm.run_with_gas(&program, 3), Ok(3));
+    /// assert_eq!(
+    ///     vm.run_with_gas(&program, 2).map_err(|error| error.source),
+    ///     Err(VmError::OutOfGas)
+    /// );
+    /// ```
+    pub fn run_with_gas(
+        &mut self,
+        program: &[u8],
+        gas: u64,
+    ) -> Result<u64, error::ExecutionError<T>> {
+        self.run_program_with_gas(&program::Program::new(program), gas)
+    }
+
+    /// Runs the VM with a program from its entry point and a limit of gas.
+    /// See `run_program` and `run_with_gas`.
+    pub fn run_program_with_gas(
+        &mut self,
+        program: &program::Program,
+        gas: u64,
+    ) -> Result<u64, error::ExecutionError<T>> {
+        self.load(program).map_err(|error| self.fault(error))?;
+        self.gas_limit = gas;
+        self.execute(program)?;
+        Ok(self.gas_used)
+    }
+
+    /// Runs the VM with a given program asynchronously, without blocking the thread of the
+    /// executor: the returned future executes a slice of the program at each poll, see
+    /// `run_async::RunFuture`. The run stops with `VmError::Cancelled` once the token is
+    /// cancelled.
+    ///
+    /// # Parameters:
+    /// - `program`: Byte array representing the machine code to execute.
+    /// - `cancel`: The token cancelling the run.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::run_async::CancelToken;
+    /// use forge_vm::vm::error::ExecutionError;
+    /// use forge_vm::vm::termination::Termination;
+    /// use forge_vm::VM;
+    ///
+    /// async fn run_cancelled(vm: &mut VM<i32>) -> Result<Termination<i32>, ExecutionError<i32>> {
+    ///     let token = CancelToken::new();
+    ///     token.cancel();
+    ///     vm.run_async(&[0x12, 0x00, 0x00, 0x00, 0x00], token).await // JMP 0x0
+    /// }
+    /// ```
+    pub fn run_async(
+        &mut self,
+        program: &[u8],
+        cancel: run_async::CancelToken,
+    ) -> run_async::RunFuture<'_, T> {
+        let error = self.load_program(&program::Program::new(program)).err();
+        run_async::RunFuture::new(self, error, cancel)
+    }
+
+    /// Runs the VM with a program from its entry point, after loading its data segments.
+    ///
+    /// The initialized data is copied into the memory at the data address of the program and the
+    /// BSS segment following it is zeroed. In the Von Neumann mode, the entry point is an offset
+    /// from the address of the program in the memory.
+    ///
+    /// # Parameters:
+    /// - `program`: The program to execute, e.g. parsed from a `.fvm` image.
+    ///
+    /// # Returns:
+    /// - `Ok(Termination)`: Total number of steps executed and exit code upon successful completion.
+    /// - `VmError::MemoryOutOfBounds`: If the data segments do not fit in the memory.
+    /// - `Err(ExecutionError)`: The error, with the faulting instruction, if another issue occurred.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::program::Program;
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// let program = Program::new(&[0x00, 0x00, 0xff]).with_entry(1); // NOP, NOP, HLT
+    /// assert_eq!(vm.run_program(&program).map(|termination| termination.steps), Ok(2));
     /// ```
-    pub fn run(&mut self, program: &[u8]) -> Result<u128, error::VmError> {
+    pub fn run_program(
+        &mut self,
+        program: &program::Program,
+    ) -> Result<termination::Termination<T>, error::ExecutionError<T>> {
+        self.run_program_with_limit(program, u128::MAX)
+    }
+
+    /// Runs the VM with a program from its entry point and a maximum number of steps.
+    /// See `run_program` and `run_with_limit`.
+    pub fn run_program_with_limit(
+        &mut self,
+        program: &program::Program,
+        max_steps: u128,
+    ) -> Result<termination::Termination<T>, error::ExecutionError<T>> {
         log::info!("Running program...");
+        self.load(program).map_err(|error| self.fault(error))?;
+        self.step_limit = max_steps;
+        self.execute(program)?;
+        log::info!("Program executed successfully in {} steps.", self.steps);
+        Ok(self.termination())
+    }
+
+    /// Get the termination of the program terminated by the last run or step.
+    fn termination(&self) -> termination::Termination<T> {
+        termination::Termination {
+            steps: self.steps,
+            exit_code: self.exit_code.unwrap_or(T::zero()),
+        }
+    }
+
+    /// Get the exit code of the program, set by its final `HLT` or `EXIT`.
+    /// Returns `None` if the program has not terminated: it is still executed with `step`, or
+    /// its run stopped with an error.
+    pub fn exit_code(&self) -> Option<T> {
+        self.exit_code
+    }
+
+    /// Load a program to execute it instruction by instruction with `step`.
+    ///
+    /// The VM is reset as by `run_program`: the memory is cleared, the images and the data
+    /// segments of the program are loaded, and the program counter is set to the entry point.
+    ///
+    /// # Parameters:
+    /// - `program`: The program to execute.
+    ///
+    /// # Errors:
+    /// Returns `VmError::MemoryOutOfBounds` if the program does not fit in the memory.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::program::Program;
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.load_program(&Program::new(&[0x0e, 0x00, 0xff])).unwrap(); // INC R0, HLT
+    /// assert_eq!(vm.step(), Ok(false));
+    /// assert_eq!(vm.pc(), 2);
+    /// assert_eq!(vm.step(), Ok(true));
+    /// ```
+    pub fn load_program(&mut self, program: &program::Program) -> Result<(), error::VmError> {
+        self.load(program)?;
+        self.program = program.clone().into_owned();
+        Ok(())
+    }
+
+    /// Reset the VM and load a program as `load_program`, without keeping a copy of its code:
+    /// the run functions execute the program they borrow, see `execute`.
+    fn load(&mut self, program: &program::Program) -> Result<(), error::VmError> {
+        self.program = program::Program::default();
         self.steps = 0;
+        self.step_limit = u128::MAX;
+        self.cycles = 0;
+        self.cycle_limit = u128::MAX;
+        self.interrupts_delivered = 0;
+        self.gas_used = 0;
+        self.gas_limit = u64::MAX;
+        self.paused_at = None;
+        self.counted = true;
+        self.exit_code = None;
+        *self.memory.device_reads_mut() = self.replay.restart();
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.clear();
+        }
+        self.decoded.clear();
+        #[cfg(feature = "jit")]
+        if let Some(jit) = &mut self.jit {
+            jit.clear();
+        }
         self.cpu.init();
         self.memory.clear();
         self.stack.clear();
-        let program = program::Program::new(program);
-        let decoder = decoder::Decoder::new();
+        self.threads.reset();
+        for (base, image) in &self.images {
+            self.memory.write_bytes(*base, image)?;
+        }
+        if let Some(layout) = &self.layout {
+            if program.size() > layout.code.len() {
+                return Err(error::VmError::MemoryOutOfBounds {
+                    address: layout.code.start,
+                    size: program.size(),
+                });
+            }
+        }
+        self.memory
+            .write_bytes(program.data_address(), program.data())?;
+        let bss = program.data_address().saturating_add(program.data().len());
+        self.memory.fill_bytes(bss, program.bss_size(), 0)?;
+        self.cpu.set_pc(program.entry());
+        self.memory.set_code_range(None);
+        if let Some(base) = self.program_base {
+            self.memory.write_bytes(base, program.code())?;
+            self.cpu.set_pc(base + program.entry());
+            self.memory
+                .set_code_range(Some(base..base + program.size()));
+        }
+        self.memory.take_code_written();
+        Ok(())
+    }
 
+    /// Execute a program loaded with `load` until it halts.
+    /// When the run stops on an error, the VM keeps a copy of the program, so that the run can
+    /// be resumed with `step`.
+    fn execute(&mut self, program: &program::Program) -> Result<(), error::ExecutionError<T>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("run", size = program.size(), max_steps = self.step_limit)
+            .entered();
         loop {
-            let instructions = decoder.decode_next_instruction(&program, self.cpu.pc())?;
-            self.steps += 1;
-            log::debug!("Executing instruction: {:?}", instructions);
-            if instructions == instructions::Instruction::<i32, u32>::HLT {
+            match self.advance(program, true) {
+                Ok(true) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(
+                        steps = self.steps,
+                        exit_code = %self.exit_code.unwrap_or(T::zero()),
+                        "terminated"
+                    );
+                    return Ok(());
+                }
+                Ok(false) => {}
+                Err(error) => {
+                    self.program = program.clone().into_owned();
+                    let error = self.fault(error);
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        pc = error.pc,
+                        step = error.step,
+                        error = %error.source,
+                        "fault"
+                    );
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Locate an error stopping a run at the program counter, see `ExecutionError`.
+    pub(crate) fn fault(&self, source: error::VmError) -> error::ExecutionError<T> {
+        let pc = self.cpu.pc();
+        error::ExecutionError {
+            pc,
+            step: self.steps + u128::from(!self.counted),
+            instruction: self.instruction_at(pc),
+            source,
+        }
+    }
+
+    /// Execute the next instruction of the program loaded with `load_program`, after
+    /// delivering the pending interrupt if any.
+    ///
+    /// # Returns:
+    /// - `Ok(true)`: The program halted, the last thread executed `HLT`.
+    /// - `Ok(false)`: The program is still running.
+    /// - `Err(VmError)`: The fault of the instruction when no guest handler is installed, or
+    ///   `VmError::StepLimitExceeded` and `VmError::WatchpointHit`, as with `run`.
+    pub fn step(&mut self) -> Result<bool, error::VmError> {
+        let program = std::mem::take(&mut self.program);
+        let result = self.advance(&program, false);
+        self.program = program;
+        result
+    }
+
+    /// Execute the next instruction as `step`, or the next two instructions when `fuse` is set
+    /// and they form a superinstruction the VM can execute at once, see `CPU::fuse`.
+    fn advance(&mut self, program: &program::Program, fuse: bool) -> Result<bool, error::VmError> {
+        if let Some(controller) = &self.controller {
+            controller.checkpoint();
+        }
+        self.counted = false;
+        self.record_history();
+        self.flush_device_reads();
+        if self.replay.is_replaying() {
+            while let Some(line) = self.replay.next_interrupt(self.steps + 1) {
+                self.deliver_line(line)?;
+            }
+        } else if self.cpu.interrupts_enabled() {
+            self.deliver_interrupt()?;
+        }
+        // the accesses of the interrupt delivery are not part of the traced instruction
+        self.memory.take_accesses();
+        let pc = self.cpu.pc();
+        #[cfg(feature = "jit")]
+        if fuse && self.run_compiled(pc, program)? {
+            return Ok(false);
+        }
+        let instructions = match self.fetch(pc, program) {
+            Ok(decode_cache::Entry {
+                fused: Some(fused), ..
+            }) if fuse && self.can_fuse(fused) => return self.step_fused(fused),
+            Ok(entry) => entry.instruction,
+            Err(error) => {
+                self.trap(error)?;
+                return Ok(false);
+            }
+        };
+        if self.paused_at.take() != Some(pc) {
+            match self.call_hook(hook::HookPhase::Pre, pc, instructions) {
+                hook::HookAction::Continue => {}
+                hook::HookAction::Pause => {
+                    self.paused_at = Some(pc);
+                    return Err(error::VmError::Paused { pc });
+                }
+                hook::HookAction::SkipInstruction => {
+                    self.cpu.set_pc(pc + instructions.size());
+                    return Ok(false);
+                }
+                hook::HookAction::Abort => return Err(error::VmError::Aborted { pc }),
+            }
+        }
+        self.steps += 1;
+        self.counted = true;
+        if self.steps > self.step_limit {
+            log::info!("Step limit of {} exceeded.", self.step_limit);
+            return Err(error::VmError::StepLimitExceeded);
+        }
+        self.cycles = self
+            .cycles
+            .saturating_add(self.cost_model.cost(instructions.opcode()) as u128);
+        if self.cycles > self.cycle_limit {
+            log::info!("Cycle budget of {} exceeded.", self.cycle_limit);
+            return Err(error::VmError::CycleBudgetExceeded);
+        }
+        self.charge_gas(self.gas_schedule.instructions.cost(instructions.opcode()))?;
+        let memory_accesses = self.memory.access_counts();
+        let stack_operations = self.cpu.stack_operations();
+        log::debug!("Executing instruction: {:?}", instructions);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            step = self.steps,
+            pc,
+            opcode = ?instructions.opcode(),
+            instruction = %instructions,
+            "step"
+        );
+        let registers = self.tracer.as_ref().map(|_| self.cpu.registers().to_vec());
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(pc);
+        }
+        let start = self.profile.as_ref().map(|_| std::time::Instant::now());
+        let result = match instructions {
+            instructions::Instruction::SPAWN { .. }
+            | instructions::Instruction::YIELD
+            | instructions::Instruction::JOIN { .. } => self
+                .threads
+                .execute(instructions, &mut self.cpu, &mut self.stack)
+                .map(|()| None),
+            instructions::Instruction::SYSCALL { .. }
+            | instructions::Instruction::IN { .. }
+            | instructions::Instruction::OUT { .. }
+                if !matches!(self.replay, replay::Replay::Off) =>
+            {
+                self.replay_syscall(instructions).map(|()| None)
+            }
+            _ => {
+                let counters = self.counters();
+                self.cpu.execute_instruction(
+                    instructions,
+                    &mut self.memory,
+                    &mut self.stack,
+                    &counters,
+                    &mut self.syscalls,
+                    &mut self.ports,
+                )
+            }
+        };
+        self.record_profile(pc, instructions, start);
+        self.trace(pc, instructions, registers);
+        self.charge_resources(memory_accesses, stack_operations)?;
+        if let (Some(coverage), Some(target), Ok(_)) = (
+            &mut self.coverage,
+            instructions.conditional_target(),
+            &result,
+        ) {
+            coverage.record_branch(pc, self.cpu.pc() == target.to_usize());
+        }
+        let stop = match result {
+            Ok(stop) => {
+                self.after_hook(pc, instructions)?;
+                stop
+            }
+            Err(error) => {
+                self.trap(error)?;
+                None
+            }
+        };
+        if let Some(stop) = stop {
+            return self.stop(stop);
+        }
+        self.memory.tick_devices()?;
+        if let Some(hit) = self.memory.take_watchpoint_hit() {
+            log::info!("Watchpoint hit at pc 0x{:x}: {:?}", pc, hit);
+            return Err(error::VmError::WatchpointHit {
+                pc,
+                address: hit.address,
+                size: hit.size,
+                access: hit.access,
+            });
+        }
+        Ok(false)
+    }
+
+    /// End the running thread on `HLT`, or the whole program on `EXIT`.
+    ///
+    /// # Returns
+    /// `true` if the program terminated, its exit code is then saved for `exit_code`.
+    fn stop(&mut self, stop: cpu::Stop<T>) -> Result<bool, error::VmError> {
+        let code = match stop {
+            cpu::Stop::Halt { code } => {
+                if self.threads.exit(&mut self.cpu, &mut self.stack)? {
+                    return Ok(false);
+                }
+                code
+            }
+            cpu::Stop::Exit { code } => code,
+        };
+        log::info!("Program terminated with exit code {}.", code);
+        self.exit_code = Some(code);
+        Ok(true)
+    }
+
+    /// Check that a superinstruction can be executed at once: nothing observes the VM between
+    /// its two instructions, no interrupt can be delivered between them, they fit in the
+    /// limits of the run and the second one cannot fail with the checked arithmetic.
+    fn can_fuse(&self, fused: cpu::Fused<T>) -> bool {
+        let (first, second) = fused.instructions();
+        let cycles = (self.cost_model.cost(first.opcode()) as u128)
+            .saturating_add(self.cost_model.cost(second.opcode()) as u128);
+        self.superinstructions
+            && self.unobserved()
+            && self.steps.saturating_add(2) <= self.step_limit
+            && self.cycles.saturating_add(cycles) <= self.cycle_limit
+    }
+
+    /// Check that several instructions can be executed at once: nothing observes the VM between
+    /// them, no interrupt can be delivered, the gas is not limited, and the arithmetic and the
+    /// memory accesses cannot fault on their own.
+    fn unobserved(&self) -> bool {
+        self.tracer.is_none()
+            && self.hook.is_none()
+            && self.profile.is_none()
+            && self.coverage.is_none()
+            && self.controller.is_none()
+            && self.history.is_none()
+            && matches!(self.replay, replay::Replay::Off)
+            && !self.cpu.interrupts_enabled()
+            && !self.cpu.checked_arithmetic()
+            && !self.memory.has_watchpoints()
+            && !self.memory.is_protected()
+            && self.gas_limit == u64::MAX
+    }
+
+    /// Run the block compiled by the JIT at an address, compiling it if the address is hot.
+    /// The block runs when nothing observes the VM, see `unobserved`, no device is mapped and
+    /// at least one run of the block fits in the limits of the run.
+    ///
+    /// # Returns
+    /// `true` if a compiled block executed instructions, `false` if the interpreter must
+    /// execute the instruction at the address.
+    #[cfg(feature = "jit")]
+    fn run_compiled(
+        &mut self,
+        pc: usize,
+        program: &program::Program,
+    ) -> Result<bool, error::VmError> {
+        #[cfg(feature = "tracing")]
+        if tracing::enabled!(tracing::Level::TRACE) {
+            return Ok(false);
+        }
+        self.invalidate_written_code();
+        // the blocks start in the code, the jumps elsewhere fault or run the data
+        let end = self.program_base.unwrap_or(0) + program.size();
+        if self.jit.is_none() || pc >= end || !self.unobserved() || self.memory.has_devices() {
+            return Ok(false);
+        }
+        let index = match self.jit.as_mut().unwrap().visit(pc) {
+            jit::Visit::Interpret => return Ok(false),
+            jit::Visit::Run(index) => index,
+            jit::Visit::Compile => {
+                let instructions = self.block_instructions(pc, program);
+                let jit = self.jit.as_mut().unwrap();
+                match jit.compile(
+                    pc,
+                    &instructions,
+                    &self.cost_model,
+                    &self.gas_schedule.instructions,
+                ) {
+                    Some(index) => index,
+                    None => return Ok(false),
+                }
+            }
+        };
+        let block = self.jit.as_ref().unwrap().block(index);
+        let budget = (self.step_limit.saturating_sub(self.steps) / block.len() as u128).min(
+            match block.cost(block.len()).0 {
+                0 => u128::MAX,
+                cycles => self.cycle_limit.saturating_sub(self.cycles) / cycles,
+            },
+        );
+        if budget == 0 {
+            return Ok(false);
+        }
+        let (registers, flags) = self.cpu.registers_and_flags_mut();
+        let (executed, next_pc) = block.run(registers, flags, budget.min(u64::MAX as u128) as u64);
+        let (cycles, gas) = block.cost(executed);
+        log::debug!("Executed {} compiled instructions at 0x{:x}", executed, pc);
+        self.cpu.set_pc(next_pc);
+        self.steps += executed as u128;
+        self.counted = true;
+        self.cycles = self.cycles.saturating_add(cycles);
+        self.gas_used = self.gas_used.saturating_add(gas);
+        Ok(executed > 0)
+    }
+
+    /// Decode the instructions of the block the JIT compiles at an address, until the first
+    /// instruction ending a block or the end of the code.
+    #[cfg(feature = "jit")]
+    fn block_instructions(
+        &mut self,
+        mut pc: usize,
+        program: &program::Program,
+    ) -> Vec<(usize, instructions::Instruction<T, T::Address>)> {
+        let mut instructions = Vec::new();
+        while instructions.len() < jit::MAX_BLOCK_INSTRUCTIONS {
+            let Ok(entry) = self.decode_at(pc, program) else {
+                break;
+            };
+            let in_code = match self.program_base {
+                Some(base) => pc >= base && pc + entry.instruction.size() <= base + program.size(),
+                None => true,
+            };
+            if !in_code {
+                break;
+            }
+            instructions.push((pc, entry.instruction));
+            if jit::ends_block(&entry.instruction) {
                 break;
             }
-            self.cpu
-                .execute_instruction(instructions, &mut self.memory, &mut self.stack)?;
+            pc += entry.instruction.size();
         }
-        log::info!("Program executed successfully in {} steps.", self.steps);
-        Ok(self.steps)
+        instructions
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Execute a superinstruction checked by `can_fuse`, accounting for its two instructions as
+    /// `step` would.
+    fn step_fused(&mut self, fused: cpu::Fused<T>) -> Result<bool, error::VmError> {
+        let (first, second) = fused.instructions();
+        #[cfg(feature = "tracing")]
+        let pc = self.cpu.pc();
+        self.steps += 1;
+        self.cycles += self.cost_model.cost(first.opcode()) as u128;
+        self.charge_gas(self.gas_schedule.instructions.cost(first.opcode()))?;
+        let memory_accesses = self.memory.access_counts();
+        let stack_operations = self.cpu.stack_operations();
+        log::debug!("Executing fused instructions: {:?}, {:?}", first, second);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            step = self.steps,
+            pc,
+            opcode = ?first.opcode(),
+            instruction = %first,
+            "step"
+        );
+        let counters = self.counters();
+        let result = self.cpu.execute_fused(
+            fused,
+            &mut self.memory,
+            &mut self.stack,
+            &counters,
+            &mut self.syscalls,
+            &mut self.ports,
+        );
+        self.charge_resources(memory_accesses, stack_operations)?;
+        if let Err(error) = result {
+            self.trap(error)?;
+            self.memory.tick_devices()?;
+            return Ok(false);
+        }
+        self.steps += 1;
+        // the second instruction follows the first one, whose effects are already applied
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            step = self.steps,
+            pc = pc + first.size(),
+            opcode = ?second.opcode(),
+            instruction = %second,
+            "step"
+        );
+        self.cycles += self.cost_model.cost(second.opcode()) as u128;
+        self.charge_gas(self.gas_schedule.instructions.cost(second.opcode()))?;
+        self.memory.tick_devices()?;
+        self.memory.tick_devices()?;
+        Ok(false)
+    }
 
-    #[test]
-    fn test_vm_create() {
-        let vm = VM::<i32>::new(1024, 1024);
-        assert_eq!(vm.stack.capacity(), 1024);
-        assert_eq!(vm.memory.capacity(), 1024);
+    /// Fetch the instruction at an address, see `decode_at`. In the Von Neumann mode, the
+    /// region descriptors of the memory must allow the execution of the instruction.
+    fn fetch(
+        &mut self,
+        pc: usize,
+        program: &program::Program,
+    ) -> Result<decode_cache::Entry<T>, error::VmError> {
+        let protected = self.program_base.is_some() && self.memory.is_protected();
+        if protected {
+            // the opcode is checked before the decoding, the whole instruction after
+            self.memory.check_execute(pc, 1)?;
+        }
+        let entry = self.decode_at(pc, program)?;
+        if protected {
+            self.memory.check_execute(pc, entry.instruction.size())?;
+        }
+        Ok(entry)
     }
 
-    #[test]
-    fn test_vm_run() {
-        let mut vm = VM::<i32>::new(1024, 1024);
-        let program = vec![0x00, 0x00, 0xff]; // NOP, NOP, HLT
-        assert_eq!(vm.run(&program), Ok(3));
+    /// Decode the instruction at an address, or get it from the cache of the decoded
+    /// instructions. In the Von Neumann mode, the cache is cleared when the program writes into
+    /// its code, and the instructions outside the code are not cached.
+    fn decode_at(
+        &mut self,
+        pc: usize,
+        program: &program::Program,
+    ) -> Result<decode_cache::Entry<T>, error::VmError> {
+        self.invalidate_written_code();
+        if let Some(entry) = self.decoded.get(pc) {
+            return Ok(entry);
+        }
+        let code = match self.program_base {
+            Some(_) => self.memory.bytes().get(pc..).unwrap_or_default(),
+            None => program.slice_from(pc),
+        };
+        let decoder = decoder::Decoder::<T>::with_registers(self.cpu.registers_count())
+            .with_endianness(self.memory.endianness());
+        let instruction = decoder.decode_instruction(code)?;
+        let in_code = |end: usize| match self.program_base {
+            Some(base) => pc >= base && end <= base + program.size(),
+            None => true,
+        };
+        let mut entry = decode_cache::Entry {
+            instruction,
+            fused: None,
+        };
+        if in_code(pc + instruction.size()) {
+            if let Ok(next) = decoder.decode_instruction(&code[instruction.size()..]) {
+                if in_code(pc + instruction.size() + next.size()) {
+                    entry.fused = cpu::CPU::fuse(instruction, next);
+                }
+            }
+            self.decoded.insert(pc, entry);
+        }
+        Ok(entry)
     }
 
-    #[test]
-    fn test_vm_run_with_stack_overflow() {
-        let mut vm = VM::<i32>::new(1, 1024);
-        let program = vec![0x10, 0x00, 0x10, 0x00, 0xff]; // PUSH 0, PUSH 0, HLT
-        assert_eq!(vm.run(&program), Err(error::VmError::StackOverflow));
+    /// Clear the decoded and the compiled instructions when the program wrote into its code.
+    fn invalidate_written_code(&mut self) {
+        if self.memory.take_code_written() {
+            self.decoded.clear();
+            #[cfg(feature = "jit")]
+            if let Some(jit) = &mut self.jit {
+                jit.clear();
+            }
+        }
     }
 
-    #[test]
-    fn test_vm_run_with_stack_underflow() {
-        let mut vm = VM::<i32>::new(1024, 1024);
-        let program = vec![0x11, 0x00, 0xff]; // POP 0, HLT
-        assert_eq!(vm.run(&program), Err(error::VmError::StackUnderflow));
+    /// Get the program counter, the address of the next instruction.
+    pub fn pc(&self) -> usize {
+        self.cpu.pc()
     }
 
-    #[test]
-    fn test_vm_run_with_memory_out_of_bounds() {
-        let mut vm = VM::<i32>::new(1024, 1024);
-        let program = vec![0x02, 0x01, 0x00, 0x04, 0x00, 0x00, 0xff]; // LD 1 0x00000400, HLT
-        assert_eq!(
-            vm.run(&program),
-            Err(error::VmError::MemoryOutOfBounds {
-                address: 1024,
-                size: 4
+    /// Get the values of the registers, to inspect the results of a run without taking a
+    /// snapshot.
+    pub fn registers(&self) -> &[T] {
+        self.cpu.registers()
+    }
+
+    /// Get the status flags.
+    pub fn flags(&self) -> cpu::StatusFlags {
+        self.cpu.flags()
+    }
+
+    /// Get the values of the stack, from the bottom to the top.
+    pub fn stack_slice(&self) -> &[T] {
+        self.stack.values()
+    }
+
+    /// Get the memory, to inspect its content with `Memory::bytes` or `Memory::peek`.
+    pub fn memory(&self) -> &memory::Memory {
+        &self.memory
+    }
+
+    /// Get the cycles consumed by the current or the last run, see `VmBuilder::cost_model`.
+    pub fn cycles(&self) -> u128 {
+        self.cycles
+    }
+
+    /// Get the gas consumed by the current or the last run, see `VmBuilder::gas_schedule`.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    /// Add gas to the gas used by the run.
+    ///
+    /// # Errors:
+    /// Returns `VmError::OutOfGas` if the gas used exceeds the gas limit.
+    fn charge_gas(&mut self, gas: u64) -> Result<(), error::VmError> {
+        self.gas_used = self.gas_used.saturating_add(gas);
+        if self.gas_used > self.gas_limit {
+            log::info!("Gas limit of {} exceeded.", self.gas_limit);
+            return Err(error::VmError::OutOfGas);
+        }
+        Ok(())
+    }
+
+    /// Charge the gas of the memory accesses and the stack operations of an instruction,
+    /// given the counts before the instruction.
+    fn charge_resources(
+        &mut self,
+        (reads, writes): (u64, u64),
+        (pushes, pops): (u64, u64),
+    ) -> Result<(), error::VmError> {
+        let (new_reads, new_writes) = self.memory.access_counts();
+        let (new_pushes, new_pops) = self.cpu.stack_operations();
+        let schedule = &self.gas_schedule;
+        let gas = [
+            (new_reads - reads, schedule.memory_read),
+            (new_writes - writes, schedule.memory_write),
+            (new_pushes - pushes, schedule.stack_push),
+            (new_pops - pops, schedule.stack_pop),
+        ]
+        .iter()
+        .fold(0u64, |gas, (count, cost)| {
+            gas.saturating_add(count.saturating_mul(*cost))
+        });
+        self.charge_gas(gas)
+    }
+
+    /// Record the execution of an instruction started at `start` in the profile, if any.
+    fn record_profile(
+        &mut self,
+        pc: usize,
+        instruction: instructions::Instruction<T, T::Address>,
+        start: Option<std::time::Instant>,
+    ) {
+        if let (Some(profile), Some(start)) = (&mut self.profile, start) {
+            profile.record(instruction.opcode(), pc, start.elapsed());
+        }
+    }
+
+    /// Call the hook, if any, with the state of the VM.
+    fn call_hook(
+        &mut self,
+        phase: hook::HookPhase,
+        pc: usize,
+        instruction: instructions::Instruction<T, T::Address>,
+    ) -> hook::HookAction {
+        let Some(hook) = &mut self.hook else {
+            return hook::HookAction::Continue;
+        };
+        hook(&hook::HookCtx {
+            phase,
+            pc,
+            instruction,
+            steps: self.steps,
+            registers: self.cpu.registers(),
+            memory: self.memory.bytes(),
+            stack: self.stack.values(),
+        })
+    }
+
+    /// Call the hook in the `Post` phase of an executed instruction.
+    fn after_hook(
+        &mut self,
+        pc: usize,
+        instruction: instructions::Instruction<T, T::Address>,
+    ) -> Result<(), error::VmError> {
+        match self.call_hook(hook::HookPhase::Post, pc, instruction) {
+            hook::HookAction::Pause => Err(error::VmError::Paused { pc: self.cpu.pc() }),
+            hook::HookAction::Abort => Err(error::VmError::Aborted { pc }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pass the event of an executed instruction to the tracer, if any.
+    ///
+    /// # Parameters:
+    /// - `pc`: The address of the instruction.
+    /// - `instruction`: The executed instruction.
+    /// - `registers`: The registers before the instruction, `None` without tracer.
+    fn trace(
+        &mut self,
+        pc: usize,
+        instruction: instructions::Instruction<T, T::Address>,
+        registers: Option<Vec<T>>,
+    ) {
+        let (Some(tracer), Some(registers)) = (&mut self.tracer, registers) else {
+            return;
+        };
+        let registers = registers
+            .into_iter()
+            .zip(self.cpu.registers())
+            .enumerate()
+            .filter(|(_, (old, new))| old != *new)
+            .map(|(register, (old, new))| trace::RegisterChange {
+                register: register as u8,
+                old,
+                new: *new,
             })
-        );
+            .collect();
+        tracer.trace(&trace::TraceEvent {
+            step: self.steps,
+            pc,
+            instruction,
+            registers,
+            memory: self.memory.take_accesses(),
+        });
     }
 
-    #[test]
-    fn test_vm_run_with_invalid_op_code() {
-        let mut vm = VM::<i32>::new(1024, 1024);
-        let program = vec![0xf0, 0xff]; // Invalid instruction
-        assert_eq!(
-            vm.run(&program),
-            Err(error::VmError::InvalidOpcode { opcode: 0xf0 })
+    /// Deliver the next pending interrupt, if any, by jumping to its handler.
+    fn deliver_interrupt(&mut self) -> Result<(), error::VmError> {
+        let Some(line) = self.interrupts.take_next(self.memory.asserted_interrupts()) else {
+            return Ok(());
+        };
+        self.replay.record(replay::ReplayEvent::Interrupt {
+            step: self.steps + 1,
+            line,
+        });
+        self.deliver_line(line)
+    }
+
+    /// Deliver an interrupt by jumping to the handler of its line.
+    fn deliver_line(&mut self, line: u8) -> Result<(), error::VmError> {
+        let handler = self
+            .memory
+            .read_privileged::<T::Address>(self.interrupts.vector_address::<T>(line))?
+            .to_usize();
+        log::debug!("Delivering interrupt {} to handler 0x{:x}", line, handler);
+        self.cpu
+            .interrupt(handler, &mut self.memory, &mut self.stack)?;
+        self.interrupts_delivered += 1;
+        Ok(())
+    }
+
+    /// Save the state before the next instruction in the history, if enabled, after adding the
+    /// bytes overwritten by the previous instruction to its state.
+    fn record_history(&mut self) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        history.journal(self.memory.take_journal());
+        history.push(history::HistoryEntry {
+            cpu: self.cpu.state(),
+            stack: self.stack.values().to_vec(),
+            steps: self.steps,
+            cycles: self.cycles,
+            interrupts_delivered: self.interrupts_delivered,
+            gas_used: self.gas_used,
+            memory: Vec::new(),
+        });
+    }
+
+    /// Record the device reads of the memory since the last call, with the current step.
+    fn flush_device_reads(&mut self) {
+        for (address, bytes) in self.memory.device_reads_mut().take() {
+            self.replay.record(replay::ReplayEvent::DeviceRead {
+                step: self.steps,
+                address,
+                bytes,
+            });
+        }
+    }
+
+    /// Execute a syscall or a port access while recording or replaying. The recording saves the
+    /// effects of the instruction on the CPU and the memory, the replay applies them without
+    /// calling the host function or the port handler.
+    fn replay_syscall(
+        &mut self,
+        instruction: instructions::Instruction<T, T::Address>,
+    ) -> Result<(), error::VmError> {
+        if self.replay.is_replaying() {
+            let (state, memory, error) = self.replay.next_syscall(self.steps)?;
+            for (address, bytes) in &memory {
+                self.memory.write_bytes(*address, bytes)?;
+            }
+            self.cpu.restore(&state)?;
+            return error.map_or(Ok(()), |message| Err(error::VmError::Other(message)));
+        }
+        self.flush_device_reads();
+        let before = self.memory.bytes().to_vec();
+        let counters = self.counters();
+        let result = self.cpu.execute_instruction(
+            instruction,
+            &mut self.memory,
+            &mut self.stack,
+            &counters,
+            &mut self.syscalls,
+            &mut self.ports,
         );
+        // the device reads of the host function are part of the effects of the syscall
+        self.memory.device_reads_mut().take();
+        self.replay.record(replay::ReplayEvent::Syscall {
+            step: self.steps,
+            state: self.cpu.state(),
+            memory: replay::memory_diff(&before, self.memory.bytes()),
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+        result.map(|_| ())
     }
 
-    #[test]
-    fn test_vm_run_add() {
-        let mut vm = VM::<i32>::new(1024, 1024);
-        let program = vec![
-            0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x01, 0x07, 0x00, 0x00, 0x00, 0x09, 0x00,
-            0x00, 0x01, 0xff,
-        ]; // LD 0 0x02, LD 1 0x07, ADD 0 0 1, HLT
-        assert_eq!(vm.run(&program), Ok(4));
-        assert_eq!(vm.cpu.get_register(0), Ok(9));
+    /// Deliver a fault to its guest handler, or return the fault if no handler is installed.
+    fn trap(&mut self, error: error::VmError) -> Result<(), error::VmError> {
+        let Some(trap) = interrupt::Trap::from_error(&error) else {
+            return Err(error);
+        };
+        let vector = self.interrupts.vector_address::<T>(trap.into());
+        let handler = match self.memory.read_privileged::<T::Address>(vector) {
+            Ok(handler) if handler != T::Address::zero() => handler.to_usize(),
+            _ => return Err(error),
+        };
+        log::debug!("Trap {:?} ({}) to handler 0x{:x}", trap, error, handler);
+        self.cpu
+            .interrupt(handler, &mut self.memory, &mut self.stack)
+            .map_err(|_| error)
+    }
+
+    /// Raise an interrupt on a line.
+    /// The interrupt stays pending until the guest enables the interrupts with `EI`,
+    /// it is then delivered to the handler found in the vector table at the next instruction boundary.
+    ///
+    /// The lines `0` to `3` are reserved for the traps, see `interrupt::Trap`.
+    ///
+    /// # Parameters:
+    /// - `line`: The interrupt line, the index of its handler in the vector table.
+    pub fn raise_interrupt(&mut self, line: u8) {
+        self.interrupts.raise(line);
+    }
+
+    /// Register a host function callable by the guest with `SYSCALL number`.
+    /// A function already registered under the same number is replaced.
+    /// Registered functions are kept across runs.
+    ///
+    /// # Parameters:
+    /// - `number`: The syscall number used by the guest.
+    /// - `function`: The host function, receiving the registers and the memory of the VM.
+    ///   An error returned by the function stops the execution.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// // syscall 1 squares R0
+    /// vm.register_host_fn(1, |cpu, _memory| {
+    ///     let value = cpu.register(0)?;
+    ///     cpu.set_register(0, value * value)
+    /// });
+    /// let program = vec![0x01, 0x00, 0x07, 0x00, 0x00, 0x00, 0x1a, 0x01, 0xff]; // MOV 0 7, SYSCALL 1, HLT
+    /// assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 49);
+    /// ```
+    pub fn register_host_fn<F>(&mut self, number: u8, function: F)
+    where
+        F: FnMut(&mut cpu::CpuView<T>, &mut memory::Memory) -> Result<(), error::VmError> + 'static,
+    {
+        self.syscalls.register(number, Box::new(function));
+    }
+
+    /// Remove the host function registered under a syscall number.
+    ///
+    /// # Returns:
+    /// `true` if a function was registered under the number.
+    pub fn unregister_host_fn(&mut self, number: u8) -> bool {
+        self.syscalls.unregister(number)
+    }
+
+    /// Register the built-in standard I/O syscalls, see `Stdio`.
+    /// The functions already registered under the numbers of the syscalls are replaced.
+    ///
+    /// # Parameters:
+    /// - `stdio`: The streams of the guest, `Stdio::inherit()` for the streams of the host.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::vm::stdio::Stdio;
+    /// use forge_vm::VM;
+    ///
+    /// // echo the input to the output of the host
+    /// let source = "
+    ///     MOV R0 0x100
+    ///     MOV R1 64
+    ///     SYSCALL 0xf0
+    ///     MOVR R1 R0
+    ///     MOV R0 0x100
+    ///     SYSCALL 0xf1
+    ///     HLT
+    /// ";
+    /// let program = Assembler::new().assemble(source).unwrap();
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.register_stdio(Stdio::new(&b"Hello, world!\n"[..], std::io::stdout(), std::io::stderr()));
+    /// vm.run(&program).unwrap();
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 14);
+    /// ```
+    pub fn register_stdio(&mut self, stdio: stdio::Stdio) {
+        stdio.register(&mut self.syscalls);
+    }
+
+    /// Register the UDP networking syscalls, see `Network`.
+    /// The functions already registered under the numbers of the syscalls are replaced.
+    ///
+    /// # Parameters:
+    /// - `network`: The bridge to the sockets of the host.
+    #[cfg(feature = "net")]
+    pub fn register_network(&mut self, network: net::Network) {
+        network.register(&mut self.syscalls);
+    }
+
+    /// Register the heap allocator syscalls, see `Heap`.
+    /// The functions already registered under the numbers of the syscalls are replaced.
+    ///
+    /// # Parameters:
+    /// - `heap`: The heap of the guest, a clone kept by the host shares its allocations.
+    pub fn register_heap(&mut self, heap: heap::Heap) {
+        heap.register(&mut self.syscalls);
+    }
+
+    /// Register the checksum and hash syscalls, see `Crypto`.
+    /// The functions already registered under the numbers of the syscalls are replaced.
+    pub fn register_crypto(&mut self) {
+        crypto::Crypto::register(&mut self.syscalls);
+    }
+
+    /// Register the handler of an I/O port, read by `IN` and written by `OUT`.
+    /// A handler already registered on the same port is replaced.
+    /// Registered handlers are kept across runs.
+    ///
+    /// # Parameters:
+    /// - `port`: The port used by the guest.
+    /// - `handler`: The peripheral connected to the port.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::vm::port::PortHandler;
+    /// use forge_vm::{VmError, VM};
+    ///
+    /// /// A port doubling the values written to it.
+    /// struct Doubler(i32);
+    ///
+    /// impl PortHandler<i32> for Doubler {
+    ///     fn read(&mut self) -> Result<i32, VmError> {
+    ///         Ok(self.0 * 2)
+    ///     }
+    ///
+    ///     fn write(&mut self, value: i32) -> Result<(), VmError> {
+    ///         self.0 = value;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.register_port(0x60, Doubler(0));
+    /// let program = Assembler::new()
+    ///     .assemble("MOV R1 21\nOUT 0x60 R1\nIN R0 0x60\nHLT")
+    ///     .unwrap();
+    /// assert_eq!(vm.run(&program).map(|termination| termination.exit_code), Ok(42));
+    /// ```
+    pub fn register_port<H: port::PortHandler<T> + 'static>(&mut self, port: u16, handler: H) {
+        self.ports.register(port, Box::new(handler));
+    }
+
+    /// Remove the handler registered on an I/O port.
+    ///
+    /// # Returns:
+    /// `true` if a handler was registered on the port.
+    pub fn unregister_port(&mut self, port: u16) -> bool {
+        self.ports.unregister(port)
+    }
+
+    /// Take a snapshot of the state of the VM after a run.
+    ///
+    /// # Returns:
+    /// The registers, status flags, program counter, memory, stack and step counter.
+    pub fn snapshot(&self) -> snapshot::VmSnapshot<T> {
+        snapshot::VmSnapshot {
+            cpu: self.cpu.state(),
+            memory: self.memory.bytes().to_vec(),
+            stack: self.stack.values().to_vec(),
+            steps: self.steps,
+        }
+    }
+
+    /// Restore a snapshot previously taken with `snapshot`.
+    ///
+    /// # Parameters:
+    /// - `snapshot`: The state to restore.
+    ///
+    /// # Errors:
+    /// Returns an error if the snapshot does not fit in the memory size, the stack capacity
+    /// or the number of registers of the VM. The state of the VM is left unchanged in that case.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.run(&[0x01, 0x00, 0x2a, 0x00, 0x00, 0x00, 0xff]).unwrap(); // MOV 0 42, HLT
+    /// let snapshot = vm.snapshot();
+    ///
+    /// let mut other = VM::<i32>::new(1024, 1024);
+    /// other.restore(&snapshot).unwrap();
+    /// assert_eq!(other.snapshot(), snapshot);
+    /// ```
+    pub fn restore(&mut self, snapshot: &snapshot::VmSnapshot<T>) -> Result<(), error::VmError> {
+        if snapshot.memory.len() != self.memory.capacity() {
+            return Err(error::VmError::Other(format!(
+                "Snapshot memory size {} does not match the memory size {}",
+                snapshot.memory.len(),
+                self.memory.capacity()
+            )));
+        }
+        if snapshot.stack.len() > self.stack.capacity() {
+            return Err(error::VmError::StackOverflow);
+        }
+        self.cpu.restore(&snapshot.cpu)?;
+        self.memory.write_bytes(0, &snapshot.memory)?;
+        self.stack.clear();
+        for value in &snapshot.stack {
+            self.stack.push(*value)?;
+        }
+        self.steps = snapshot.steps;
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        Ok(())
+    }
+
+    /// Keep the states before the last `depth` instructions, so that they can be undone with
+    /// `step_back`. A depth of `0` disables the history, disabled by default.
+    /// The history is cleared at the start of each run and when a snapshot is restored.
+    ///
+    /// # Parameters
+    /// - `depth`: The maximum number of instructions that can be undone.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history = (depth > 0).then(|| history::History::new(depth));
+        self.memory.set_journal(depth > 0);
+    }
+
+    /// Get the number of executed instructions that can be undone with `step_back`.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, history::History::len)
+    }
+
+    /// Undo the last executed instruction, restoring the registers, the memory, the stack and
+    /// the counters of the VM as they were before the instruction, see `set_history_depth`.
+    /// An instruction that stopped with a fault can be undone as well.
+    ///
+    /// The state of the devices, the pending interrupts and the green threads is not restored.
+    ///
+    /// # Errors:
+    /// Returns `VmError::NoHistory` if the history is disabled or exhausted.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::program::Program;
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.set_history_depth(100);
+    /// vm.load_program(&Program::new(&[0x0e, 0x00, 0xff])).unwrap(); // INC R0, HLT
+    /// vm.step().unwrap();
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 1);
+    /// vm.step_back().unwrap();
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 0);
+    /// assert_eq!(vm.pc(), 0);
+    /// ```
+    pub fn step_back(&mut self) -> Result<(), error::VmError> {
+        let Some(history) = &mut self.history else {
+            return Err(error::VmError::NoHistory);
+        };
+        history.journal(self.memory.take_journal());
+        let entry = history.pop().ok_or(error::VmError::NoHistory)?;
+        for (address, bytes) in entry.memory.iter().rev() {
+            self.memory.write_bytes(*address, bytes)?;
+        }
+        self.memory.take_journal();
+        self.cpu.restore(&entry.cpu)?;
+        self.stack.clear();
+        for value in entry.stack {
+            self.stack.push(value)?;
+        }
+        self.steps = entry.steps;
+        self.cycles = entry.cycles;
+        self.interrupts_delivered = entry.interrupts_delivered;
+        self.gas_used = entry.gas_used;
+        self.paused_at = None;
+        self.counted = true;
+        self.exit_code = None;
+        Ok(())
+    }
+
+    /// Add a watchpoint on a range of the memory.
+    /// The execution stops with `VmError::WatchpointHit` right after an instruction
+    /// accesses the watched range, the access itself being performed.
+    /// Watchpoints are kept across runs.
+    ///
+    /// # Parameters
+    /// - `watchpoint`: The watchpoint to add.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::watchpoint::{Access, Watchpoint};
+    /// use forge_vm::{VmError, VM};
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.add_watchpoint(Watchpoint::write(0x10..0x14));
+    /// let program = vec![0x00, 0x03, 0x00, 0x10, 0x00, 0x00, 0x00, 0xff]; // NOP, ST 0 0x10, HLT
+    /// assert_eq!(
+    ///     vm.run(&program).map_err(|error| error.source),
+    ///     Err(VmError::WatchpointHit { pc: 1, address: 0x10, size: 4, access: Access::Write })
+    /// );
+    /// ```
+    pub fn add_watchpoint(&mut self, watchpoint: watchpoint::Watchpoint) {
+        self.memory.add_watchpoint(watchpoint);
+    }
+
+    /// Remove all the watchpoints.
+    pub fn clear_watchpoints(&mut self) {
+        self.memory.clear_watchpoints();
+    }
+
+    /// Add a region descriptor to the memory, see `Region`: once a region is added, the loads
+    /// and stores of the program, and the instruction fetches in the Von Neumann mode, raise
+    /// `VmError::ProtectionFault` outside the regions allowing them. The fault is delivered to
+    /// the `Trap::InvalidMemory` handler if one is installed. Regions are kept across runs.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::protection::{Permission, Permissions, Region};
+    /// use forge_vm::{VmError, VM};
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.add_region(Region::new(0x0..0x100, Permissions::READ_WRITE));
+    /// vm.add_region(Region::new(0x100..0x200, Permissions::READ));
+    /// // ST R0 0x0, ST R0 0x100, HLT
+    /// let program = vec![0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x01, 0x00, 0x00, 0xff];
+    /// assert_eq!(
+    ///     vm.run(&program).map_err(|error| error.source),
+    ///     Err(VmError::ProtectionFault { address: 0x100, size: 4, permission: Permission::Write })
+    /// );
+    /// ```
+    pub fn add_region(&mut self, region: protection::Region) {
+        self.memory.add_region(region);
+    }
+
+    /// Remove all the region descriptors, the memory is no longer protected.
+    pub fn clear_regions(&mut self) {
+        self.memory.clear_regions();
+    }
+
+    /// Set the tracer receiving an event for every executed instruction, with its address,
+    /// the modified registers and the memory accesses, replacing the previous tracer.
+    /// The tracer is kept across runs, see `trace::Tracer` for the built-in tracers.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::trace::RingBufferTracer;
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.set_tracer(RingBufferTracer::new(16));
+    /// vm.run(&[0x0e, 0x00, 0xff]).unwrap(); // INC R0, HLT
+    /// let tracer = vm.tracer::<RingBufferTracer<i32>>().unwrap();
+    /// let lines: Vec<_> = tracer.events().map(|event| event.to_string()).collect();
+    /// assert_eq!(lines, ["1 0x00000000: INC R0 ; R0 0 -> 1", "2 0x00000002: HLT"]);
+    /// ```
+    pub fn set_tracer<X: trace::Tracer<T>>(&mut self, tracer: X) {
+        self.tracer = Some(Box::new(tracer));
+        self.memory.set_access_recording(true);
+    }
+
+    /// Get the tracer of type `X`.
+    ///
+    /// # Returns:
+    /// The tracer, or `None` if no tracer of type `X` is set.
+    pub fn tracer<X: trace::Tracer<T>>(&self) -> Option<&X> {
+        let tracer = self.tracer.as_deref()?;
+        (tracer as &dyn std::any::Any).downcast_ref()
+    }
+
+    /// Get the tracer of type `X`, mutably.
+    ///
+    /// # Returns:
+    /// The tracer, or `None` if no tracer of type `X` is set.
+    pub fn tracer_mut<X: trace::Tracer<T>>(&mut self) -> Option<&mut X> {
+        let tracer = self.tracer.as_deref_mut()?;
+        (tracer as &mut dyn std::any::Any).downcast_mut()
+    }
+
+    /// Remove the tracer.
+    pub fn clear_tracer(&mut self) {
+        self.tracer = None;
+        self.memory.set_access_recording(false);
+    }
+
+    /// Set the hook called before and after each instruction, replacing the previous hook.
+    /// The hook inspects the state of the VM and can pause the execution, skip the instruction
+    /// or abort the execution, see `hook::HookAction`. The hook is kept across runs.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::error::VmError;
+    /// use forge_vm::vm::hook::{HookAction, HookPhase};
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// // a breakpoint at 0x2
+    /// vm.set_hook(|ctx| match (ctx.phase, ctx.pc) {
+    ///     (HookPhase::Pre, 0x2) => HookAction::Pause,
+    ///     _ => HookAction::Continue,
+    /// });
+    /// let program = vec![0x0e, 0x00, 0x0e, 0x00, 0xff]; // INC R0, INC R0, HLT
+    /// assert_eq!(vm.run(&program).map_err(|error| error.source), Err(VmError::Paused { pc: 0x2 }));
+    /// while !vm.step().unwrap() {}
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 2);
+    /// ```
+    pub fn set_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&hook::HookCtx<T>) -> hook::HookAction + 'static,
+    {
+        self.hook = Some(Box::new(hook));
+    }
+
+    /// Remove the hook.
+    pub fn clear_hook(&mut self) {
+        self.hook = None;
+    }
+
+    /// Attach a controller pausing and resuming the VM from another thread, replacing the
+    /// previous controller. The VM blocks at the next instruction boundary while a pause is
+    /// requested, see `control::VmController`.
+    pub fn set_controller(&mut self, controller: control::VmController) {
+        self.controller = Some(controller);
+    }
+
+    /// Detach the controller.
+    pub fn clear_controller(&mut self) {
+        self.controller = None;
+    }
+
+    /// Start recording the nondeterministic inputs of the runs: the delivered interrupts, the
+    /// device reads and the effects of the syscalls and of the port accesses. The recording restarts at each run and is
+    /// retrieved with `take_recording`, a replay in progress is stopped.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::VM;
+    /// let program = vec![0x1a, 0x01, 0xff]; // SYSCALL 1, HLT
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.register_host_fn(1, |cpu, _memory| cpu.set_register(0, 42));
+    /// vm.start_recording();
+    /// vm.run(&program).unwrap();
+    /// let recording = vm.take_recording().unwrap();
+    ///
+    /// // the replay applies the effects of the syscall without calling the host function
+    /// let mut replay = VM::<i32>::new(1024, 1024);
+    /// replay.replay(recording);
+    /// assert_eq!(replay.run(&program).map(|termination| termination.steps), Ok(2));
+    /// assert_eq!(replay.snapshot().cpu.registers[0], 42);
+    /// ```
+    pub fn start_recording(&mut self) {
+        self.replay = replay::Replay::Recording(replay::Recording::new());
+        *self.memory.device_reads_mut() = self.replay.restart();
+    }
+
+    /// Stop the recording and take the inputs recorded since the start of the last run.
+    ///
+    /// # Returns:
+    /// The recording, or `None` if the VM was not recording.
+    pub fn take_recording(&mut self) -> Option<replay::Recording<T>> {
+        if !matches!(self.replay, replay::Replay::Recording(_)) {
+            return None;
+        }
+        self.flush_device_reads();
+        *self.memory.device_reads_mut() = replay::DeviceReads::Off;
+        match std::mem::replace(&mut self.replay, replay::Replay::Off) {
+            replay::Replay::Recording(recording) => Some(recording),
+            _ => None,
+        }
+    }
+
+    /// Replay a recording in the next runs, which must execute the program of the recording:
+    /// the recorded interrupts are delivered at their step, the device reads, the syscalls and
+    /// the port accesses return the recorded values without calling the devices, the host
+    /// functions and the port handlers.
+    /// A recording in progress is stopped.
+    ///
+    /// A run fails with `VmError::ReplayDivergence` when the program makes a syscall or a
+    /// device read that does not match the recording.
+    pub fn replay(&mut self, recording: replay::Recording<T>) {
+        self.replay = replay::Replay::Replaying {
+            recording,
+            next_interrupt: 0,
+            next_syscall: 0,
+        };
+        *self.memory.device_reads_mut() = self.replay.restart();
+    }
+
+    /// Stop the replay, the next runs call the devices and the host functions again.
+    pub fn stop_replay(&mut self) {
+        if self.replay.is_replaying() {
+            self.replay = replay::Replay::Off;
+            *self.memory.device_reads_mut() = replay::DeviceReads::Off;
+        }
+    }
+
+    /// Enable or disable the profiler, which counts the executions and measures the host time
+    /// per opcode and per instruction address. The profile is reset at the start of each run.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::instructions::OpCode;
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.set_profiling(true);
+    /// vm.run(&[0x0e, 0x00, 0x0e, 0x00, 0xff]).unwrap(); // INC R0, INC R0, HLT
+    /// let profile = vm.profile().unwrap();
+    /// assert_eq!(profile.opcode(OpCode::INC).count, 2);
+    /// println!("{}", profile);
+    /// ```
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(profiler::Profile::default);
+    }
+
+    /// Get the profile of the last run, `None` when the profiling is disabled.
+    pub fn profile(&self) -> Option<&profiler::Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Enable or disable the coverage, which records the executed instructions and the
+    /// directions taken by the conditional jumps. The coverage accumulates the runs until it
+    /// is disabled, enabling it again starts an empty coverage.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.set_coverage(true);
+    /// // INC R0, JMPZ 0x8, HLT, INC R1
+    /// vm.run(&[0x0e, 0x00, 0x15, 0x08, 0x00, 0x00, 0x00, 0xff, 0x0e, 0x01]).unwrap();
+    /// let coverage = vm.coverage().unwrap();
+    /// assert_eq!(coverage.executed().collect::<Vec<_>>(), [0x0, 0x2, 0x7]);
+    /// assert_eq!(coverage.branch(0x2).unwrap().not_taken, 1);
+    /// ```
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.coverage = enabled.then(coverage::Coverage::default);
+    }
+
+    /// Get the coverage of the runs, `None` when the coverage is disabled.
+    pub fn coverage(&self) -> Option<&coverage::Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Map a memory-mapped I/O device into a range of addresses.
+    /// The `LD` and `ST` instructions to the range are routed to the device.
+    /// The devices are kept across runs and reset before each run.
+    ///
+    /// # Parameters:
+    /// - `range`: The addresses handled by the device, inside or beyond the memory size.
+    /// - `device`: The device receiving the accesses to the range.
+    ///
+    /// # Returns:
+    /// - `Err(VmError)`: If the range is empty or overlaps another device.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::vm::device::Device;
+    /// use forge_vm::vm::error::Result;
+    /// use forge_vm::VM;
+    ///
+    /// // A device always reading 42
+    /// struct Answer;
+    ///
+    /// impl Device for Answer {
+    ///     fn read(&mut self, _offset: usize, size: usize) -> Result<Vec<u8>> {
+    ///         let mut bytes = 42u64.to_le_bytes().to_vec();
+    ///         bytes.truncate(size);
+    ///         Ok(bytes)
+    ///     }
+    ///
+    ///     fn write(&mut self, _offset: usize, _data: &[u8]) -> Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.map_device(0x1000..0x1004, Answer).unwrap();
+    /// let program = vec![0x02, 0x00, 0x00, 0x10, 0x00, 0x00, 0xff]; // LD 0 0x1000, HLT
+    /// assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(2));
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 42);
+    /// ```
+    pub fn map_device<D: device::Device + 'static>(
+        &mut self,
+        range: std::ops::Range<usize>,
+        device: D,
+    ) -> Result<(), error::VmError> {
+        self.memory.map_device(range, Box::new(device))
+    }
+
+    /// Get the device of type `D` mapped at an address.
+    ///
+    /// # Parameters:
+    /// - `address`: Any address in the range of the device.
+    ///
+    /// # Returns:
+    /// The device, or `None` if no device of type `D` is mapped at the address.
+    pub fn device<D: device::Device>(&self, address: usize) -> Option<&D> {
+        self.memory.device(address)
+    }
+
+    /// Get the device of type `D` mapped at an address, mutably.
+    ///
+    /// # Parameters:
+    /// - `address`: Any address in the range of the device.
+    ///
+    /// # Returns:
+    /// The device, or `None` if no device of type `D` is mapped at the address.
+    pub fn device_mut<D: device::Device>(&mut self, address: usize) -> Option<&mut D> {
+        self.memory.device_mut(address)
+    }
+
+    /// Map a memory-mapped I/O device able to request interrupts.
+    /// The interrupt line is asserted while `Device::interrupt` returns `true`.
+    ///
+    /// # Parameters:
+    /// - `range`: The addresses handled by the device, inside or beyond the memory size.
+    /// - `device`: The device receiving the accesses to the range.
+    /// - `line`: The interrupt line of the device.
+    ///
+    /// # Returns:
+    /// - `Err(VmError)`: If the range is empty or overlaps another device.
+    pub fn map_device_with_interrupt<D: device::Device + 'static>(
+        &mut self,
+        range: std::ops::Range<usize>,
+        device: D,
+        line: u8,
+    ) -> Result<(), error::VmError> {
+        self.memory
+            .map_device_with_interrupt(range, Box::new(device), Some(line))
+    }
+
+    /// Remove all the devices.
+    pub fn clear_devices(&mut self) {
+        self.memory.clear_devices();
+    }
+
+    /// Load an image, such as a data blob or another program, into the memory at an address.
+    /// The images are kept across runs: the memory is cleared at the start of each run, then the
+    /// images are copied in the order they were loaded, before the program in the Von Neumann mode.
+    ///
+    /// # Parameters:
+    /// - `image`: The bytes to load.
+    /// - `base`: The address of the first byte.
+    ///
+    /// # Errors:
+    /// Returns `VmError::MemoryOutOfBounds` if the image does not fit in the memory.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::VM;
+    /// let mut vm = VM::<i32>::new(1024, 1024);
+    /// vm.load_at(&42i32.to_le_bytes(), 0x100).unwrap();
+    /// let program = vec![0x02, 0x00, 0x00, 0x01, 0x00, 0x00, 0xff]; // LD 0 0x100, HLT
+    /// assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(2));
+    /// assert_eq!(vm.snapshot().cpu.registers[0], 42);
+    /// ```
+    pub fn load_at(&mut self, image: &[u8], base: usize) -> Result<(), error::VmError> {
+        self.memory.write_bytes(base, image)?;
+        self.images.push((base, image.to_vec()));
+        Ok(())
+    }
+
+    /// Remove all the images loaded with `load_at`.
+    pub fn clear_images(&mut self) {
+        self.images.clear();
+    }
+
+    /// Get the layout of the memory, if the VM was built with one.
+    pub fn layout(&self) -> Option<&layout::MemoryLayout> {
+        self.layout.as_ref()
+    }
+
+    /// Set the debug information of the programs run by the VM, used by `location` to describe
+    /// the program counter in terms of the source.
+    pub fn set_debug_info(&mut self, debug_info: debug_info::DebugInfo) {
+        self.debug_info = Some(debug_info);
+    }
+
+    /// Get the debug information set with `set_debug_info`.
+    pub fn debug_info(&self) -> Option<&debug_info::DebugInfo> {
+        self.debug_info.as_ref()
+    }
+
+    /// Describe the location of the program counter, e.g. the faulting instruction after an
+    /// error, as ``in function `loop`, line 12`` with the debug information, or as its address.
+    pub fn location(&self) -> String {
+        match &self.debug_info {
+            Some(debug_info) => debug_info.describe(self.cpu.pc()),
+            None => format!("at address 0x{:x}", self.cpu.pc()),
+        }
+    }
+
+    /// Render the state of the VM as a compact table: the registers, four per line, the status
+    /// flags, the program counter with its disassembled instruction and the top of the stack.
+    /// `Display` renders the same table.
+    ///
+    /// # Example:
+    /// ```
+    /// use forge_vm::asm::Assembler;
+    /// use forge_vm::VM;
+    /// let program = Assembler::new().assemble("DEC R1\nPUSH 7\nHLT").unwrap();
+    /// let mut vm = VM::<i32>::new(16, 1024);
+    /// vm.run(&program).unwrap();
+    /// assert_eq!(
+    ///     vm.render_state(),
+    ///     "R0  0x00000000 0   R1  0xffffffff -1  R2  0x00000000 0   R3  0x00000000 0\n\
+    ///      flags Z=0 C=0 O=0 N=1 I=0\n\
+    ///      pc    0x00000007 HLT\n\
+    ///      stack [7] (depth 1)\n"
+    /// );
+    /// ```
+    pub fn render_state(&self) -> String {
+        let width = 2 * std::mem::size_of::<T>();
+        let cells: Vec<String> = (self.registers().iter().enumerate())
+            .map(|(index, value)| format!("R{:<2} 0x{:0width$x} {}", index, value, value))
+            .collect();
+        let column = cells.iter().map(String::len).max().unwrap_or_default();
+        let mut state = String::new();
+        for row in cells.chunks(4) {
+            let row: Vec<String> = row
+                .iter()
+                .map(|cell| format!("{:<column$}", cell))
+                .collect();
+            state += row.join("  ").trim_end();
+            state.push('\n');
+        }
+
+        let flags = self.flags();
+        state += &format!(
+            "flags Z={} C={} O={} N={} I={}\n",
+            flags.zero as u8,
+            flags.carry as u8,
+            flags.overflow as u8,
+            flags.negative as u8,
+            flags.interrupt_enable as u8
+        );
+        let pc = self.pc();
+        let instruction = match self.instruction_at(pc) {
+            Some(instruction) => instruction.to_string(),
+            None => "?".to_string(),
+        };
+        state += &format!("pc    0x{:0width$x} {}\n", pc, instruction);
+
+        // the 8 values at the top of the stack
+        let stack = self.stack_slice();
+        let top = &stack[stack.len().saturating_sub(8)..];
+        let values: Vec<String> = top.iter().map(ToString::to_string).collect();
+        let ellipsis = if top.len() < stack.len() { "..., " } else { "" };
+        state += &format!(
+            "stack [{}{}] (depth {})\n",
+            ellipsis,
+            values.join(", "),
+            stack.len()
+        );
+        state
+    }
+
+    /// Get the instruction at an address of the program, from the decoded instructions of
+    /// the last run or else from its code, `None` if it cannot be decoded.
+    fn instruction_at(&self, pc: usize) -> Option<instructions::Instruction<T, T::Address>> {
+        let code = match self.program_base {
+            // the code can be modified since its decoding
+            Some(_) => self.memory.bytes().get(pc..).unwrap_or_default(),
+            None => match self.decoded.get(pc) {
+                Some(entry) => return Some(entry.instruction),
+                None => self.program.slice_from(pc),
+            },
+        };
+        decoder::Decoder::<T>::with_registers(self.cpu.registers_count())
+            .with_endianness(self.memory.endianness())
+            .decode_instruction(code)
+            .ok()
+    }
+
+    /// Get the id of the running green thread, `0` for the thread started by `run`.
+    pub fn current_thread(&self) -> usize {
+        self.threads.current()
+    }
+
+    /// Get a snapshot of the performance counters of the last run.
+    ///
+    /// # Returns
+    /// The counters as seen by the guest through the `RDCNT` instruction.
+    pub fn counters(&self) -> counters::PerfCounters {
+        counters::PerfCounters {
+            steps: self.steps,
+            fuel_remaining: self.step_limit.saturating_sub(self.steps),
+            stack_high_water: self.cpu.stack_high_water_mark(&self.stack),
+            cycles: self.cycles,
+            interrupts_delivered: self.interrupts_delivered,
+        }
+    }
+}
+
+impl<T: Word> std::fmt::Display for VM<T> {
+    /// Render the state of the VM, see `render_state`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.render_state())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vm_create() {
+        let vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(vm.stack.capacity(), 1024);
+        assert_eq!(vm.memory.capacity(), 1024);
+    }
+
+    #[test]
+    fn test_vm_run() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x00, 0x00, 0xff]; // NOP, NOP, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+    }
+
+    #[test]
+    fn test_vm_run_with_stack_overflow() {
+        let mut vm = VM::<i32>::new(1, 1024);
+        let program = vec![0x10, 0x00, 0x10, 0x00, 0xff]; // PUSH 0, PUSH 0, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::StackOverflow)
+        );
+    }
+
+    #[test]
+    fn test_vm_run_with_stack_underflow() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x11, 0x00, 0xff]; // POP 0, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn test_vm_run_with_memory_out_of_bounds() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x02, 0x01, 0x00, 0x04, 0x00, 0x00, 0xff]; // LD 1 0x00000400, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 1024,
+                size: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_with_invalid_op_code() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0xf0, 0xff]; // Invalid instruction
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::InvalidOpcode { opcode: 0xf0 })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_add() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![
+            0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x01, 0x07, 0x00, 0x00, 0x00, 0x09, 0x00,
+            0x00, 0x01, 0xff,
+        ]; // LD 0 0x02, LD 1 0x07, ADD 0 0 1, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        assert_eq!(vm.cpu.get_register(0), Ok(9));
+    }
+
+    #[test]
+    fn test_vm_snapshot_restore() {
+        let mut vm = VM::<i32>::new(16, 16);
+        let program = vec![
+            0x01, 0x01, 0x07, 0x00, 0x00, 0x00, 0x10, 0x01, 0x03, 0x01, 0x08, 0x00, 0x00, 0x00,
+            0xff,
+        ]; // MOV 1 7, PUSHREG 1, ST 1 0x08, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        let snapshot = vm.snapshot();
+        assert_eq!(snapshot.cpu.registers, [0, 7, 0, 0]);
+        assert_eq!(snapshot.cpu.pc, 14);
+        assert_eq!(snapshot.stack, vec![7]);
+        assert_eq!(&snapshot.memory[8..12], &[7, 0, 0, 0]);
+        assert_eq!(snapshot.steps, 4);
+
+        let mut other = VM::<i32>::new(16, 16);
+        assert_eq!(other.restore(&snapshot), Ok(()));
+        assert_eq!(other.snapshot(), snapshot);
+        assert_eq!(other.cpu.get_register(1), Ok(7));
+    }
+
+    #[test]
+    fn test_vm_restore_mismatch() {
+        let snapshot = VM::<i32>::new(16, 16).snapshot();
+        assert!(VM::<i32>::new(16, 32).restore(&snapshot).is_err());
+    }
+
+    #[test]
+    fn test_vm_run_with_limit() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x00, 0x00, 0xff]; // NOP, NOP, HLT
+        assert_eq!(
+            vm.run_with_limit(&program, 3)
+                .map(|termination| termination.steps),
+            Ok(3)
+        );
+        assert_eq!(
+            vm.run_with_limit(&program, 2).map_err(|error| error.source),
+            Err(error::VmError::StepLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_vm_step_after_run_error() {
+        // the VM keeps a copy of a program stopped on an error, to resume it with `step`
+        let code = crate::asm::Assembler::new()
+            .assemble("INC R0\nDIV R1 R1 R2\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        assert_eq!(
+            vm.run(&code).map_err(|error| error.source),
+            Err(error::VmError::DivisionByZero)
+        );
+        assert_eq!(vm.pc(), 2);
+        assert_eq!(vm.step(), Err(error::VmError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_vm_run_arbitrary_programs() {
+        let mut rng = random::Rng::new(random::RandomSource::Seeded(7));
+        for _ in 0..50 {
+            let input: Vec<u8> = (0..256).map(|_| rng.next_u64() as u8).collect();
+            let mut vm = VM::<i32>::new(64, 1024);
+            let _ = vm.run_with_limit(&fuzz::arbitrary_program(&input, 4), 1000);
+        }
+    }
+
+    #[test]
+    fn test_vm_run_with_limit_infinite_loop() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x00, 0x12, 0x00, 0x00, 0x00, 0x00]; // NOP, JMP 0x0
+        assert_eq!(
+            vm.run_with_limit(&program, 1000)
+                .map_err(|error| error.source),
+            Err(error::VmError::StepLimitExceeded)
+        );
+        assert_eq!(vm.counters().steps, 1001);
+    }
+
+    #[test]
+    fn test_vm_run_jump_targets() {
+        // the jumps execute the instruction at their target and RET returns after the CALL,
+        // rather than skipping the size of the jump past them
+        let program = crate::asm::Assembler::new()
+            .assemble(
+                "
+                    JMP target
+                    MOV R0 1
+                target:
+                    MOV R1 2
+                    CALL function
+                    MOV R2 3
+                    HLT
+                function:
+                    INC R3
+                    RET
+                ",
+            )
+            .unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(7));
+        assert_eq!(vm.registers(), &[0, 2, 3, 1]);
+        // the HLT after JMP, MOV, MOV, CALL and MOV
+        assert_eq!(vm.pc(), 5 + 6 + 6 + 5 + 6);
+    }
+
+    #[test]
+    fn test_vm_run_rdcnt_fuel_remaining() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x00, 0x19, 0x00, 0x02, 0xff]; // NOP, RDCNT 0 FUEL_REMAINING, HLT
+        assert_eq!(
+            vm.run_with_limit(&program, 10)
+                .map(|termination| termination.steps),
+            Ok(3)
+        );
+        assert_eq!(vm.cpu.get_register(0), Ok(8));
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.cpu.get_register(0), Ok(i32::MAX));
+    }
+
+    #[test]
+    fn test_vm_run_with_watchpoint() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.add_watchpoint(watchpoint::Watchpoint::read(0x20..0x24));
+        let program = vec![
+            0x02, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x02, 0x01, 0x20, 0x00, 0x00, 0x00, 0xff,
+        ]; // LD 0 0x1c, LD 1 0x20, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::WatchpointHit {
+                pc: 6,
+                address: 0x20,
+                size: 4,
+                access: watchpoint::Access::Read
+            })
+        );
+
+        vm.clear_watchpoints();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+    }
+
+    #[test]
+    fn test_vm_run_memory_protection() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.add_region(protection::Region::new(
+            0x0..0x100,
+            protection::Permissions::READ_WRITE,
+        ));
+        vm.add_region(protection::Region::new(
+            0x100..0x104,
+            protection::Permissions::READ,
+        ));
+        // the data is writable, the read-only word is readable
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 7\nST R0 0x80\nLD R1 0x100\nHLT")
+            .unwrap();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+
+        // the read-only word is not writable, nothing is accessible outside the regions
+        for (source, address, size, permission) in [
+            ("ST R0 0x100\nHLT", 0x100, 4, protection::Permission::Write),
+            ("LD R0 0x104\nHLT", 0x104, 4, protection::Permission::Read),
+            (
+                "LDB R1 0xff\nLDH R1 0x102\nLDB R1 0x104\nHLT",
+                0x104,
+                1,
+                protection::Permission::Read,
+            ),
+        ] {
+            let program = crate::asm::Assembler::new().assemble(source).unwrap();
+            assert_eq!(
+                vm.run(&program).map_err(|error| error.source),
+                Err(error::VmError::ProtectionFault {
+                    address,
+                    size,
+                    permission
+                })
+            );
+        }
+
+        // the unprotected memory accepts the same accesses
+        vm.clear_regions();
+        let program = crate::asm::Assembler::new()
+            .assemble("ST R0 0x100\nLD R0 0x104\nHLT")
+            .unwrap();
+        assert!(vm.run(&program).is_ok());
+    }
+
+    #[test]
+    fn test_vm_run_von_neumann_protection() {
+        let mut vm = builder::VmBuilder::new()
+            .memory_size(0x400)
+            .von_neumann(0x100)
+            .build::<i32>();
+        vm.add_region(protection::Region::new(
+            0x100..0x200,
+            protection::Permissions::READ_EXECUTE,
+        ));
+        vm.add_region(protection::Region::new(
+            0x200..0x300,
+            protection::Permissions::READ_WRITE,
+        ));
+        let program = crate::asm::Assembler::new()
+            .origin(0x100)
+            .assemble("LDBU R0 data\nST R0 0x200\nHLT\ndata:\nNOP")
+            .unwrap();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+
+        // the code is not writable, the data is not executable
+        for (source, address, size, permission) in [
+            (
+                "ST R0 0x104\nHLT".to_string(),
+                0x104,
+                4,
+                protection::Permission::Write,
+            ),
+            (
+                "JMP 0x200\nHLT".to_string(),
+                0x200,
+                1,
+                protection::Permission::Execute,
+            ),
+            // the MOV crosses the end of the code
+            (
+                format!("{}MOV R0 1", "NOP\n".repeat(0xfe)),
+                0x1fe,
+                6,
+                protection::Permission::Execute,
+            ),
+        ] {
+            let program = crate::asm::Assembler::new()
+                .origin(0x100)
+                .assemble(&source)
+                .unwrap();
+            assert_eq!(
+                vm.run(&program).map_err(|error| error.source),
+                Err(error::VmError::ProtectionFault {
+                    address,
+                    size,
+                    permission
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_vm_run_layout_protection() {
+        let layout =
+            layout::MemoryLayout::new(0x100..0x200, 0x200..0x300, 0x300..0x380, 0x380..0x400)
+                .unwrap();
+        let mut vm = layout
+            .protection()
+            .into_iter()
+            .fold(
+                builder::VmBuilder::new().memory_size(0),
+                |builder, region| builder.region(region),
+            )
+            .layout(layout)
+            .build::<i32>();
+        // the stack in memory, the data and the heap are writable
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 7\nPUSHREG R0\nST R0 0x200\nST R0 0x300\nHLT")
+            .unwrap();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(5));
+
+        // the code is not writable, the data is not executable, nothing is accessible outside
+        for (source, address, size, permission) in [
+            ("ST R0 0x100\nHLT", 0x100, 4, protection::Permission::Write),
+            ("JMP 0x200\nHLT", 0x200, 1, protection::Permission::Execute),
+            ("LD R0 0x0\nHLT", 0x0, 4, protection::Permission::Read),
+        ] {
+            let program = crate::asm::Assembler::new().assemble(source).unwrap();
+            assert_eq!(
+                vm.run(&program).map_err(|error| error.source),
+                Err(error::VmError::ProtectionFault {
+                    address,
+                    size,
+                    permission
+                })
+            );
+        }
+
+        // the CPU reads the vector table at 0, outside the regions, to enter the handler
+        let faulting = crate::asm::Assembler::new()
+            .assemble("ST R0 0x100\nHLT\n")
+            .unwrap();
+        let program = crate::asm::Assembler::new()
+            .assemble("ST R0 0x100\nHLT\nMOV R1 9\nHLT")
+            .unwrap();
+        let handler = 0x100 + faulting.len() as u32;
+        vm.load_at(&handler.to_le_bytes(), 4).unwrap();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.cpu.get_register(1), Ok(9));
+    }
+
+    #[test]
+    fn test_vm_run_rdcnt() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![
+            0x10, 0x00, 0x10, 0x01, 0x11, 0x00, 0x19, 0x02, 0x00, 0x19, 0x03, 0x01, 0xff,
+        ]; // PUSHREG 0, PUSHREG 1, POPREG 0, RDCNT 2 STEPS, RDCNT 3 STACK_HIGH_WATER, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(6));
+        assert_eq!(vm.cpu.get_register(2), Ok(4));
+        assert_eq!(vm.cpu.get_register(3), Ok(2));
+        assert_eq!(vm.counters().stack_high_water, 2);
+    }
+
+    #[test]
+    fn test_vm_run_rdcycle() {
+        let model = cost::CostModel::new().with_cost(instructions::OpCode::MULT, 10);
+        let mut vm = builder::VmBuilder::new().cost_model(model).build::<i32>();
+        let program = vec![0x0b, 0x00, 0x01, 0x02, 0x73, 0x01, 0x19, 0x02, 0x03, 0xff]; // MULT R0 R1 R2, RDCYCLE 1, RDCNT 2 CYCLES, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        assert_eq!(vm.cpu.get_register(1), Ok(11));
+        assert_eq!(vm.cpu.get_register(2), Ok(12));
+        assert_eq!(vm.cycles(), 13);
+    }
+
+    #[test]
+    fn test_vm_run_with_invalid_counter() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x19, 0x00, 0x7f, 0xff]; // RDCNT 0 0x7f, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::InvalidCounter { counter: 0x7f })
+        );
+    }
+
+    #[test]
+    fn test_vm_64_run_add() {
+        let mut vm = VM::<i64>::new(1024, 1024);
+        let mut program = vec![0x01, 0x00]; // MOV 0 0x7fffffff
+        program.extend_from_slice(&0x7fff_ffffi64.to_le_bytes());
+        program.extend_from_slice(&[0x01, 0x01]); // MOV 1 1
+        program.extend_from_slice(&1i64.to_le_bytes());
+        program.extend_from_slice(&[0x09, 0x02, 0x00, 0x01]); // ADD 2 0 1
+        program.extend_from_slice(&[0x03, 0x02]); // ST 2 0x8
+        program.extend_from_slice(&8u64.to_le_bytes());
+        program.extend_from_slice(&[0x02, 0x03]); // LD 3 0x8
+        program.extend_from_slice(&8u64.to_le_bytes());
+        program.push(0xff); // HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(6));
+        assert_eq!(vm.cpu.get_register(2), Ok(0x8000_0000));
+        assert_eq!(vm.cpu.get_register(3), Ok(0x8000_0000));
+    }
+
+    #[test]
+    fn test_vm_64_run_call_ret() {
+        let mut vm = VM::<i64>::new(1024, 1024);
+        let mut program = vec![0x16]; // CALL 0xb
+        program.extend_from_slice(&11u64.to_le_bytes());
+        program.push(0xff); // HLT
+        program.push(0x00); // NOP
+        program.extend_from_slice(&[0x0e, 0x00, 0x17]); // INC 0, RET
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        assert_eq!(vm.cpu.get_register(0), Ok(1));
+    }
+
+    #[test]
+    fn test_vm_64_run_with_limit() {
+        let mut vm = VM::<i64>::new(1024, 1024);
+        let mut program = vec![0x12]; // JMP 0x0
+        program.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(
+            vm.run_with_limit(&program, 100)
+                .map_err(|error| error.source),
+            Err(error::VmError::StepLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn test_vm_16_run_add() {
+        let mut vm = VM::<i16>::new(1024, 1024);
+        let program = vec![
+            0x01, 0x00, 0xff, 0x7f, 0x01, 0x01, 0x01, 0x00, 0x09, 0x02, 0x00, 0x01, 0x13, 0x11,
+            0x00, 0xff, 0x00, 0xff,
+        ]; // MOV 0 0x7fff, MOV 1 1, ADD 2 0 1, JMPN 0x11, HLT, NOP, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(5));
+        assert_eq!(vm.cpu.get_register(2), Ok(i16::MIN));
+    }
+
+    #[test]
+    fn test_vm_unsigned_run_sub() {
+        let mut vm = VM::<u32>::new(1024, 1024);
+        let program = vec![0x0f, 0x00, 0x14, 0x07, 0x00, 0x00, 0x00, 0xff, 0xff]; // DEC 0, JMPP 0x7, HLT, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.cpu.get_register(0), Ok(u32::MAX));
+    }
+
+    #[test]
+    fn test_vm_run_with_division_by_zero() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x0c, 0x00, 0x01, 0x02, 0xff]; // DIV 0 1 2, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::DivisionByZero)
+        );
+        let program = vec![0x0d, 0x00, 0x01, 0x02, 0xff]; // MOD 0 1 2, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_vm_run_execution_error() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x00, 0x00, 0x0c, 0x00, 0x01, 0x02, 0xff]; // NOP, NOP, DIV 0 1 2, HLT
+        let error = vm.run(&program).unwrap_err();
+        assert_eq!(
+            error,
+            error::ExecutionError {
+                pc: 2,
+                step: 3,
+                instruction: Some(instructions::Instruction::DIV {
+                    dest: 0,
+                    reg1: 1,
+                    reg2: 2
+                }),
+                source: error::VmError::DivisionByZero,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Attempted to divide by zero at address 0x2 (DIV R0 R1 R2), step 3"
+        );
+        assert_eq!(error::VmError::from(error), error::VmError::DivisionByZero);
+
+        // an invalid opcode cannot be decoded
+        let error = vm.run(&[0x00, 0xf0]).unwrap_err();
+        assert_eq!((error.pc, error.step, error.instruction), (1, 2, None));
+    }
+
+    #[test]
+    fn test_vm_run_termination() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x01, 0x00, 0x07, 0x00, 0x00, 0x00, 0xff]; // MOV 0 7, HLT
+        assert_eq!(
+            vm.run(&program),
+            Ok(termination::Termination {
+                steps: 2,
+                exit_code: 7
+            })
+        );
+        assert_eq!(vm.exit_code(), Some(7));
+
+        // EXIT terminates the program at once and stays on its address
+        let program = vec![0x70, 0xfd, 0xff, 0xff, 0xff, 0x0e, 0x00, 0xff]; // EXIT -3, INC 0, HLT
+        assert_eq!(
+            vm.run(&program),
+            Ok(termination::Termination {
+                steps: 1,
+                exit_code: -3
+            })
+        );
+        assert_eq!((vm.pc(), vm.cpu.get_register(0)), (0, Ok(0)));
+
+        vm.load_program(&program::Program::new(&[0x0e, 0x00, 0xff])) // INC 0, HLT
+            .unwrap();
+        assert_eq!(vm.step(), Ok(false));
+        assert_eq!(vm.exit_code(), None);
+        assert_eq!(vm.step(), Ok(true));
+        assert_eq!(vm.exit_code(), Some(1));
+    }
+
+    #[test]
+    fn test_vm_run_syscall() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_host_fn(0, |cpu, _memory| {
+            let value = cpu.register(1)?;
+            cpu.set_register(1, value * 2)
+        });
+        let program = vec![
+            0x01, 0x01, 0x15, 0x00, 0x00, 0x00, 0x1a, 0x00, 0x1a, 0x00, 0xff,
+        ]; // MOV 1 21, SYSCALL 0, SYSCALL 0, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        assert_eq!(vm.cpu.get_register(1), Ok(84));
+    }
+
+    #[test]
+    fn test_vm_run_ports() {
+        /// A port counting its reads and keeping the last written value.
+        struct Counter {
+            reads: i32,
+            written: std::rc::Rc<std::cell::Cell<i32>>,
+        }
+
+        impl port::PortHandler<i32> for Counter {
+            fn read(&mut self) -> Result<i32, error::VmError> {
+                self.reads += 1;
+                Ok(self.reads)
+            }
+
+            fn write(&mut self, value: i32) -> Result<(), error::VmError> {
+                self.written.set(value);
+                Ok(())
+            }
+        }
+
+        let written = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_port(
+            0x3f8,
+            Counter {
+                reads: 0,
+                written: written.clone(),
+            },
+        );
+        let program = vec![
+            0x71, 0x00, 0xf8, 0x03, 0x71, 0x01, 0xf8, 0x03, 0x72, 0xf8, 0x03, 0x01, 0xff,
+        ]; // IN 0 0x3f8, IN 1 0x3f8, OUT 0x3f8 1, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        assert_eq!(vm.registers()[..2], [1, 2]);
+        assert_eq!(written.get(), 2);
+
+        assert!(vm.unregister_port(0x3f8));
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::UnmappedPort { port: 0x3f8 })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_syscall_memory() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_host_fn(7, |cpu, memory| {
+            let address = cpu.register(0)? as usize;
+            memory.write_bytes(address, b"ok")
+        });
+        let program = vec![0x01, 0x00, 0x10, 0x00, 0x00, 0x00, 0x1a, 0x07, 0xff]; // MOV 0 16, SYSCALL 7, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(&vm.memory.bytes()[16..18], b"ok");
+    }
+
+    #[test]
+    fn test_vm_run_unknown_syscall() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![0x1a, 0x03, 0xff]; // SYSCALL 3, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::UnknownSyscall { number: 3 })
+        );
+        vm.register_host_fn(3, |_cpu, _memory| Err(error::VmError::Other("exit".into())));
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::Other("exit".into()))
+        );
+        assert!(vm.unregister_host_fn(3));
+        assert!(!vm.unregister_host_fn(3));
+    }
+
+    /// A device recording the bytes written to it.
+    struct RecorderDevice {
+        written: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+    }
+
+    impl device::Device for RecorderDevice {
+        fn read(&mut self, _offset: usize, size: usize) -> error::Result<Vec<u8>> {
+            Ok(vec![0xff; size])
+        }
+
+        fn write(&mut self, _offset: usize, data: &[u8]) -> error::Result<()> {
+            self.written.borrow_mut().extend_from_slice(data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_vm_run_with_device() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let written = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        vm.map_device(
+            0x2000..0x2004,
+            RecorderDevice {
+                written: written.clone(),
+            },
+        )
+        .unwrap();
+        let program = vec![
+            0x02, 0x01, 0x00, 0x20, 0x00, 0x00, 0x01, 0x00, 0x41, 0x00, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x20, 0x00, 0x00, 0xff,
+        ]; // LD 1 0x2000, MOV 0 0x41, ST 0 0x2000, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        assert_eq!(vm.cpu.get_register(1), Ok(-1));
+        assert_eq!(*written.borrow(), vec![0x41, 0x00, 0x00, 0x00]);
+
+        vm.clear_devices();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 0x2000,
+                size: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_with_interrupt() {
+        let source = "
+                MOV R0 handler
+                ST R0 16
+                EI
+                NOP
+                HLT
+            handler:
+                INC R1
+                IRET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.raise_interrupt(4);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(7));
+        assert_eq!(vm.cpu.get_register(1), Ok(1));
+        assert!(vm.stack.values().is_empty());
+        assert!(vm.cpu.interrupts_enabled());
+
+        // the interrupt stays pending while the interrupts are disabled
+        let program = crate::asm::Assembler::new()
+            .assemble("DI\nNOP\nHLT")
+            .unwrap();
+        vm.raise_interrupt(4);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert!(vm.interrupts.is_pending(4));
+    }
+
+    #[test]
+    fn test_vm_run_with_timer_interrupt() {
+        let source = "
+                MOV R0 handler
+                ST R0 16
+                MOV R0 5
+                ST R0 0x1000
+                ST R0 0x1008
+                MOV R0 1
+                ST R0 0x1010
+                MOV R0 3
+                EI
+            loop:
+                NOP
+                JMP loop
+            end:
+                RDCNT R2 4
+                HLT
+            handler:
+                INC R1
+                ST R1 0x1018
+                CMP R1 R0
+                JMPZ end
+                IRET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.map_device_with_interrupt(
+            0x1000..0x1000 + timer::TimerDevice::SIZE,
+            timer::TimerDevice::new(),
+            4,
+        )
+        .unwrap();
+        assert!(vm.run_with_limit(&program, 1000).is_ok());
+        assert_eq!(vm.cpu.get_register(1), Ok(3));
+        // halted in the handler of the third interrupt, with the saved state on the stack
+        assert_eq!(vm.stack.values().len(), 2);
+        // the guest and the host count the delivered interrupts
+        assert_eq!(vm.cpu.get_register(2), Ok(3));
+        assert_eq!(vm.counters().interrupts_delivered, 3);
+    }
+
+    #[test]
+    fn test_vm_run_with_division_by_zero_trap() {
+        // the handler skips the faulting DIV, 4 bytes long, and counts the traps in R3
+        let source = "
+                MOV R0 handler
+                ST R0 0
+                DIV R0 R1 R2
+                DIV R0 R1 R2
+                HLT
+            handler:
+                INC R3
+                POPREG R1
+                POPREG R0
+                MOV R2 4
+                ADD R0 R0 R2
+                PUSHREG R0
+                PUSHREG R1
+                MOV R1 0
+                MOV R2 0
+                IRET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(
+            vm.run(&program).map(|termination| termination.steps),
+            Ok(25)
+        );
+        assert_eq!(vm.cpu.get_register(3), Ok(2));
+        assert!(vm.stack.values().is_empty());
+        // the traps are not counted as interrupts
+        assert_eq!(vm.counters().interrupts_delivered, 0);
+    }
+
+    #[test]
+    fn test_vm_run_without_superinstructions() {
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 10\nloop: INC R1\nDEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        let fused = cpu::CPU::<i32>::fuse(
+            instructions::Instruction::DEC { reg: 0 },
+            instructions::Instruction::JMPNZ { address: 2 },
+        )
+        .unwrap();
+        let mut vm = builder::VmBuilder::new().build::<i32>();
+        let mut unfused = builder::VmBuilder::new()
+            .superinstructions(false)
+            .build::<i32>();
+        assert!(vm.can_fuse(fused));
+        assert!(!unfused.can_fuse(fused));
+        // the pairs executed one at a time give the same run
+        assert_eq!(vm.run(&program), unfused.run(&program));
+        assert_eq!(vm.snapshot(), unfused.snapshot());
+    }
+
+    #[test]
+    fn test_vm_run_with_checked_arithmetic() {
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 0x7fffffff\nMOV R1 1\nADD R2 R0 R1\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(2), Ok(i32::MIN));
+
+        let mut vm = builder::VmBuilder::new()
+            .checked_arithmetic(true)
+            .build::<i32>();
+        let error = vm.run(&program).unwrap_err();
+        assert_eq!(error.source, error::VmError::ArithmeticOverflow);
+        assert_eq!(error.pc, 12);
+        // the faulting instruction has no effect
+        assert_eq!(vm.cpu.get_register(2), Ok(0));
+        assert!(!vm.cpu.flags().overflow);
+
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 0x80000000\nMOV R1 -1\nDIV R2 R0 R1\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn test_vm_run_fixed_point() {
+        // the area of a circle of radius 2.5 in Q16.16, then divided back by the radius
+        let source = "
+            MOV R0 0x28000
+            MOV R1 0x3243f
+            FXMUL R2 R0 R0
+            FXMUL R2 R2 R1
+            FXDIV R3 R2 R0
+            HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        for (rounding, area, quotient) in [
+            (fixed::Rounding::TowardZero, 1286793, 514717),
+            (fixed::Rounding::Nearest, 1286794, 514718),
+        ] {
+            let mut vm = builder::VmBuilder::new()
+                .fixed_rounding(rounding)
+                .build::<i32>();
+            assert!(vm.run(&program).is_ok());
+            assert_eq!(vm.cpu.get_register(2), Ok(area));
+            assert_eq!(vm.cpu.get_register(3), Ok(quotient));
+        }
+
+        let program = crate::asm::Assembler::new()
+            .assemble(
+                "MOV R0 0x10000
+FXDIV R1 R0 R1
+HLT",
+            )
+            .unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::DivisionByZero)
+        );
+        // 256 * 256 overflows the 16 bits of the integer part
+        let program = crate::asm::Assembler::new()
+            .assemble(
+                "MOV R0 0x1000000
+FXMUL R1 R0 R0
+HLT",
+            )
+            .unwrap();
+        assert!(vm.run(&program).is_ok());
+        assert!(vm.cpu.flags().overflow);
+    }
+
+    #[test]
+    fn test_vm_run_with_overflow_trap() {
+        // the handler of the arithmetic trap counts the overflows in R3 and skips the INC, 2 bytes
+        let source = "
+                MOV R0 handler
+                ST R0 0
+                MOV R0 0x7ffffffe
+                INC R0
+                INC R0
+                INC R0
+                HLT
+            handler:
+                INC R3
+                POPREG R1
+                POPREG R2
+                INC R2
+                INC R2
+                PUSHREG R2
+                PUSHREG R1
+                IRET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = builder::VmBuilder::new()
+            .checked_arithmetic(true)
+            .build::<i32>();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(0), Ok(i32::MAX));
+        assert_eq!(vm.cpu.get_register(3), Ok(2));
+    }
+
+    #[test]
+    fn test_vm_run_with_invalid_memory_trap() {
+        let source = "
+                MOV R0 handler
+                ST R0 4
+                LD R0 0xffff
+            handler:
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        // the address of the faulting LD is saved under the flags
+        assert_eq!(vm.stack.values(), &[12, 0]);
+
+        // without handler the fault stops the execution
+        let program = crate::asm::Assembler::new()
+            .assemble("LD R0 0xffff\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 0xffff,
+                size: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_with_invalid_instruction_trap() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![
+            0x01, 0x00, 0x0e, 0x00, 0x00, 0x00, 0x03, 0x00, 0x08, 0x00, 0x00, 0x00, 0xee, 0xff,
+            0xff,
+        ]; // MOV 0 0xe, ST 0 0x8, <invalid>, HLT, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(3));
+        assert_eq!(vm.stack.values(), &[12, 0]);
+
+        // a jump past the end of the program is an invalid instruction
+        let program = vec![0x12, 0x00, 0x01, 0x00, 0x00, 0xff]; // JMP 0x100, HLT
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::InvalidInstruction)
+        );
+    }
+
+    #[test]
+    fn test_vm_run_shifts() {
+        let source = "
+                MOV R0 -16
+                MOV R1 2
+                SHL R2 R0 R1
+                SHRI R3 R0 28
+                SAR R0 R0 R1
+                SARI R1 R1 2
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(7));
+        assert_eq!(vm.cpu.get_register(0), Ok(-4));
+        assert_eq!(vm.cpu.get_register(1), Ok(0));
+        assert_eq!(vm.cpu.get_register(2), Ok(-64));
+        assert_eq!(vm.cpu.get_register(3), Ok(0xf));
+        // SARI 2 shifted out the bit 1 of R1
+        let flags = vm.snapshot().cpu.status_flags;
+        assert!(flags.carry && flags.zero && !flags.negative);
+    }
+
+    #[test]
+    fn test_vm_run_rotations() {
+        let source = "
+                MOV R0 0x80000001
+                MOV R1 4
+                ROL R2 R0 R1
+                ROR R3 R0 R1
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(5));
+        assert_eq!(vm.cpu.get_register(2), Ok(0x18));
+        assert_eq!(vm.cpu.get_register(3), Ok(0x18000000));
+        // ROR 4 rotated out the bit 3, now the bit 31
+        assert!(!vm.snapshot().cpu.status_flags.carry);
+    }
+
+    #[test]
+    fn test_vm_run_movr() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let program = vec![
+            0x01, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x26, 0x03, 0x00, 0x26, 0x01, 0x03, 0xff,
+        ]; // MOV 0 42, MOVR 3 0, MOVR 1 3, HLT
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(4));
+        assert_eq!(vm.cpu.get_register(1), Ok(42));
+        assert_eq!(vm.cpu.get_register(3), Ok(42));
+    }
+
+    #[test]
+    fn test_vm_run_indirect_load_store() {
+        // sum the array of 3 words at 0x100 and store the sum after it
+        let source = "
+                MOV R0 5
+                ST R0 0x100
+                MOV R0 7
+                ST R0 0x104
+                MOV R0 9
+                ST R0 0x108
+                MOV R1 0x100
+                MOV R2 4
+                MOV R3 0
+            loop:
+                LDR R0 [R1]
+                ADD R3 R3 R0
+                ADD R1 R1 R2
+                MOV R0 0x10c
+                CMP R0 R1
+                JMPZ end
+                JMP loop
+            end:
+                STR R3 [R1]
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.memory.read::<i32>(0x10c), Ok(21));
+
+        let program = vec![0x01, 0x00, 0xff, 0xff, 0xff, 0xff, 0x27, 0x01, 0x00, 0xff]; // MOV 0 -1, LDR 1 [0], HLT
+        assert!(matches!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_vm_run_sub_word_load_store() {
+        let source = "
+                MOV R0 0x1234fe80
+                ST R0 0x100
+                LDB R1 0x100
+                LDBU R2 0x100
+                LDH R3 0x102
+                LDHU R0 0x100
+                STB R1 0x105
+                STH R2 0x106
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(9));
+        assert_eq!(vm.cpu.get_register(0), Ok(0xfe80));
+        assert_eq!(vm.cpu.get_register(1), Ok(-0x80));
+        assert_eq!(vm.cpu.get_register(2), Ok(0x80));
+        assert_eq!(vm.cpu.get_register(3), Ok(0x1234));
+        assert_eq!(
+            &vm.memory.bytes()[0x100..0x108],
+            &[0x80, 0xfe, 0x34, 0x12, 0x00, 0x80, 0x80, 0x00]
+        );
+
+        let program = crate::asm::Assembler::new()
+            .assemble("LDH R0 0x101\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryNotAligned {
+                address: 0x101,
+                size: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_vm_64_run_sub_word_load() {
+        use instructions::Instruction;
+        let instructions = [
+            Instruction::<i64, u64>::MOV { dest: 0, value: -2 },
+            Instruction::ST {
+                src: 0,
+                address: 0x10,
+            },
+            Instruction::LDH {
+                dest: 1,
+                address: 0x10,
+            },
+            Instruction::LDHU {
+                dest: 2,
+                address: 0x10,
+            },
+            Instruction::HLT,
+        ];
+        let program = encoder::Encoder::new().encode_program(&instructions);
+        let mut vm = VM::<i64>::new(1024, 1024);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(5));
+        assert_eq!(vm.cpu.get_register(1), Ok(-2));
+        assert_eq!(vm.cpu.get_register(2), Ok(0xfffe));
+    }
+
+    #[test]
+    fn test_vm_run_unsigned_arithmetic() {
+        let source = "
+                MOV R0 -2
+                MOV R1 2
+                DIVU R2 R0 R1
+                MODU R3 R0 R1
+                MULU R0 R0 R1
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(6));
+        assert_eq!(vm.cpu.get_register(2), Ok(0x7fffffff));
+        assert_eq!(vm.cpu.get_register(3), Ok(0));
+        assert_eq!(vm.cpu.get_register(0), Ok(-4));
+        assert!(vm.snapshot().cpu.status_flags.overflow);
+
+        let program = crate::asm::Assembler::new()
+            .assemble("DIVU R0 R1 R2\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_vm_run_unsigned_comparison() {
+        // R3 counts the taken jumps: -1 is above 1 when unsigned
+        let source = "
+                MOV R0 -1
+                MOV R1 1
+                CMPU R0 R1
+                JMPA above
+                HLT
+            above:
+                INC R3
+                JMPAE above_or_equal
+                HLT
+            above_or_equal:
+                INC R3
+                CMPU R1 R0
+                JMPB below
+                HLT
+            below:
+                INC R3
+                CMPU R1 R1
+                JMPBE below_or_equal
+                HLT
+            below_or_equal:
+                INC R3
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(3), Ok(4));
+    }
+
+    #[test]
+    fn test_vm_run_signed_comparison() {
+        // R3 counts the taken jumps: -1 is less than 1 when signed but above it when unsigned
+        let source = "
+                MOV R0 -1
+                MOV R1 1
+                CMP R0 R1
+                JMPLT less
+                HLT
+            less:
+                INC R3
+                CMP R0 R1
+                JMPLE less_or_equal
+                HLT
+            less_or_equal:
+                INC R3
+                CMP R0 R1
+                JMPNC no_carry
+                HLT
+            no_carry:
+                INC R3
+                CMP R1 R0
+                JMPGT greater
+                HLT
+            greater:
+                INC R3
+                CMP R1 R0
+                JMPC carry
+                HLT
+            carry:
+                INC R3
+                CMP R1 R1
+                JMPGE greater_or_equal
+                HLT
+            greater_or_equal:
+                INC R3
+                CMP R1 R1
+                JMPLT less
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(3), Ok(6));
+    }
+
+    #[test]
+    fn test_vm_run_signed_comparison_overflow() {
+        // the difference overflows, the negative flag alone would give the wrong order
+        let source = "
+                MOV R0 -2147483648
+                MOV R1 1
+                CMP R0 R1
+                JMPGE wrong
+                JMPLT done
+            wrong:
+                INC R3
+            done:
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(3), Ok(0));
+        assert!(vm.snapshot().cpu.status_flags.overflow);
+    }
+
+    #[test]
+    fn test_vm_run_jump_not_zero_and_overflow() {
+        // R1 counts the iterations of the loop, R3 is set when the addition overflows
+        let source = "
+                MOV R0 5
+            loop:
+                INC R1
+                DEC R0
+                JMPNZ loop
+                MOV R0 2147483647
+                MOV R2 1
+                ADD R0 R0 R2
+                JMPO overflow
+                HLT
+            overflow:
+                INC R3
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(1), Ok(5));
+        assert_eq!(vm.cpu.get_register(3), Ok(1));
+    }
+
+    #[test]
+    fn test_vm_run_conditional_move() {
+        let source = "
+                MOV R0 3
+                MOV R1 7
+                CMP R0 R1
+                CMOVN R2 R0
+                CMOVZ R2 R1
+                CMP R1 R1
+                CMOVZ R3 R1
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(2), Ok(3));
+        assert_eq!(vm.cpu.get_register(3), Ok(7));
+    }
+
+    #[test]
+    fn test_vm_run_min_max() {
+        let source = "
+                MOV R0 -5
+                MOV R1 3
+                MIN R2 R0 R1
+                MAX R3 R0 R1
+                PUSHREG R2
+                PUSHREG R3
+                MINU R2 R0 R1
+                MAXU R3 R0 R1
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.stack.pop(), Ok(3));
+        assert_eq!(vm.stack.pop(), Ok(-5));
+        assert_eq!(vm.cpu.get_register(2), Ok(3));
+        assert_eq!(vm.cpu.get_register(3), Ok(-5));
+    }
+
+    #[test]
+    fn test_vm_run_bit_counts() {
+        let source = "
+                MOV R0 0x00f0
+                POPCNT R1 R0
+                CLZ R2 R0
+                CTZ R3 R0
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(1), Ok(4));
+        assert_eq!(vm.cpu.get_register(2), Ok(24));
+        assert_eq!(vm.cpu.get_register(3), Ok(4));
+    }
+
+    #[test]
+    fn test_vm_run_bit_operations() {
+        // R3 counts the tested bits that were set
+        let source = "
+                MOV R0 0b1010
+                MOV R1 3
+                BT R0 R1
+                JMPNC clear
+                INC R3
+            clear:
+                BTI R0 2
+                JMPNC set
+                INC R3
+            set:
+                BSI R0 0
+                BC R0 R1
+                BTGI R0 33
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(0), Ok(0b0001));
+        assert_eq!(vm.cpu.get_register(3), Ok(1));
+        assert!(vm.snapshot().cpu.status_flags.carry);
+    }
+
+    #[test]
+    fn test_vm_run_stack_manipulation() {
+        let source = "
+                PUSH 1
+                PUSH 2
+                SWAP
+                DUP
+                PUSH 3
+                DROP
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.stack.values(), &[2, 1, 1]);
+
+        let program = crate::asm::Assembler::new()
+            .assemble("PUSH 1\nSWAP\nHLT")
+            .unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn test_vm_run_push_pop_all() {
+        let source = "
+                MOV R0 1
+                MOV R3 4
+                PUSHA
+                MOV R0 0
+                MOV R3 0
+                POPA
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(0), Ok(1));
+        assert_eq!(vm.cpu.get_register(3), Ok(4));
+        assert!(vm.stack.is_empty());
+
+        let mut vm = VM::<i32>::new(3, 1024);
+        let program = crate::asm::Assembler::new().assemble("PUSHA\nHLT").unwrap();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::StackOverflow)
+        );
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn test_vm_run_push_pop_flags() {
+        // the flags of the comparison survive a call clobbering them
+        let source = "
+                MOV R1 1
+                MOV R2 2
+                CMP R1 R2
+                PUSHF
+                CALL clobber
+                POPF
+                JMPNZ done
+                MOV R0 1
+            done:
+                HLT
+            clobber:
+                CMP R1 R1
+                RET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(0), Ok(0));
+        assert!(!vm.cpu.flags().zero && vm.cpu.flags().negative);
+        assert!(vm.stack.is_empty());
+
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 0x1d\nPUSHREG R0\nPOPF\nPUSHF\nPOPREG R1\nHLT")
+            .unwrap();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.flags(), cpu::StatusFlags::from_bits(0x1d));
+        assert_eq!(vm.cpu.get_register(1), Ok(0x1d));
+    }
+
+    #[test]
+    fn test_vm_run_carry_chain() {
+        // add then subtract 0x2_00000001 to 0x1_ffffffff, 64-bit integers held as R1:R0 and R3:R2
+        let source = "
+                MOV R0 -1
+                MOV R1 1
+                MOV R2 1
+                MOV R3 2
+                ADD R0 R0 R2
+                ADC R1 R1 R3
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(0), Ok(0));
+        assert_eq!(vm.cpu.get_register(1), Ok(4));
+        assert!(!vm.cpu.flags().carry);
+
+        let source = source.replace("HLT", "SUB R0 R0 R2\nSBB R1 R1 R3\nHLT");
+        let program = crate::asm::Assembler::new().assemble(&source).unwrap();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(0), Ok(-1));
+        assert_eq!(vm.cpu.get_register(1), Ok(1));
+        assert!(!vm.cpu.flags().carry);
+    }
+
+    #[test]
+    fn test_vm_run_add_carry_flags() {
+        // the signed overflow and the unsigned carry are independent
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 0x7fffffff\nMOV R1 1\nADD R2 R0 R1\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert!(vm.cpu.flags().overflow && !vm.cpu.flags().carry);
+
+        // -1 + 0 + carry wraps to zero without signed overflow
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 -1\nMOV R1 0\nCMP R1 R0\nADC R2 R0 R1\nHLT")
+            .unwrap();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(2), Ok(0));
+        let flags = vm.cpu.flags();
+        assert!(flags.carry && flags.zero && !flags.overflow && !flags.negative);
+
+        // MIN - 0 - borrow overflows without unsigned borrow
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 0x80000000\nMOV R1 0\nMOV R3 1\nCMP R1 R3\nSBB R2 R0 R1\nHLT")
+            .unwrap();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(2), Ok(i32::MAX));
+        let flags = vm.cpu.flags();
+        assert!(flags.overflow && !flags.carry);
+    }
+
+    #[test]
+    fn test_vm_run_floating_point() {
+        // R3 is set when 7 / 2 is below 4
+        let source = "
+                MOV R0 7
+                ITOF F0 R0
+                FMOV F1 2.0
+                FDIV F2 F0 F1
+                FMOV F3 4
+                FCMP F2 F3
+                JMPAE done
+                INC R3
+            done:
+                FMUL F2 F2 F1
+                FADD F2 F2 F1
+                FSUB F2 F2 F3
+                FTOI R1 F2
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_float_register(2), Ok(5.0));
+        assert_eq!(vm.cpu.get_register(1), Ok(5));
+        assert_eq!(vm.cpu.get_register(3), Ok(1));
+    }
+
+    #[test]
+    fn test_vm_run_floating_point_nan() {
+        let source = "
+                FMOV F0 0
+                FDIV F1 F0 F0
+                FCMP F1 F1
+                FTOI R0 F1
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert!(vm.cpu.get_float_register(1).unwrap().is_nan());
+        assert_eq!(vm.cpu.get_register(0), Ok(0));
+
+        let flags = vm.snapshot().cpu.status_flags;
+        assert!(flags.overflow);
+        assert!(!flags.zero);
+    }
+
+    #[test]
+    fn test_vm_run_high_multiplication() {
+        let source = "
+                MOV R0 -2
+                MOV R1 0x40000000
+                MULH R2 R0 R1
+                UMULH R3 R0 R1
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(2), Ok(-1));
+        assert_eq!(vm.cpu.get_register(3), Ok(0x3fff_ffff));
+    }
+
+    #[test]
+    fn test_vm_run_atomics() {
+        let source = "
+                MOV R0 0x100
+                MOV R1 5
+                XADD R1 [R0]
+                MOV R1 3
+                XADD R1 [R0]
+                MOV R2 9
+                CAS R1 R2 [R0]
+                JMPZ done
+                CAS R1 R2 [R0]
+            done:
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.memory.read::<i32>(0x100), Ok(9));
+        assert_eq!(vm.cpu.get_register(1), Ok(8));
+        assert!(vm.snapshot().cpu.status_flags.zero);
+    }
+
+    #[test]
+    fn test_vm_run_page_fault_handler() {
+        // the page fault handler maps the missing page to the frame 0x3000 and retries the access
+        let source = "
+                MOV R0 handler
+                ST R0 12
+                MOV R0 0x2003
+                ST R0 0x1000
+                MOV R0 0x1000
+                MMUON R0
+                MOV R1 42
+                ST R1 0x5010
+                LD R2 0x5010
+                MMUOFF
+                HLT
+            handler:
+                MMUOFF
+                RDFAULT R3
+                SHRI R3 R3 12
+                SHLI R3 R3 2
+                MOV R2 0x2000
+                ADD R3 R3 R2
+                MOV R2 0x3003
+                STR R2 [R3]
+                MOV R2 0x1000
+                MMUON R2
+                IRET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 0x4000);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.memory.read::<i32>(0x3010), Ok(42));
+        assert_eq!(vm.cpu.get_register(2), Ok(42));
+        assert_eq!(vm.cpu.get_register(3), Ok(0x2014));
+        assert_eq!(vm.cpu.mmu().fault_address(), 0x5010);
+        assert_eq!(vm.cpu.mmu().directory(), None);
+    }
+
+    #[test]
+    fn test_vm_run_page_fault() {
+        // the page 0 is mapped read-only to the frame 0x2000
+        let source = "
+                MOV R0 0x1003
+                ST R0 0x0
+                MOV R0 0x2001
+                ST R0 0x1000
+                MOV R0 7
+                ST R0 0x2004
+                MOV R0 0x0
+                MMUON R0
+                LD R1 0x4
+                ST R1 0x8
+                HLT
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 0x4000);
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::PageFault {
+                address: 0x8,
+                write: true
+            })
+        );
+        assert_eq!(vm.cpu.get_register(1), Ok(7));
+        assert_eq!(vm.snapshot().cpu.page_directory, Some(0x0));
+    }
+
+    #[test]
+    fn test_vm_run_memory_stack() {
+        // the function increments its argument in the stack frame of the caller
+        let source = "
+                MOV R0 5
+                PUSHREG R0
+                CALL func
+                POPREG R1
+                HLT
+            func:
+                RDSP R2
+                MOV R3 4
+                ADD R3 R2 R3
+                LDR R0 [R3]
+                INC R0
+                STR R0 [R3]
+                RET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = builder::VmBuilder::new()
+            .stack_capacity(16)
+            .memory_size(1024)
+            .memory_stack(true)
+            .build::<i32>();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(1), Ok(6));
+        assert_eq!(vm.cpu.get_register(2), Ok(1016));
+        assert_eq!(vm.cpu.sp(), Some(1024));
+        assert_eq!(vm.counters().stack_high_water, 2);
+
+        let program = crate::asm::Assembler::new()
+            .assemble("PUSH 1\nPUSH 2\nPUSH 3\nHLT")
+            .unwrap();
+        let mut vm = builder::VmBuilder::new()
+            .stack_capacity(2)
+            .memory_size(1024)
+            .memory_stack(true)
+            .build::<i32>();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::StackOverflow)
+        );
+        assert_eq!(vm.memory.read::<i32>(1016), Ok(2));
+    }
+
+    #[test]
+    fn test_vm_run_stack_pointer_separate_stack() {
+        let program = crate::asm::Assembler::new()
+            .assemble("RDSP R0\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::InvalidInstruction)
+        );
+        assert_eq!(vm.cpu.sp(), None);
+    }
+
+    #[test]
+    fn test_vm_run_stack_frames() {
+        // the argument is above the saved frame pointer and the return address, the local below
+        let source = "
+                MOV R0 7
+                PUSHREG R0
+                CALL square_plus_one
+                POPREG R1
+                HLT
+            square_plus_one:
+                ENTER 4
+                LDF R0 8
+                MULT R0 R0 R0
+                STF R0 -4
+                LDF R2 -4
+                INC R2
+                STF R2 8
+                RDFP R3
+                LEAVE
+                RET
+        ";
+        let program = crate::asm::Assembler::new().assemble(source).unwrap();
+        let mut vm = builder::VmBuilder::new()
+            .stack_capacity(16)
+            .memory_size(1024)
+            .memory_stack(true)
+            .build::<i32>();
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.cpu.get_register(1), Ok(50));
+        assert_eq!(vm.cpu.get_register(3), Ok(1012));
+        assert_eq!(vm.cpu.sp(), Some(1024));
+        assert_eq!(vm.cpu.fp(), Some(1024));
+        assert_eq!(vm.counters().stack_high_water, 4);
+
+        // the frame does not fit in the stack, nothing is pushed
+        let program = crate::asm::Assembler::new()
+            .assemble("ENTER 8\nHLT")
+            .unwrap();
+        let mut vm = builder::VmBuilder::new()
+            .stack_capacity(2)
+            .memory_size(1024)
+            .memory_stack(true)
+            .build::<i32>();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::StackOverflow)
+        );
+        assert_eq!(vm.cpu.sp(), Some(1024));
+    }
+
+    #[test]
+    fn test_vm_run_von_neumann() {
+        // the program patches the immediate of its last MOV and reads its own opcode
+        let source = "
+                MOV R0 patch
+                MOV R2 2
+                ADD R0 R0 R2
+                MOV R1 99
+                STR R1 [R0]
+                LDBU R2 patch
+            patch:
+                MOV R3 1
+                HLT
+        ";
+        let program = crate::asm::Assembler::new()
+            .origin(0x100)
+            .assemble(source)
+            .unwrap();
+        let mut vm = builder::VmBuilder::new()
+            .memory_size(1024)
+            .strict_alignment(false)
+            .von_neumann(0x100)
+            .build::<i32>();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(8));
+        assert_eq!(vm.cpu.get_register(3), Ok(99));
+        assert_eq!(vm.cpu.get_register(2), Ok(0x01));
+        assert_eq!(vm.memory.bytes()[0x100], 0x01);
+
+        let mut vm = builder::VmBuilder::new()
+            .memory_size(16)
+            .von_neumann(0x8)
+            .build::<i32>();
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 0x8,
+                size: program.len()
+            })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_memory_layout() {
+        // the program calls a function loaded in the code region on a value of the data region
+        let layout =
+            layout::MemoryLayout::new(0x100..0x200, 0x200..0x300, 0x300..0x380, 0x380..0x400)
+                .unwrap();
+        let mut vm = builder::VmBuilder::new()
+            .memory_size(0)
+            .layout(layout)
+            .build::<i32>();
+        let function = crate::asm::Assembler::new()
+            .assemble("LDR R1 [R0]\nINC R1\nRET")
+            .unwrap();
+        vm.load_at(&function, 0x180).unwrap();
+        vm.load_at(&41i32.to_le_bytes(), vm.layout().unwrap().data.start)
+            .unwrap();
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 0x200\nCALL 0x180\nST R1 0x300\nHLT")
+            .unwrap();
+        for _ in 0..2 {
+            assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(7));
+            assert_eq!(vm.memory.read::<i32>(0x300), Ok(42));
+            assert_eq!(vm.cpu.sp(), Some(0x400));
+        }
+        assert_eq!(vm.counters().stack_high_water, 1);
+        assert_eq!(vm.memory.capacity(), 0x400);
+
+        assert_eq!(
+            vm.run(&[0x00; 0x101]).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 0x100,
+                size: 0x101
+            })
+        );
+        assert_eq!(
+            vm.load_at(&[0x00; 2], 0x3ff),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 0x3ff,
+                size: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_program_image() {
+        // the entry point skips the NOP, the program sums the data word and the zeroed BSS word
+        let code = crate::asm::Assembler::new()
+            .assemble("NOP\nLD R0 0x100\nLD R1 0x104\nADD R0 R0 R1\nHLT")
+            .unwrap();
+        let image = program::Program::new(&code)
+            .with_entry(1)
+            .with_data(0x100, &7i32.to_le_bytes(), 4)
+            .to_image();
+        let program = program::Program::from_image(&image).unwrap();
+        let mut vm = VM::<i32>::new(16, 0x200);
+        vm.load_at(&[0xff; 4], 0x104).unwrap();
+        assert_eq!(
+            vm.run_program(&program)
+                .map(|termination| termination.steps),
+            Ok(4)
+        );
+        assert_eq!(vm.cpu.get_register(0), Ok(7));
+        assert_eq!(vm.memory.read::<i32>(0x104), Ok(0));
+
+        let program = program::Program::new(&code).with_data(0x1fe, &[1, 2], 1);
+        assert_eq!(
+            vm.run_program(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 0x200,
+                size: 1
+            })
+        );
+
+        // the size of the BSS comes from the header, it is checked before being zeroed
+        let image = program::Program::new(&code)
+            .with_data(0x100, &[], u32::MAX as usize)
+            .to_image();
+        let program = program::Program::from_image(&image).unwrap();
+        assert_eq!(
+            vm.run_program(&program).map_err(|error| error.source),
+            Err(error::VmError::MemoryOutOfBounds {
+                address: 0x100,
+                size: u32::MAX as usize
+            })
+        );
+    }
+
+    #[test]
+    fn test_vm_run_program_von_neumann() {
+        let code = crate::asm::Assembler::new()
+            .origin(0x40)
+            .assemble("NOP\nMOV R0 1\nHLT")
+            .unwrap();
+        let program = program::Program::new(&code).with_entry(1);
+        let mut vm = builder::VmBuilder::new()
+            .memory_size(0x100)
+            .von_neumann(0x40)
+            .build::<i32>();
+        assert_eq!(
+            vm.run_program(&program)
+                .map(|termination| termination.steps),
+            Ok(2)
+        );
+        assert_eq!(vm.cpu.get_register(0), Ok(1));
+    }
+
+    #[test]
+    fn test_vm_state_accessors() {
+        let code = crate::asm::Assembler::new()
+            .assemble("MOV R0 7\nPUSH 3\nPUSHREG R0\nST R0 0x10\nCMP R0 R0\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 1024);
+        assert!(vm.run(&code).is_ok());
+        assert_eq!(vm.registers(), [7, 0, 0, 0]);
+        assert!(vm.flags().zero);
+        assert_eq!(vm.pc(), code.len() - 1); // at the HLT
+        assert_eq!(vm.stack_slice(), [3, 7]);
+        assert_eq!(vm.memory().peek::<i32>(0x10), Ok(7));
+        assert_eq!(vm.memory().bytes()[0x10], 7);
+    }
+
+    #[test]
+    fn test_vm_render_state() {
+        let code = crate::asm::Assembler::new()
+            .assemble("MOV R0 10\nloop: PUSHREG R0\nDEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        let mut vm = builder::VmBuilder::new().registers(5).build::<i32>();
+        vm.load_program(&program::Program::new(&code)).unwrap();
+        assert!(vm.render_state().contains("pc    0x00000000 MOV R0 10\n"));
+
+        while !vm.step().unwrap() {}
+        let state = vm.render_state();
+        assert_eq!(state.lines().count(), 5);
+        assert!(state.starts_with("R0  0x00000000 0  R1  0x00000000 0  R2"));
+        assert!(state.contains("\nR4  0x00000000 0\nflags Z=1 C=0 O=0 N=0 I=0\n"));
+        assert!(state.contains("\npc    0x0000000f HLT\n"));
+        assert!(state.ends_with("stack [..., 8, 7, 6, 5, 4, 3, 2, 1] (depth 10)\n"));
+        assert_eq!(vm.to_string(), state);
+
+        vm.cpu.set_pc(0x1000);
+        assert!(vm.render_state().contains("\npc    0x00001000 ?\n"));
+    }
+
+    /// A `tracing` subscriber recording the fields of the events, one line per event.
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct Recorder(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut line = format!("span {}", span.metadata().name());
+            span.record(
+                &mut |field: &tracing::field::Field, value: &dyn std::fmt::Debug| {
+                    line += &format!(" {}={:?}", field, value);
+                },
+            );
+            self.0.lock().unwrap().push(line);
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut line = event.metadata().level().to_string();
+            event.record(
+                &mut |field: &tracing::field::Field, value: &dyn std::fmt::Debug| {
+                    line += &format!(" {}={:?}", field, value);
+                },
+            );
+            self.0.lock().unwrap().push(line);
+        }
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_vm_tracing() {
+        let recorder = Recorder::default();
+        // `DEC` and `JMPNZ` are fused, but traced as two steps
+        let program = crate::asm::Assembler::new()
+            .assemble("MOV R0 2\nloop: DEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        let termination = tracing::subscriber::with_default(recorder.clone(), || {
+            vm.run_with_limit(&program, 100)
+        })
+        .unwrap();
+        let lines = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            lines[0],
+            format!("span run size={} max_steps=100", program.len())
+        );
+        assert_eq!(
+            lines[1],
+            "TRACE message=step step=1 pc=0 opcode=MOV instruction=MOV R0 2"
+        );
+        assert_eq!(lines.len(), 2 + termination.steps as usize);
+        assert!(lines[2..lines.len() - 1]
+            .iter()
+            .all(|line| line.starts_with("TRACE message=step")));
+        assert_eq!(
+            lines.last().unwrap(),
+            &format!(
+                "INFO message=terminated steps={} exit_code=0",
+                termination.steps
+            )
+        );
+
+        recorder.0.lock().unwrap().clear();
+        let program = crate::asm::Assembler::new()
+            .assemble("POPREG R0\nHLT")
+            .unwrap();
+        tracing::subscriber::with_default(recorder.clone(), || vm.run(&program)).unwrap_err();
+        assert_eq!(
+            recorder.0.lock().unwrap().last().unwrap(),
+            &format!(
+                "WARN message=fault pc=0 step=1 error={}",
+                error::VmError::StackUnderflow
+            )
+        );
     }
 }
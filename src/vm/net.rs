@@ -0,0 +1,274 @@
+//! The UDP networking syscalls of the VM, see `Network`.
+
+use std::cell::RefCell;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::rc::Rc;
+
+use super::cpu::CpuView;
+use super::error::{Result, VmError};
+use super::memory::Memory;
+use super::syscall::SyscallTable;
+use super::word::Word;
+
+/// The number of bytes of an endpoint in the memory of the guest.
+pub const ENDPOINT_SIZE: usize = 6;
+
+/// A bridge between the guest and the UDP sockets of the host, exposed as syscalls on socket
+/// descriptors.
+///
+/// An endpoint is stored in the memory of the guest as `ENDPOINT_SIZE` bytes: the 4 bytes of an
+/// IPv4 address, followed by the port in network byte order (big-endian).
+/// The syscalls return `-1` in `R0` when the host socket fails, and stop the execution with an
+/// error on an invalid descriptor:
+/// - `SYSCALL Network::OPEN`: Bind a socket to the port in `R0`, `0` for any port, and return
+///   its descriptor.
+/// - `SYSCALL Network::SEND`: Send the buffer at `R1` of `R2` bytes through the socket `R0` to
+///   the endpoint at `R3`, and return the number of bytes sent.
+/// - `SYSCALL Network::RECV`: Receive a pending datagram through the socket `R0` into the buffer
+///   at `R1` of `R2` bytes, store its source at `R3` and return its size, truncated to the
+///   buffer, or `-1` without pending datagram. The syscall never blocks.
+/// - `SYSCALL Network::CLOSE`: Close the socket `R0`.
+pub struct Network {
+    address: IpAddr,
+}
+
+/// The sockets opened by the guest, indexed by descriptor.
+type Sockets = Rc<RefCell<Vec<Option<UdpSocket>>>>;
+
+impl Network {
+    /// The syscall number binding a socket.
+    pub const OPEN: u8 = 0xf4;
+    /// The syscall number sending a datagram.
+    pub const SEND: u8 = 0xf5;
+    /// The syscall number receiving a datagram.
+    pub const RECV: u8 = 0xf6;
+    /// The syscall number closing a socket.
+    pub const CLOSE: u8 = 0xf7;
+
+    /// Create a bridge binding the sockets of the guest to an address of the host.
+    pub fn new(address: IpAddr) -> Self {
+        Self { address }
+    }
+
+    /// Create a bridge binding the sockets of the guest to the loopback interface,
+    /// only reachable from the host.
+    pub fn localhost() -> Self {
+        Self::new(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+
+    /// Register the syscalls in a syscall table, replacing the functions registered under
+    /// the same numbers.
+    pub fn register<T: Word>(self, syscalls: &mut SyscallTable<T>) {
+        let sockets = Sockets::default();
+        let open = Rc::clone(&sockets);
+        syscalls.register(
+            Self::OPEN,
+            Box::new(move |cpu, _memory| {
+                let port = u16::try_from(cpu.register(0)?.to_usize()).unwrap_or(0);
+                let result = UdpSocket::bind(SocketAddr::new(self.address, port))
+                    .and_then(|socket| socket.set_nonblocking(true).map(|_| socket));
+                let descriptor = result.ok().map(|socket| {
+                    let mut sockets = open.borrow_mut();
+                    sockets.push(Some(socket));
+                    sockets.len() - 1
+                });
+                cpu.set_register(0, descriptor.map_or_else(failure, T::from_usize))
+            }),
+        );
+        let send = Rc::clone(&sockets);
+        syscalls.register(
+            Self::SEND,
+            Box::new(move |cpu, memory| {
+                let bytes = read_bytes(cpu, memory)?;
+                let target = read_endpoint(memory, cpu.register(3)?.to_usize())?;
+                let sent = with_socket(&send, cpu, |socket| socket.send_to(&bytes, target).ok())?;
+                cpu.set_register(0, sent.map_or_else(failure, T::from_usize))
+            }),
+        );
+        let recv = Rc::clone(&sockets);
+        syscalls.register(
+            Self::RECV,
+            Box::new(move |cpu, memory| {
+                let (address, size) = buffer(cpu, memory)?;
+                let endpoint = cpu.register(3)?.to_usize();
+                check_range(memory, endpoint, ENDPOINT_SIZE)?;
+                let mut bytes = vec![0; size];
+                let received = with_socket(&recv, cpu, |socket| socket.recv_from(&mut bytes))?;
+                // no pending datagram, a failure of the socket or an IPv6 source
+                let Ok((received, SocketAddr::V4(source))) = received else {
+                    return cpu.set_register(0, failure());
+                };
+                let received = received.min(size);
+                for (offset, byte) in bytes[..received].iter().enumerate() {
+                    memory.write::<u8>(address + offset, *byte)?;
+                }
+                write_endpoint(memory, endpoint, source)?;
+                cpu.set_register(0, T::from_usize(received))
+            }),
+        );
+        syscalls.register(
+            Self::CLOSE,
+            Box::new(move |cpu, _memory| {
+                let descriptor = cpu.register(0)?.to_usize();
+                match sockets.borrow_mut().get_mut(descriptor) {
+                    Some(socket @ Some(_)) => {
+                        *socket = None;
+                        cpu.set_register(0, T::zero())
+                    }
+                    _ => Err(VmError::Other(format!(
+                        "Invalid socket descriptor: {}",
+                        descriptor
+                    ))),
+                }
+            }),
+        );
+    }
+}
+
+/// Get the value returned to the guest when the host socket fails: `-1`.
+fn failure<T: Word>() -> T {
+    T::zero().overflowing_sub(T::one()).0
+}
+
+/// Call a function with the socket of the descriptor in `R0`.
+fn with_socket<T: Word, R>(
+    sockets: &Sockets,
+    cpu: &CpuView<T>,
+    function: impl FnOnce(&UdpSocket) -> R,
+) -> Result<R> {
+    let descriptor = cpu.register(0)?.to_usize();
+    sockets
+        .borrow()
+        .get(descriptor)
+        .and_then(Option::as_ref)
+        .map(function)
+        .ok_or_else(|| VmError::Other(format!("Invalid socket descriptor: {}", descriptor)))
+}
+
+/// Check a range of bytes is in the memory.
+fn check_range(memory: &Memory, address: usize, size: usize) -> Result<()> {
+    if address.saturating_add(size) > memory.capacity() {
+        return Err(VmError::MemoryOutOfBounds { address, size });
+    }
+    Ok(())
+}
+
+/// Get the buffer of a syscall, from the address in `R1` and the length in `R2`.
+fn buffer<T: Word>(cpu: &CpuView<T>, memory: &Memory) -> Result<(usize, usize)> {
+    let address = cpu.register(1)?.to_usize();
+    let size = cpu.register(2)?.to_usize();
+    check_range(memory, address, size)?;
+    Ok((address, size))
+}
+
+/// Read the bytes of the buffer of a syscall.
+fn read_bytes<T: Word>(cpu: &CpuView<T>, memory: &mut Memory) -> Result<Vec<u8>> {
+    let (address, size) = buffer(cpu, memory)?;
+    (address..address + size)
+        .map(|address| memory.read::<u8>(address))
+        .collect()
+}
+
+/// Read an endpoint from the memory of the guest.
+fn read_endpoint(memory: &mut Memory, address: usize) -> Result<SocketAddrV4> {
+    check_range(memory, address, ENDPOINT_SIZE)?;
+    let mut bytes = [0u8; ENDPOINT_SIZE];
+    for (offset, byte) in bytes.iter_mut().enumerate() {
+        *byte = memory.read::<u8>(address + offset)?;
+    }
+    Ok(SocketAddrV4::new(
+        Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]),
+        u16::from_be_bytes([bytes[4], bytes[5]]),
+    ))
+}
+
+/// Write an endpoint to the memory of the guest.
+fn write_endpoint(memory: &mut Memory, address: usize, endpoint: SocketAddrV4) -> Result<()> {
+    let ip = endpoint.ip().octets();
+    let port = endpoint.port().to_be_bytes();
+    for (offset, byte) in ip.iter().chain(&port).enumerate() {
+        memory.write::<u8>(address + offset, *byte)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_network_echo() {
+        // the host echoes the first datagram it receives back to its source
+        let host = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = host.local_addr().unwrap().port();
+        let echo = thread::spawn(move || {
+            let mut bytes = [0u8; 16];
+            let (size, source) = host.recv_from(&mut bytes).unwrap();
+            host.send_to(&bytes[..size], source).unwrap();
+        });
+
+        // send "ping" to the host, then poll the socket until the echo arrives
+        let source = format!(
+            "
+                MOV R0 0
+                SYSCALL 0xf4
+                MOV R1 message
+                MOV R2 4
+                MOV R3 host
+                SYSCALL 0xf5
+                MOV R0 0
+                MOV R1 reply
+                MOV R2 16
+                MOV R3 sender
+            poll:
+                SYSCALL 0xf6
+                INC R0
+                JMPZ retry
+                DEC R0
+                HLT
+            retry:
+                MOV R0 0
+                JMP poll
+            .data
+            host: .byte 127 0 0 1 {} {}
+            message: .ascii \"ping\"
+            sender: .byte 0 0 0 0 0 0
+            reply: .byte 0 0 0 0
+            ",
+            port >> 8,
+            port & 0xff
+        );
+        let program = Assembler::new().assemble_program(&source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_network(Network::localhost());
+        vm.run_program(&program).unwrap();
+        echo.join().unwrap();
+
+        assert_eq!(vm.snapshot().cpu.registers[0], 4);
+        let data = program.data_address();
+        let memory = vm.snapshot().memory;
+        // the reply follows the 6 bytes of the host, the 4 of the message and the 6 of the sender
+        assert_eq!(&memory[data + 16..data + 20], b"ping");
+        assert_eq!(
+            &memory[data + 10..data + 16],
+            &[127, 0, 0, 1, (port >> 8) as u8, port as u8]
+        );
+    }
+
+    #[test]
+    fn test_network_invalid_descriptor() {
+        let program = Assembler::new()
+            .assemble("MOV R0 3\nSYSCALL 0xf7\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_network(Network::localhost());
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::Other("Invalid socket descriptor: 3".to_string()))
+        );
+    }
+}
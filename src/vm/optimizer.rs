@@ -0,0 +1,271 @@
+//! The peephole optimization of the bytecode of a program, see `Optimizer`.
+
+use super::decoder::Decoder;
+use super::encoder::Encoder;
+use super::error::{Result, VmError};
+use super::instructions::Instruction;
+use super::program::Program;
+use super::word::Word;
+
+/// An optimizer rewriting short sequences of instructions into fewer instructions:
+/// - `PUSHREG R` followed by `POPREG R` is removed,
+/// - `MOV R` followed by another `MOV R` keeps only the second `MOV`,
+/// - `JMP` to the next instruction is removed.
+///
+/// The rules are applied until none matches, the program is then encoded again with the targets
+/// of its jumps, calls and spawns and its entry point moved to the new offsets. A sequence is
+/// kept when an instruction after its first one is the target of a jump, so that the program
+/// behaves the same from every entry.
+///
+/// The code addresses used as values, e.g. the address of an interrupt handler loaded with
+/// `MOV`, are not updated: such programs must not be optimized.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::optimizer::Optimizer;
+/// use forge_vm::vm::program::Program;
+/// // MOV R0 1, MOV R0 2, HLT
+/// let program = Program::new(&[0x01, 0x00, 1, 0, 0, 0, 0x01, 0x00, 2, 0, 0, 0, 0xff]);
+/// let optimized = Optimizer::<i32>::new().optimize(&program).unwrap();
+/// assert_eq!(optimized.code(), [0x01, 0x00, 2, 0, 0, 0, 0xff]);
+/// ```
+pub struct Optimizer<T> {
+    decoder: Decoder<T>,
+}
+
+impl<T: Word> Optimizer<T> {
+    pub fn new() -> Self {
+        Self {
+            decoder: Decoder::new(),
+        }
+    }
+
+    /// Create an optimizer for a CPU with a specific number of registers.
+    pub fn with_registers(registers_count: u8) -> Self {
+        Self {
+            decoder: Decoder::with_registers(registers_count),
+        }
+    }
+
+    /// Optimize the code of a program, the data segments are kept.
+    ///
+    /// # Errors
+    /// Returns the error of the decoding if the code is not a sequence of valid instructions, or
+    /// `VmError::Other` if a target or the entry point is not the start of an instruction.
+    pub fn optimize(&self, program: &Program) -> Result<Program<'static>> {
+        let mut instructions = Vec::new();
+        let mut offset = 0;
+        while offset < program.size() {
+            let instruction = self.decoder.decode_next_instruction(program, offset)?;
+            instructions.push((offset, instruction));
+            offset += instruction.size();
+        }
+        let index_of = |target: usize| {
+            instructions
+                .binary_search_by_key(&target, |(offset, _)| *offset)
+                .or_else(
+                    |index| match index == instructions.len() && target == offset {
+                        true => Ok(index),
+                        false => Err(VmError::Other(format!(
+                            "Target 0x{:x} is not the start of an instruction",
+                            target
+                        ))),
+                    },
+                )
+        };
+        // the targets as indexes of instructions, the end of the code being the last index
+        let targets = instructions
+            .iter()
+            .map(|(_, instruction)| match instruction.jump_target() {
+                Some(target) => index_of(target.to_usize()).map(Some),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let entry = index_of(program.entry())?;
+
+        let mut kept = vec![true; instructions.len()];
+        while self.rewrite(&instructions, &targets, entry, &mut kept) {}
+
+        // the new offset of each instruction, a removed instruction moving to the next one kept
+        let mut offsets = vec![0; instructions.len() + 1];
+        let mut size = 0;
+        for (index, (_, instruction)) in instructions.iter().enumerate() {
+            offsets[index] = size;
+            if kept[index] {
+                size += instruction.size();
+            }
+        }
+        offsets[instructions.len()] = size;
+
+        let encoder = Encoder::new();
+        let mut code = Vec::with_capacity(size);
+        for (index, (_, mut instruction)) in instructions.into_iter().enumerate() {
+            if !kept[index] {
+                continue;
+            }
+            if let (Some(address), Some(target)) = (instruction.jump_target_mut(), targets[index]) {
+                *address = T::Address::from_usize(offsets[target]);
+            }
+            encoder.encode_instruction(&instruction, &mut code);
+        }
+        log::debug!(
+            "Optimized a program of {} bytes to {} bytes",
+            program.size(),
+            code.len()
+        );
+        Ok(Program::new(&code)
+            .with_entry(offsets[entry])
+            .with_data(program.data_address(), program.data(), program.bss_size())
+            .into_owned())
+    }
+
+    /// Apply the rules once to the kept instructions.
+    ///
+    /// # Returns
+    /// `true` if an instruction was removed.
+    fn rewrite(
+        &self,
+        instructions: &[(usize, Instruction<T, T::Address>)],
+        targets: &[Option<usize>],
+        entry: usize,
+        kept: &mut [bool],
+    ) -> bool {
+        let next_kept = |kept: &[bool], index: usize| {
+            (index..instructions.len())
+                .find(|index| kept[*index])
+                .unwrap_or(instructions.len())
+        };
+        // the instructions entered other than from the previous one
+        let mut entered = vec![false; instructions.len() + 1];
+        entered[next_kept(kept, entry)] = true;
+        for (index, target) in targets.iter().enumerate() {
+            if let (true, Some(target)) = (kept[index], target) {
+                entered[next_kept(kept, *target)] = true;
+            }
+        }
+
+        let mut changed = false;
+        let mut index = next_kept(kept, 0);
+        while index < instructions.len() {
+            let next = next_kept(kept, index + 1);
+            let instruction = instructions[index].1;
+            if let (Instruction::JMP { .. }, Some(target)) = (instruction, targets[index]) {
+                if next_kept(kept, target) == next {
+                    kept[index] = false;
+                    changed = true;
+                    index = next;
+                    continue;
+                }
+            }
+            let Some((_, following)) = instructions.get(next).filter(|_| !entered[next]) else {
+                index = next;
+                continue;
+            };
+            match (instruction, *following) {
+                (Instruction::PUSHREG { reg }, Instruction::POPREG { reg: popped })
+                    if reg == popped =>
+                {
+                    kept[index] = false;
+                    kept[next] = false;
+                    changed = true;
+                    index = next_kept(kept, next);
+                }
+                (
+                    Instruction::MOV { dest, .. },
+                    Instruction::MOV {
+                        dest: overwritten, ..
+                    },
+                ) if dest == overwritten => {
+                    kept[index] = false;
+                    changed = true;
+                    index = next;
+                }
+                _ => index = next,
+            }
+        }
+        changed
+    }
+}
+
+impl<T: Word> Default for Optimizer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    fn optimize(source: &str) -> Program<'static> {
+        let program = Assembler::new().assemble_program(source).unwrap();
+        Optimizer::<i32>::new().optimize(&program).unwrap()
+    }
+
+    fn assemble(source: &str) -> Vec<u8> {
+        Assembler::new().assemble(source).unwrap()
+    }
+
+    #[test]
+    fn test_optimize_rules() {
+        let source = "MOV R0 1\nMOV R0 2\nPUSHREG R1\nPOPREG R1\nJMP next\nnext: INC R0\nHLT";
+        let optimized = optimize(source);
+        assert_eq!(optimized.code(), assemble("MOV R0 2\nINC R0\nHLT"));
+
+        let mut vm = VM::<i32>::new(16, 64);
+        assert_eq!(
+            vm.run_program(&optimized)
+                .map(|termination| termination.steps),
+            Ok(3)
+        );
+        let expected = vm.snapshot().cpu.registers;
+        assert_eq!(
+            vm.run(&assemble(source))
+                .map(|termination| termination.steps),
+            Ok(7)
+        );
+        assert_eq!(vm.snapshot().cpu.registers, expected);
+    }
+
+    #[test]
+    fn test_optimize_jump_fixup() {
+        // the loop moves back by the removed MOV
+        let optimized = optimize("MOV R0 7\nMOV R0 3\nloop: DEC R0\nJMPNZ loop\nHLT");
+        assert_eq!(
+            optimized.code(),
+            assemble("MOV R0 3\nloop: DEC R0\nJMPNZ loop\nHLT")
+        );
+
+        // the removal of the jump makes the PUSHREG and the POPREG adjacent
+        assert_eq!(
+            optimize("PUSHREG R0\nJMP x\nx: POPREG R0\nHLT").code(),
+            assemble("HLT")
+        );
+
+        // the second MOV is the target of a jump
+        let source = "MOV R0 1\nagain: MOV R0 2\nDEC R1\nJMPNZ again\nHLT";
+        assert_eq!(optimize(source).code(), assemble(source));
+    }
+
+    #[test]
+    fn test_optimize_entry_and_errors() {
+        let code = assemble("MOV R0 1\nMOV R0 2\nNOP\nHLT");
+        let program = Program::new(&code).with_entry(12);
+        let optimized = Optimizer::<i32>::new().optimize(&program).unwrap();
+        assert_eq!(optimized.entry(), 6);
+        // the second MOV is the entry point
+        let program = program.with_entry(6);
+        let optimized = Optimizer::<i32>::new().optimize(&program).unwrap();
+        assert_eq!(optimized.code(), program.code());
+
+        let program = Program::new(&[0x0e]);
+        assert_eq!(
+            Optimizer::<i32>::new().optimize(&program),
+            Err(VmError::InvalidInstruction)
+        );
+        let program = Program::new(&[0x12, 0x01, 0x00, 0x00, 0x00]); // JMP 0x1
+        assert!(Optimizer::<i32>::new().optimize(&program).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! The port-mapped I/O of the VM, see `PortBus`.
+
+use std::collections::HashMap;
+
+use super::error::{Result, VmError};
+use super::word::Word;
+
+/// A peripheral connected to an I/O port, read by `IN` and written by `OUT`.
+///
+/// The ports are a lighter alternative to the memory-mapped devices for simple peripherals:
+/// each access transfers a whole word of the guest, without address decoding.
+pub trait PortHandler<T: Word> {
+    /// Read a value from the port, `0` by default for the write-only ports.
+    ///
+    /// # Errors
+    /// Returns an error to abort the access, the error stops the execution.
+    fn read(&mut self) -> Result<T> {
+        Ok(T::zero())
+    }
+
+    /// Write a value to the port, ignored by default for the read-only ports.
+    ///
+    /// # Errors
+    /// Returns an error to abort the access, the error stops the execution.
+    fn write(&mut self, _value: T) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The registry of the port handlers of a VM, indexed by their 16-bit port.
+pub struct PortBus<T> {
+    handlers: HashMap<u16, Box<dyn PortHandler<T>>>,
+}
+
+impl<T: Word> PortBus<T> {
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Register a handler on a port, replacing any previous one.
+    pub fn register(&mut self, port: u16, handler: Box<dyn PortHandler<T>>) {
+        self.handlers.insert(port, handler);
+    }
+
+    /// Remove the handler registered on a port.
+    ///
+    /// # Returns
+    /// `true` if a handler was registered on the port.
+    pub fn unregister(&mut self, port: u16) -> bool {
+        self.handlers.remove(&port).is_some()
+    }
+
+    /// Read a value from a port, for `IN`.
+    ///
+    /// # Errors
+    /// Returns `VmError::UnmappedPort` if no handler is registered on the port,
+    /// or the error returned by the handler.
+    pub fn read(&mut self, port: u16) -> Result<T> {
+        self.handler(port)?.read()
+    }
+
+    /// Write a value to a port, for `OUT`.
+    ///
+    /// # Errors
+    /// Returns `VmError::UnmappedPort` if no handler is registered on the port,
+    /// or the error returned by the handler.
+    pub fn write(&mut self, port: u16, value: T) -> Result<()> {
+        self.handler(port)?.write(value)
+    }
+
+    fn handler(&mut self, port: u16) -> Result<&mut Box<dyn PortHandler<T>>> {
+        self.handlers
+            .get_mut(&port)
+            .ok_or(VmError::UnmappedPort { port })
+    }
+}
+
+impl<T: Word> Default for PortBus<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A port returning the sum of the values written to it.
+    struct Accumulator(i32);
+
+    impl PortHandler<i32> for Accumulator {
+        fn read(&mut self) -> Result<i32> {
+            Ok(self.0)
+        }
+
+        fn write(&mut self, value: i32) -> Result<()> {
+            self.0 += value;
+            Ok(())
+        }
+    }
+
+    /// A write-only port.
+    struct Sink;
+
+    impl PortHandler<i32> for Sink {}
+
+    #[test]
+    fn test_port_bus() {
+        let mut bus = PortBus::<i32>::new();
+        bus.register(0x10, Box::new(Accumulator(1)));
+        bus.register(0xffff, Box::new(Sink));
+        assert_eq!(bus.write(0x10, 2), Ok(()));
+        assert_eq!(bus.read(0x10), Ok(3));
+        assert_eq!(bus.write(0xffff, 7), Ok(()));
+        assert_eq!(bus.read(0xffff), Ok(0));
+        assert_eq!(bus.read(0x11), Err(VmError::UnmappedPort { port: 0x11 }));
+
+        assert!(bus.unregister(0x10));
+        assert!(!bus.unregister(0x10));
+        assert_eq!(
+            bus.write(0x10, 1),
+            Err(VmError::UnmappedPort { port: 0x10 })
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! The execution profiler of the VM, see `VM::set_profiling`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use super::instructions::OpCode;
+
+/// The executions of an opcode or of an instruction address.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// The number of executions.
+    pub count: u64,
+    /// The cumulative host time of the executions.
+    pub time: Duration,
+}
+
+impl ProfileEntry {
+    fn add(&mut self, time: Duration) {
+        self.count += 1;
+        self.time += time;
+    }
+}
+
+/// The profile of a run: the executions and the host time per opcode and per address.
+///
+/// The time of an instruction is measured around its execution, it excludes the decoding,
+/// the hook and the tracer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    opcodes: BTreeMap<OpCode, ProfileEntry>,
+    pcs: BTreeMap<usize, ProfileEntry>,
+}
+
+impl Profile {
+    /// Record an execution of the instruction with `opcode` at the address `pc`.
+    pub(crate) fn record(&mut self, opcode: OpCode, pc: usize, time: Duration) {
+        self.opcodes.entry(opcode).or_default().add(time);
+        self.pcs.entry(pc).or_default().add(time);
+    }
+
+    /// Remove all the recorded executions.
+    pub fn clear(&mut self) {
+        self.opcodes.clear();
+        self.pcs.clear();
+    }
+
+    /// Get the executions of an opcode, zero if it was not executed.
+    pub fn opcode(&self, opcode: OpCode) -> ProfileEntry {
+        self.opcodes.get(&opcode).copied().unwrap_or_default()
+    }
+
+    /// Get the executions of the instruction at an address, zero if it was not executed.
+    pub fn pc(&self, pc: usize) -> ProfileEntry {
+        self.pcs.get(&pc).copied().unwrap_or_default()
+    }
+
+    /// Get the executed opcodes, in the order of their value.
+    pub fn opcodes(&self) -> impl Iterator<Item = (OpCode, ProfileEntry)> + '_ {
+        self.opcodes.iter().map(|(opcode, entry)| (*opcode, *entry))
+    }
+
+    /// Get the addresses of the executed instructions, in increasing order.
+    pub fn pcs(&self) -> impl Iterator<Item = (usize, ProfileEntry)> + '_ {
+        self.pcs.iter().map(|(pc, entry)| (*pc, *entry))
+    }
+
+    /// Get the `count` addresses where the most time was spent, from the hottest.
+    /// The addresses with the same time are ordered by decreasing number of executions.
+    pub fn hottest_pcs(&self, count: usize) -> Vec<(usize, ProfileEntry)> {
+        let mut pcs: Vec<_> = self.pcs().collect();
+        pcs.sort_by(|(a_pc, a), (b_pc, b)| (b.time, b.count, a_pc).cmp(&(a.time, a.count, b_pc)));
+        pcs.truncate(count);
+        pcs
+    }
+
+    /// Get the total of the executions.
+    pub fn total(&self) -> ProfileEntry {
+        self.opcodes
+            .values()
+            .fold(ProfileEntry::default(), |total, entry| ProfileEntry {
+                count: total.count + entry.count,
+                time: total.time + entry.time,
+            })
+    }
+}
+
+/// Format the report of the profile: the opcodes by decreasing time, then the 10 hottest
+/// addresses, with the number of executions, the time and its share of the total time.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        let share = |time: Duration| match total.time.is_zero() {
+            true => 0.0,
+            false => time.as_secs_f64() * 100.0 / total.time.as_secs_f64(),
+        };
+        let mut opcodes: Vec<_> = self.opcodes().collect();
+        opcodes.sort_by(|(a_opcode, a), (b_opcode, b)| {
+            (b.time, b.count, a_opcode).cmp(&(a.time, a.count, b_opcode))
+        });
+        writeln!(
+            f,
+            "{:<10} {:>12} {:>14} {:>7}",
+            "opcode", "count", "time", "time %"
+        )?;
+        for (opcode, entry) in opcodes {
+            writeln!(
+                f,
+                "{:<10} {:>12} {:>14} {:>6.2}%",
+                format!("{:?}", opcode),
+                entry.count,
+                format!("{:?}", entry.time),
+                share(entry.time)
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<10} {:>12} {:>14}",
+            "total",
+            total.count,
+            format!("{:?}", total.time)
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<10} {:>12} {:>14} {:>7}",
+            "pc", "count", "time", "time %"
+        )?;
+        for (pc, entry) in self.hottest_pcs(10) {
+            writeln!(
+                f,
+                "0x{:08x} {:>12} {:>14} {:>6.2}%",
+                pc,
+                entry.count,
+                format!("{:?}", entry.time),
+                share(entry.time)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_profile_record() {
+        let mut profile = Profile::default();
+        profile.record(OpCode::INC, 0x0, Duration::from_nanos(10));
+        profile.record(OpCode::INC, 0x2, Duration::from_nanos(30));
+        profile.record(OpCode::INC, 0x0, Duration::from_nanos(10));
+        profile.record(OpCode::HLT, 0x4, Duration::from_nanos(5));
+        assert_eq!(
+            profile.opcode(OpCode::INC),
+            ProfileEntry {
+                count: 3,
+                time: Duration::from_nanos(50)
+            }
+        );
+        assert_eq!(profile.opcode(OpCode::DEC), ProfileEntry::default());
+        assert_eq!(profile.pc(0x0).count, 2);
+        assert_eq!(
+            profile
+                .hottest_pcs(2)
+                .iter()
+                .map(|(pc, _)| *pc)
+                .collect::<Vec<_>>(),
+            [0x2, 0x0]
+        );
+        assert_eq!(profile.total().count, 4);
+
+        let report = profile.to_string();
+        assert!(report.starts_with("opcode"));
+        assert!(report.contains("INC                   3           50ns  90.91%\n"));
+        assert!(report.contains("0x00000004            1            5ns   9.09%\n"));
+        profile.clear();
+        assert_eq!(profile.total(), ProfileEntry::default());
+    }
+
+    #[test]
+    fn test_profile_run() {
+        // DEC at 0x6, JMPNZ at 0x8
+        let program = Assembler::new()
+            .assemble("MOV R0 3\nloop: DEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        assert!(vm.profile().is_none());
+        vm.set_profiling(true);
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(8));
+        let profile = vm.profile().unwrap();
+        assert_eq!(profile.opcode(OpCode::DEC).count, 3);
+        assert_eq!(profile.opcode(OpCode::HLT).count, 1);
+        assert_eq!(profile.pc(0x8).count, 3);
+        assert_eq!(profile.total().count, 8);
+
+        // the profile is reset at each run
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(8));
+        assert_eq!(vm.profile().unwrap().total().count, 8);
+        vm.set_profiling(false);
+        assert!(vm.profile().is_none());
+    }
+}
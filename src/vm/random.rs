@@ -0,0 +1,181 @@
+//! The random number generator of the VM, see `Rng`.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use super::device::Device;
+use super::error::{Result, VmError};
+use super::syscall::SyscallTable;
+use super::word::Word;
+
+/// The source of the random numbers of a VM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandomSource {
+    /// A deterministic generator started from a seed, the runs are reproducible.
+    Seeded(u64),
+    /// A generator seeded from the entropy of the operating system.
+    Entropy,
+}
+
+/// A pseudo-random number generator, the SplitMix64 algorithm.
+///
+/// The generator is not cryptographically secure, it is meant for the simulations and games of
+/// the guests.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::random::{RandomSource, Rng};
+/// let mut first = Rng::new(RandomSource::Seeded(42));
+/// let mut second = Rng::new(RandomSource::Seeded(42));
+/// assert_eq!(first.next_u64(), second.next_u64());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// The syscall number setting `R0` to a random word.
+    pub const SYSCALL: u8 = 0xf3;
+
+    /// Create a generator from a source.
+    pub fn new(source: RandomSource) -> Self {
+        let seed = match source {
+            RandomSource::Seeded(seed) => seed,
+            // the keys of the hashers of the standard library are drawn from the OS
+            RandomSource::Entropy => RandomState::new().build_hasher().finish(),
+        };
+        Self { state: seed }
+    }
+
+    /// Get the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    /// Get a random word of the VM.
+    pub fn next_word<T: Word>(&mut self) -> T {
+        let bytes = self.next_u64().to_le_bytes();
+        T::read_le(&bytes[..std::mem::size_of::<T>()]).unwrap_or_else(T::zero)
+    }
+
+    /// Register the syscall `Rng::SYSCALL` in a syscall table, replacing the function
+    /// registered under the same number.
+    pub fn register<T: Word>(mut self, syscalls: &mut SyscallTable<T>) {
+        syscalls.register(
+            Self::SYSCALL,
+            Box::new(move |cpu, _memory| cpu.set_register(0, self.next_word())),
+        );
+    }
+}
+
+/// A random number generator device.
+/// The device has a single data register at offset `0` of its range, of any size up to 8 bytes:
+/// - A read returns a new random value.
+/// - A write reseeds the generator with the value, zero-extended.
+///
+/// Use `RandomDevice::SIZE` as the length of the mapped range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomDevice {
+    rng: Rng,
+}
+
+impl RandomDevice {
+    /// The number of bytes of the data register.
+    pub const SIZE: usize = 8;
+
+    /// Create a device generating the numbers from a source.
+    pub fn new(source: RandomSource) -> Self {
+        Self {
+            rng: Rng::new(source),
+        }
+    }
+
+    /// Check the access targets the data register.
+    fn check_register(offset: usize, size: usize) -> Result<()> {
+        if offset != 0 || size > Self::SIZE {
+            return Err(VmError::MemoryOutOfBounds {
+                address: offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Device for RandomDevice {
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        Self::check_register(offset, size)?;
+        Ok(self.rng.next_u64().to_le_bytes()[..size].to_vec())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        Self::check_register(offset, data.len())?;
+        let mut bytes = [0u8; 8];
+        bytes[..data.len()].copy_from_slice(data);
+        self.rng = Rng::new(RandomSource::Seeded(u64::from_le_bytes(bytes)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::vm::builder::VmBuilder;
+    use crate::VM;
+
+    #[test]
+    fn test_rng_seeded() {
+        let mut rng = Rng::new(RandomSource::Seeded(0));
+        // the first outputs of SplitMix64 from the seed 0
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(rng.next_word::<i32>(), 0x8009_454f_u32 as i32);
+    }
+
+    #[test]
+    fn test_random_device() {
+        let mut device = RandomDevice::new(RandomSource::Entropy);
+        device.write(0, &[7]).unwrap();
+        let mut rng = Rng::new(RandomSource::Seeded(7));
+        assert_eq!(
+            device.read(0, 4),
+            Ok(rng.next_u64().to_le_bytes()[..4].to_vec())
+        );
+        assert_eq!(
+            device.read(4, 4),
+            Err(VmError::MemoryOutOfBounds {
+                address: 4,
+                size: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_random_syscall() {
+        let program = Assembler::new()
+            .assemble("SYSCALL 0xf3\nMOVR R1 R0\nSYSCALL 0xf3\nHLT")
+            .unwrap();
+        let run = |source| {
+            let mut vm = VmBuilder::new().random(source).build::<i32>();
+            vm.run(&program).unwrap();
+            let registers = vm.snapshot().cpu.registers;
+            (registers[1], registers[0])
+        };
+        // the seeded runs are reproducible
+        assert_eq!(run(RandomSource::Seeded(42)), run(RandomSource::Seeded(42)));
+        assert_ne!(run(RandomSource::Seeded(42)), run(RandomSource::Seeded(43)));
+
+        // without a source, the syscall is not registered
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::UnknownSyscall { number: 0xf3 })
+        );
+    }
+}
@@ -0,0 +1,783 @@
+//! A reference interpreter of the core instructions and a differential harness, see `compare`.
+//!
+//! The `Reference` interpreter is deliberately simple and slow: it decodes every instruction
+//! again at each step, has no handler table nor cache, and computes the results and the flags
+//! of `VM<i32>` from their mathematical definition on 64-bit integers rather than from the
+//! `Word` operations of the CPU. A faster engine (pre-decoding, a new dispatch, a JIT) is
+//! validated by running the same programs on both and diffing their final states.
+
+use std::fmt;
+
+use super::cpu::{CpuState, StatusFlags};
+use super::decoder::Decoder;
+use super::error::VmError;
+use super::hardware_config::REGISTERS_COUNT;
+use super::instructions::Instruction;
+use super::program::Program;
+use super::snapshot::VmSnapshot;
+use super::termination::Termination;
+use super::VM;
+
+/// The size of a word of the reference interpreter, in bytes.
+const WORD_SIZE: usize = 4;
+
+/// The reference interpreter of `VM<i32>`, in the Harvard mode with the default configuration:
+/// the stack separated from the memory, little-endian and strictly aligned memory accesses, no
+/// traps, interrupts, devices nor checked arithmetic.
+///
+/// It executes the integer, memory, stack and control flow instructions of the core set, see
+/// `Reference::supports`; the execution stops on the other instructions, which are recorded in
+/// `Reference::unsupported`.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::reference::Reference;
+///
+/// let program = Assembler::new()
+///     .assemble("MOV R0 7\nMOV R1 6\nMULT R0 R0 R1\nHLT")
+///     .unwrap();
+/// let mut reference = Reference::new(1024, 1024);
+/// assert_eq!(reference.run(&program, 100).unwrap().exit_code, 42);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Reference {
+    registers: Vec<i32>,
+    flags: StatusFlags,
+    pc: usize,
+    memory: Vec<u8>,
+    stack: Vec<i32>,
+    stack_capacity: usize,
+    steps: u128,
+    unsupported: Option<Instruction<i32, u32>>,
+}
+
+/// The effect of an instruction on the control flow.
+enum Flow {
+    /// Continue with the next instruction.
+    Next,
+    /// Continue at an address.
+    Jump(usize),
+    /// Stop the program with an exit code, the PC staying at the instruction.
+    Stop(i32),
+}
+
+impl Reference {
+    /// Create a reference interpreter with the default number of registers.
+    ///
+    /// # Parameters
+    /// - `stack_capacity`: The maximum number of values on the stack.
+    /// - `memory_size`: The size of the memory, in bytes.
+    pub fn new(stack_capacity: usize, memory_size: usize) -> Self {
+        Reference {
+            registers: vec![0; REGISTERS_COUNT as usize],
+            flags: StatusFlags::default(),
+            pc: 0,
+            memory: vec![0; memory_size],
+            stack: Vec::new(),
+            stack_capacity,
+            steps: 0,
+            unsupported: None,
+        }
+    }
+
+    /// Set the number of registers.
+    pub fn with_registers(mut self, count: u8) -> Self {
+        self.registers = vec![0; count as usize];
+        self
+    }
+
+    /// Check if the reference interpreter executes an instruction.
+    pub fn supports(instruction: &Instruction<i32, u32>) -> bool {
+        use Instruction::*;
+        matches!(
+            instruction,
+            NOP | JMP { .. }
+                | JMPN { .. }
+                | JMPP { .. }
+                | JMPZ { .. }
+                | JMPNZ { .. }
+                | JMPB { .. }
+                | JMPBE { .. }
+                | JMPA { .. }
+                | JMPAE { .. }
+                | JMPLT { .. }
+                | JMPLE { .. }
+                | JMPGT { .. }
+                | JMPGE { .. }
+                | JMPC { .. }
+                | JMPNC { .. }
+                | JMPO { .. }
+                | CALL { .. }
+                | RET
+                | HLT
+                | EXIT { .. }
+                | MOV { .. }
+                | MOVR { .. }
+                | CMOVZ { .. }
+                | CMOVN { .. }
+                | LD { .. }
+                | ST { .. }
+                | PUSHREG { .. }
+                | POPREG { .. }
+                | ADD { .. }
+                | SUB { .. }
+                | ADC { .. }
+                | SBB { .. }
+                | MULT { .. }
+                | DIV { .. }
+                | MOD { .. }
+                | MULU { .. }
+                | DIVU { .. }
+                | MODU { .. }
+                | MULH { .. }
+                | UMULH { .. }
+                | INC { .. }
+                | DEC { .. }
+                | AND { .. }
+                | OR { .. }
+                | XOR { .. }
+                | NOT { .. }
+                | SHL { .. }
+                | SHR { .. }
+                | SAR { .. }
+                | ROL { .. }
+                | ROR { .. }
+                | MIN { .. }
+                | MAX { .. }
+                | MINU { .. }
+                | MAXU { .. }
+                | CMP { .. }
+                | CMPU { .. }
+        )
+    }
+
+    /// Get the unsupported instruction which stopped the last run, if any.
+    pub fn unsupported(&self) -> Option<Instruction<i32, u32>> {
+        self.unsupported
+    }
+
+    /// Run a program from its first byte, with a fresh state.
+    ///
+    /// # Returns
+    /// The termination of the program, like `VM::run_with_limit`, or the error which stopped it:
+    /// `VmError::StepLimitExceeded` after `max_steps` steps, and `VmError::Other` on an
+    /// unsupported instruction.
+    pub fn run(&mut self, program: &[u8], max_steps: u128) -> Result<Termination<i32>, VmError> {
+        self.registers.iter_mut().for_each(|register| *register = 0);
+        self.flags = StatusFlags::default();
+        self.pc = 0;
+        self.memory.iter_mut().for_each(|byte| *byte = 0);
+        self.stack.clear();
+        self.steps = 0;
+        self.unsupported = None;
+
+        let program = Program::new(program);
+        let decoder = Decoder::<i32>::with_registers(self.registers.len() as u8);
+        loop {
+            if self.steps >= max_steps {
+                self.steps += 1;
+                return Err(VmError::StepLimitExceeded);
+            }
+            self.steps += 1;
+            let instruction = decoder.decode_next_instruction(&program, self.pc)?;
+            if !Self::supports(&instruction) {
+                self.unsupported = Some(instruction);
+                return Err(VmError::Other(format!(
+                    "{} is not supported by the reference interpreter",
+                    instruction
+                )));
+            }
+            match self.execute(instruction)? {
+                Flow::Next => self.pc += instruction.size(),
+                Flow::Jump(address) => self.pc = address,
+                Flow::Stop(exit_code) => {
+                    return Ok(Termination {
+                        steps: self.steps,
+                        exit_code,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Get the state of the reference interpreter, in the format of `VM::snapshot`.
+    pub fn snapshot(&self) -> VmSnapshot<i32> {
+        VmSnapshot {
+            cpu: CpuState {
+                registers: self.registers.clone(),
+                float_registers: Vec::new(),
+                status_flags: self.flags,
+                pc: self.pc,
+                sp: 0,
+                fp: 0,
+                page_directory: None,
+            },
+            memory: self.memory.clone(),
+            stack: self.stack.clone(),
+            steps: self.steps,
+        }
+    }
+
+    /// Execute a supported instruction.
+    fn execute(&mut self, instruction: Instruction<i32, u32>) -> Result<Flow, VmError> {
+        use Instruction::*;
+        let flags = self.flags;
+        let jump = |condition: bool, address: u32| {
+            if condition {
+                Flow::Jump(address as usize)
+            } else {
+                Flow::Next
+            }
+        };
+        let flow = match instruction {
+            NOP => Flow::Next,
+            JMP { address } => Flow::Jump(address as usize),
+            JMPN { address } => jump(flags.negative, address),
+            JMPP { address } => jump(!flags.negative, address),
+            JMPZ { address } => jump(flags.zero, address),
+            JMPNZ { address } => jump(!flags.zero, address),
+            JMPB { address } | JMPC { address } => jump(flags.carry, address),
+            JMPBE { address } => jump(flags.carry || flags.zero, address),
+            JMPA { address } => jump(!flags.carry && !flags.zero, address),
+            JMPAE { address } | JMPNC { address } => jump(!flags.carry, address),
+            JMPO { address } => jump(flags.overflow, address),
+            JMPLT { address } => jump(flags.negative != flags.overflow, address),
+            JMPLE { address } => jump(flags.zero || flags.negative != flags.overflow, address),
+            JMPGT { address } => jump(!flags.zero && flags.negative == flags.overflow, address),
+            JMPGE { address } => jump(flags.negative == flags.overflow, address),
+            CALL { address } => {
+                self.push((self.pc + instruction.size()) as i32)?;
+                Flow::Jump(address as usize)
+            }
+            // the address is sign-extended like a word converted to an address by the CPU
+            RET => Flow::Jump(self.pop()? as isize as usize),
+            HLT => Flow::Stop(self.registers[0]),
+            EXIT { code } => Flow::Stop(code),
+            MOV { dest, value } => self.set(dest, value),
+            MOVR { dest, src } => self.set(dest, self.get(src)),
+            CMOVZ { dest, src } if flags.zero => self.set(dest, self.get(src)),
+            CMOVN { dest, src } if flags.negative => self.set(dest, self.get(src)),
+            CMOVZ { .. } | CMOVN { .. } => Flow::Next,
+            LD { dest, address } => {
+                let address = self.check(address as usize)?;
+                let mut bytes = [0; WORD_SIZE];
+                bytes.copy_from_slice(&self.memory[address..address + WORD_SIZE]);
+                self.set(dest, i32::from_le_bytes(bytes))
+            }
+            ST { src, address } => {
+                let address = self.check(address as usize)?;
+                let bytes = self.get(src).to_le_bytes();
+                self.memory[address..address + WORD_SIZE].copy_from_slice(&bytes);
+                Flow::Next
+            }
+            PUSHREG { reg } => {
+                self.push(self.get(reg))?;
+                Flow::Next
+            }
+            POPREG { reg } => {
+                let value = self.pop()?;
+                self.set(reg, value)
+            }
+            ADD { dest, reg1, reg2 } => self.add(dest, reg1, reg2, 0),
+            ADC { dest, reg1, reg2 } => self.add(dest, reg1, reg2, flags.carry as i64),
+            SUB { dest, reg1, reg2 } => self.sub(dest, reg1, reg2, 0),
+            SBB { dest, reg1, reg2 } => self.sub(dest, reg1, reg2, flags.carry as i64),
+            MULT { dest, reg1, reg2 } => {
+                let product = self.wide(reg1) * self.wide(reg2);
+                self.signed(dest, product)
+            }
+            DIV { dest, reg1, reg2 } => {
+                let divisor = self.nonzero(reg2)?;
+                // `i32::MIN / -1` is the only quotient out of the word
+                let quotient = self.wide(reg1) / divisor;
+                self.signed(dest, quotient)
+            }
+            MOD { dest, reg1, reg2 } => {
+                let divisor = self.nonzero(reg2)?;
+                let remainder = self.wide(reg1) % divisor;
+                self.logical(dest, remainder as i32)
+            }
+            MULU { dest, reg1, reg2 } => {
+                let product = self.unsigned(reg1) * self.unsigned(reg2);
+                self.flags.overflow = product > u64::from(u32::MAX);
+                self.logical(dest, product as i32)
+            }
+            DIVU { dest, reg1, reg2 } => {
+                self.nonzero(reg2)?;
+                let quotient = self.unsigned(reg1) / self.unsigned(reg2);
+                self.logical(dest, quotient as i32)
+            }
+            MODU { dest, reg1, reg2 } => {
+                self.nonzero(reg2)?;
+                let remainder = self.unsigned(reg1) % self.unsigned(reg2);
+                self.logical(dest, remainder as i32)
+            }
+            MULH { dest, reg1, reg2 } => {
+                let product = self.wide(reg1) * self.wide(reg2);
+                self.logical(dest, (product >> 32) as i32)
+            }
+            UMULH { dest, reg1, reg2 } => {
+                let product = self.unsigned(reg1) * self.unsigned(reg2);
+                self.logical(dest, (product >> 32) as i32)
+            }
+            INC { reg } => {
+                let sum = self.wide(reg) + 1;
+                self.signed(reg, sum)
+            }
+            DEC { reg } => {
+                let difference = self.wide(reg) - 1;
+                self.signed(reg, difference)
+            }
+            AND { dest, reg1, reg2 } => self.logical(dest, self.get(reg1) & self.get(reg2)),
+            OR { dest, reg1, reg2 } => self.logical(dest, self.get(reg1) | self.get(reg2)),
+            XOR { dest, reg1, reg2 } => self.logical(dest, self.get(reg1) ^ self.get(reg2)),
+            NOT { dest, reg } => self.logical(dest, !self.get(reg)),
+            SHL { dest, reg1, reg2 } => {
+                let bits = self.unsigned(reg1);
+                let (result, carry) = match self.amount(reg2) {
+                    0 => (bits, false),
+                    amount @ 1..=32 => {
+                        let wide = bits << amount;
+                        (wide & 0xffff_ffff, wide >> 32 & 1 == 1)
+                    }
+                    _ => (0, false),
+                };
+                self.shifted(dest, result as i32, carry)
+            }
+            SHR { dest, reg1, reg2 } => {
+                let bits = self.unsigned(reg1);
+                let (result, carry) = match self.amount(reg2) {
+                    0 => (bits, false),
+                    amount @ 1..=32 => {
+                        // the bits shifted out are kept below the word
+                        let wide = (bits << 32) >> amount;
+                        (wide >> 32, wide >> 31 & 1 == 1)
+                    }
+                    _ => (0, false),
+                };
+                self.shifted(dest, result as i32, carry)
+            }
+            SAR { dest, reg1, reg2 } => {
+                let value = self.wide(reg1);
+                let (result, carry) = match self.amount(reg2) {
+                    0 => (value, false),
+                    amount @ 1..=31 => {
+                        let wide = (value << 32) >> amount;
+                        (wide >> 32, wide >> 31 & 1 == 1)
+                    }
+                    _ => (if value < 0 { -1 } else { 0 }, value < 0),
+                };
+                self.shifted(dest, result as i32, carry)
+            }
+            ROL { dest, reg1, reg2 } => {
+                let bits = self.unsigned(reg1);
+                let amount = self.amount(reg2);
+                let rotation = amount % 32;
+                let result = ((bits << rotation) | (bits >> (32 - rotation))) & 0xffff_ffff;
+                self.shifted(dest, result as i32, amount != 0 && result & 1 == 1)
+            }
+            ROR { dest, reg1, reg2 } => {
+                let bits = self.unsigned(reg1);
+                let amount = self.amount(reg2);
+                let rotation = amount % 32;
+                let result = ((bits >> rotation) | (bits << (32 - rotation))) & 0xffff_ffff;
+                self.shifted(dest, result as i32, amount != 0 && result >> 31 == 1)
+            }
+            MIN { dest, reg1, reg2 } => self.set(dest, self.get(reg1).min(self.get(reg2))),
+            MAX { dest, reg1, reg2 } => self.set(dest, self.get(reg1).max(self.get(reg2))),
+            MINU { dest, reg1, reg2 } => {
+                self.set(dest, self.unsigned(reg1).min(self.unsigned(reg2)) as i32)
+            }
+            MAXU { dest, reg1, reg2 } => {
+                self.set(dest, self.unsigned(reg1).max(self.unsigned(reg2)) as i32)
+            }
+            CMP { reg1, reg2 } => {
+                let difference = self.wide(reg1) - self.wide(reg2);
+                self.flags.carry = self.unsigned(reg1) < self.unsigned(reg2);
+                self.flags.overflow = i32::try_from(difference).is_err();
+                self.flags.zero = difference == 0;
+                self.flags.negative = (difference as i32) < 0;
+                Flow::Next
+            }
+            CMPU { reg1, reg2 } => {
+                let (value1, value2) = (self.unsigned(reg1), self.unsigned(reg2));
+                self.flags.carry = value1 < value2;
+                self.flags.zero = value1 == value2;
+                self.flags.negative = (value1.wrapping_sub(value2) as i32) < 0;
+                Flow::Next
+            }
+            _ => unreachable!("unsupported instructions are not executed"),
+        };
+        Ok(flow)
+    }
+
+    /// Get the value of a register.
+    fn get(&self, register: u8) -> i32 {
+        self.registers[register as usize]
+    }
+
+    /// Get the value of a register as a signed 64-bit integer.
+    fn wide(&self, register: u8) -> i64 {
+        i64::from(self.get(register))
+    }
+
+    /// Get the bits of a register as an unsigned 64-bit integer.
+    fn unsigned(&self, register: u8) -> u64 {
+        u64::from(self.get(register) as u32)
+    }
+
+    /// Get a shift amount from a register, the negative amounts being larger than any word.
+    fn amount(&self, register: u8) -> u64 {
+        u64::try_from(self.get(register)).unwrap_or(u64::from(u32::MAX))
+    }
+
+    /// Get a divisor from a register, which must not be zero.
+    fn nonzero(&self, register: u8) -> Result<i64, VmError> {
+        match self.wide(register) {
+            0 => Err(VmError::DivisionByZero),
+            divisor => Ok(divisor),
+        }
+    }
+
+    /// Set a register without changing the flags.
+    fn set(&mut self, register: u8, value: i32) -> Flow {
+        self.registers[register as usize] = value;
+        Flow::Next
+    }
+
+    /// Set a register and the zero and negative flags.
+    fn logical(&mut self, register: u8, value: i32) -> Flow {
+        self.flags.zero = value == 0;
+        self.flags.negative = value < 0;
+        self.set(register, value)
+    }
+
+    /// Set a register to the truncation of an exact signed result, and the overflow, zero and
+    /// negative flags.
+    fn signed(&mut self, register: u8, exact: i64) -> Flow {
+        self.flags.overflow = i32::try_from(exact).is_err();
+        self.logical(register, exact as i32)
+    }
+
+    /// Set a register to a shifted value with the last bit shifted out in the carry flag.
+    fn shifted(&mut self, register: u8, value: i32, carry: bool) -> Flow {
+        self.flags.carry = carry;
+        self.logical(register, value)
+    }
+
+    /// Add two registers and a carry, setting all the flags.
+    fn add(&mut self, dest: u8, reg1: u8, reg2: u8, carry: i64) -> Flow {
+        let unsigned = self.unsigned(reg1) + self.unsigned(reg2) + carry as u64;
+        self.flags.carry = unsigned > u64::from(u32::MAX);
+        let sum = self.wide(reg1) + self.wide(reg2) + carry;
+        self.signed(dest, sum)
+    }
+
+    /// Subtract a register and a borrow from a register, setting all the flags.
+    fn sub(&mut self, dest: u8, reg1: u8, reg2: u8, borrow: i64) -> Flow {
+        self.flags.carry = (self.unsigned(reg1) as i64) - (self.unsigned(reg2) as i64) - borrow < 0;
+        let difference = self.wide(reg1) - self.wide(reg2) - borrow;
+        self.signed(dest, difference)
+    }
+
+    /// Check that a word can be accessed at an address.
+    fn check(&self, address: usize) -> Result<usize, VmError> {
+        if address + WORD_SIZE > self.memory.len() {
+            return Err(VmError::MemoryOutOfBounds {
+                address,
+                size: WORD_SIZE,
+            });
+        }
+        if !address.is_multiple_of(WORD_SIZE) {
+            return Err(VmError::MemoryNotAligned {
+                address,
+                size: WORD_SIZE,
+            });
+        }
+        Ok(address)
+    }
+
+    /// Push a value on the stack.
+    fn push(&mut self, value: i32) -> Result<(), VmError> {
+        if self.stack.len() >= self.stack_capacity {
+            return Err(VmError::StackOverflow);
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    /// Pop a value from the stack.
+    fn pop(&mut self) -> Result<i32, VmError> {
+        self.stack.pop().ok_or(VmError::StackUnderflow)
+    }
+}
+
+/// The first difference found between a VM and the reference interpreter, see `compare`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The program uses an instruction the reference interpreter does not execute,
+    /// so the run cannot be compared.
+    Unsupported {
+        /// The unsupported instruction.
+        instruction: Instruction<i32, u32>,
+    },
+    /// The runs terminated differently, with a different exit code, step count or error.
+    Result {
+        /// The result of the VM.
+        vm: Result<Termination<i32>, VmError>,
+        /// The result of the reference interpreter.
+        reference: Result<Termination<i32>, VmError>,
+    },
+    /// The final values of a register differ.
+    Register {
+        /// The index of the register.
+        index: usize,
+        /// The value in the VM.
+        vm: i32,
+        /// The value in the reference interpreter.
+        reference: i32,
+    },
+    /// The final status flags differ.
+    Flags {
+        /// The flags of the VM.
+        vm: StatusFlags,
+        /// The flags of the reference interpreter.
+        reference: StatusFlags,
+    },
+    /// The final program counters differ.
+    Pc {
+        /// The PC of the VM.
+        vm: usize,
+        /// The PC of the reference interpreter.
+        reference: usize,
+    },
+    /// The final contents of the memory differ, first at an address.
+    Memory {
+        /// The address of the first differing byte.
+        address: usize,
+        /// The byte in the VM.
+        vm: u8,
+        /// The byte in the reference interpreter.
+        reference: u8,
+    },
+    /// The final contents of the stack differ.
+    Stack {
+        /// The values of the stack of the VM, from the bottom to the top.
+        vm: Vec<i32>,
+        /// The values of the stack of the reference interpreter, from the bottom to the top.
+        reference: Vec<i32>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Unsupported { instruction } => {
+                write!(f, "{} is not supported by the reference", instruction)
+            }
+            Divergence::Result { vm, reference } => {
+                write!(f, "result {:?} != reference {:?}", vm, reference)
+            }
+            Divergence::Register {
+                index,
+                vm,
+                reference,
+            } => {
+                write!(f, "R{} = {} != reference {}", index, vm, reference)
+            }
+            Divergence::Flags { vm, reference } => {
+                write!(f, "flags {:?} != reference {:?}", vm, reference)
+            }
+            Divergence::Pc { vm, reference } => {
+                write!(f, "PC = {:#x} != reference {:#x}", vm, reference)
+            }
+            Divergence::Memory {
+                address,
+                vm,
+                reference,
+            } => write!(
+                f,
+                "memory[{:#x}] = {:#04x} != reference {:#04x}",
+                address, vm, reference
+            ),
+            Divergence::Stack { vm, reference } => {
+                write!(f, "stack {:?} != reference {:?}", vm, reference)
+            }
+        }
+    }
+}
+
+/// Run a program on a VM and on a `Reference` interpreter of the same size, and diff the
+/// results and the final states: the registers, the status flags, the PC, the memory and the
+/// stack. The states are also compared after an error, the faulting instruction having no effect
+/// in both.
+///
+/// The VM must be in the configuration of the reference interpreter, see `Reference`.
+///
+/// # Returns
+/// The first divergence found, `Ok(())` if the runs are identical.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::reference;
+/// use forge_vm::VM;
+///
+/// let program = Assembler::new()
+///     .assemble("MOV R0 -1\nMOV R1 31\nSHR R2 R0 R1\nADD R3 R0 R2\nHLT")
+///     .unwrap();
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// assert_eq!(reference::compare(&mut vm, &program, 100), Ok(()));
+/// ```
+pub fn compare(vm: &mut VM<i32>, program: &[u8], max_steps: u128) -> Result<(), Divergence> {
+    let mut reference = Reference::new(vm.stack.capacity(), vm.memory.bytes().len())
+        .with_registers(vm.cpu.registers_count());
+    let expected = reference.run(program, max_steps);
+    if let Some(instruction) = reference.unsupported() {
+        return Err(Divergence::Unsupported { instruction });
+    }
+    let result = vm.run_with_limit(program, max_steps);
+    let result = result.map_err(|error| error.source);
+    if result != expected {
+        return Err(Divergence::Result {
+            vm: result,
+            reference: expected,
+        });
+    }
+
+    let (actual, expected) = (vm.snapshot(), reference.snapshot());
+    let registers = actual.cpu.registers.iter().zip(&expected.cpu.registers);
+    if let Some((index, (vm, reference))) = registers
+        .enumerate()
+        .find(|(_, (vm, reference))| vm != reference)
+    {
+        return Err(Divergence::Register {
+            index,
+            vm: *vm,
+            reference: *reference,
+        });
+    }
+    if actual.cpu.status_flags != expected.cpu.status_flags {
+        return Err(Divergence::Flags {
+            vm: actual.cpu.status_flags,
+            reference: expected.cpu.status_flags,
+        });
+    }
+    if actual.cpu.pc != expected.cpu.pc {
+        return Err(Divergence::Pc {
+            vm: actual.cpu.pc,
+            reference: expected.cpu.pc,
+        });
+    }
+    let memory = actual.memory.iter().zip(&expected.memory);
+    if let Some((address, (vm, reference))) = memory
+        .enumerate()
+        .find(|(_, (vm, reference))| vm != reference)
+    {
+        return Err(Divergence::Memory {
+            address,
+            vm: *vm,
+            reference: *reference,
+        });
+    }
+    if actual.stack != expected.stack {
+        return Err(Divergence::Stack {
+            vm: actual.stack,
+            reference: expected.stack,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::gen::Generator;
+    use crate::vm::builder::VmBuilder;
+
+    /// Compare an assembled program on a default VM.
+    fn compare_source(source: &str) -> Result<(), Divergence> {
+        let program = Assembler::new().assemble(source).unwrap();
+        compare(&mut VM::<i32>::new(16, 1024), &program, 1000)
+    }
+
+    #[test]
+    fn test_reference_generated_programs() {
+        let generator = Generator::new();
+        for seed in 0..200 {
+            let program = generator.generate(seed);
+            let mut vm = VM::<i32>::new(1024, 1024);
+            if let Err(divergence) = compare(&mut vm, &program, generator.max_steps()) {
+                panic!("seed {}: {}", seed, divergence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reference_arithmetic_edges() {
+        let mut source = String::new();
+        let values = [0, 1, -1, 2, 31, 32, 33, i32::MAX, i32::MIN, 0x5555_5555];
+        let mut label = 0;
+        for value1 in values {
+            for value2 in values {
+                source += &format!("MOV R1 {}\nMOV R2 {}\n", value1, value2);
+                for operation in [
+                    "ADD", "SUB", "ADC", "SBB", "MULT", "MULU", "MULH", "UMULH", "SHL", "SHR",
+                    "SAR", "ROL", "ROR", "MINU", "MAXU",
+                ] {
+                    // the results are stored to be compared, the flags select the branches
+                    source += &format!("{} R3 R1 R2\nST R3 {}\n", operation, label * 8);
+                    source += &format!("CMP R1 R2\nJMPLT less{}\n", label);
+                    source += &format!("{} R3 R1 R2\nJMPB less{}\n", operation, label);
+                    source += &format!("PUSHREG R3\nPOPREG R0\nless{}: ", label);
+                    source += &format!("ST R0 {}\n", label * 8 + 4);
+                    label += 1;
+                }
+                source += "CMPU R1 R2\nINC R1\nDEC R2\nADC R0 R1 R2\n";
+            }
+        }
+        source += "HLT";
+        let program = Assembler::new().assemble(&source).unwrap();
+        let mut vm = VM::<i32>::new(16, label * 8);
+        assert_eq!(compare(&mut vm, &program, 100_000), Ok(()));
+    }
+
+    #[test]
+    fn test_reference_errors() {
+        // the state before the faulting instruction is compared
+        assert_eq!(compare_source("MOV R1 7\nDIV R0 R1 R2\nHLT"), Ok(()));
+        assert_eq!(
+            compare_source("MOV R1 -1\nMOV R2 0x80000000\nDIV R0 R2 R1\nMOD R3 R2 R1\nHLT"),
+            Ok(())
+        );
+        assert_eq!(compare_source("LD R0 1022\nHLT"), Ok(()));
+        assert_eq!(compare_source("ST R0 2\nHLT"), Ok(()));
+        assert_eq!(compare_source("POPREG R0\nHLT"), Ok(()));
+        assert_eq!(compare_source("loop: PUSHREG R0\nJMP loop"), Ok(()));
+        assert_eq!(compare_source("loop: INC R0\nJMP loop"), Ok(()));
+        assert_eq!(
+            compare_source("CALL function\nEXIT 3\nfunction: RET"),
+            Ok(())
+        );
+        assert_eq!(compare_source("MOV R0 -8\nPUSHREG R0\nRET"), Ok(()));
+        assert_eq!(compare_source("JMP 1000"), Ok(()));
+    }
+
+    #[test]
+    fn test_reference_divergence() {
+        assert!(matches!(
+            compare_source("MOV R0 1\nPUSHF\nHLT"),
+            Err(Divergence::Unsupported {
+                instruction: Instruction::PUSHF
+            })
+        ));
+        // a VM with another configuration is detected
+        let program = Assembler::new().assemble("MOV R0 1\nST R0 2\nHLT").unwrap();
+        let mut vm = VmBuilder::new().strict_alignment(false).build::<i32>();
+        let divergence = compare(&mut vm, &program, 100).unwrap_err();
+        assert!(matches!(divergence, Divergence::Result { .. }));
+        assert!(divergence.to_string().starts_with("result Ok("));
+    }
+}
@@ -0,0 +1,393 @@
+//! The record and replay of the nondeterministic inputs of a run, see `VM::start_recording`.
+
+use std::collections::VecDeque;
+
+use super::cpu::CpuState;
+use super::error::{Result, VmError};
+
+/// A nondeterministic input of a run, recorded with the step of the instruction it affects:
+/// the interrupts are delivered before the instruction of their step, the device reads and the
+/// syscalls happen during the instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReplayEvent<T> {
+    /// An interrupt delivered to the handler of its line.
+    Interrupt { step: u128, line: u8 },
+    /// A read routed to a device, with the bytes returned by the device.
+    DeviceRead {
+        step: u128,
+        address: usize,
+        bytes: Vec<u8>,
+    },
+    /// A syscall, with its effects on the CPU and on the memory.
+    Syscall {
+        step: u128,
+        /// The state of the CPU after the syscall.
+        state: CpuState<T>,
+        /// The bytes of the memory modified by the syscall, with their address.
+        memory: Vec<(usize, Vec<u8>)>,
+        /// The message of the error of the syscall, if it failed.
+        error: Option<String>,
+    },
+}
+
+impl<T> ReplayEvent<T> {
+    /// Get the step of the event.
+    pub fn step(&self) -> u128 {
+        match self {
+            ReplayEvent::Interrupt { step, .. }
+            | ReplayEvent::DeviceRead { step, .. }
+            | ReplayEvent::Syscall { step, .. } => *step,
+        }
+    }
+}
+
+/// The nondeterministic inputs of a run, in the order they happened: the interrupts, the
+/// device reads and the syscalls. Replayed by a VM running the same program, they reproduce the
+/// run exactly, without calling the host functions and the devices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Recording<T> {
+    events: Vec<ReplayEvent<T>>,
+}
+
+impl<T> Recording<T> {
+    pub fn new() -> Self {
+        Self { events: Vec::new() }
+    }
+
+    /// Get the recorded events, in the order they happened.
+    pub fn events(&self) -> &[ReplayEvent<T>] {
+        &self.events
+    }
+}
+
+/// The record or replay mode of a VM.
+pub(crate) enum Replay<T> {
+    Off,
+    Recording(Recording<T>),
+    /// The events of each kind are replayed in order, with a cursor per kind.
+    Replaying {
+        recording: Recording<T>,
+        next_interrupt: usize,
+        next_syscall: usize,
+    },
+}
+
+impl<T: Clone> Replay<T> {
+    /// Restart the recording or the replay for a new run.
+    ///
+    /// # Returns
+    /// The device reads to replay, or to record, by the memory.
+    pub(crate) fn restart(&mut self) -> DeviceReads {
+        match self {
+            Replay::Off => DeviceReads::Off,
+            Replay::Recording(recording) => {
+                recording.events.clear();
+                DeviceReads::Record(Vec::new())
+            }
+            Replay::Replaying {
+                recording,
+                next_interrupt,
+                next_syscall,
+            } => {
+                *next_interrupt = 0;
+                *next_syscall = 0;
+                let reads = recording.events.iter().filter_map(|event| match event {
+                    ReplayEvent::DeviceRead { address, bytes, .. } => {
+                        Some((*address, bytes.clone()))
+                    }
+                    _ => None,
+                });
+                DeviceReads::Replay(reads.collect())
+            }
+        }
+    }
+
+    /// Record an event when recording.
+    pub(crate) fn record(&mut self, event: ReplayEvent<T>) {
+        if let Replay::Recording(recording) = self {
+            recording.events.push(event);
+        }
+    }
+
+    pub(crate) fn is_replaying(&self) -> bool {
+        matches!(self, Replay::Replaying { .. })
+    }
+
+    /// Take the next recorded interrupt if it was delivered at `step`.
+    pub(crate) fn next_interrupt(&mut self, step: u128) -> Option<u8> {
+        let Replay::Replaying {
+            recording,
+            next_interrupt,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let (index, line) = recording.events[*next_interrupt..]
+            .iter()
+            .enumerate()
+            .find_map(|(index, event)| match event {
+                ReplayEvent::Interrupt { step: at, line } => Some((index, *at, *line)),
+                _ => None,
+            })
+            .filter(|(_, at, _)| *at == step)
+            .map(|(index, _, line)| (index, line))?;
+        *next_interrupt += index + 1;
+        Some(line)
+    }
+
+    /// Take the next recorded syscall, which must have been made at `step`.
+    ///
+    /// # Returns
+    /// The state of the CPU, the modified memory and the error of the syscall.
+    ///
+    /// # Errors
+    /// Returns `VmError::ReplayDivergence` if the next syscall was not made at `step`.
+    pub(crate) fn next_syscall(&mut self, step: u128) -> Result<SyscallEffects<T>> {
+        let Replay::Replaying {
+            recording,
+            next_syscall,
+            ..
+        } = self
+        else {
+            return Err(VmError::ReplayDivergence);
+        };
+        let (index, state, memory, error) = recording.events[*next_syscall..]
+            .iter()
+            .enumerate()
+            .find_map(|(index, event)| match event {
+                ReplayEvent::Syscall {
+                    step: at,
+                    state,
+                    memory,
+                    error,
+                } => Some((index, *at, state, memory, error)),
+                _ => None,
+            })
+            .filter(|(_, at, ..)| *at == step)
+            .map(|(index, _, state, memory, error)| (index, state, memory, error))
+            .ok_or(VmError::ReplayDivergence)?;
+        *next_syscall += index + 1;
+        Ok((state.clone(), memory.clone(), error.clone()))
+    }
+}
+
+/// The effects of a syscall: the state of the CPU, the modified memory and the error.
+pub(crate) type SyscallEffects<T> = (CpuState<T>, Vec<(usize, Vec<u8>)>, Option<String>);
+
+/// The device reads recorded or replayed by the memory.
+#[derive(Debug, Default)]
+pub(crate) enum DeviceReads {
+    #[default]
+    Off,
+    /// The reads since the last call to `take`, with their address.
+    Record(Vec<(usize, Vec<u8>)>),
+    /// The reads left to replay, returned instead of reading the devices.
+    Replay(VecDeque<(usize, Vec<u8>)>),
+}
+
+impl DeviceReads {
+    /// Take the recorded reads, empty when not recording.
+    pub(crate) fn take(&mut self) -> Vec<(usize, Vec<u8>)> {
+        match self {
+            DeviceReads::Record(reads) => std::mem::take(reads),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Find the ranges of bytes that differ between two versions of the memory.
+pub(crate) fn memory_diff(before: &[u8], after: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let mut ranges: Vec<(usize, Vec<u8>)> = Vec::new();
+    for (address, (old, new)) in before.iter().zip(after).enumerate() {
+        if old == new {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some((start, bytes)) if *start + bytes.len() == address => bytes.push(*new),
+            _ => ranges.push((address, vec![*new])),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::vm::device::Device;
+    use crate::vm::port::PortHandler;
+    use crate::vm::program::Program;
+    use crate::VM;
+
+    /// A device reading a different value at each read.
+    struct Sensor(u32);
+
+    impl Device for Sensor {
+        fn read(&mut self, _offset: usize, size: usize) -> Result<Vec<u8>> {
+            self.0 = self.0.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let mut bytes = self.0.to_le_bytes().to_vec();
+            bytes.truncate(size);
+            Ok(bytes)
+        }
+
+        fn write(&mut self, _offset: usize, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_memory_diff() {
+        assert_eq!(
+            memory_diff(&[0, 1, 2, 3, 4], &[0, 9, 9, 3, 8]),
+            [(1, vec![9, 9]), (4, vec![8])]
+        );
+        assert!(memory_diff(&[1, 2], &[1, 2]).is_empty());
+    }
+
+    #[test]
+    fn test_record_replay() {
+        // read the sensor twice, then a syscall stores a host value in R2 and in the memory
+        let source = "LD R0 0x1000\nLD R1 0x1000\nSYSCALL 1\nLD R3 0x20\nHLT";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        vm.map_device(0x1000..0x1004, Sensor(1)).unwrap();
+        let mut calls = 0;
+        vm.register_host_fn(1, move |cpu, memory| {
+            calls += 1;
+            cpu.set_register(2, calls)?;
+            memory.write::<i32>(0x20, 100 + calls)
+        });
+        vm.start_recording();
+        assert_eq!(vm.run(&program).map(|termination| termination.steps), Ok(5));
+        let recorded = vm.snapshot();
+        let recording = vm.take_recording().unwrap();
+        assert!(vm.take_recording().is_none());
+        assert_eq!(
+            recording
+                .events()
+                .iter()
+                .map(ReplayEvent::step)
+                .collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        let ReplayEvent::Syscall {
+            state,
+            memory,
+            error: None,
+            ..
+        } = &recording.events()[2]
+        else {
+            panic!("unexpected event {:?}", recording.events()[2]);
+        };
+        assert_eq!(state.registers[2], 1);
+        assert_eq!(memory, &[(0x20, vec![101])]);
+
+        // the replay reads the recorded values, without calling the device and the syscall
+        let mut replay = VM::<i32>::new(16, 64);
+        replay.map_device(0x1000..0x1004, Sensor(7)).unwrap();
+        replay.replay(recording);
+        assert_eq!(
+            replay.run(&program).map(|termination| termination.steps),
+            Ok(5)
+        );
+        assert_eq!(replay.snapshot(), recorded);
+        // the replay restarts at each run
+        assert_eq!(
+            replay.run(&program).map(|termination| termination.steps),
+            Ok(5)
+        );
+        assert_eq!(replay.snapshot(), recorded);
+
+        // a different program diverges from the recording
+        let program = Assembler::new().assemble("SYSCALL 1\nHLT").unwrap();
+        assert_eq!(
+            replay.run(&program).map_err(|error| error.source),
+            Err(VmError::ReplayDivergence)
+        );
+        replay.stop_replay();
+        assert_eq!(
+            replay.run(&program).map_err(|error| error.source),
+            Err(VmError::UnknownSyscall { number: 1 })
+        );
+    }
+
+    #[test]
+    fn test_record_replay_ports() {
+        struct Keyboard(i32);
+
+        impl PortHandler<i32> for Keyboard {
+            fn read(&mut self) -> Result<i32> {
+                self.0 += 1;
+                Ok(self.0)
+            }
+        }
+
+        let program = Assembler::new()
+            .assemble("IN R0 0x60\nIN R1 0x60\nOUT 0x61 R1\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        vm.register_port(0x60, Keyboard(40));
+        vm.register_port(0x61, Keyboard(0));
+        vm.start_recording();
+        assert!(vm.run(&program).is_ok());
+        let recorded = vm.snapshot();
+        let recording = vm.take_recording().unwrap();
+        assert_eq!(recording.events().len(), 3);
+
+        // the replay needs no port handler
+        let mut replay = VM::<i32>::new(16, 64);
+        replay.replay(recording);
+        assert!(replay.run(&program).is_ok());
+        assert_eq!(replay.snapshot(), recorded);
+        assert_eq!(replay.registers()[..2], [41, 42]);
+    }
+
+    #[test]
+    fn test_record_replay_interrupts() {
+        let source = "
+                MOV R0 handler
+                ST R0 16
+                MOV R3 20
+                EI
+            loop:
+                INC R2
+                CMP R2 R3
+                JMPNZ loop
+                HLT
+            handler:
+                INC R1
+                IRET
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        vm.start_recording();
+        vm.load_program(&Program::new(&program)).unwrap();
+        for step in 0.. {
+            if step == 10 {
+                vm.raise_interrupt(4);
+            }
+            if vm.step().unwrap() {
+                break;
+            }
+        }
+        let recorded = vm.snapshot();
+        assert_eq!(recorded.cpu.registers[1], 1);
+        let recording = vm.take_recording().unwrap();
+        assert_eq!(
+            recording.events(),
+            [ReplayEvent::Interrupt { step: 11, line: 4 }]
+        );
+
+        // the interrupt is delivered at the same step without being raised
+        let mut replay = VM::<i32>::new(16, 64);
+        replay.replay(recording);
+        assert_eq!(
+            replay.run(&program).map(|termination| termination.steps),
+            Ok(recorded.steps)
+        );
+        assert_eq!(replay.snapshot(), recorded);
+    }
+}
@@ -0,0 +1,165 @@
+//! The asynchronous run of a program, see `VM::run_async`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use super::error::{ExecutionError, VmError};
+use super::termination::Termination;
+use super::word::Word;
+use super::VM;
+
+/// The default number of steps executed by each poll of a `RunFuture`.
+pub const STEPS_PER_POLL: usize = 1024;
+
+/// A token cancelling the asynchronous runs it was given to, shared by its clones.
+///
+/// The token can be cancelled from any thread, the run checks it before each poll and stops
+/// with `VmError::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the runs of the token and of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// The future of an asynchronous run, returned by `VM::run_async`.
+///
+/// Each poll executes up to `steps_per_poll` instructions, then yields to the executor by waking
+/// itself and returning `Poll::Pending` if the program is still running, so that a long-running
+/// program never blocks the thread of the executor for long.
+///
+/// The future resolves to the termination of the program or to the located error, as `VM::run`.
+pub struct RunFuture<'a, T: Word> {
+    vm: &'a mut VM<T>,
+    cancel: CancelToken,
+    steps_per_poll: usize,
+    /// The error of the loading of the program, returned by the first poll.
+    error: Option<VmError>,
+}
+
+impl<'a, T: Word> RunFuture<'a, T> {
+    pub(crate) fn new(vm: &'a mut VM<T>, error: Option<VmError>, cancel: CancelToken) -> Self {
+        Self {
+            vm,
+            cancel,
+            steps_per_poll: STEPS_PER_POLL,
+            error,
+        }
+    }
+
+    /// Set the number of steps executed by each poll, at least one.
+    pub fn with_steps_per_poll(mut self, steps: usize) -> Self {
+        self.steps_per_poll = steps.max(1);
+        self
+    }
+}
+
+impl<T: Word> Future for RunFuture<'_, T> {
+    type Output = Result<Termination<T>, ExecutionError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(error) = this.error.take() {
+            return Poll::Ready(Err(this.vm.fault(error)));
+        }
+        if this.cancel.is_cancelled() {
+            log::info!("Run cancelled after {} steps.", this.vm.counters().steps);
+            return Poll::Ready(Err(this.vm.fault(VmError::Cancelled)));
+        }
+        for _ in 0..this.steps_per_poll {
+            match this.vm.step() {
+                Ok(false) => {}
+                Ok(true) => return Poll::Ready(Ok(this.vm.termination())),
+                Err(error) => return Poll::Ready(Err(this.vm.fault(error))),
+            }
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use std::task::Waker;
+
+    /// Poll a future until it is ready, returning the result and the number of polls.
+    fn poll_to_end<F: Future>(future: F) -> (F::Output, usize) {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut polls = 1;
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return (output, polls);
+            }
+            polls += 1;
+        }
+    }
+
+    #[test]
+    fn test_run_async() {
+        // 1 + 10 * 2 + 1 steps
+        let program = Assembler::new()
+            .assemble("MOV R0 10\nloop: DEC R0\nJMPNZ loop\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        let future = vm
+            .run_async(&program, CancelToken::new())
+            .with_steps_per_poll(5);
+        assert_eq!(
+            poll_to_end(future),
+            (
+                Ok(Termination {
+                    steps: 22,
+                    exit_code: 0
+                }),
+                5
+            )
+        );
+        assert_eq!(vm.snapshot().cpu.registers[0], 0);
+
+        let (result, polls) = poll_to_end(vm.run_async(&[0x12], CancelToken::new()));
+        assert_eq!(
+            result.map_err(|error| error.source),
+            Err(VmError::InvalidInstruction)
+        );
+        assert_eq!(polls, 1);
+    }
+
+    #[test]
+    fn test_run_async_cancel() {
+        let program = Assembler::new().assemble("loop: JMP loop").unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        let token = CancelToken::new();
+        let mut future = std::pin::pin!(vm.run_async(&program, token.clone()));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        std::thread::scope(|scope| {
+            scope.spawn(|| token.cancel());
+        });
+        assert!(token.is_cancelled());
+        let Poll::Ready(Err(error)) = future.as_mut().poll(&mut cx) else {
+            panic!("the run is not cancelled");
+        };
+        assert_eq!(error.source, VmError::Cancelled);
+        assert_eq!(vm.counters().steps, 2 * STEPS_PER_POLL as u128);
+    }
+}
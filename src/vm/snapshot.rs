@@ -0,0 +1,34 @@
+use super::cpu::CpuState;
+
+/// A snapshot of the whole state of a VM: CPU, memory, stack and step counter.
+/// Snapshots are taken with `VM::snapshot` and restored with `VM::restore`.
+/// With the `serde` feature, snapshots can be serialized to persist or transfer the state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmSnapshot<T> {
+    /// The registers, status flags and program counter.
+    pub cpu: CpuState<T>,
+    /// The content of the memory.
+    pub memory: Vec<u8>,
+    /// The values of the stack, from the bottom to the top.
+    pub stack: Vec<T>,
+    /// The number of steps executed.
+    pub steps: u128,
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::vm::cpu::StatusFlags;
+    use crate::vm::memory::Memory;
+
+    fn assert_serde<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+
+    #[test]
+    fn test_serde_implemented() {
+        assert_serde::<VmSnapshot<i32>>();
+        assert_serde::<CpuState<i32>>();
+        assert_serde::<StatusFlags>();
+        assert_serde::<Memory>();
+    }
+}
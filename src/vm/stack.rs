@@ -5,6 +5,8 @@ use super::error::{Result, VmError};
 pub struct Stack<T> {
     data: Vec<T>,
     capacity: usize,
+    /// The highest number of values held since the last clear.
+    high_water: usize,
 }
 
 impl<T> Stack<T> {
@@ -12,6 +14,7 @@ impl<T> Stack<T> {
         Self {
             data: Vec::with_capacity(capacity),
             capacity,
+            high_water: 0,
         }
     }
 
@@ -21,6 +24,7 @@ impl<T> Stack<T> {
         }
 
         self.data.push(value);
+        self.high_water = self.high_water.max(self.data.len());
         Ok(())
     }
 
@@ -40,6 +44,17 @@ impl<T> Stack<T> {
         Ok(&self.data[self.data.len() - 1])
     }
 
+    /// Swap the two values on the top of the stack.
+    pub fn swap(&mut self) -> Result<()> {
+        let len = self.data.len();
+        if len < 2 {
+            return Err(VmError::StackUnderflow);
+        }
+
+        self.data.swap(len - 1, len - 2);
+        Ok(())
+    }
+
     pub fn len(&self) -> usize {
         self.data.len()
     }
@@ -50,11 +65,45 @@ impl<T> Stack<T> {
 
     pub fn clear(&mut self) {
         self.data.clear();
+        self.high_water = 0;
     }
 
     pub fn capacity(&self) -> usize {
         self.capacity
     }
+
+    /// Get the values of the stack, from the bottom to the top.
+    pub fn values(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Get the highest number of values held by the stack since it was created or cleared.
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water
+    }
+}
+
+/// The region of the memory holding the stack of a CPU, when the stack lives in the memory.
+///
+/// The stack grows down from `top` to `bottom`. The stack pointer (SP) of the CPU is the address
+/// of the value on the top of the stack, and `top` when the stack is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStack {
+    /// The lowest address of the stack, the stack is full when the SP reaches it.
+    pub bottom: usize,
+    /// The address following the highest value of the stack, the SP of the empty stack.
+    pub top: usize,
+}
+
+impl MemoryStack {
+    /// Create the region of a stack of `capacity` values of `size` bytes ending at `top`.
+    /// The region is truncated at the address `0`.
+    pub fn new(top: usize, capacity: usize, size: usize) -> Self {
+        Self {
+            bottom: top.saturating_sub(capacity.saturating_mul(size)),
+            top,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -100,6 +149,16 @@ mod tests {
         assert_eq!(stack.peek().unwrap_err(), VmError::StackUnderflow);
     }
 
+    #[test]
+    fn test_stack_swap() {
+        let mut stack = Stack::<i32>::new(1024);
+        stack.push(1).unwrap();
+        assert_eq!(stack.swap().unwrap_err(), VmError::StackUnderflow);
+        stack.push(2).unwrap();
+        stack.swap().unwrap();
+        assert_eq!(stack.values(), &[2, 1]);
+    }
+
     #[test]
     fn test_stack_len() {
         let mut stack = Stack::<i32>::new(1024);
@@ -121,9 +180,28 @@ mod tests {
         assert!(stack.is_empty());
     }
 
+    #[test]
+    fn test_stack_high_water_mark() {
+        let mut stack = Stack::<i32>::new(1024);
+        stack.push(1).unwrap();
+        stack.push(2).unwrap();
+        stack.pop().unwrap();
+        assert_eq!(stack.high_water_mark(), 2);
+        stack.clear();
+        assert_eq!(stack.high_water_mark(), 0);
+    }
+
     #[test]
     fn test_stack_capacity() {
         let stack = Stack::<i32>::new(1024);
         assert_eq!(stack.capacity(), 1024);
     }
+
+    #[test]
+    fn test_memory_stack_new() {
+        let region = MemoryStack::new(1024, 16, 4);
+        assert_eq!(region.bottom, 960);
+        assert_eq!(region.top, 1024);
+        assert_eq!(MemoryStack::new(16, 16, 4).bottom, 0);
+    }
 }
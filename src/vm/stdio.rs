@@ -0,0 +1,199 @@
+//! The built-in standard I/O syscalls of the VM, see `Stdio`.
+
+use std::io::{Read, Write};
+
+use super::cpu::CpuView;
+use super::error::{Result, VmError};
+use super::memory::Memory;
+use super::syscall::SyscallTable;
+use super::word::Word;
+
+/// The standard streams of a guest, exposed as syscalls transferring byte buffers.
+///
+/// Every syscall takes the address of the buffer in `R0` and its length in `R1`,
+/// and returns the number of bytes transferred in `R0`:
+/// - `SYSCALL Stdio::READ` reads at most `R1` bytes of the input into the buffer,
+///   `0` at the end of the input.
+/// - `SYSCALL Stdio::WRITE` writes the buffer to the output.
+/// - `SYSCALL Stdio::WRITE_ERR` writes the buffer to the error output.
+pub struct Stdio {
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    error: Box<dyn Write>,
+}
+
+impl Stdio {
+    /// The syscall number reading the input into a buffer.
+    pub const READ: u8 = 0xf0;
+    /// The syscall number writing a buffer to the output.
+    pub const WRITE: u8 = 0xf1;
+    /// The syscall number writing a buffer to the error output.
+    pub const WRITE_ERR: u8 = 0xf2;
+
+    /// Create the streams of the host process: the standard input, output and error.
+    pub fn inherit() -> Self {
+        Self::new(std::io::stdin(), std::io::stdout(), std::io::stderr())
+    }
+
+    /// Create the streams with a specific input, output and error output.
+    ///
+    /// # Parameters
+    /// - `input`: The source of the bytes read by the guest.
+    /// - `output`: The destination of the bytes written by the guest.
+    /// - `error`: The destination of the bytes written by the guest to its error output.
+    pub fn new<R, W, E>(input: R, output: W, error: E) -> Self
+    where
+        R: Read + 'static,
+        W: Write + 'static,
+        E: Write + 'static,
+    {
+        Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            error: Box::new(error),
+        }
+    }
+
+    /// Register the syscalls in a syscall table, replacing the functions registered under
+    /// the same numbers.
+    pub fn register<T: Word>(self, syscalls: &mut SyscallTable<T>) {
+        let Self {
+            mut input,
+            mut output,
+            mut error,
+        } = self;
+        syscalls.register(
+            Self::READ,
+            Box::new(move |cpu, memory| read(&mut input, cpu, memory)),
+        );
+        syscalls.register(
+            Self::WRITE,
+            Box::new(move |cpu, memory| write(&mut output, cpu, memory)),
+        );
+        syscalls.register(
+            Self::WRITE_ERR,
+            Box::new(move |cpu, memory| write(&mut error, cpu, memory)),
+        );
+    }
+}
+
+/// Get the buffer of a syscall, from the address in `R0` and the length in `R1`.
+fn buffer<T: Word>(cpu: &CpuView<T>, memory: &Memory) -> Result<(usize, usize)> {
+    let address = cpu.register(0)?.to_usize();
+    let size = cpu.register(1)?.to_usize();
+    if address.saturating_add(size) > memory.capacity() {
+        return Err(VmError::MemoryOutOfBounds { address, size });
+    }
+    Ok((address, size))
+}
+
+fn read<T: Word>(input: &mut dyn Read, cpu: &mut CpuView<T>, memory: &mut Memory) -> Result<()> {
+    let (address, size) = buffer(cpu, memory)?;
+    let mut bytes = vec![0; size];
+    let read = input
+        .read(&mut bytes)
+        .map_err(|error| VmError::Other(format!("Standard input error: {}", error)))?;
+    for (offset, byte) in bytes[..read].iter().enumerate() {
+        memory.write::<u8>(address + offset, *byte)?;
+    }
+    cpu.set_register(0, T::from_usize(read))
+}
+
+fn write<T: Word>(output: &mut dyn Write, cpu: &mut CpuView<T>, memory: &mut Memory) -> Result<()> {
+    let (address, size) = buffer(cpu, memory)?;
+    let bytes = (address..address + size)
+        .map(|address| memory.read::<u8>(address))
+        .collect::<Result<Vec<u8>>>()?;
+    output
+        .write_all(&bytes)
+        .and_then(|_| output.flush())
+        .map_err(|error| VmError::Other(format!("Standard output error: {}", error)))?;
+    cpu.set_register(0, T::from_usize(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    /// An output shared with the test, to inspect the bytes written by the guest.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stdio_echo() {
+        // read the input into 0x100 and write it back to both outputs
+        let source = "
+            MOV R0 0x100
+            MOV R1 16
+            SYSCALL 0xf0
+            MOVR R1 R0
+            MOV R0 0x100
+            SYSCALL 0xf1
+            MOV R0 0x100
+            SYSCALL 0xf2
+            MOVR R2 R0
+            MOV R0 0x100
+            SYSCALL 0xf0
+            HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let output = SharedOutput::default();
+        let error = SharedOutput::default();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_stdio(Stdio::new(
+            Cursor::new(b"hello".to_vec()),
+            output.clone(),
+            error.clone(),
+        ));
+        vm.run(&program).unwrap();
+        assert_eq!(*output.0.borrow(), b"hello");
+        assert_eq!(*error.0.borrow(), b"hello");
+        let registers = vm.snapshot().cpu.registers;
+        // the written length, then the end of the input
+        assert_eq!((registers[2], registers[0]), (5, 0));
+        assert_eq!(&vm.snapshot().memory[0x100..0x105], b"hello");
+    }
+
+    #[test]
+    fn test_stdio_out_of_bounds() {
+        let source = "
+            MOV R0 1020
+            MOV R1 8
+            SYSCALL 0xf1
+            HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let output = SharedOutput::default();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.register_stdio(Stdio::new(
+            std::io::empty(),
+            output.clone(),
+            std::io::sink(),
+        ));
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::MemoryOutOfBounds {
+                address: 1020,
+                size: 8
+            })
+        );
+        assert!(output.0.borrow().is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use super::cpu::CpuView;
+use super::error::{Result as VmResult, VmError};
+use super::memory::Memory;
+
+/// A host function callable by the guest with the `SYSCALL` instruction.
+/// The function receives a view of the CPU registers and the memory of the VM.
+pub type HostFn<T> = Box<dyn FnMut(&mut CpuView<T>, &mut Memory) -> VmResult<()>>;
+
+/// The registry of the host functions of a VM, indexed by syscall number.
+pub struct SyscallTable<T> {
+    functions: HashMap<u8, HostFn<T>>,
+}
+
+impl<T> SyscallTable<T> {
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Register a host function under a syscall number, replacing any previous one.
+    ///
+    /// # Parameters
+    /// - `number`: The syscall number used by the guest.
+    /// - `function`: The host function to call.
+    pub fn register(&mut self, number: u8, function: HostFn<T>) {
+        self.functions.insert(number, function);
+    }
+
+    /// Remove the host function registered under a syscall number.
+    ///
+    /// # Returns
+    /// `true` if a function was registered under the number.
+    pub fn unregister(&mut self, number: u8) -> bool {
+        self.functions.remove(&number).is_some()
+    }
+
+    /// Call the host function registered under a syscall number.
+    ///
+    /// # Errors
+    /// Returns an error if no function is registered under the number,
+    /// or the error returned by the function.
+    pub fn call(&mut self, number: u8, cpu: &mut CpuView<T>, memory: &mut Memory) -> VmResult<()> {
+        let function = self
+            .functions
+            .get_mut(&number)
+            .ok_or(VmError::UnknownSyscall { number })?;
+        function(cpu, memory)
+    }
+}
+
+impl<T> Default for SyscallTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
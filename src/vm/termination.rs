@@ -0,0 +1,34 @@
+//! The normal termination of a run, see `Termination`.
+
+use super::word::Word;
+
+/// The termination of a program: the number of steps it executed and its exit code.
+///
+/// A program terminates when its last thread executes `HLT`, with the value of its `R0`
+/// register as exit code, or when any of its threads executes `EXIT`, with the immediate of
+/// the instruction as exit code.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::termination::Termination;
+/// use forge_vm::VM;
+/// let program = Assembler::new().assemble("MOV R0 3\nHLT").unwrap();
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// assert_eq!(vm.run(&program), Ok(Termination { steps: 2, exit_code: 3 }));
+/// assert!(!vm.run(&program).unwrap().is_success());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Termination<T> {
+    /// The number of executed instructions, including the final `HLT` or `EXIT`.
+    pub steps: u128,
+    /// The exit code of the program.
+    pub exit_code: T,
+}
+
+impl<T: Word> Termination<T> {
+    /// Check if the program reported a success, with the exit code `0`.
+    pub fn is_success(&self) -> bool {
+        self.exit_code == T::zero()
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::VecDeque;
+
+use super::cpu::{CpuState, StatusFlags, CPU};
+use super::error::{Result, VmError};
+use super::instructions::Instruction;
+use super::stack::Stack;
+use super::word::Word;
+
+/// A suspended green thread: the state of its CPU and its stack.
+struct Thread<T> {
+    id: usize,
+    cpu: CpuState<T>,
+    stack: Stack<T>,
+}
+
+/// Cooperative scheduler of the green threads of a VM.
+///
+/// The running thread lives in the CPU and the stack of the VM, the scheduler holds the
+/// suspended threads and swaps them in on `YIELD`, `JOIN` and `HLT`. The thread started by
+/// `VM::run` has the id `0`, the spawned threads get increasing ids. `HLT` ends the running
+/// thread, and the program ends with the last thread.
+pub(crate) struct Scheduler<T> {
+    /// The id of the running thread.
+    current: usize,
+    /// The id of the next spawned thread.
+    next_id: usize,
+    /// The threads ready to run, in the order they will resume.
+    ready: VecDeque<Thread<T>>,
+    /// The threads waiting in `JOIN`, with the id of the thread they wait for.
+    joining: Vec<(Thread<T>, usize)>,
+}
+
+impl<T: Word> Scheduler<T> {
+    pub fn new() -> Self {
+        Self {
+            current: 0,
+            next_id: 1,
+            ready: VecDeque::new(),
+            joining: Vec::new(),
+        }
+    }
+
+    /// Drop all the threads, the running thread becomes the thread `0`.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Get the id of the running thread.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Execute a thread instruction, `SPAWN`, `YIELD` or `JOIN`, for the running thread.
+    ///
+    /// # Errors
+    /// Returns `VmError::Deadlock` if the running thread joins while no other thread can run.
+    pub fn execute(
+        &mut self,
+        instruction: Instruction<T, T::Address>,
+        cpu: &mut CPU<T>,
+        stack: &mut Stack<T>,
+    ) -> Result<()> {
+        cpu.set_pc(cpu.pc() + instruction.size());
+        match instruction {
+            Instruction::SPAWN { dest, address } => {
+                let id = self.next_id;
+                self.next_id += 1;
+                let mut state = cpu.state();
+                state.registers[dest as usize] = T::zero();
+                state.status_flags = StatusFlags::default();
+                state.pc = address.to_usize();
+                self.ready.push_back(Thread {
+                    id,
+                    cpu: state,
+                    stack: Stack::new(stack.capacity()),
+                });
+                cpu.set_register(dest, T::from_usize(id))?;
+            }
+            Instruction::YIELD => {
+                if let Some(next) = self.ready.pop_front() {
+                    let thread = self.switch(cpu, stack, next)?;
+                    self.ready.push_back(thread);
+                }
+            }
+            Instruction::JOIN { reg } => {
+                let id = cpu.get_register(reg)?.to_usize();
+                if id != self.current && self.is_alive(id) {
+                    let Some(next) = self.ready.pop_front() else {
+                        return Err(VmError::Deadlock);
+                    };
+                    let thread = self.switch(cpu, stack, next)?;
+                    self.joining.push((thread, id));
+                }
+            }
+            _ => {
+                return Err(VmError::Other(format!(
+                    "Not a thread instruction: {}",
+                    instruction
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// End the running thread on `HLT` and resume the next ready thread.
+    /// The threads waiting for the running thread become ready.
+    ///
+    /// # Returns
+    /// `false` if there is no thread left, the program has ended.
+    ///
+    /// # Errors
+    /// Returns `VmError::Deadlock` if the remaining threads are all waiting in `JOIN`.
+    pub fn exit(&mut self, cpu: &mut CPU<T>, stack: &mut Stack<T>) -> Result<bool> {
+        let (woken, joining): (Vec<_>, Vec<_>) = std::mem::take(&mut self.joining)
+            .into_iter()
+            .partition(|(_, id)| *id == self.current);
+        self.joining = joining;
+        self.ready
+            .extend(woken.into_iter().map(|(thread, _)| thread));
+
+        let Some(next) = self.ready.pop_front() else {
+            if self.joining.is_empty() {
+                return Ok(false);
+            }
+            return Err(VmError::Deadlock);
+        };
+        self.switch(cpu, stack, next)?;
+        Ok(true)
+    }
+
+    /// Resume a thread in place of the running thread, returning the suspended running thread.
+    fn switch(
+        &mut self,
+        cpu: &mut CPU<T>,
+        stack: &mut Stack<T>,
+        next: Thread<T>,
+    ) -> Result<Thread<T>> {
+        let thread = Thread {
+            id: self.current,
+            cpu: cpu.state(),
+            stack: std::mem::replace(stack, next.stack),
+        };
+        cpu.restore(&next.cpu)?;
+        self.current = next.id;
+        Ok(thread)
+    }
+
+    /// Check if a thread has been spawned and has not ended.
+    fn is_alive(&self, id: usize) -> bool {
+        id == self.current
+            || self.ready.iter().any(|thread| thread.id == id)
+            || self.joining.iter().any(|(thread, _)| thread.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::Assembler;
+    use crate::vm::error::VmError;
+    use crate::VM;
+
+    #[test]
+    fn test_threads_spawn_join() {
+        // the worker adds 1 to 5 in R1 and stores it, the main thread waits for it
+        let source = "
+                MOV R0 0x100
+                SPAWN R2 worker
+                JOIN R2
+                LD R3 0x100
+                HLT
+            worker:
+                MOV R1 0
+                MOV R3 5
+            loop:
+                ADD R1 R1 R3
+                YIELD
+                DEC R3
+                JMPNZ loop
+                STR R1 [R0]
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.current_thread(), 0);
+        assert_eq!(vm.cpu.get_register(2), Ok(1));
+        assert_eq!(vm.cpu.get_register(3), Ok(15));
+    }
+
+    #[test]
+    fn test_threads_exit() {
+        // EXIT terminates the program while the main thread is still running
+        let source = "
+                SPAWN R2 worker
+            loop:
+                YIELD
+                JMP loop
+            worker:
+                EXIT 5
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(
+            vm.run(&program).map(|termination| termination.exit_code),
+            Ok(5)
+        );
+
+        // the exit code of HLT is the R0 register of the last thread
+        let source = "
+                MOV R0 1
+                SPAWN R2 worker
+                HLT
+            worker:
+                MOV R0 2
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        assert_eq!(
+            vm.run(&program).map(|termination| termination.exit_code),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_threads_yield_interleaving() {
+        // the main thread and the worker both run the loop, incrementing the counter in turn
+        let source = "
+                MOV R1 0x100
+                SPAWN R0 worker
+                MOV R0 0
+            worker:
+                MOV R2 3
+            loop:
+                MOV R3 1
+                XADD R3 [R1]
+                YIELD
+                DEC R2
+                JMPNZ loop
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert!(vm.run(&program).is_ok());
+        assert_eq!(vm.memory.read::<i32>(0x100), Ok(6));
+    }
+
+    #[test]
+    fn test_threads_deadlock() {
+        // the worker waits for the main thread, which waits for the worker
+        let source = "
+                SPAWN R1 worker
+                JOIN R1
+                HLT
+            worker:
+                JOIN R0
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(1024, 1024);
+        assert_eq!(
+            vm.run(&program).map_err(|error| error.source),
+            Err(VmError::Deadlock)
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use super::device::Device;
+use super::error::{Result, VmError};
+
+/// Offset of the counter register: the number of steps before the timer expires.
+pub const TIMER_COUNTER: usize = 0x00;
+/// Offset of the reload register: the value loaded in the counter when the timer expires,
+/// `0` for a one-shot timer.
+pub const TIMER_RELOAD: usize = 0x08;
+/// Offset of the control register: bit `0` enables the timer.
+pub const TIMER_CONTROL: usize = 0x10;
+/// Offset of the status register: bit `0` is set when the timer expires, any write clears it.
+pub const TIMER_STATUS: usize = 0x18;
+
+/// A programmable timer device counting the steps of the VM.
+/// The timer has 4 registers of 8 bytes, a register can be accessed with any size up to 8 bytes,
+/// the values written are zero-extended:
+/// - `TIMER_COUNTER` (`0x00`): Steps left before the timer expires.
+/// - `TIMER_RELOAD` (`0x08`): Period of the timer, `0` for a one-shot timer.
+/// - `TIMER_CONTROL` (`0x10`): Bit `0` enables the timer.
+/// - `TIMER_STATUS` (`0x18`): Bit `0` is the expired flag, any write clears it.
+///
+/// When enabled, the counter is decremented after each step, when it reaches `0` the expired
+/// flag is set and the counter is reloaded with the period, or the timer is disabled if the period is `0`.
+/// The timer requests an interrupt while the expired flag is set.
+/// Use `TimerDevice::SIZE` as the length of the mapped range.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimerDevice {
+    counter: u64,
+    reload: u64,
+    enabled: bool,
+    expired: bool,
+}
+
+impl TimerDevice {
+    /// The number of bytes of the registers of the timer.
+    pub const SIZE: usize = 0x20;
+
+    /// Create a disabled timer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if the timer expired since the expired flag was last cleared.
+    pub fn expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Check the access targets a single register.
+    fn check_register(offset: usize, size: usize) -> Result<()> {
+        if !offset.is_multiple_of(8) || size > 8 || offset >= Self::SIZE {
+            return Err(VmError::MemoryOutOfBounds {
+                address: offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Device for TimerDevice {
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        Self::check_register(offset, size)?;
+        let value = match offset {
+            TIMER_COUNTER => self.counter,
+            TIMER_RELOAD => self.reload,
+            TIMER_CONTROL => self.enabled as u64,
+            _ => self.expired as u64,
+        };
+        Ok(value.to_le_bytes()[..size].to_vec())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        Self::check_register(offset, data.len())?;
+        let mut bytes = [0u8; 8];
+        bytes[..data.len()].copy_from_slice(data);
+        let value = u64::from_le_bytes(bytes);
+        match offset {
+            TIMER_COUNTER => self.counter = value,
+            TIMER_RELOAD => self.reload = value,
+            TIMER_CONTROL => self.enabled = value & 1 != 0,
+            _ => self.expired = false,
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        if !self.enabled || self.counter == 0 {
+            return;
+        }
+        self.counter -= 1;
+        if self.counter == 0 {
+            self.expired = true;
+            if self.reload == 0 {
+                self.enabled = false;
+            } else {
+                self.counter = self.reload;
+            }
+        }
+    }
+
+    fn interrupt(&self) -> bool {
+        self.expired
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_timer_one_shot() {
+        let mut timer = TimerDevice::new();
+        timer.write(TIMER_COUNTER, &[2, 0, 0, 0]).unwrap();
+        timer.write(TIMER_CONTROL, &[1]).unwrap();
+        timer.tick();
+        assert!(!timer.expired());
+        timer.tick();
+        assert!(timer.expired());
+        assert_eq!(timer.read(TIMER_CONTROL, 4), Ok(vec![0, 0, 0, 0]));
+        assert_eq!(timer.read(TIMER_STATUS, 4), Ok(vec![1, 0, 0, 0]));
+
+        timer.write(TIMER_STATUS, &[0]).unwrap();
+        timer.tick();
+        assert!(!timer.expired());
+    }
+
+    #[test]
+    fn test_timer_periodic() {
+        let mut timer = TimerDevice::new();
+        timer.write(TIMER_COUNTER, &[3]).unwrap();
+        timer.write(TIMER_RELOAD, &[3]).unwrap();
+        timer.write(TIMER_CONTROL, &[1]).unwrap();
+        let mut expirations = 0;
+        for _ in 0..9 {
+            timer.tick();
+            if timer.expired() {
+                expirations += 1;
+                timer.write(TIMER_STATUS, &[1]).unwrap();
+            }
+        }
+        assert_eq!(expirations, 3);
+        assert_eq!(
+            timer.read(TIMER_COUNTER, 8),
+            Ok(3u64.to_le_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn test_timer_invalid_register() {
+        let mut timer = TimerDevice::new();
+        assert_eq!(
+            timer.read(4, 4),
+            Err(VmError::MemoryOutOfBounds {
+                address: 4,
+                size: 4
+            })
+        );
+        assert!(timer.write(TimerDevice::SIZE, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_timer_polling() {
+        let mut vm = VM::<i32>::new(1024, 1024);
+        vm.map_device(0x1000..0x1000 + TimerDevice::SIZE, TimerDevice::new())
+            .unwrap();
+        // count the loop iterations until the timer expires
+        let source = "
+                MOV R0 10
+                ST R0 0x1000
+                MOV R0 1
+                ST R0 0x1010
+                MOV R1 0
+            wait:
+                INC R1
+                LD R0 0x1018
+                DEC R0
+                JMPZ end
+                JMP wait
+            end:
+                HLT
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        assert_eq!(
+            vm.run_with_limit(&program, 1000)
+                .map(|termination| termination.steps),
+            Ok(20)
+        );
+        assert_eq!(vm.snapshot().cpu.registers[1], 3);
+        assert!(vm.device::<TimerDevice>(0x1000).unwrap().expired());
+    }
+}
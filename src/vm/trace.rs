@@ -0,0 +1,292 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+
+use super::instructions::Instruction;
+use super::watchpoint::Access;
+use super::word::Word;
+
+/// A memory access of an executed instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The address of the access.
+    pub address: usize,
+    /// The size of the access in bytes.
+    pub size: usize,
+    /// The kind of the access.
+    pub access: Access,
+}
+
+/// A register modified by an executed instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RegisterChange<T> {
+    pub register: u8,
+    /// The value before the instruction.
+    pub old: T,
+    /// The value after the instruction.
+    pub new: T,
+}
+
+/// The trace of an executed instruction, passed to the `Tracer` of the VM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent<T: Word> {
+    /// The number of the step, starting at `1` for the first instruction of a run.
+    pub step: u128,
+    /// The address of the instruction.
+    pub pc: usize,
+    pub instruction: Instruction<T, T::Address>,
+    /// The registers modified by the instruction, in the order of the registers.
+    pub registers: Vec<RegisterChange<T>>,
+    /// The memory accesses of the instruction, in the order of execution.
+    pub memory: Vec<MemoryAccess>,
+}
+
+impl<T: Word> TraceEvent<T> {
+    /// Write the event as a JSON object on a single line, e.g.
+    /// `{"step":1,"pc":0,"instruction":"ST R0 0x10","registers":[],"memory":[{"address":16,"size":4,"access":"write"}]}`.
+    pub fn to_json(&self) -> String {
+        let registers: Vec<_> = self
+            .registers
+            .iter()
+            .map(|change| {
+                format!(
+                    "{{\"register\":{},\"old\":{},\"new\":{}}}",
+                    change.register, change.old, change.new
+                )
+            })
+            .collect();
+        let memory: Vec<_> = self
+            .memory
+            .iter()
+            .map(|access| {
+                format!(
+                    "{{\"address\":{},\"size\":{},\"access\":\"{}\"}}",
+                    access.address, access.size, access.access
+                )
+            })
+            .collect();
+        format!(
+            "{{\"step\":{},\"pc\":{},\"instruction\":{:?},\"registers\":[{}],\"memory\":[{}]}}",
+            self.step,
+            self.pc,
+            self.instruction.to_string(),
+            registers.join(","),
+            memory.join(",")
+        )
+    }
+}
+
+/// Format the event on a single line, e.g. `3 0x00000006: DEC R0 ; R0 2 -> 1`.
+impl<T: Word> fmt::Display for TraceEvent<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} 0x{:08x}: {}", self.step, self.pc, self.instruction)?;
+        for change in &self.registers {
+            write!(
+                f,
+                " ; R{} {} -> {}",
+                change.register, change.old, change.new
+            )?;
+        }
+        for access in &self.memory {
+            write!(
+                f,
+                " ; {} {} bytes at 0x{:08x}",
+                access.access, access.size, access.address
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A sink of the trace of the execution, invoked by the VM after every executed instruction.
+/// The tracer is set with `VM::set_tracer`, and the built-in tracers are `RingBufferTracer`,
+/// `WriteTracer` and `JsonLinesTracer`.
+///
+/// # Example:
+/// ```
+/// use forge_vm::vm::trace::{TraceEvent, Tracer};
+/// use forge_vm::VM;
+///
+/// // Count the instructions modifying a register
+/// #[derive(Default)]
+/// struct Writes(usize);
+///
+/// impl Tracer<i32> for Writes {
+///     fn trace(&mut self, event: &TraceEvent<i32>) {
+///         self.0 += event.registers.len();
+///     }
+/// }
+///
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// vm.set_tracer(Writes::default());
+/// vm.run(&[0x0e, 0x00, 0x0e, 0x01, 0xff]).unwrap(); // INC R0, INC R1, HLT
+/// assert_eq!(vm.tracer::<Writes>().unwrap().0, 2);
+/// ```
+pub trait Tracer<T: Word>: Any {
+    /// Receive the trace of an executed instruction.
+    fn trace(&mut self, event: &TraceEvent<T>);
+}
+
+/// Tracer keeping the last events in a buffer of fixed capacity, e.g. to show the instructions
+/// leading to a fault.
+pub struct RingBufferTracer<T: Word> {
+    capacity: usize,
+    events: VecDeque<TraceEvent<T>>,
+}
+
+impl<T: Word> RingBufferTracer<T> {
+    /// Create a tracer keeping the last `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Get the kept events, from the oldest to the latest.
+    pub fn events(&self) -> impl Iterator<Item = &TraceEvent<T>> {
+        self.events.iter()
+    }
+
+    /// Remove the kept events.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl<T: Word> Tracer<T> for RingBufferTracer<T> {
+    fn trace(&mut self, event: &TraceEvent<T>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+    }
+}
+
+/// Tracer writing the events to a stream, one line per event in the format of `TraceEvent`'s
+/// `Display`. The write errors are ignored so that tracing never stops the execution.
+pub struct WriteTracer<W> {
+    writer: W,
+}
+
+impl<W: Write> WriteTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Get the stream.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<T: Word, W: Write + 'static> Tracer<T> for WriteTracer<W> {
+    fn trace(&mut self, event: &TraceEvent<T>) {
+        let _ = writeln!(self.writer, "{}", event);
+    }
+}
+
+/// Tracer writing the events to a stream in the JSON lines format, one JSON object per line as
+/// written by `TraceEvent::to_json`. The write errors are ignored as for `WriteTracer`.
+pub struct JsonLinesTracer<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Get the stream.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<T: Word, W: Write + 'static> Tracer<T> for JsonLinesTracer<W> {
+    fn trace(&mut self, event: &TraceEvent<T>) {
+        let _ = writeln!(self.writer, "{}", event.to_json());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_trace_events() {
+        let program = Assembler::new()
+            .assemble("MOV R0 7\nST R0 0x10\nLD R1 0x10\nHLT")
+            .unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        vm.set_tracer(RingBufferTracer::new(3));
+        vm.run(&program).unwrap();
+
+        // the first event was dropped by the buffer of 3 events
+        let tracer = vm.tracer::<RingBufferTracer<i32>>().unwrap();
+        let events: Vec<_> = tracer.events().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            *events[0],
+            TraceEvent {
+                step: 2,
+                pc: 6,
+                instruction: Instruction::ST {
+                    src: 0,
+                    address: 0x10
+                },
+                registers: vec![],
+                memory: vec![MemoryAccess {
+                    address: 0x10,
+                    size: 4,
+                    access: Access::Write
+                }],
+            }
+        );
+        assert_eq!(
+            events[1].registers,
+            [RegisterChange {
+                register: 1,
+                old: 0,
+                new: 7
+            }]
+        );
+        assert_eq!(events[2].instruction, Instruction::HLT);
+    }
+
+    #[test]
+    fn test_trace_sinks() {
+        let program = Assembler::new().assemble("DEC R0\nST R0 0x8\nHLT").unwrap();
+        let mut vm = VM::<i32>::new(16, 64);
+        vm.set_tracer(WriteTracer::new(Vec::new()));
+        vm.run(&program).unwrap();
+        let text = vm.tracer::<WriteTracer<Vec<u8>>>().unwrap().get_ref();
+        assert_eq!(
+            String::from_utf8_lossy(text),
+            "1 0x00000000: DEC R0 ; R0 0 -> -1\n\
+             2 0x00000002: ST R0 0x8 ; write 4 bytes at 0x00000008\n\
+             3 0x00000008: HLT\n"
+        );
+
+        vm.set_tracer(JsonLinesTracer::new(Vec::new()));
+        vm.run(&program).unwrap();
+        let lines = vm.tracer::<JsonLinesTracer<Vec<u8>>>().unwrap().get_ref();
+        let lines = String::from_utf8_lossy(lines);
+        assert_eq!(
+            lines.lines().nth(1),
+            Some(
+                r#"{"step":2,"pc":2,"instruction":"ST R0 0x8","registers":[],"memory":[{"address":8,"size":4,"access":"write"}]}"#
+            )
+        );
+
+        assert!(vm.tracer::<RingBufferTracer<i32>>().is_none());
+        vm.clear_tracer();
+        assert!(vm.tracer::<JsonLinesTracer<Vec<u8>>>().is_none());
+    }
+}
@@ -0,0 +1,176 @@
+use super::device::Device;
+use super::error::{Result, VmError};
+
+/// Offset of the pet register: any write pets the watchdog, a read returns the steps left.
+pub const WATCHDOG_PET: usize = 0x00;
+/// Offset of the period register: the steps allowed between two pets, read-only.
+pub const WATCHDOG_PERIOD: usize = 0x08;
+
+/// A watchdog device supervising a guest that must pet it periodically.
+/// The watchdog has 2 registers of 8 bytes, a register can be accessed with any size up to
+/// 8 bytes:
+/// - `WATCHDOG_PET` (`0x00`): Any write pets the watchdog, a read returns the steps left.
+/// - `WATCHDOG_PERIOD` (`0x08`): Steps allowed between two pets, the writes are ignored.
+///
+/// The watchdog is armed at the start of each run, and the guest cannot disable it. When the
+/// guest does not pet it within its period, the execution stops with `VmError::WatchdogExpired`,
+/// even if the interrupts are disabled.
+/// Use `WatchdogDevice::SIZE` as the length of the mapped range.
+///
+/// # Example:
+/// ```
+/// use forge_vm::asm::Assembler;
+/// use forge_vm::vm::watchdog::WatchdogDevice;
+/// use forge_vm::{VmError, VM};
+///
+/// let mut vm = VM::<i32>::new(1024, 1024);
+/// vm.map_device(0x1000..0x1000 + WatchdogDevice::SIZE, WatchdogDevice::new(100))
+///     .unwrap();
+/// // a runaway loop never pets the watchdog
+/// let program = Assembler::new().assemble("loop: JMP loop").unwrap();
+/// let error = vm.run(&program).unwrap_err();
+/// assert_eq!(error.source, VmError::WatchdogExpired { period: 100 });
+/// assert_eq!(error.step, 100);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogDevice {
+    period: u64,
+    counter: u64,
+    /// Set by a pet, the step of the pet does not count.
+    petted: bool,
+}
+
+impl WatchdogDevice {
+    /// The number of bytes of the registers of the watchdog.
+    pub const SIZE: usize = 0x10;
+
+    /// Create an armed watchdog.
+    ///
+    /// # Parameters
+    /// - `period`: The steps allowed between two pets, at least `1`.
+    pub fn new(period: u64) -> Self {
+        let period = period.max(1);
+        Self {
+            period,
+            counter: period,
+            petted: false,
+        }
+    }
+
+    /// Check if the watchdog expired since the last pet.
+    pub fn expired(&self) -> bool {
+        self.counter == 0
+    }
+
+    /// Check the access targets a single register.
+    fn check_register(offset: usize, size: usize) -> Result<()> {
+        if !offset.is_multiple_of(8) || size > 8 || offset >= Self::SIZE {
+            return Err(VmError::MemoryOutOfBounds {
+                address: offset,
+                size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Device for WatchdogDevice {
+    fn read(&mut self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        Self::check_register(offset, size)?;
+        let value = match offset {
+            WATCHDOG_PET => self.counter,
+            _ => self.period,
+        };
+        Ok(value.to_le_bytes()[..size].to_vec())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        Self::check_register(offset, data.len())?;
+        // an expired watchdog stays expired until the next run
+        if offset == WATCHDOG_PET && !self.expired() {
+            self.counter = self.period;
+            self.petted = true;
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        if !std::mem::take(&mut self.petted) {
+            self.counter = self.counter.saturating_sub(1);
+        }
+    }
+
+    fn fault(&self) -> Option<VmError> {
+        self.expired().then_some(VmError::WatchdogExpired {
+            period: self.period,
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.period);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::Assembler;
+    use crate::VM;
+
+    #[test]
+    fn test_watchdog_pet() {
+        let mut watchdog = WatchdogDevice::new(2);
+        watchdog.tick();
+        assert_eq!(
+            watchdog.read(WATCHDOG_PET, 8),
+            Ok(1u64.to_le_bytes().to_vec())
+        );
+        watchdog.write(WATCHDOG_PET, &[0]).unwrap();
+        watchdog.write(WATCHDOG_PERIOD, &[0]).unwrap();
+        // the step of the pet does not count
+        watchdog.tick();
+        assert_eq!(
+            watchdog.read(WATCHDOG_PET, 8),
+            Ok(2u64.to_le_bytes().to_vec())
+        );
+        watchdog.tick();
+        assert_eq!(watchdog.fault(), None);
+        watchdog.tick();
+        assert_eq!(
+            watchdog.fault(),
+            Some(VmError::WatchdogExpired { period: 2 })
+        );
+
+        // petting too late does not rearm the watchdog
+        watchdog.write(WATCHDOG_PET, &[0]).unwrap();
+        assert!(watchdog.expired());
+        watchdog.reset();
+        assert_eq!(watchdog.read(WATCHDOG_PERIOD, 1), Ok(vec![2]));
+        assert_eq!(watchdog.fault(), None);
+    }
+
+    #[test]
+    fn test_watchdog_supervised_loop() {
+        // the loop pets the watchdog every 3 steps, then stops petting it after 10 iterations
+        let source = "
+                MOV R1 10
+            work:
+                ST R0 0x1000
+                DEC R1
+                JMPNZ work
+            runaway:
+                JMP runaway
+        ";
+        let program = Assembler::new().assemble(source).unwrap();
+        let mut vm = VM::<i32>::new(8192, 1024);
+        vm.map_device(
+            0x1000..0x1000 + WatchdogDevice::SIZE,
+            WatchdogDevice::new(8),
+        )
+        .unwrap();
+        let error = vm.run_with_limit(&program, 1000).unwrap_err();
+        assert_eq!(error.source, VmError::WatchdogExpired { period: 8 });
+        // the last pet is the step 29, the watchdog expires 8 steps later
+        assert_eq!(error.step, 37);
+    }
+}